@@ -50,317 +50,321 @@ CREATE TABLE "elements" (
   "value" ANY
 ) STRICT;
 
+CREATE TABLE "relation_names" ("name" TEXT PRIMARY KEY) STRICT,
+WITHOUT ROWID;
+
+INSERT INTO
+  "relation_names" ("name")
+VALUES
+  ('action'),
+  ('actionDefinition'),
+  ('actorParameter'),
+  ('allocation'),
+  ('allocationDefinition'),
+  ('analysisCaseDefinition'),
+  ('annotatedElement'),
+  ('annotatingElement'),
+  ('annotation'),
+  ('argument'),
+  ('assertedConstraint'),
+  ('association'),
+  ('associationEnd'),
+  ('assumedConstraint'),
+  ('attributeDefinition'),
+  ('behavior'),
+  ('bodyAction'),
+  ('bound'),
+  ('calculation'),
+  ('calculationDefinition'),
+  ('caseDefinition'),
+  ('chainingFeature'),
+  ('client'),
+  ('concernDefinition'),
+  ('condition'),
+  ('conjugatedPortDefinition'),
+  ('conjugatedType'),
+  ('connectionDefinition'),
+  ('connectionEnd'),
+  ('connectorEnd'),
+  ('constraintDefinition'),
+  ('crossFeature'),
+  ('crossedFeature'),
+  ('crossingFeature'),
+  ('defaultBranch'),
+  ('defaultFeaturingType'),
+  ('definition'),
+  ('differencingType'),
+  ('directedFeature'),
+  ('directedUsage'),
+  ('disjoiningType'),
+  ('doAction'),
+  ('documentation'),
+  ('documentedElement'),
+  ('effectAction'),
+  ('elementEnd'),
+  ('elseAction'),
+  ('endFeature'),
+  ('endOwningType'),
+  ('entryAction'),
+  ('enumeratedValue'),
+  ('enumerationDefinition'),
+  ('eventOccurrence'),
+  ('exhibitedState'),
+  ('exitAction'),
+  ('exposedElement'),
+  ('expression'),
+  ('externalDataEnd'),
+  ('feature'),
+  ('featureChained'),
+  ('featureInverted'),
+  ('featureMembership'),
+  ('featureOfType'),
+  ('featureTarget'),
+  ('featureWithValue'),
+  ('featuringType'),
+  ('filterCondition'),
+  ('flowDefinition'),
+  ('flowEnd'),
+  ('framedConcern'),
+  ('function'),
+  ('general'),
+  ('guardExpression'),
+  ('head'),
+  ('ifArgument'),
+  ('importOwningNamespace'),
+  ('importedElement'),
+  ('importedMembership'),
+  ('importedNamespace'),
+  ('includedUseCase'),
+  ('individualDefinition'),
+  ('inheritedFeature'),
+  ('inheritedMembership'),
+  ('input'),
+  ('instantiatedType'),
+  ('interaction'),
+  ('interfaceDefinition'),
+  ('interfaceEnd'),
+  ('intersectingType'),
+  ('invertingFeature'),
+  ('itemDefinition'),
+  ('loopVariable'),
+  ('lowerBound'),
+  ('member'),
+  ('memberElement'),
+  ('membership'),
+  ('membershipOwningNamespace'),
+  ('metaclass'),
+  ('metadataDefinition'),
+  ('multiplicity'),
+  ('nestedAction'),
+  ('nestedAllocation'),
+  ('nestedAnalysisCase'),
+  ('nestedAttribute'),
+  ('nestedCalculation'),
+  ('nestedCase'),
+  ('nestedConcern'),
+  ('nestedConnection'),
+  ('nestedConstraint'),
+  ('nestedEnumeration'),
+  ('nestedFlow'),
+  ('nestedInterface'),
+  ('nestedItem'),
+  ('nestedMetadata'),
+  ('nestedOccurrence'),
+  ('nestedPart'),
+  ('nestedPort'),
+  ('nestedReference'),
+  ('nestedRendering'),
+  ('nestedRequirement'),
+  ('nestedState'),
+  ('nestedTransition'),
+  ('nestedUsage'),
+  ('nestedUseCase'),
+  ('nestedVerificationCase'),
+  ('nestedView'),
+  ('nestedViewpoint'),
+  ('objectiveRequirement'),
+  ('occurrenceDefinition'),
+  ('originalPortDefinition'),
+  ('originalType'),
+  ('output'),
+  ('operand'),
+  ('ownedAction'),
+  ('ownedActorParameter'),
+  ('ownedAllocation'),
+  ('ownedAnalysisCase'),
+  ('ownedAnnotatingElement'),
+  ('ownedAnnotatingRelationship'),
+  ('ownedAnnotation'),
+  ('ownedAttribute'),
+  ('ownedCalculation'),
+  ('ownedCase'),
+  ('ownedConcern'),
+  ('ownedConjugator'),
+  ('ownedConnection'),
+  ('ownedConstraint'),
+  ('ownedCrossSubsetting'),
+  ('ownedDifferencing'),
+  ('ownedDisjoining'),
+  ('ownedElement'),
+  ('ownedEndFeature'),
+  ('ownedEnumeration'),
+  ('ownedFeature'),
+  ('ownedFeatureChaining'),
+  ('ownedFeatureInverting'),
+  ('ownedFeatureMembership'),
+  ('ownedFlow'),
+  ('ownedImport'),
+  ('ownedInterface'),
+  ('ownedIntersecting'),
+  ('ownedItem'),
+  ('ownedMember'),
+  ('ownedMemberElement'),
+  ('ownedMemberFeature'),
+  ('ownedMemberParameter'),
+  ('ownedMembership'),
+  ('ownedMetadata'),
+  ('ownedObjectiveRequirement'),
+  ('ownedOccurrence'),
+  ('ownedPart'),
+  ('ownedPort'),
+  ('ownedPortConjugator'),
+  ('ownedRedefinition'),
+  ('ownedReference'),
+  ('ownedReferenceSubsetting'),
+  ('ownedRelatedElement'),
+  ('ownedRelationship'),
+  ('ownedRendering'),
+  ('ownedRequirement'),
+  ('ownedResultExpression'),
+  ('ownedSpecialization'),
+  ('ownedStakeholderParameter'),
+  ('ownedState'),
+  ('ownedSubclassification'),
+  ('ownedSubjectParameter'),
+  ('ownedSubsetting'),
+  ('ownedTransition'),
+  ('ownedTypeFeaturing'),
+  ('ownedTyping'),
+  ('ownedUnioning'),
+  ('ownedUsage'),
+  ('ownedUseCase'),
+  ('ownedVariantUsage'),
+  ('ownedVerificationCase'),
+  ('ownedView'),
+  ('ownedViewpoint'),
+  ('owner'),
+  ('owningAnnotatedElement'),
+  ('owningAnnotatingElement'),
+  ('owningAnnotatingRelationship'),
+  ('owningClassifier'),
+  ('owningDefinition'),
+  ('owningFeature'),
+  ('owningFeatureMembership'),
+  ('owningFeatureOfType'),
+  ('owningMembership'),
+  ('owningNamespace'),
+  ('owningProject'),
+  ('owningRelatedElement'),
+  ('owningRelationship'),
+  ('owningType'),
+  ('owningUsage'),
+  ('parameter'),
+  ('partDefinition'),
+  ('payloadArgument'),
+  ('payloadFeature'),
+  ('payloadParameter'),
+  ('payloadType'),
+  ('performedAction'),
+  ('portDefinition'),
+  ('predicate'),
+  ('previousCommit'),
+  ('receiverArgument'),
+  ('redefinedFeature'),
+  ('redefiningFeature'),
+  ('referencedCommit'),
+  ('referencedConcern'),
+  ('referencedConstraint'),
+  ('referencedElement'),
+  ('referencedFeature'),
+  ('referencedRendering'),
+  ('referencingFeature'),
+  ('referent'),
+  ('relatedElement'),
+  ('relatedFeature'),
+  ('relatedType'),
+  ('rendering'),
+  ('renderingDefinition'),
+  ('representedElement'),
+  ('requiredConstraint'),
+  ('requirementDefinition'),
+  ('result'),
+  ('resultExpression'),
+  ('satisfiedRequirement'),
+  ('satisfiedViewpoint'),
+  ('satisfyingFeature'),
+  ('senderArgument'),
+  ('seqArgument'),
+  ('source'),
+  ('sourceFeature'),
+  ('sourceOutputFeature'),
+  ('sourceType'),
+  ('specific'),
+  ('stakeholderParameter'),
+  ('state'),
+  ('stateDefinition'),
+  ('step'),
+  ('subclassifier'),
+  ('subjectParameter'),
+  ('subsettedFeature'),
+  ('subsettingFeature'),
+  ('succession'),
+  ('superclassifier'),
+  ('supplier'),
+  ('taggedCommit'),
+  ('target'),
+  ('targetArgument'),
+  ('targetFeature'),
+  ('targetInputFeature'),
+  ('targetType'),
+  ('terminatedOccurrenceArgument'),
+  ('textualRepresentation'),
+  ('thenAction'),
+  ('transitionFeature'),
+  ('triggerAction'),
+  ('type'),
+  ('typeDifferenced'),
+  ('typeDisjoined'),
+  ('typeIntersected'),
+  ('typeUnioned'),
+  ('typedFeature'),
+  ('unioningType'),
+  ('untilArgument'),
+  ('upperBound'),
+  ('usage'),
+  ('useCaseDefinition'),
+  ('useCaseIncluded'),
+  ('usedCommit'),
+  ('usedProject'),
+  ('valueExpression'),
+  ('variant'),
+  ('variantMembership'),
+  ('verificationCaseDefinition'),
+  ('verifiedRequirement'),
+  ('view'),
+  ('viewCondition'),
+  ('viewDefinition'),
+  ('viewRendering'),
+  ('viewpointDefinition'),
+  ('viewpointStakeholder'),
+  ('whileArgument'),
+  ('value');
+
 CREATE TABLE "relations" (
-  "name" TEXT NOT NULL CHECK (
-    "name" IN (
-      'action',
-      'actionDefinition',
-      'actorParameter',
-      'allocation',
-      'allocationDefinition',
-      'analysisCaseDefinition',
-      'annotatedElement',
-      'annotatingElement',
-      'annotation',
-      'argument',
-      'assertedConstraint',
-      'association',
-      'associationEnd',
-      'assumedConstraint',
-      'attributeDefinition',
-      'behavior',
-      'bodyAction',
-      'bound',
-      'calculation',
-      'calculationDefinition',
-      'caseDefinition',
-      'chainingFeature',
-      'client',
-      'concernDefinition',
-      'condition',
-      'conjugatedPortDefinition',
-      'conjugatedType',
-      'connectionDefinition',
-      'connectionEnd',
-      'connectorEnd',
-      'constraintDefinition',
-      'crossFeature',
-      'crossedFeature',
-      'crossingFeature',
-      'defaultBranch',
-      'defaultFeaturingType',
-      'definition',
-      'differencingType',
-      'directedFeature',
-      'directedUsage',
-      'disjoiningType',
-      'doAction',
-      'documentation',
-      'documentedElement',
-      'effectAction',
-      'elementEnd',
-      'elseAction',
-      'endFeature',
-      'endOwningType',
-      'entryAction',
-      'enumeratedValue',
-      'enumerationDefinition',
-      'eventOccurrence',
-      'exhibitedState',
-      'exitAction',
-      'exposedElement',
-      'expression',
-      'externalDataEnd',
-      'feature',
-      'featureChained',
-      'featureInverted',
-      'featureMembership',
-      'featureOfType',
-      'featureTarget',
-      'featureWithValue',
-      'featuringType',
-      'filterCondition',
-      'flowDefinition',
-      'flowEnd',
-      'framedConcern',
-      'function',
-      'general',
-      'guardExpression',
-      'head',
-      'ifArgument',
-      'importOwningNamespace',
-      'importedElement',
-      'importedMembership',
-      'importedNamespace',
-      'includedUseCase',
-      'individualDefinition',
-      'inheritedFeature',
-      'inheritedMembership',
-      'input',
-      'instantiatedType',
-      'interaction',
-      'interfaceDefinition',
-      'interfaceEnd',
-      'intersectingType',
-      'invertingFeature',
-      'itemDefinition',
-      'loopVariable',
-      'lowerBound',
-      'member',
-      'memberElement',
-      'membership',
-      'membershipOwningNamespace',
-      'metaclass',
-      'metadataDefinition',
-      'multiplicity',
-      'nestedAction',
-      'nestedAllocation',
-      'nestedAnalysisCase',
-      'nestedAttribute',
-      'nestedCalculation',
-      'nestedCase',
-      'nestedConcern',
-      'nestedConnection',
-      'nestedConstraint',
-      'nestedEnumeration',
-      'nestedFlow',
-      'nestedInterface',
-      'nestedItem',
-      'nestedMetadata',
-      'nestedOccurrence',
-      'nestedPart',
-      'nestedPort',
-      'nestedReference',
-      'nestedRendering',
-      'nestedRequirement',
-      'nestedState',
-      'nestedTransition',
-      'nestedUsage',
-      'nestedUseCase',
-      'nestedVerificationCase',
-      'nestedView',
-      'nestedViewpoint',
-      'objectiveRequirement',
-      'occurrenceDefinition',
-      'originalPortDefinition',
-      'originalType',
-      'output',
-      'operand',
-      'ownedAction',
-      'ownedActorParameter',
-      'ownedAllocation',
-      'ownedAnalysisCase',
-      'ownedAnnotatingElement',
-      'ownedAnnotatingRelationship',
-      'ownedAnnotation',
-      'ownedAttribute',
-      'ownedCalculation',
-      'ownedCase',
-      'ownedConcern',
-      'ownedConjugator',
-      'ownedConnection',
-      'ownedConstraint',
-      'ownedCrossSubsetting',
-      'ownedDifferencing',
-      'ownedDisjoining',
-      'ownedElement',
-      'ownedEndFeature',
-      'ownedEnumeration',
-      'ownedFeature',
-      'ownedFeatureChaining',
-      'ownedFeatureInverting',
-      'ownedFeatureMembership',
-      'ownedFlow',
-      'ownedImport',
-      'ownedInterface',
-      'ownedIntersecting',
-      'ownedItem',
-      'ownedMember',
-      'ownedMemberElement',
-      'ownedMemberFeature',
-      'ownedMemberParameter',
-      'ownedMembership',
-      'ownedMetadata',
-      'ownedObjectiveRequirement',
-      'ownedOccurrence',
-      'ownedPart',
-      'ownedPort',
-      'ownedPortConjugator',
-      'ownedRedefinition',
-      'ownedReference',
-      'ownedReferenceSubsetting',
-      'ownedRelatedElement',
-      'ownedRelationship',
-      'ownedRendering',
-      'ownedRequirement',
-      'ownedResultExpression',
-      'ownedSpecialization',
-      'ownedStakeholderParameter',
-      'ownedState',
-      'ownedSubclassification',
-      'ownedSubjectParameter',
-      'ownedSubsetting',
-      'ownedTransition',
-      'ownedTypeFeaturing',
-      'ownedTyping',
-      'ownedUnioning',
-      'ownedUsage',
-      'ownedUseCase',
-      'ownedVariantUsage',
-      'ownedVerificationCase',
-      'ownedView',
-      'ownedViewpoint',
-      'owner',
-      'owningAnnotatedElement',
-      'owningAnnotatingElement',
-      'owningAnnotatingRelationship',
-      'owningClassifier',
-      'owningDefinition',
-      'owningFeature',
-      'owningFeatureMembership',
-      'owningFeatureOfType',
-      'owningMembership',
-      'owningNamespace',
-      'owningProject',
-      'owningRelatedElement',
-      'owningRelationship',
-      'owningType',
-      'owningUsage',
-      'parameter',
-      'partDefinition',
-      'payloadArgument',
-      'payloadFeature',
-      'payloadParameter',
-      'payloadType',
-      'performedAction',
-      'portDefinition',
-      'predicate',
-      'previousCommit',
-      'receiverArgument',
-      'redefinedFeature',
-      'redefiningFeature',
-      'referencedCommit',
-      'referencedConcern',
-      'referencedConstraint',
-      'referencedElement',
-      'referencedFeature',
-      'referencedRendering',
-      'referencingFeature',
-      'referent',
-      'relatedElement',
-      'relatedFeature',
-      'relatedType',
-      'rendering',
-      'renderingDefinition',
-      'representedElement',
-      'requiredConstraint',
-      'requirementDefinition',
-      'result',
-      'resultExpression',
-      'satisfiedRequirement',
-      'satisfiedViewpoint',
-      'satisfyingFeature',
-      'senderArgument',
-      'seqArgument',
-      'source',
-      'sourceFeature',
-      'sourceOutputFeature',
-      'sourceType',
-      'specific',
-      'stakeholderParameter',
-      'state',
-      'stateDefinition',
-      'step',
-      'subclassifier',
-      'subjectParameter',
-      'subsettedFeature',
-      'subsettingFeature',
-      'succession',
-      'superclassifier',
-      'supplier',
-      'taggedCommit',
-      'target',
-      'targetArgument',
-      'targetFeature',
-      'targetInputFeature',
-      'targetType',
-      'terminatedOccurrenceArgument',
-      'textualRepresentation',
-      'thenAction',
-      'transitionFeature',
-      'triggerAction',
-      'type',
-      'typeDifferenced',
-      'typeDisjoined',
-      'typeIntersected',
-      'typeUnioned',
-      'typedFeature',
-      'unioningType',
-      'untilArgument',
-      'upperBound',
-      'usage',
-      'useCaseDefinition',
-      'useCaseIncluded',
-      'usedCommit',
-      'usedProject',
-      'valueExpression',
-      'variant',
-      'variantMembership',
-      'verificationCaseDefinition',
-      'verifiedRequirement',
-      'view',
-      'viewCondition',
-      'viewDefinition',
-      'viewRendering',
-      'viewpointDefinition',
-      'viewpointStakeholder',
-      'whileArgument',
-      'value',
-      'analysisAction'
-    )
-  ),
+  "name" TEXT NOT NULL,
   "origin_id" TEXT NOT NULL,
   "target_id" TEXT NOT NULL,
+  FOREIGN KEY ("name") REFERENCES "relation_names" ("name"),
   FOREIGN KEY ("origin_id") REFERENCES "elements" ("@id") DEFERRABLE INITIALLY DEFERRED,
   FOREIGN KEY ("target_id") REFERENCES "elements" ("@id") DEFERRABLE INITIALLY DEFERRED,
   PRIMARY KEY ("name", "origin_id", "target_id")
@@ -410,3 +414,74 @@ CREATE INDEX "relations.origin_id" ON "relations" ("origin_id");
 DROP INDEX IF EXISTS "relations.target_id";
 
 CREATE INDEX "relations.target_id" ON "relations" ("target_id");
+
+CREATE VIRTUAL TABLE "elements_fts" USING fts5 (
+  "declaredName",
+  "qualifiedName",
+  "body",
+  content = "elements",
+  content_rowid = 'rowid'
+);
+
+CREATE TRIGGER "elements_fts.insert" AFTER INSERT ON "elements" BEGIN
+INSERT INTO
+  "elements_fts" (rowid, "declaredName", "qualifiedName", "body")
+VALUES
+  (
+    new.rowid,
+    new."declaredName",
+    new."qualifiedName",
+    new."body"
+  );
+
+END;
+
+CREATE TRIGGER "elements_fts.delete" AFTER DELETE ON "elements" BEGIN
+INSERT INTO
+  "elements_fts" (
+    "elements_fts",
+    rowid,
+    "declaredName",
+    "qualifiedName",
+    "body"
+  )
+VALUES
+  (
+    'delete',
+    old.rowid,
+    old."declaredName",
+    old."qualifiedName",
+    old."body"
+  );
+
+END;
+
+CREATE TRIGGER "elements_fts.update" AFTER UPDATE ON "elements" BEGIN
+INSERT INTO
+  "elements_fts" (
+    "elements_fts",
+    rowid,
+    "declaredName",
+    "qualifiedName",
+    "body"
+  )
+VALUES
+  (
+    'delete',
+    old.rowid,
+    old."declaredName",
+    old."qualifiedName",
+    old."body"
+  );
+
+INSERT INTO
+  "elements_fts" (rowid, "declaredName", "qualifiedName", "body")
+VALUES
+  (
+    new.rowid,
+    new."declaredName",
+    new."qualifiedName",
+    new."body"
+  );
+
+END;
@@ -0,0 +1,40 @@
+/*
+ * C interface of sysml-v2-sql, see src/ffi.rs
+ *
+ * Build the shared library with
+ *
+ *     cargo rustc --lib --release --features ffi --crate-type cdylib
+ *
+ * All functions take paths as NUL-terminated UTF-8 strings and return 0 on success. On failure,
+ * they return the exit code the CLI exits with for the same failure, and sysml_last_error() tells
+ * what went wrong.
+ */
+
+#ifndef SYSML_V2_SQL_H
+#define SYSML_V2_SQL_H
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+/* Initializes the db at db_path with the embedded schema, like the init-db command */
+int sysml_init_db(const char *db_path);
+
+/* Imports the elements of the JSON file at json_path into the db at db_path, like the import-json
+ * command */
+int sysml_import_json(const char *db_path, const char *json_path);
+
+/* Generates the SQL schema from the JSON schema at json_schema_path, initializes the db at db_path
+ * with it and writes it to sql_path. db_path and sql_path may be NULL to skip either. */
+int sysml_generate_schema(const char *json_schema_path, const char *db_path,
+                          const char *sql_path);
+
+/* The message of the last failure of a call on the calling thread, or NULL if it succeeded. The
+ * string is owned by the library and remains valid until the next call on the same thread. */
+const char *sysml_last_error(void);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif /* SYSML_V2_SQL_H */
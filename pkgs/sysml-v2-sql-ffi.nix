@@ -0,0 +1,4 @@
+# The tool with the C ABI, running the tests of the C ABI as well
+{ sysml-v2-sql }:
+
+sysml-v2-sql.override { extraFeatures = [ "ffi" ]; }
@@ -0,0 +1,4 @@
+# The tool without the network stack, checking that the core builds and passes its tests without it
+{ sysml-v2-sql }:
+
+sysml-v2-sql.override { withFetch = false; }
@@ -4,6 +4,15 @@
   pkg-config,
   openssl,
   sqlite,
+
+  # the commands talking to API servers, which need an HTTP client and an async runtime
+  withFetch ? true,
+
+  # the transformation of imported elements by Lua scripts
+  withLua ? true,
+
+  # additional features of the crate, e.g. `ffi`, which are also enabled for the tests
+  extraFeatures ? [ ],
 }:
 
 let
@@ -18,6 +27,8 @@ let
 
       # Files to explicitly include
       files = [
+        "assets/report.html.jinja"
+        "assets/report.md.jinja"
         "assets/schema.sql"
         "tests/example-dump.json"
       ];
@@ -60,7 +71,12 @@ rustPlatform.buildRustPackage {
     sqlite
   ];
   buildNoDefaultFeatures = true; # we don't want rusqlite's bundled sqlite
-  buildFeatures = [ "native-tls" ]; # use native openssl
+  buildFeatures = [
+    "native-tls" # use native openssl
+  ]
+  ++ lib.optional withFetch "fetch"
+  ++ lib.optional withLua "lua"
+  ++ extraFeatures;
   cargoLock = {
     lockFile = ../Cargo.lock;
     allowBuiltinFetchGit = true;
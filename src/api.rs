@@ -0,0 +1,230 @@
+//! API for using this tool as a library
+//!
+//! Unlike the CLI, which spins up a tokio runtime of its own for each command, the async functions
+//! of this module run on the runtime of the caller, and can be cancelled via a
+//! [`CancellationToken`]. Progress is reported to a [`ProgressSink`] instead of the log. Fetching
+//! from an API server requires the `fetch` feature, importing JSON files does not.
+
+#[cfg(feature = "fetch")]
+use std::time::{Duration, Instant};
+use std::{path::PathBuf, sync::Arc};
+
+use eyre::Result;
+#[cfg(feature = "fetch")]
+use tokio_util::task::AbortOnDropHandle;
+
+#[cfg(feature = "fetch")]
+pub use crate::{
+    cli::{ApiVersion, CommitSelector, ProjectSelector},
+    fetch::HttpOptions,
+};
+pub use crate::{
+    progress::{Phase, ProgressSink},
+    transform::ElementTransform,
+};
+#[cfg(feature = "fetch")]
+pub use tokio_util::sync::CancellationToken;
+
+#[cfg(feature = "fetch")]
+use crate::fetch;
+use crate::{import, progress::Progress, transform::Transform, tweaks};
+
+/// What to fetch from an API server, and which db to import it into
+#[cfg(feature = "fetch")]
+pub struct FetchImport {
+    /// Base URL of the API server, or the name of a server profile of the config
+    pub server: String,
+
+    /// The project and commit to fetch
+    pub project: ProjectSelector,
+
+    /// The db to import into, which must be initialized already
+    pub db_file: PathBuf,
+
+    /// Options of the HTTP client
+    pub http_options: HttpOptions,
+
+    /// The version of the API, detected from the server if not given
+    pub maybe_api_version: Option<ApiVersion>,
+
+    /// Merge the derived properties of each element, which takes one request per element
+    pub derived: bool,
+
+    /// Merge the relationships of each element, which takes one request per element
+    pub relationships: bool,
+
+    /// Receiver of the progress of the fetch and the import
+    pub maybe_progress: Option<Arc<dyn ProgressSink>>,
+
+    /// Rewrites or drops each element before it is imported
+    pub maybe_transform: Option<Arc<dyn ElementTransform>>,
+}
+
+/// Summary of a completed [`fetch_and_import`]
+#[cfg(feature = "fetch")]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ImportSummary {
+    /// Id of the fetched project
+    pub project_id: String,
+
+    /// Id of the fetched commit
+    pub commit_id: String,
+
+    /// Number of imported elements
+    pub elements: usize,
+
+    /// Time taken to fetch the elements
+    pub fetch_duration: Duration,
+
+    /// Time taken to import the elements
+    pub import_duration: Duration,
+}
+
+/// Error of an operation which was cancelled via its [`CancellationToken`]
+///
+/// Cancelled operations leave the db as it was before, as the import either completes or is rolled
+/// back as a whole.
+#[cfg(feature = "fetch")]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Cancelled;
+
+#[cfg(feature = "fetch")]
+impl std::fmt::Display for Cancelled {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("cancelled")
+    }
+}
+
+#[cfg(feature = "fetch")]
+impl std::error::Error for Cancelled {}
+
+/// Fetch a commit of a project from an API server and import it into a db
+///
+/// Once `cancel` is cancelled, the fetch is aborted, or the import is rolled back, and the function
+/// fails with [`Cancelled`], see [`eyre::Report::is`]. Cancelling after the import committed has no
+/// effect.
+#[cfg(feature = "fetch")]
+pub async fn fetch_and_import(
+    request: FetchImport,
+    cancel: CancellationToken,
+) -> Result<ImportSummary> {
+    let FetchImport {
+        server,
+        project,
+        db_file,
+        http_options,
+        maybe_api_version,
+        derived,
+        relationships,
+        maybe_progress,
+        maybe_transform,
+    } = request;
+    let progress = maybe_progress.map(Progress::new).unwrap_or_default();
+
+    // open the db first, to fail before fetching anything
+    let mut conn = tweaks::open_db(&db_file)?;
+    let browser = fetch::SysmlV2ApiBrowser::new(&server, http_options, maybe_api_version)?;
+    let source = browser.base_url().to_string();
+
+    let fetch_t0 = Instant::now();
+    progress.phase(Phase::Fetching);
+    let fetch = async {
+        let (project_id, commit_id) = fetch::interprete_cli(&browser, &project).await?;
+        let url_path = fetch::build_url_path(&project_id, &commit_id, None);
+        let fetch_options = fetch::FetchOptions {
+            derived,
+            relationships,
+            progress: progress.clone(),
+            ..Default::default()
+        };
+        let elements = fetch::fetch_model_elements(&browser, &url_path, &fetch_options).await?;
+        Ok::<_, eyre::Report>((project_id, commit_id, elements))
+    };
+    let (project_id, commit_id, elements) =
+        cancel.run_until_cancelled(fetch).await.ok_or(Cancelled)??;
+    let fetch_duration = fetch_t0.elapsed();
+
+    let import_t0 = Instant::now();
+    let elements_count = elements.len();
+    let import_options = import::ImportOptions {
+        provenance: import::ImportProvenance {
+            source: Some(source),
+            project_id: Some(project_id.clone()),
+            commit_id: Some(commit_id.clone()),
+            ..Default::default()
+        },
+        progress,
+        maybe_transform: maybe_transform.map(Transform::new),
+        cancellation: import::Cancellation::new({
+            let cancel = cancel.clone();
+            move || cancel.is_cancelled()
+        }),
+        ..Default::default()
+    };
+
+    // the import blocks, hence it runs on a thread of its own. It checks for the cancellation
+    // between the elements, and long running statements are interrupted.
+    let interrupt_handle = conn.get_interrupt_handle();
+    let cancel_clone = cancel.clone();
+    let _interrupter = AbortOnDropHandle::new(tokio::spawn(async move {
+        cancel_clone.cancelled().await;
+        debug!("interrupting the import");
+        interrupt_handle.interrupt();
+    }));
+    let import_result = tokio::task::spawn_blocking(move || {
+        import::import_from_slice(&elements, &mut conn, &import_options)
+    })
+    .await?;
+    if import_result.is_err() && cancel.is_cancelled() {
+        return Err(Cancelled.into());
+    }
+    import_result?;
+
+    Ok(ImportSummary {
+        project_id,
+        commit_id,
+        elements: elements_count,
+        fetch_duration,
+        import_duration: import_t0.elapsed(),
+    })
+}
+
+/// What to import from a JSON file, and which db to import it into
+pub struct ImportJson {
+    /// The JSON file to import, an array of elements
+    pub json_file: PathBuf,
+
+    /// The db to import into, which must be initialized already
+    pub db_file: PathBuf,
+
+    /// Receiver of the progress of the import
+    pub maybe_progress: Option<Arc<dyn ProgressSink>>,
+
+    /// Rewrites or drops each element before it is imported
+    pub maybe_transform: Option<Arc<dyn ElementTransform>>,
+}
+
+/// Import the elements of a JSON file into a db, like the `import-json` command
+///
+/// The import blocks, async callers should run it via `tokio::task::spawn_blocking`.
+pub fn import_json_file(request: ImportJson) -> Result<()> {
+    let ImportJson {
+        json_file,
+        db_file,
+        maybe_progress,
+        maybe_transform,
+    } = request;
+
+    let mut conn = tweaks::open_db(&db_file)?;
+    let elements_stream = crate::util::CloneableJsonArrayStreamIterator::new(&json_file)?;
+    let options = import::ImportOptions {
+        provenance: import::ImportProvenance {
+            source: Some(json_file.to_string_lossy().into_owned()),
+            ..Default::default()
+        },
+        progress: maybe_progress.map(Progress::new).unwrap_or_default(),
+        maybe_transform: maybe_transform.map(Transform::new),
+        ..Default::default()
+    };
+    import::import_from_iter(elements_stream, &mut conn, &options)
+}
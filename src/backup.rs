@@ -0,0 +1,86 @@
+//! Snapshots of a db, and the transfer of in-memory dbs from and to files
+
+use std::{path::Path, time::Duration};
+
+use eyre::{Result, bail};
+use rusqlite::{
+    Connection, DatabaseName,
+    backup::{Backup, Progress},
+};
+
+/// Number of pages copied per step of the online backup
+const PAGES_PER_STEP: std::ffi::c_int = 1024;
+
+/// Pause between two steps of the online backup, allowing other connections to access the db
+const PAUSE_BETWEEN_STEPS: Duration = Duration::from_millis(10);
+
+/// Write a consistent snapshot of the db to a new file
+///
+/// By default, the online backup API of SQLite is used. It copies the db page by page, releasing
+/// the lock on the db between the steps. If another connection writes to the db meanwhile, the
+/// backup restarts, so the snapshot always reflects a single point in time. With `vacuum`, the
+/// snapshot is written via `VACUUM INTO` instead, which yields a compacted copy but holds a read
+/// transaction until it is done.
+///
+/// Refuses to overwrite an existing file, unless `force` is set.
+pub(crate) fn backup(conn: &Connection, target: &Path, vacuum: bool, force: bool) -> Result<()> {
+    if target.exists() {
+        if !force {
+            bail!("the file {target:?} already exists, refusing to overwrite it");
+        }
+        warn!("overwriting {target:?}");
+        std::fs::remove_file(target)?;
+    }
+
+    let now = std::time::Instant::now();
+    if vacuum {
+        info!("writing a compacted snapshot of the db to {target:?}");
+        conn.execute("VACUUM INTO ?1", [target.to_string_lossy()])?;
+    } else {
+        info!("writing a snapshot of the db to {target:?}");
+        let mut target_conn = Connection::open(target)?;
+        let backup = Backup::new(conn, &mut target_conn)?;
+        backup.run_to_completion(PAGES_PER_STEP, PAUSE_BETWEEN_STEPS, Some(report_progress))?;
+    }
+    info!("that took {:?}", now.elapsed());
+
+    Ok(())
+}
+
+/// Load a db file into the db of the connection, typically an in-memory db
+pub(crate) fn load_from(conn: &mut Connection, source: &Path) -> Result<()> {
+    info!("loading the db from {source:?}");
+    let now = std::time::Instant::now();
+    conn.restore(DatabaseName::Main, source, Some(report_progress))?;
+    info!("that took {:?}", now.elapsed());
+    Ok(())
+}
+
+/// Save the db of the connection to a file, typically at the end of working in an in-memory db
+///
+/// The db is written via `VACUUM INTO` into a temporary file beside the target, which then replaces
+/// the target. Thus the target is never left half-written.
+pub(crate) fn save_to(conn: &Connection, target: &Path) -> Result<()> {
+    let mut temporary = target.as_os_str().to_owned();
+    temporary.push(".tmp");
+    let temporary = Path::new(&temporary);
+    if temporary.exists() {
+        std::fs::remove_file(temporary)?;
+    }
+
+    info!("saving the db to {target:?}");
+    let now = std::time::Instant::now();
+    conn.execute("VACUUM INTO ?1", [temporary.to_string_lossy()])?;
+    std::fs::rename(temporary, target)?;
+    info!("that took {:?}", now.elapsed());
+    Ok(())
+}
+
+/// Report the progress of the online backup
+fn report_progress(progress: Progress) {
+    debug!(
+        "backed up {} of {} pages",
+        progress.pagecount - progress.remaining,
+        progress.pagecount
+    );
+}
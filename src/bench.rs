@@ -0,0 +1,301 @@
+//! Benchmark of the import with different tunings of SQLite
+
+use std::{
+    io::Write,
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+use eyre::{Context, Result, bail};
+use rusqlite::Connection;
+
+use crate::{
+    config::{ELEMENT_PK_COL, Pragmas, config},
+    import::{ImportOptions, import_from_iter},
+    json_schema_to_sql::{Root, SchemaOptions, consume_json_schema},
+    util::{CloneableJsonArrayStreamIterator, escape_sql_ident},
+};
+
+/// Variants compared if none are given, each a name, the pragmas it overrides and its batch size
+type DefaultVariant = (
+    &'static str,
+    &'static [(&'static str, &'static str)],
+    Option<usize>,
+);
+const DEFAULT_VARIANTS: &[DefaultVariant] = &[
+    ("config", &[], None),
+    ("synchronous=NORMAL", &[("synchronous", "NORMAL")], None),
+    ("journal_mode=WAL", &[("journal_mode", "WAL")], None),
+    ("cache_size=-2000", &[("cache_size", "-2000")], None),
+    ("batch_size=1", &[], Some(1)),
+];
+
+/// A tuning of the import to benchmark, given as `NAME:PRAGMA=VALUE,...`
+#[derive(Debug, Clone)]
+pub(crate) struct BenchVariant {
+    /// Name of the variant in the comparison table
+    pub(crate) name: String,
+
+    /// Pragmas overriding those of the config, as `(name, value)`
+    pub(crate) pragmas: Vec<(String, String)>,
+
+    /// Number of rows inserted per statement, see [`ImportOptions::maybe_batch_size`]
+    pub(crate) maybe_batch_size: Option<usize>,
+
+    /// Generate the elements table `WITHOUT ROWID`, see [`SchemaOptions::without_rowid_elements`]
+    pub(crate) without_rowid: bool,
+}
+
+/// Measurements of a single run of a variant
+struct RunResult {
+    elements: u64,
+    duration: Duration,
+    db_size: u64,
+    lookups_duration: Duration,
+}
+
+/// Timings of the runs of a variant
+struct VariantResult {
+    name: String,
+    elements: u64,
+    durations: Vec<Duration>,
+    db_size: u64,
+    lookups_durations: Vec<Duration>,
+}
+
+impl VariantResult {
+    fn min(&self) -> Duration {
+        self.durations.iter().copied().min().unwrap_or_default()
+    }
+
+    fn max(&self) -> Duration {
+        self.durations.iter().copied().max().unwrap_or_default()
+    }
+
+    fn median(&self) -> Duration {
+        median(&self.durations)
+    }
+
+    fn lookups_median(&self) -> Duration {
+        median(&self.lookups_durations)
+    }
+}
+
+/// The median of some durations, zero if there are none
+fn median(durations: &[Duration]) -> Duration {
+    let mut durations = durations.to_vec();
+    durations.sort();
+    match durations.len() {
+        0 => Duration::ZERO,
+        n if n % 2 == 0 => (durations[n / 2 - 1] + durations[n / 2]) / 2,
+        n => durations[n / 2],
+    }
+}
+
+/// Import the JSON dump `file` `runs` times per variant and print a comparison table
+///
+/// Each run imports into a fresh db in `dir`, which is deleted afterwards, so that the runs do not
+/// influence each other. The db is initialized with the embedded schema, or with the schema
+/// generated from `maybe_schema`. Only the import itself is timed, not the initialization of the
+/// db. The table lists the minimum, median and maximum duration per variant, the elements imported
+/// per second at the median and the median relative to that of the first variant, followed by the
+/// size of the db and the elements looked up by their id per second, once each. Without variants,
+/// the pragmas of the config are compared to a few common alternatives.
+pub(crate) fn bench<W: Write>(
+    file: &Path,
+    maybe_schema: Option<&Root>,
+    runs: usize,
+    variants: &[BenchVariant],
+    dir: &Path,
+    out: &mut W,
+) -> Result<()> {
+    if runs == 0 {
+        bail!("at least one run per variant is required");
+    }
+    let variants = if variants.is_empty() {
+        default_variants()
+    } else {
+        variants.to_vec()
+    };
+
+    // all variants are checked before the first import
+    let variant_pragmas = variants
+        .iter()
+        .map(|variant| {
+            let mut pragmas = config().pragmas.clone();
+            for (name, value) in &variant.pragmas {
+                pragmas
+                    .set(name, value)
+                    .wrap_err_with(|| format!("in the variant {:?}", variant.name))?;
+            }
+            Ok(pragmas)
+        })
+        .collect::<Result<Vec<_>>>()?;
+    if let Some(variant) = variants
+        .iter()
+        .find(|variant| variant.without_rowid && maybe_schema.is_none())
+    {
+        bail!(
+            "the variant {:?} generates the elements table WITHOUT ROWID, which requires a JSON schema to generate the schema from",
+            variant.name
+        );
+    }
+
+    let mut results = Vec::with_capacity(variants.len());
+    for (variant, pragmas) in variants.iter().zip(&variant_pragmas) {
+        let mut result = VariantResult {
+            name: variant.name.clone(),
+            elements: 0,
+            durations: Vec::with_capacity(runs),
+            db_size: 0,
+            lookups_durations: Vec::with_capacity(runs),
+        };
+        let maybe_variant_schema = maybe_schema.map(|schema| {
+            let options = SchemaOptions {
+                without_rowid_elements: variant.without_rowid,
+                ..Default::default()
+            };
+            (schema, options)
+        });
+        for run in 1..=runs {
+            info!("benchmarking {:?}, run {run} of {runs}", variant.name);
+            let db_path = dir.join(format!(
+                "sysml-v2-sql-bench-{}-{}-{run}.db",
+                std::process::id(),
+                results.len()
+            ));
+            let run_result = bench_run(
+                file,
+                maybe_variant_schema.as_ref(),
+                &db_path,
+                pragmas,
+                variant.maybe_batch_size,
+            );
+            remove_db(&db_path)?;
+            let run_result = run_result?;
+            result.elements = run_result.elements;
+            result.durations.push(run_result.duration);
+            result.db_size = run_result.db_size;
+            result.lookups_durations.push(run_result.lookups_duration);
+        }
+        results.push(result);
+    }
+
+    writeln!(
+        out,
+        "variant\truns\tmin [s]\tmedian [s]\tmax [s]\telements/s\trelative\tsize [KiB]\tlookups/s"
+    )?;
+    let baseline = results[0].median();
+    for result in &results {
+        let median = result.median();
+        writeln!(
+            out,
+            "{}\t{}\t{:.3}\t{:.3}\t{:.3}\t{:.0}\t{:.2}\t{}\t{:.0}",
+            result.name,
+            result.durations.len(),
+            result.min().as_secs_f64(),
+            median.as_secs_f64(),
+            result.max().as_secs_f64(),
+            result.elements as f64 / median.as_secs_f64(),
+            median.as_secs_f64() / baseline.as_secs_f64(),
+            result.db_size / 1024,
+            result.elements as f64 / result.lookups_median().as_secs_f64(),
+        )?;
+    }
+
+    Ok(())
+}
+
+/// The variants compared if none are given
+fn default_variants() -> Vec<BenchVariant> {
+    DEFAULT_VARIANTS
+        .iter()
+        .map(|(name, pragmas, maybe_batch_size)| BenchVariant {
+            name: (*name).to_owned(),
+            pragmas: pragmas
+                .iter()
+                .map(|(name, value)| ((*name).to_owned(), (*value).to_owned()))
+                .collect(),
+            maybe_batch_size: *maybe_batch_size,
+            without_rowid: false,
+        })
+        .collect()
+}
+
+/// Import `file` into a fresh db at `db_path`, then look up each imported element by its id
+///
+/// The db is initialized with the schema generated from the JSON schema with the given options, or
+/// with the embedded schema if there is none.
+fn bench_run(
+    file: &Path,
+    maybe_schema: Option<&(&Root, SchemaOptions)>,
+    db_path: &Path,
+    pragmas: &Pragmas,
+    maybe_batch_size: Option<usize>,
+) -> Result<RunResult> {
+    if db_path.exists() {
+        bail!("the db {db_path:?} for benchmarking already exists");
+    }
+    let mut conn = Connection::open(db_path)?;
+    match maybe_schema {
+        Some((schema, options)) => {
+            consume_json_schema(schema, Some(&mut conn), options)?;
+        }
+        None => crate::init_db::init_db(&mut conn)?,
+    }
+    crate::tweaks::apply_connection_pragmas(&conn, pragmas)?;
+
+    let elements_stream = CloneableJsonArrayStreamIterator::new(file)?;
+    let options = ImportOptions {
+        maybe_pragmas: Some(pragmas.clone()),
+        maybe_batch_size,
+        ..Default::default()
+    };
+    let t0 = std::time::Instant::now();
+    import_from_iter(elements_stream, &mut conn, &options)?;
+    let duration = t0.elapsed();
+
+    let elements_table = escape_sql_ident(&config().elements_table);
+    let pk_column = escape_sql_ident(ELEMENT_PK_COL);
+    let ids = conn
+        .prepare(&format!("SELECT {pk_column} FROM {elements_table}"))?
+        .query_map([], |row| row.get::<_, String>(0))?
+        .collect::<Result<Vec<_>, _>>()?;
+    let mut lookup_stmt = conn.prepare(&format!(
+        "SELECT * FROM {elements_table} WHERE {pk_column} = ?1"
+    ))?;
+    let t0 = std::time::Instant::now();
+    for id in &ids {
+        if !lookup_stmt.exists([id])? {
+            bail!("the element {id:?} was not found by its id");
+        }
+    }
+    let lookups_duration = t0.elapsed();
+    lookup_stmt.finalize()?;
+
+    let db_size = conn.query_row(
+        "SELECT page_count * page_size FROM pragma_page_count(), pragma_page_size()",
+        [],
+        |row| row.get(0),
+    )?;
+    Ok(RunResult {
+        elements: ids.len() as u64,
+        duration,
+        db_size,
+        lookups_duration,
+    })
+}
+
+/// Delete a db used for benchmarking, together with its journal files
+fn remove_db(db_path: &Path) -> Result<()> {
+    for suffix in ["", "-journal", "-wal", "-shm"] {
+        let mut path = db_path.as_os_str().to_owned();
+        path.push(suffix);
+        let path = PathBuf::from(path);
+        if path.exists() {
+            std::fs::remove_file(&path)
+                .wrap_err_with(|| format!("deleting the db {path:?} used for benchmarking"))?;
+        }
+    }
+    Ok(())
+}
@@ -1,9 +1,40 @@
 //! Command Line Interface (CLI) of this software
-use clap::{Parser, Subcommand};
-use std::path::PathBuf;
+use clap::{Args, Parser, Subcommand, ValueEnum};
+use std::{
+    collections::BTreeSet,
+    path::{Path, PathBuf},
+};
+#[cfg(feature = "fetch")]
+use std::{net::SocketAddr, time::Duration};
+
+#[cfg(feature = "fetch")]
+use crate::fetch::HttpOptions;
+use crate::{
+    bench::BenchVariant,
+    config::{DEFAULT_HOT_FIELDS, TYPE_COL},
+    import::{AttributeFilter, ImportOptions},
+    json_schema_to_sql::SchemaOptions,
+};
+
+/// URL of the latest JSON schema in the official SysML-v2-API-Services repository
+#[cfg(feature = "fetch")]
+const UPSTREAM_SCHEMA_URL: &str = "https://raw.githubusercontent.com/Systems-Modeling/SysML-v2-API-Services/refs/heads/master/conf/json/schema/api/schemas.json";
+
+/// Exit codes telling apart the kinds of failures, see [`crate::failure::Failure`]
+const EXIT_CODES_HELP: &str = "\
+Exit codes:
+  0  success
+  1  any other failure
+  2  invalid command line arguments
+  3  schema mismatch, the db does not match the schema expected by this tool
+  4  foreign key violation
+  5  network error, the API server could not be reached
+  6  conflicting elements, e.g. differing elements with the same id
+  7  attributes not understood by an import in strict mode
+  8  SQL error in a script run by the sql command";
 
 #[derive(Parser)]
-#[command(version, about, long_about = None)]
+#[command(version, about, long_about = None, after_long_help = EXIT_CODES_HELP)]
 pub(crate) struct Cli {
     /// Increase verbosity (i.e. debug or trace level logging)
     ///
@@ -11,15 +42,266 @@ pub(crate) struct Cli {
     #[arg(short, long, action = clap::ArgAction::Count)]
     pub verbose: u8,
 
+    /// Only log errors
+    ///
+    /// Scripts can tell the kind of failure from the exit code instead, see the list below.
+    #[arg(short, long, conflicts_with = "verbose")]
+    pub quiet: bool,
+
+    /// Format of the log messages written to stderr
+    ///
+    /// With `json`, each message is a JSON object on a line of its own, carrying the level, the
+    /// message and the spans of the phases it occurred in (e.g. fetch, parse, insert elements and
+    /// insert relations). Meant for automation to follow progress and warnings.
+    #[arg(long, value_name = "FORMAT", default_value = "text")]
+    pub log_format: LogFormat,
+
+    /// Write the log messages of all levels to this file as well
+    ///
+    /// The messages on stderr keep the level selected by --verbose. Meant to be attached to support
+    /// requests, e.g. to include the complete log of an import. The file is overwritten.
+    #[arg(long, value_name = "FILE")]
+    pub log_file: Option<PathBuf>,
+
+    /// TOML file overriding table names and other knobs
+    ///
+    /// This allows to adapt to evolutions of the SysML v2 schema without rebuilding this tool. The
+    /// embedded schema used by init-db is adapted to the table names and polymorphic properties of
+    /// the config. The config file also defines the server profiles, which API commands take
+    /// instead of a base URL. Defaults to the file given by the environment variable SYSML_CONFIG,
+    /// if set.
+    #[arg(short, long)]
+    pub config: Option<PathBuf>,
+
+    /// Set a pragma tuning SQLite, given as `NAME=VALUE`, e.g. `mmap_size=1073741824`
+    ///
+    /// Takes precedence over the pragmas of the config file. The pragmas cache_size, page_size,
+    /// synchronous, mmap_size, journal_mode and threads can be set. The cache size, page size and
+    /// synchronous mode apply to bulk inserts, the others to every connection. Repeat to set
+    /// multiple pragmas.
+    #[arg(long, value_name = "NAME=VALUE", value_parser = parse_pragma)]
+    pub pragma: Vec<(String, String)>,
+
+    /// Limit the memory used, in bytes or suffixed with K, M or G, e.g. `2G`
+    ///
+    /// Applies the soft heap limit of SQLite, bounds the page cache of bulk inserts to half of
+    /// the limit and keeps fewer pages fetched from the API server in flight. The limit is soft,
+    /// the elements fetched from the API server are still held in memory in full. Takes precedence
+    /// over the limit of the config file.
+    #[arg(long, value_name = "SIZE", value_parser = parse_byte_size)]
+    pub max_memory: Option<u64>,
+
+    /// Open the db read-only
+    ///
+    /// This allows to query dbs owned by other users. Only commands which do not modify the db are
+    /// permitted.
+    #[arg(long, action)]
+    pub read_only: bool,
+
+    /// Open the db read-only, and assume that nobody modifies it meanwhile
+    ///
+    /// Meant for dbs on read-only media, which do not permit SQLite to take any locks. Implies
+    /// --read-only.
+    #[arg(long, action)]
+    pub immutable: bool,
+
+    /// Milliseconds to wait for a lock held by another process on the db, before giving up
+    #[arg(long, default_value_t = 5000)]
+    pub busy_timeout: u64,
+
+    /// Switch the db to the given journal mode, which persists until switched back
+    ///
+    /// In the `wal` mode, readers and a writer do not block each other, so queries can run while an
+    /// import is in progress. Bulk inserts are somewhat slower, and the db has to reside on a
+    /// local file system. After a bulk insert, the write-ahead log is checkpointed into the db.
+    /// Takes precedence over the journal mode of the config file.
+    #[arg(long, value_name = "MODE", conflicts_with = "wal")]
+    pub journal_mode: Option<JournalMode>,
+
+    /// Switch the db to write-ahead logging, short for `--journal-mode wal`
+    #[arg(long, action)]
+    pub wal: bool,
+
+    /// Save the db to this file once the command completed
+    ///
+    /// Meant for use with the in-memory db `:memory:`, which is then loaded from this file first if
+    /// it exists. Working in memory is much faster, for example importing a medium-sized model.
+    #[arg(long)]
+    pub save_to: Option<PathBuf>,
+
+    /// Project to operate on, in dbs storing multiple projects
+    ///
+    /// Commands which only read the db see only the elements, relations and extended properties of
+    /// this project. import-json stores the imported elements under this project. See the
+    /// --multi-project option of the schema generation.
+    #[arg(long, value_name = "PROJECT_ID")]
+    pub project: Option<String>,
+
+    /// Attach another db under an alias, given as `ALIAS=PATH`
+    ///
+    /// The tables of the attached db can then be referred to as `ALIAS.table` in SQL, for example in
+    /// validation rules, to join and compare two imported models in a single statement. Repeat to
+    /// attach multiple dbs.
+    #[arg(long, value_name = "ALIAS=PATH", value_parser = parse_attachment)]
+    pub attach: Vec<(String, PathBuf)>,
+
+    /// Version of the SysML v2 API implemented by the API server, detected if not given
+    ///
+    /// The versions differ slightly in their payloads, e.g. in how a commit refers to its previous
+    /// commit. The version is detected from the OpenAPI document served at `docs/openapi.json`
+    /// below the base URL, assuming `pilot` if there is none.
+    #[arg(long, value_name = "VERSION")]
+    pub api_version: Option<ApiVersion>,
+
     /// SQLite db to operate on
     ///
-    /// Creates a new file on demand
+    /// Creates a new file on demand. Use `:memory:` for an in-memory db, which is lost at the end
+    /// unless --save-to is given.
     pub db_file: PathBuf,
 
     #[command(subcommand)]
     pub command: Commands,
 }
 
+/// Parses an `ALIAS=PATH` pair for `--attach`
+fn parse_attachment(arg: &str) -> Result<(String, PathBuf), String> {
+    match arg.split_once('=') {
+        Some((alias, path)) if !alias.is_empty() && !path.is_empty() => {
+            Ok((alias.to_owned(), PathBuf::from(path)))
+        }
+        _ => Err(format!("expected ALIAS=PATH, found {arg:?}")),
+    }
+}
+
+/// Parses a `NAME=VALUE` pair for `--pragma`
+fn parse_pragma(arg: &str) -> Result<(String, String), String> {
+    match arg.split_once('=') {
+        Some((name, value)) if !name.is_empty() && !value.is_empty() => {
+            Ok((name.to_owned(), value.to_owned()))
+        }
+        _ => Err(format!("expected NAME=VALUE, found {arg:?}")),
+    }
+}
+
+/// Parses an `ATTRIBUTE[:MODE]` rule for `--redact`, blanking the attribute if no mode is given
+fn parse_redaction(arg: &str) -> Result<(String, RedactMode), String> {
+    let (attribute, mode) = match arg.split_once(':') {
+        Some((attribute, mode)) => (attribute, RedactMode::from_str(mode, true)?),
+        None => (arg, RedactMode::Blank),
+    };
+    if attribute.is_empty() {
+        return Err(format!("expected ATTRIBUTE[:MODE], found {arg:?}"));
+    }
+    Ok((attribute.to_owned(), mode))
+}
+
+/// Parses a `NAME[:TYPE]=VALUE` parameter for `sql --param`, with the types text, int and real
+///
+/// Without type, the value is bound as text. A leading `:`, `@` or `$` of the name is ignored.
+fn parse_sql_param(arg: &str) -> Result<(String, rusqlite::types::Value), String> {
+    use rusqlite::types::Value;
+
+    let Some((name, value)) = arg.split_once('=') else {
+        return Err(format!("expected NAME[:TYPE]=VALUE, found {arg:?}"));
+    };
+    let (name, ty) = name.split_once(':').unwrap_or((name, "text"));
+    let name = name.trim_start_matches([':', '@', '$']);
+    if name.is_empty() {
+        return Err(format!("expected NAME[:TYPE]=VALUE, found {arg:?}"));
+    }
+    let value = match ty {
+        "text" => Value::Text(value.to_owned()),
+        "int" => Value::Integer(
+            value
+                .parse()
+                .map_err(|_| format!("expected an integer for {name:?}, found {value:?}"))?,
+        ),
+        "real" => Value::Real(
+            value
+                .parse()
+                .map_err(|_| format!("expected a number for {name:?}, found {value:?}"))?,
+        ),
+        _ => return Err(format!("expected the type text, int or real, found {ty:?}")),
+    };
+    Ok((name.to_owned(), value))
+}
+
+/// Parses a size like `512M` or `2G`, with binary units, plain numbers are taken as bytes
+fn parse_byte_size(arg: &str) -> Result<u64, String> {
+    let (number, unit_bytes) = match arg.as_bytes().last() {
+        Some(b'K' | b'k') => (&arg[..arg.len() - 1], 1 << 10),
+        Some(b'M' | b'm') => (&arg[..arg.len() - 1], 1 << 20),
+        Some(b'G' | b'g') => (&arg[..arg.len() - 1], 1 << 30),
+        _ => (arg, 1),
+    };
+    match number.parse::<u64>().map(|n| n.checked_mul(unit_bytes)) {
+        Ok(Some(n)) if n > 0 => Ok(n),
+        _ => Err(format!(
+            "expected a positive number of bytes, or one suffixed with K, M or G, found {arg:?}"
+        )),
+    }
+}
+
+/// Parses a `NAME:PRAGMA=VALUE,...` variant for `bench --variant`, the name defaults to the pragmas
+///
+/// Besides pragmas, `batch_size=N` sets the number of rows inserted per statement, and
+/// `without_rowid=true` generates the elements table `WITHOUT ROWID`.
+fn parse_bench_variant(arg: &str) -> Result<BenchVariant, String> {
+    let (name, settings) = arg.split_once(':').unwrap_or((arg, arg));
+    if name.is_empty() {
+        return Err(format!("expected NAME:PRAGMA=VALUE,..., found {arg:?}"));
+    }
+    let mut variant = BenchVariant {
+        name: name.to_owned(),
+        pragmas: Vec::new(),
+        maybe_batch_size: None,
+        without_rowid: false,
+    };
+    for setting in settings.split(',').filter(|s| !s.is_empty()) {
+        match parse_pragma(setting)? {
+            (name, value) if name.replace('-', "_") == "batch_size" => match value.parse() {
+                Ok(batch_size) if batch_size > 0 => variant.maybe_batch_size = Some(batch_size),
+                _ => return Err(format!("expected a positive batch size, found {value:?}")),
+            },
+            (name, value) if name.replace('-', "_") == "without_rowid" => match value.parse() {
+                Ok(without_rowid) => variant.without_rowid = without_rowid,
+                _ => return Err(format!("expected true or false, found {value:?}")),
+            },
+            pragma => variant.pragmas.push(pragma),
+        }
+    }
+    Ok(variant)
+}
+
+/// Parses a JSON object, e.g. a query for `--query`
+#[cfg(feature = "fetch")]
+fn parse_json_object(arg: &str) -> Result<serde_json::Value, String> {
+    match serde_json::from_str(arg) {
+        Ok(value @ serde_json::Value::Object(_)) => Ok(value),
+        Ok(_) => Err(format!("expected a JSON object, found {arg:?}")),
+        Err(e) => Err(format!("invalid JSON: {e}")),
+    }
+}
+
+/// Parses a time interval like `90s`, `15m` or `2h`, plain numbers are taken as seconds
+#[cfg(feature = "fetch")]
+fn parse_interval(arg: &str) -> Result<Duration, String> {
+    let (number, unit_secs) = match arg.as_bytes().last() {
+        Some(b's') => (&arg[..arg.len() - 1], 1),
+        Some(b'm') => (&arg[..arg.len() - 1], 60),
+        Some(b'h') => (&arg[..arg.len() - 1], 60 * 60),
+        _ => (arg, 1),
+    };
+    match number.parse::<u64>() {
+        Ok(n) if n > 0 => Ok(Duration::from_secs(n * unit_secs)),
+        _ => Err(format!(
+            "expected a positive number of seconds, or one suffixed with s, m or h, found {arg:?}"
+        )),
+    }
+}
+
+// parsed once per run, thus the size of the largest variant does not matter
+#[allow(clippy::large_enum_variant)]
 #[derive(Subcommand)]
 pub(crate) enum Commands {
     /// Import data from JSON file to the db
@@ -48,14 +330,560 @@ pub(crate) enum Commands {
         /// removed since the last vacuum/initial database creation.
         #[arg(short, long, action)]
         vacuum: bool,
+
+        #[command(flatten)]
+        import_args: ImportArgs,
     },
 
     /// Initialize a db, creating all missing tables to the db
     ///
     /// This operation is idempotent, i.e. one db can be initialized multiple times over without harm.
-    /// However, this operation does not handle schema migrations.
+    /// However, this operation does not handle schema migrations, see `upgrade-db` for these.
     InitDb,
 
+    /// Migrate a db to the schema version of this tool
+    ///
+    /// The schema version of a db is tracked in `PRAGMA user_version`. Imports are refused until a
+    /// db created by an older version of this tool is upgraded. Each migration step is atomic, i.e.
+    /// if it fails, the db remains at the schema version reached so far.
+    UpgradeDb,
+
+    /// Write a consistent snapshot of the db to a new file
+    ///
+    /// Unlike copying the db file, this is safe while other processes use the db, for example
+    /// during a long import. By default the online backup API of SQLite is used, which copies the
+    /// db in small steps and restarts if the db is modified meanwhile.
+    Backup {
+        /// File to write the snapshot to
+        file: PathBuf,
+
+        /// Write a compacted snapshot via `VACUUM INTO` instead
+        ///
+        /// The snapshot is smaller, but the db is locked against writes until it is written.
+        #[arg(short, long, action)]
+        vacuum: bool,
+
+        /// Overwrite the file if it exists
+        #[arg(short, long, action)]
+        force: bool,
+    },
+
+    /// Set a primitive attribute of an element
+    ///
+    /// The attribute must be a column of the elements table. For text columns, VALUE is taken
+    /// verbatim, for other columns it is interpreted as JSON value, e.g. `true` or `42`. Without
+    /// VALUE, the attribute is set to NULL. The constraints and triggers of the schema reject
+    /// values which violate it.
+    SetAttribute {
+        /// Id of the element
+        id: String,
+
+        /// Name of the attribute
+        attribute: String,
+
+        /// New value of the attribute
+        value: Option<String>,
+    },
+
+    /// Delete an element, together with its extended properties and relations
+    ///
+    /// All relations originating from or targeting the element are deleted, so no dangling
+    /// references remain.
+    DeleteElement {
+        /// Id of the element
+        id: String,
+
+        /// Also delete all elements owned by the element, directly or indirectly
+        #[arg(short, long, action)]
+        recursive: bool,
+    },
+
+    /// Add a relation between two elements
+    ///
+    /// The relation name must be known to the db, and the type of the origin must carry it if the
+    /// db knows which properties each type carries.
+    AddRelation {
+        /// Name of the relation, e.g. `ownedElement`
+        name: String,
+
+        /// Id of the element the relation originates from
+        origin_id: String,
+
+        /// Id of the element the relation targets
+        target_id: String,
+    },
+
+    /// Remove a relation between two elements
+    RemoveRelation {
+        /// Name of the relation, e.g. `ownedElement`
+        name: String,
+
+        /// Id of the element the relation originates from
+        origin_id: String,
+
+        /// Id of the element the relation targets
+        target_id: String,
+    },
+
+    /// Search elements by name, qualified name and type
+    ///
+    /// Prints one line per matching element, containing its id, its type and its qualified name
+    /// (or name, if there is no qualified name), separated by tabs.
+    Search {
+        /// Substring to search for in the names and qualified names of the elements
+        ///
+        /// If -f/--fts is set, this is interpreted as FTS5 query instead.
+        pattern: String,
+
+        /// Only consider elements of this type (e.g. `PartUsage`)
+        #[arg(short, long)]
+        r#type: Option<String>,
+
+        /// Use the full-text index instead of substring matching
+        #[arg(short, long, action)]
+        fts: bool,
+
+        /// Maximum number of matches to print
+        #[arg(short, long, default_value_t = 100)]
+        limit: usize,
+    },
+
+    /// Print the ownership hierarchy of the model
+    ///
+    /// Each line contains the name, the type and the id of an element, indented by its depth in
+    /// the tree.
+    Tree {
+        /// Id or qualified name of the element to start from
+        ///
+        /// If omitted, all elements which are not owned by another element are used as roots.
+        root: Option<String>,
+
+        /// Maximum depth to descend into, the root being at depth 0
+        #[arg(short = 'd', long)]
+        max_depth: Option<u32>,
+
+        /// Only print elements of this type (e.g. `PartUsage`), may be given multiple times
+        ///
+        /// Elements of other types are still traversed, but not printed.
+        #[arg(short, long)]
+        r#type: Vec<String>,
+    },
+
+    /// Show everything known about a single element
+    ///
+    /// Prints all non-null columns of the element, its outgoing and incoming relations grouped by
+    /// relation name, and its extended properties.
+    Show {
+        /// Id or qualified name of the element
+        element: String,
+    },
+
+    /// Print the traceability chain of a requirement
+    ///
+    /// Follows satisfy, verify, derive and refine relations transitively, starting from the given
+    /// requirement. Derivations are recognized as connections (`ConnectionUsage`) from the
+    /// original requirement (source) to the derived ones (target), refinements as dependencies
+    /// (`Dependency`) from the refining element (client) to the refined requirement (supplier).
+    Trace {
+        /// Id or qualified name of the requirement
+        requirement: String,
+
+        /// Maximum number of trace links to follow from the requirement
+        #[arg(short = 'd', long)]
+        max_depth: Option<usize>,
+
+        /// CSV file to write the traceability matrix to
+        #[arg(short, long)]
+        csv: Option<PathBuf>,
+    },
+
+    /// Write the traceability matrices of all requirements to an Excel workbook
+    ///
+    /// The workbook contains one sheet per kind of trace link (satisfy, verify, derive, refine),
+    /// see `trace`. Each sheet has one row per requirement and one column per element tracing a
+    /// requirement, marking the direct trace links between them. Requirements which are not traced
+    /// at all are listed as well, so gaps in the traceability stand out.
+    TraceMatrix {
+        /// The `.xlsx` file to write the workbook to
+        xlsx: PathBuf,
+    },
+
+    /// Print which requirements are satisfied and verified, with percentages per package
+    ///
+    /// Requirements are grouped by the closest package owning them. Lists the requirements lacking
+    /// satisfy or verify links afterwards.
+    Coverage {
+        /// Print the coverage as JSON object instead
+        #[arg(short, long, action)]
+        json: bool,
+    },
+
+    /// Find the shortest chain of relations between two elements
+    ///
+    /// Prints each element along the path, together with the relation leading to it. Arrows
+    /// pointing to the left denote relations traversed against their direction.
+    Path {
+        /// Id or qualified name of the element to start from
+        from: String,
+
+        /// Id or qualified name of the element to reach
+        to: String,
+
+        /// Only traverse relations from their origin to their target
+        #[arg(short, long, action)]
+        directed: bool,
+
+        /// Only traverse relations of this name, may be given multiple times
+        #[arg(short, long)]
+        relation: Vec<String>,
+    },
+
+    /// Compute all elements affected by a change of an element
+    ///
+    /// Prints all elements transitively reachable from the given element via relations, each
+    /// prefixed with its distance to the given element.
+    Impact {
+        /// Id or qualified name of the element
+        element: String,
+
+        /// Follow relations against their direction, i.e. find all elements leading to the element
+        #[arg(short = 'R', long, action)]
+        reverse: bool,
+
+        /// Only follow relations of this name, may be given multiple times
+        #[arg(short, long)]
+        relation: Vec<String>,
+
+        /// Maximum number of relations to follow from the element
+        #[arg(short = 'd', long)]
+        max_depth: Option<usize>,
+    },
+
+    /// Print the flattened bill of materials of a part, as CSV
+    ///
+    /// Counts the parts below the given part (usage or definition) per definition, multiplying the
+    /// upper bounds of their multiplicities along the part tree. Parts owning no parts themselves
+    /// are expanded by the parts of their definitions.
+    Bom {
+        /// Id or qualified name of the part to list the materials of
+        root: String,
+
+        /// Print the bill of materials as JSON array instead
+        #[arg(short, long, action)]
+        json: bool,
+    },
+
+    /// Export the element/relation graph for analysis in graph tools
+    ///
+    /// Each element becomes a node, labelled by its name and type, and each row of the relations
+    /// table a directed edge, labelled by the name of the relation. The graph is printed to
+    /// stdout.
+    ///
+    /// The graph can be narrowed down to a subgraph, e.g. to render focused diagrams of the
+    /// ownership or traceability structure: only relations of the given names are exported, and
+    /// with a root only the elements reachable from it via these relations. Of those, only elements
+    /// of the given types are exported.
+    ExportGraph {
+        /// Format to export the graph in
+        #[arg(short, long, value_enum)]
+        format: GraphFormat,
+
+        /// Id or qualified name of the element to start from
+        root: Option<String>,
+
+        /// Only export relations of this name, may be given multiple times
+        #[arg(short, long)]
+        relation: Vec<String>,
+
+        /// Only export elements of this type (e.g. `PartUsage`), may be given multiple times
+        ///
+        /// Relations through elements of other types are still followed from the root.
+        #[arg(short, long)]
+        r#type: Vec<String>,
+
+        /// Maximum number of relations to follow from the root
+        #[arg(short = 'd', long, requires = "root")]
+        max_depth: Option<usize>,
+    },
+
+    /// Export the elements, relations and extended properties tables as Parquet files
+    ///
+    /// Writes one file per table into the given directory, named after the table, for use in data
+    /// pipelines such as Spark or polars. The column types are preserved. With `--project`, only
+    /// the rows of the selected project are exported.
+    ExportParquet {
+        /// Directory to write the files to, created if missing
+        dir: PathBuf,
+
+        /// Overwrite existing files
+        #[arg(short, long, action)]
+        force: bool,
+    },
+
+    /// Export the model stripped of names, documentation and ids, e.g. for bug reports
+    ///
+    /// Writes the elements as JSON array, which `import-json` reads, or with `--db` a new db with
+    /// the elements imported. The attributes holding names and documentation, e.g.
+    /// `declaredName`, `qualifiedName`, `memberName` and `body`, are blanked, as are those redacted
+    /// by the config file or `--redact`. The ids are replaced by pseudonyms as with `import-json
+    /// --pseudonymize-ids`, keyed by SYSML_PSEUDONYM_KEY if set and by a random key otherwise. The
+    /// types of the elements, their relations and all other attributes are kept, including the
+    /// values of literals, add `--redact value` to blank those too. With `--project`, only the
+    /// selected project is exported.
+    ExportAnonymized {
+        /// File to write the elements to
+        out: PathBuf,
+
+        /// Write a db instead of a JSON file
+        #[arg(long, action)]
+        db: bool,
+
+        /// Overwrite an existing file
+        #[arg(short, long, action)]
+        force: bool,
+
+        /// Redact these attributes as well, given as `ATTRIBUTE[:MODE]`, see `import-json`
+        #[arg(
+            long,
+            value_name = "ATTRIBUTE[:MODE]",
+            value_delimiter = ',',
+            value_parser = parse_redaction
+        )]
+        redact: Vec<(String, RedactMode)>,
+    },
+
+    /// Print metadata describing the db, for publishing it via Datasette or similar browsers
+    ///
+    /// The metadata is printed in the `metadata.json` format of Datasette. It describes each
+    /// table and view, the columns of the core tables with the definitions of the JSON schema they
+    /// stem from, and contains canned queries for common lookups, e.g. the elements owned by an
+    /// element. Serve it via `datasette DB_FILE --metadata metadata.json`.
+    ExportMetadata {
+        /// Title of the db shown by the browser, defaults to the name of the db file
+        #[arg(short, long)]
+        title: Option<String>,
+    },
+
+    /// Export the structure of a definition as PlantUML component diagram
+    ///
+    /// Shows the parts and ports owned by the definition, nested according to ownership, and the
+    /// connections between them. Parts owning no parts or ports themselves are shown with those of
+    /// their definitions instead.
+    ExportPlantuml {
+        /// Id or qualified name of the definition (or usage) to show the structure of
+        root: String,
+
+        /// Maximum nesting depth of the parts below the root
+        #[arg(short = 'd', long)]
+        max_depth: Option<usize>,
+    },
+
+    /// Render a report about the model, e.g. to publish a snapshot of it
+    ///
+    /// The report lists the package tree, the elements of each type, the requirements with their
+    /// documentation and some basic metrics. It is rendered from a MiniJinja template, either a
+    /// built-in one or a custom one given via `--template`.
+    Report {
+        /// Format of the built-in template to render
+        #[arg(short, long, value_enum, default_value_t = ReportFormat::Markdown)]
+        format: ReportFormat,
+
+        /// Render this template instead of a built-in one
+        ///
+        /// Its output is HTML-escaped if the file name ends in `.html`, `.htm` or `.xml`.
+        #[arg(short, long, conflicts_with = "format")]
+        template: Option<PathBuf>,
+    },
+
+    /// Compare the db to another db, listing the differences grouped by element type
+    ///
+    /// The db is considered the old, the other db the new version of the model. Added (`+`),
+    /// removed (`-`) and changed (`~`) elements are printed with their id and label, changed
+    /// elements also with the names of the changed columns. Added and removed relations are listed
+    /// under the type of their origin element, extended properties under the type of their element.
+    DbDiff {
+        /// The db containing the new version of the model
+        other: PathBuf,
+
+        /// Only print the number of differences per element type
+        #[arg(short, long, action)]
+        summary: bool,
+    },
+
+    /// Compare two JSON dumps element by element, without importing them
+    ///
+    /// Elements are matched by their id. Added (`+`), removed (`-`) and changed (`~`) elements are
+    /// printed with their id and label, grouped by element type. For changed elements, the changed
+    /// attributes are listed with their old and new value, and the added and removed relations with
+    /// their target. The db is not used.
+    JsonDiff {
+        /// JSON file containing the old version of the model
+        old: PathBuf,
+
+        /// JSON file containing the new version of the model
+        new: PathBuf,
+
+        /// Print the differences as JSON array instead
+        #[arg(short, long, action)]
+        json: bool,
+    },
+
+    /// Print statistics about the model in the db and the db itself
+    ///
+    /// Lists the number of elements per type, of relations per name and of extended property
+    /// values per property, followed by the size of the db and of each of its indices.
+    Stats {
+        /// Print the statistics as JSON object instead
+        #[arg(short, long, action)]
+        json: bool,
+    },
+
+    /// Print the schema version and fingerprint, the import history and the settings of the db
+    ///
+    /// The import history lists the time, source, project and commit of each import. Imports
+    /// performed by versions of this tool predating the import log are missing.
+    DbInfo {
+        /// Print the information as JSON object instead
+        #[arg(short, long, action)]
+        json: bool,
+    },
+
+    /// Benchmark the import of a JSON dump with different tunings of SQLite
+    ///
+    /// Imports the dump repeatedly into throwaway dbs, for each variant of the pragmas, and prints
+    /// a table comparing the durations of the imports, the sizes of the dbs and the rates of
+    /// lookups by element id per variant. Without `--variant`, the pragmas of the config are
+    /// compared to a few common alternatives. The db is not used.
+    Bench {
+        /// JSON file to import
+        file: PathBuf,
+
+        /// Generate the schema of the throwaway dbs from this JSON schema, instead of using the
+        /// embedded one
+        ///
+        /// Required by variants generating the elements table `WITHOUT ROWID`.
+        #[arg(long, value_name = "FILE")]
+        json_schema: Option<PathBuf>,
+
+        /// Number of imports per variant
+        #[arg(short = 'n', long, default_value_t = 3)]
+        runs: usize,
+
+        /// Variant to compare, as `NAME:PRAGMA=VALUE,...`, e.g. `wal:journal_mode=WAL`
+        ///
+        /// The pragmas override those of the config, see `--pragma`. Additionally, `batch_size=N`
+        /// sets the number of rows inserted per statement, see `import-json --batch-size`, and
+        /// `without_rowid=true` generates the elements table `WITHOUT ROWID`, see
+        /// `json-schema-to-sql-schema --without-rowid`. A variant without pragmas uses those of the
+        /// config, the name may be omitted. The first variant is the baseline of the relative
+        /// durations. Repeat to compare multiple variants.
+        #[arg(long, value_parser = parse_bench_variant)]
+        variant: Vec<BenchVariant>,
+
+        /// Directory to create the throwaway dbs in, defaults to the temporary directory
+        #[arg(long)]
+        dir: Option<PathBuf>,
+    },
+
+    /// Analyze the db and report missing and unused indices
+    ///
+    /// Runs `ANALYZE`, then compares the indices of the db with the query shapes used by this tool
+    /// and common analytical queries. Prints one line per finding, consisting of the kind
+    /// (`missing` or `unused`), the name of the index and the reason. Unused indices are never
+    /// dropped automatically.
+    Optimize {
+        /// Create the missing indices
+        #[arg(short, long, action)]
+        apply: bool,
+    },
+
+    /// Run sanity checks against the model in the db
+    ///
+    /// Checks for elements without owner (except for root packages), relations and extended
+    /// properties referencing missing elements, duplicate qualified names, empty names, cycles in
+    /// the ownership and more.
+    /// Each finding is printed as line with the severity, the check name, the id of the offending
+    /// element and a message, separated by tabs. Fails if at least one finding has the severity
+    /// error.
+    Lint {
+        /// Print the findings as JSON array instead
+        #[arg(short, long, action)]
+        json: bool,
+    },
+
+    /// Run a SQL script against the db and print the rows returned by its statements
+    ///
+    /// The statements are executed in order, each statement returning rows has its result set
+    /// printed in the chosen format. The first failing statement aborts the script with exit code
+    /// 8, the statements before it remain in effect unless the script wraps them into a
+    /// transaction. With --read-only, statements modifying the db fail, and with --project, the
+    /// elements, relations and extended properties tables contain only the rows of that project.
+    Sql {
+        /// File to read the script from, `-` for stdin
+        #[arg(required_unless_present = "execute")]
+        file: Option<PathBuf>,
+
+        /// Run this SQL instead of a script file
+        #[arg(short, long, value_name = "SQL", conflicts_with = "file")]
+        execute: Option<String>,
+
+        /// Bind the named parameter `:NAME` of the statements to VALUE, e.g. `--param limit:int=10`
+        ///
+        /// TYPE is one of text (the default), int and real. Binding values instead of pasting them
+        /// into the SQL spares wrapper scripts from escaping them, and rules out SQL injection.
+        /// Repeat to bind multiple parameters.
+        #[arg(short, long, value_name = "NAME[:TYPE]=VALUE", value_parser = parse_sql_param)]
+        param: Vec<(String, rusqlite::types::Value)>,
+
+        /// Format to print the result sets in
+        #[arg(short, long, value_name = "FORMAT", default_value = "table")]
+        format: ResultFormat,
+    },
+
+    /// Run user-defined validation rules against the model in the db
+    ///
+    /// A rule is a SQL query yielding one row per violation, with the id of the offending element
+    /// (or NULL) in the first and a message in the second column. Rules are read from all `.sql`
+    /// files in a directory, named after the file, with the severity (`error` or `warning`) given
+    /// by a line like `-- severity: warning` (defaulting to `error`). Additionally, rules are read
+    /// from the `validation_rules` table with the columns `name`, `severity` and `query`, if it
+    /// exists in the db.
+    ///
+    /// The output is identical to the one of `lint`. Exits with failure if at least one violation
+    /// with the severity error was found.
+    Validate {
+        /// Directory to read rules from
+        #[arg(short, long)]
+        rules_dir: Option<PathBuf>,
+
+        /// Print the findings as JSON array instead
+        #[arg(short, long, action)]
+        json: bool,
+
+        /// Also exit with failure if a violation with the severity warning was found
+        #[arg(short, long, action)]
+        fail_on_warning: bool,
+    },
+
+    /// Verify that the db faithfully represents the elements of a JSON dump
+    ///
+    /// Compares the columns, relations and extended properties of each element in the dump with
+    /// the db. Discrepancies are reported as errors, properties which the import does not store at
+    /// all as warnings. The output is identical to the one of `lint`. Exits with failure if at
+    /// least one discrepancy was found.
+    Verify {
+        /// JSON file to compare the db with
+        file: PathBuf,
+
+        /// Also report elements, relations and extended properties missing from the dump
+        #[arg(short, long, action)]
+        both_ways: bool,
+
+        /// Print the findings as JSON array instead
+        #[arg(short, long, action)]
+        json: bool,
+    },
+
     /// Parse a JSON schema and generate a suitable SQL schema from it
     ///
     /// This command does not work with arbitrary JSON schemata, but is meant to work with the
@@ -73,6 +901,98 @@ pub(crate) enum Commands {
         // Do not run the generated SQL in DB
         #[arg(short, long, action)]
         no_init: bool,
+
+        #[command(flatten)]
+        schema_args: SchemaArgs,
+    },
+
+    /// Compare two JSON schemata, and the SQL schemata generated from them
+    ///
+    /// Lists added (`+`), removed (`-`) and changed (`~`) definitions and their properties, followed
+    /// by the resulting differences of the columns in the generated SQL schema. This allows to
+    /// assess the changes of a new upstream schema before regenerating the SQL schema.
+    SchemaDiff {
+        /// File to read the old JSON schema from
+        old: PathBuf,
+
+        /// File to read the new JSON schema from
+        new: PathBuf,
+
+        #[command(flatten)]
+        schema_args: SchemaArgs,
+    },
+
+    /// Migrate a db created from an older JSON schema to a newly generated SQL schema
+    ///
+    /// The SQL schema is generated from the given JSON schema, and compared against the tables in
+    /// the db. Columns which were only added are added in place, tables which changed otherwise are
+    /// rebuilt, copying all data from the columns present in both versions. Indexes, triggers,
+    /// views and auxiliary tables are recreated from the new schema.
+    ///
+    /// Make sure to pass the same schema options as when the db was initialized.
+    MigrateSchema {
+        /// File to read the new JSON schema from
+        file: PathBuf,
+
+        /// SQL file to write the migration statements to
+        #[arg(short, long, action)]
+        dump_sql: Option<PathBuf>,
+
+        /// Apply the migration to the db
+        #[arg(short, long, action)]
+        apply: bool,
+
+        #[command(flatten)]
+        schema_args: SchemaArgs,
+    },
+
+    /// Download the JSON schema of the SysML v2 API to a file
+    ///
+    /// By default, the latest `schemas.json` from the official SysML-v2-API-Services repository is
+    /// downloaded. The download is only stored if it parses as JSON schema. Optionally, the SQL
+    /// schema is generated from it and applied to the db right away: an empty db is initialized with
+    /// it, an existing db is migrated to it as with `migrate-schema`.
+    #[cfg(feature = "fetch")]
+    DownloadSchema {
+        /// File to store the JSON schema in
+        file: PathBuf,
+
+        /// URL to download the JSON schema from
+        #[arg(short, long, default_value = UPSTREAM_SCHEMA_URL)]
+        url: String,
+
+        /// Allow downloading via HTTPS from a server without valid certificate
+        #[arg(long)]
+        allow_invalid_certs: bool,
+
+        /// PEM file with CA certificates to trust in addition to the built-in ones
+        #[arg(long, value_name = "PEM_FILE")]
+        ca_cert: Option<PathBuf>,
+
+        /// Generate the SQL schema from the downloaded JSON schema and apply it to the db
+        #[arg(short, long, action)]
+        apply: bool,
+
+        #[command(flatten)]
+        schema_args: SchemaArgs,
+    },
+
+    /// Verify or regenerate the SQL schema embedded into this tool
+    ///
+    /// `init-db` initializes dbs with a SQL schema baked into this tool. This command generates the
+    /// SQL schema from the given JSON schema, and lists the tables, indexes, triggers and views in
+    /// which the embedded schema differs from it, failing if there are any. With -w/--write, the
+    /// generated schema is written to the asset instead, and the tool has to be rebuilt.
+    RegenEmbeddedSchema {
+        /// File to read the JSON schema from
+        file: PathBuf,
+
+        /// Write the generated schema to this asset, usually `assets/schema.sql`
+        #[arg(short, long)]
+        write: Option<PathBuf>,
+
+        #[command(flatten)]
+        schema_args: SchemaArgs,
     },
 
     /// Fetch from the API to a JSON file
@@ -83,17 +1003,27 @@ pub(crate) enum Commands {
     /// HTTP basic auth is supported. Simply set the environment variables SYSML_USERNAME and
     /// SYSML_PASSWORD accordingly. If setting environment variables is complicated, you can also
     /// put them in a .env file.
+    ///
+    /// Credentials not set that way are looked up by the host of the server in the `~/.netrc`
+    /// file, or the file given by the environment variable NETRC. If built with the `keyring`
+    /// feature, a password is looked up in the keyring of the OS as well, stored for the service
+    /// `sysml-v2-sql` and the user `{username}@{host}`.
+    ///
+    /// Servers behind a form login issuing a session cookie are supported as well. Set
+    /// SYSML_LOGIN_URL to the URL the login form posts to, and the credentials as above. The form
+    /// fields default to `username` and `password`, set SYSML_LOGIN_USERNAME_FIELD and
+    /// SYSML_LOGIN_PASSWORD_FIELD to override them. The session cookie is kept for all requests.
+    #[cfg(feature = "fetch")]
     Fetch {
-        // URL to the SysML v2 API server, without trailing `/`
+        /// URL to the SysML v2 API server without trailing `/`, or the name of a server profile
         base_url: String,
 
         /// The project ID
         #[command(subcommand)]
         project: ProjectSelector,
 
-        /// Allow fetching via HTTPS from a server without valid certificate
-        #[arg(short, long)]
-        allow_invalid_certs: bool,
+        #[command(flatten)]
+        http_args: HttpArgs,
 
         /// JSON File to write output to
         #[arg(short, long, action)]
@@ -110,9 +1040,277 @@ pub(crate) enum Commands {
         /// Do not import the fetched data into the DB
         #[arg(short, long, action)]
         no_import: bool,
+
+        #[command(flatten)]
+        import_args: ImportArgs,
+
+        /// Merge derived properties (e.g. `qualifiedName`) the server omits when listing elements
+        ///
+        /// Fetches each element on its own, as servers may compute derived properties only when a
+        /// single element is requested. Takes one request per element.
+        #[arg(long, action)]
+        derived: bool,
+
+        /// Merge the relationships served below `.../elements/{id}/relationships`
+        ///
+        /// For servers omitting relationships or their references (e.g. `source` and `target`)
+        /// when listing elements. Takes at least one request per element.
+        #[arg(long, action)]
+        relationships: bool,
+
+        /// Fetch only the results of the query stored on the server by this id
+        ///
+        /// The import of the results is logged as partial, as for `--query`.
+        #[arg(long, value_name = "QUERY_ID", conflicts_with = "query")]
+        query_id: Option<String>,
+
+        /// Fetch only the results of this query, a JSON object of the SysML v2 Query API
+        ///
+        /// E.g. `{"@type": "Query", "where": {"@type": "PrimitiveConstraint", "property":
+        /// "declaredName", "operator": "=", "value": "Vehicle"}}`. The query is posted to the
+        /// server, which runs it on the selected commit. The import of the results is logged as
+        /// partial, `push` refuses to push from it.
+        #[arg(long, value_name = "JSON", value_parser = parse_json_object)]
+        query: Option<serde_json::Value>,
+    },
+
+    /// Keep the db in sync with a branch on the API server
+    ///
+    /// Periodically looks up the selected commit, usually the head of a branch. Whenever it differs
+    /// from the commit the project was imported from last, the commit is fetched and imported.
+    /// Elements which are absent from the new commit are removed from the db. Runs until
+    /// interrupted, unless `--once` is given. HTTP basic auth is supported as for `fetch`.
+    ///
+    /// With `--two-way`, local changes of the db are pushed to the branch as well, see `push`. If
+    /// an element was changed both in the db and on the server since the last sync, the conflicting
+    /// elements are reported and the sync stops without touching either side.
+    #[cfg(feature = "fetch")]
+    Sync {
+        /// URL to the SysML v2 API server without trailing `/`, or the name of a server profile
+        base_url: String,
+
+        /// The project ID
+        #[command(subcommand)]
+        project: ProjectSelector,
+
+        /// Time between two checks for a new commit, e.g. `90s`, `15m` or `2h`
+        #[arg(short, long, default_value = "15m", value_parser = parse_interval)]
+        interval: Duration,
+
+        /// Check for a new commit once, then exit
+        #[arg(long, action)]
+        once: bool,
+
+        /// Serve a webhook on this address, e.g. `127.0.0.1:8080`
+        ///
+        /// Each `POST` request to the webhook triggers an immediate check for a new commit, so CI
+        /// or the modeling tool can notify about new commits instead of waiting for the next check.
+        /// If the environment variable SYSML_WEBHOOK_TOKEN is set, requests must carry it as
+        /// `Authorization: Bearer <token>`. Without it, only loopback addresses are served.
+        #[arg(short, long, value_name = "ADDR")]
+        listen: Option<SocketAddr>,
+
+        /// Push local changes to the branch as well
+        ///
+        /// The branch must be selected by its id or as default branch. As for `push`, the project
+        /// must not have been fetched partially.
+        #[arg(long, action)]
+        two_way: bool,
+
+        #[command(flatten)]
+        http_args: HttpArgs,
+
+        /// Page size to request from SysML v2 API server
+        #[arg(short, long)]
+        page_size: Option<u32>,
+
+        /// Do not verify that the db matches the schema it was initialized with, see `import-json`
+        #[arg(long, action)]
+        skip_schema_check: bool,
+    },
+
+    /// Push the local changes of a project to the API as a new commit
+    ///
+    /// The changes are determined relative to the commit the project was last fetched from, by
+    /// fetching that commit again and comparing it with the db. Only properties the db represents
+    /// are compared, all other properties of changed elements are sent as found in that commit. The
+    /// order of relation targets is not recorded in the db, hence only the addition and removal of
+    /// targets counts as change. A project which was never fetched, such as one just created by
+    /// `create-project`, receives all elements of the db as its first commit. Prints the id of the
+    /// new commit. HTTP basic auth is supported as for `fetch`.
+    ///
+    /// Pushing is refused if the project was last fetched partially, e.g. with `--package` or
+    /// `--transform`, as the elements and values left out or rewritten would count as changes.
+    #[cfg(feature = "fetch")]
+    Push {
+        /// URL to the SysML v2 API server without trailing `/`, or the name of a server profile
+        base_url: String,
+
+        /// The project ID
+        project_id: String,
+
+        /// Commit to the branch of this id instead of the default branch
+        #[arg(short, long)]
+        branch_id: Option<String>,
+
+        /// Description of the new commit
+        #[arg(short, long)]
+        message: Option<String>,
+
+        /// Print the commit instead of pushing it
+        #[arg(short = 'n', long, action)]
+        dry_run: bool,
+
+        #[command(flatten)]
+        http_args: HttpArgs,
+
+        /// Page size to request from SysML v2 API server
+        #[arg(short, long)]
+        page_size: Option<u32>,
+    },
+
+    /// Create a new project on the API server
+    ///
+    /// Prints the id of the new project. Its default branch has no commits, the first one can be
+    /// created with `push`. HTTP basic auth is supported as for `fetch`.
+    #[cfg(feature = "fetch")]
+    CreateProject {
+        /// URL to the SysML v2 API server without trailing `/`, or the name of a server profile
+        base_url: String,
+
+        /// Name of the new project
+        name: String,
+
+        /// Description of the new project
+        #[arg(short, long)]
+        description: Option<String>,
+
+        #[command(flatten)]
+        http_args: HttpArgs,
+    },
+
+    /// Create a new branch in a project on the API server
+    ///
+    /// The branch starts at the head of the default branch, unless another commit is given. Prints
+    /// the id of the new branch, which can be pushed to via `push --branch-id`. HTTP basic auth is
+    /// supported as for `fetch`.
+    #[cfg(feature = "fetch")]
+    CreateBranch {
+        /// URL to the SysML v2 API server without trailing `/`, or the name of a server profile
+        base_url: String,
+
+        /// The project ID
+        project_id: String,
+
+        /// Name of the new branch
+        name: String,
+
+        /// Let the branch start at the commit of this id
+        #[arg(short, long)]
+        commit_id: Option<String>,
+
+        #[command(flatten)]
+        http_args: HttpArgs,
+    },
+
+    /// Mirror a project from the API, with its branches and commits
+    ///
+    /// Fetches the head commit of each branch of the project, or with `--all-commits` every commit
+    /// in the history of the branches, and stores it in the db. The db must store multiple projects
+    /// (see `--multi-project`), each commit is stored under its id in place of a project id. Thus a
+    /// commit can be selected via `--project <COMMIT_ID>`. The branches and their head commits are
+    /// recorded in the `branches` table.
+    ///
+    /// Commits already stored in the db are skipped, so cloning the same project again only fetches
+    /// the new commits. HTTP basic auth is supported as for `fetch`.
+    #[cfg(feature = "fetch")]
+    Clone {
+        /// URL to the SysML v2 API server without trailing `/`, or the name of a server profile
+        base_url: String,
+
+        /// The project ID
+        project_id: String,
+
+        /// Only clone the branch of this name, may be given multiple times
+        #[arg(short, long = "branch", value_name = "NAME")]
+        branches: Vec<String>,
+
+        /// Clone the complete history of the branches, not only their head commits
+        #[arg(long, action)]
+        all_commits: bool,
+
+        #[command(flatten)]
+        http_args: HttpArgs,
+
+        /// Page size to request from SysML v2 API server
+        #[arg(short, long)]
+        page_size: Option<u32>,
+
+        /// Do not verify that the db matches the schema it was initialized with, see `import-json`
+        #[arg(long, action)]
+        skip_schema_check: bool,
     },
 }
 
+impl Commands {
+    /// Whether the command leaves the db untouched, and thus may run on a read-only db
+    pub(crate) fn is_read_only(&self) -> bool {
+        match self {
+            Commands::Backup { .. }
+            | Commands::Search { .. }
+            | Commands::Tree { .. }
+            | Commands::Show { .. }
+            | Commands::Trace { .. }
+            | Commands::TraceMatrix { .. }
+            | Commands::Coverage { .. }
+            | Commands::Path { .. }
+            | Commands::Impact { .. }
+            | Commands::Bom { .. }
+            | Commands::ExportGraph { .. }
+            | Commands::ExportParquet { .. }
+            | Commands::ExportAnonymized { .. }
+            | Commands::ExportMetadata { .. }
+            | Commands::ExportPlantuml { .. }
+            | Commands::Report { .. }
+            | Commands::DbDiff { .. }
+            | Commands::JsonDiff { .. }
+            | Commands::Bench { .. }
+            | Commands::Stats { .. }
+            | Commands::DbInfo { .. }
+            | Commands::Lint { .. }
+            | Commands::Validate { .. }
+            | Commands::Verify { .. }
+            | Commands::SchemaDiff { .. }
+            | Commands::RegenEmbeddedSchema { .. } => true,
+            // the db itself rejects writing statements if opened read-only, and with a selected
+            // project they see only its rows
+            Commands::Sql { .. } => true,
+            #[cfg(feature = "fetch")]
+            Commands::CreateProject { .. } | Commands::CreateBranch { .. } => true,
+            Commands::JsonSchemaToSqlSchema { no_init, .. } => *no_init,
+            Commands::MigrateSchema { apply, .. } => !*apply,
+            #[cfg(feature = "fetch")]
+            Commands::DownloadSchema { apply, .. } => !*apply,
+            #[cfg(feature = "fetch")]
+            Commands::Fetch { no_import, .. } => *no_import,
+            #[cfg(feature = "fetch")]
+            Commands::Push { dry_run, .. } => *dry_run,
+            #[cfg(feature = "fetch")]
+            Commands::Sync { .. } | Commands::Clone { .. } => false,
+            Commands::ImportJson { .. }
+            | Commands::SetAttribute { .. }
+            | Commands::DeleteElement { .. }
+            | Commands::AddRelation { .. }
+            | Commands::RemoveRelation { .. }
+            | Commands::InitDb
+            | Commands::UpgradeDb
+            | Commands::Optimize { .. } => false,
+        }
+    }
+}
+
+/// Selects the project to work on
+#[cfg(feature = "fetch")]
 #[derive(Subcommand)]
 pub enum ProjectSelector {
     /// Select an identified project
@@ -120,6 +1318,7 @@ pub enum ProjectSelector {
         /// The project ID
         project_id: String,
 
+        /// The commit of the project
         #[command(subcommand)]
         commit: CommitSelector,
     },
@@ -128,22 +1327,476 @@ pub enum ProjectSelector {
         /// The project name
         project_name: String,
 
+        /// The commit of the project
         #[command(subcommand)]
         commit: CommitSelector,
     },
 }
 
+/// Selects the commit of a project to work on
+#[cfg(feature = "fetch")]
 #[derive(Subcommand)]
 pub enum CommitSelector {
     /// Select an identified commit
-    CommitId { commit_id: String },
+    CommitId {
+        /// The commit ID
+        commit_id: String,
+    },
 
     /// Select the latest commit from an identified branch
-    BranchId { branch_id: String },
+    BranchId {
+        /// The branch ID
+        branch_id: String,
+    },
 
     /// Select the latest commit from a named branch
-    BranchName { branch_name: String },
+    BranchName {
+        /// The branch name
+        branch_name: String,
+    },
 
     /// Select the latest commit from the default branch
     DefaultBranch,
 }
+
+/// Formats the element/relation graph can be exported in
+#[derive(Clone, Copy, Debug, ValueEnum)]
+pub enum GraphFormat {
+    /// GraphML, as read by e.g. yEd and Gephi
+    Graphml,
+
+    /// DOT, as rendered by Graphviz
+    Dot,
+
+    /// Cypher script creating the graph in Neo4j
+    Cypher,
+
+    /// RDF in the Turtle syntax, as loaded by triple stores
+    Turtle,
+}
+
+/// Formats the result sets of SQL scripts can be printed in
+#[derive(Clone, Copy, Debug, ValueEnum)]
+pub enum ResultFormat {
+    /// Columns aligned for reading, NULL printed as such
+    Table,
+
+    /// Tab separated values, with tabs and line breaks inside of values escaped
+    Tsv,
+
+    /// Comma separated values
+    Csv,
+
+    /// One JSON array of objects per result set
+    Json,
+}
+
+/// Formats of the built-in report templates
+#[derive(Clone, Copy, Debug, ValueEnum)]
+pub enum ReportFormat {
+    /// Markdown
+    Markdown,
+
+    /// Standalone HTML page
+    Html,
+}
+
+/// Formats of the log messages, see `--log-format`
+#[derive(Clone, Copy, Debug, ValueEnum)]
+pub enum LogFormat {
+    /// Human readable lines
+    Text,
+
+    /// One JSON object per line
+    Json,
+}
+
+/// Journal modes of the db, see `--journal-mode`
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub enum JournalMode {
+    /// Delete the rollback journal at the end of each transaction, the default of SQLite
+    Delete,
+
+    /// Truncate the rollback journal instead of deleting it
+    Truncate,
+
+    /// Keep the rollback journal, overwriting its header instead
+    Persist,
+
+    /// Write-ahead logging, allowing readers concurrent to a writer
+    Wal,
+}
+
+impl JournalMode {
+    /// The value of the journal_mode pragma for this mode
+    pub fn pragma_value(self) -> &'static str {
+        match self {
+            JournalMode::Delete => "DELETE",
+            JournalMode::Truncate => "TRUNCATE",
+            JournalMode::Persist => "PERSIST",
+            JournalMode::Wal => "WAL",
+        }
+    }
+}
+
+/// Versions of the SysML v2 API, see `--api-version`
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum, serde::Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ApiVersion {
+    /// The pilot implementation, which predates the standardized API
+    Pilot,
+
+    /// The standardized SysML v2 API and Services, version 1.0 or later
+    V1,
+}
+
+/// How attributes are redacted during the import, see `--redact`
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum, serde::Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum RedactMode {
+    /// Replace strings by the empty string
+    Blank,
+
+    /// Replace strings by the hex-encoded SHA-256 hash of their value, prefixed by `sha256:`
+    Hash,
+}
+
+/// Options of the import of elements into the db
+#[derive(Args)]
+pub(crate) struct ImportArgs {
+    /// Maintain integer surrogate keys for elements and relations
+    ///
+    /// Assigns each element a stable integer key (stored in the `element_keys` table) and
+    /// mirrors the relations table using these keys (into the `keyed_relations` table). Joins
+    /// over integers are significantly faster than over the TEXT ids, which pays off for
+    /// analytical queries on huge models.
+    #[arg(long, action)]
+    pub integer_keys: bool,
+
+    /// Maintain the transitive closure of the relations of this name, e.g. `ownedElement`
+    ///
+    /// Stores each pair of elements connected by a chain of such relations in the
+    /// `relation_closure` table, so that e.g. all elements owned by an element, directly or
+    /// indirectly, can be looked up instead of queried recursively. Once added, the closure is
+    /// kept up to date by all later imports and edits. Repeat to maintain multiple closures.
+    #[arg(long, value_name = "RELATION")]
+    pub closure: Vec<String>,
+
+    /// Maintain the owner of each element in the indexed `owner_id` column of the elements table
+    ///
+    /// The owner is taken from the `owner` relation, or for relationships from the
+    /// `owningRelatedElement` relation. Looking up the children of an element via this column
+    /// is significantly faster than joining the relations table, which pays off for queries
+    /// over the ownership tree. Once added, the column is kept up to date by all later imports
+    /// and edits.
+    #[arg(long, action)]
+    pub owner_column: bool,
+
+    /// Maintain the ownership path and depth of each element in the `element_paths` table
+    ///
+    /// The path of an element consists of the ids of its owners, from the root of the ownership
+    /// tree down to the element itself, separated by `/`. Hence all elements below an element
+    /// can be found by a prefix query over the indexed paths instead of a recursive query. Once
+    /// added, the table is rebuilt by all later imports and edits.
+    #[arg(long, action)]
+    pub element_paths: bool,
+
+    /// Do not verify that the db matches the schema it was initialized with
+    ///
+    /// By default, the import is refused if the tables of the db were altered since the db was
+    /// initialized, or if the db was initialized with another schema than the one embedded into
+    /// this version of the tool.
+    #[arg(long, action)]
+    pub skip_schema_check: bool,
+
+    /// Refuse the import if attributes of the elements are not understood
+    ///
+    /// Attributes are not understood if there is neither a column for them, nor do they refer
+    /// to other elements. By default, these attributes are ignored with a warning.
+    #[arg(long, action)]
+    pub strict: bool,
+
+    /// Rewrite or drop each element by the Lua script SCRIPT before it is imported
+    ///
+    /// The script defines a global function `transform`, which is called with each element as
+    /// table and returns the element, changed in place or newly built, or `nil` to drop it.
+    /// E.g. `function transform(e) e.declaredName = e.declaredName or e.name; return e end`.
+    /// The function is called twice per element and must yield the same result each time.
+    /// The import is logged as partial, `push` refuses to push from it. Requires the `lua`
+    /// feature, which is enabled by default.
+    #[arg(long, value_name = "SCRIPT")]
+    pub transform: Option<PathBuf>,
+
+    /// Redact the attributes ATTRIBUTE of all elements, so that the db can be shared
+    ///
+    /// Given as `ATTRIBUTE[:MODE]`, separated by commas, e.g. `body,declaredName:hash`. The
+    /// mode `blank`, the default, replaces strings by the empty string, `hash` replaces them by
+    /// their SHA-256 hash, which keeps equal values equal. Numbers and booleans are set to
+    /// null, references to other elements are kept. Adds to the rules of the `[redact]`
+    /// section of the config file, and applies after `--transform`. The import is logged as
+    /// partial, `push` refuses to push from it.
+    #[arg(
+        long,
+        value_name = "ATTRIBUTE[:MODE]",
+        value_delimiter = ',',
+        value_parser = parse_redaction
+    )]
+    pub redact: Vec<(String, RedactMode)>,
+
+    /// Replace the ids of the elements by pseudonyms, so that the db can be shared
+    ///
+    /// The pseudonyms are derived from the ids by a hash keyed by the environment variable
+    /// SYSML_PSEUDONYM_KEY, which must be set. The references to other elements and the
+    /// attributes holding ids, e.g. `memberElementId`, are pseudonymized alike, so that the
+    /// relations still join. The same key yields the same pseudonyms on each import, keep it
+    /// secret to keep the ids from being confirmed by guessing. Applies after `--transform`
+    /// and `--redact`. The import is logged as partial, `push` refuses to push from it.
+    #[arg(long, action)]
+    pub pseudonymize_ids: bool,
+
+    /// Only import the elements within the namespace PACKAGE, e.g. `Vehicle::Powertrain`
+    ///
+    /// Elements are within the namespace if their qualified name starts with PACKAGE, or if
+    /// they are owned by such an element, directly or indirectly. The elements outside of the
+    /// namespace which are referred to from within are imported as stubs, carrying only their
+    /// id, type and names. Builds small, focused dbs from huge dumps, at the cost of reading
+    /// the file twice more. Applies before `--transform`, to the original qualified names. The
+    /// import is logged as partial, `push` refuses to push from it.
+    #[arg(long, value_name = "PACKAGE")]
+    pub package: Option<String>,
+
+    /// Import only these attributes of the elements, separated by commas
+    ///
+    /// E.g. `--only-attributes declaredName,owner,ownedRelationship` imports the names and the
+    /// ownership structure only, which shrinks the db and speeds up the import. The attributes
+    /// are given by their name in the JSON, `@id` and `@type` are always imported. The other
+    /// attributes are neither stored nor reported as not understood. Applies after
+    /// `--transform`. The import is logged as partial, `push` refuses to push from it.
+    #[arg(
+        long,
+        value_name = "ATTRIBUTE",
+        value_delimiter = ',',
+        conflicts_with = "skip_attributes"
+    )]
+    pub only_attributes: Vec<String>,
+
+    /// Import all but these attributes of the elements, separated by commas
+    ///
+    /// E.g. `--skip-attributes body,documentation` leaves out the texts of the documentation.
+    /// The attributes are given by their name in the JSON. Applies after `--transform`. The
+    /// import is logged as partial, `push` refuses to push from it.
+    #[arg(long, value_name = "ATTRIBUTE", value_delimiter = ',')]
+    pub skip_attributes: Vec<String>,
+
+    /// Scan all elements instead of aborting on the first error, reporting all errors at the end
+    ///
+    /// Errors such as values of the wrong type, relations to missing elements and violated
+    /// constraints are collected, and reported together once all elements were scanned. If
+    /// there are any, the import is not committed. A syntax error in the JSON file still ends
+    /// the scan, as the file can not be parsed any further.
+    #[arg(long, action)]
+    pub collect_errors: bool,
+
+    /// Skip the elements which fail to import instead of aborting, and commit the others
+    ///
+    /// The skipped elements are written together with their errors to the file given by
+    /// --skipped-file, by default next to the imported file with the suffix `.skipped.json`. When
+    /// fetching, this is the JSON dump if written, or else the db. Elements referring to skipped
+    /// elements are skipped as well. A syntax error in the JSON file still aborts the import.
+    #[arg(long, action, conflicts_with = "collect_errors")]
+    pub continue_on_error: bool,
+
+    /// File to write the skipped elements to, see --continue-on-error
+    #[arg(long, value_name = "FILE", requires = "continue_on_error")]
+    pub skipped_file: Option<PathBuf>,
+
+    /// Maximum number of rows inserted per statement
+    ///
+    /// Larger batches save overhead per statement, at the cost of some memory. With
+    /// --collect-errors or --continue-on-error, each row is inserted on its own.
+    #[arg(long, default_value_t = crate::import::DEFAULT_BATCH_SIZE, value_parser = clap::builder::RangedU64ValueParser::<usize>::new().range(1..))]
+    pub batch_size: usize,
+}
+
+impl ImportArgs {
+    /// Converts the CLI arguments into the options understood by the importer
+    ///
+    /// Unless given, the skipped elements are written next to `imported_file`.
+    pub(crate) fn to_options(&self, imported_file: &Path) -> eyre::Result<ImportOptions> {
+        let maybe_skipped_file = self.continue_on_error.then(|| {
+            self.skipped_file.clone().unwrap_or_else(|| {
+                let mut path = imported_file.as_os_str().to_owned();
+                path.push(".skipped.json");
+                path.into()
+            })
+        });
+        Ok(ImportOptions {
+            integer_keys: self.integer_keys,
+            closure_relations: self.closure.clone(),
+            owner_column: self.owner_column,
+            element_paths: self.element_paths,
+            skip_schema_check: self.skip_schema_check,
+            strict: self.strict,
+            maybe_transform: crate::transform::cli_transform(
+                self.transform.as_deref(),
+                &self.redact,
+                self.pseudonymize_ids,
+            )?,
+            maybe_package: self.package.clone(),
+            attribute_filter: AttributeFilter::new(
+                self.only_attributes.clone(),
+                self.skip_attributes.clone(),
+            ),
+            collect_errors: self.collect_errors,
+            maybe_skipped_file,
+            maybe_batch_size: Some(self.batch_size),
+            ..Default::default()
+        })
+    }
+}
+
+/// Options of the HTTP client talking to the API server
+#[cfg(feature = "fetch")]
+#[derive(Args)]
+pub(crate) struct HttpArgs {
+    /// Allow fetching via HTTPS from a server without valid certificate
+    #[arg(short, long)]
+    pub allow_invalid_certs: bool,
+
+    /// PEM file with CA certificates to trust in addition to the built-in ones
+    ///
+    /// Allows to connect to a server with a certificate issued by a private CA, e.g. a corporate
+    /// one, while keeping the certificates validated. The file may contain multiple certificates.
+    #[arg(long, value_name = "PEM_FILE")]
+    pub ca_cert: Option<PathBuf>,
+
+    /// Maximum number of idle connections kept open to the server
+    #[arg(long, value_name = "N", help_heading = "Connection tuning")]
+    pub pool_size: Option<usize>,
+
+    /// Use HTTP/2 with servers offering it, instead of always using HTTP/1.1
+    ///
+    /// HTTP/2 is negotiated during the TLS handshake, thus only used with HTTPS servers.
+    #[arg(long, action, help_heading = "Connection tuning")]
+    pub http2: bool,
+
+    /// Interval of TCP keep-alive probes on idle connections, e.g. `30s`
+    ///
+    /// Keeps connections through VPNs and firewalls which drop them when idle.
+    #[arg(long, value_name = "INTERVAL", value_parser = parse_interval, help_heading = "Connection tuning")]
+    pub keep_alive: Option<Duration>,
+
+    /// Time after which idle connections are closed, e.g. `90s`
+    #[arg(long, value_name = "TIMEOUT", value_parser = parse_interval, help_heading = "Connection tuning")]
+    pub idle_timeout: Option<Duration>,
+
+    /// Time to wait for a connection to the server to be established, e.g. `10s`
+    #[arg(long, value_name = "TIMEOUT", value_parser = parse_interval, help_heading = "Connection tuning")]
+    pub connect_timeout: Option<Duration>,
+
+    /// Time to wait for each request to complete, including its response body, e.g. `5m`
+    #[arg(long, value_name = "TIMEOUT", value_parser = parse_interval, help_heading = "Connection tuning")]
+    pub timeout: Option<Duration>,
+}
+
+#[cfg(feature = "fetch")]
+impl HttpArgs {
+    /// Converts the CLI arguments into the options understood by the HTTP client
+    pub(crate) fn to_options(&self) -> HttpOptions {
+        HttpOptions {
+            allow_invalid_certs: self.allow_invalid_certs,
+            maybe_ca_cert: self.ca_cert.clone(),
+            maybe_pool_size: self.pool_size,
+            http2: self.http2,
+            maybe_keep_alive: self.keep_alive,
+            maybe_idle_timeout: self.idle_timeout,
+            maybe_connect_timeout: self.connect_timeout,
+            maybe_timeout: self.timeout,
+        }
+    }
+}
+
+/// Options affecting the SQL schema generated from a JSON schema
+#[derive(Args)]
+pub(crate) struct SchemaArgs {
+    /// Do not generate triggers enforcing required properties to be non-null
+    ///
+    /// By default, for each element type, triggers are generated which reject elements of that
+    /// type if they lack a value for a property the JSON schema declares as required. Without
+    /// these triggers, imports are lenient towards incomplete data.
+    #[arg(long, action)]
+    pub no_not_null_triggers: bool,
+
+    /// Store the variants of string enums in lookup tables instead of CHECK constraints
+    ///
+    /// For each column holding a string enum, a table named after the column with the prefix
+    /// `enum_` is generated, containing one row per legal variant. The column references this
+    /// table via a foreign key, so the variants can be queried and joined.
+    #[arg(long, action)]
+    pub enum_tables: bool,
+
+    /// Generate ordinary tables instead of STRICT tables, for SQLite versions before 3.37
+    ///
+    /// The types of the columns are then only enforced by CHECK constraints on their storage
+    /// class, while SQLite applies its usual type affinities.
+    #[arg(long, action)]
+    pub no_strict: bool,
+
+    /// Generate the elements table as WITHOUT ROWID table, clustered by the element id
+    ///
+    /// This saves a lookup per access by id, while bulk inserts in random id order become slower
+    /// and the wide rows of the elements take more space than with the separate index of the ids.
+    /// As the full-text index refers to the elements by their rowid, it is omitted with this
+    /// option. Compare both with `bench --json-schema` and the variant `without_rowid=true`.
+    #[arg(long, action)]
+    pub without_rowid: bool,
+
+    /// Add a project_id column to the elements, relations and extended properties tables
+    ///
+    /// This allows to import multiple projects into one db, each identified by its project id. An
+    /// element id then only is unique within its project, thus element ids are not required to be
+    /// unique across the db.
+    #[arg(long, action)]
+    pub multi_project: bool,
+
+    /// Store each element as raw JSON, with generated columns for the hot fields only
+    ///
+    /// The elements table then holds the JSON of each element in the `@json` column, from which
+    /// SQLite extracts the hot fields into generated columns, which are indexed as usual. This
+    /// keeps the schema thin, while all other properties remain accessible via `json_extract`, e.g.
+    /// `json_extract("@json", '$.isAbstract')`. Relations and extended properties are stored in
+    /// their tables as usual. As no property is lost, `--strict` imports accept all properties.
+    #[arg(long, action)]
+    pub raw_json: bool,
+
+    /// Property to extract into a generated column with --raw-json, e.g. `declaredName`
+    ///
+    /// Defaults to the properties covered by the indices and the full-text index, the type and
+    /// names of the elements, `isLibraryElement` and `body`. The type is always extracted. The
+    /// full-text index is omitted unless `declaredName`, `qualifiedName` and `body` are extracted.
+    /// Repeat to extract multiple properties.
+    #[arg(long, value_name = "PROPERTY", requires = "raw_json")]
+    pub hot_field: Vec<String>,
+}
+
+impl SchemaArgs {
+    /// Converts the CLI arguments into the options understood by the schema generator
+    pub(crate) fn to_options(&self) -> SchemaOptions {
+        SchemaOptions {
+            not_null_triggers: !self.no_not_null_triggers,
+            enum_lookup_tables: self.enum_tables,
+            strict_tables: !self.no_strict,
+            without_rowid_elements: self.without_rowid,
+            multi_project: self.multi_project,
+            maybe_hot_fields: self.raw_json.then(|| {
+                let mut hot_fields: BTreeSet<String> = if self.hot_field.is_empty() {
+                    DEFAULT_HOT_FIELDS.map(str::to_owned).into()
+                } else {
+                    self.hot_field.iter().cloned().collect()
+                };
+                hot_fields.insert(TYPE_COL.to_owned());
+                hot_fields
+            }),
+        }
+    }
+}
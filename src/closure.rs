@@ -0,0 +1,161 @@
+//! Materialized transitive closure of selected relations, e.g. of the ownership
+//!
+//! The closure of a relation holds a row for each pair of elements connected by a chain of
+//! relations of that name, so that e.g. all elements owned by an element, directly or indirectly,
+//! are a lookup instead of a recursive query. The names of the relations the closure is maintained
+//! for are stored in the db. Each import and edit keeps their closure up to date.
+
+use eyre::{Result, bail};
+use rusqlite::Connection;
+
+use crate::{
+    config::{CLOSURE_RELATIONS_TABLE, CLOSURE_TABLE, PROJECT_COL, config},
+    import::get_table_columns,
+    util::{escape_sql_ident, table_exists},
+};
+
+/// Starts maintaining the closure of the relations of the given names, computing it from scratch
+///
+/// Names whose closure is maintained already are skipped. Dbs storing multiple projects are not
+/// supported.
+pub(crate) fn add_closure_relations(conn: &Connection, names: &[String]) -> Result<()> {
+    if names.is_empty() {
+        return Ok(());
+    }
+    let is_multi_project = get_table_columns(conn, &config().elements_table)?
+        .iter()
+        .any(|(n, _)| n == PROJECT_COL);
+    if is_multi_project {
+        bail!("the closure of relations is not supported for dbs storing multiple projects");
+    }
+
+    let closure_relations_table = escape_sql_ident(CLOSURE_RELATIONS_TABLE);
+    let closure_table = escape_sql_ident(CLOSURE_TABLE);
+    // the pairs refer to the elements without foreign keys, as they are only consistent again once
+    // updated after a change
+    conn.execute_batch(&format!(
+        r#"
+        CREATE TABLE IF NOT EXISTS {closure_relations_table} (
+            "name" TEXT PRIMARY KEY
+        ) STRICT;
+
+        CREATE TABLE IF NOT EXISTS {closure_table} (
+            "name" TEXT NOT NULL REFERENCES {closure_relations_table}("name"),
+            "origin_id" TEXT NOT NULL,
+            "target_id" TEXT NOT NULL,
+            PRIMARY KEY("name", "origin_id", "target_id")
+        ) STRICT, WITHOUT ROWID;
+
+        CREATE INDEX IF NOT EXISTS "{CLOSURE_TABLE}.target_id"
+            ON {closure_table}("name", "target_id");
+        "#
+    ))?;
+
+    for name in names {
+        let added = conn.execute(
+            &format!(r#"INSERT OR IGNORE INTO {closure_relations_table}("name") VALUES (?1)"#),
+            [name],
+        )?;
+        if added > 0 {
+            info!("maintaining the closure of the relation {name:?} from now on");
+            update_closure(conn, name, None)?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Updates the closure of all maintained relations, if any, after the relations changed
+///
+/// `maybe_changed_elements` is a query selecting the ids of all elements whose relations were
+/// inserted, replaced or deleted, or which were deleted themselves. Only the closure of these and
+/// of the elements they can be reached from is recomputed. Without the query, the closure is
+/// recomputed from scratch.
+pub(crate) fn update_closures(
+    conn: &Connection,
+    maybe_changed_elements: Option<&str>,
+) -> Result<()> {
+    if !table_exists(conn, CLOSURE_RELATIONS_TABLE)? {
+        return Ok(());
+    }
+
+    let names = conn
+        .prepare(&format!(
+            r#"SELECT "name" FROM {}"#,
+            escape_sql_ident(CLOSURE_RELATIONS_TABLE)
+        ))?
+        .query_map([], |row| row.get(0))?
+        .collect::<Result<Vec<String>, _>>()?;
+    for name in names {
+        update_closure(conn, &name, maybe_changed_elements)?;
+    }
+
+    Ok(())
+}
+
+/// Recomputes the closure of a relation, for the changed elements only if given
+fn update_closure(
+    conn: &Connection,
+    name: &str,
+    maybe_changed_elements: Option<&str>,
+) -> Result<()> {
+    let now = std::time::Instant::now();
+    let closure_table = escape_sql_ident(CLOSURE_TABLE);
+    let relations_table = escape_sql_ident(&config().relations_table);
+
+    let origin_condition = match maybe_changed_elements {
+        Some(changed_elements) => {
+            // a chain of relations reaching a changed element in the changed relations also did so
+            // before, as only the relations originating from changed elements changed. Hence the
+            // elements whose closure may change are the changed ones and those they were reachable
+            // from.
+            conn.execute_batch(&format!(
+                r#"
+                CREATE TEMPORARY TABLE "closure_origins"("id" TEXT PRIMARY KEY);
+                INSERT OR IGNORE INTO "closure_origins" {changed_elements};
+                "#
+            ))?;
+            conn.execute(
+                &format!(
+                    r#"INSERT OR IGNORE INTO "closure_origins"
+                    SELECT "origin_id" FROM {closure_table}
+                    WHERE "name" = ?1 AND "target_id" IN (SELECT "id" FROM "closure_origins")"#
+                ),
+                [name],
+            )?;
+            r#" AND "origin_id" IN (SELECT "id" FROM "closure_origins")"#
+        }
+        None => "",
+    };
+
+    debug!("updating the closure of the relation {name:?}");
+    let removed = conn.execute(
+        &format!(r#"DELETE FROM {closure_table} WHERE "name" = ?1{origin_condition}"#),
+        [name],
+    )?;
+    // the union stops at pairs found already, thus terminates for cyclic relations
+    let inserted = conn.execute(
+        &format!(
+            r#"WITH RECURSIVE "reachable"("origin_id", "target_id") AS (
+                SELECT "origin_id", "target_id" FROM {relations_table}
+                WHERE "name" = ?1{origin_condition}
+                UNION
+                SELECT re."origin_id", r."target_id" FROM "reachable" AS re
+                JOIN {relations_table} AS r ON r."origin_id" = re."target_id"
+                WHERE r."name" = ?1
+            )
+            INSERT INTO {closure_table}("name", "origin_id", "target_id")
+            SELECT ?1, "origin_id", "target_id" FROM "reachable""#
+        ),
+        [name],
+    )?;
+    if maybe_changed_elements.is_some() {
+        conn.execute_batch(r#"DROP TABLE "closure_origins""#)?;
+    }
+    debug!(
+        "replaced {removed} pairs of the closure of {name:?} by {inserted} pairs, which took {:?}",
+        now.elapsed()
+    );
+
+    Ok(())
+}
@@ -1,18 +1,349 @@
-// Name of the table which contains all the elements
-pub(crate) static ELEMENTS_TABLE: &str = "elements";
+//! Configuration of this tool
+//!
+//! Most names are compile-time constants. Those which users may need to adapt to an evolution of
+//! the SysML v2 schema can be overridden by an optional TOML config file, see [`Config`].
 
-// Name of the table which contains all element to element relations
-pub(crate) static RELATIONS_TABLE: &str = "relations";
+use std::{
+    collections::BTreeMap,
+    path::{Path, PathBuf},
+    sync::OnceLock,
+    time::Duration,
+};
 
-// Name of the table which contains other 1:n properties for an element
-pub(crate) static EXTENDED_TABLE: &str = "extended_properties";
+use eyre::{Result, WrapErr, bail};
+
+use crate::cli::{ApiVersion, RedactMode};
+
+/// Knobs which can be set via the TOML config file
+///
+/// All fields are optional in the config file, missing fields take their default value. An example
+/// config file, containing the defaults:
+///
+/// ```toml
+/// elements-table = "elements"
+/// relations-table = "relations"
+/// extended-table = "extended_properties"
+/// polymorphic-props = ["value"]
+/// status-report-interval-secs = 5
+/// ```
+///
+/// Additionally, the config file may limit the memory used, e.g. `max-memory = 2147483648` for
+/// 2 GiB, tune SQLite, see [`Pragmas`], define API servers as named profiles, see
+/// [`ServerProfile`], and redact attributes on each import, see [`Config::redact`].
+#[derive(Debug, Clone, serde::Deserialize)]
+#[serde(default, rename_all = "kebab-case", deny_unknown_fields)]
+pub(crate) struct Config {
+    /// Name of the table which contains all the elements
+    pub(crate) elements_table: String,
+
+    /// Name of the table which contains all element to element relations
+    pub(crate) relations_table: String,
+
+    /// Name of the table which contains other 1:n properties for an element
+    pub(crate) extended_table: String,
+
+    /// Name of known polymorphic properties
+    pub(crate) polymorphic_props: Vec<String>,
+
+    /// Minimum time interval inbetween status reports, in seconds
+    pub(crate) status_report_interval_secs: u64,
+
+    /// Soft limit of the memory used, in bytes, as `--max-memory` sets it
+    pub(crate) max_memory: Option<u64>,
+
+    /// Settings tuning SQLite to the disks and RAM at hand
+    pub(crate) pragmas: Pragmas,
+
+    /// API servers by the name of their profile
+    pub(crate) profiles: BTreeMap<String, ServerProfile>,
+
+    /// Attributes to redact on each import, by their name, see `--redact`
+    ///
+    /// ```toml
+    /// [redact]
+    /// body = "hash"
+    /// declaredName = "blank"
+    /// ```
+    pub(crate) redact: BTreeMap<String, RedactMode>,
+}
+
+/// Pragmas tuning the performance of SQLite, which can also be set via `--pragma NAME=VALUE`
+///
+/// The pragmas without default are left at the defaults of SQLite. An example section, containing
+/// the defaults and examples for the others:
+///
+/// ```toml
+/// [pragmas]
+/// cache-size = 134217728
+/// page-size = 4096
+/// synchronous = "OFF"
+/// mmap-size = 1073741824
+/// journal-mode = "WAL"
+/// threads = 4
+/// ```
+#[derive(Debug, Clone, serde::Deserialize)]
+#[serde(default, rename_all = "kebab-case", deny_unknown_fields)]
+pub(crate) struct Pragmas {
+    /// Size of the page cache during bulk inserts, in pages or, if negative, in KiB
+    pub(crate) cache_size: i64,
+
+    /// Size of the pages of new dbs, and of existing dbs once vacuumed, in bytes
+    pub(crate) page_size: u32,
+
+    /// How often SQLite waits for the data to reach the disk during bulk inserts, e.g. `OFF`
+    pub(crate) synchronous: String,
+
+    /// Maximum number of bytes of the db accessed via memory-mapped I/O
+    pub(crate) mmap_size: Option<u64>,
+
+    /// Journal mode of the db, e.g. `WAL`, which is a persistent property of the db file
+    pub(crate) journal_mode: Option<String>,
+
+    /// Maximum number of auxiliary threads a statement may use, e.g. for sorting
+    pub(crate) threads: Option<u32>,
+}
+
+/// Settings of an API server, which commands take by the name of the profile instead of a base URL
+///
+/// An example profile, named `dev`, with all optional fields:
+///
+/// ```toml
+/// [profiles.dev]
+/// base-url = "https://sysml.example.com/api"
+/// allow-invalid-certs = false
+/// ca-cert = "/etc/ssl/certs/corporate-ca.pem"
+/// api-version = "v1"
+/// username = "alice"
+/// password = "secret"
+/// login-url = "https://sysml.example.com/login"
+/// login-username-field = "username"
+/// login-password-field = "password"
+/// ```
+///
+/// The environment variables for the credentials and the login take precedence over the profile.
+/// Rather than in the config file, a password is better kept in the `.netrc` file or the keyring.
+#[derive(Debug, Clone, serde::Deserialize)]
+#[serde(rename_all = "kebab-case", deny_unknown_fields)]
+#[cfg_attr(not(feature = "fetch"), allow(dead_code))]
+pub(crate) struct ServerProfile {
+    /// Base URL of the API, without trailing `/`
+    pub(crate) base_url: String,
+
+    /// Accept invalid certificates, as `--allow-invalid-certs` does
+    #[serde(default)]
+    pub(crate) allow_invalid_certs: bool,
+
+    /// PEM file with CA certificates to trust, as `--ca-cert` sets it
+    pub(crate) ca_cert: Option<PathBuf>,
+
+    /// Version of the API, as `--api-version` sets it
+    pub(crate) api_version: Option<ApiVersion>,
+
+    /// Username, for HTTP basic auth or the login
+    pub(crate) username: Option<String>,
+
+    /// Password, for HTTP basic auth or the login
+    pub(crate) password: Option<String>,
+
+    /// URL of a form login issuing a session cookie, used instead of HTTP basic auth
+    pub(crate) login_url: Option<String>,
+
+    /// Name of the username field of the login form
+    pub(crate) login_username_field: Option<String>,
+
+    /// Name of the password field of the login form
+    pub(crate) login_password_field: Option<String>,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            elements_table: "elements".to_owned(),
+            relations_table: "relations".to_owned(),
+            extended_table: "extended_properties".to_owned(),
+            polymorphic_props: vec!["value".to_owned()],
+            status_report_interval_secs: 5,
+            max_memory: None,
+            pragmas: Pragmas::default(),
+            profiles: BTreeMap::new(),
+            redact: BTreeMap::new(),
+        }
+    }
+}
+
+impl Default for Pragmas {
+    fn default() -> Self {
+        let page_size = 4096;
+        Self {
+            cache_size: i64::from(page_size) * 2i64.pow(15),
+            page_size,
+            synchronous: "OFF".to_owned(),
+            mmap_size: None,
+            journal_mode: None,
+            threads: None,
+        }
+    }
+}
+
+impl Pragmas {
+    /// Sets a pragma by its name, as given by `--pragma NAME=VALUE`
+    ///
+    /// Names are taken both as in SQLite, e.g. `cache_size`, and as in the config file.
+    pub(crate) fn set(&mut self, name: &str, value: &str) -> Result<()> {
+        let parse_err = || format!("invalid value {value:?} for the pragma {name:?}");
+        match name.replace('-', "_").as_str() {
+            "cache_size" => self.cache_size = value.parse().wrap_err_with(parse_err)?,
+            "page_size" => self.page_size = value.parse().wrap_err_with(parse_err)?,
+            "synchronous" => self.synchronous = value.to_owned(),
+            "mmap_size" => self.mmap_size = Some(value.parse().wrap_err_with(parse_err)?),
+            "journal_mode" => self.journal_mode = Some(value.to_owned()),
+            "threads" => self.threads = Some(value.parse().wrap_err_with(parse_err)?),
+            _ => bail!(
+                "the pragma {name:?} can not be set, only cache_size, page_size, synchronous, mmap_size, journal_mode and threads can"
+            ),
+        }
+        Ok(())
+    }
+}
+
+impl Config {
+    /// Checks whether a property is a known polymorphic property
+    pub(crate) fn is_polymorphic(&self, prop_name: &str) -> bool {
+        self.polymorphic_props.iter().any(|p| p == prop_name)
+    }
+
+    /// Minimum time interval inbetween status reports
+    pub(crate) fn time_between_status_reports(&self) -> Duration {
+        Duration::from_secs(self.status_report_interval_secs)
+    }
+}
+
+static CONFIG: OnceLock<Config> = OnceLock::new();
+
+/// Loads the config from a TOML file, or uses the defaults if no file is given
+///
+/// The pragmas given as `(name, value)` and the memory limit take precedence over those of the
+/// config file. Must be called at most once, before the config is first accessed via [`config`].
+pub(crate) fn load_config(
+    maybe_path: Option<&Path>,
+    pragmas: &[(String, String)],
+    maybe_max_memory: Option<u64>,
+) -> Result<()> {
+    let mut config: Config = match maybe_path {
+        Some(path) => {
+            info!("loading config from {path:?}");
+            let content = std::fs::read_to_string(path)
+                .wrap_err_with(|| format!("reading config file {path:?}"))?;
+            toml::from_str(&content).wrap_err_with(|| format!("parsing config file {path:?}"))?
+        }
+        None => Config::default(),
+    };
+    for (name, value) in pragmas {
+        config.pragmas.set(name, value)?;
+    }
+    config.max_memory = maybe_max_memory.or(config.max_memory);
+    debug!("using config {config:?}");
+
+    if CONFIG.set(config).is_err() {
+        bail!("the config was already loaded");
+    }
+    Ok(())
+}
+
+/// Returns the loaded config, falling back to the defaults if none was loaded
+pub(crate) fn config() -> &'static Config {
+    CONFIG.get_or_init(Config::default)
+}
+
+// Name of the table which contains the names of all known relations
+pub(crate) static RELATION_NAMES_TABLE: &str = "relation_names";
+
+// Name of the table which maps each element id to an integer surrogate key
+pub(crate) static ELEMENT_KEYS_TABLE: &str = "element_keys";
+
+// Name of the table which contains all element to element relations, using surrogate keys
+pub(crate) static KEYED_RELATIONS_TABLE: &str = "keyed_relations";
+
+// Name of the table which contains the names of the relations whose transitive closure is maintained
+pub(crate) static CLOSURE_RELATIONS_TABLE: &str = "closure_relations";
+
+// Name of the table which contains the transitive closure of the relations named in the above table
+pub(crate) static CLOSURE_TABLE: &str = "relation_closure";
+
+// Name of the table which maps each element to its ownership path, see [`crate::paths`]
+pub(crate) static ELEMENT_PATHS_TABLE: &str = "element_paths";
+
+// Separator between the ids in the ownership path of an element
+pub(crate) const PATH_SEPARATOR: &str = "/";
+
+// Name of the FTS5 virtual table which indexes the textual columns of the elements table
+pub(crate) static FTS_TABLE: &str = "elements_fts";
+
+// Columns of the elements table which are indexed for full-text search
+pub(crate) const FTS_COLUMNS: [&str; 3] = ["declaredName", "qualifiedName", "body"];
+
+// Properties extracted into generated columns of dbs storing the elements as raw JSON, unless
+// others are given, which are those covered by the indices and the full-text index
+pub(crate) const DEFAULT_HOT_FIELDS: [&str; 7] = [
+    "@type",
+    "body",
+    "declaredName",
+    "declaredShortName",
+    "isLibraryElement",
+    "name",
+    "qualifiedName",
+];
+
+// Name of the relation pointing from an element to each element it owns
+pub(crate) const OWNED_ELEMENT_RELATION: &str = "ownedElement";
+
+// Name of the relation pointing from an element to each relationship it owns
+pub(crate) const OWNED_RELATIONSHIP_RELATION: &str = "ownedRelationship";
+
+// Names of the relations pointing from an element to its owner, in order of precedence. Elements
+// are owned via their owning relationship, relationships directly by their owning related element.
+pub(crate) const OWNER_RELATIONS: [&str; 2] = ["owner", "owningRelatedElement"];
+
+// Name of the table which maps each property to the element types that may carry it
+pub(crate) static PROPERTY_DOMAINS_TABLE: &str = "property_domains";
+
+// Prefix of the lookup tables which contain the legal variants of string enum columns
+pub(crate) static ENUM_TABLE_PREFIX: &str = "enum_";
+
+// Name of the table which contains information about the schema the db was initialized with
+pub(crate) static SCHEMA_INFO_TABLE: &str = "schema_info";
+
+// Name of the table which records the provenance of each import into the db
+pub(crate) static IMPORT_LOG_TABLE: &str = "import_log";
+
+// Name of the table which records the branches of cloned projects
+pub(crate) static BRANCHES_TABLE: &str = "branches";
+
+// Name of the table which documents from which definitions of the JSON schema each column stems
+pub(crate) static SCHEMA_META_TABLE: &str = "schema_meta";
+
+// Name of the table which contains user-defined validation rules
+pub(crate) static VALIDATION_RULES_TABLE: &str = "validation_rules";
 
 // Name of the column which contains the pimary key
 pub(crate) const ELEMENT_PK_COL: &str = "@id";
 
-// Name of known polymorphic properties
-pub(crate) const POLYMORPHIC_PROPS: [&str; 1] = ["value"];
+// Name of the column which contains the type of an element
+pub(crate) const TYPE_COL: &str = "@type";
+
+// Name of the column which contains the project of an element, in dbs storing multiple projects
+pub(crate) const PROJECT_COL: &str = "project_id";
+
+// Name of the column which contains the project of the target of a relation, in dbs storing multiple
+// projects
+pub(crate) const TARGET_PROJECT_COL: &str = "target_project_id";
+
+// Name of the column which contains the JSON of an element as imported, in dbs storing the elements
+// as raw JSON
+pub(crate) const RAW_JSON_COL: &str = "@json";
+
+// Name of the optional column which contains the owner of an element, see [`crate::owner`]
+pub(crate) const OWNER_COL: &str = "owner_id";
 
-/// Minimum time interval inbetween status reports
-pub(crate) const TIME_BETWEEN_STATUS_REPORTS: std::time::Duration =
-    std::time::Duration::from_secs(5);
+// Definition of the above column, as appended to the elements table
+pub(crate) const OWNER_COL_DEF: &str = r#""owner_id" TEXT"#;
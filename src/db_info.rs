@@ -0,0 +1,193 @@
+//! Overview over the schema, the import history and the settings of a db
+
+use std::io::Write;
+
+use eyre::Result;
+use rusqlite::Connection;
+
+use crate::{
+    config::IMPORT_LOG_TABLE,
+    import::get_table_columns,
+    projects::{is_multi_project, list_projects},
+    schema_info::{read_schema_info, schema_fingerprint},
+    upgrade_db::{SCHEMA_VERSION, schema_version},
+    util::{escape_sql_ident, table_exists},
+};
+
+/// Persistent settings of a db, as reported by the equally named PRAGMAs
+const PRAGMAS: &[&str] = &[
+    "application_id",
+    "auto_vacuum",
+    "encoding",
+    "freelist_count",
+    "journal_mode",
+    "page_count",
+    "page_size",
+];
+
+/// A single import, as recorded in the import log
+#[derive(Debug, Clone, serde::Serialize)]
+struct Import {
+    /// Time of the import, in UTC
+    timestamp: String,
+
+    /// File or base URL of the API server the elements were read from
+    source: Option<String>,
+
+    /// Id of the project the elements were fetched from
+    project_id: Option<String>,
+
+    /// Id of the commit the elements were fetched from
+    commit_id: Option<String>,
+
+    /// Number of imported elements
+    elements: u64,
+
+    /// Why the import was partial, e.g. filtered or transformed, `None` if it was complete
+    partial: Option<String>,
+}
+
+/// Overview over the schema, the import history and the settings of a db
+#[derive(Debug, Clone, serde::Serialize)]
+struct DbInfo {
+    /// Schema version of the db, see [`crate::upgrade_db`]
+    schema_version: u32,
+
+    /// Schema version of dbs initialized by this version of the tool
+    current_schema_version: u32,
+
+    /// Fingerprint the db was stamped with, see [`crate::schema_info`]
+    stamped_fingerprint: Option<String>,
+
+    /// Fingerprint of the current layout of the core tables
+    fingerprint: String,
+
+    /// Where the schema of the db originates from
+    schema_origin: Option<String>,
+
+    /// Projects stored in the db with their number of elements, if it stores multiple projects
+    projects: Option<Vec<(String, u64)>>,
+
+    /// All imports into the db, oldest first
+    imports: Vec<Import>,
+
+    /// Persistent settings of the db
+    pragmas: Vec<(String, String)>,
+}
+
+/// Print the schema fingerprint and version, the import history and the settings of the db
+///
+/// The import history is only available for imports performed by a version of this tool which
+/// maintains the import log. With `json`, the information is printed as JSON object instead.
+pub(crate) fn db_info<W: Write>(conn: &Connection, json: bool, out: &mut W) -> Result<()> {
+    let mut imports = Vec::new();
+    if table_exists(conn, IMPORT_LOG_TABLE)? {
+        // logs predating the partial imports lack the column
+        let has_partial = get_table_columns(conn, IMPORT_LOG_TABLE)?
+            .iter()
+            .any(|(name, _)| name == "partial");
+        let mut stmt = conn.prepare(&format!(
+            r#"SELECT "timestamp", "source", "project_id", "commit_id", "elements", {} FROM {}
+            ORDER BY "timestamp", rowid"#,
+            if has_partial { r#""partial""# } else { "NULL" },
+            escape_sql_ident(IMPORT_LOG_TABLE)
+        ))?;
+        imports = stmt
+            .query_map((), |row| {
+                Ok(Import {
+                    timestamp: row.get(0)?,
+                    source: row.get(1)?,
+                    project_id: row.get(2)?,
+                    commit_id: row.get(3)?,
+                    elements: row.get(4)?,
+                    partial: row.get(5)?,
+                })
+            })?
+            .collect::<Result<_, _>>()?;
+    }
+
+    let mut pragmas = Vec::new();
+    for pragma in PRAGMAS {
+        let value: rusqlite::types::Value =
+            conn.pragma_query_value(None, pragma, |row| row.get(0))?;
+        let value = match value {
+            rusqlite::types::Value::Integer(i) => i.to_string(),
+            rusqlite::types::Value::Text(t) => t,
+            v => format!("{v:?}"),
+        };
+        pragmas.push((pragma.to_string(), value));
+    }
+
+    let info = DbInfo {
+        schema_version: schema_version(conn)?,
+        current_schema_version: SCHEMA_VERSION,
+        stamped_fingerprint: read_schema_info(conn, "fingerprint")?,
+        fingerprint: schema_fingerprint(conn)?,
+        schema_origin: read_schema_info(conn, "origin")?,
+        projects: if is_multi_project(conn)? {
+            Some(list_projects(conn)?)
+        } else {
+            None
+        },
+        imports,
+        pragmas,
+    };
+
+    if json {
+        serde_json::to_writer_pretty(&mut *out, &info)?;
+        writeln!(out)?;
+        return Ok(());
+    }
+
+    let or_unknown = |value: &Option<String>| value.clone().unwrap_or_else(|| "-".to_owned());
+
+    writeln!(out, "# schema")?;
+    writeln!(
+        out,
+        "version\t{} (this tool: {})",
+        info.schema_version, info.current_schema_version
+    )?;
+    writeln!(out, "fingerprint\t{}", info.fingerprint)?;
+    writeln!(
+        out,
+        "stamped fingerprint\t{}",
+        or_unknown(&info.stamped_fingerprint)
+    )?;
+    writeln!(out, "origin\t{}", or_unknown(&info.schema_origin))?;
+
+    if let Some(projects) = &info.projects {
+        writeln!(out, "\n# projects")?;
+        for (project_id, elements) in projects {
+            writeln!(out, "{project_id}\t{elements} elements")?;
+        }
+    }
+
+    writeln!(out, "\n# imports")?;
+    for import in &info.imports {
+        writeln!(
+            out,
+            "{}\t{}\t{}\t{}\t{} elements{}",
+            import.timestamp,
+            or_unknown(&import.source),
+            or_unknown(&import.project_id),
+            or_unknown(&import.commit_id),
+            import.elements,
+            import
+                .partial
+                .as_ref()
+                .map(|partial| format!(" (partial: {partial})"))
+                .unwrap_or_default()
+        )?;
+    }
+    match info.imports.last() {
+        Some(import) => writeln!(out, "last import\t{}", import.timestamp)?,
+        None => writeln!(out, "last import\t-")?,
+    }
+
+    writeln!(out, "\n# pragmas")?;
+    for (pragma, value) in &info.pragmas {
+        writeln!(out, "{pragma}\t{value}")?;
+    }
+
+    Ok(())
+}
@@ -0,0 +1,298 @@
+//! The elements of the db as JSON, as far as the db represents them, the inverse of the import
+//!
+//! `push` compares the elements of the db with those of the commit they were fetched from, and
+//! `export-anonymized` exports them. Properties which the importer does not store, e.g. the values
+//! of columns it could not represent, are lost, and the order of relation targets is not recorded.
+
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+
+use eyre::Result;
+use rusqlite::{Connection, types::Value as RusValue};
+use serde_json::{Map, Value, json};
+
+use crate::{
+    config::{ELEMENT_PK_COL, OWNER_COL, PROJECT_COL, RAW_JSON_COL, config},
+    import::{Element, get_table_columns, is_relation_object, json_value_for_column},
+    util::escape_sql_ident,
+    verify::same_db_value,
+};
+
+/// An element as represented in the db
+#[derive(Debug, Clone, Default, PartialEq)]
+pub(crate) struct DbElement {
+    /// Values of the columns of the elements table, except for the id and project
+    pub(crate) columns: BTreeMap<String, RusValue>,
+
+    /// Targets of the relations originating from the element, by relation name
+    pub(crate) relations: BTreeMap<String, BTreeSet<String>>,
+
+    /// Values of the extended properties of the element, sorted, by property
+    pub(crate) extended: BTreeMap<String, Vec<String>>,
+}
+
+/// Reads all elements of the db as JSON, as far as the db represents them, sorted by id
+pub(crate) fn db_elements_json(conn: &Connection) -> Result<Vec<Element>> {
+    let to_many = to_many_relations(conn, &[])?;
+    let mut elements = read_db_elements(conn)?
+        .iter()
+        .map(|(id, db_element)| {
+            let attributes = element_json(id, None, db_element, &DbElement::default(), &to_many);
+            serde_json::from_value(Value::Object(attributes))
+        })
+        .collect::<Result<Vec<Element>, _>>()?;
+    elements.sort_by(|a, b| a.id.cmp(&b.id));
+    Ok(elements)
+}
+
+/// The columns of the elements table, except for the id, project and owner
+pub(crate) fn element_columns(conn: &Connection) -> Result<Vec<(String, rusqlite::types::Type)>> {
+    Ok(get_table_columns(conn, &config().elements_table)?
+        .into_iter()
+        .filter(|(name, _)| ![ELEMENT_PK_COL, PROJECT_COL, OWNER_COL].contains(&name.as_str()))
+        .collect())
+}
+
+/// Reads all elements of the db, with their relations and extended properties
+pub(crate) fn read_db_elements(conn: &Connection) -> Result<HashMap<String, DbElement>> {
+    let config = config();
+    let pk_col = escape_sql_ident(ELEMENT_PK_COL);
+    let columns = element_columns(conn)?;
+
+    let mut db_elements: HashMap<String, DbElement> = HashMap::new();
+    let mut stmt = conn.prepare(&format!(
+        "SELECT {pk_col}, {} FROM {}",
+        columns
+            .iter()
+            .map(|(name, _)| escape_sql_ident(name))
+            .collect::<Vec<_>>()
+            .join(", "),
+        escape_sql_ident(&config.elements_table)
+    ))?;
+    let mut rows = stmt.query(())?;
+    while let Some(row) = rows.next()? {
+        let id: String = row.get(0)?;
+        let mut db_element = DbElement::default();
+        for (idx, (name, _)) in columns.iter().enumerate() {
+            db_element.columns.insert(name.clone(), row.get(idx + 1)?);
+        }
+        db_elements.insert(id, db_element);
+    }
+
+    let mut stmt = conn.prepare(&format!(
+        r#"SELECT "origin_id", "name", "target_id" FROM {}"#,
+        escape_sql_ident(&config.relations_table)
+    ))?;
+    let mut rows = stmt.query(())?;
+    while let Some(row) = rows.next()? {
+        let origin_id: String = row.get(0)?;
+        if let Some(db_element) = db_elements.get_mut(&origin_id) {
+            db_element
+                .relations
+                .entry(row.get(1)?)
+                .or_default()
+                .insert(row.get(2)?);
+        }
+    }
+
+    for (column, _) in get_table_columns(conn, &config.extended_table)? {
+        if column == ELEMENT_PK_COL || column == PROJECT_COL {
+            continue;
+        }
+        let column_escaped = escape_sql_ident(&column);
+        let mut stmt = conn.prepare(&format!(
+            "SELECT {pk_col}, {column_escaped} FROM {} WHERE {column_escaped} IS NOT NULL",
+            escape_sql_ident(&config.extended_table)
+        ))?;
+        let mut rows = stmt.query(())?;
+        while let Some(row) = rows.next()? {
+            let id: String = row.get(0)?;
+            if let Some(db_element) = db_elements.get_mut(&id) {
+                db_element
+                    .extended
+                    .entry(column.clone())
+                    .or_default()
+                    .push(row.get(1)?);
+            }
+        }
+    }
+    for db_element in db_elements.values_mut() {
+        for values in db_element.extended.values_mut() {
+            values.sort();
+        }
+    }
+
+    Ok(db_elements)
+}
+
+/// Checks whether a JSON value is a relation object
+pub(crate) fn is_relation_value(value: &Value) -> bool {
+    matches!(value, Value::Object(o) if is_relation_object(o))
+}
+
+/// Determines which relations hold a list of targets, rather than a single target
+///
+/// A relation is considered to hold a list if it does so in any of the given elements, or if any
+/// element of the db has more than one target for it.
+pub(crate) fn to_many_relations(
+    conn: &Connection,
+    elements: &[Element],
+) -> Result<HashSet<String>> {
+    let mut to_many: HashSet<String> = elements
+        .iter()
+        .flat_map(|e| &e.rest)
+        .filter(|(_, v)| matches!(v, Value::Array(a) if a.iter().all(is_relation_value)))
+        .map(|(name, _)| name.clone())
+        .collect();
+
+    let mut stmt = conn.prepare(&format!(
+        r#"SELECT DISTINCT "name" FROM {} GROUP BY "name", "origin_id" HAVING COUNT(*) > 1"#,
+        escape_sql_ident(&config().relations_table)
+    ))?;
+    for name in stmt.query_map((), |row| row.get(0))? {
+        to_many.insert(name?);
+    }
+    Ok(to_many)
+}
+
+/// Builds the JSON of an element from its db representation
+///
+/// If the element existed before, its previous JSON is the starting point, and only the properties
+/// which differ from its previous db representation are replaced.
+pub(crate) fn element_json(
+    id: &str,
+    maybe_base: Option<&Element>,
+    db_element: &DbElement,
+    base_db_element: &DbElement,
+    to_many: &HashSet<String>,
+) -> Map<String, Value> {
+    let mut attributes = maybe_base.map(|b| b.rest.clone()).unwrap_or_default();
+
+    for (name, value) in &db_element.columns {
+        match (maybe_base, base_db_element.columns.get(name)) {
+            // the previous value could not be stored, hence the db does not tell anything about it
+            (Some(_), None) => continue,
+            (_, Some(base_value)) if same_db_value(base_value, value) => continue,
+            (None, _) if *value == RusValue::Null => continue,
+            _ => {}
+        }
+        // the raw JSON of the element, whose relations are superseded by the relations table
+        if name == RAW_JSON_COL {
+            if let RusValue::Text(text) = value {
+                let raw: Map<String, Value> = serde_json::from_str(text).unwrap_or_default();
+                attributes.extend(raw.into_iter().filter(|(name, value)| {
+                    name != ELEMENT_PK_COL
+                        && !is_relation_value(value)
+                        && !matches!(value, Value::Array(a) if !a.is_empty() && a.iter().all(is_relation_value))
+                }));
+            }
+            continue;
+        }
+        let hint = json_value_for_column(&attributes, name).cloned();
+        set_column(
+            &mut attributes,
+            name,
+            json_value(name, value, hint.as_ref()),
+        );
+    }
+
+    let relation_names: BTreeSet<&String> = db_element
+        .relations
+        .keys()
+        .chain(base_db_element.relations.keys())
+        .collect();
+    for name in relation_names {
+        let targets = db_element.relations.get(name);
+        if targets == base_db_element.relations.get(name) {
+            continue;
+        }
+        let targets = targets.cloned().unwrap_or_default();
+
+        // retain the order of the previous targets, which the db does not record
+        let mut ordered: Vec<String> = match attributes.get(name) {
+            Some(Value::Array(previous)) => previous
+                .iter()
+                .filter_map(|v| v[ELEMENT_PK_COL].as_str())
+                .filter(|t| targets.contains(*t))
+                .map(str::to_owned)
+                .collect(),
+            _ => Vec::new(),
+        };
+        let retained: HashSet<String> = ordered.iter().cloned().collect();
+        ordered.extend(targets.into_iter().filter(|t| !retained.contains(t)));
+
+        let is_to_many =
+            to_many.contains(name) || matches!(attributes.get(name), Some(Value::Array(_)));
+        let value = match ordered.as_slice() {
+            _ if is_to_many => Value::Array(ordered.iter().map(|t| json!({ "@id": t })).collect()),
+            [] => Value::Null,
+            [target] => json!({ "@id": target }),
+            _ => unreachable!("relations with multiple targets are to-many relations"),
+        };
+        attributes.insert(name.clone(), value);
+    }
+
+    for name in db_element
+        .extended
+        .keys()
+        .chain(base_db_element.extended.keys())
+    {
+        if db_element.extended.get(name) != base_db_element.extended.get(name) {
+            let values = db_element.extended.get(name).cloned().unwrap_or_default();
+            attributes.insert(name.clone(), Value::from(values));
+        }
+    }
+
+    let mut element = Map::new();
+    element.insert(ELEMENT_PK_COL.to_owned(), Value::from(id));
+    element.extend(attributes);
+    element
+}
+
+/// Converts a value of a column back to JSON, the inverse of
+/// [`crate::import::db_value_for_column`]
+///
+/// The previous JSON value of the property, if any, tells apart booleans from integers and objects
+/// stored as JSON text from strings.
+fn json_value(column_name: &str, value: &RusValue, maybe_hint: Option<&Value>) -> Value {
+    let is_flag =
+        column_name.starts_with("is") && column_name.chars().nth(2).is_some_and(char::is_uppercase);
+    match value {
+        RusValue::Null => Value::Null,
+        RusValue::Integer(i) if is_flag || matches!(maybe_hint, Some(Value::Bool(_))) => {
+            Value::Bool(*i != 0)
+        }
+        RusValue::Integer(i) => Value::from(*i),
+        RusValue::Real(r) => Value::from(*r),
+        RusValue::Text(t) => match maybe_hint {
+            Some(Value::Object(_)) | None if t.starts_with('{') => {
+                serde_json::from_str(t).unwrap_or_else(|_| Value::from(t.as_str()))
+            }
+            _ => Value::from(t.as_str()),
+        },
+        RusValue::Blob(_) => {
+            warn!("can not represent the blob in the column {column_name:?} as JSON, omitting it");
+            Value::Null
+        }
+    }
+}
+
+/// Sets the value of a column in the JSON attributes of an element
+///
+/// Columns of flattened nested properties are set within the nested object.
+fn set_column(attributes: &mut Map<String, Value>, column_name: &str, value: Value) {
+    if !attributes.contains_key(column_name) {
+        if let Some((property, nested_property)) = column_name.split_once('.') {
+            let nested = attributes
+                .entry(property)
+                .or_insert_with(|| Value::Object(Map::new()));
+            if !nested.is_object() {
+                *nested = Value::Object(Map::new());
+            }
+            if let Value::Object(nested_attributes) = nested {
+                set_column(nested_attributes, nested_property, value);
+            }
+            return;
+        }
+    }
+    attributes.insert(column_name.to_owned(), value);
+}
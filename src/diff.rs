@@ -0,0 +1,9 @@
+//! Commands comparing two versions of a model
+//!
+//! These commands help to review what changed between two commits of a model.
+
+mod db_diff;
+mod json_diff;
+
+pub(crate) use db_diff::db_diff;
+pub(crate) use json_diff::json_diff;
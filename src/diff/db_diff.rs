@@ -0,0 +1,290 @@
+use std::{collections::BTreeMap, io::Write, path::Path};
+
+use eyre::Result;
+use rusqlite::{Connection, types::Value};
+
+use crate::{
+    config::{ELEMENT_PK_COL, config},
+    query::element_label_expr,
+    util::{escape_sql_ident, escape_sql_str_lit},
+};
+
+/// Name under which the other db is attached
+const OTHER_SCHEMA: &str = "diff_other";
+
+/// Differences concerning the elements of one type
+#[derive(Default)]
+struct TypeDiff {
+    /// Id and label of added elements
+    added: Vec<(String, String)>,
+
+    /// Id and label of removed elements
+    removed: Vec<(String, String)>,
+
+    /// Id, label and the names of the changed columns of changed elements
+    changed: Vec<(String, String, Vec<String>)>,
+
+    /// Name, origin id and target id of added relations originating from elements of this type
+    relations_added: Vec<(String, String, String)>,
+
+    /// Name, origin id and target id of removed relations originating from elements of this type
+    relations_removed: Vec<(String, String, String)>,
+
+    /// Id, property and value of added extended properties of elements of this type
+    extended_added: Vec<(String, String, String)>,
+
+    /// Id, property and value of removed extended properties of elements of this type
+    extended_removed: Vec<(String, String, String)>,
+}
+
+/// Compare the db to another db, printing the differences grouped by element type
+///
+/// The db is considered the old, the other db the new version of the model. Elements are matched
+/// by their id; elements which differ in any column present in both dbs are listed as changed.
+/// Relations are attributed to the type of their origin element, extended properties to the type of
+/// their element. With `summary`, only the number of differences per type is printed.
+pub(crate) fn db_diff<W: Write>(
+    conn: &Connection,
+    other: &Path,
+    summary: bool,
+    out: &mut W,
+) -> Result<usize> {
+    conn.execute(
+        &format!("ATTACH DATABASE ?1 AS {}", escape_sql_ident(OTHER_SCHEMA)),
+        [other.to_string_lossy()],
+    )?;
+    let result = diff_attached(conn, summary, out);
+    conn.execute(
+        &format!("DETACH DATABASE {}", escape_sql_ident(OTHER_SCHEMA)),
+        (),
+    )?;
+    result
+}
+
+/// Compare the db to the attached other db, returning the total number of differences
+fn diff_attached<W: Write>(conn: &Connection, summary: bool, out: &mut W) -> Result<usize> {
+    let config = config();
+    let old = escape_sql_ident("main");
+    let new = escape_sql_ident(OTHER_SCHEMA);
+    let elements_table = escape_sql_ident(&config.elements_table);
+    let relations_table = escape_sql_ident(&config.relations_table);
+    let extended_table = escape_sql_ident(&config.extended_table);
+    let pk_col = escape_sql_ident(ELEMENT_PK_COL);
+    let label = element_label_expr("e");
+
+    let mut diffs: BTreeMap<String, TypeDiff> = BTreeMap::new();
+
+    //
+    // Elements
+    //
+
+    for (this, that, added) in [(&new, &old, true), (&old, &new, false)] {
+        let mut stmt = conn.prepare(&format!(
+            r#"SELECT e.{pk_col}, COALESCE(e."@type", ''), {label} FROM {this}.{elements_table} AS e
+            WHERE NOT EXISTS (SELECT 1 FROM {that}.{elements_table} AS o WHERE o.{pk_col} = e.{pk_col})
+            ORDER BY 3, 1"#
+        ))?;
+        let mut rows = stmt.query(())?;
+        while let Some(row) = rows.next()? {
+            let diff = diffs.entry(row.get(1)?).or_default();
+            let element = (row.get(0)?, row.get(2)?);
+            if added {
+                diff.added.push(element);
+            } else {
+                diff.removed.push(element);
+            }
+        }
+    }
+
+    let columns = common_columns(conn, &config.elements_table)?;
+    let compared_columns: Vec<_> = columns.iter().filter(|c| *c != ELEMENT_PK_COL).collect();
+    if !compared_columns.is_empty() {
+        let differs = compared_columns
+            .iter()
+            .map(|c| {
+                let c = escape_sql_ident(c);
+                format!("o.{c} IS NOT e.{c}")
+            })
+            .collect::<Vec<_>>();
+        let mut stmt = conn.prepare(&format!(
+            r#"SELECT e.{pk_col}, COALESCE(e."@type", ''), {label}, {} FROM {new}.{elements_table} AS e
+            JOIN {old}.{elements_table} AS o ON o.{pk_col} = e.{pk_col}
+            WHERE {}
+            ORDER BY 3, 1"#,
+            differs.join(", "),
+            differs.join(" OR ")
+        ))?;
+        let mut rows = stmt.query(())?;
+        while let Some(row) = rows.next()? {
+            let mut changed_columns = Vec::new();
+            for (idx, column) in compared_columns.iter().enumerate() {
+                if row.get(3 + idx)? {
+                    changed_columns.push(column.to_string());
+                }
+            }
+            diffs.entry(row.get(1)?).or_default().changed.push((
+                row.get(0)?,
+                row.get(2)?,
+                changed_columns,
+            ));
+        }
+    }
+
+    //
+    // Relations
+    //
+
+    for (this, that, added) in [(&new, &old, true), (&old, &new, false)] {
+        let mut stmt = conn.prepare(&format!(
+            r#"SELECT r."name", r."origin_id", r."target_id", COALESCE(e."@type", '') FROM (
+                SELECT "name", "origin_id", "target_id" FROM {this}.{relations_table}
+                EXCEPT SELECT "name", "origin_id", "target_id" FROM {that}.{relations_table}
+            ) AS r
+            LEFT JOIN {this}.{elements_table} AS e ON e.{pk_col} = r."origin_id"
+            ORDER BY 2, 1, 3"#
+        ))?;
+        let mut rows = stmt.query(())?;
+        while let Some(row) = rows.next()? {
+            let diff = diffs.entry(row.get(3)?).or_default();
+            let relation = (row.get(0)?, row.get(1)?, row.get(2)?);
+            if added {
+                diff.relations_added.push(relation);
+            } else {
+                diff.relations_removed.push(relation);
+            }
+        }
+    }
+
+    //
+    // Extended properties
+    //
+
+    let columns = common_columns(conn, &config.extended_table)?;
+    let value_columns: Vec<_> = columns.iter().filter(|c| *c != ELEMENT_PK_COL).collect();
+    let column_list = columns
+        .iter()
+        .map(escape_sql_ident)
+        .collect::<Vec<_>>()
+        .join(", ");
+    for (this, that, added) in [(&new, &old, true), (&old, &new, false)] {
+        if value_columns.is_empty() {
+            break;
+        }
+
+        // each row carries the value of one property, the others are null
+        let (property, value): (Vec<_>, Vec<_>) = value_columns
+            .iter()
+            .map(|c| {
+                let c_escaped = escape_sql_ident(c);
+                (
+                    format!(
+                        "WHEN x.{c_escaped} IS NOT NULL THEN {}",
+                        escape_sql_str_lit(c)
+                    ),
+                    format!("x.{c_escaped}"),
+                )
+            })
+            .unzip();
+        let mut stmt = conn.prepare(&format!(
+            r#"SELECT x.{pk_col}, CASE {} END, CAST(COALESCE({}) AS TEXT), COALESCE(e."@type", '') FROM (
+                SELECT {column_list} FROM {this}.{extended_table}
+                EXCEPT SELECT {column_list} FROM {that}.{extended_table}
+            ) AS x
+            LEFT JOIN {this}.{elements_table} AS e ON e.{pk_col} = x.{pk_col}
+            ORDER BY 1, 2, 3"#,
+            property.join(" "),
+            value.join(", ")
+        ))?;
+        let mut rows = stmt.query(())?;
+        while let Some(row) = rows.next()? {
+            let property: Option<String> = row.get(1)?;
+            let value: Value = row.get(2)?;
+            let value = match value {
+                Value::Text(t) => t,
+                _ => String::new(),
+            };
+            let diff = diffs.entry(row.get(3)?).or_default();
+            let extended_property = (row.get(0)?, property.unwrap_or_default(), value);
+            if added {
+                diff.extended_added.push(extended_property);
+            } else {
+                diff.extended_removed.push(extended_property);
+            }
+        }
+    }
+
+    //
+    // Report
+    //
+
+    let mut total = 0;
+    for (type_name, diff) in &diffs {
+        let type_name = if type_name.is_empty() {
+            "(unknown type)"
+        } else {
+            type_name
+        };
+        total += diff.added.len()
+            + diff.removed.len()
+            + diff.changed.len()
+            + diff.relations_added.len()
+            + diff.relations_removed.len()
+            + diff.extended_added.len()
+            + diff.extended_removed.len();
+
+        if summary {
+            writeln!(
+                out,
+                "{type_name}\telements +{} -{} ~{}\trelations +{} -{}\textended properties +{} -{}",
+                diff.added.len(),
+                diff.removed.len(),
+                diff.changed.len(),
+                diff.relations_added.len(),
+                diff.relations_removed.len(),
+                diff.extended_added.len(),
+                diff.extended_removed.len()
+            )?;
+            continue;
+        }
+
+        writeln!(out, "# {type_name}")?;
+        for (id, label) in &diff.added {
+            writeln!(out, "+ {id}\t{label}")?;
+        }
+        for (id, label) in &diff.removed {
+            writeln!(out, "- {id}\t{label}")?;
+        }
+        for (id, label, columns) in &diff.changed {
+            writeln!(out, "~ {id}\t{label}\t{}", columns.join(", "))?;
+        }
+        for (name, origin_id, target_id) in &diff.relations_added {
+            writeln!(out, "+ {origin_id} -{name}-> {target_id}")?;
+        }
+        for (name, origin_id, target_id) in &diff.relations_removed {
+            writeln!(out, "- {origin_id} -{name}-> {target_id}")?;
+        }
+        for (id, property, value) in &diff.extended_added {
+            writeln!(out, "+ {id}\t{property}: {value}")?;
+        }
+        for (id, property, value) in &diff.extended_removed {
+            writeln!(out, "- {id}\t{property}: {value}")?;
+        }
+        writeln!(out)?;
+    }
+
+    info!("found {total} differences");
+    Ok(total)
+}
+
+/// Names of the columns a table has in both the db and the attached other db
+fn common_columns(conn: &Connection, table: &str) -> Result<Vec<String>> {
+    let mut stmt = conn.prepare(
+        "SELECT m.name FROM pragma_table_info(?1, 'main') AS m
+        JOIN pragma_table_info(?1, ?2) AS o ON o.name = m.name
+        ORDER BY m.cid",
+    )?;
+    let columns = stmt
+        .query_map((table, OTHER_SCHEMA), |row| row.get(0))?
+        .collect::<Result<_, _>>()?;
+    Ok(columns)
+}
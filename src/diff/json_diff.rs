@@ -0,0 +1,252 @@
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    io::Write,
+    path::Path,
+};
+
+use eyre::{Result, bail};
+use serde_json::{Map, Value};
+
+use crate::{
+    config::ELEMENT_PK_COL,
+    import::{Element, is_relation_object},
+    util::CloneableJsonArrayStreamIterator,
+};
+
+/// Kind of difference of an element between two dumps
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize)]
+#[serde(rename_all = "lowercase")]
+enum Change {
+    /// The element only exists in the new dump
+    Added,
+
+    /// The element only exists in the old dump
+    Removed,
+
+    /// The element exists in both dumps, but differs
+    Changed,
+}
+
+/// Change of an attribute, i.e. a property which does not refer to other elements
+#[derive(Debug, Clone, serde::Serialize)]
+struct AttributeChange {
+    /// Name of the property
+    name: String,
+
+    /// Value in the old dump, null if absent
+    old: Value,
+
+    /// Value in the new dump, null if absent
+    new: Value,
+}
+
+/// Change of a relation, i.e. a property referring to other elements
+#[derive(Debug, Clone, serde::Serialize)]
+struct RelationChange {
+    /// Name of the property
+    name: String,
+
+    /// Ids of the elements referred to only in the new dump
+    added: Vec<String>,
+
+    /// Ids of the elements referred to only in the old dump
+    removed: Vec<String>,
+}
+
+/// Difference of a single element between two dumps
+#[derive(Debug, Clone, serde::Serialize)]
+struct ElementDiff {
+    /// Id of the element
+    #[serde(rename = "@id")]
+    id: String,
+
+    /// Type of the element, taken from the new dump unless the element was removed
+    #[serde(rename = "@type")]
+    ty: Option<String>,
+
+    /// Human readable label of the element
+    label: String,
+
+    /// Kind of the difference
+    change: Change,
+
+    /// Changed attributes, only for changed elements
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    attributes: Vec<AttributeChange>,
+
+    /// Changed relations, only for changed elements
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    relations: Vec<RelationChange>,
+}
+
+/// Compare two JSON dumps element by element, printing the differences
+///
+/// Elements are matched by their id. For changed elements, properties referring to other elements
+/// (`{"@id": "..."}` or arrays thereof) are compared as sets of relations, all other properties are
+/// compared by value. The human readable report groups the differences by element type, with `json`
+/// a JSON array with one object per differing element is printed instead. Returns the number of
+/// differing elements.
+pub(crate) fn json_diff<W: Write>(
+    old: &Path,
+    new: &Path,
+    json: bool,
+    out: &mut W,
+) -> Result<usize> {
+    let old_elements = read_dump(old)?;
+    let new_elements = read_dump(new)?;
+
+    let ids: BTreeSet<_> = old_elements.keys().chain(new_elements.keys()).collect();
+    let mut diffs = Vec::new();
+    for id in ids {
+        let diff = match (old_elements.get(id), new_elements.get(id)) {
+            (None, Some(new_element)) => element_diff(id, new_element, Change::Added),
+            (Some(old_element), None) => element_diff(id, old_element, Change::Removed),
+            (Some(old_element), Some(new_element)) if old_element != new_element => {
+                let mut diff = element_diff(id, new_element, Change::Changed);
+                compare_properties(old_element, new_element, &mut diff);
+                diff
+            }
+            _ => continue,
+        };
+        diffs.push(diff);
+    }
+
+    if json {
+        serde_json::to_writer_pretty(&mut *out, &diffs)?;
+        writeln!(out)?;
+    } else {
+        write_report(&diffs, out)?;
+    }
+
+    info!("found {} differing elements", diffs.len());
+    Ok(diffs.len())
+}
+
+/// Read all elements of a dump, indexed by their id
+fn read_dump(file: &Path) -> Result<BTreeMap<String, Map<String, Value>>> {
+    let mut elements = BTreeMap::new();
+    for element in CloneableJsonArrayStreamIterator::<Element>::new(file)? {
+        let Element { id, rest } = element?;
+        if elements.insert(id.clone(), rest).is_some() {
+            bail!("the element {id:?} occurs more than once in {file:?}");
+        }
+    }
+    Ok(elements)
+}
+
+/// Create the difference of an element, without any property changes
+fn element_diff(id: &str, element: &Map<String, Value>, change: Change) -> ElementDiff {
+    let label = ["qualifiedName", "name", "declaredName"]
+        .into_iter()
+        .find_map(|property| element.get(property).and_then(Value::as_str))
+        .unwrap_or_default();
+
+    ElementDiff {
+        id: id.to_owned(),
+        ty: element
+            .get("@type")
+            .and_then(Value::as_str)
+            .map(str::to_owned),
+        label: label.to_owned(),
+        change,
+        attributes: Vec::new(),
+        relations: Vec::new(),
+    }
+}
+
+/// Compare the properties of two versions of an element, recording the changes in `diff`
+fn compare_properties(
+    old_element: &Map<String, Value>,
+    new_element: &Map<String, Value>,
+    diff: &mut ElementDiff,
+) {
+    let names: BTreeSet<_> = old_element.keys().chain(new_element.keys()).collect();
+    for name in names {
+        let old_value = old_element.get(name).unwrap_or(&Value::Null);
+        let new_value = new_element.get(name).unwrap_or(&Value::Null);
+        if old_value == new_value {
+            continue;
+        }
+
+        match (relation_targets(old_value), relation_targets(new_value)) {
+            (Some(old_targets), Some(new_targets)) => {
+                let added: Vec<_> = new_targets.difference(&old_targets).cloned().collect();
+                let removed: Vec<_> = old_targets.difference(&new_targets).cloned().collect();
+
+                // a mere reordering of the targets is no change worth reporting
+                if !added.is_empty() || !removed.is_empty() {
+                    diff.relations.push(RelationChange {
+                        name: name.to_owned(),
+                        added,
+                        removed,
+                    });
+                }
+            }
+            _ => diff.attributes.push(AttributeChange {
+                name: name.to_owned(),
+                old: old_value.clone(),
+                new: new_value.clone(),
+            }),
+        }
+    }
+}
+
+/// Ids of the elements a property value refers to, or `None` if it is not a relation
+///
+/// Null and empty arrays are considered relations without targets, as these are the values of an
+/// absent relation.
+fn relation_targets(value: &Value) -> Option<BTreeSet<String>> {
+    let id_of = |value: &Value| match value {
+        Value::Object(o) if is_relation_object(o) => o[ELEMENT_PK_COL].as_str().map(str::to_owned),
+        _ => None,
+    };
+
+    match value {
+        Value::Null => Some(BTreeSet::new()),
+        Value::Object(_) => id_of(value).map(|id| BTreeSet::from([id])),
+        Value::Array(values) => values.iter().map(id_of).collect(),
+        _ => None,
+    }
+}
+
+/// Print the differences grouped by element type
+fn write_report<W: Write>(diffs: &[ElementDiff], out: &mut W) -> Result<()> {
+    let mut diffs_by_type: BTreeMap<&str, Vec<&ElementDiff>> = BTreeMap::new();
+    for diff in diffs {
+        diffs_by_type
+            .entry(diff.ty.as_deref().unwrap_or("(unknown type)"))
+            .or_default()
+            .push(diff);
+    }
+
+    for (type_name, diffs) in diffs_by_type {
+        writeln!(out, "# {type_name}")?;
+        for diff in diffs {
+            let marker = match diff.change {
+                Change::Added => '+',
+                Change::Removed => '-',
+                Change::Changed => '~',
+            };
+            writeln!(out, "{marker} {}\t{}", diff.id, diff.label)?;
+
+            for attribute in &diff.attributes {
+                writeln!(
+                    out,
+                    "    ~ {}: {} -> {}",
+                    attribute.name, attribute.old, attribute.new
+                )?;
+            }
+            for relation in &diff.relations {
+                for target in &relation.added {
+                    writeln!(out, "    + -{}-> {target}", relation.name)?;
+                }
+                for target in &relation.removed {
+                    writeln!(out, "    - -{}-> {target}", relation.name)?;
+                }
+            }
+        }
+        writeln!(out)?;
+    }
+
+    Ok(())
+}
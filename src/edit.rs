@@ -0,0 +1,368 @@
+//! Small modifications of the model in a db, without hand-written SQL
+//!
+//! All modifications run in a transaction with foreign key constraints enforced, so the triggers and
+//! constraints of the schema reject edits which would violate its invariants. In dbs storing
+//! multiple projects, the project to edit must be given.
+
+use eyre::{Result, bail};
+use rusqlite::{Connection, OptionalExtension, Transaction};
+use serde_json::Value;
+
+use crate::{
+    config::{
+        ELEMENT_PK_COL, KEYED_RELATIONS_TABLE, OWNED_ELEMENT_RELATION, OWNED_RELATIONSHIP_RELATION,
+        OWNER_COL, PROJECT_COL, PROPERTY_DOMAINS_TABLE, RELATION_NAMES_TABLE, TARGET_PROJECT_COL,
+        config,
+    },
+    import::{db_value_for_column, get_table_columns},
+    projects::{is_multi_project, resolve_cross_project_targets},
+    util::{escape_sql_ident, escape_sql_str_lit, table_exists},
+};
+
+/// Conditions restricting rows to the edited project, empty for dbs storing a single project
+struct ProjectConditions {
+    /// The edited project, absent for dbs storing a single project
+    maybe_project_id: Option<String>,
+
+    /// Condition on the project of elements, relations and extended properties
+    project: String,
+
+    /// Condition on the project of the target of relations
+    target_project: String,
+}
+
+/// Begins a transaction for an edit, determining the conditions restricting it to the project
+fn begin_edit<'a>(
+    conn: &'a mut Connection,
+    maybe_project: Option<&str>,
+) -> Result<(Transaction<'a>, ProjectConditions)> {
+    conn.pragma_update(None, "foreign_keys", "ON")?;
+    let conditions = match (is_multi_project(conn)?, maybe_project) {
+        (true, Some(project_id)) => {
+            let maybe_project_id = Some(project_id.to_owned());
+            let project_id = escape_sql_str_lit(project_id);
+            ProjectConditions {
+                maybe_project_id,
+                project: format!(" AND {} = {project_id}", escape_sql_ident(PROJECT_COL)),
+                target_project: format!(
+                    " AND {} = {project_id}",
+                    escape_sql_ident(TARGET_PROJECT_COL)
+                ),
+            }
+        }
+        (true, None) => {
+            bail!("the db stores multiple projects, hence the project to edit must be given")
+        }
+        (false, _) => ProjectConditions {
+            maybe_project_id: None,
+            project: String::new(),
+            target_project: String::new(),
+        },
+    };
+    Ok((conn.transaction()?, conditions))
+}
+
+/// Checks whether an element exists, in the project given by the condition
+fn element_exists(db_ta: &Transaction, id: &str, project_condition: &str) -> Result<bool> {
+    let exists = db_ta
+        .prepare(&format!(
+            "SELECT 1 FROM {} WHERE {} = ?1{project_condition}",
+            escape_sql_ident(&config().elements_table),
+            escape_sql_ident(ELEMENT_PK_COL),
+        ))?
+        .exists([id])?;
+    Ok(exists)
+}
+
+/// Updates the tables derived from the relations which the db maintains, after the relations of
+/// the elements selected by the query `changed_elements` changed
+///
+/// These are the relations using integer surrogate keys, the closure of relations, the owner
+/// column and the paths of elements.
+fn update_derived_tables(db_ta: &Transaction, changed_elements: &str) -> Result<()> {
+    if table_exists(db_ta, KEYED_RELATIONS_TABLE)? {
+        crate::surrogate_keys::update_surrogate_keys(db_ta, Some(changed_elements))?;
+    }
+    crate::closure::update_closures(db_ta, Some(changed_elements))?;
+    crate::owner::update_owner_column(db_ta, Some(changed_elements))?;
+    crate::paths::update_element_paths(db_ta)
+}
+
+/// Set a primitive attribute of an element, i.e. a column of the elements table
+///
+/// For `TEXT` columns, the value is taken verbatim. For other columns, it is interpreted as JSON
+/// value and converted the same way as by the importer, e.g. `true` becomes `1`. Without a value,
+/// the attribute is set to `NULL`.
+pub(crate) fn set_attribute(
+    conn: &mut Connection,
+    maybe_project: Option<&str>,
+    id: &str,
+    attribute: &str,
+    maybe_value: Option<&str>,
+) -> Result<()> {
+    let (db_ta, conditions) = begin_edit(conn, maybe_project)?;
+
+    let columns = get_table_columns(&db_ta, &config().elements_table)?;
+    let Some((_, column_type)) = columns
+        .iter()
+        .find(|(name, _)| name == attribute)
+        .filter(|(name, _)| ![ELEMENT_PK_COL, PROJECT_COL, OWNER_COL].contains(&name.as_str()))
+    else {
+        bail!("the elements table has no column {attribute:?} which could be set");
+    };
+    if !element_exists(&db_ta, id, &conditions.project)? {
+        bail!("there is no element with the id {id:?}");
+    }
+
+    let db_value = match maybe_value {
+        None => rusqlite::types::Value::Null,
+        Some(value) if *column_type == rusqlite::types::Type::Text => {
+            rusqlite::types::Value::Text(value.to_owned())
+        }
+        Some(value) => {
+            let json_value: Value = serde_json::from_str(value)
+                .map_err(|e| eyre::eyre!("{value:?} is no valid JSON value: {e}"))?;
+            let Some(db_value) = db_value_for_column(attribute, *column_type, Some(&json_value))?
+            else {
+                bail!("{value} can not be stored in the column {attribute:?}");
+            };
+            db_value
+        }
+    };
+
+    info!("setting {attribute:?} of the element {id:?}");
+    db_ta.execute(
+        &format!(
+            "UPDATE {} SET {} = ?2 WHERE {} = ?1{}",
+            escape_sql_ident(&config().elements_table),
+            escape_sql_ident(attribute),
+            escape_sql_ident(ELEMENT_PK_COL),
+            conditions.project
+        ),
+        (id, db_value),
+    )?;
+
+    db_ta.commit()?;
+    Ok(())
+}
+
+/// Delete an element, together with its extended properties and all relations from and to it
+///
+/// With `recursive`, all elements owned by the element, directly or indirectly, are deleted as well.
+/// Otherwise, the owned elements remain without owner. Returns the number of deleted elements.
+pub(crate) fn delete_element(
+    conn: &mut Connection,
+    maybe_project: Option<&str>,
+    id: &str,
+    recursive: bool,
+) -> Result<usize> {
+    let (db_ta, conditions) = begin_edit(conn, maybe_project)?;
+    if !element_exists(&db_ta, id, &conditions.project)? {
+        bail!("there is no element with the id {id:?}");
+    }
+
+    let config = config();
+    let elements_table = escape_sql_ident(&config.elements_table);
+    let relations_table = escape_sql_ident(&config.relations_table);
+    let extended_table = escape_sql_ident(&config.extended_table);
+    let pk_col = escape_sql_ident(ELEMENT_PK_COL);
+    let ProjectConditions {
+        project,
+        target_project,
+        ..
+    } = &conditions;
+
+    db_ta.execute_batch(r#"CREATE TEMPORARY TABLE "deleted_elements"("id" TEXT PRIMARY KEY)"#)?;
+    if recursive {
+        db_ta.execute(
+            &format!(
+                r#"WITH RECURSIVE "owned"("id") AS (
+                    SELECT ?1
+                    UNION
+                    SELECT r."target_id" FROM "owned" AS o
+                    JOIN {relations_table} AS r ON r."origin_id" = o."id"
+                    WHERE r."name" IN ({}, {}){project}{target_project}
+                )
+                INSERT INTO "deleted_elements" SELECT "id" FROM "owned""#,
+                escape_sql_str_lit(OWNED_ELEMENT_RELATION),
+                escape_sql_str_lit(OWNED_RELATIONSHIP_RELATION),
+            ),
+            [id],
+        )?;
+    } else {
+        db_ta.execute(r#"INSERT INTO "deleted_elements" VALUES (?1)"#, [id])?;
+    }
+
+    let relations = db_ta.execute(
+        &format!(
+            r#"DELETE FROM {relations_table}
+            WHERE ("origin_id" IN (SELECT "id" FROM "deleted_elements"){project})
+            OR ("target_id" IN (SELECT "id" FROM "deleted_elements"){target_project})"#
+        ),
+        (),
+    )?;
+    db_ta.execute(
+        &format!(
+            r#"DELETE FROM {extended_table} WHERE {pk_col} IN (SELECT "id" FROM "deleted_elements"){project}"#
+        ),
+        (),
+    )?;
+    let elements = db_ta.execute(
+        &format!(
+            r#"DELETE FROM {elements_table} WHERE {pk_col} IN (SELECT "id" FROM "deleted_elements"){project}"#
+        ),
+        (),
+    )?;
+    info!("deleted {elements} elements and {relations} relations");
+
+    update_derived_tables(&db_ta, r#"SELECT "id" FROM "deleted_elements""#)?;
+    db_ta.execute_batch(r#"DROP TABLE "deleted_elements""#)?;
+    db_ta.commit()?;
+    Ok(elements)
+}
+
+/// Checks whether a relation name is allowed for an element
+///
+/// The name must be a known relation name, i.e. listed in the relation names table or, in dbs
+/// predating that table, in use by some relation. If the db knows which properties each type may
+/// carry, the type of the origin must define the relation as well.
+fn check_relation_name(db_ta: &Transaction, name: &str, origin_id: &str) -> Result<()> {
+    let config = config();
+    let names_query = if table_exists(db_ta, RELATION_NAMES_TABLE)? {
+        format!(
+            r#"SELECT "name" FROM {} ORDER BY "name""#,
+            escape_sql_ident(RELATION_NAMES_TABLE)
+        )
+    } else {
+        format!(
+            r#"SELECT DISTINCT "name" FROM {} ORDER BY "name""#,
+            escape_sql_ident(&config.relations_table)
+        )
+    };
+    let known_names = db_ta
+        .prepare(&names_query)?
+        .query_map((), |row| row.get::<_, String>(0))?
+        .collect::<Result<Vec<_>, _>>()?;
+    if !known_names.iter().any(|n| n == name) {
+        debug!("the known relation names are:\n{known_names:#?}");
+        bail!("{name:?} is no known relation name");
+    }
+
+    if table_exists(db_ta, PROPERTY_DOMAINS_TABLE)? {
+        let domains_table = escape_sql_ident(PROPERTY_DOMAINS_TABLE);
+        let maybe_undefined_for: Option<String> = db_ta
+            .query_row(
+                &format!(
+                    r#"SELECT e."@type" FROM {} AS e
+                    WHERE e.{} = ?1
+                    AND EXISTS (SELECT 1 FROM {domains_table} AS d WHERE d."@type" = e."@type")
+                    AND NOT EXISTS (
+                        SELECT 1 FROM {domains_table} AS d
+                        WHERE d."@type" = e."@type" AND d."property" = ?2
+                    )"#,
+                    escape_sql_ident(&config.elements_table),
+                    escape_sql_ident(ELEMENT_PK_COL),
+                ),
+                (origin_id, name),
+                |row| row.get(0),
+            )
+            .optional()?;
+        if let Some(r#type) = maybe_undefined_for {
+            bail!("elements of type {type:?} do not carry the relation {name:?}");
+        }
+    }
+
+    Ok(())
+}
+
+/// Add a relation between two elements
+///
+/// The relation name is checked against the known relation names, see [`check_relation_name`]. In
+/// dbs storing multiple projects, the target may be an element of another project.
+pub(crate) fn add_relation(
+    conn: &mut Connection,
+    maybe_project: Option<&str>,
+    name: &str,
+    origin_id: &str,
+    target_id: &str,
+) -> Result<()> {
+    let (db_ta, conditions) = begin_edit(conn, maybe_project)?;
+    if !element_exists(&db_ta, origin_id, &conditions.project)? {
+        bail!("there is no element with the id {origin_id:?}");
+    }
+    // the target may be in any project
+    if !element_exists(&db_ta, target_id, "")? {
+        bail!("there is no element with the id {target_id:?}");
+    }
+    check_relation_name(&db_ta, name, origin_id)?;
+
+    let relations_table = escape_sql_ident(&config().relations_table);
+    let exists = db_ta
+        .prepare(&format!(
+            r#"SELECT 1 FROM {relations_table}
+            WHERE "name" = ?1 AND "origin_id" = ?2 AND "target_id" = ?3{}"#,
+            conditions.project
+        ))?
+        .exists((name, origin_id, target_id))?;
+    if exists {
+        bail!("the relation {origin_id:?} -{name}-> {target_id:?} exists already");
+    }
+
+    info!("adding the relation {origin_id:?} -{name}-> {target_id:?}");
+    match &conditions.maybe_project_id {
+        Some(project_id) => {
+            // the target is looked up in the same project first, and in the other projects after
+            db_ta.execute(
+                &format!(
+                    r#"INSERT INTO {relations_table}({}, "name", "origin_id", "target_id", {})
+                    VALUES (?4, ?1, ?2, ?3, ?4)"#,
+                    escape_sql_ident(PROJECT_COL),
+                    escape_sql_ident(TARGET_PROJECT_COL)
+                ),
+                (name, origin_id, target_id, project_id),
+            )?;
+            resolve_cross_project_targets(&db_ta)?;
+        }
+        None => {
+            db_ta.execute(
+                &format!(
+                    r#"INSERT INTO {relations_table}("name", "origin_id", "target_id")
+                    VALUES (?1, ?2, ?3)"#
+                ),
+                (name, origin_id, target_id),
+            )?;
+        }
+    }
+
+    update_derived_tables(&db_ta, &format!("SELECT {}", escape_sql_str_lit(origin_id)))?;
+    db_ta.commit()?;
+    Ok(())
+}
+
+/// Remove a relation between two elements
+pub(crate) fn remove_relation(
+    conn: &mut Connection,
+    maybe_project: Option<&str>,
+    name: &str,
+    origin_id: &str,
+    target_id: &str,
+) -> Result<()> {
+    let (db_ta, conditions) = begin_edit(conn, maybe_project)?;
+
+    info!("removing the relation {origin_id:?} -{name}-> {target_id:?}");
+    let removed = db_ta.execute(
+        &format!(
+            r#"DELETE FROM {} WHERE "name" = ?1 AND "origin_id" = ?2 AND "target_id" = ?3{}"#,
+            escape_sql_ident(&config().relations_table),
+            conditions.project
+        ),
+        (name, origin_id, target_id),
+    )?;
+    if removed == 0 {
+        bail!("there is no relation {origin_id:?} -{name}-> {target_id:?}");
+    }
+
+    update_derived_tables(&db_ta, &format!("SELECT {}", escape_sql_str_lit(origin_id)))?;
+    db_ta.commit()?;
+    Ok(())
+}
@@ -0,0 +1,247 @@
+//! Export of the model to formats of other tools
+//!
+//! Graph tools are served with the element/relation graph, which consists of one node per element,
+//! and one edge per row of the relations table. Each graph format is implemented in its own module,
+//! all of them work on the [`Graph`] read from the db, optionally narrowed down to a subgraph by a
+//! [`GraphFilter`]. Data pipelines are served with the tables themselves, see [`parquet`]. Diagram
+//! tools are served with structural views of single definitions, see [`plantuml`]. Browsers of
+//! SQLite dbs are served with a description of the db, see [`metadata`]. Bug reports are served
+//! with the model stripped of its content, see [`anonymized`].
+
+use std::{
+    collections::{HashMap, HashSet, VecDeque},
+    io::Write,
+};
+
+use eyre::Result;
+use rusqlite::Connection;
+
+use crate::{
+    cli::GraphFormat,
+    config::{ELEMENT_PK_COL, config},
+    query::{element_name_expr, resolve_element},
+    util::escape_sql_ident,
+};
+
+mod anonymized;
+mod cypher;
+mod dot;
+mod graphml;
+mod metadata;
+mod parquet;
+mod plantuml;
+mod turtle;
+mod xlsx;
+
+pub(crate) use anonymized::export_anonymized;
+pub(crate) use metadata::export_metadata;
+pub(crate) use parquet::export_parquet;
+pub(crate) use plantuml::export_plantuml;
+pub(crate) use xlsx::{Cell, Sheet, write_xlsx};
+
+/// An element, as node of the graph
+#[derive(Debug, Clone)]
+pub(crate) struct Node {
+    /// Id of the element
+    pub(crate) id: String,
+
+    /// Name of the element, empty if it has none
+    pub(crate) name: String,
+
+    /// Type of the element, empty if unknown
+    pub(crate) r#type: String,
+}
+
+impl Node {
+    /// The name of the element, or its type if it has no name
+    pub(crate) fn label(&self) -> &str {
+        if self.name.is_empty() {
+            &self.r#type
+        } else {
+            &self.name
+        }
+    }
+}
+
+/// A relation, as directed edge of the graph
+#[derive(Debug, Clone)]
+pub(crate) struct Edge {
+    /// Id of the element the relation originates from
+    pub(crate) origin_id: String,
+
+    /// Name of the relation
+    pub(crate) name: String,
+
+    /// Id of the element the relation points to
+    pub(crate) target_id: String,
+}
+
+/// The element/relation graph of a model
+#[derive(Debug, Clone, Default)]
+pub(crate) struct Graph {
+    /// The elements, ordered by id
+    pub(crate) nodes: Vec<Node>,
+
+    /// The relations, ordered by origin, name and target
+    pub(crate) edges: Vec<Edge>,
+}
+
+/// Restrictions of the exported graph to a subgraph
+#[derive(Debug, Clone, Default)]
+pub(crate) struct GraphFilter {
+    /// Id or qualified name of the element to start from, all elements are exported if absent
+    pub(crate) maybe_root: Option<String>,
+
+    /// Only export relations of these names, all if empty
+    pub(crate) relations: Vec<String>,
+
+    /// Only export elements of these types, all if empty
+    pub(crate) types: Vec<String>,
+
+    /// Maximum number of relations to follow from the root
+    pub(crate) maybe_max_depth: Option<usize>,
+}
+
+/// Export the element/relation graph of the db in the given format
+pub(crate) fn export_graph<W: Write>(
+    conn: &Connection,
+    format: GraphFormat,
+    filter: &GraphFilter,
+    out: &mut W,
+) -> Result<()> {
+    let maybe_root_id = filter
+        .maybe_root
+        .as_deref()
+        .map(|root| resolve_element(conn, root))
+        .transpose()?;
+    let graph = filter_graph(read_graph(conn)?, maybe_root_id.as_deref(), filter);
+    info!(
+        "exporting {} elements and {} relations",
+        graph.nodes.len(),
+        graph.edges.len()
+    );
+
+    match format {
+        GraphFormat::Graphml => graphml::write_graphml(&graph, out)?,
+        GraphFormat::Dot => dot::write_dot(&graph, out)?,
+        GraphFormat::Cypher => cypher::write_cypher(&graph, out)?,
+        GraphFormat::Turtle => turtle::write_turtle(&graph, out)?,
+    }
+
+    Ok(())
+}
+
+/// Reads all elements and relations of the db
+fn read_graph(conn: &Connection) -> Result<Graph> {
+    let config = config();
+    let elements_table = escape_sql_ident(&config.elements_table);
+    let relations_table = escape_sql_ident(&config.relations_table);
+    let pk_col = escape_sql_ident(ELEMENT_PK_COL);
+    let name = element_name_expr("e");
+
+    let mut stmt = conn.prepare(&format!(
+        r#"SELECT e.{pk_col}, {name}, COALESCE(e."@type", '') FROM {elements_table} AS e
+        ORDER BY e.{pk_col}"#
+    ))?;
+    let nodes = stmt
+        .query_map((), |row| {
+            Ok(Node {
+                id: row.get(0)?,
+                name: row.get(1)?,
+                r#type: row.get(2)?,
+            })
+        })?
+        .collect::<Result<_, _>>()?;
+
+    // relations to elements outside the db, e.g. of another project, have no node to point to
+    let mut stmt = conn.prepare(&format!(
+        r#"SELECT r."origin_id", r."name", r."target_id" FROM {relations_table} AS r
+        WHERE EXISTS (SELECT 1 FROM {elements_table} AS e WHERE e.{pk_col} = r."target_id")
+        ORDER BY r."origin_id", r."name", r."target_id""#
+    ))?;
+    let edges = stmt
+        .query_map((), |row| {
+            Ok(Edge {
+                origin_id: row.get(0)?,
+                name: row.get(1)?,
+                target_id: row.get(2)?,
+            })
+        })?
+        .collect::<Result<_, _>>()?;
+
+    Ok(Graph { nodes, edges })
+}
+
+/// Narrows the graph down to the subgraph selected by the filter
+///
+/// Only edges of the selected relation names are kept. With a root, only the elements reachable
+/// from it via these edges within the maximum depth are kept. Of the remaining elements, only those
+/// of the selected types are kept, together with the edges between them. The traversal continues
+/// through elements of other types.
+fn filter_graph(graph: Graph, maybe_root_id: Option<&str>, filter: &GraphFilter) -> Graph {
+    let Graph { nodes, mut edges } = graph;
+
+    if !filter.relations.is_empty() {
+        edges.retain(|edge| filter.relations.contains(&edge.name));
+    }
+
+    let maybe_reachable = maybe_root_id.map(|root_id| {
+        let mut successors: HashMap<&str, Vec<&str>> = HashMap::new();
+        for edge in &edges {
+            successors
+                .entry(edge.origin_id.as_str())
+                .or_default()
+                .push(edge.target_id.as_str());
+        }
+
+        let mut reachable = HashSet::from([root_id.to_owned()]);
+        let mut queue = VecDeque::from([(root_id, 0)]);
+        while let Some((id, depth)) = queue.pop_front() {
+            if filter
+                .maybe_max_depth
+                .is_some_and(|max_depth| depth >= max_depth)
+            {
+                continue;
+            }
+            for &successor in successors.get(id).into_iter().flatten() {
+                if reachable.insert(successor.to_owned()) {
+                    queue.push_back((successor, depth + 1));
+                }
+            }
+        }
+        reachable
+    });
+
+    let nodes: Vec<Node> = nodes
+        .into_iter()
+        .filter(|node| {
+            maybe_reachable
+                .as_ref()
+                .is_none_or(|reachable| reachable.contains(&node.id))
+        })
+        .filter(|node| filter.types.is_empty() || filter.types.contains(&node.r#type))
+        .collect();
+
+    let ids: HashSet<&str> = nodes.iter().map(|node| node.id.as_str()).collect();
+    edges.retain(|edge| {
+        ids.contains(edge.origin_id.as_str()) && ids.contains(edge.target_id.as_str())
+    });
+
+    Graph { nodes, edges }
+}
+
+/// Escape a string for use in XML text and attribute values
+fn escape_xml(s: &str) -> String {
+    let mut escaped = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&apos;"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
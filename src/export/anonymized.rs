@@ -0,0 +1,71 @@
+//! Export of the model stripped of its content, to attach to bug reports against this tool
+//!
+//! The elements are read back from the db as JSON, then their names and documentation are blanked
+//! and their ids pseudonymized, see [`crate::redact::anonymization`]. What remains is the structure
+//! of the model: the types of the elements, their relations and all other attributes, which is what
+//! most bugs of the import and the queries depend on.
+
+use std::{io::Write, path::Path};
+
+use eyre::{Result, bail};
+use rusqlite::Connection;
+
+use crate::{
+    cli::RedactMode,
+    db_json::db_elements_json,
+    import::{self, ImportOptions, ImportProvenance},
+    init_db, redact,
+    tweaks::open_db,
+};
+
+/// Export the elements of the db, anonymized, to `out`
+///
+/// The elements are written as JSON array, as `import-json` reads it, or if `as_db` is set imported
+/// into a new db with the embedded schema. Refuses to overwrite an existing file, unless `force` is
+/// set.
+pub(crate) fn export_anonymized(
+    conn: &Connection,
+    out: &Path,
+    as_db: bool,
+    force: bool,
+    redact_rules: &[(String, RedactMode)],
+) -> Result<()> {
+    if out.exists() {
+        if !force {
+            bail!("the file {out:?} already exists, refusing to overwrite it");
+        }
+        warn!("overwriting {out:?}");
+        std::fs::remove_file(out)?;
+    }
+
+    let anonymization = redact::anonymization(redact_rules)?;
+    let mut elements = Vec::new();
+    for element in db_elements_json(conn)? {
+        elements.extend(anonymization.apply(element)?);
+    }
+    // sorted by the pseudonyms, the order tells nothing about the original ids
+    elements.sort_by(|a, b| a.id.cmp(&b.id));
+    info!(
+        "exporting {} anonymized elements to {out:?}",
+        elements.len()
+    );
+
+    if as_db {
+        let mut out_conn = open_db(out)?;
+        init_db::init_db(&mut out_conn)?;
+        let options = ImportOptions {
+            provenance: ImportProvenance {
+                source: Some("export-anonymized".to_owned()),
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        import::import_from_slice(&elements, &mut out_conn, &options)?;
+    } else {
+        let mut writer = std::io::BufWriter::new(std::fs::File::create(out)?);
+        serde_json::to_writer(&mut writer, &elements)?;
+        writer.flush()?;
+    }
+
+    Ok(())
+}
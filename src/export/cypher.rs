@@ -0,0 +1,92 @@
+use std::{collections::BTreeMap, io::Write};
+
+use eyre::Result;
+
+use super::{Edge, Graph};
+
+/// Number of nodes or relationships created per statement
+const BATCH_SIZE: usize = 1000;
+
+/// Write the graph as Cypher script, e.g. to be run by `cypher-shell` against Neo4j
+///
+/// Each element becomes a node `(:Element {id, type, name})`, each relation a relationship whose
+/// type is the name of the relation. Elements without name have no `name` property. A uniqueness
+/// constraint on the id of the elements speeds up the creation of the relationships.
+pub(super) fn write_cypher<W: Write>(graph: &Graph, out: &mut W) -> Result<()> {
+    writeln!(
+        out,
+        "CREATE CONSTRAINT element_id IF NOT EXISTS FOR (e:Element) REQUIRE e.id IS UNIQUE;"
+    )?;
+
+    for batch in graph.nodes.chunks(BATCH_SIZE) {
+        writeln!(out, "UNWIND [")?;
+        for (idx, node) in batch.iter().enumerate() {
+            let name = if node.name.is_empty() {
+                "null".to_owned()
+            } else {
+                quote_cypher(&node.name)
+            };
+            writeln!(
+                out,
+                "  {{id: {}, type: {}, name: {name}}}{}",
+                quote_cypher(&node.id),
+                quote_cypher(&node.r#type),
+                if idx + 1 < batch.len() { "," } else { "" }
+            )?;
+        }
+        writeln!(
+            out,
+            "] AS e CREATE (:Element {{id: e.id, type: e.type, name: e.name}});"
+        )?;
+    }
+
+    // the type of a relationship can not be parameterized, hence one statement per relation name
+    let mut edges_by_name: BTreeMap<&str, Vec<&Edge>> = BTreeMap::new();
+    for edge in &graph.edges {
+        edges_by_name.entry(&edge.name).or_default().push(edge);
+    }
+    for (name, edges) in edges_by_name {
+        for batch in edges.chunks(BATCH_SIZE) {
+            writeln!(out, "UNWIND [")?;
+            for (idx, edge) in batch.iter().enumerate() {
+                writeln!(
+                    out,
+                    "  {{origin: {}, target: {}}}{}",
+                    quote_cypher(&edge.origin_id),
+                    quote_cypher(&edge.target_id),
+                    if idx + 1 < batch.len() { "," } else { "" }
+                )?;
+            }
+            writeln!(
+                out,
+                "] AS r MATCH (o:Element {{id: r.origin}}), (t:Element {{id: r.target}}) CREATE (o)-[:{}]->(t);",
+                escape_cypher_ident(name)
+            )?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Quote a string as Cypher string literal
+fn quote_cypher(s: &str) -> String {
+    let mut quoted = String::with_capacity(s.len() + 2);
+    quoted.push('\'');
+    for c in s.chars() {
+        match c {
+            '\'' => quoted.push_str("\\'"),
+            '\\' => quoted.push_str("\\\\"),
+            '\n' => quoted.push_str("\\n"),
+            '\r' => quoted.push_str("\\r"),
+            '\t' => quoted.push_str("\\t"),
+            c => quoted.push(c),
+        }
+    }
+    quoted.push('\'');
+    quoted
+}
+
+/// Escape a string to be used as Cypher identifier, e.g. as relationship type
+fn escape_cypher_ident(s: &str) -> String {
+    format!("`{}`", s.replace('`', "``"))
+}
@@ -0,0 +1,58 @@
+use std::io::Write;
+
+use eyre::Result;
+
+use super::Graph;
+
+/// Write the graph in the DOT language of Graphviz
+///
+/// Nodes are labelled by the name of their element above its type, edges by the name of their
+/// relation. Render e.g. via `dot -Tsvg`.
+pub(super) fn write_dot<W: Write>(graph: &Graph, out: &mut W) -> Result<()> {
+    writeln!(out, "digraph model {{")?;
+    writeln!(out, "  node [shape=box];")?;
+
+    for node in &graph.nodes {
+        let label = if node.name.is_empty() {
+            format!("«{}»", node.r#type)
+        } else {
+            format!("{}\n«{}»", node.name, node.r#type)
+        };
+        writeln!(
+            out,
+            "  {} [label={}];",
+            quote_dot(&node.id),
+            quote_dot(&label)
+        )?;
+    }
+
+    for edge in &graph.edges {
+        writeln!(
+            out,
+            "  {} -> {} [label={}];",
+            quote_dot(&edge.origin_id),
+            quote_dot(&edge.target_id),
+            quote_dot(&edge.name)
+        )?;
+    }
+
+    writeln!(out, "}}")?;
+
+    Ok(())
+}
+
+/// Quote a string as DOT identifier
+fn quote_dot(s: &str) -> String {
+    let mut quoted = String::with_capacity(s.len() + 2);
+    quoted.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => quoted.push_str("\\\""),
+            '\\' => quoted.push_str("\\\\"),
+            '\n' => quoted.push_str("\\n"),
+            c => quoted.push(c),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
@@ -0,0 +1,55 @@
+use std::io::Write;
+
+use eyre::Result;
+
+use super::{Graph, escape_xml};
+
+/// Write the graph as GraphML, as read by e.g. yEd and Gephi
+///
+/// Nodes carry the name of their element (or its type, if it has no name) as `label` and its type
+/// as `type`, edges the name of their relation as `label`.
+pub(super) fn write_graphml<W: Write>(graph: &Graph, out: &mut W) -> Result<()> {
+    writeln!(out, r#"<?xml version="1.0" encoding="UTF-8"?>"#)?;
+    writeln!(
+        out,
+        r#"<graphml xmlns="http://graphml.graphdrawing.org/xmlns" xmlns:xsi="http://www.w3.org/2001/XMLSchema-instance" xsi:schemaLocation="http://graphml.graphdrawing.org/xmlns http://graphml.graphdrawing.org/xmlns/1.0/graphml.xsd">"#
+    )?;
+    writeln!(
+        out,
+        r#"  <key id="label" for="node" attr.name="label" attr.type="string"/>"#
+    )?;
+    writeln!(
+        out,
+        r#"  <key id="type" for="node" attr.name="type" attr.type="string"/>"#
+    )?;
+    writeln!(
+        out,
+        r#"  <key id="relation" for="edge" attr.name="label" attr.type="string"/>"#
+    )?;
+    writeln!(out, r#"  <graph id="model" edgedefault="directed">"#)?;
+
+    for node in &graph.nodes {
+        writeln!(
+            out,
+            r#"    <node id="{}"><data key="label">{}</data><data key="type">{}</data></node>"#,
+            escape_xml(&node.id),
+            escape_xml(node.label()),
+            escape_xml(&node.r#type)
+        )?;
+    }
+
+    for edge in &graph.edges {
+        writeln!(
+            out,
+            r#"    <edge source="{}" target="{}"><data key="relation">{}</data></edge>"#,
+            escape_xml(&edge.origin_id),
+            escape_xml(&edge.target_id),
+            escape_xml(&edge.name)
+        )?;
+    }
+
+    writeln!(out, "  </graph>")?;
+    writeln!(out, "</graphml>")?;
+
+    Ok(())
+}
@@ -0,0 +1,274 @@
+//! Metadata describing the db to browsers like Datasette
+//!
+//! The metadata follows the `metadata.json` format of Datasette, see
+//! <https://docs.datasette.io/en/stable/metadata.html>: each table and view present in the db gets a
+//! description, the columns of the core tables get the definitions of the JSON schema they stem
+//! from, as far as recorded in the schema meta table, and a few canned queries cover the most
+//! common lookups.
+
+use std::{collections::BTreeMap, io::Write};
+
+use eyre::Result;
+use rusqlite::Connection;
+use serde_json::{Value, json};
+
+use crate::{
+    config::{
+        BRANCHES_TABLE, CLOSURE_RELATIONS_TABLE, CLOSURE_TABLE, ELEMENT_KEYS_TABLE,
+        ELEMENT_PATHS_TABLE, ELEMENT_PK_COL, ENUM_TABLE_PREFIX, FTS_TABLE, IMPORT_LOG_TABLE,
+        KEYED_RELATIONS_TABLE, OWNED_ELEMENT_RELATION, OWNER_COL, PROJECT_COL,
+        PROPERTY_DOMAINS_TABLE, RELATION_NAMES_TABLE, SCHEMA_INFO_TABLE, SCHEMA_META_TABLE,
+        TARGET_PROJECT_COL, TYPE_COL, VALIDATION_RULES_TABLE, config,
+    },
+    import::get_table_columns,
+    util::{escape_sql_ident, escape_sql_str_lit, table_exists},
+};
+
+/// Descriptions of the views generated from the JSON schema, apart from the per type views
+const VIEW_DESCRIPTIONS: &[(&str, &str)] = &[
+    (
+        "requirements",
+        "Requirements with their id and the text of their documentation",
+    ),
+    (
+        "parts_tree",
+        "Parts nested in parts, with their parent and depth in the ownership tree",
+    ),
+    (
+        "connections",
+        "Connections with the elements at their source and target end",
+    ),
+    (
+        "allocations",
+        "Allocations with the elements allocated from and to",
+    ),
+    (
+        "documentation_texts",
+        "Documentation comments with the element they document",
+    ),
+];
+
+/// Print the metadata describing the db as Datasette `metadata.json`
+///
+/// `db_name` is the name Datasette serves the db under, i.e. its file name without extension.
+pub(crate) fn export_metadata<W: Write>(
+    conn: &Connection,
+    db_name: &str,
+    maybe_title: Option<&str>,
+    out: &mut W,
+) -> Result<()> {
+    let mut tables = serde_json::Map::new();
+    let mut stmt = conn.prepare(
+        "SELECT type, name FROM sqlite_schema WHERE type IN ('table', 'view')
+        AND name NOT LIKE 'sqlite_%' ORDER BY name",
+    )?;
+    let objects = stmt
+        .query_map([], |row| {
+            Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?))
+        })?
+        .collect::<Result<Vec<_>, _>>()?;
+    for (ty, name) in objects {
+        // the shadow tables of the full-text index are an implementation detail
+        if name.starts_with(&format!("{FTS_TABLE}_")) {
+            continue;
+        }
+        let mut table = serde_json::Map::new();
+        if let Some(description) = table_description(&name, &ty) {
+            table.insert("description".to_owned(), description.into());
+        }
+        let columns = column_descriptions(conn, &name)?;
+        if !columns.is_empty() {
+            table.insert("columns".to_owned(), json!(columns));
+        }
+        if name == FTS_TABLE {
+            table.insert("hidden".to_owned(), true.into());
+        }
+        tables.insert(name, table.into());
+    }
+
+    let metadata = json!({
+        "title": maybe_title.unwrap_or(db_name),
+        "description": "SysML v2 model, imported by sysml-v2-sql",
+        "databases": {
+            db_name: {
+                "tables": tables,
+                "queries": canned_queries(conn)?,
+            }
+        }
+    });
+    writeln!(out, "{}", serde_json::to_string_pretty(&metadata)?)?;
+
+    Ok(())
+}
+
+/// Description of a table or view, if it is known
+fn table_description(name: &str, ty: &str) -> Option<String> {
+    let config = config();
+    let description = match name {
+        n if n == config.elements_table => {
+            "All elements of the model, one row per element with one column per property holding a single value"
+        }
+        n if n == config.relations_table => {
+            "All references between elements, one row per referenced element, named after the referencing property"
+        }
+        n if n == config.extended_table => {
+            "Properties holding multiple primitive values, one row per value"
+        }
+        n if n == RELATION_NAMES_TABLE => "Names of the properties referencing elements",
+        n if n == PROPERTY_DOMAINS_TABLE => "The element types which may carry each property",
+        n if n == SCHEMA_META_TABLE => {
+            "The definitions of the JSON schema each property stems from, and how it is stored"
+        }
+        n if n == SCHEMA_INFO_TABLE => "Fingerprint and origin of the schema of the db",
+        n if n == IMPORT_LOG_TABLE => "Provenance of each import into the db",
+        n if n == BRANCHES_TABLE => "Branches of the projects cloned from an API server",
+        n if n == VALIDATION_RULES_TABLE => "User-defined validation rules, as SQL queries",
+        n if n == ELEMENT_KEYS_TABLE => "Integer surrogate key of each element",
+        n if n == KEYED_RELATIONS_TABLE => "The relations, referring to the integer surrogate keys",
+        n if n == CLOSURE_RELATIONS_TABLE => {
+            "Names of the relations whose transitive closure is maintained"
+        }
+        n if n == CLOSURE_TABLE => {
+            "Transitive closure of the relations, one row per pair of elements connected by a chain of relations"
+        }
+        n if n == ELEMENT_PATHS_TABLE => {
+            "Ownership path of each element, the ids of its owners from the root down, separated by /"
+        }
+        n if n == FTS_TABLE => "Full-text index over the names and bodies of the elements",
+        n => {
+            if let Some(column) = n.strip_prefix(ENUM_TABLE_PREFIX) {
+                return Some(format!("Legal values of the column {column:?}"));
+            }
+            if let Some((_, description)) = VIEW_DESCRIPTIONS.iter().find(|(v, _)| *v == n) {
+                return Some((*description).to_owned());
+            }
+            // the per type views are named after the type they select
+            if ty == "view" && n.starts_with(|c: char| c.is_ascii_uppercase()) {
+                return Some(format!(
+                    "Elements of the type {n}, with the columns this type defines"
+                ));
+            }
+            return None;
+        }
+    };
+    Some(description.to_owned())
+}
+
+/// Descriptions of the columns of a table, for the core tables only
+fn column_descriptions(conn: &Connection, table: &str) -> Result<BTreeMap<String, String>> {
+    let config = config();
+    let is_core_table = [
+        config.elements_table.as_str(),
+        config.relations_table.as_str(),
+        config.extended_table.as_str(),
+    ]
+    .contains(&table);
+    if !is_core_table {
+        return Ok(BTreeMap::new());
+    }
+
+    // the schema meta table is only generated along with the SQL schema
+    let mut definitions = BTreeMap::new();
+    if table_exists(conn, SCHEMA_META_TABLE)? {
+        let mut stmt = conn.prepare(&format!(
+            r#"SELECT "property", "definitions" FROM {} WHERE "table" = ?1"#,
+            escape_sql_ident(SCHEMA_META_TABLE)
+        ))?;
+        let mut rows = stmt.query([table])?;
+        while let Some(row) = rows.next()? {
+            let property: String = row.get(0)?;
+            let defs: String = row.get(1)?;
+            let defs: Vec<String> = serde_json::from_str(&defs)?;
+            definitions.insert(property, defs.join(", "));
+        }
+    }
+
+    let mut columns = BTreeMap::new();
+    for (column, _) in get_table_columns(conn, table)? {
+        let description = match column.as_str() {
+            ELEMENT_PK_COL => "Id of the element".to_owned(),
+            TYPE_COL => "Type of the element, e.g. PartUsage".to_owned(),
+            PROJECT_COL => "Project the row belongs to".to_owned(),
+            TARGET_PROJECT_COL => "Project of the referenced element".to_owned(),
+            OWNER_COL => "Id of the owner of the element".to_owned(),
+            "name" if table == config.relations_table => {
+                "Name of the referencing property".to_owned()
+            }
+            "origin_id" => "Id of the referencing element".to_owned(),
+            "target_id" => "Id of the referenced element".to_owned(),
+            property => match definitions.get(property) {
+                Some(defs) => format!("Property {property} of {defs}"),
+                None => continue,
+            },
+        };
+        columns.insert(column, description);
+    }
+    Ok(columns)
+}
+
+/// Canned queries for the most common lookups, using the named parameters Datasette asks for
+fn canned_queries(conn: &Connection) -> Result<Value> {
+    let config = config();
+    let elements = escape_sql_ident(&config.elements_table);
+    let relations = escape_sql_ident(&config.relations_table);
+    let pk = escape_sql_ident(ELEMENT_PK_COL);
+    let ty = escape_sql_ident(TYPE_COL);
+    let has_name = get_table_columns(conn, &config.elements_table)?
+        .iter()
+        .any(|(n, _)| n == "declaredName");
+
+    let mut queries = serde_json::Map::new();
+    let mut query = |name: &str, title: &str, sql: String| {
+        queries.insert(name.to_owned(), json!({ "title": title, "sql": sql }));
+    };
+    query(
+        "element",
+        "Element by id",
+        format!("SELECT * FROM {elements} WHERE {pk} = :id"),
+    );
+    query(
+        "elements_per_type",
+        "Number of elements per type",
+        format!(
+            r#"SELECT {ty}, count(*) AS "elements" FROM {elements} GROUP BY {ty} ORDER BY 2 DESC"#
+        ),
+    );
+    query(
+        "outgoing_relations",
+        "Elements referenced by an element",
+        format!(
+            r#"SELECT r."name", r."target_id" FROM {relations} AS r WHERE r."origin_id" = :id ORDER BY 1, 2"#
+        ),
+    );
+    query(
+        "incoming_relations",
+        "Elements referencing an element",
+        format!(
+            r#"SELECT r."name", r."origin_id" FROM {relations} AS r WHERE r."target_id" = :id ORDER BY 1, 2"#
+        ),
+    );
+    if has_name {
+        query(
+            "owned_elements",
+            "Elements owned by an element",
+            format!(
+                r#"SELECT e.{pk}, e.{ty}, e."declaredName" FROM {relations} AS r
+JOIN {elements} AS e ON e.{pk} = r."target_id"
+WHERE r."origin_id" = :id AND r."name" = {}
+ORDER BY e."declaredName""#,
+                escape_sql_str_lit(OWNED_ELEMENT_RELATION)
+            ),
+        );
+        query(
+            "elements_by_name",
+            "Elements whose name contains a text",
+            format!(
+                r#"SELECT {pk}, {ty}, "declaredName" FROM {elements}
+WHERE "declaredName" LIKE '%' || :name || '%'
+ORDER BY "declaredName""#
+            ),
+        );
+    }
+
+    Ok(queries.into())
+}
@@ -0,0 +1,456 @@
+//! Minimal writer of Parquet files
+//!
+//! Each table is written in row groups of a bounded number of rows, each column of a row group in
+//! data pages of a bounded size. Values are stored uncompressed in the `PLAIN` encoding, which every
+//! Parquet reader supports. All columns are optional, their definition levels are stored in the
+//! `RLE` encoding. The metadata is serialized in the Thrift compact protocol, as the format
+//! prescribes. See <https://parquet.apache.org/docs/file-format/> for the format.
+
+use std::{
+    fs::File,
+    io::{BufWriter, Write},
+    path::Path,
+};
+
+use eyre::{Result, bail};
+use rusqlite::{Connection, types::ValueRef};
+
+use crate::{
+    config::config,
+    import::get_table_columns,
+    util::{escape_sql_ident, table_exists},
+};
+
+/// Magic number at the start and the end of each Parquet file
+const MAGIC: &[u8] = b"PAR1";
+
+/// Physical types of Parquet
+const TYPE_INT64: i32 = 2;
+const TYPE_DOUBLE: i32 = 5;
+const TYPE_BYTE_ARRAY: i32 = 6;
+
+/// Converted type marking a byte array as UTF-8 string
+const CONVERTED_TYPE_UTF8: i32 = 0;
+
+/// Repetition type of columns which may be null
+const REPETITION_OPTIONAL: i32 = 1;
+
+/// Encodings of Parquet
+const ENCODING_PLAIN: i32 = 0;
+const ENCODING_RLE: i32 = 3;
+
+/// Page type of data pages
+const PAGE_TYPE_DATA_PAGE: i32 = 0;
+
+/// Compression codec of uncompressed data
+const CODEC_UNCOMPRESSED: i32 = 0;
+
+/// Write the elements, relations and extended properties tables as Parquet files into a directory
+///
+/// Each table is written to a file named after it, with the extension `.parquet`. The column types
+/// are preserved: `INTEGER` columns become `INT64`, `REAL` columns `DOUBLE`, `TEXT` columns UTF-8
+/// strings and `BLOB` columns byte arrays. Refuses to overwrite existing files, unless `force` is
+/// set.
+pub(crate) fn export_parquet(conn: &Connection, dir: &Path, force: bool) -> Result<()> {
+    let config = config();
+    let tables = [
+        &config.elements_table,
+        &config.relations_table,
+        &config.extended_table,
+    ];
+
+    std::fs::create_dir_all(dir)?;
+    for table in tables {
+        if !table_exists(conn, table)? {
+            warn!("skipping the table {table:?}, which does not exist");
+            continue;
+        }
+
+        let path = dir.join(format!("{table}.parquet"));
+        if path.exists() {
+            if !force {
+                bail!("the file {path:?} already exists, refusing to overwrite it");
+            }
+            warn!("overwriting {path:?}");
+        }
+
+        info!("writing the table {table:?} to {path:?}");
+        let now = std::time::Instant::now();
+        let mut out = BufWriter::new(File::create(&path)?);
+        let rows = write_table(conn, table, &mut out)?;
+        out.flush()?;
+        info!("wrote {rows} rows in {:?}", now.elapsed());
+    }
+
+    Ok(())
+}
+
+/// Maximal number of rows per row group, which bounds the rows buffered while writing a table
+const ROW_GROUP_ROWS: usize = 16 * 1024;
+
+/// Size of the values at which a data page is completed and the next one begun
+const PAGE_SIZE: usize = 1024 * 1024;
+
+/// The values of a column, encoded into the data pages of the current row group
+struct ColumnData {
+    /// Name of the column
+    name: String,
+
+    /// Physical Parquet type of the column
+    physical_type: i32,
+
+    /// Whether the byte arrays of the column are UTF-8 strings
+    is_utf8: bool,
+
+    /// Definition level of each row of the current page, 0 for null and 1 for a value
+    definition_levels: Vec<bool>,
+
+    /// The non-null values of the current page in the `PLAIN` encoding
+    values: Vec<u8>,
+
+    /// The completed pages of the current row group, each with its header
+    chunk: Vec<u8>,
+}
+
+impl ColumnData {
+    /// Appends a value of the column
+    fn push(&mut self, value: ValueRef) -> Result<()> {
+        let is_byte_array = self.physical_type == TYPE_BYTE_ARRAY;
+        match value {
+            ValueRef::Null => {
+                self.definition_levels.push(false);
+                return Ok(());
+            }
+            ValueRef::Integer(i) if self.physical_type == TYPE_INT64 => {
+                self.values.extend_from_slice(&i.to_le_bytes())
+            }
+            ValueRef::Real(r) if self.physical_type == TYPE_DOUBLE => {
+                self.values.extend_from_slice(&r.to_le_bytes())
+            }
+            ValueRef::Integer(i) if self.physical_type == TYPE_DOUBLE => {
+                self.values.extend_from_slice(&(i as f64).to_le_bytes())
+            }
+            ValueRef::Text(bytes) | ValueRef::Blob(bytes) if is_byte_array => {
+                self.push_bytes(bytes)?
+            }
+
+            // columns without declared type hold values of any type, which are exported as text
+            ValueRef::Integer(i) if is_byte_array => self.push_bytes(i.to_string().as_bytes())?,
+            ValueRef::Real(r) if is_byte_array => self.push_bytes(r.to_string().as_bytes())?,
+            v => bail!(
+                "the column {:?} contains the value {v:?}, which does not match its type",
+                self.name
+            ),
+        }
+        self.definition_levels.push(true);
+
+        if self.values.len() >= PAGE_SIZE {
+            self.finish_page()?;
+        }
+        Ok(())
+    }
+
+    /// Appends a byte array in the `PLAIN` encoding, prefixed by its length
+    fn push_bytes(&mut self, bytes: &[u8]) -> Result<()> {
+        let Ok(len) = u32::try_from(bytes.len()) else {
+            bail!("a value of the column {:?} is too large", self.name);
+        };
+        self.values.extend_from_slice(&len.to_le_bytes());
+        self.values.extend_from_slice(bytes);
+        Ok(())
+    }
+
+    /// Completes the current page, appending it with its header to the chunk of the row group
+    fn finish_page(&mut self) -> Result<()> {
+        if self.definition_levels.is_empty() {
+            return Ok(());
+        }
+
+        // the definition levels are encoded as runs of equal levels, with a bit width of 1
+        let mut levels = Vec::new();
+        for run in self.definition_levels.chunk_by(|a, b| a == b) {
+            write_varint(&mut levels, (run.len() as u64) << 1);
+            levels.push(run[0] as u8);
+        }
+        let page_size = 4 + levels.len() + self.values.len();
+        let Ok(page_size) = i32::try_from(page_size) else {
+            bail!("a page of the column {:?} is too large", self.name);
+        };
+
+        let mut header = ThriftWriter::default();
+        header.field_i32(1, PAGE_TYPE_DATA_PAGE);
+        header.field_i32(2, page_size);
+        header.field_i32(3, page_size);
+        header.field_struct_begin(5);
+        header.field_i32(1, i32::try_from(self.definition_levels.len())?);
+        header.field_i32(2, ENCODING_PLAIN);
+        header.field_i32(3, ENCODING_RLE);
+        header.field_i32(4, ENCODING_RLE);
+        header.struct_end();
+        header.struct_end();
+
+        self.chunk.extend_from_slice(&header.buf);
+        self.chunk
+            .extend_from_slice(&u32::try_from(levels.len())?.to_le_bytes());
+        self.chunk.extend_from_slice(&levels);
+        self.chunk.extend_from_slice(&self.values);
+        self.definition_levels.clear();
+        self.values.clear();
+        Ok(())
+    }
+}
+
+/// A row group written to the file
+struct RowGroup {
+    /// Offset and size of the chunk of each column
+    chunks: Vec<(i64, i64)>,
+
+    /// Number of rows of the row group
+    num_rows: usize,
+}
+
+/// Write the buffered rows of all columns as row group, returning its column chunks
+fn write_row_group<W: Write>(
+    columns: &mut [ColumnData],
+    num_rows: usize,
+    offset: &mut i64,
+    out: &mut W,
+) -> Result<RowGroup> {
+    let mut chunks = Vec::with_capacity(columns.len());
+    for column in columns {
+        column.finish_page()?;
+        out.write_all(&column.chunk)?;
+        let size = i64::try_from(column.chunk.len())?;
+        chunks.push((*offset, size));
+        *offset += size;
+        column.chunk.clear();
+    }
+    Ok(RowGroup { chunks, num_rows })
+}
+
+/// Write a table as Parquet file, returning the number of rows written
+///
+/// The rows are written in row groups of at most [`ROW_GROUP_ROWS`] rows, each column of a row
+/// group in pages of about [`PAGE_SIZE`] bytes, so that only one row group is held in memory.
+fn write_table<W: Write>(conn: &Connection, table: &str, out: &mut W) -> Result<usize> {
+    let mut columns = Vec::new();
+    for (name, ty) in get_table_columns(conn, table)? {
+        let physical_type = match ty {
+            rusqlite::types::Type::Integer => TYPE_INT64,
+            rusqlite::types::Type::Real => TYPE_DOUBLE,
+            _ => TYPE_BYTE_ARRAY,
+        };
+        columns.push(ColumnData {
+            name,
+            physical_type,
+            is_utf8: ty == rusqlite::types::Type::Text,
+            definition_levels: Vec::new(),
+            values: Vec::new(),
+            chunk: Vec::new(),
+        });
+    }
+
+    let mut offset = i64::try_from(MAGIC.len())?;
+    out.write_all(MAGIC)?;
+
+    // the columns are named explicitly, as the view of a selected project may add the rowid
+    let mut stmt = conn.prepare(&format!(
+        "SELECT {} FROM {}",
+        columns
+            .iter()
+            .map(|c| escape_sql_ident(&c.name))
+            .collect::<Vec<_>>()
+            .join(", "),
+        escape_sql_ident(table)
+    ))?;
+    let mut rows = stmt.query(())?;
+    let mut row_groups = Vec::new();
+    let mut num_rows = 0;
+    let mut group_rows = 0;
+    while let Some(row) = rows.next()? {
+        for (idx, column) in columns.iter_mut().enumerate() {
+            column.push(row.get_ref(idx)?)?;
+        }
+        num_rows += 1;
+        group_rows += 1;
+        if group_rows == ROW_GROUP_ROWS {
+            row_groups.push(write_row_group(&mut columns, group_rows, &mut offset, out)?);
+            group_rows = 0;
+        }
+    }
+    if group_rows > 0 {
+        row_groups.push(write_row_group(&mut columns, group_rows, &mut offset, out)?);
+    }
+
+    let mut metadata = ThriftWriter::default();
+    metadata.field_i32(1, 1);
+
+    metadata.field_list_begin(2, ThriftWriter::STRUCT, columns.len() + 1);
+    metadata.field_binary(4, b"schema");
+    metadata.field_i32(5, i32::try_from(columns.len())?);
+    metadata.struct_end();
+    for column in &columns {
+        metadata.field_i32(1, column.physical_type);
+        metadata.field_i32(3, REPETITION_OPTIONAL);
+        metadata.field_binary(4, column.name.as_bytes());
+        if column.is_utf8 {
+            metadata.field_i32(6, CONVERTED_TYPE_UTF8);
+        }
+        metadata.struct_end();
+    }
+
+    metadata.field_i64(3, i64::try_from(num_rows)?);
+
+    metadata.field_list_begin(4, ThriftWriter::STRUCT, row_groups.len());
+    for row_group in &row_groups {
+        let group_rows = i64::try_from(row_group.num_rows)?;
+        metadata.field_list_begin(1, ThriftWriter::STRUCT, columns.len());
+        for (column, (chunk_offset, chunk_size)) in columns.iter().zip(&row_group.chunks) {
+            metadata.field_i64(2, *chunk_offset);
+            metadata.field_struct_begin(3);
+            metadata.field_i32(1, column.physical_type);
+            metadata.field_list_begin(2, ThriftWriter::I32, 2);
+            metadata.write_i32(ENCODING_PLAIN);
+            metadata.write_i32(ENCODING_RLE);
+            metadata.field_list_begin(3, ThriftWriter::BINARY, 1);
+            metadata.write_binary(column.name.as_bytes());
+            metadata.field_i32(4, CODEC_UNCOMPRESSED);
+            metadata.field_i64(5, group_rows);
+            metadata.field_i64(6, *chunk_size);
+            metadata.field_i64(7, *chunk_size);
+            metadata.field_i64(9, *chunk_offset);
+            metadata.struct_end();
+            metadata.struct_end();
+        }
+        metadata.field_i64(2, row_group.chunks.iter().map(|(_, size)| size).sum());
+        metadata.field_i64(3, group_rows);
+        metadata.struct_end();
+    }
+
+    metadata.field_binary(
+        6,
+        format!(
+            "{} version {}",
+            env!("CARGO_PKG_NAME"),
+            env!("CARGO_PKG_VERSION")
+        )
+        .as_bytes(),
+    );
+    metadata.struct_end();
+
+    out.write_all(&metadata.buf)?;
+    out.write_all(&u32::try_from(metadata.buf.len())?.to_le_bytes())?;
+    out.write_all(MAGIC)?;
+
+    Ok(num_rows)
+}
+
+/// Serializer for the Thrift compact protocol, as far as needed for the Parquet metadata
+///
+/// Structs are written field by field, ended by [`ThriftWriter::struct_end`]. The elements of a
+/// list of structs are each ended the same way, without a begin.
+#[derive(Default)]
+struct ThriftWriter {
+    /// The serialized bytes
+    buf: Vec<u8>,
+
+    /// Id of the last field written, per struct nesting level
+    last_field_ids: Vec<i16>,
+
+    /// Id of the last field written in the current struct
+    last_field_id: i16,
+}
+
+impl ThriftWriter {
+    /// Compact protocol type ids
+    const I32: u8 = 5;
+    const I64: u8 = 6;
+    const BINARY: u8 = 8;
+    const LIST: u8 = 9;
+    const STRUCT: u8 = 12;
+
+    /// Writes the header of a field, encoding the id as delta to the previous field if possible
+    fn field_header(&mut self, id: i16, ty: u8) {
+        let delta = id - self.last_field_id;
+        if (1..=15).contains(&delta) {
+            self.buf.push(((delta as u8) << 4) | ty);
+        } else {
+            self.buf.push(ty);
+            write_varint(&mut self.buf, zigzag(i64::from(id)));
+        }
+        self.last_field_id = id;
+    }
+
+    fn field_i32(&mut self, id: i16, value: i32) {
+        self.field_header(id, Self::I32);
+        self.write_i32(value);
+    }
+
+    fn field_i64(&mut self, id: i16, value: i64) {
+        self.field_header(id, Self::I64);
+        write_varint(&mut self.buf, zigzag(value));
+    }
+
+    fn field_binary(&mut self, id: i16, value: &[u8]) {
+        self.field_header(id, Self::BINARY);
+        self.write_binary(value);
+    }
+
+    /// Begins a struct valued field, ended by [`ThriftWriter::struct_end`]
+    fn field_struct_begin(&mut self, id: i16) {
+        self.field_header(id, Self::STRUCT);
+        self.last_field_ids.push(self.last_field_id);
+        self.last_field_id = 0;
+    }
+
+    /// Begins a list valued field, followed by its elements
+    ///
+    /// Each struct element is ended by [`ThriftWriter::struct_end`], other elements are written
+    /// via the `write_` functions.
+    fn field_list_begin(&mut self, id: i16, element_type: u8, len: usize) {
+        self.field_header(id, Self::LIST);
+        if len < 15 {
+            self.buf.push(((len as u8) << 4) | element_type);
+        } else {
+            self.buf.push(0xf0 | element_type);
+            write_varint(&mut self.buf, len as u64);
+        }
+        if element_type == Self::STRUCT && len > 0 {
+            // each element starts afresh, see [`ThriftWriter::struct_end`]
+            self.last_field_ids.push(self.last_field_id);
+            self.last_field_ids
+                .extend(std::iter::repeat_n(0, len.saturating_sub(1)));
+            self.last_field_id = 0;
+        }
+    }
+
+    /// Ends a struct, i.e. a struct valued field, an element of a list of structs, or the outermost
+    /// struct
+    fn struct_end(&mut self) {
+        self.buf.push(0);
+        self.last_field_id = self.last_field_ids.pop().unwrap_or_default();
+    }
+
+    fn write_i32(&mut self, value: i32) {
+        write_varint(&mut self.buf, zigzag(i64::from(value)));
+    }
+
+    fn write_binary(&mut self, value: &[u8]) {
+        write_varint(&mut self.buf, value.len() as u64);
+        self.buf.extend_from_slice(value);
+    }
+}
+
+/// Maps signed to unsigned integers, such that small magnitudes yield small numbers
+fn zigzag(value: i64) -> u64 {
+    ((value << 1) ^ (value >> 63)) as u64
+}
+
+/// Writes an unsigned LEB128 varint
+fn write_varint(buf: &mut Vec<u8>, mut value: u64) {
+    while value >= 0x80 {
+        buf.push((value as u8) | 0x80);
+        value >>= 7;
+    }
+    buf.push(value as u8);
+}
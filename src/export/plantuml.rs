@@ -0,0 +1,469 @@
+use std::{collections::HashSet, io::Write};
+
+use eyre::Result;
+use rusqlite::{Connection, Statement};
+
+use crate::{
+    config::{ELEMENT_PK_COL, OWNED_ELEMENT_RELATION, config},
+    query::{element_name_expr, resolve_element},
+    util::{escape_sql_ident, escape_sql_str_lit},
+};
+
+/// Types of the features shown as nested parts
+const PART_TYPES: [&str; 1] = ["PartUsage"];
+
+/// Types of the features shown as ports
+const PORT_TYPES: [&str; 1] = ["PortUsage"];
+
+/// Types of the features shown as connections between parts or ports
+const CONNECTION_TYPES: [&str; 5] = [
+    "ConnectionUsage",
+    "InterfaceUsage",
+    "FlowConnectionUsage",
+    "SuccessionFlowConnectionUsage",
+    "BindingConnectorAsUsage",
+];
+
+/// Relation from a usage to its definitions
+const DEFINITION_RELATION: &str = "definition";
+
+/// Relations from a connection to the features it connects
+const CONNECTION_END_RELATIONS: [&str; 2] = ["sourceFeature", "targetFeature"];
+
+/// Relation from a feature chain (e.g. `engine.shaft`) to the features it consists of
+const CHAINING_FEATURE_RELATION: &str = "chainingFeature";
+
+/// How a feature is drawn
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Kind {
+    Part,
+    Port,
+    Connection,
+}
+
+/// A feature placed in the diagram
+///
+/// As features of a definition are shown within each usage of that definition, the same element
+/// may be placed multiple times.
+#[derive(Debug, Clone)]
+struct Feature {
+    id: String,
+    name: String,
+    r#type: String,
+    kind: Kind,
+
+    /// Names of the definitions of the feature
+    definitions: Vec<String>,
+
+    /// Index of the feature the feature is placed in, `None` for the root
+    parent: Option<usize>,
+
+    /// Indices of the parts and ports placed in this feature
+    children: Vec<usize>,
+}
+
+/// Prepared statements to explore the structure
+struct Statements<'conn> {
+    /// Yields the id, name and type of the structural features owned by an element
+    owned_features: Statement<'conn>,
+
+    /// Yields the id and name of the definitions of a usage
+    definitions: Statement<'conn>,
+
+    /// Yields the relation name and id of the features connected by a connection
+    connection_ends: Statement<'conn>,
+
+    /// Yields the ids of the features a feature chain consists of
+    chaining_features: Statement<'conn>,
+}
+
+/// Export the structure of a definition (or usage) as PlantUML component diagram
+///
+/// The structure is derived from ownership and typing: the parts, ports and connections owned by
+/// the root are shown nested in it, each part in turn with its own parts and ports. A part owning
+/// no parts or ports itself is shown with those of its definitions instead. Connections are drawn
+/// between the parts or ports they connect, as long as these are shown in the diagram.
+pub(crate) fn export_plantuml<W: Write>(
+    conn: &Connection,
+    root: &str,
+    maybe_max_depth: Option<usize>,
+    out: &mut W,
+) -> Result<()> {
+    let elements_table = escape_sql_ident(&config().elements_table);
+    let relations_table = escape_sql_ident(&config().relations_table);
+    let pk_col = escape_sql_ident(ELEMENT_PK_COL);
+    let owned_element = escape_sql_str_lit(OWNED_ELEMENT_RELATION);
+    let definition = escape_sql_str_lit(DEFINITION_RELATION);
+    let chaining_feature = escape_sql_str_lit(CHAINING_FEATURE_RELATION);
+    let structural_types = PART_TYPES
+        .iter()
+        .chain(&PORT_TYPES)
+        .chain(&CONNECTION_TYPES)
+        .map(escape_sql_str_lit)
+        .collect::<Vec<_>>()
+        .join(", ");
+    let connection_end_relations = CONNECTION_END_RELATIONS
+        .iter()
+        .map(escape_sql_str_lit)
+        .collect::<Vec<_>>()
+        .join(", ");
+    let name = element_name_expr("e");
+
+    let mut statements = Statements {
+        owned_features: conn.prepare(&format!(
+            r#"SELECT e.{pk_col}, {name}, e."@type" FROM {relations_table} AS r
+            JOIN {elements_table} AS e ON e.{pk_col} = r."target_id"
+            WHERE r."name" = {owned_element} AND r."origin_id" = ?1
+                AND e."@type" IN ({structural_types})
+            ORDER BY 2, 1"#
+        ))?,
+        definitions: conn.prepare(&format!(
+            r#"SELECT e.{pk_col}, {name} FROM {relations_table} AS r
+            JOIN {elements_table} AS e ON e.{pk_col} = r."target_id"
+            WHERE r."name" = {definition} AND r."origin_id" = ?1
+            ORDER BY 2, 1"#
+        ))?,
+        connection_ends: conn.prepare(&format!(
+            r#"SELECT "name", "target_id" FROM {relations_table}
+            WHERE "name" IN ({connection_end_relations}) AND "origin_id" = ?1
+            ORDER BY 2"#
+        ))?,
+        chaining_features: conn.prepare(&format!(
+            r#"SELECT "target_id" FROM {relations_table}
+            WHERE "name" = {chaining_feature} AND "origin_id" = ?1"#
+        ))?,
+    };
+
+    let root_id = resolve_element(conn, root)?;
+    let (root_name, root_type): (String, Option<String>) = conn.query_row(
+        &format!(r#"SELECT {name}, e."@type" FROM {elements_table} AS e WHERE e.{pk_col} = ?1"#),
+        [&root_id],
+        |row| Ok((row.get(0)?, row.get(1)?)),
+    )?;
+
+    let mut features = vec![Feature {
+        definitions: read_definitions(&mut statements, &root_id)?,
+        id: root_id.clone(),
+        name: root_name,
+        r#type: root_type.unwrap_or_default(),
+        kind: Kind::Part,
+        parent: None,
+        children: Vec::new(),
+    }];
+    let mut connections = Vec::new();
+    place_features(
+        &mut statements,
+        &mut features,
+        &mut connections,
+        0,
+        &mut vec![root_id],
+        maybe_max_depth,
+    )?;
+    debug!(
+        "placed {} features and {} connections",
+        features.len() - connections.len(),
+        connections.len()
+    );
+
+    writeln!(out, "@startuml")?;
+    writeln!(out, "skinparam componentStyle rectangle")?;
+    write_feature(&features, 0, 0, out)?;
+
+    for &connection_idx in &connections {
+        let connection = &features[connection_idx];
+        let Some(owner_idx) = connection.parent else {
+            continue;
+        };
+
+        let mut sources = Vec::new();
+        let mut targets = Vec::new();
+        let mut rows = statements.connection_ends.query([&connection.id])?;
+        while let Some(row) = rows.next()? {
+            let relation: String = row.get(0)?;
+            let end_id: String = row.get(1)?;
+            if relation == CONNECTION_END_RELATIONS[0] {
+                sources.push(end_id);
+            } else {
+                targets.push(end_id);
+            }
+        }
+        drop(rows);
+
+        let mut place_end = |end_id: &str| -> Result<Option<usize>> {
+            let maybe_idx = place_connection_end(&mut statements, &features, owner_idx, end_id)?;
+            if maybe_idx.is_none() {
+                warn!(
+                    "the end {end_id:?} of the connection {:?} is not shown in the diagram",
+                    connection.id
+                );
+            }
+            Ok(maybe_idx)
+        };
+        let source_idxs = sources
+            .iter()
+            .map(|id| place_end(id))
+            .collect::<Result<Vec<_>>>()?;
+        let target_idxs = targets
+            .iter()
+            .map(|id| place_end(id))
+            .collect::<Result<Vec<_>>>()?;
+
+        // labels of links are not quoted, hence need no escaping of double quotes
+        let link_label = match label(connection) {
+            label if label.is_empty() => String::new(),
+            label => format!(" : {}", label.replace('\n', "\\n")),
+        };
+        for source_idx in source_idxs.iter().flatten() {
+            for target_idx in target_idxs.iter().flatten() {
+                writeln!(out, "f{source_idx} -- f{target_idx}{link_label}")?;
+            }
+        }
+    }
+
+    writeln!(out, "@enduml")?;
+
+    Ok(())
+}
+
+/// Place the features owned by the feature at `parent_idx`, or by its definitions
+///
+/// `definition_ids` holds the definitions whose features are placed around the parent, to stop at
+/// definitions containing usages of themselves.
+fn place_features(
+    statements: &mut Statements,
+    features: &mut Vec<Feature>,
+    connections: &mut Vec<usize>,
+    parent_idx: usize,
+    definition_ids: &mut Vec<String>,
+    maybe_max_depth: Option<usize>,
+) -> Result<()> {
+    let parent_id = features[parent_idx].id.clone();
+    let mut owned = read_owned_features(statements, &parent_id)?;
+    let mut pushed_definitions = 0;
+    if owned
+        .iter()
+        .all(|(_, _, _, kind)| *kind == Kind::Connection)
+    {
+        let definitions = statements
+            .definitions
+            .query_map([&parent_id], |row| row.get::<_, String>(0))?
+            .collect::<Result<Vec<_>, _>>()?;
+        for definition_id in definitions {
+            if definition_ids.contains(&definition_id) {
+                debug!("not placing the recursive definition {definition_id:?} once more");
+                continue;
+            }
+            owned.extend(read_owned_features(statements, &definition_id)?);
+            definition_ids.push(definition_id);
+            pushed_definitions += 1;
+        }
+    }
+
+    // the same feature may be inherited from multiple definitions
+    let mut placed_ids = HashSet::new();
+    for (id, name, r#type, kind) in owned {
+        if !placed_ids.insert(id.clone()) {
+            continue;
+        }
+        let idx = features.len();
+        features.push(Feature {
+            definitions: read_definitions(statements, &id)?,
+            id,
+            name,
+            r#type,
+            kind,
+            parent: Some(parent_idx),
+            children: Vec::new(),
+        });
+        if kind == Kind::Connection {
+            connections.push(idx);
+            continue;
+        }
+        features[parent_idx].children.push(idx);
+
+        let depth = depth(features, idx);
+        if kind == Kind::Part && maybe_max_depth.is_none_or(|max_depth| depth < max_depth) {
+            place_features(
+                statements,
+                features,
+                connections,
+                idx,
+                definition_ids,
+                maybe_max_depth,
+            )?;
+        }
+    }
+
+    definition_ids.truncate(definition_ids.len() - pushed_definitions);
+
+    Ok(())
+}
+
+/// Read the parts, ports and connections owned by an element
+fn read_owned_features(
+    statements: &mut Statements,
+    owner_id: &str,
+) -> Result<Vec<(String, String, String, Kind)>> {
+    let mut owned = Vec::new();
+    let mut rows = statements.owned_features.query([owner_id])?;
+    while let Some(row) = rows.next()? {
+        let r#type: String = row.get(2)?;
+        let kind = if PART_TYPES.contains(&r#type.as_str()) {
+            Kind::Part
+        } else if PORT_TYPES.contains(&r#type.as_str()) {
+            Kind::Port
+        } else {
+            Kind::Connection
+        };
+        owned.push((row.get(0)?, row.get(1)?, r#type, kind));
+    }
+    Ok(owned)
+}
+
+/// Read the names of the definitions of a usage
+fn read_definitions(statements: &mut Statements, usage_id: &str) -> Result<Vec<String>> {
+    let definitions = statements
+        .definitions
+        .query_map([usage_id], |row| row.get(1))?
+        .collect::<Result<_, _>>()?;
+    Ok(definitions)
+}
+
+/// Number of features the feature at `idx` is nested in
+fn depth(features: &[Feature], mut idx: usize) -> usize {
+    let mut depth = 0;
+    while let Some(parent_idx) = features[idx].parent {
+        depth += 1;
+        idx = parent_idx;
+    }
+    depth
+}
+
+/// Find the part or port a connection owned by the feature at `owner_idx` connects
+///
+/// The end is looked up among the features placed within the owner. Ends referring to a nested
+/// feature via a feature chain (e.g. `engine.shaft`) are placed at the feature whose nesting
+/// matches the most features of the chain.
+fn place_connection_end(
+    statements: &mut Statements,
+    features: &[Feature],
+    owner_idx: usize,
+    end_id: &str,
+) -> Result<Option<usize>> {
+    let mut within_owner = Vec::new();
+    let mut stack = vec![owner_idx];
+    while let Some(idx) = stack.pop() {
+        within_owner.push(idx);
+        stack.extend(features[idx].children.iter().rev());
+    }
+
+    if let Some(&idx) = within_owner.iter().find(|&&idx| features[idx].id == end_id) {
+        return Ok(Some(idx));
+    }
+
+    let chain: HashSet<String> = statements
+        .chaining_features
+        .query_map([end_id], |row| row.get(0))?
+        .collect::<Result<_, _>>()?;
+    if chain.is_empty() {
+        return Ok(None);
+    }
+
+    let mut best: Option<(usize, usize)> = None;
+    for idx in within_owner {
+        if !chain.contains(&features[idx].id) {
+            continue;
+        }
+        let mut matches = 0;
+        let mut current = Some(idx);
+        while let Some(current_idx) = current.filter(|&current_idx| current_idx != owner_idx) {
+            if chain.contains(&features[current_idx].id) {
+                matches += 1;
+            }
+            current = features[current_idx].parent;
+        }
+        if best.is_none_or(|(_, best_matches)| matches > best_matches) {
+            best = Some((idx, matches));
+        }
+    }
+
+    Ok(best.map(|(idx, _)| idx))
+}
+
+/// Write a part or port together with the features placed in it
+fn write_feature<W: Write>(
+    features: &[Feature],
+    idx: usize,
+    depth: usize,
+    out: &mut W,
+) -> Result<()> {
+    let feature = &features[idx];
+    let indent = "  ".repeat(depth);
+    let label = escape_plantuml(&label(feature));
+
+    if feature.kind == Kind::Port {
+        writeln!(out, "{indent}port \"{label}\" as f{idx}")?;
+        return Ok(());
+    }
+
+    write!(
+        out,
+        "{indent}component \"{label}\" <<{}>> as f{idx}",
+        stereotype(&feature.r#type)
+    )?;
+    if feature.children.is_empty() {
+        writeln!(out)?;
+        return Ok(());
+    }
+    writeln!(out, " {{")?;
+    for &child_idx in &feature.children {
+        write_feature(features, child_idx, depth + 1, out)?;
+    }
+    writeln!(out, "{indent}}}")?;
+
+    Ok(())
+}
+
+/// The label of a feature, its name followed by the names of its definitions
+fn label(feature: &Feature) -> String {
+    let definitions = feature
+        .definitions
+        .iter()
+        .filter(|definition| !definition.is_empty())
+        .map(String::as_str)
+        .collect::<Vec<_>>()
+        .join(", ");
+    match (feature.name.is_empty(), definitions.is_empty()) {
+        (_, true) => feature.name.clone(),
+        (true, false) => format!(": {definitions}"),
+        (false, false) => format!("{} : {definitions}", feature.name),
+    }
+}
+
+/// The stereotype of an element type in SysML v2 textual notation, e.g. `part def` for a
+/// `PartDefinition`
+fn stereotype(r#type: &str) -> String {
+    let (base, suffix) = if let Some(base) = r#type.strip_suffix("Definition") {
+        (base, " def")
+    } else {
+        (r#type.strip_suffix("Usage").unwrap_or(r#type), "")
+    };
+
+    let mut keyword = String::new();
+    for c in base.chars() {
+        if c.is_uppercase() && !keyword.is_empty() {
+            keyword.push(' ');
+        }
+        keyword.extend(c.to_lowercase());
+    }
+    keyword + suffix
+}
+
+/// Escape a string to be used within a quoted PlantUML label
+///
+/// PlantUML has no escape sequence for double quotes, hence these are written as Unicode code point.
+fn escape_plantuml(s: &str) -> String {
+    s.replace('"', "<U+0022>")
+        .replace('\n', "\\n")
+        .replace('\r', "")
+}
@@ -0,0 +1,127 @@
+use std::io::Write;
+
+use eyre::Result;
+
+use super::Graph;
+
+/// Namespace of the element types and relation names
+///
+/// SysML v2 defines no RDF vocabulary, hence one of this tool is used. SPARQL queries refer to it
+/// via the `sysml:` prefix.
+const VOCABULARY_IRI: &str = "https://github.com/DLR-FT/sysml-v2-sql/vocabulary#";
+
+/// Write the graph as RDF in the Turtle syntax, e.g. to be loaded into a triple store
+///
+/// Each element becomes a resource identified by the URN of its UUID `@id`, typed by its element
+/// type and labelled by its name. Each relation becomes a triple whose predicate is named after the
+/// relation.
+pub(super) fn write_turtle<W: Write>(graph: &Graph, out: &mut W) -> Result<()> {
+    writeln!(
+        out,
+        "@prefix rdf: <http://www.w3.org/1999/02/22-rdf-syntax-ns#> ."
+    )?;
+    writeln!(
+        out,
+        "@prefix rdfs: <http://www.w3.org/2000/01/rdf-schema#> ."
+    )?;
+    writeln!(out, "@prefix sysml: <{VOCABULARY_IRI}> .")?;
+
+    // the edges are ordered by origin, so those of each node form a contiguous run
+    let mut edges = graph.edges.iter().peekable();
+    for node in &graph.nodes {
+        let mut predicates = Vec::new();
+        if !node.r#type.is_empty() {
+            predicates.push(format!("a {}", vocabulary_term(&node.r#type)));
+        }
+        if !node.name.is_empty() {
+            predicates.push(format!("rdfs:label {}", quote_turtle(&node.name)));
+        }
+
+        while let Some(edge) = edges.next_if(|edge| edge.origin_id < node.id) {
+            warn!(
+                "skipping the relation {:?} of the unknown element {:?}",
+                edge.name, edge.origin_id
+            );
+        }
+        let mut node_edges = Vec::new();
+        while let Some(edge) = edges.next_if(|edge| edge.origin_id == node.id) {
+            node_edges.push(edge);
+        }
+        for same_name in node_edges.chunk_by(|a, b| a.name == b.name) {
+            let objects: Vec<String> = same_name
+                .iter()
+                .map(|edge| element_iri(&edge.target_id))
+                .collect();
+            predicates.push(format!(
+                "{} {}",
+                vocabulary_term(&same_name[0].name),
+                objects.join(", ")
+            ));
+        }
+
+        if predicates.is_empty() {
+            predicates.push("a rdfs:Resource".to_owned());
+        }
+        writeln!(
+            out,
+            "\n{} {} .",
+            element_iri(&node.id),
+            predicates.join(" ;\n    ")
+        )?;
+    }
+
+    Ok(())
+}
+
+/// The IRI of an element, the URN of its UUID
+fn element_iri(id: &str) -> String {
+    format!("<urn:uuid:{}>", percent_encode(id))
+}
+
+/// A type or relation name as term of the vocabulary
+///
+/// Names which are no valid local name in Turtle are written as full IRI.
+fn vocabulary_term(name: &str) -> String {
+    let mut chars = name.chars();
+    let is_local_name = chars
+        .next()
+        .is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_');
+    if is_local_name {
+        format!("sysml:{name}")
+    } else {
+        format!("<{VOCABULARY_IRI}{}>", percent_encode(name))
+    }
+}
+
+/// Percent-encode all characters but the unreserved ones of RFC 3986
+fn percent_encode(s: &str) -> String {
+    let mut encoded = String::with_capacity(s.len());
+    for byte in s.bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => {
+                encoded.push(byte as char)
+            }
+            _ => encoded.push_str(&format!("%{byte:02X}")),
+        }
+    }
+    encoded
+}
+
+/// Quote a string as Turtle string literal
+fn quote_turtle(s: &str) -> String {
+    let mut quoted = String::with_capacity(s.len() + 2);
+    quoted.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => quoted.push_str("\\\""),
+            '\\' => quoted.push_str("\\\\"),
+            '\n' => quoted.push_str("\\n"),
+            '\r' => quoted.push_str("\\r"),
+            '\t' => quoted.push_str("\\t"),
+            c => quoted.push(c),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
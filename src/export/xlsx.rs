@@ -0,0 +1,136 @@
+use std::{fmt::Write as _, io::Write};
+
+use eyre::{Result, bail};
+
+use super::escape_xml;
+use crate::util::write_zip;
+
+/// A cell of a worksheet
+#[derive(Debug, Clone)]
+pub(crate) enum Cell {
+    /// A cell without value
+    Empty,
+
+    /// A cell holding text
+    Text(String),
+
+    /// A cell holding a number
+    Number(i64),
+}
+
+/// A worksheet of a workbook
+#[derive(Debug, Clone)]
+pub(crate) struct Sheet {
+    /// Name of the sheet, as shown on its tab
+    pub(crate) name: String,
+
+    /// The cells, row by row
+    pub(crate) rows: Vec<Vec<Cell>>,
+}
+
+/// Write a workbook in the Office Open XML format (`.xlsx`), as read by Excel and LibreOffice
+///
+/// Text is stored inline in the cells, hence the workbook needs no shared string table.
+pub(crate) fn write_xlsx<W: Write>(sheets: &[Sheet], out: &mut W) -> Result<()> {
+    for sheet in sheets {
+        let name = &sheet.name;
+        if name.is_empty()
+            || name.chars().count() > 31
+            || name.contains(['[', ']', ':', '*', '?', '/', '\\'])
+        {
+            bail!("{name:?} is no valid name of a worksheet");
+        }
+    }
+
+    let mut content_types = String::from(
+        r#"<?xml version="1.0" encoding="UTF-8" standalone="yes"?>
+<Types xmlns="http://schemas.openxmlformats.org/package/2006/content-types"><Default Extension="rels" ContentType="application/vnd.openxmlformats-package.relationships+xml"/><Default Extension="xml" ContentType="application/xml"/><Override PartName="/xl/workbook.xml" ContentType="application/vnd.openxmlformats-officedocument.spreadsheetml.sheet.main+xml"/>"#,
+    );
+    let mut workbook = String::from(
+        r#"<?xml version="1.0" encoding="UTF-8" standalone="yes"?>
+<workbook xmlns="http://schemas.openxmlformats.org/spreadsheetml/2006/main" xmlns:r="http://schemas.openxmlformats.org/officeDocument/2006/relationships"><sheets>"#,
+    );
+    let mut workbook_rels = String::from(
+        r#"<?xml version="1.0" encoding="UTF-8" standalone="yes"?>
+<Relationships xmlns="http://schemas.openxmlformats.org/package/2006/relationships">"#,
+    );
+    let mut worksheets = Vec::new();
+    for (idx, sheet) in sheets.iter().enumerate() {
+        let number = idx + 1;
+        write!(
+            content_types,
+            r#"<Override PartName="/xl/worksheets/sheet{number}.xml" ContentType="application/vnd.openxmlformats-officedocument.spreadsheetml.worksheet+xml"/>"#
+        )?;
+        write!(
+            workbook,
+            r#"<sheet name="{}" sheetId="{number}" r:id="rId{number}"/>"#,
+            escape_xml(&sheet.name)
+        )?;
+        write!(
+            workbook_rels,
+            r#"<Relationship Id="rId{number}" Type="http://schemas.openxmlformats.org/officeDocument/2006/relationships/worksheet" Target="worksheets/sheet{number}.xml"/>"#
+        )?;
+        worksheets.push((
+            format!("xl/worksheets/sheet{number}.xml"),
+            worksheet_xml(sheet)?,
+        ));
+    }
+    content_types.push_str("</Types>");
+    workbook.push_str("</sheets></workbook>");
+    workbook_rels.push_str("</Relationships>");
+
+    let package_rels = r#"<?xml version="1.0" encoding="UTF-8" standalone="yes"?>
+<Relationships xmlns="http://schemas.openxmlformats.org/package/2006/relationships"><Relationship Id="rId1" Type="http://schemas.openxmlformats.org/officeDocument/2006/relationships/officeDocument" Target="xl/workbook.xml"/></Relationships>"#;
+
+    let mut entries: Vec<(&str, &[u8])> = vec![
+        ("[Content_Types].xml", content_types.as_bytes()),
+        ("_rels/.rels", package_rels.as_bytes()),
+        ("xl/workbook.xml", workbook.as_bytes()),
+        ("xl/_rels/workbook.xml.rels", workbook_rels.as_bytes()),
+    ];
+    for (path, xml) in &worksheets {
+        entries.push((path, xml.as_bytes()));
+    }
+    write_zip(&entries, out)
+}
+
+/// The XML of a worksheet
+fn worksheet_xml(sheet: &Sheet) -> Result<String> {
+    let mut xml = String::from(
+        r#"<?xml version="1.0" encoding="UTF-8" standalone="yes"?>
+<worksheet xmlns="http://schemas.openxmlformats.org/spreadsheetml/2006/main"><sheetData>"#,
+    );
+    for (row_idx, row) in sheet.rows.iter().enumerate() {
+        let row_number = row_idx + 1;
+        write!(xml, r#"<row r="{row_number}">"#)?;
+        for (column_idx, cell) in row.iter().enumerate() {
+            let reference = format!("{}{row_number}", column_name(column_idx));
+            match cell {
+                Cell::Empty => {}
+                Cell::Text(text) => write!(
+                    xml,
+                    r#"<c r="{reference}" t="inlineStr"><is><t xml:space="preserve">{}</t></is></c>"#,
+                    escape_xml(text)
+                )?,
+                Cell::Number(number) => write!(xml, r#"<c r="{reference}"><v>{number}</v></c>"#)?,
+            }
+        }
+        xml.push_str("</row>");
+    }
+    xml.push_str("</sheetData></worksheet>");
+    Ok(xml)
+}
+
+/// The name of a column, as used in cell references: `A` to `Z`, then `AA`, `AB` and so on
+fn column_name(mut idx: usize) -> String {
+    let mut name = Vec::new();
+    loop {
+        name.push(b'A' + (idx % 26) as u8);
+        if idx < 26 {
+            break;
+        }
+        idx = idx / 26 - 1;
+    }
+    name.reverse();
+    String::from_utf8(name).unwrap()
+}
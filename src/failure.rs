@@ -0,0 +1,94 @@
+//! Kinds of failures which are told apart by the exit code, so that scripts can branch on them
+
+use std::fmt::Display;
+
+/// Exit code of failures which are of none of the kinds of [`Failure`]
+const GENERIC_EXIT_CODE: u8 = 1;
+
+/// A kind of failure with an exit code of its own
+///
+/// The failure is the root cause of the error reported to the user, see [`Failure::report`]. Errors
+/// of the HTTP client and foreign key violations reported by SQLite are classified without being
+/// wrapped, see [`exit_code`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Failure {
+    /// The db does not match the schema expected by the tool, or the one it was initialized with
+    SchemaMismatch,
+
+    /// Rows of the db refer to rows which do not exist
+    ForeignKeyViolation,
+
+    /// The API server could not be reached, or the connection failed
+    #[cfg_attr(not(feature = "fetch"), allow(dead_code))]
+    Network,
+
+    /// Elements differ despite having the same id, or were changed on both sides of a sync
+    #[cfg_attr(not(feature = "fetch"), allow(dead_code))]
+    ConflictingElements,
+
+    /// Attributes of the imported elements were not understood in strict mode
+    StrictAttributes,
+
+    /// A statement of a SQL script given by the user failed
+    Sql,
+}
+
+impl Failure {
+    /// The exit code of the failure, documented in the help of the CLI
+    pub(crate) fn exit_code(self) -> u8 {
+        match self {
+            Failure::SchemaMismatch => 3,
+            Failure::ForeignKeyViolation => 4,
+            Failure::Network => 5,
+            Failure::ConflictingElements => 6,
+            Failure::StrictAttributes => 7,
+            Failure::Sql => 8,
+        }
+    }
+
+    /// An error report of this kind of failure, with the message for the user
+    pub(crate) fn report(self, msg: impl Display + Send + Sync + 'static) -> eyre::Report {
+        eyre::Report::new(self).wrap_err(msg)
+    }
+}
+
+impl Display for Failure {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let description = match self {
+            Failure::SchemaMismatch => "schema mismatch",
+            Failure::ForeignKeyViolation => "foreign key violation",
+            Failure::Network => "network error",
+            Failure::ConflictingElements => "conflicting elements",
+            Failure::StrictAttributes => "attributes not understood in strict mode",
+            Failure::Sql => "SQL error",
+        };
+        f.write_str(description)
+    }
+}
+
+impl std::error::Error for Failure {}
+
+/// The exit code for an error report, telling apart the kinds of [`Failure`]
+pub(crate) fn exit_code(report: &eyre::Report) -> u8 {
+    report
+        .chain()
+        .find_map(classify)
+        .map_or(GENERIC_EXIT_CODE, Failure::exit_code)
+}
+
+/// The kind of failure an error in the chain of an error report stands for, if any
+fn classify(error: &(dyn std::error::Error + 'static)) -> Option<Failure> {
+    if let Some(failure) = error.downcast_ref::<Failure>() {
+        return Some(*failure);
+    }
+    #[cfg(feature = "fetch")]
+    if let Some(e) = error.downcast_ref::<reqwest::Error>() {
+        // a body which is not the expected JSON is no network error
+        return (!e.is_decode()).then_some(Failure::Network);
+    }
+    if let Some(rusqlite::Error::SqliteFailure(e, _)) = error.downcast_ref::<rusqlite::Error>() {
+        return (e.extended_code == rusqlite::ffi::SQLITE_CONSTRAINT_FOREIGNKEY)
+            .then_some(Failure::ForeignKeyViolation);
+    }
+    None
+}
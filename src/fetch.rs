@@ -1,7 +1,10 @@
 use crate::{
-    cli::{CommitSelector, ProjectSelector},
-    import::Element,
+    cli::{ApiVersion, CommitSelector, ProjectSelector},
+    config::{ServerProfile, config},
+    failure::Failure,
+    import::{Element, ImportOptions},
     maybe_time_report,
+    progress::Progress,
 };
 use std::{
     collections::HashMap,
@@ -9,15 +12,54 @@ use std::{
     path::PathBuf,
     sync::atomic::Ordering::Relaxed,
     sync::{Arc, atomic::AtomicUsize},
+    time::Duration,
 };
 
 use api_data_types::{Branch, Project};
-use eyre::{Result, bail, ensure};
+use eyre::{Context, Result, bail, ensure};
 use reqwest::{Client, RequestBuilder, Response, Url};
-use tokio::task::JoinHandle;
+use tokio::{sync::OnceCell, task::JoinSet};
+use tokio_util::task::AbortOnDropHandle;
+use tracing::Instrument;
 
 mod api_data_types;
+mod clone;
+mod create;
+mod credentials;
+mod push;
+mod sync;
+mod webhook;
 
+pub use clone::clone_project;
+pub use create::{create_branch, create_project};
+pub use push::{PushOptions, push};
+pub use sync::{SyncOptions, sync};
+
+/// Number of requests sent at the same time when fetching per element, e.g. derived properties
+const CONCURRENT_REQUESTS: usize = 16;
+
+/// Path of the OpenAPI document below the base URL, probed to detect the [`ApiVersion`]
+const OPENAPI_DOCUMENT_PATH: &str = "docs/openapi.json";
+
+/// Default name of the username field of a login form, see [`FormLogin`]
+const DEFAULT_USERNAME_FIELD: &str = "username";
+
+/// Default name of the password field of a login form, see [`FormLogin`]
+const DEFAULT_PASSWORD_FIELD: &str = "password";
+
+/// Form login issuing a session cookie, used instead of HTTP basic auth
+///
+/// Configured by the environment variables SYSML_LOGIN_URL, and optionally
+/// SYSML_LOGIN_USERNAME_FIELD and SYSML_LOGIN_PASSWORD_FIELD naming the form fields, or by the
+/// corresponding fields of a [`ServerProfile`].
+#[derive(Clone, Debug)]
+struct FormLogin {
+    url: Url,
+    username_field: String,
+    password_field: String,
+}
+
+#[derive(Clone)]
 pub struct SysmlV2ApiBrowser {
     base_url: Url,
 
@@ -25,45 +67,73 @@ pub struct SysmlV2ApiBrowser {
     maybe_password: Option<String>,
 
     http_client: Client,
-}
 
-impl SysmlV2ApiBrowser {
-    pub fn new(base_url: Url, allow_invalid_certs: bool) -> Result<Self> {
-        ensure!(
-            !base_url.path().ends_with('/'),
-            "base_url must not end with /"
-        );
+    /// Login to perform before the first request, if the server requires a session
+    maybe_login: Option<FormLogin>,
 
-        let http_client;
+    /// Set once the login was performed
+    logged_in: Arc<OnceCell<()>>,
 
-        #[cfg(any(feature = "bundled-tls", feature = "native-tls"))]
-        {
-            if allow_invalid_certs {
-                warn!("accepting invalid certificates, connection to server is NOT trustworthy");
-            }
-            http_client = Client::builder().danger_accept_invalid_certs(allow_invalid_certs);
-        }
+    /// The API version of the server, given or detected on first use
+    api_version: Arc<OnceCell<ApiVersion>>,
+}
 
-        #[cfg(not(any(feature = "bundled-tls", feature = "native-tls")))]
-        {
-            http_client = Client::builder();
-            if allow_invalid_certs {
-                warn!(
-                    "-a/--allow-invalid-certs is ignored since no TLS support was compiled in at all"
-                );
+impl SysmlV2ApiBrowser {
+    /// Creates a browser of the API server given by its base URL or by the name of its profile
+    ///
+    /// The settings of a [`ServerProfile`] apply in addition to the given ones. The API version
+    /// is detected unless `maybe_api_version` is given or set by the profile.
+    pub fn new(
+        server: &str,
+        mut http_options: HttpOptions,
+        maybe_api_version: Option<ApiVersion>,
+    ) -> Result<Self> {
+        let maybe_profile = config().profiles.get(server);
+        let base_url = match maybe_profile {
+            Some(profile) => {
+                debug!("using the server profile {server:?}");
+                Url::parse(&profile.base_url).wrap_err_with(|| {
+                    format!("the base URL of the server profile {server:?} is invalid")
+                })?
             }
+            None => Url::parse(server).wrap_err_with(|| {
+                format!("{server:?} is neither a valid URL nor the name of a server profile")
+            })?,
+        };
+        if let Some(profile) = maybe_profile {
+            http_options.allow_invalid_certs |= profile.allow_invalid_certs;
+            http_options.maybe_ca_cert = http_options.maybe_ca_cert.or(profile.ca_cert.clone());
         }
+        let maybe_api_version =
+            maybe_api_version.or(maybe_profile.and_then(|profile| profile.api_version));
 
-        let http_client = http_client.build()?;
+        ensure!(
+            !base_url.path().ends_with('/'),
+            "base_url must not end with /"
+        );
 
-        let maybe_username = match std::env::var("SYSML_USERNAME") {
-            Err(std::env::VarError::NotPresent) => None,
-            maybe_u => Some(maybe_u?),
-        };
+        let http_client = http_client(&http_options)?;
 
-        let maybe_password = match std::env::var("SYSML_PASSWORD") {
-            Err(std::env::VarError::NotPresent) => None,
-            maybe_p => Some(maybe_p?),
+        let credentials::Credentials {
+            maybe_username,
+            maybe_password,
+        } = credentials::lookup_credentials(&base_url, maybe_profile)?;
+
+        let maybe_login_url = env_or_profile("SYSML_LOGIN_URL", maybe_profile, |p| &p.login_url)?;
+        let maybe_login = match maybe_login_url {
+            Some(url) => Some(FormLogin {
+                url: Url::parse(&url)
+                    .wrap_err_with(|| format!("the login URL {url:?} is invalid"))?,
+                username_field: env_or_profile("SYSML_LOGIN_USERNAME_FIELD", maybe_profile, |p| {
+                    &p.login_username_field
+                })?
+                .unwrap_or_else(|| DEFAULT_USERNAME_FIELD.to_owned()),
+                password_field: env_or_profile("SYSML_LOGIN_PASSWORD_FIELD", maybe_profile, |p| {
+                    &p.login_password_field
+                })?
+                .unwrap_or_else(|| DEFAULT_PASSWORD_FIELD.to_owned()),
+            }),
+            None => None,
         };
 
         Ok(Self {
@@ -71,21 +141,88 @@ impl SysmlV2ApiBrowser {
             maybe_username,
             maybe_password,
             http_client,
+            maybe_login,
+            logged_in: Arc::new(OnceCell::new()),
+            api_version: Arc::new(OnceCell::new_with(maybe_api_version)),
         })
     }
 
+    /// The base URL of the API
+    pub fn base_url(&self) -> &Url {
+        &self.base_url
+    }
+
+    /// Performs the form login on first use, keeping the session cookie for all later requests
+    async fn maybe_login(&self) -> Result<()> {
+        let Some(login) = &self.maybe_login else {
+            return Ok(());
+        };
+
+        self.logged_in
+            .get_or_try_init(|| async {
+                let Some(username) = &self.maybe_username else {
+                    bail!("logging in requires a username, set SYSML_USERNAME");
+                };
+                let password = self.maybe_password.as_deref().unwrap_or_default();
+
+                info!("logging in at {} as {username:?}", login.url);
+                let form = [
+                    (login.username_field.as_str(), username.as_str()),
+                    (login.password_field.as_str(), password),
+                ];
+                let resp = self
+                    .http_client
+                    .post(login.url.clone())
+                    .form(&form)
+                    .send()
+                    .await?;
+                ensure!(
+                    resp.status().is_success(),
+                    "logging in failed with status {}",
+                    resp.status()
+                );
+                debug!("logged in, continuing with the session cookie");
+
+                Ok(())
+            })
+            .await?;
+
+        Ok(())
+    }
+
+    /// The API version of the server, probing the server for it on first use
+    async fn api_version(&self) -> Result<ApiVersion> {
+        let api_version = self
+            .api_version
+            .get_or_try_init(|| detect_api_version(self))
+            .await?;
+        Ok(*api_version)
+    }
+
     fn absolute_url<S: AsRef<str>>(&self, url_path: S) -> Url {
         let mut url = self.base_url.clone();
-        if url_path.as_ref().starts_with('/') {
-            url.set_path(url_path.as_ref());
+        let (path, maybe_query) = match url_path.as_ref().split_once('?') {
+            Some((path, query)) => (path, Some(query)),
+            None => (url_path.as_ref(), None),
+        };
+        if path.starts_with('/') {
+            url.set_path(path);
         } else {
             let previous_path = url.path();
-            url.set_path(&format!("{previous_path}/{}", url_path.as_ref()));
+            url.set_path(&format!("{previous_path}/{path}"));
+        }
+        if maybe_query.is_some() {
+            url.set_query(maybe_query);
         }
         url
     }
 
     fn maybe_set_auth(&self, req: RequestBuilder) -> Result<RequestBuilder> {
+        // with a login, the session cookie authenticates the requests
+        if self.maybe_login.is_some() {
+            return Ok(req);
+        }
+
         let req = match (&self.maybe_username, &self.maybe_password) {
             (None, None) => req,
             (None, Some(_)) => {
@@ -99,6 +236,7 @@ impl SysmlV2ApiBrowser {
 
     async fn http_get<T: reqwest::IntoUrl + std::fmt::Display>(&self, url: T) -> Result<Response> {
         trace!("about to get {url}");
+        self.maybe_login().await?;
 
         // prepare the request
         let req = self.http_client.get(url);
@@ -109,6 +247,197 @@ impl SysmlV2ApiBrowser {
         // perform the request
         req.send().await.map_err(|e| e.into())
     }
+
+    async fn http_post_json<T: reqwest::IntoUrl + std::fmt::Display, B: serde::Serialize>(
+        &self,
+        url: T,
+        body: &B,
+    ) -> Result<Response> {
+        trace!("about to post to {url}");
+        self.maybe_login().await?;
+
+        // prepare the request
+        let req = self.http_client.post(url).json(body);
+
+        // optionally add auth
+        let req = self.maybe_set_auth(req)?;
+
+        // perform the request
+        req.send().await.map_err(|e| e.into())
+    }
+}
+
+/// Detects the API version from the version of the OpenAPI document served by the server
+///
+/// Servers of the standardized API state its version, i.e. `1.0.0` or later. Servers without
+/// OpenAPI document, or stating an earlier version, are taken to be pilot implementations.
+async fn detect_api_version(browser: &SysmlV2ApiBrowser) -> Result<ApiVersion> {
+    let url = browser.absolute_url(OPENAPI_DOCUMENT_PATH);
+    let resp = browser.http_get(url.clone()).await?;
+    let maybe_version = if resp.status().is_success() {
+        resp.json::<serde_json::Value>()
+            .await
+            .ok()
+            .and_then(|document| document["info"]["version"].as_str().map(str::to_owned))
+    } else {
+        None
+    };
+
+    let api_version = match &maybe_version {
+        Some(version) if major_version(version).is_some_and(|major| major >= 1) => ApiVersion::V1,
+        Some(version) => {
+            info!("the server implements the API version {version:?}, assuming a pilot server");
+            ApiVersion::Pilot
+        }
+        None => {
+            info!("found no OpenAPI document at {url}, assuming a pilot server");
+            ApiVersion::Pilot
+        }
+    };
+    debug!("detected the API version {api_version:?}");
+
+    Ok(api_version)
+}
+
+/// The major version of a version like `1.0.0`, none for versions not starting with a number
+fn major_version(version: &str) -> Option<u64> {
+    let version = version.strip_prefix('v').unwrap_or(version);
+    version.split('.').next()?.parse().ok()
+}
+
+/// The value of an environment variable, none if it is not set
+fn maybe_env_var(name: &str) -> Result<Option<String>> {
+    match std::env::var(name) {
+        Err(std::env::VarError::NotPresent) => Ok(None),
+        maybe_value => Ok(Some(
+            maybe_value.wrap_err_with(|| format!("reading {name}"))?,
+        )),
+    }
+}
+
+/// The value of an environment variable, or else of a field of the server profile if any
+fn env_or_profile(
+    name: &str,
+    maybe_profile: Option<&ServerProfile>,
+    field: fn(&ServerProfile) -> &Option<String>,
+) -> Result<Option<String>> {
+    Ok(maybe_env_var(name)?.or_else(|| maybe_profile.and_then(|profile| field(profile).clone())))
+}
+
+/// Options of the HTTP client
+#[derive(Debug, Clone, Default)]
+pub struct HttpOptions {
+    /// Accept invalid certificates, making the connection to the server untrustworthy
+    pub allow_invalid_certs: bool,
+
+    /// PEM file with CA certificates to trust in addition to the built-in ones
+    pub maybe_ca_cert: Option<PathBuf>,
+
+    /// Maximum number of idle connections kept open per host
+    pub maybe_pool_size: Option<usize>,
+
+    /// Use HTTP/2 with servers offering it, instead of always using HTTP/1.1
+    pub http2: bool,
+
+    /// Interval of TCP keep-alive probes
+    pub maybe_keep_alive: Option<Duration>,
+
+    /// Time after which idle connections are closed
+    pub maybe_idle_timeout: Option<Duration>,
+
+    /// Time to wait for a connection to be established
+    pub maybe_connect_timeout: Option<Duration>,
+
+    /// Time to wait for each request to complete
+    pub maybe_timeout: Option<Duration>,
+}
+
+/// Builds the HTTP client, as configured by `http_options`
+fn http_client(http_options: &HttpOptions) -> Result<Client> {
+    let HttpOptions {
+        allow_invalid_certs,
+        maybe_ca_cert,
+        maybe_pool_size,
+        http2,
+        maybe_keep_alive,
+        maybe_idle_timeout,
+        maybe_connect_timeout,
+        maybe_timeout,
+    } = http_options;
+    let mut http_client;
+
+    #[cfg(any(feature = "bundled-tls", feature = "native-tls"))]
+    {
+        if *allow_invalid_certs {
+            warn!("accepting invalid certificates, connection to server is NOT trustworthy");
+        }
+        let mut builder = Client::builder().danger_accept_invalid_certs(*allow_invalid_certs);
+
+        if let Some(path) = maybe_ca_cert {
+            let pem_bundle = std::fs::read(path)
+                .wrap_err_with(|| format!("reading the CA certificates from {path:?}"))?;
+            let certs = reqwest::Certificate::from_pem_bundle(&pem_bundle)
+                .wrap_err_with(|| format!("parsing the CA certificates from {path:?}"))?;
+            ensure!(!certs.is_empty(), "found no CA certificates in {path:?}");
+            debug!("trusting {} CA certificates from {path:?}", certs.len());
+            for cert in certs {
+                builder = builder.add_root_certificate(cert);
+            }
+        }
+        http_client = builder;
+    }
+
+    #[cfg(not(any(feature = "bundled-tls", feature = "native-tls")))]
+    {
+        http_client = Client::builder();
+        if *allow_invalid_certs {
+            warn!(
+                "-a/--allow-invalid-certs is ignored since no TLS support was compiled in at all"
+            );
+        }
+        if maybe_ca_cert.is_some() {
+            warn!("--ca-cert is ignored since no TLS support was compiled in at all");
+        }
+    }
+
+    if !http2 {
+        http_client = http_client.http1_only();
+    }
+    if let Some(pool_size) = maybe_pool_size {
+        http_client = http_client.pool_max_idle_per_host(*pool_size);
+    }
+    if let Some(idle_timeout) = maybe_idle_timeout {
+        http_client = http_client.pool_idle_timeout(*idle_timeout);
+    }
+    if let Some(connect_timeout) = maybe_connect_timeout {
+        http_client = http_client.connect_timeout(*connect_timeout);
+    }
+    if let Some(timeout) = maybe_timeout {
+        http_client = http_client.timeout(*timeout);
+    }
+    if let Some(keep_alive) = maybe_keep_alive {
+        http_client = http_client.tcp_keepalive(*keep_alive);
+    }
+
+    // request the responses gzip or brotli compressed, which shrinks the verbose element JSON
+    // manifold. They are decompressed transparently before being parsed.
+    http_client = http_client.gzip(true).brotli(true);
+
+    // keep the cookies set by the server, e.g. the session cookie of a login
+    Ok(http_client.cookie_store(true).build()?)
+}
+
+/// Downloads the JSON schema from `url`, returning it verbatim
+pub async fn download_schema(url: Url, http_options: &HttpOptions) -> Result<String> {
+    info!("downloading the JSON schema from {url}");
+    let resp = http_client(http_options)?.get(url).send().await?;
+    ensure!(
+        resp.status().is_success(),
+        "downloading the JSON schema failed with status {}",
+        resp.status()
+    );
+
+    Ok(resp.text().await?)
 }
 
 /// Interprete the CLI arguments, finding the matching project and commit id
@@ -165,7 +494,7 @@ pub async fn interprete_cli(
         CommitSelector::BranchId { branch_id } => {
             let url = browser.absolute_url(format!("projects/{project_id}/branches/{branch_id}"));
             let branch: Branch = browser.http_get(url).await?.json().await?;
-            branch.head.id.to_owned()
+            head_commit_id(&branch)?
         }
         CommitSelector::BranchName { branch_name } => {
             debug!("searching for branch by the name {branch_name:?}");
@@ -192,7 +521,7 @@ pub async fn interprete_cli(
                 bail!("error finding exactly one matching branch");
             }
 
-            matching_branches.first().unwrap().head.id.to_owned()
+            head_commit_id(matching_branches.first().unwrap())?
         }
         CommitSelector::DefaultBranch => {
             let matched_project = match maybe_matched_project {
@@ -210,13 +539,40 @@ pub async fn interprete_cli(
             ));
             let default_branch: Branch = browser.http_get(url).await?.json().await?;
 
-            default_branch.head.id
+            head_commit_id(&default_branch)?
         }
     };
 
     Ok((project_id, commit_id))
 }
 
+/// Fetches a branch of a project, or its default branch
+async fn fetch_branch(
+    browser: &SysmlV2ApiBrowser,
+    project_id: &str,
+    maybe_branch_id: Option<&str>,
+) -> Result<Branch> {
+    let branch_id = match maybe_branch_id {
+        Some(branch_id) => branch_id.to_owned(),
+        None => {
+            let url = browser.absolute_url(format!("projects/{project_id}"));
+            let project: Project = browser.http_get(url).await?.json().await?;
+            project.default_branch.id
+        }
+    };
+
+    let url = browser.absolute_url(format!("projects/{project_id}/branches/{branch_id}"));
+    Ok(browser.http_get(url).await?.json().await?)
+}
+
+/// The id of the head commit of a branch, failing for a branch without commits
+fn head_commit_id(branch: &Branch) -> Result<String> {
+    match &branch.head {
+        Some(head) => Ok(head.id.clone()),
+        None => bail!("the branch {:?} has no commits yet", branch.name),
+    }
+}
+
 /// Checks if there are [`Element`]s with conflicting values
 ///
 /// The SysML v2 API works with Arrays of [`Element`]s, which have a unique id. A faulty yet
@@ -236,9 +592,9 @@ pub fn check_for_conflicting_elements<'a>(
 
             // existing element is **not** identical, this is an issue
             Some(existing_element) => {
-                bail!(
+                return Err(Failure::ConflictingElements.report(format!(
                     "Differing Elements with colliding ids where found:\n{existing_element:#?}\n{new_element:#?}"
-                );
+                )));
             }
 
             // no existing element
@@ -264,18 +620,63 @@ pub fn build_url_path(project_id: &str, commit_id: &str, maybe_page_size: Option
     url
 }
 
+/// Builds the path to the results of a query on a commit, see [`ElementQuery`]
+///
+/// With `maybe_query_id`, the path yields the results of the query stored on the server by that
+/// id. Otherwise, a query is to be posted to the path.
+pub fn build_query_url_path(
+    project_id: &str,
+    commit_id: &str,
+    maybe_query_id: Option<&str>,
+) -> String {
+    match maybe_query_id {
+        Some(query_id) => {
+            format!("projects/{project_id}/queries/{query_id}/results?commitId={commit_id}")
+        }
+        None => format!("projects/{project_id}/query-results?commitId={commit_id}"),
+    }
+}
+
+/// A query of the SysML v2 Query API, selecting the elements to fetch
+#[derive(Debug, Clone)]
+pub struct ElementQuery {
+    /// Path of the query results, see [`build_query_url_path`]
+    pub url_path: String,
+
+    /// The query to post, e.g. `{"@type": "Query", "where": ...}`, or none to get the results of a
+    /// query stored on the server
+    pub maybe_query: Option<serde_json::Value>,
+}
+
+/// What to fetch besides the listed elements of a commit
+#[derive(Debug, Clone, Default)]
+pub struct FetchOptions {
+    /// Fetch only the results of this query instead of all elements of the commit
+    pub maybe_query: Option<ElementQuery>,
+
+    /// Merge the derived properties of each element, see [`merge_derived_properties`]
+    pub derived: bool,
+
+    /// Merge the relationships of each element, see [`merge_relationships`]
+    pub relationships: bool,
+
+    /// Receiver of the progress of the fetch, see [`crate::progress`]
+    pub(crate) progress: Progress,
+}
+
 /// # Overview
 ///
-/// Fetches all data from `base_url`,
+/// Fetches all data from `base_url`, together with the additional data selected by
+/// `fetch_options`
 pub async fn fetch_from_url_to_file(
     browser: SysmlV2ApiBrowser,
     url_path: &str,
     maybe_path: &Option<PathBuf>,
     maybe_conn: Option<&mut rusqlite::Connection>,
+    import_options: &ImportOptions,
     pretty_json: bool,
+    fetch_options: FetchOptions,
 ) -> Result<()> {
-    let fetch_t0 = std::time::Instant::now();
-
     let mut element_id_idx_map: HashMap<_, usize> = HashMap::new();
     if let Some(path) = maybe_path {
         if path.is_file() {
@@ -284,12 +685,72 @@ pub async fn fetch_from_url_to_file(
             check_for_conflicting_elements(&mut elements, &mut element_id_idx_map)?;
         }
     }
+
+    let elements = fetch_model_elements(&browser, url_path, &fetch_options).await?;
+
+    // TODO maybe deduplicate
+
+    if let Some(path) = maybe_path {
+        info!("writing the fetched data to {path:?}");
+        let f = File::create(path)?;
+        if pretty_json {
+            serde_json::to_writer_pretty(f, &elements)?;
+        } else {
+            serde_json::to_writer(f, &elements)?;
+        }
+    }
+
+    // deduplicate_elements(&mut elements, &mut element_id_idx_map)?;
+
+    if let Some(conn) = maybe_conn {
+        crate::import::import_from_slice(&elements, conn, import_options)?;
+    }
+
+    Ok(())
+}
+
+/// Fetches the elements from `url_path`, together with the additional data selected by
+/// `fetch_options`
+pub(crate) async fn fetch_model_elements(
+    browser: &SysmlV2ApiBrowser,
+    url_path: &str,
+    fetch_options: &FetchOptions,
+) -> Result<Vec<Element>> {
+    let mut elements = match &fetch_options.maybe_query {
+        Some(query) => fetch_query_results(browser, query, &fetch_options.progress).await?,
+        None => fetch_elements(browser.clone(), url_path, fetch_options.progress.clone()).await?,
+    };
+    if fetch_options.relationships {
+        merge_relationships(browser, url_path, &mut elements).await?;
+    }
+    if fetch_options.derived {
+        merge_derived_properties(browser, url_path, &mut elements).await?;
+    }
+    Ok(elements)
+}
+
+/// Fetches all pages of elements from `url_path`
+///
+/// Runs in the `fetch` span, the bodies of the pages are parsed in its child span `parse`.
+#[tracing::instrument(name = "fetch", skip_all, fields(url_path = %url_path))]
+async fn fetch_elements(
+    browser: SysmlV2ApiBrowser,
+    url_path: &str,
+    progress: Progress,
+) -> Result<Vec<Element>> {
+    let fetch_t0 = std::time::Instant::now();
     info!("fetching started");
 
     let now = std::time::Instant::now();
 
-    // channel to move responses from the http task to the deser task
-    let (resp_tx, mut resp_rx) = tokio::sync::mpsc::channel::<Response>(32);
+    // channel to move responses from the http task to the deser task, which holds fewer pages in
+    // flight if the memory is limited
+    let queued_pages = if crate::config::config().max_memory.is_some() {
+        2
+    } else {
+        32
+    };
+    let (resp_tx, mut resp_rx) = tokio::sync::mpsc::channel::<Response>(queued_pages);
 
     // performance counters
     let elements_count = Arc::new(AtomicUsize::new(0));
@@ -297,78 +758,77 @@ pub async fn fetch_from_url_to_file(
 
     // this task receives `reqwest::Response`s and parses their bodies JSON
     let elements_count_clone = elements_count.clone();
-    let json_deser_task: JoinHandle<Result<Vec<Element>>> = tokio::task::spawn(async move {
-        let mut elements: Vec<Element> = Vec::new();
-        while let Some(resp) = resp_rx.recv().await {
-            trace!("parsing new response body");
-            let mut new_elements: Vec<Element> = resp.json().await?;
-
-            if new_elements.is_empty() {
-                warn!("detectected empty page, terminating parser task");
-                break;
+    let progress_clone = progress.clone();
+    // the tasks are aborted if the fetch is dropped, e.g. when it is cancelled
+    let json_deser_task = AbortOnDropHandle::<Result<Vec<Element>>>::new(tokio::task::spawn(
+        async move {
+            let mut elements: Vec<Element> = Vec::new();
+            while let Some(resp) = resp_rx.recv().await {
+                trace!("parsing new response body");
+                let mut new_elements: Vec<Element> = resp.json().await?;
+
+                if new_elements.is_empty() {
+                    warn!("detectected empty page, terminating parser task");
+                    break;
+                }
+
+                elements.append(&mut new_elements);
+                elements_count_clone.store(elements.len(), Relaxed);
+                progress_clone.elements_parsed(elements.len());
             }
 
-            elements.append(&mut new_elements);
-            elements_count_clone.store(elements.len(), Relaxed);
+            Ok(elements)
         }
-
-        Ok(elements)
-    });
+        .instrument(info_span!("parse")),
+    ));
 
     let mut maybe_url = Some(browser.absolute_url(url_path));
 
     // this task pulls the next page until there is no next page
     let pages_count_clone = pages_count.clone();
-    let http_paginator_task: JoinHandle<Result<()>> = tokio::task::spawn(async move {
-        while let Some(url) = maybe_url.take() {
-            // send request and gather response
-            trace!("sending new request to {url}");
-            let resp = browser.http_get(url).await?;
-
-            // if there is a next page, make sure we get to it in the next iteration
-            'next_page_exists: {
-                let Some(link_header) = resp.headers().get(reqwest::header::LINK) else {
-                    break 'next_page_exists;
-                };
-                let link_headers = parse_link_header::parse_with_rel(link_header.to_str()?)?;
-
-                trace!("found the following headers in the current page\n{link_headers:#?}");
-
-                let Some(next_url) = link_headers.get("next") else {
-                    break 'next_page_exists;
-                };
-                let next_url = Url::parse(&next_url.raw_uri)?;
-                trace!("next url to be processed: {next_url:#?}");
-                maybe_url = Some(next_url);
-            }
+    let http_paginator_task = AbortOnDropHandle::<Result<()>>::new(tokio::task::spawn(
+        async move {
+            while let Some(url) = maybe_url.take() {
+                // send request and gather response
+                trace!("sending new request to {url}");
+                let resp = browser.http_get(url).await?;
+
+                // if there is a next page, make sure we get to it in the next iteration
+                maybe_url = next_page_url(&resp)?;
+
+                // submit the response to the json parser task
+                if resp_tx.send(resp).await.is_err() {
+                    trace!("deser_task dropped resp_rx, shutting down");
+                    break;
+                }
 
-            // submit the response to the json parser task
-            if resp_tx.send(resp).await.is_err() {
-                trace!("deser_task dropped resp_rx, shutting down");
-                break;
+                // and count the pages we processed
+                let pages = pages_count_clone.fetch_add(1, Relaxed) + 1;
+                progress.pages_fetched(pages);
             }
 
-            // and count the pages we processed
-            pages_count_clone.fetch_add(1, Relaxed);
+            Ok(())
         }
-
-        Ok(())
-    });
+        .in_current_span(),
+    ));
 
     // this task just montitors the progress of the other tasks
     let mut report_td = std::time::Duration::from_secs(0);
     let elements_count_clone = elements_count.clone();
-    let monitor_task = tokio::task::spawn(async move {
-        loop {
-            tokio::time::sleep(std::time::Duration::from_millis(500)).await;
-            maybe_time_report!(
-                "element",
-                fetch_t0,
-                report_td,
-                elements_count_clone.load(Relaxed)
-            );
+    let monitor_task = AbortOnDropHandle::new(tokio::task::spawn(
+        async move {
+            loop {
+                tokio::time::sleep(std::time::Duration::from_millis(500)).await;
+                maybe_time_report!(
+                    "element",
+                    fetch_t0,
+                    report_td,
+                    elements_count_clone.load(Relaxed)
+                );
+            }
         }
-    });
+        .in_current_span(),
+    ));
 
     http_paginator_task.await??;
     let elements = json_deser_task.await??;
@@ -381,23 +841,282 @@ pub async fn fetch_from_url_to_file(
         now.elapsed()
     );
 
-    // TODO maybe deduplicate
+    Ok(elements)
+}
 
-    if let Some(path) = maybe_path {
-        info!("writing the fetched data to {path:?}");
-        let f = File::create(path)?;
-        if pretty_json {
-            serde_json::to_writer_pretty(f, &elements)?;
+/// Fetches the elements yielded by a query, following the pages of its results
+async fn fetch_query_results(
+    browser: &SysmlV2ApiBrowser,
+    query: &ElementQuery,
+    progress: &Progress,
+) -> Result<Vec<Element>> {
+    let Some(body) = &query.maybe_query else {
+        info!(
+            "fetching the results of the stored query {}",
+            query.url_path
+        );
+        return fetch_elements(browser.clone(), &query.url_path, progress.clone()).await;
+    };
+    post_query(browser, &query.url_path, body, progress).await
+}
+
+/// Posts a query to `url_path`, fetching all pages of its results
+#[tracing::instrument(name = "fetch", skip_all, fields(url_path = %url_path))]
+async fn post_query(
+    browser: &SysmlV2ApiBrowser,
+    url_path: &str,
+    body: &serde_json::Value,
+    progress: &Progress,
+) -> Result<Vec<Element>> {
+    let t0 = std::time::Instant::now();
+    info!("posting the query to {url_path}");
+    trace!("posting the query\n{body:#}");
+
+    let url = browser.absolute_url(url_path);
+    let resp = browser.http_post_json(url, body).await?;
+    ensure!(
+        resp.status().is_success(),
+        "running the query failed with status {}: {}",
+        resp.status(),
+        resp.text().await.unwrap_or_default()
+    );
+    let mut maybe_url = next_page_url(&resp)?;
+    let mut elements: Vec<Element> = resp
+        .json()
+        .await
+        .wrap_err("unable to parse the query results")?;
+    let mut pages = 1;
+    progress.pages_fetched(pages);
+    progress.elements_parsed(elements.len());
+
+    while let Some(url) = maybe_url.take() {
+        let resp = browser.http_get(url.clone()).await?;
+        maybe_url = next_page_url(&resp)?;
+        let mut page: Vec<Element> = resp
+            .json()
+            .await
+            .wrap_err_with(|| format!("unable to parse the query results fetched from {url}"))?;
+        elements.append(&mut page);
+        pages += 1;
+        progress.pages_fetched(pages);
+        progress.elements_parsed(elements.len());
+    }
+
+    info!(
+        "fetched {} elements yielded by the query in {:?}",
+        elements.len(),
+        t0.elapsed()
+    );
+
+    Ok(elements)
+}
+
+/// Fetches each element on its own, adding the properties missing from the listed elements
+///
+/// Servers may omit derived properties such as `qualifiedName` when listing the elements of a
+/// commit, computing them only when a single element is requested. Properties which are absent or
+/// null in the listed element are taken from the single element. The single elements are requested
+/// from below `url_path`, the path the elements were listed from.
+#[tracing::instrument(name = "fetch_derived", skip_all)]
+async fn merge_derived_properties(
+    browser: &SysmlV2ApiBrowser,
+    url_path: &str,
+    elements: &mut [Element],
+) -> Result<()> {
+    let t0 = std::time::Instant::now();
+    let mut report_td = std::time::Duration::from_secs(0);
+    info!(
+        "fetching {} elements one by one for their derived properties",
+        elements.len()
+    );
+
+    let elements_path = url_path.split('?').next().unwrap_or(url_path);
+    let requests: Vec<_> = elements
+        .iter()
+        .enumerate()
+        .map(|(idx, element)| {
+            let browser = browser.clone();
+            let url = browser.absolute_url(format!("{elements_path}/{}", element.id));
+            async move {
+                let single_element: Element = browser
+                    .http_get(url.clone())
+                    .await?
+                    .json()
+                    .await
+                    .wrap_err_with(|| format!("unable to parse the element fetched from {url}"))?;
+                Ok((idx, single_element))
+            }
+        })
+        .collect();
+
+    let mut fetched = 0;
+    let mut merged_properties = 0;
+    run_concurrently(requests, |(idx, single_element)| {
+        let element = &mut elements[idx];
+        if single_element.id == element.id {
+            merged_properties += merge_properties(element, single_element);
         } else {
-            serde_json::to_writer(f, &elements)?;
+            warn!(
+                "requested the element {:?}, but received the element {:?}",
+                element.id, single_element.id
+            );
         }
-    }
+        fetched += 1;
+        maybe_time_report!("derived element", t0, report_td, fetched);
+    })
+    .await?;
 
-    // deduplicate_elements(&mut elements, &mut element_id_idx_map)?;
+    info!(
+        "merged {merged_properties} derived property values into {} elements in {:?}",
+        elements.len(),
+        t0.elapsed()
+    );
 
-    if let Some(conn) = maybe_conn {
-        crate::import::import_from_slice(&elements, conn, false)?;
-    }
+    Ok(())
+}
+
+/// Fetches the relationships of each element, adding those missing from the listed elements
+///
+/// Some servers serve the relationships an element takes part in below
+/// `.../elements/{id}/relationships`, while omitting them or their references (e.g. `source` and
+/// `target`) from the listed elements. Relationships which are not listed are added, those which
+/// are listed get their absent or null properties from the served relationship. The relationships
+/// are requested from below `url_path`, the path the elements were listed from.
+#[tracing::instrument(name = "fetch_relationships", skip_all)]
+async fn merge_relationships(
+    browser: &SysmlV2ApiBrowser,
+    url_path: &str,
+    elements: &mut Vec<Element>,
+) -> Result<()> {
+    let t0 = std::time::Instant::now();
+    let mut report_td = std::time::Duration::from_secs(0);
+    info!("fetching the relationships of {} elements", elements.len());
+
+    let elements_path = url_path.split('?').next().unwrap_or(url_path);
+    let requests: Vec<_> = elements
+        .iter()
+        .map(|element| {
+            let browser = browser.clone();
+            let mut url =
+                browser.absolute_url(format!("{elements_path}/{}/relationships", element.id));
+            url.query_pairs_mut().append_pair("direction", "both");
+            let mut maybe_url = Some(url);
+            async move {
+                let mut relationships: Vec<Element> = Vec::new();
+                while let Some(url) = maybe_url.take() {
+                    let resp = browser.http_get(url.clone()).await?;
+                    maybe_url = next_page_url(&resp)?;
+                    let mut page: Vec<Element> = resp.json().await.wrap_err_with(|| {
+                        format!("unable to parse the relationships fetched from {url}")
+                    })?;
+                    relationships.append(&mut page);
+                }
+                Ok(relationships)
+            }
+        })
+        .collect();
+
+    let mut element_idxs: HashMap<String, usize> = elements
+        .iter()
+        .enumerate()
+        .map(|(idx, element)| (element.id.clone(), idx))
+        .collect();
+    let listed_count = elements.len();
+    let mut fetched = 0;
+    let mut merged_properties = 0;
+    run_concurrently(requests, |relationships| {
+        for relationship in relationships {
+            match element_idxs.get(&relationship.id) {
+                Some(&idx) => {
+                    merged_properties += merge_properties(&mut elements[idx], relationship)
+                }
+                None => {
+                    element_idxs.insert(relationship.id.clone(), elements.len());
+                    elements.push(relationship);
+                }
+            }
+        }
+        fetched += 1;
+        maybe_time_report!("element relationship", t0, report_td, fetched);
+    })
+    .await?;
 
+    info!(
+        "added {} relationships and merged {merged_properties} property values into listed ones in {:?}",
+        elements.len() - listed_count,
+        t0.elapsed()
+    );
+
+    Ok(())
+}
+
+/// Runs the requests, [`CONCURRENT_REQUESTS`] at a time, handing their outputs to `handle` in the
+/// order they complete
+async fn run_concurrently<T, F>(requests: Vec<F>, mut handle: impl FnMut(T)) -> Result<()>
+where
+    T: Send + 'static,
+    F: std::future::Future<Output = Result<T>> + Send + 'static,
+{
+    let mut tasks = JoinSet::new();
+    for request in requests {
+        if tasks.len() >= CONCURRENT_REQUESTS {
+            if let Some(result) = tasks.join_next().await {
+                handle(result??);
+            }
+        }
+        tasks.spawn(request);
+    }
+    while let Some(result) = tasks.join_next().await {
+        handle(result??);
+    }
     Ok(())
 }
+
+/// Adds the properties of `other` which are absent or null in `element`, returning the number of
+/// added properties
+fn merge_properties(element: &mut Element, other: Element) -> usize {
+    let mut merged = 0;
+    for (key, value) in other.rest {
+        if value.is_null() {
+            continue;
+        }
+        let existing = element.rest.entry(key).or_insert(serde_json::Value::Null);
+        if existing.is_null() {
+            *existing = value;
+            merged += 1;
+        }
+    }
+    merged
+}
+
+/// The URL of the next page, if the response is a page followed by another one
+fn next_page_url(resp: &Response) -> Result<Option<Url>> {
+    let Some(link_header) = resp.headers().get(reqwest::header::LINK) else {
+        return Ok(None);
+    };
+    let link_headers = parse_link_header::parse_with_rel(link_header.to_str()?)?;
+
+    trace!("found the following headers in the current page\n{link_headers:#?}");
+
+    let Some(next_url) = link_headers.get("next") else {
+        return Ok(None);
+    };
+    let next_url = Url::parse(&next_url.raw_uri)?;
+    trace!("next url to be processed: {next_url:#?}");
+    Ok(Some(next_url))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::major_version;
+
+    #[test]
+    fn major_versions() {
+        assert_eq!(major_version("1.0.0"), Some(1));
+        assert_eq!(major_version("v2.1"), Some(2));
+        assert_eq!(major_version("0.9.1"), Some(0));
+        assert_eq!(major_version("12"), Some(12));
+        assert_eq!(major_version("pilot"), None);
+        assert_eq!(major_version(""), None);
+    }
+}
@@ -26,10 +26,13 @@ pub struct Branch {
     // #[serde(rename = "@type")]
     // pub type_field: String,
     pub created: String,
-    pub head: BranchHead,
+    /// Absent for a branch without commits, e.g. the default branch of a new project
+    #[serde(default)]
+    pub head: Option<BranchHead>,
     pub name: String,
     pub owning_project: OwningProject,
-    pub referenced_commit: ReferencedCommit,
+    #[serde(default)]
+    pub referenced_commit: Option<ReferencedCommit>,
 }
 
 #[derive(Default, Debug, Clone, PartialEq, serde::Deserialize)]
@@ -52,3 +55,37 @@ pub struct ReferencedCommit {
     #[serde(rename = "@id")]
     pub id: String,
 }
+
+#[derive(Default, Debug, Clone, PartialEq, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Commit {
+    #[serde(rename = "@id")]
+    pub id: String,
+    // #[serde(rename = "@type")]
+    // pub type_field: String,
+    pub created: String,
+    /// A single identity on pilot servers, a list on later versions
+    #[serde(default, deserialize_with = "one_or_many")]
+    pub previous_commit: Vec<ReferencedCommit>,
+}
+
+/// Deserializes a list, or a single value or null as list of at most one value
+fn one_or_many<'de, D, T>(deserializer: D) -> Result<Vec<T>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+    T: serde::Deserialize<'de>,
+{
+    #[derive(serde::Deserialize)]
+    #[serde(untagged)]
+    enum OneOrMany<T> {
+        Many(Vec<T>),
+        One(Option<T>),
+    }
+
+    Ok(
+        match <OneOrMany<T> as serde::Deserialize>::deserialize(deserializer)? {
+            OneOrMany::Many(values) => values,
+            OneOrMany::One(maybe_value) => maybe_value.into_iter().collect(),
+        },
+    )
+}
@@ -0,0 +1,150 @@
+//! Mirroring of entire projects, with all their branches and commits
+
+use std::collections::{BTreeSet, HashSet};
+
+use eyre::{Result, bail};
+use rusqlite::Connection;
+
+use super::{
+    FetchOptions, SysmlV2ApiBrowser,
+    api_data_types::{Branch, Commit},
+    build_url_path, fetch_from_url_to_file,
+};
+use crate::{
+    config::BRANCHES_TABLE,
+    import::{ImportOptions, ImportProvenance},
+    projects::{is_multi_project, list_projects},
+    util::escape_sql_ident,
+};
+
+/// Fetch the branches of a project and their commits, storing each commit in the db
+///
+/// The db must store multiple projects, see [`crate::projects`]. Each commit is stored under its id
+/// in place of a project id, hence a commit can be selected via `--project <commit_id>` afterwards.
+/// The branches of the project and their head commits are recorded in the branches table.
+///
+/// Only the heads of the branches are fetched, unless `all_commits` is set, in which case the
+/// history of each branch is followed back to its first commit. If `branch_names` is not empty,
+/// only the branches of these names are cloned. Commits already stored in the db are not fetched
+/// again, hence repeatedly cloning a project only fetches the commits added in the meantime.
+pub async fn clone_project(
+    browser: &SysmlV2ApiBrowser,
+    conn: &mut Connection,
+    project_id: &str,
+    branch_names: &[String],
+    all_commits: bool,
+    maybe_page_size: Option<u32>,
+    import_options: &ImportOptions,
+) -> Result<()> {
+    if !is_multi_project(conn)? {
+        bail!("cloning requires a db storing multiple projects, see --multi-project");
+    }
+
+    let url = browser.absolute_url(format!("projects/{project_id}/branches"));
+    let mut branches: Vec<Branch> = browser.http_get(url).await?.json().await?;
+    trace!("found the following branches:\n{branches:#?}");
+
+    if !branch_names.is_empty() {
+        for branch_name in branch_names {
+            if !branches.iter().any(|b| &b.name == branch_name) {
+                bail!("the project {project_id:?} has no branch named {branch_name:?}");
+            }
+        }
+        branches.retain(|b| branch_names.contains(&b.name));
+    }
+    info!(
+        "cloning {} branches of the project {project_id:?}",
+        branches.len()
+    );
+
+    branches.retain(|b| {
+        if b.head.is_none() {
+            warn!("skipping the branch {:?}, which has no commits yet", b.name);
+        }
+        b.head.is_some()
+    });
+
+    // the commits to fetch, in order of their ids for reproducible imports
+    let mut commit_ids: BTreeSet<String> = branches
+        .iter()
+        .filter_map(|b| b.head.as_ref())
+        .map(|head| head.id.clone())
+        .collect();
+    if all_commits {
+        let mut pending: Vec<String> = commit_ids.iter().cloned().collect();
+        while let Some(commit_id) = pending.pop() {
+            let url = browser.absolute_url(format!("projects/{project_id}/commits/{commit_id}"));
+            let commit: Commit = browser.http_get(url).await?.json().await?;
+            for previous in commit.previous_commit {
+                if commit_ids.insert(previous.id.clone()) {
+                    pending.push(previous.id);
+                }
+            }
+        }
+    }
+
+    let stored: HashSet<String> = list_projects(conn)?.into_iter().map(|(p, _)| p).collect();
+    let missing: Vec<_> = commit_ids.iter().filter(|c| !stored.contains(*c)).collect();
+    info!(
+        "{} of {} commits are already stored, fetching the remaining {}",
+        commit_ids.len() - missing.len(),
+        commit_ids.len(),
+        missing.len()
+    );
+
+    for commit_id in missing {
+        info!("fetching the commit {commit_id:?}");
+        let options = ImportOptions {
+            provenance: ImportProvenance {
+                project_id: Some(project_id.to_owned()),
+                commit_id: Some(commit_id.to_owned()),
+                ..import_options.provenance.clone()
+            },
+            project_key: Some(commit_id.to_owned()),
+            ..import_options.clone()
+        };
+        fetch_from_url_to_file(
+            browser.clone(),
+            &build_url_path(project_id, commit_id, maybe_page_size),
+            &None,
+            Some(conn),
+            &options,
+            false,
+            FetchOptions::default(),
+        )
+        .await?;
+    }
+
+    record_branches(conn, project_id, &branches)?;
+
+    Ok(())
+}
+
+/// Records the cloned branches of a project, replacing those recorded by an earlier clone
+fn record_branches(conn: &mut Connection, project_id: &str, branches: &[Branch]) -> Result<()> {
+    let table = escape_sql_ident(BRANCHES_TABLE);
+    let db_ta = conn.transaction()?;
+    db_ta.execute_batch(&format!(
+        r#"CREATE TABLE IF NOT EXISTS {table} (
+            "project_id" TEXT NOT NULL,
+            "branch_id" TEXT NOT NULL,
+            "name" TEXT NOT NULL,
+            "head_commit_id" TEXT NOT NULL,
+            PRIMARY KEY ("project_id", "branch_id")
+        ) STRICT;"#
+    ))?;
+
+    let mut stmt = db_ta.prepare(&format!(
+        r#"INSERT OR REPLACE INTO {table} ("project_id", "branch_id", "name", "head_commit_id")
+        VALUES (?1, ?2, ?3, ?4)"#
+    ))?;
+    for branch in branches {
+        if let Some(head) = &branch.head {
+            stmt.execute((project_id, &branch.id, &branch.name, &head.id))?;
+        }
+    }
+    stmt.finalize()?;
+
+    db_ta.commit()?;
+    Ok(())
+}
@@ -0,0 +1,86 @@
+//! Creating projects and branches on the API server
+//!
+//! A model built locally can only be pushed into an existing project. These functions create the
+//! project, or a branch to push to, beforehand.
+
+use std::io::Write;
+
+use eyre::{Result, ensure};
+use serde_json::{Value, json};
+
+use super::{
+    SysmlV2ApiBrowser,
+    api_data_types::{Branch, Project},
+    fetch_branch, head_commit_id,
+};
+
+/// Create a new project on the API server and print its id
+///
+/// The API server creates the default branch of the project along with it, which has no commits
+/// until the first push.
+pub async fn create_project<W: Write>(
+    browser: &SysmlV2ApiBrowser,
+    name: &str,
+    maybe_description: Option<&str>,
+    out: &mut W,
+) -> Result<()> {
+    let mut project = json!({
+        "@type": "Project",
+        "name": name,
+    });
+    if let Some(description) = maybe_description {
+        project["description"] = Value::from(description);
+    }
+
+    let url = browser.absolute_url("projects");
+    info!("creating the project {name:?}");
+    let resp = browser.http_post_json(url, &project).await?;
+    ensure!(
+        resp.status().is_success(),
+        "creating the project failed with status {}: {}",
+        resp.status(),
+        resp.text().await.unwrap_or_default()
+    );
+    let new_project: Project = resp.json().await?;
+    info!("created the project {:?}", new_project.id);
+    writeln!(out, "{}", new_project.id)?;
+
+    Ok(())
+}
+
+/// Create a new branch in a project on the API server and print its id
+///
+/// The branch starts at the given commit, or at the head of the default branch of the project.
+pub async fn create_branch<W: Write>(
+    browser: &SysmlV2ApiBrowser,
+    project_id: &str,
+    name: &str,
+    maybe_commit_id: Option<&str>,
+    out: &mut W,
+) -> Result<()> {
+    let head_id = match maybe_commit_id {
+        Some(commit_id) => commit_id.to_owned(),
+        None => head_commit_id(&fetch_branch(browser, project_id, None).await?)?,
+    };
+
+    let branch = json!({
+        "@type": "Branch",
+        "name": name,
+        "head": { "@id": head_id },
+    });
+
+    let url = browser.absolute_url(format!("projects/{project_id}/branches"));
+    info!("creating the branch {name:?} at the commit {head_id:?}");
+    let resp = browser.http_post_json(url, &branch).await?;
+    ensure!(
+        resp.status().is_success(),
+        "creating the branch failed with status {}: {}",
+        resp.status(),
+        resp.text().await.unwrap_or_default()
+    );
+    let new_branch: Branch = resp.json().await?;
+    info!("created the branch {:?}", new_branch.id);
+    writeln!(out, "{}", new_branch.id)?;
+
+    Ok(())
+}
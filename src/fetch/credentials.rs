@@ -0,0 +1,146 @@
+//! Lookup of the credentials for the API server
+//!
+//! The environment variables SYSML_USERNAME and SYSML_PASSWORD take precedence, followed by the
+//! server profile if any. Missing parts are looked up by the host of the API server in the `.netrc`
+//! file, at the path given by the environment variable NETRC or else in the home directory. With the
+//! `keyring` feature, a still missing password is looked up in the keyring of the OS.
+
+use std::path::PathBuf;
+
+use eyre::{Context, Result};
+use reqwest::Url;
+
+use super::{env_or_profile, maybe_env_var};
+use crate::config::ServerProfile;
+
+/// Service under which the passwords are stored in the keyring of the OS
+///
+/// The passwords are stored per user and host, as `{username}@{host}`.
+#[cfg(feature = "keyring")]
+const KEYRING_SERVICE: &str = "sysml-v2-sql";
+
+/// Credentials for the API server, each part possibly missing
+#[derive(Clone, Default)]
+pub(super) struct Credentials {
+    pub maybe_username: Option<String>,
+    pub maybe_password: Option<String>,
+}
+
+/// Login and password of an entry of a `.netrc` file
+#[derive(Default)]
+struct NetrcEntry {
+    maybe_login: Option<String>,
+    maybe_password: Option<String>,
+}
+
+/// Looks up the credentials for the API server at `base_url`, given by the profile if any
+pub(super) fn lookup_credentials(
+    base_url: &Url,
+    maybe_profile: Option<&ServerProfile>,
+) -> Result<Credentials> {
+    let mut credentials = Credentials {
+        maybe_username: env_or_profile("SYSML_USERNAME", maybe_profile, |p| &p.username)?,
+        maybe_password: env_or_profile("SYSML_PASSWORD", maybe_profile, |p| &p.password)?,
+    };
+    if credentials.maybe_password.is_some() {
+        return Ok(credentials);
+    }
+    let Some(host) = base_url.host_str() else {
+        return Ok(credentials);
+    };
+
+    if let Some(path) = netrc_path()?.filter(|path| path.is_file()) {
+        let content = std::fs::read_to_string(&path)
+            .wrap_err_with(|| format!("reading the netrc file {path:?}"))?;
+        if let Some(entry) = netrc_entry(&content, host) {
+            // the password only applies to the login it is given for
+            let login_matches = match (&credentials.maybe_username, &entry.maybe_login) {
+                (Some(username), Some(login)) => username == login,
+                _ => true,
+            };
+            if login_matches {
+                debug!("using the credentials for {host:?} from {path:?}");
+                credentials.maybe_username = credentials.maybe_username.or(entry.maybe_login);
+                credentials.maybe_password = entry.maybe_password;
+            }
+        }
+    }
+
+    #[cfg(feature = "keyring")]
+    if credentials.maybe_password.is_none() {
+        if let Some(username) = &credentials.maybe_username {
+            credentials.maybe_password = keyring_password(username, host)?;
+        }
+    }
+
+    Ok(credentials)
+}
+
+/// The path of the `.netrc` file, none if there is no home directory
+fn netrc_path() -> Result<Option<PathBuf>> {
+    if let Some(path) = maybe_env_var("NETRC")? {
+        return Ok(Some(PathBuf::from(path)));
+    }
+    Ok(maybe_env_var("HOME")?.map(|home| PathBuf::from(home).join(".netrc")))
+}
+
+/// The first entry of a `.netrc` file for the host, or else its default entry
+///
+/// Macro definitions are skipped, quoted tokens are not supported.
+fn netrc_entry(content: &str, host: &str) -> Option<NetrcEntry> {
+    // a macro definition extends up to the next empty line
+    let mut tokens = Vec::new();
+    let mut in_macdef = false;
+    for line in content.lines() {
+        if in_macdef {
+            in_macdef = !line.trim().is_empty();
+            continue;
+        }
+        for token in line.split_whitespace() {
+            if token == "macdef" {
+                in_macdef = true;
+                break;
+            }
+            tokens.push(token);
+        }
+    }
+
+    // only the tokens of a matching entry are taken, which ends at the next entry
+    let mut maybe_entry: Option<NetrcEntry> = None;
+    let mut tokens = tokens.into_iter();
+    while let Some(token) = tokens.next() {
+        match token {
+            "machine" | "default" if maybe_entry.is_some() => break,
+            "machine" => maybe_entry = (tokens.next() == Some(host)).then(NetrcEntry::default),
+            "default" => maybe_entry = Some(NetrcEntry::default()),
+            "login" | "password" | "account" => {
+                let maybe_value = tokens.next().map(str::to_owned);
+                if let Some(entry) = maybe_entry.as_mut() {
+                    match token {
+                        "login" => entry.maybe_login = maybe_value,
+                        "password" => entry.maybe_password = maybe_value,
+                        _ => {}
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+
+    maybe_entry
+}
+
+/// The password of the user for the host in the keyring of the OS, if one is stored
+#[cfg(feature = "keyring")]
+fn keyring_password(username: &str, host: &str) -> Result<Option<String>> {
+    let keyring_user = format!("{username}@{host}");
+    let entry = keyring::Entry::new(KEYRING_SERVICE, &keyring_user)?;
+    match entry.get_password() {
+        Ok(password) => {
+            debug!("using the password for {keyring_user:?} from the keyring");
+            Ok(Some(password))
+        }
+        Err(keyring::Error::NoEntry) => Ok(None),
+        Err(e) => Err(e).wrap_err_with(|| format!("looking up {keyring_user:?} in the keyring")),
+    }
+}
@@ -0,0 +1,311 @@
+//! Writing local changes of a project back to the API server as a new commit
+//!
+//! The changes are determined by comparing the db with the commit the project was last fetched
+//! from. As the db does not represent every property of the elements, only those properties which
+//! the db represents are compared. Changed elements are sent with all their other properties as
+//! found in that commit, so properties unknown to the db survive a push.
+
+use std::{collections::HashSet, io::Write};
+
+use eyre::{Result, bail, ensure};
+use rusqlite::Connection;
+use serde_json::{Value, json};
+
+use super::{
+    SysmlV2ApiBrowser,
+    api_data_types::Commit,
+    build_url_path, fetch_branch, fetch_elements,
+    sync::{ensure_complete_import, last_imported_commit},
+};
+use crate::{
+    cli::ApiVersion,
+    config::{ELEMENT_PK_COL, PROJECT_COL, config},
+    db_json::{
+        DbElement, element_columns, element_json, is_relation_value, read_db_elements,
+        to_many_relations,
+    },
+    import::{
+        Element, ImportProvenance, element_db_value, get_table_columns, is_relation_object,
+        record_import,
+    },
+    progress::Progress,
+    projects::{is_multi_project, select_project},
+    verify::same_db_value,
+};
+
+/// Options affecting how local changes are pushed
+#[derive(Debug, Clone, Default)]
+pub struct PushOptions {
+    /// Branch to commit to, the default branch of the project if absent
+    pub maybe_branch_id: Option<String>,
+
+    /// Description of the new commit
+    pub maybe_message: Option<String>,
+
+    /// Print the commit instead of sending it to the API server
+    pub dry_run: bool,
+
+    /// Page size to request from the API server
+    pub maybe_page_size: Option<u32>,
+}
+
+/// Push the local changes of a project to the API server as a new commit
+///
+/// The base of the new commit is the commit the project was last imported from, as recorded in the
+/// import log. A project which was never imported can only be pushed to if the branch has no commits
+/// yet, e.g. right after creating the project. Then all elements of the db are pushed. After a successful push, the new commit is recorded in the import log, so it becomes
+/// the base for the next push. Pushing is refused if the last import was partial, e.g. filtered or
+/// transformed, as the changes relative to the whole commit would delete or overwrite what the
+/// import left out or rewrote.
+pub async fn push<W: Write>(
+    browser: &SysmlV2ApiBrowser,
+    conn: &mut Connection,
+    project_id: &str,
+    push_options: &PushOptions,
+    out: &mut W,
+) -> Result<()> {
+    if is_multi_project(conn)? {
+        select_project(conn, project_id)?;
+    }
+    ensure_complete_import(conn, project_id)?;
+
+    let maybe_base_commit_id = match last_imported_commit(conn, project_id)? {
+        Some(commit_id) => Some(commit_id),
+        None => {
+            let branch =
+                fetch_branch(browser, project_id, push_options.maybe_branch_id.as_deref()).await?;
+            if branch.head.is_some() {
+                bail!(
+                    "the project {project_id:?} was never fetched, hence there is no commit to push onto"
+                );
+            }
+            info!(
+                "the branch {:?} has no commits yet, pushing all elements of the db",
+                branch.name
+            );
+            None
+        }
+    };
+
+    let base_elements = match &maybe_base_commit_id {
+        Some(base_commit_id) => {
+            info!("fetching the commit {base_commit_id:?} to determine the local changes");
+            fetch_elements(
+                browser.clone(),
+                &build_url_path(project_id, base_commit_id, push_options.maybe_page_size),
+                Progress::default(),
+            )
+            .await?
+        }
+        None => Vec::new(),
+    };
+
+    let changes = local_changes(conn, &base_elements)?;
+    if changes.is_empty() {
+        info!("there are no local changes to push");
+        return Ok(());
+    }
+    info!("found {} changed elements", changes.len());
+
+    let commit = new_commit(
+        browser.api_version().await?,
+        &changes,
+        maybe_base_commit_id.as_deref(),
+        push_options.maybe_message.as_deref(),
+    );
+
+    if push_options.dry_run {
+        serde_json::to_writer_pretty(&mut *out, &commit)?;
+        writeln!(out)?;
+        return Ok(());
+    }
+
+    let new_commit = post_commit(
+        browser,
+        project_id,
+        push_options.maybe_branch_id.as_deref(),
+        &commit,
+    )
+    .await?;
+    info!("created the commit {:?}", new_commit.id);
+    writeln!(out, "{}", new_commit.id)?;
+
+    // the db now matches the new commit
+    let provenance = ImportProvenance {
+        source: Some(browser.base_url.to_string()),
+        project_id: Some(project_id.to_owned()),
+        commit_id: Some(new_commit.id),
+        ..Default::default()
+    };
+    record_import(conn, &provenance, changes.len())?;
+
+    Ok(())
+}
+
+/// Assembles a commit of the given changes, on top of the given commit if any
+///
+/// Pilot servers take the previous commit as a single identity, later versions take a list.
+pub(super) fn new_commit(
+    api_version: ApiVersion,
+    changes: &[Value],
+    maybe_previous_commit_id: Option<&str>,
+    maybe_message: Option<&str>,
+) -> Value {
+    let mut commit = json!({
+        "@type": "Commit",
+        "change": changes,
+    });
+    if let Some(previous_commit_id) = maybe_previous_commit_id {
+        let previous_commit = json!({ "@id": previous_commit_id });
+        commit["previousCommit"] = match api_version {
+            ApiVersion::Pilot => previous_commit,
+            ApiVersion::V1 => json!([previous_commit]),
+        };
+    }
+    if let Some(message) = maybe_message {
+        commit["description"] = Value::from(message);
+    }
+    commit
+}
+
+/// Sends a commit to the API server, to the given branch or the default branch of the project
+pub(super) async fn post_commit(
+    browser: &SysmlV2ApiBrowser,
+    project_id: &str,
+    maybe_branch_id: Option<&str>,
+    commit: &Value,
+) -> Result<Commit> {
+    let mut url = browser.absolute_url(format!("projects/{project_id}/commits"));
+    if let Some(branch_id) = maybe_branch_id {
+        url.query_pairs_mut().append_pair("branchId", branch_id);
+    }
+    info!("pushing the changes to {url}");
+    let resp = browser.http_post_json(url, commit).await?;
+    ensure!(
+        resp.status().is_success(),
+        "pushing the changes failed with status {}: {}",
+        resp.status(),
+        resp.text().await.unwrap_or_default()
+    );
+    Ok(resp.json().await?)
+}
+
+/// Determines the changes of the db relative to the given elements, as a list of data versions
+///
+/// Each data version carries the full new state of a changed or created element, or no state at
+/// all for a deleted element.
+pub(super) fn local_changes(conn: &Connection, base_elements: &[Element]) -> Result<Vec<Value>> {
+    let db_elements = read_db_elements(conn)?;
+    let config = config();
+    let columns = element_columns(conn)?;
+    let extended_columns: HashSet<String> = get_table_columns(conn, &config.extended_table)?
+        .into_iter()
+        .map(|(name, _)| name)
+        .filter(|name| name != ELEMENT_PK_COL && name != PROJECT_COL)
+        .collect();
+    let to_many = to_many_relations(conn, base_elements)?;
+
+    let mut changes = Vec::new();
+    let mut base_ids = HashSet::new();
+    for base in base_elements {
+        base_ids.insert(base.id.as_str());
+        let payload = match db_elements.get(&base.id) {
+            Some(db_element) => {
+                let base_db_element = db_representation(base, &columns, &extended_columns)?;
+                if same_db_element(&base_db_element, db_element) {
+                    continue;
+                }
+                debug!("the element {:?} was changed", base.id);
+                Value::Object(element_json(
+                    &base.id,
+                    Some(base),
+                    db_element,
+                    &base_db_element,
+                    &to_many,
+                ))
+            }
+            None => {
+                debug!("the element {:?} was deleted", base.id);
+                Value::Null
+            }
+        };
+        changes.push(data_version(&base.id, payload));
+    }
+
+    for (id, db_element) in &db_elements {
+        if base_ids.contains(id.as_str()) {
+            continue;
+        }
+        debug!("the element {id:?} was created");
+        let payload = element_json(id, None, db_element, &DbElement::default(), &to_many);
+        changes.push(data_version(id, Value::Object(payload)));
+    }
+
+    Ok(changes)
+}
+
+/// Determines how an element would be represented in the db, as the importer does
+///
+/// Columns whose value can not be represented in the db are left out.
+pub(super) fn db_representation(
+    element: &Element,
+    columns: &[(String, rusqlite::types::Type)],
+    extended_columns: &HashSet<String>,
+) -> Result<DbElement> {
+    let mut db_element = DbElement::default();
+    for (name, ty) in columns {
+        if let Some(db_value) = element_db_value(element, name, *ty)? {
+            db_element.columns.insert(name.clone(), db_value);
+        }
+    }
+
+    for (name, value) in &element.rest {
+        let targets: Vec<&str> = match value {
+            Value::Object(o) if is_relation_object(o) => vec![o[ELEMENT_PK_COL].as_str().unwrap()],
+            Value::Array(a) if !a.is_empty() && a.iter().all(is_relation_value) => a
+                .iter()
+                .map(|v| v[ELEMENT_PK_COL].as_str().unwrap())
+                .collect(),
+            Value::Array(a) if extended_columns.contains(name) => {
+                let mut values: Vec<String> = a
+                    .iter()
+                    .filter_map(|v| v.as_str().map(str::to_owned))
+                    .collect();
+                values.sort();
+                if !values.is_empty() {
+                    db_element.extended.insert(name.clone(), values);
+                }
+                continue;
+            }
+            _ => continue,
+        };
+        db_element
+            .relations
+            .entry(name.clone())
+            .or_default()
+            .extend(targets.into_iter().map(str::to_owned));
+    }
+
+    Ok(db_element)
+}
+
+/// Compares two db representations, skipping columns absent from the expected one
+pub(super) fn same_db_element(expected: &DbElement, actual: &DbElement) -> bool {
+    expected.relations == actual.relations
+        && expected.extended == actual.extended
+        && actual.columns.iter().all(|(name, actual_value)| {
+            expected
+                .columns
+                .get(name)
+                .is_none_or(|expected_value| same_db_value(expected_value, actual_value))
+        })
+}
+
+/// Wraps the new state of an element in a data version, as expected by the API
+fn data_version(id: &str, payload: Value) -> Value {
+    json!({
+        "@type": "DataVersion",
+        "identity": { "@id": id },
+        "payload": payload,
+    })
+}
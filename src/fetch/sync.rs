@@ -0,0 +1,286 @@
+//! Periodic synchronization of a db with a branch on the API server
+
+use std::{collections::HashMap, net::SocketAddr, sync::Arc, time::Duration};
+
+use eyre::{Result, bail};
+use rusqlite::{Connection, OptionalExtension};
+use serde_json::Value;
+use tokio::sync::Notify;
+
+use super::{
+    FetchOptions, SysmlV2ApiBrowser, build_url_path, fetch_elements, fetch_from_url_to_file,
+    interprete_cli,
+    push::{local_changes, new_commit, post_commit},
+    webhook,
+};
+use crate::{
+    cli::{CommitSelector, ProjectSelector},
+    config::IMPORT_LOG_TABLE,
+    failure::Failure,
+    import::{Element, ImportOptions, ImportProvenance, get_table_columns},
+    progress::Progress,
+    projects::{deselect_project, is_multi_project, select_project},
+    util::{escape_sql_ident, table_exists},
+};
+
+/// Options affecting how the db is kept in sync
+#[derive(Debug, Clone)]
+pub struct SyncOptions {
+    /// Time between two checks for a new commit
+    pub interval: Duration,
+
+    /// Check for a new commit once, then return
+    pub once: bool,
+
+    /// Address to serve the webhook on, if any
+    pub maybe_listen_addr: Option<SocketAddr>,
+
+    /// Page size to request from the API server
+    pub maybe_page_size: Option<u32>,
+
+    /// Push local changes of the db to the API server as well
+    pub two_way: bool,
+}
+
+/// Keep the db in sync with the selected commit, usually the head of a branch
+///
+/// Every interval, the selected commit is looked up. If it differs from the commit the project was
+/// last imported from, as recorded in the import log, the commit is fetched and imported. Elements
+/// absent from the new commit are removed from the db. With `once`, the check is performed a single
+/// time only.
+///
+/// With a listen address, a webhook endpoint is served on that address. Each authenticated `POST`
+/// request to it triggers an immediate check, instead of waiting for the interval to pass, see
+/// [`webhook::listen`].
+///
+/// With `two_way`, each check also determines the local changes of the db relative to the commit
+/// last imported, as [`super::push`] does. They are pushed as a new commit on top of the selected
+/// commit, which is then imported. If the selected commit changed an element which was also changed
+/// locally, the conflicting elements are reported and the sync stops, leaving both sides untouched.
+pub async fn sync(
+    browser: &SysmlV2ApiBrowser,
+    conn: &mut Connection,
+    project_selector: &ProjectSelector,
+    sync_options: &SyncOptions,
+    import_options: &ImportOptions,
+) -> Result<()> {
+    let SyncOptions {
+        interval,
+        once,
+        maybe_listen_addr,
+        maybe_page_size,
+        two_way,
+    } = *sync_options;
+
+    let maybe_push_branch_id = if two_way {
+        push_branch_id(project_selector)?
+    } else {
+        None
+    };
+
+    let trigger = Arc::new(Notify::new());
+    if let Some(addr) = maybe_listen_addr.filter(|_| !once) {
+        webhook::listen(addr, trigger.clone()).await?;
+    }
+
+    loop {
+        let (project_id, commit_id) = interprete_cli(browser, project_selector).await?;
+        let maybe_last_commit_id = last_imported_commit(conn, &project_id)?;
+
+        let mut sync_commit_id = commit_id.clone();
+        if let Some(last_commit_id) = maybe_last_commit_id.as_ref().filter(|_| two_way) {
+            ensure_complete_import(conn, &project_id)?;
+            let base_elements = fetch_elements(
+                browser.clone(),
+                &build_url_path(&project_id, last_commit_id, maybe_page_size),
+                Progress::default(),
+            )
+            .await?;
+            let changes = project_local_changes(conn, &project_id, &base_elements)?;
+
+            if !changes.is_empty() {
+                info!("found {} locally changed elements", changes.len());
+                if *last_commit_id != commit_id {
+                    let remote_elements = fetch_elements(
+                        browser.clone(),
+                        &build_url_path(&project_id, &commit_id, maybe_page_size),
+                        Progress::default(),
+                    )
+                    .await?;
+                    let conflicts =
+                        conflicting_elements(&base_elements, &remote_elements, &changes);
+                    if !conflicts.is_empty() {
+                        for id in &conflicts {
+                            error!(
+                                "the element {id:?} was changed both in the db and on the server"
+                            );
+                        }
+                        return Err(Failure::ConflictingElements.report(format!(
+                            "{} elements were changed both in the db and in the commit {commit_id:?}, resolve the conflicts before syncing again",
+                            conflicts.len()
+                        )));
+                    }
+                }
+
+                let commit = new_commit(
+                    browser.api_version().await?,
+                    &changes,
+                    Some(&commit_id),
+                    None,
+                );
+                sync_commit_id = post_commit(browser, &project_id, maybe_push_branch_id, &commit)
+                    .await?
+                    .id;
+                info!("pushed the local changes as the commit {sync_commit_id:?}");
+            }
+        }
+        let commit_id = sync_commit_id;
+
+        if maybe_last_commit_id.as_deref() == Some(commit_id.as_str()) {
+            info!("the project {project_id:?} is up to date at the commit {commit_id:?}");
+        } else {
+            match &maybe_last_commit_id {
+                Some(last_commit_id) => info!(
+                    "the project {project_id:?} moved from the commit {last_commit_id:?} to {commit_id:?}, syncing"
+                ),
+                None => info!("the project {project_id:?} was not imported yet, syncing"),
+            }
+
+            let options = ImportOptions {
+                provenance: ImportProvenance {
+                    project_id: Some(project_id.clone()),
+                    commit_id: Some(commit_id.clone()),
+                    ..import_options.provenance.clone()
+                },
+                prune: true,
+                ..import_options.clone()
+            };
+            fetch_from_url_to_file(
+                browser.clone(),
+                &build_url_path(&project_id, &commit_id, maybe_page_size),
+                &None,
+                Some(conn),
+                &options,
+                false,
+                FetchOptions::default(),
+            )
+            .await?;
+        }
+
+        if once {
+            return Ok(());
+        }
+        debug!("checking again in {interval:?}");
+        if tokio::time::timeout(interval, trigger.notified())
+            .await
+            .is_ok()
+        {
+            debug!("checking early, as triggered via the webhook");
+        }
+    }
+}
+
+/// The commit a project was imported from most recently, according to the import log
+pub(super) fn last_imported_commit(conn: &Connection, project_id: &str) -> Result<Option<String>> {
+    if !table_exists(conn, IMPORT_LOG_TABLE)? {
+        return Ok(None);
+    }
+
+    let commit_id = conn
+        .query_row(
+            &format!(
+                r#"SELECT "commit_id" FROM {} WHERE "project_id" = ?1
+                ORDER BY "timestamp" DESC, rowid DESC LIMIT 1"#,
+                escape_sql_ident(IMPORT_LOG_TABLE)
+            ),
+            [project_id],
+            |row| row.get(0),
+        )
+        .optional()?;
+    Ok(commit_id.flatten())
+}
+
+/// Fails if the project was last imported partially, see [`ImportProvenance::partial`]
+///
+/// The local changes are determined relative to the whole commit last imported. After a partial
+/// import, the elements left out would be deleted and the filtered or rewritten values uploaded.
+pub(super) fn ensure_complete_import(conn: &Connection, project_id: &str) -> Result<()> {
+    if !table_exists(conn, IMPORT_LOG_TABLE)?
+        || !get_table_columns(conn, IMPORT_LOG_TABLE)?
+            .iter()
+            .any(|(name, _)| name == "partial")
+    {
+        return Ok(());
+    }
+
+    let maybe_partial: Option<String> = conn
+        .query_row(
+            &format!(
+                r#"SELECT "partial" FROM {} WHERE "project_id" = ?1
+                ORDER BY "timestamp" DESC, rowid DESC LIMIT 1"#,
+                escape_sql_ident(IMPORT_LOG_TABLE)
+            ),
+            [project_id],
+            |row| row.get(0),
+        )
+        .optional()?
+        .flatten();
+    if let Some(partial) = maybe_partial {
+        bail!(
+            "the project {project_id:?} was last imported partially ({partial}), pushing would delete or overwrite what was left out on the server; import the complete commit first"
+        );
+    }
+    Ok(())
+}
+
+/// The branch a two-way sync pushes to, `None` for the default branch of the project
+fn push_branch_id(project_selector: &ProjectSelector) -> Result<Option<&str>> {
+    let (ProjectSelector::ProjectId { commit, .. } | ProjectSelector::ProjectName { commit, .. }) =
+        project_selector;
+    match commit {
+        CommitSelector::BranchId { branch_id } => Ok(Some(branch_id)),
+        CommitSelector::DefaultBranch => Ok(None),
+        CommitSelector::BranchName { .. } | CommitSelector::CommitId { .. } => {
+            bail!("a two-way sync requires selecting the branch by its id, or the default branch")
+        }
+    }
+}
+
+/// Determines the local changes of a project, see [`local_changes`]
+fn project_local_changes(
+    conn: &Connection,
+    project_id: &str,
+    base_elements: &[Element],
+) -> Result<Vec<Value>> {
+    if !is_multi_project(conn)? {
+        return local_changes(conn, base_elements);
+    }
+
+    // the import writes to the core tables, hence the selection must not outlive the comparison
+    select_project(conn, project_id)?;
+    let changes = local_changes(conn, base_elements);
+    deselect_project(conn)?;
+    changes
+}
+
+/// The ids of the locally changed elements which also differ between the base and remote elements
+///
+/// An element created or deleted on one side counts as changed on that side. The ids are sorted.
+fn conflicting_elements(
+    base_elements: &[Element],
+    remote_elements: &[Element],
+    local_changes: &[Value],
+) -> Vec<String> {
+    let base: HashMap<&str, &Element> = base_elements.iter().map(|e| (e.id.as_str(), e)).collect();
+    let remote: HashMap<&str, &Element> =
+        remote_elements.iter().map(|e| (e.id.as_str(), e)).collect();
+
+    let mut conflicts: Vec<String> = local_changes
+        .iter()
+        .filter_map(|change| change["identity"]["@id"].as_str())
+        .filter(|id| base.get(id) != remote.get(id))
+        .map(str::to_owned)
+        .collect();
+    conflicts.sort();
+    conflicts
+}
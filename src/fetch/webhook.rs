@@ -0,0 +1,141 @@
+//! Minimal HTTP endpoint, allowing other tools to trigger a sync via a `POST` request
+//!
+//! Requests are authenticated by a shared token, taken from the environment variable
+//! `SYSML_WEBHOOK_TOKEN` and sent as `Authorization: Bearer <token>`. Without the token, the
+//! endpoint accepts any request, hence it is then only served on loopback addresses.
+
+use std::{net::SocketAddr, sync::Arc, time::Duration};
+
+use eyre::{Result, bail};
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::{TcpListener, TcpStream},
+    sync::Notify,
+};
+
+use super::maybe_env_var;
+
+/// Environment variable holding the token authenticating the requests
+const TOKEN_VAR: &str = "SYSML_WEBHOOK_TOKEN";
+
+/// Upper limit for the size of a request, larger requests are rejected
+const MAX_REQUEST_SIZE: usize = 64 * 1024;
+
+/// Time a client may take to send the header of its request, slower requests are dropped
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Listen for webhook requests on `addr`, notifying `trigger` on each authenticated `POST` request
+///
+/// Fails if the address can not be bound, or if it is reachable from other hosts while no token is
+/// set. Any authenticated `POST` request is accepted regardless of its path and body, which are
+/// ignored. Other methods are rejected. The requests are handled concurrently in the background,
+/// errors in the handling of single requests are logged.
+pub(super) async fn listen(addr: SocketAddr, trigger: Arc<Notify>) -> Result<()> {
+    let maybe_token = maybe_env_var(TOKEN_VAR)?.filter(|token| !token.is_empty());
+    if maybe_token.is_none() && !addr.ip().is_loopback() {
+        bail!(
+            "refusing to serve the webhook on {addr} without authentication, set {TOKEN_VAR} or listen on a loopback address"
+        );
+    }
+    let token: Arc<Option<String>> = Arc::new(maybe_token);
+
+    let listener = TcpListener::bind(addr).await?;
+    info!(
+        "listening for webhook requests on {}",
+        listener.local_addr()?
+    );
+
+    tokio::spawn(async move {
+        loop {
+            let (stream, peer) = match listener.accept().await {
+                Ok(accepted) => accepted,
+                Err(e) => {
+                    warn!("failed to accept a webhook request: {e}");
+                    continue;
+                }
+            };
+            let trigger = trigger.clone();
+            let token = token.clone();
+            tokio::spawn(async move {
+                if let Err(e) = handle_request(stream, &trigger, token.as_deref()).await {
+                    warn!("failed to handle the webhook request from {peer}: {e}");
+                }
+            });
+        }
+    });
+    Ok(())
+}
+
+/// Handles a single HTTP request
+async fn handle_request(
+    mut stream: TcpStream,
+    trigger: &Notify,
+    maybe_token: Option<&str>,
+) -> Result<()> {
+    // read until the end of the header, the body is not of interest
+    let mut request = Vec::new();
+    let mut buf = [0; 4096];
+    let read_header = async {
+        loop {
+            let n = stream.read(&mut buf).await?;
+            if n == 0 {
+                break Ok::<_, std::io::Error>(false);
+            }
+            request.extend_from_slice(&buf[..n]);
+            if request.windows(4).any(|w| w == b"\r\n\r\n") {
+                break Ok(true);
+            }
+            if request.len() > MAX_REQUEST_SIZE {
+                break Ok(false);
+            }
+        }
+    };
+    let Ok(header_complete) = tokio::time::timeout(REQUEST_TIMEOUT, read_header).await else {
+        bail!("the request header was not received within {REQUEST_TIMEOUT:?}");
+    };
+    let header_complete = header_complete?;
+
+    let request = String::from_utf8_lossy(&request);
+    let mut lines = request.lines();
+    let request_line = lines.next().unwrap_or_default();
+    let authorized = match maybe_token {
+        Some(token) => lines.any(|line| match line.split_once(':') {
+            Some((name, value)) if name.trim().eq_ignore_ascii_case("authorization") => value
+                .trim()
+                .strip_prefix("Bearer ")
+                .is_some_and(|sent| tokens_equal(sent.trim(), token)),
+            _ => false,
+        }),
+        None => true,
+    };
+    let status = match request_line.split_whitespace().next() {
+        _ if !header_complete => "400 Bad Request",
+        _ if !authorized => "401 Unauthorized",
+        Some("POST") => {
+            info!("received webhook request {request_line:?}, triggering a sync");
+            trigger.notify_one();
+            "202 Accepted"
+        }
+        _ => "405 Method Not Allowed",
+    };
+
+    debug!("answering the webhook request {request_line:?} with {status}");
+    stream
+        .write_all(
+            format!("HTTP/1.1 {status}\r\nContent-Length: 0\r\nConnection: close\r\n\r\n")
+                .as_bytes(),
+        )
+        .await?;
+    stream.shutdown().await?;
+    Ok(())
+}
+
+/// Compares the sent token to the expected one, in time independent of where they differ
+fn tokens_equal(sent: &str, token: &str) -> bool {
+    sent.len() == token.len()
+        && sent
+            .bytes()
+            .zip(token.bytes())
+            .fold(0, |diff, (a, b)| diff | (a ^ b))
+            == 0
+}
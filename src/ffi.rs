@@ -0,0 +1,164 @@
+//! C ABI to embed the import and the schema generation into non-Rust tooling, e.g. C++ or MATLAB
+//!
+//! This module is only built with the `ffi` feature. The shared library is built via
+//!
+//! ```sh
+//! cargo rustc --lib --release --features ffi --crate-type cdylib
+//! ```
+//!
+//! and the declarations for C are found in `include/sysml_v2_sql.h`. All functions take paths as
+//! NUL-terminated UTF-8 strings and return `0` on success. On failure, they return the exit code the
+//! CLI exits with for the same failure, and [`sysml_last_error`] tells what went wrong.
+
+#![allow(unsafe_code)]
+
+use std::{
+    cell::RefCell,
+    ffi::{CStr, CString, c_char, c_int},
+    path::PathBuf,
+};
+
+use crate::{failure, import, init_db, json_schema_to_sql, tweaks::open_db};
+use eyre::{Result, eyre};
+
+thread_local! {
+    /// The message of the last failure of a call on this thread
+    static LAST_ERROR: RefCell<Option<CString>> = const { RefCell::new(None) };
+}
+
+/// Initializes the db at `db_path` with the embedded schema, like the `init-db` command
+///
+/// # Safety
+///
+/// `db_path` must point to a NUL-terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn sysml_init_db(db_path: *const c_char) -> c_int {
+    call(|| {
+        let db_path = path_arg(db_path, "db_path")?.ok_or_else(|| eyre!("db_path is NULL"))?;
+        let mut conn = open_db(&db_path)?;
+        init_db::init_db(&mut conn)
+    })
+}
+
+/// Imports the elements of the JSON file at `json_path` into the db at `db_path`, like the
+/// `import-json` command
+///
+/// # Safety
+///
+/// `db_path` and `json_path` must point to NUL-terminated strings.
+#[no_mangle]
+pub unsafe extern "C" fn sysml_import_json(
+    db_path: *const c_char,
+    json_path: *const c_char,
+) -> c_int {
+    call(|| {
+        let db_path = path_arg(db_path, "db_path")?.ok_or_else(|| eyre!("db_path is NULL"))?;
+        let json_path =
+            path_arg(json_path, "json_path")?.ok_or_else(|| eyre!("json_path is NULL"))?;
+        let mut conn = open_db(&db_path)?;
+
+        let elements_stream = crate::util::CloneableJsonArrayStreamIterator::new(&json_path)?;
+        let options = import::ImportOptions {
+            provenance: import::ImportProvenance {
+                source: Some(json_path.to_string_lossy().into_owned()),
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        import::import_from_iter(elements_stream, &mut conn, &options)
+    })
+}
+
+/// Generates the SQL schema from the JSON schema at `json_schema_path`, like the
+/// `json-schema-to-sql-schema` command
+///
+/// The db at `db_path` is initialized with the schema, and the schema is written to `sql_path`.
+/// Either may be `NULL` to skip it.
+///
+/// # Safety
+///
+/// `json_schema_path` must point to a NUL-terminated string, `db_path` and `sql_path` must either
+/// be `NULL` or point to NUL-terminated strings.
+#[no_mangle]
+pub unsafe extern "C" fn sysml_generate_schema(
+    json_schema_path: *const c_char,
+    db_path: *const c_char,
+    sql_path: *const c_char,
+) -> c_int {
+    call(|| {
+        let json_schema_path = path_arg(json_schema_path, "json_schema_path")?
+            .ok_or_else(|| eyre!("json_schema_path is NULL"))?;
+        let maybe_db_path = path_arg(db_path, "db_path")?;
+        let maybe_sql_path = path_arg(sql_path, "sql_path")?;
+
+        let schema: json_schema_to_sql::Root = crate::util::read_json_file(&json_schema_path)?;
+        let mut maybe_conn = maybe_db_path.as_deref().map(open_db).transpose()?;
+        let sql = json_schema_to_sql::consume_json_schema(
+            &schema,
+            maybe_conn.as_mut(),
+            &json_schema_to_sql::SchemaOptions::default(),
+        )?;
+
+        if let Some(path) = maybe_sql_path {
+            info!("writing the SQL schema to {path:?}");
+            std::fs::write(path, sql)?;
+        }
+        Ok(())
+    })
+}
+
+/// The message of the last failure of a call on the calling thread, or `NULL` if it succeeded
+///
+/// The string is owned by the library and remains valid until the next call on the same thread.
+#[no_mangle]
+pub extern "C" fn sysml_last_error() -> *const c_char {
+    LAST_ERROR.with_borrow(|maybe_msg| {
+        maybe_msg
+            .as_ref()
+            .map_or(std::ptr::null(), |msg| msg.as_ptr())
+    })
+}
+
+/// Runs `f`, recording its failure for [`sysml_last_error`] and turning it into the exit code
+///
+/// Panics must not unwind into the foreign caller, hence they are caught and reported as failure.
+fn call(f: impl FnOnce() -> Result<()>) -> c_int {
+    let result =
+        std::panic::catch_unwind(std::panic::AssertUnwindSafe(f)).unwrap_or_else(|panic| {
+            let msg = panic
+                .downcast_ref::<&str>()
+                .map(|s| s.to_string())
+                .or_else(|| panic.downcast_ref::<String>().cloned())
+                .unwrap_or_default();
+            Err(eyre!("panicked: {msg}"))
+        });
+
+    let (code, maybe_msg) = match result {
+        Ok(()) => (0, None),
+        Err(report) => {
+            // interior NUL bytes would truncate the message
+            let msg = format!("{report:#}").replace('\0', "\\0");
+            (
+                c_int::from(failure::exit_code(&report)),
+                CString::new(msg).ok(),
+            )
+        }
+    };
+    LAST_ERROR.set(maybe_msg);
+    code
+}
+
+/// Reads the path argument `name`, `None` if it is `NULL`
+///
+/// # Safety
+///
+/// `ptr` must either be `NULL` or point to a NUL-terminated string.
+unsafe fn path_arg(ptr: *const c_char, name: &str) -> Result<Option<PathBuf>> {
+    if ptr.is_null() {
+        return Ok(None);
+    }
+    let path = CStr::from_ptr(ptr)
+        .to_str()
+        .map_err(|e| eyre!("{name} is not valid UTF-8: {e}"))?;
+    Ok(Some(PathBuf::from(path)))
+}
@@ -1,16 +1,28 @@
 // TODO Track element ids of thos eelements imported in the current operation, remove all relations of these
 
 use color_eyre::Section;
-use eyre::{Result, bail};
-use rusqlite::{Connection, Statement, ToSql};
+use eyre::{Context, Result, bail};
+use rusqlite::{Connection, Statement, limits::Limit, params_from_iter, types::Value as DbValue};
 use serde::Deserialize;
 use serde_json::{Map, Value};
-use std::collections::HashSet;
+use std::{
+    collections::{HashMap, HashSet},
+    fs::File,
+    io::BufWriter,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
 
 use crate::{
-    config::{ELEMENT_PK_COL, POLYMORPHIC_PROPS, TIME_BETWEEN_STATUS_REPORTS},
+    config::{
+        ELEMENT_PK_COL, IMPORT_LOG_TABLE, KEYED_RELATIONS_TABLE, PROJECT_COL, Pragmas,
+        RAW_JSON_COL, RELATION_NAMES_TABLE, TARGET_PROJECT_COL, config,
+    },
+    failure::Failure,
     maybe_time_report,
-    util::escape_sql_ident,
+    progress::{Phase, Progress},
+    transform::Transform,
+    util::{escape_sql_ident, escape_sql_str_lit, table_exists},
 };
 
 /// JSON representation of an Element in the SysML-v2 API
@@ -23,16 +35,186 @@ pub(crate) struct Element {
     pub(crate) rest: Map<String, Value>,
 }
 
+/// Options affecting how an import is performed
+#[derive(Debug, Clone, Default)]
+pub(crate) struct ImportOptions {
+    /// Run vacuum after the import
+    pub(crate) vacuum: bool,
+
+    /// Maintain integer surrogate keys for elements and relations
+    pub(crate) integer_keys: bool,
+
+    /// Names of relations to maintain the transitive closure of, in addition to those maintained
+    /// already, see [`crate::closure`]
+    pub(crate) closure_relations: Vec<String>,
+
+    /// Maintain the owner of each element in a column of the elements table, see [`crate::owner`]
+    pub(crate) owner_column: bool,
+
+    /// Maintain the ownership path of each element, see [`crate::paths`]
+    pub(crate) element_paths: bool,
+
+    /// Do not verify that the db matches the schema it was initialized with
+    pub(crate) skip_schema_check: bool,
+
+    /// Fail instead of warning if attributes of the elements are not understood
+    pub(crate) strict: bool,
+
+    /// Scan all elements instead of aborting on the first error, then report all errors together
+    /// and refuse to commit
+    pub(crate) collect_errors: bool,
+
+    /// Skip the elements which fail to import instead of aborting, and commit the others. The
+    /// skipped elements are written to this file, together with their errors.
+    pub(crate) maybe_skipped_file: Option<PathBuf>,
+
+    /// Where the imported elements originate from, recorded in the import log
+    pub(crate) provenance: ImportProvenance,
+
+    /// Delete the elements which are not part of the import, along with their relations and
+    /// extended properties. In dbs storing multiple projects, only elements of the same project are
+    /// deleted.
+    pub(crate) prune: bool,
+
+    /// Key the elements are stored under in dbs storing multiple projects, defaults to the project
+    /// of the provenance
+    pub(crate) project_key: Option<String>,
+
+    /// Pragmas tuning the bulk insert, instead of those of the config
+    pub(crate) maybe_pragmas: Option<Pragmas>,
+
+    /// Maximum number of rows inserted per statement, defaults to [`DEFAULT_BATCH_SIZE`]. When
+    /// collecting errors or continuing on errors, each row is inserted on its own.
+    pub(crate) maybe_batch_size: Option<usize>,
+
+    /// Receiver of the progress of the import, see [`crate::progress`]
+    pub(crate) progress: Progress,
+
+    /// Rewrites or drops each element before it is imported, see [`crate::transform`]
+    pub(crate) maybe_transform: Option<Transform>,
+
+    /// Only import the elements within the namespace of this qualified name, see
+    /// [`crate::package_filter`]
+    pub(crate) maybe_package: Option<String>,
+
+    /// Which attributes of the elements to import
+    pub(crate) attribute_filter: AttributeFilter,
+
+    /// Aborts the import once cancelled, checked for each element and before committing
+    pub(crate) cancellation: Cancellation,
+}
+
+/// Tells whether an import is cancelled, see [`ImportOptions::cancellation`]
+///
+/// Defaults to never cancelling. Cancelled imports fail and roll back, leaving the db unchanged.
+#[derive(Clone, Default)]
+pub(crate) struct Cancellation(Option<Arc<dyn Fn() -> bool + Send + Sync>>);
+
+impl Cancellation {
+    /// Cancel the import once `is_cancelled` returns `true`
+    #[cfg_attr(not(feature = "fetch"), allow(dead_code))]
+    pub(crate) fn new(is_cancelled: impl Fn() -> bool + Send + Sync + 'static) -> Self {
+        Self(Some(Arc::new(is_cancelled)))
+    }
+
+    /// Fails if the import is cancelled
+    fn check(&self) -> Result<()> {
+        if self.0.as_ref().is_some_and(|is_cancelled| is_cancelled()) {
+            bail!("the import was cancelled");
+        }
+        Ok(())
+    }
+}
+
+impl std::fmt::Debug for Cancellation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("Cancellation")
+    }
+}
+
+/// Which attributes of the elements are imported, by their name in the JSON
+///
+/// Attributes starting with `@`, e.g. `@type`, are always imported. Attributes filtered out are
+/// neither stored nor reported as not understood. An import filtering attributes is logged as
+/// partial, see [`ImportProvenance::partial`].
+#[derive(Debug, Clone, Default)]
+pub(crate) enum AttributeFilter {
+    /// Import all attributes
+    #[default]
+    All,
+
+    /// Import only these attributes
+    Only(HashSet<String>),
+
+    /// Import all but these attributes
+    Skip(HashSet<String>),
+}
+
+impl AttributeFilter {
+    /// The filter of `--only-attributes` or `--skip-attributes`, all attributes if neither is given
+    pub(crate) fn new(only: Vec<String>, skip: Vec<String>) -> Self {
+        match (only.is_empty(), skip.is_empty()) {
+            (false, _) => Self::Only(only.into_iter().collect()),
+            (true, false) => Self::Skip(skip.into_iter().collect()),
+            (true, true) => Self::All,
+        }
+    }
+
+    /// Whether the attribute `name` is imported
+    fn keeps(&self, name: &str) -> bool {
+        match self {
+            _ if name.starts_with('@') => true,
+            Self::All => true,
+            Self::Only(names) => names.contains(name),
+            Self::Skip(names) => !names.contains(name),
+        }
+    }
+}
+
+/// Default maximum number of rows inserted per statement, see [`ImportOptions::maybe_batch_size`]
+pub(crate) const DEFAULT_BATCH_SIZE: usize = 256;
+
+/// An element left out of an import continuing on errors, see [`ImportOptions::maybe_skipped_file`]
+#[derive(Debug, Clone, serde::Serialize)]
+struct SkippedElement {
+    #[serde(rename = "@id")]
+    id: String,
+
+    /// The error the element was skipped for, with its context
+    error: String,
+
+    /// The element as found in the imported JSON, absent if it is not part of the import
+    element: Option<Element>,
+}
+
+/// Origin of the elements of an import
+#[derive(Debug, Clone, Default)]
+pub(crate) struct ImportProvenance {
+    /// File or base URL of the API server the elements were read from
+    pub(crate) source: Option<String>,
+
+    /// Id of the project the elements were fetched from
+    pub(crate) project_id: Option<String>,
+
+    /// Id of the commit the elements were fetched from
+    pub(crate) commit_id: Option<String>,
+
+    /// Why the imported elements do not match the source as a whole, e.g. as they were filtered
+    /// or rewritten, empty if they do. Pushing from a partial import is refused, see
+    /// [`crate::fetch::push`].
+    pub(crate) partial: Vec<String>,
+}
+
 /// This function is passed a slice of [`Element`]s, and then calls [`import_from_iter`]
 pub(crate) fn import_from_slice(
     elements: &[Element],
     conn: &mut Connection,
-    vacuum: bool,
+    options: &ImportOptions,
 ) -> Result<()> {
     let maybe_elements_iter = elements
         .iter()
         .map(|e| -> Result<_, std::convert::Infallible> { Ok(e.to_owned()) });
-    import_from_iter(maybe_elements_iter, conn, vacuum)
+    import_from_iter(maybe_elements_iter, conn, options)
 }
 
 /// # Overview
@@ -49,20 +231,108 @@ pub(crate) fn import_from_slice(
 pub(crate) fn import_from_iter<E: Send + Sync + std::error::Error + 'static>(
     elements: impl Clone + Iterator<Item = Result<Element, E>>,
     conn: &mut Connection,
-    vacuum: bool,
+    options: &ImportOptions,
 ) -> Result<()> {
     let import_t0 = std::time::Instant::now();
 
-    crate::tweaks::before_bulk_insert(conn)?;
+    if options.skip_schema_check {
+        debug!("skipping the schema check");
+    } else {
+        crate::schema_info::verify_schema(conn)?;
+    }
+
+    let mut provenance = options.provenance.clone();
+    provenance.partial.extend(partial_reasons(options));
+
+    // the package filter applies first, as it selects the elements by their original names
+    let filtered_options;
+    let options = match &options.maybe_package {
+        Some(package) => {
+            let filter = crate::package_filter::package_filter(elements.clone(), package)?;
+            filtered_options = ImportOptions {
+                maybe_transform: Some(match options.maybe_transform.clone() {
+                    Some(transform) => filter.then(transform),
+                    None => filter,
+                }),
+                ..options.clone()
+            };
+            &filtered_options
+        }
+        None => options,
+    };
+
+    let pragmas = options.maybe_pragmas.as_ref().unwrap_or(&config().pragmas);
+    crate::tweaks::before_bulk_insert(conn, pragmas)?;
 
     debug!("enabling foreign key constraint support");
     conn.pragma_update(None, "foreign_keys", "ON")?;
 
+    // `INSERT OR REPLACE` only fires delete triggers (e.g. those maintaining the full-text index) for
+    // replaced rows if recursive triggers are enabled
+    debug!("enabling recursive triggers");
+    conn.pragma_update(None, "recursive_triggers", "ON")?;
+
     debug!("starting db transaction for import");
     let db_ta = conn.transaction()?;
 
-    let elements_table_columns = get_table_columns(&db_ta, "elements")?;
-    let extended_properties_table_columns = get_table_columns(&db_ta, "extended_properties")?;
+    let config = config();
+    let elements_table = escape_sql_ident(&config.elements_table);
+    let relations_table = escape_sql_ident(&config.relations_table);
+    let extended_table = escape_sql_ident(&config.extended_table);
+
+    let elements_table_columns = get_table_columns(&db_ta, &config.elements_table)?;
+    if elements_table_columns.is_empty() {
+        return Err(Failure::SchemaMismatch.report(format!(
+            "the db lacks the elements table {:?}",
+            config.elements_table
+        )));
+    }
+    let extended_properties_table_columns: Vec<_> =
+        get_table_columns(&db_ta, &config.extended_table)?
+            .into_iter()
+            .filter(|(col_name, _)| col_name != PROJECT_COL)
+            .collect();
+
+    // In dbs storing multiple projects, all rows are stored under the project of the import
+    let maybe_project = if elements_table_columns.iter().any(|(n, _)| n == PROJECT_COL) {
+        let Some(project_id) = options
+            .project_key
+            .as_deref()
+            .or(options.provenance.project_id.as_deref())
+        else {
+            bail!(
+                "the db stores multiple projects, hence the project of the imported elements must be given"
+            );
+        };
+        if options.integer_keys {
+            bail!("integer surrogate keys are not supported for dbs storing multiple projects");
+        }
+        debug!("importing into the project {project_id:?}");
+        Some(project_id)
+    } else {
+        None
+    };
+    let (
+        project_column,
+        project_value,
+        project_condition,
+        target_project_column,
+        target_project_value,
+    ) = match maybe_project {
+        Some(project_id) => {
+            let project_column = escape_sql_ident(PROJECT_COL);
+            let project_value = escape_sql_str_lit(project_id);
+            (
+                format!("{project_column}, "),
+                format!("{project_value}, "),
+                format!(" AND {project_column} = {project_value}"),
+                // targets are assumed to be in the same project, until resolved otherwise
+                format!(", {}", escape_sql_ident(TARGET_PROJECT_COL)),
+                format!(", {project_value}"),
+            )
+        }
+        None => Default::default(),
+    };
 
     //
     // Damage tracking
@@ -75,57 +345,101 @@ pub(crate) fn import_from_iter<E: Send + Sync + std::error::Error + 'static>(
     // Prepare SQL statements
     //
 
+    // errors have to be attributed to single elements right away to collect or skip them
+    let continue_on_error = options.maybe_skipped_file.is_some();
+    let batch_size = if options.collect_errors || continue_on_error {
+        1
+    } else {
+        options.maybe_batch_size.unwrap_or(DEFAULT_BATCH_SIZE)
+    };
+
     // Statement to insert into the elements table
-    let statement = format!(
-        r#"INSERT OR REPLACE INTO "elements" VALUES ({})"#,
-        std::iter::repeat_n("?", elements_table_columns.len())
-            .collect::<Vec<_>>()
-            .join(", ")
-    );
-    debug!("prepared the following statement:\n{statement}"); // debug, since statement is actually generated as opposed to being hardcoded.
-    let mut e_insert_stmt = db_ta.prepare(&statement)?;
+    let mut e_inserts = BatchedInsert::new(
+        &db_ta,
+        format!(r#"INSERT OR REPLACE INTO {elements_table} VALUES "#),
+        format!(
+            "({})",
+            std::iter::repeat_n("?", elements_table_columns.len())
+                .collect::<Vec<_>>()
+                .join(", ")
+        ),
+        elements_table_columns.len(),
+        batch_size,
+    )?;
 
     // Statement to insert into the relations table
     // TODO why do we fail with primary key unique failure with `INSERT INTO`?
-    let statement =
-        r#"INSERT OR REPLACE INTO "relations"("name", "origin_id", "target_id") VALUES (?, ?, ?)"#;
-    trace!("prepared the following statement:\n{statement}");
-    let mut r_insert_stmt = db_ta.prepare(statement)?;
+    let mut r_inserts = BatchedInsert::new(
+        &db_ta,
+        format!(
+            r#"INSERT OR REPLACE INTO {relations_table}({project_column}"name", "origin_id", "target_id"{target_project_column}) VALUES "#
+        ),
+        format!("({project_value}?, ?, ?{target_project_value})"),
+        3,
+        batch_size,
+    )?
+    .with_notes(&[
+        "a cause for this could be an incomplete JSON file, that does not contain all elements of the model",
+        "are both element ids present in the imported JSON?",
+    ]);
+
+    // Statement to register relation names on first sight, dbs predating the relation names table
+    // lack it
+    let mut rn_insert_stmt = if table_exists(&db_ta, RELATION_NAMES_TABLE)? {
+        let statement = format!(
+            r#"INSERT OR IGNORE INTO {}("name") VALUES (?)"#,
+            escape_sql_ident(RELATION_NAMES_TABLE)
+        );
+        trace!("prepared the following statement:\n{statement}");
+        Some(db_ta.prepare(&statement)?)
+    } else {
+        None
+    };
 
     // One statements for each column in the extended_properties table
-    let maybe_e_p_insert_stmts: Result<Vec<_>, rusqlite::Error> = extended_properties_table_columns
+    let maybe_e_p_inserts: Result<Vec<_>> = extended_properties_table_columns
         .iter()
         .filter(|(col_name, _)| col_name != ELEMENT_PK_COL) // filter out an insert for the first column, the "@id@ primary key
         .map(|(col_name, _)| {
-            format!(
-                r#"INSERT INTO "extended_properties"("@id", {}) VALUES (?, ?)"#,
-                escape_sql_ident(col_name)
+            BatchedInsert::new(
+                &db_ta,
+                format!(
+                    r#"INSERT INTO {extended_table}({project_column}"@id", {}) VALUES "#,
+                    escape_sql_ident(col_name)
+                ),
+                format!("({project_value}?, ?)"),
+                2,
+                batch_size,
             )
         })
-        .inspect(|statement| debug!("prepared the following statement:\n{statement}"))
-        .map(|statement| db_ta.prepare(&statement))
         .collect();
-    let mut e_p_insert_stmts = maybe_e_p_insert_stmts?;
+    let mut e_p_inserts = maybe_e_p_inserts?;
     assert_eq!(
         extended_properties_table_columns.len(),
-        e_p_insert_stmts.len() + 1,
+        e_p_inserts.len() + 1,
         r#"extended_properties_table columns must have exactly one element more than maybe_e_p_insert statements, because there is an insert statement for each column except for the primary key column 0 with the name "@id""#
     );
 
     // Statement to track those elements inserted during this import for relations/
     // extended_properties damage tracking
-    let statement = r#"INSERT INTO "inserted_elements" VALUES (?)"#;
-    trace!("prepared the following statement:\n{statement}");
-    let mut e_tracking_insert_stmt = db_ta.prepare(statement)?;
+    let mut e_tracking_inserts = BatchedInsert::new(
+        &db_ta,
+        r#"INSERT INTO "inserted_elements" VALUES "#.to_owned(),
+        "(?)".to_owned(),
+        1,
+        batch_size,
+    )?;
 
     // Statement to remove relations and extended_properties originating from the recently inserted
     // elements
-    let statement = r#"
-        DELETE FROM "relations" WHERE "origin_id" IN (SELECT "@id" FROM "inserted_elements");
-        DELETE FROM "extended_properties" WHERE "@id" IN (SELECT "@id" FROM "inserted_elements");
-    "#;
+    let statement = format!(
+        r#"
+        DELETE FROM {relations_table} WHERE "origin_id" IN (SELECT "@id" FROM "inserted_elements"){project_condition};
+        DELETE FROM {extended_table} WHERE "@id" IN (SELECT "@id" FROM "inserted_elements"){project_condition};
+    "#
+    );
     trace!("prepared the following statement:\n{statement}");
-    let mut obsolete_delete_stmt = db_ta.prepare(statement)?;
+    let mut obsolete_delete_stmt = db_ta.prepare(&statement)?;
 
     //
     // Track unused or misunderstood JSON properties and database columns
@@ -141,7 +455,7 @@ pub(crate) fn import_from_iter<E: Send + Sync + std::error::Error + 'static>(
     // A complex attribute is one which itself is a JSON Object, for example the `{ "@id": "..." }`
     // observed for relations between elements. These will be imported into the relations table.
     //
-    // Very few (currently only one, tracked in POLYMORPHIC_PROPS) elements are know to be either
+    // Very few (tracked in the polymorphic properties of the config) elements are know to be either
     // primitive or complex. These get special treatment, they either might be inserted into a
     // corresponding column in elements, or into the relations table.
 
@@ -164,6 +478,9 @@ pub(crate) fn import_from_iter<E: Send + Sync + std::error::Error + 'static>(
     // all attributes which where observed at least once as relation (both 1:1 and 1:*)
     let mut observed_relational_attrs = HashSet::new();
 
+    // all relation names which were registered in the relation names table during this import
+    let mut registered_relation_names = HashSet::new();
+
     // all attributes which where observed at least once as not a relation but complex
     let mut observed_unexpected_complex_attrs = HashSet::new();
 
@@ -171,92 +488,130 @@ pub(crate) fn import_from_iter<E: Send + Sync + std::error::Error + 'static>(
     // Insert elements
     //
 
+    let insert_elements_span = info_span!("insert_elements").entered();
     info!("inserting elements");
+    options.progress.phase(Phase::InsertingElements);
     let elements_t0 = std::time::Instant::now();
-    let mut report_td = TIME_BETWEEN_STATUS_REPORTS;
-    let mut elements_inserted = 0;
-    for maybe_element in elements.clone() {
-        let element = maybe_element?;
+    let mut report_td = config.time_between_status_reports();
+    let mut elements_parsed: usize = 0;
+    let mut elements_inserted: usize = 0;
+    // errors of single elements, collected instead of aborting on the first one if requested
+    let mut element_errors: Vec<eyre::Report> = Vec::new();
+    let mut failed_element_ids = HashSet::new();
 
-        // sporadically report on timing
-        maybe_time_report!("element", elements_t0, report_td, elements_inserted);
-        elements_inserted += 1;
+    // elements left out of the import, if it continues on errors
+    let mut skipped_elements = Vec::new();
 
+    let mut insert_element = |element: &Element| -> Result<()> {
         let mut db_row_values: Vec<_> = Vec::with_capacity(elements_table_columns.len());
 
         for (column_name, column_type) in &elements_table_columns {
             // special case: the @id is not in the Element::rest, but in Element::id
             if column_name == ELEMENT_PK_COL {
-                db_row_values.push(RusValue::Text(element.id.clone()));
+                db_row_values.push(DbValue::Text(element.id.clone()));
+                unused_db_columns.remove(column_name);
+                continue;
+            }
+
+            // special case: the raw JSON column holds the whole element
+            if column_name == RAW_JSON_COL {
+                db_row_values.push(DbValue::Text(serde_json::to_string(element)?));
                 unused_db_columns.remove(column_name);
                 continue;
             }
 
-            let maybe_json_value = element.rest.get(column_name);
+            // special case: the project is not in the JSON, but given by the import
+            if let Some(project_id) = maybe_project.filter(|_| column_name == PROJECT_COL) {
+                db_row_values.push(DbValue::Text(project_id.to_owned()));
+                unused_db_columns.remove(column_name);
+                continue;
+            }
+
+            let maybe_json_value = json_value_for_column(&element.rest, column_name);
             if maybe_json_value.is_some() {
                 unused_db_columns.remove(column_name);
             }
 
-            use rusqlite::types::Value as RusValue;
-            let db_value = match maybe_json_value {
+            let db_value = match db_value_for_column(column_name, *column_type, maybe_json_value)
+                .wrap_err_with(|| {
+                    attribute_context(&element.id, &json_pointer(column_name.split('.')))
+                })? {
+                Some(db_value) => db_value,
                 None => {
-                    trace!(
-                        "setting {column_name:?} to NULL, its not present in this element's JSON"
+                    warn!(
+                        "db expects column {column_name:?} of type {column_type:?}, but JSON is {maybe_json_value:?}"
                     );
-                    RusValue::Null
-                }
-                Some(Value::Null) => RusValue::Null,
-                Some(Value::Bool(b)) => RusValue::Integer(if *b { 1 } else { 0 }),
-                Some(Value::String(s))
-                    if column_name.starts_with("is")
-                        && column_name
-                            .chars()
-                            .nth(2)
-                            .map(char::is_uppercase)
-                            .unwrap_or(false) =>
-                {
-                    RusValue::Integer(if s.parse()? { 1 } else { 0 })
-                }
-                Some(Value::Number(n)) if n.is_f64() => {
-                    RusValue::Real(n.as_f64().expect("floating point number"))
-                }
-                Some(Value::Number(n)) => RusValue::Integer(n.as_i64().expect("integer number")),
-                Some(Value::String(s)) => RusValue::Text(s.to_string()),
-                Some(v @ Value::Array(_)) | Some(v @ Value::Object(_)) => {
-                    if POLYMORPHIC_PROPS.iter().any(|kpf| kpf == column_name) {
-                        trace!(
-                            "the {column_name:?} column is known to be polymorph, setting it to NULL"
-                        );
-                    } else {
-                        warn!(
-                            "db expects column {column_name:?} of type {column_type:?}, but JSON is {v:?}"
-                        );
-                        warn!("skipping this entry, setting it to NULL instead");
-                    }
-                    RusValue::Null
+                    warn!("skipping this entry, setting it to NULL instead");
+                    DbValue::Null
                 }
             };
 
             db_row_values.push(db_value);
         }
-
-        // TODO remove this ugly vtable hack
-        let mut ref_vec = Vec::with_capacity(db_row_values.len());
-        for v in &db_row_values {
-            ref_vec.push(v as &dyn ToSql);
-        }
         assert_eq!(elements_table_columns.len(), db_row_values.len());
 
         trace!("inserting row for element");
-        e_insert_stmt.execute(ref_vec.as_slice())?;
+        e_inserts.push(db_row_values, || {
+            format!("inserting the element {:?}", element.id)
+        })?;
 
         // retain the information that this element was (re-) inserted by the current import run
-        e_tracking_insert_stmt.execute([&element.id])?;
+        e_tracking_inserts.push([DbValue::Text(element.id.clone())], || {
+            format!("tracking the element {:?}", element.id)
+        })?;
+        Ok(())
+    };
+    for maybe_element in elements.clone() {
+        options.cancellation.check()?;
+        let element = match maybe_element {
+            Ok(element) => element,
+            // the stream of elements can not be resumed after a parse error
+            Err(e) if options.collect_errors => {
+                element_errors.push(e.into());
+                break;
+            }
+            Err(e) => return Err(e.into()),
+        };
+        elements_parsed += 1;
+        options.progress.elements_parsed(elements_parsed);
+        let Some(element) = transform_element(options, element)? else {
+            continue;
+        };
+
+        // sporadically report on timing
+        maybe_time_report!("element", elements_t0, report_td, elements_inserted);
+
+        let result = if continue_on_error {
+            in_savepoint(&db_ta, || insert_element(&element))
+        } else {
+            insert_element(&element)
+        };
+        match result {
+            Ok(()) => {
+                elements_inserted += 1;
+                options.progress.rows_inserted(elements_inserted);
+            }
+            Err(e) if options.collect_errors => {
+                element_errors.push(e);
+                failed_element_ids.insert(element.id);
+            }
+            Err(e) if continue_on_error => {
+                warn!("skipping the element {:?}: {e:#}", element.id);
+                failed_element_ids.insert(element.id.clone());
+                skipped_elements.push(SkippedElement {
+                    id: element.id.clone(),
+                    error: format!("{e:#}"),
+                    element: Some(element),
+                });
+            }
+            Err(e) => return Err(e),
+        }
     }
 
-    // finalize all prepared statements which are not used later
-    e_insert_stmt.finalize()?;
-    e_tracking_insert_stmt.finalize()?;
+    // insert the rows of the last batches, and finalize all prepared statements which are not used
+    // later
+    e_inserts.finish()?;
+    e_tracking_inserts.finish()?;
 
     // Each relation associated with each element imported during this import run needs to be
     // deleted, to have only those relations from the current import, without remnants from the
@@ -266,25 +621,40 @@ pub(crate) fn import_from_iter<E: Send + Sync + std::error::Error + 'static>(
     );
     obsolete_delete_stmt.execute(())?;
     obsolete_delete_stmt.finalize()?;
-    db_ta.execute(r#"DROP TABLE "inserted_elements""#, ())?;
 
+    if options.prune {
+        debug!("removing elements which are not part of the current import");
+        db_ta.execute_batch(&format!(
+            r#"
+            DELETE FROM {relations_table} WHERE "origin_id" NOT IN (SELECT "@id" FROM "inserted_elements"){project_condition};
+            DELETE FROM {extended_table} WHERE "@id" NOT IN (SELECT "@id" FROM "inserted_elements"){project_condition};
+        "#
+        ))?;
+        let pruned = db_ta.execute(
+            &format!(
+                r#"DELETE FROM {elements_table} WHERE "@id" NOT IN (SELECT "@id" FROM "inserted_elements"){project_condition}"#
+            ),
+            (),
+        )?;
+        info!("removed {pruned} elements which are not part of the current import");
+    }
     maybe_time_report!("element", elements_t0, elements_inserted);
+    insert_elements_span.exit();
 
     //
     // Insert relations & extended properties
     //
 
+    let insert_relations_span = info_span!("insert_relations").entered();
     info!("inserting relations & extended_properties");
+    options.progress.phase(Phase::InsertingRelations);
 
     let mut relations_inserted = 0;
 
     let relations_t0 = std::time::Instant::now();
     report_td = std::time::Duration::from_secs(0);
-    for maybe_element in elements {
-        let element = maybe_element?;
-
-        // sporadically report on timing
-        maybe_time_report!("relation", relations_t0, report_td, relations_inserted);
+    let mut insert_relations = |element: &Element| -> Result<usize> {
+        let mut relations_inserted = 0;
 
         // go through all JSON attributes, and try to stuff them into our db
         for (json_attr_name, json_attr_value) in &element.rest {
@@ -307,13 +677,17 @@ pub(crate) fn import_from_iter<E: Send + Sync + std::error::Error + 'static>(
                                     trace!("found 1:1 relation of type {json_attr_name:?}");
 
                     observed_relational_attrs.insert(json_attr_name.to_owned());
+                    if registered_relation_names.insert(json_attr_name.to_owned()) {
+                        register_relation_name(&mut rn_insert_stmt, json_attr_name)?;
+                    }
                     relations_inserted += 1;
 
                     insert_relation(
-                        &mut r_insert_stmt,
+                        &mut r_inserts,
                         json_attr_name,
                         &element.id,
                         &target_element.id,
+                        || attribute_context(&element.id, &json_pointer([json_attr_name.as_str()])),
                     )?;
                 }
 
@@ -326,14 +700,19 @@ pub(crate) fn import_from_iter<E: Send + Sync + std::error::Error + 'static>(
 
                     trace!("found a 1:* relation of type {json_attr_name:?}");
                     observed_relational_attrs.insert(json_attr_name.to_owned());
+                    // empty arrays are no evidence for a relation, hence their name is not registered
+                    if !target_elements.is_empty() && registered_relation_names.insert(json_attr_name.to_owned()) {
+                        register_relation_name(&mut rn_insert_stmt, json_attr_name)?;
+                    }
                     relations_inserted += target_elements.len();
 
-                    for target_element in target_elements {
+                    for (idx, target_element) in target_elements.into_iter().enumerate() {
                         insert_relation(
-                            &mut r_insert_stmt,
+                            &mut r_inserts,
                             json_attr_name,
                             &element.id,
                             &target_element.id,
+                            || attribute_context(&element.id, &json_pointer([json_attr_name.as_str(), &idx.to_string()])),
                         )?;
                     }
                 }
@@ -349,10 +728,14 @@ pub(crate) fn import_from_iter<E: Send + Sync + std::error::Error + 'static>(
 
                         match column_type {
                             rusqlite::types::Type::Text => {
-                                let text_values: Vec<String> = serde_json::from_value(json_value.to_owned())?;
-                                for text_value in text_values {
+                                let text_values: Vec<String> = serde_json::from_value(json_value.to_owned())
+                                    .wrap_err_with(|| attribute_context(&element.id, &json_pointer([column_name.as_str()])))?;
+                                for (idx, text_value) in text_values.into_iter().enumerate() {
                                     trace!("inserting row for extended_properties");
-                                    e_p_insert_stmts[column_idx - 1].execute([&element.id, &text_value])?;
+                                    e_p_inserts[column_idx - 1].push(
+                                        [DbValue::Text(element.id.clone()), DbValue::Text(text_value)],
+                                        || attribute_context(&element.id, &json_pointer([column_name.as_str(), &idx.to_string()])),
+                                    )?;
                                 }
                             }
                             rusqlite::types::Type::Null
@@ -365,12 +748,22 @@ pub(crate) fn import_from_iter<E: Send + Sync + std::error::Error + 'static>(
                     }
                 }
 
+                // nested objects are stored in the elements table, either flattened into one
+                // column per nested property or as JSON text
+                Value::Object(_)
+                    if elements_table_columns
+                        .iter()
+                        .any(|(n, _)| is_column_of_property(n, json_attr_name)) =>
+                {
+                    continue;
+                }
+
                 // This property is complex, but believed to be primitive and is not known to be
                 // polymorph.
                 // Occurences of this indicate a bug in our business logic
                 v @ Value::Array(_) | v @ Value::Object(_)
                     if observed_primitive_attrs.contains(json_attr_name)
-                        && POLYMORPHIC_PROPS.iter().all(|kpf| kpf != json_attr_name) =>
+                        && !config.is_polymorphic(json_attr_name) =>
                 {
                     observed_unexpected_polymorph_attrs.insert(json_attr_name.to_owned());
                     error!("the JSON attribute {json_attr_name:?} is believed to be literal, but was found with the following value:\n{v:#?}");
@@ -385,17 +778,125 @@ pub(crate) fn import_from_iter<E: Send + Sync + std::error::Error + 'static>(
                 }
             }
         }
+        Ok(relations_inserted)
+    };
+    for maybe_element in elements.clone() {
+        options.cancellation.check()?;
+        let element = match maybe_element {
+            Ok(element) => element,
+            // the parse error was already collected while inserting the elements
+            Err(_) if options.collect_errors => break,
+            Err(e) => return Err(e.into()),
+        };
+        let Some(element) = transform_element(options, element)? else {
+            continue;
+        };
+        if failed_element_ids.contains(&element.id) {
+            continue;
+        }
+
+        // sporadically report on timing
+        maybe_time_report!("relation", relations_t0, report_td, relations_inserted);
+
+        let result = if continue_on_error {
+            in_savepoint(&db_ta, || insert_relations(&element))
+        } else {
+            insert_relations(&element)
+        };
+        match result {
+            Ok(inserted) => {
+                relations_inserted += inserted;
+                options.progress.rows_inserted(relations_inserted);
+            }
+            Err(e) if options.collect_errors => element_errors.push(e),
+            Err(e) if continue_on_error => {
+                warn!("skipping the element {:?}: {e:#}", element.id);
+                remove_element(&db_ta, &element.id, &project_condition)?;
+                elements_inserted -= 1;
+                failed_element_ids.insert(element.id.clone());
+                skipped_elements.push(SkippedElement {
+                    id: element.id.clone(),
+                    error: format!("{e:#}"),
+                    element: Some(element),
+                });
+            }
+            Err(e) => return Err(e),
+        }
     }
-    r_insert_stmt.finalize()?;
+    r_inserts.finish()?;
+    rn_insert_stmt.map(Statement::finalize).transpose()?;
 
-    for stmt in e_p_insert_stmts {
-        stmt.finalize()?;
+    for e_p_insert in e_p_inserts {
+        e_p_insert.finish()?;
     }
 
     maybe_time_report!("relations", relations_t0, relations_inserted);
+    insert_relations_span.exit();
 
-    info!("committing changes to db");
-    db_ta.commit()?;
+    if maybe_project.is_some() {
+        crate::projects::resolve_cross_project_targets(&db_ta)?;
+    }
+
+    // the foreign keys are only checked on commit, i.e. the first violation would abort the import
+    if options.collect_errors {
+        let violations = foreign_key_violations(&db_ta)?;
+        element_errors.extend(violations.into_iter().map(|(_, report)| report));
+        if !element_errors.is_empty() {
+            for e in &element_errors {
+                error!("{e:#}");
+            }
+            bail!(
+                "found {} errors in the imported elements, refusing to commit the import",
+                element_errors.len()
+            );
+        }
+    }
+
+    // skipping an element leaves the relations to it dangling, hence this repeats until the
+    // elements referring to skipped elements are skipped as well
+    if continue_on_error {
+        loop {
+            let violations = foreign_key_violations(&db_ta)?;
+            if violations.is_empty() {
+                break;
+            }
+            for (maybe_id, report) in violations {
+                let Some(id) = maybe_id else {
+                    return Err(report);
+                };
+                if failed_element_ids.insert(id.clone()) {
+                    warn!("skipping the element {id:?}: {report:#}");
+                    remove_element(&db_ta, &id, &project_condition)?;
+                    elements_inserted = elements_inserted.saturating_sub(1);
+                    skipped_elements.push(SkippedElement {
+                        id,
+                        error: format!("{report:#}"),
+                        element: None,
+                    });
+                }
+            }
+        }
+    }
+
+    options.progress.phase(Phase::UpdatingDerivedTables);
+    // pruning may change the relations of any element
+    let maybe_changed_elements =
+        (!options.prune).then_some(r#"SELECT "@id" FROM "inserted_elements""#);
+    // once added, the surrogate keys are kept up to date by each import, as by each edit
+    if options.integer_keys || table_exists(&db_ta, KEYED_RELATIONS_TABLE)? {
+        crate::surrogate_keys::update_surrogate_keys(&db_ta, maybe_changed_elements)?;
+    }
+    crate::closure::update_closures(&db_ta, maybe_changed_elements)?;
+    crate::closure::add_closure_relations(&db_ta, &options.closure_relations)?;
+    crate::owner::update_owner_column(&db_ta, maybe_changed_elements)?;
+    if options.owner_column {
+        crate::owner::add_owner_column(&db_ta)?;
+    }
+    crate::paths::update_element_paths(&db_ta)?;
+    if options.element_paths {
+        crate::paths::add_element_paths(&db_ta)?;
+    }
+    db_ta.execute(r#"DROP TABLE "inserted_elements""#, ())?;
 
     trace!("observed JSON attributes:\n{observed_json_attrs:#?}");
     trace!("observed non-relation JSON attributes:\n{observed_primitive_attrs:#?}");
@@ -425,32 +926,75 @@ pub(crate) fn import_from_iter<E: Send + Sync + std::error::Error + 'static>(
         .cloned()
         .collect();
 
-    let problematic_attributes: HashSet<_> = observed_json_attrs
-        .difference(&always_valid_attributes)
-        .cloned()
-        .collect();
+    // with the raw JSON of the elements stored, no attribute is lost
+    let problematic_attributes: HashSet<_> = if known_db_column_set.contains(RAW_JSON_COL) {
+        HashSet::new()
+    } else {
+        observed_json_attrs
+            .difference(&always_valid_attributes)
+            .cloned()
+            .collect()
+    };
 
     if !problematic_attributes.is_empty() {
+        // the transaction is rolled back, leaving the db unchanged
+        if options.strict {
+            return Err(Failure::StrictAttributes.report(format!(
+                "the following attributes were not always understood:\n{problematic_attributes:#?}"
+            )));
+        }
         warn!("the following attributes were not always understood:\n{problematic_attributes:#?}");
     }
 
-    crate::tweaks::after_bulk_insert(conn, vacuum)?;
+    if let Some(path) = &options.maybe_skipped_file {
+        write_skipped_elements(path, skipped_elements, elements)?;
+    }
+
+    record_import(&db_ta, &provenance, elements_inserted)?;
+
+    info!("committing changes to db");
+    options.progress.phase(Phase::Committing);
+    // cancelling while no statement runs, e.g. after updating the derived tables, is noticed here
+    options.cancellation.check()?;
+    db_ta.commit()?;
+
+    crate::tweaks::after_bulk_insert(conn, options.vacuum)?;
 
     info!("import took {:?}", import_t0.elapsed());
     Ok(())
 }
 
+/// Applies the transform of the import to `element`, if any, `None` if the element is dropped
+///
+/// The attribute filter applies to the transformed element, so that transforms may derive the
+/// attributes to import from those filtered out.
+fn transform_element(options: &ImportOptions, element: Element) -> Result<Option<Element>> {
+    let maybe_element = match &options.maybe_transform {
+        Some(transform) => transform.apply(element)?,
+        None => Some(element),
+    };
+    Ok(maybe_element.map(|mut element| {
+        if !matches!(options.attribute_filter, AttributeFilter::All) {
+            element
+                .rest
+                .retain(|name, _| options.attribute_filter.keeps(name));
+        }
+        element
+    }))
+}
+
 /// Gets a [`Vec`] with column name, column type tuples for a given table
 ///
 /// Returns a Vec, so that the order as returned by the DB is maintained
-fn get_table_columns(
+pub(crate) fn get_table_columns(
     conn: &Connection,
     table_name: &str,
 ) -> Result<Vec<(String, rusqlite::types::Type)>> {
     // contains the type as `String`
     let mut columns_str = Vec::new();
 
-    conn.pragma(None, "table_info", table_name, |row| {
+    // the table in the main schema, even if a project is selected, see [`crate::projects`]
+    conn.pragma(Some(rusqlite::DatabaseName::Main), "table_info", table_name, |row| {
         let idx: usize = row.get_unwrap(0);
         let name: String = row.get_unwrap(1);
         let r#type: String = row.get_unwrap(2);
@@ -470,9 +1014,24 @@ fn get_table_columns(
             "TEXT" => rusqlite::types::Type::Text,
             "BLOB" => rusqlite::types::Type::Blob,
             "ANY" => rusqlite::types::Type::Text, // TODO revisit this hack
-            x => bail!(
-                "unexpected SQLite data type {x:?} encountered in schema of {table_name:?} table"
-            ),
+
+            // tables which are not STRICT may declare arbitrary types, these are mapped following
+            // the rules SQLite uses to determine the affinity of a column
+            // see <https://www.sqlite.org/datatype3.html#determination_of_column_affinity>
+            x => match x.to_uppercase() {
+                x if x.contains("INT") => rusqlite::types::Type::Integer,
+                x if ["CHAR", "CLOB", "TEXT"].iter().any(|t| x.contains(t)) => {
+                    rusqlite::types::Type::Text
+                }
+                x if x.contains("BLOB") => rusqlite::types::Type::Blob,
+                x if x.is_empty() => rusqlite::types::Type::Text, // same hack as for ANY
+                x if ["REAL", "FLOA", "DOUB"].iter().any(|t| x.contains(t)) => {
+                    rusqlite::types::Type::Real
+                }
+                x => bail!(
+                    "unexpected SQLite data type {x:?} encountered in schema of {table_name:?} table"
+                ),
+            },
         };
         columns_typed.push((column_name, parsed_ty));
     }
@@ -485,24 +1044,508 @@ fn get_table_columns(
     Ok(columns_typed)
 }
 
+/// Determines the value the importer stores for `element` in a column of the elements table
+///
+/// The raw JSON column holds the whole element, all other columns are converted by
+/// [`db_value_for_column`].
+pub(crate) fn element_db_value(
+    element: &Element,
+    column_name: &str,
+    column_type: rusqlite::types::Type,
+) -> Result<Option<rusqlite::types::Value>> {
+    if column_name == RAW_JSON_COL {
+        return Ok(Some(rusqlite::types::Value::Text(serde_json::to_string(
+            element,
+        )?)));
+    }
+    db_value_for_column(
+        column_name,
+        column_type,
+        json_value_for_column(&element.rest, column_name),
+    )
+}
+
+/// Converts the JSON value of a property into the value stored for it in a column of the elements
+/// table
+///
+/// Returns `None` if the value can not be represented in the column. Complex values of known
+/// polymorph properties are stored as `NULL`, as they are represented as relations instead.
+pub(crate) fn db_value_for_column(
+    column_name: &str,
+    column_type: rusqlite::types::Type,
+    maybe_json_value: Option<&Value>,
+) -> Result<Option<rusqlite::types::Value>> {
+    use rusqlite::types::Value as RusValue;
+    let db_value = match maybe_json_value {
+        None => {
+            trace!("setting {column_name:?} to NULL, its not present in this element's JSON");
+            RusValue::Null
+        }
+        Some(Value::Null) => RusValue::Null,
+        Some(Value::Bool(b)) => RusValue::Integer(if *b { 1 } else { 0 }),
+        Some(Value::String(s))
+            if column_name.starts_with("is")
+                && column_name
+                    .chars()
+                    .nth(2)
+                    .map(char::is_uppercase)
+                    .unwrap_or(false) =>
+        {
+            RusValue::Integer(if s.parse()? { 1 } else { 0 })
+        }
+        Some(Value::Number(n)) if n.is_f64() => {
+            RusValue::Real(n.as_f64().expect("floating point number"))
+        }
+        Some(Value::Number(n)) => RusValue::Integer(n.as_i64().expect("integer number")),
+        Some(Value::String(s)) => RusValue::Text(s.to_string()),
+        Some(v @ Value::Object(_))
+            if column_type == rusqlite::types::Type::Text
+                && !config().is_polymorphic(column_name) =>
+        {
+            RusValue::Text(v.to_string())
+        }
+        Some(Value::Array(_)) | Some(Value::Object(_)) if config().is_polymorphic(column_name) => {
+            trace!("the {column_name:?} column is known to be polymorph, setting it to NULL");
+            RusValue::Null
+        }
+        Some(Value::Array(_)) | Some(Value::Object(_)) => return Ok(None),
+    };
+
+    Ok(Some(db_value))
+}
+
+/// Registers a relation name in the relation names table, unless it is already known
+fn register_relation_name(
+    prepared_statement: &mut Option<Statement>,
+    relation_kind: &str,
+) -> Result<()> {
+    let Some(prepared_statement) = prepared_statement else {
+        return Ok(());
+    };
+
+    if prepared_statement.execute([relation_kind])? > 0 {
+        info!("registered the new relation name {relation_kind:?}");
+    }
+    Ok(())
+}
+
+/// Why an import with these options does not match the source as a whole, see
+/// [`ImportProvenance::partial`]
+fn partial_reasons(options: &ImportOptions) -> Vec<String> {
+    let mut reasons = Vec::new();
+    if let Some(package) = &options.maybe_package {
+        reasons.push(format!("only the namespace {package}"));
+    }
+    let sorted = |attributes: &HashSet<String>| {
+        let mut attributes: Vec<_> = attributes.iter().map(String::as_str).collect();
+        attributes.sort_unstable();
+        attributes.join(", ")
+    };
+    match &options.attribute_filter {
+        AttributeFilter::All => {}
+        AttributeFilter::Only(only) => {
+            reasons.push(format!("only the attributes {}", sorted(only)))
+        }
+        AttributeFilter::Skip(skip) => {
+            reasons.push(format!("skipping the attributes {}", sorted(skip)))
+        }
+    }
+    if let Some(transform) = &options.maybe_transform {
+        reasons.push(transform.description().to_owned());
+    }
+    reasons
+}
+
+/// Records an import in the import log, creating the log on demand
+///
+/// Logs created before partial imports were recorded gain the `partial` column.
+pub(crate) fn record_import(
+    conn: &Connection,
+    provenance: &ImportProvenance,
+    elements_inserted: usize,
+) -> Result<()> {
+    let table = escape_sql_ident(IMPORT_LOG_TABLE);
+    conn.execute_batch(&format!(
+        r#"CREATE TABLE IF NOT EXISTS {table} (
+            "timestamp" TEXT NOT NULL,
+            "source" TEXT,
+            "project_id" TEXT,
+            "commit_id" TEXT,
+            "elements" INTEGER NOT NULL,
+            "partial" TEXT
+        ) STRICT;"#
+    ))?;
+    if !get_table_columns(conn, IMPORT_LOG_TABLE)?
+        .iter()
+        .any(|(name, _)| name == "partial")
+    {
+        conn.execute_batch(&format!(r#"ALTER TABLE {table} ADD COLUMN "partial" TEXT"#))?;
+    }
+
+    conn.execute(
+        &format!(
+            r#"INSERT INTO {table} ("timestamp", "source", "project_id", "commit_id", "elements", "partial")
+            VALUES (strftime('%Y-%m-%dT%H:%M:%SZ', 'now'), ?1, ?2, ?3, ?4, ?5)"#
+        ),
+        (
+            &provenance.source,
+            &provenance.project_id,
+            &provenance.commit_id,
+            elements_inserted,
+            (!provenance.partial.is_empty()).then(|| provenance.partial.join("; ")),
+        ),
+    )?;
+    Ok(())
+}
+
 /// Insert a relation into the `relations` table
 fn insert_relation(
-    prepared_statement: &mut Statement,
+    relation_inserts: &mut BatchedInsert,
     relation_kind: &str,
     origin_id: &str,
     target_id: &str,
+    context: impl FnOnce() -> String,
 ) -> Result<()> {
-    prepared_statement.execute((relation_kind, origin_id, target_id))
-        .with_warning(|| format!("failed to insert relation ({relation_kind}, {origin_id}, {target_id})"))
-        .note("a cause for this could be an incomplete JSON file, that does not contain all elements of the model")
-        .note("are both element ids present in the imported JSON?")?;
+    relation_inserts.push(
+        [
+            DbValue::Text(relation_kind.to_owned()),
+            DbValue::Text(origin_id.to_owned()),
+            DbValue::Text(target_id.to_owned()),
+        ],
+        context,
+    )
+}
+
+/// Inserts rows by multi-row `INSERT` statements, buffering the rows until a batch is full
+///
+/// One statement per batch instead of per row saves most of the overhead of executing statements.
+/// If a batch fails, its rows are inserted one by one, so that the error is reported in the context
+/// of the row causing it. Batches of a single row are inserted right away.
+struct BatchedInsert<'conn> {
+    conn: &'conn Connection,
+
+    /// The statement up to and including `VALUES`
+    head: String,
+
+    /// The tuple of values of a single row, e.g. `(?, ?)`
+    values_tuple: String,
+
+    /// Number of parameters per row
+    row_len: usize,
+
+    /// Maximum number of rows per statement
+    rows_per_batch: usize,
+
+    /// Statement inserting a full batch
+    batch_stmt: Statement<'conn>,
+
+    /// Notes attached to the errors of inserting a row
+    notes: &'static [&'static str],
+
+    /// Parameters of the buffered rows
+    values: Vec<DbValue>,
+
+    /// Context of each buffered row
+    contexts: Vec<String>,
+}
+
+impl<'conn> BatchedInsert<'conn> {
+    /// Prepares the insertion of rows of `row_len` parameters each, up to `batch_size` at once
+    ///
+    /// The number of rows per statement is further limited by the maximum number of parameters of
+    /// a statement, as configured in SQLite.
+    fn new(
+        conn: &'conn Connection,
+        head: String,
+        values_tuple: String,
+        row_len: usize,
+        batch_size: usize,
+    ) -> Result<Self> {
+        let max_params = usize::try_from(conn.limit(Limit::SQLITE_LIMIT_VARIABLE_NUMBER))?;
+        let rows_per_batch = batch_size.min(max_params / row_len.max(1)).max(1);
+        let statement = batch_statement(&head, &values_tuple, rows_per_batch);
+        debug!(
+            "prepared the following statement for batches of {rows_per_batch} rows:\n{head}{values_tuple}"
+        ); // debug, since statement is actually generated as opposed to being hardcoded.
+        let batch_stmt = conn.prepare(&statement)?;
+        Ok(Self {
+            conn,
+            head,
+            values_tuple,
+            row_len,
+            rows_per_batch,
+            batch_stmt,
+            notes: &[],
+            values: Vec::with_capacity(rows_per_batch * row_len),
+            contexts: Vec::with_capacity(rows_per_batch),
+        })
+    }
+
+    /// Attaches notes to the errors of inserting a row, e.g. hinting at likely causes
+    fn with_notes(self, notes: &'static [&'static str]) -> Self {
+        Self { notes, ..self }
+    }
+
+    /// Adds a row, inserting the batch if it is full
+    ///
+    /// The `context` describes the row in errors.
+    fn push(
+        &mut self,
+        row: impl IntoIterator<Item = DbValue>,
+        context: impl FnOnce() -> String,
+    ) -> Result<()> {
+        self.values.extend(row);
+        debug_assert_eq!(self.values.len(), (self.contexts.len() + 1) * self.row_len);
+
+        if self.rows_per_batch == 1 {
+            let result = self
+                .batch_stmt
+                .execute(params_from_iter(self.values.drain(..)));
+            return self.row_result(result, context);
+        }
+
+        self.contexts.push(context());
+        if self.contexts.len() == self.rows_per_batch {
+            let result = self.batch_stmt.execute(params_from_iter(&self.values));
+            self.locate_error(result)?;
+        }
+        Ok(())
+    }
+
+    /// Inserts the remaining rows, then finalizes the statement
+    fn finish(mut self) -> Result<()> {
+        if !self.contexts.is_empty() {
+            let statement = batch_statement(&self.head, &self.values_tuple, self.contexts.len());
+            let result = self
+                .conn
+                .execute(&statement, params_from_iter(&self.values));
+            self.locate_error(result)?;
+        }
+        self.batch_stmt.finalize()?;
+        Ok(())
+    }
+
+    /// Clears the buffered rows after their batch was executed, on error inserting them one by one
+    /// to find the row causing it
+    fn locate_error(&mut self, result: rusqlite::Result<usize>) -> Result<()> {
+        let Err(batch_error) = result else {
+            self.values.clear();
+            self.contexts.clear();
+            return Ok(());
+        };
+
+        // the failed statement was rolled back, hence the rows before the culprit can be inserted
+        let statement = batch_statement(&self.head, &self.values_tuple, 1);
+        let mut row_stmt = self.conn.prepare(&statement)?;
+        for (row, context) in self.values.chunks(self.row_len).zip(&self.contexts) {
+            let result = row_stmt.execute(params_from_iter(row));
+            self.row_result(result, || context.clone())?;
+        }
+        Err(batch_error)
+            .wrap_err_with(|| format!("inserting a batch of {} rows", self.contexts.len()))
+    }
+
+    /// The result of inserting a single row, in its context
+    fn row_result(
+        &self,
+        result: rusqlite::Result<usize>,
+        context: impl FnOnce() -> String,
+    ) -> Result<()> {
+        let Err(e) = result else {
+            return Ok(());
+        };
+        let mut report = eyre::Report::new(e).wrap_err(context());
+        for note in self.notes {
+            report = report.note(*note);
+        }
+        Err(report)
+    }
+}
+
+/// An `INSERT` statement of `rows` rows
+fn batch_statement(head: &str, values_tuple: &str, rows: usize) -> String {
+    let mut statement = head.to_owned();
+    for row in 0..rows {
+        if row > 0 {
+            statement.push_str(", ");
+        }
+        statement.push_str(values_tuple);
+    }
+    statement
+}
+
+/// Describes each violation of a foreign key constraint in the db, e.g. relations to missing elements
+///
+/// Each violation comes with the id of the element the violating row originates from, if the row is
+/// a relation or an extended property.
+fn foreign_key_violations(conn: &Connection) -> Result<Vec<(Option<String>, eyre::Report)>> {
+    let config = config();
+    let mut stmt =
+        conn.prepare(r#"SELECT "table", "rowid", "parent" FROM pragma_foreign_key_check"#)?;
+    let violations = stmt
+        .query_map((), |row| {
+            Ok((
+                row.get::<_, String>(0)?,
+                row.get::<_, Option<i64>>(1)?,
+                row.get::<_, String>(2)?,
+            ))
+        })?
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let mut relation_stmt = conn.prepare(&format!(
+        r#"SELECT "name", "origin_id", "target_id" FROM {} WHERE rowid = ?1"#,
+        escape_sql_ident(&config.relations_table)
+    ))?;
+    let mut extended_stmt = conn.prepare(&format!(
+        r#"SELECT "@id" FROM {} WHERE rowid = ?1"#,
+        escape_sql_ident(&config.extended_table)
+    ))?;
+    let mut described = Vec::with_capacity(violations.len());
+    for (table, maybe_rowid, parent) in violations {
+        let violation = match maybe_rowid {
+            Some(rowid) if table == config.relations_table && parent == config.elements_table => {
+                let (name, origin_id, target_id): (String, String, String) = relation_stmt
+                    .query_row([rowid], |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)))?;
+                let report =
+                    eyre::eyre!("the relation to {target_id:?} refers to a missing element")
+                        .wrap_err(attribute_context(
+                            &origin_id,
+                            &json_pointer([name.as_str()]),
+                        ));
+                (Some(origin_id), report)
+            }
+            Some(rowid) if table == config.extended_table => {
+                let id: String = extended_stmt.query_row([rowid], |row| row.get(0))?;
+                let report = eyre::eyre!("the extended property refers to a missing element")
+                    .wrap_err(format!("in the element {id:?}"));
+                (Some(id), report)
+            }
+            _ => (
+                None,
+                eyre::eyre!(
+                    "the row {maybe_rowid:?} of the table {table:?} refers to a missing row of the table {parent:?}"
+                ),
+            ),
+        };
+        described.push(violation);
+    }
+    Ok(described)
+}
+
+/// Removes an element along with the relations and extended properties originating from it
+fn remove_element(conn: &Connection, id: &str, project_condition: &str) -> Result<()> {
+    let config = config();
+    for (table, id_col) in [
+        (&config.relations_table, "origin_id"),
+        (&config.extended_table, ELEMENT_PK_COL),
+        (&config.elements_table, ELEMENT_PK_COL),
+    ] {
+        conn.execute(
+            &format!(
+                "DELETE FROM {} WHERE {} = ?1{project_condition}",
+                escape_sql_ident(table),
+                escape_sql_ident(id_col)
+            ),
+            [id],
+        )?;
+    }
+    Ok(())
+}
+
+/// Writes the skipped elements to a JSON file, filling in the JSON of the elements from `elements`
+///
+/// Elements skipped for referring to skipped elements are only known by their id at first.
+fn write_skipped_elements<E: Send + Sync + std::error::Error + 'static>(
+    path: &Path,
+    mut skipped_elements: Vec<SkippedElement>,
+    elements: impl Iterator<Item = Result<Element, E>>,
+) -> Result<()> {
+    let missing_idxs: HashMap<String, usize> = skipped_elements
+        .iter()
+        .enumerate()
+        .filter(|(_, skipped)| skipped.element.is_none())
+        .map(|(idx, skipped)| (skipped.id.clone(), idx))
+        .collect();
+    if !missing_idxs.is_empty() {
+        for maybe_element in elements {
+            let element = maybe_element?;
+            if let Some(&idx) = missing_idxs.get(&element.id) {
+                skipped_elements[idx].element = Some(element);
+            }
+        }
+    }
+
+    if skipped_elements.is_empty() {
+        info!("no elements were skipped");
+    } else {
+        warn!(
+            "skipped {} elements, writing them to {path:?}",
+            skipped_elements.len()
+        );
+    }
+    let f = File::create(path).wrap_err_with(|| format!("creating the file {path:?}"))?;
+    serde_json::to_writer_pretty(BufWriter::new(f), &skipped_elements)?;
     Ok(())
 }
 
+/// Runs `f` within a savepoint, rolling back its changes if it fails
+fn in_savepoint<T>(conn: &Connection, f: impl FnOnce() -> Result<T>) -> Result<T> {
+    conn.execute_batch(r#"SAVEPOINT "import_element""#)?;
+    let result = f();
+    if result.is_err() {
+        conn.execute_batch(r#"ROLLBACK TO "import_element""#)?;
+    }
+    conn.execute_batch(r#"RELEASE "import_element""#)?;
+    result
+}
+
+/// Describes where in the imported JSON an error occurred, for the context of the error
+fn attribute_context(element_id: &str, pointer: &str) -> String {
+    format!("at {pointer:?} in the element {element_id:?}")
+}
+
+/// Builds a JSON pointer (RFC 6901) from its reference tokens, e.g. `/ownedRelationship/2`
+///
+/// For flattened columns, the tokens are the property and the nested properties.
+fn json_pointer<'a>(tokens: impl IntoIterator<Item = &'a str>) -> String {
+    tokens
+        .into_iter()
+        .map(|token| format!("/{}", token.replace('~', "~0").replace('/', "~1")))
+        .collect()
+}
+
+/// Looks up the JSON value for a column of the elements table
+///
+/// Nested objects may be flattened into one column per nested property, named
+/// `<property>.<nested property>`. For these columns, the value is looked up in the nested object.
+pub(crate) fn json_value_for_column<'a>(
+    attributes: &'a Map<String, Value>,
+    column_name: &str,
+) -> Option<&'a Value> {
+    if let Some(value) = attributes.get(column_name) {
+        return Some(value);
+    }
+
+    let (property, nested_property) = column_name.split_once('.')?;
+    match attributes.get(property)? {
+        Value::Object(nested_attributes) => {
+            json_value_for_column(nested_attributes, nested_property)
+        }
+        _ => None,
+    }
+}
+
+/// Checks whether a column of the elements table stores a property, either as a whole or flattened
+pub(crate) fn is_column_of_property(column_name: &str, property: &str) -> bool {
+    column_name
+        .strip_prefix(property)
+        .is_some_and(|rest| rest.is_empty() || rest.starts_with('.'))
+}
+
 /// Checks whether an object is a relation object
 ///
 /// It is assumed, that relation objects are JSON objects with single attribute, which must be named "@id" and of type string.
-fn is_relation_object(json_object: &serde_json::Map<String, Value>) -> bool {
+pub(crate) fn is_relation_object(json_object: &serde_json::Map<String, Value>) -> bool {
     let maybe_id_attribute = json_object.get(ELEMENT_PK_COL);
     matches!(maybe_id_attribute, Some(Value::String(_))) && json_object.len() == 1
 }
@@ -2,12 +2,107 @@ use color_eyre::Section;
 use eyre::Result;
 use rusqlite::Connection;
 
+use crate::{config::config, util::escape_sql_ident};
+
+/// The SQL schema embedded into this tool, see [`crate::regen_embedded_schema`]
+pub(crate) const EMBEDDED_SCHEMA: &str = include_str!("../assets/schema.sql");
+
+/// Names of the core tables in the embedded schema, and of the indices on them
+const EMBEDDED_TABLES: [&str; 3] = ["elements", "relations", "extended_properties"];
+
 /// Initializes a db with the schema and views from `schema.sql`
 pub(crate) fn init_db(conn: &mut Connection) -> Result<()> {
     info!("creating tables");
-    conn.execute_batch(include_str!("../assets/schema.sql"))
+    conn.execute_batch(&embedded_schema())
         .note("are there pre-existing tables/views in the db?")?;
+    crate::upgrade_db::set_schema_version(conn, crate::upgrade_db::SCHEMA_VERSION)?;
+    crate::schema_info::stamp_schema(conn, crate::schema_info::SchemaOrigin::Embedded)?;
     info!("done");
 
     Ok(())
 }
+
+/// The embedded schema, adapted to the table names and polymorphic properties of the config
+///
+/// The core tables and the indices on them are renamed as the schema generation would name them.
+/// The columns of the polymorphic properties are typed `ANY`, those missing are appended to the
+/// elements table.
+pub(crate) fn embedded_schema() -> String {
+    let config = config();
+    let configured_tables = [
+        &config.elements_table,
+        &config.relations_table,
+        &config.extended_table,
+    ];
+
+    // swap the identifiers in a single pass, so that swapped in names are not swapped again
+    let mut schema = String::with_capacity(EMBEDDED_SCHEMA.len());
+    let mut rest = EMBEDDED_SCHEMA;
+    while let Some(start) = rest.find(['"', '\'']) {
+        schema.push_str(&rest[..start]);
+        let delim = rest[start..].chars().next().unwrap();
+        let len = quoted_len(&rest[start..], delim);
+        let quoted = &rest[start..start + len];
+        rest = &rest[start + len..];
+
+        if delim == '\'' {
+            schema.push_str(quoted);
+            continue;
+        }
+        let ident = quoted[1..quoted.len() - 1].replace("\"\"", "\"");
+        let mut swapped = None;
+        for (embedded, configured) in EMBEDDED_TABLES.iter().zip(configured_tables) {
+            if ident == *embedded {
+                swapped = Some(configured.clone());
+            } else if let Some(column) = ident.strip_prefix(&format!("{embedded}.")) {
+                swapped = Some(format!("{configured}.{column}"));
+            }
+        }
+        match swapped {
+            Some(swapped) => schema.push_str(&escape_sql_ident(swapped)),
+            None => schema.push_str(quoted),
+        }
+    }
+    schema.push_str(rest);
+
+    let elements_table = format!(
+        "CREATE TABLE {} (\n",
+        escape_sql_ident(&config.elements_table)
+    );
+    let start = schema.find(&elements_table).unwrap() + elements_table.len();
+    let len = schema[start..].find("\n)").unwrap();
+    let mut columns: Vec<String> = schema[start..start + len]
+        .lines()
+        .map(|line| line.trim_end_matches(',').to_owned())
+        .collect();
+    for prop in &config.polymorphic_props {
+        let name = escape_sql_ident(prop);
+        let column = format!("  {name} ANY");
+        match columns
+            .iter_mut()
+            .find(|c| c.trim_start().starts_with(&format!("{name} ")))
+        {
+            Some(existing) => *existing = column,
+            None => columns.push(column),
+        }
+    }
+    schema.replace_range(start..start + len, &columns.join(",\n"));
+
+    schema
+}
+
+/// Length of the quoted string or identifier at the start of `sql`, including its quotes
+fn quoted_len(sql: &str, delim: char) -> usize {
+    let mut chars = sql.char_indices().skip(1).peekable();
+    while let Some((i, c)) = chars.next() {
+        if c == delim {
+            // a doubled quote is an escaped one
+            if chars.peek().is_some_and(|(_, next)| *next == delim) {
+                chars.next();
+            } else {
+                return i + 1;
+            }
+        }
+    }
+    sql.len()
+}
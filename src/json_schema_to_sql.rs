@@ -23,17 +23,60 @@ use eyre::{Result, bail, ensure};
 use rusqlite::Connection;
 use std::collections::{BTreeMap, BTreeSet};
 
+mod diff;
 mod json_schema;
 mod sql;
 
 use json_schema::*;
 use sql::*;
 
-use crate::config::{ELEMENT_PK_COL, POLYMORPHIC_PROPS};
+pub(crate) use diff::schema_diff;
+pub(crate) use json_schema::Root;
+pub(crate) use sql::create_fts;
+
+use crate::config::{ELEMENT_PK_COL, RAW_JSON_COL, TYPE_COL, config};
+
+/// Options affecting which SQL is generated from the JSON schema
+#[derive(Debug, Clone)]
+pub(crate) struct SchemaOptions {
+    /// Generate triggers rejecting elements which lack a required property
+    pub(crate) not_null_triggers: bool,
+
+    /// Store the variants of string enums in lookup tables referenced via foreign keys, instead of
+    /// inlining them into CHECK constraints
+    pub(crate) enum_lookup_tables: bool,
+
+    /// Generate `STRICT` tables, which require SQLite 3.37 or newer
+    pub(crate) strict_tables: bool,
+
+    /// Generate the elements table as `WITHOUT ROWID` table, which precludes the full-text index
+    pub(crate) without_rowid_elements: bool,
+
+    /// Add a project column to the core tables, so that the db can store multiple projects
+    pub(crate) multi_project: bool,
+
+    /// Store the JSON of each element in the column [`RAW_JSON_COL`], and only these properties in
+    /// columns, generated from the JSON
+    pub(crate) maybe_hot_fields: Option<BTreeSet<String>>,
+}
+
+impl Default for SchemaOptions {
+    fn default() -> Self {
+        Self {
+            not_null_triggers: true,
+            enum_lookup_tables: false,
+            strict_tables: true,
+            without_rowid_elements: false,
+            multi_project: false,
+            maybe_hot_fields: None,
+        }
+    }
+}
 
 pub(crate) fn consume_json_schema(
     schema: &Root,
     maybe_conn: Option<&mut Connection>,
+    options: &SchemaOptions,
 ) -> Result<String> {
     let now = std::time::Instant::now();
 
@@ -45,13 +88,51 @@ pub(crate) fn consume_json_schema(
 
     let mut problematic_cases = BTreeSet::new();
 
+    // maps each column holding a string enum to its legal variants
+    let mut enum_variants: BTreeMap<String, BTreeSet<String>> = BTreeMap::new();
+
+    // maps each element type to the properties which must not be null for it
+    let mut required_by_type: BTreeMap<String, BTreeSet<String>> = BTreeMap::new();
+
+    // maps each property to the definitions defining it
+    let mut definitions_by_property: BTreeMap<String, BTreeSet<String>> = BTreeMap::new();
+
+    // maps each element type to the properties it defines
+    let mut properties_by_type: BTreeMap<String, BTreeSet<String>> = BTreeMap::new();
+
     // iterate through all definitions
     for (def_name, def) in defs {
         trace!("Processing definition {def_name:?}");
         match &def.ty {
             // Case: the definition is an object containing attributes
-            Type::Concrete(ConcreteType::Object { properties, .. }) => {
-                handle_properties(properties.iter(), &mut columns, &mut problematic_cases)?;
+            Type::Concrete(ConcreteType::Object {
+                properties,
+                required,
+                ..
+            }) => {
+                handle_properties(
+                    def_name,
+                    properties.iter(),
+                    &mut columns,
+                    &mut definitions_by_property,
+                    &mut enum_variants,
+                    &mut problematic_cases,
+                    options,
+                )?;
+                collect_required(
+                    def_name,
+                    properties,
+                    required,
+                    &mut required_by_type,
+                    options,
+                );
+                // definitions without a constant type are not elements on their own
+                if let Some(type_name) = type_const(properties) {
+                    properties_by_type
+                        .entry(type_name.to_owned())
+                        .or_default()
+                        .extend(properties.keys().cloned());
+                }
             }
 
             // Case: the definition is a string
@@ -64,9 +145,13 @@ pub(crate) fn consume_json_schema(
                     match ty {
                         Type::Concrete(ConcreteType::Object { properties, .. }) => {
                             handle_properties(
+                                def_name,
                                 properties.iter(),
                                 &mut columns,
+                                &mut definitions_by_property,
+                                &mut enum_variants,
                                 &mut problematic_cases,
+                                options,
                             )?;
                         }
 
@@ -89,9 +174,13 @@ pub(crate) fn consume_json_schema(
                     match ty {
                         Type::Concrete(ConcreteType::Object { properties, .. }) => {
                             handle_properties(
+                                def_name,
                                 properties.iter(),
                                 &mut columns,
+                                &mut definitions_by_property,
+                                &mut enum_variants,
                                 &mut problematic_cases,
+                                options,
                             )?;
                         }
 
@@ -125,7 +214,7 @@ pub(crate) fn consume_json_schema(
     info!("fusing polymorphic SQL representations");
     let mut fused_columns = BTreeMap::new();
     for (name, reprs) in &columns {
-        if POLYMORPHIC_PROPS.contains(&name.as_str()) {
+        if config().is_polymorphic(name) {
             // TODO handle the existence of value both in the relations and the main table
             continue;
         }
@@ -139,7 +228,7 @@ pub(crate) fn consume_json_schema(
         fused_columns.insert(name.to_string(), final_repr?);
     }
 
-    for name in POLYMORPHIC_PROPS {
+    for name in &config().polymorphic_props {
         if let Some(x) = fused_columns.insert(
             name.to_string(),
             SqlRepresentation::Column {
@@ -155,9 +244,116 @@ pub(crate) fn consume_json_schema(
         }
     }
 
+    // the elements table is shared by all element types, hence a column may only be NOT NULL if
+    // every element type requires it; the nullability of the fused JSON types is irrelevant for
+    // this, as an element lacks all properties its type does not define
+    let not_null_columns = required_by_all_types(&required_by_type, &properties_by_type);
+    debug!("columns required by all element types: {not_null_columns:?}");
+    for (name, repr) in &mut fused_columns {
+        if let SqlRepresentation::Column { null, .. } = repr {
+            *null = name != ELEMENT_PK_COL && !not_null_columns.contains(name);
+        }
+    }
+
+    // with the elements stored as raw JSON, only the hot fields are extracted into columns
+    if let Some(hot_fields) = &options.maybe_hot_fields {
+        for hot_field in hot_fields {
+            if !matches!(
+                fused_columns.get(hot_field),
+                Some(SqlRepresentation::Column { .. })
+            ) {
+                warn!(
+                    "the hot field {hot_field:?} is no column, it remains in {RAW_JSON_COL:?} only"
+                );
+            }
+        }
+        fused_columns.retain(|name, repr| {
+            !matches!(repr, SqlRepresentation::Column { .. })
+                || name == ELEMENT_PK_COL
+                || hot_fields.contains(name)
+        });
+    }
+
     debug!("Pathologic cases:\n{problematic_cases:#?}");
 
-    let create_table = sql::to_create_table(&fused_columns)?;
+    let mut create_table = sql::to_create_table(&fused_columns, options)?;
+
+    if options.enum_lookup_tables {
+        // columns with varying types were relaxed to plain TEXT during fusion, these do not
+        // reference a lookup table
+        enum_variants.retain(|column_name, _| {
+            matches!(
+                fused_columns.get(column_name),
+                Some(SqlRepresentation::Column { ty, .. }) if *ty == sql::enum_lookup_column_type(column_name)
+            )
+        });
+
+        info!("generating {} enum lookup tables", enum_variants.len());
+        create_table.push_str(&sql::create_enum_lookup_tables(&enum_variants, options));
+    }
+
+    if options.not_null_triggers {
+        // only properties which ended up as nullable column need to be checked by the triggers
+        for required in required_by_type.values_mut() {
+            required.retain(|prop_name| {
+                prop_name != TYPE_COL
+                    && matches!(
+                        fused_columns.get(prop_name),
+                        Some(SqlRepresentation::Column { null: true, .. })
+                    )
+            });
+        }
+        required_by_type.retain(|_, required| !required.is_empty());
+
+        info!(
+            "generating NOT NULL triggers for {} types",
+            required_by_type.len()
+        );
+        create_table.push_str(&sql::create_not_null_triggers(&required_by_type));
+    }
+
+    info!("generating the provenance of {} properties", columns.len());
+    create_table.push_str(&sql::create_schema_meta(
+        &columns,
+        &fused_columns,
+        &definitions_by_property,
+        options,
+    ));
+
+    info!("generating property domains");
+    create_table.push_str(&sql::create_property_domains(&properties_by_type, options));
+
+    info!("generating convenience views");
+    create_table.push_str(&sql::create_convenience_views(
+        &properties_by_type,
+        &fused_columns,
+        options,
+    ));
+
+    // only properties which ended up as column can be exposed by the views, flattened nested
+    // objects are exposed by the columns of their nested properties
+    for properties in properties_by_type.values_mut() {
+        *properties = properties
+            .iter()
+            .flat_map(|prop_name| {
+                fused_columns
+                    .iter()
+                    .filter(move |(column_name, repr)| {
+                        matches!(repr, SqlRepresentation::Column { .. })
+                            && column_name
+                                .strip_prefix(prop_name.as_str())
+                                .is_some_and(|rest| rest.is_empty() || rest.starts_with('.'))
+                    })
+                    .map(|(column_name, _)| column_name.to_owned())
+            })
+            .collect();
+    }
+    info!(
+        "generating views for {} element types",
+        properties_by_type.len()
+    );
+    create_table.push_str(&sql::create_type_views(&properties_by_type));
+
     debug!("schema conversion took {:?}", now.elapsed());
 
     trace!("The following SQL schema was generated:\n{create_table}");
@@ -165,6 +361,8 @@ pub(crate) fn consume_json_schema(
     if let Some(conn) = maybe_conn {
         info!("running CREATE TABLE statements in db");
         conn.execute_batch(&create_table)?;
+        crate::upgrade_db::set_schema_version(conn, crate::upgrade_db::SCHEMA_VERSION)?;
+        crate::schema_info::stamp_schema(conn, crate::schema_info::SchemaOrigin::Generated)?;
     }
 
     Ok(create_table)
@@ -174,23 +372,61 @@ pub(crate) fn consume_json_schema(
 ///
 /// # Arguments
 ///
+/// - `def_name`: Name of the definition the properties stem from
 /// - `properties`: Iterator over `(property name, property)` tuples
 /// - `columns`: Set of [`SqlRepresentation`]s to represent a given property
+/// - `definitions_by_property`: Names of the definitions defining a given property
+/// - `enum_variants`: Legal variants of each property which is a string enum
 /// - `problems`: Set of properties that have no [`SqlRepresentation`]
+/// - `options`: Options affecting the generated SQL
 fn handle_properties<I: Iterator<Item = (U, T)>, U: AsRef<str>, T: AsRef<Type>>(
+    def_name: &str,
     properties: I,
     columns: &mut BTreeMap<String, BTreeSet<SqlRepresentation>>,
+    definitions_by_property: &mut BTreeMap<String, BTreeSet<String>>,
+    enum_variants: &mut BTreeMap<String, BTreeSet<String>>,
     problems: &mut BTreeSet<Type>,
+    options: &SchemaOptions,
 ) -> Result<()> {
     for (prop_name, prop) in properties {
         let prop_name = prop_name.as_ref();
+
+        // small nested objects are flattened into one column per nested property
+        if let Some(nested_properties) = sql::flattenable_object(prop_name, prop.as_ref(), options)
+        {
+            debug!("flattening the nested object {prop_name:?} into columns");
+            let flattened_properties: Vec<_> = nested_properties
+                .iter()
+                .map(|(nested_name, nested_prop)| {
+                    (format!("{prop_name}.{nested_name}"), nested_prop)
+                })
+                .collect();
+            handle_properties(
+                def_name,
+                flattened_properties.into_iter(),
+                columns,
+                definitions_by_property,
+                enum_variants,
+                problems,
+                options,
+            )?;
+            continue;
+        }
+
         let Ok(new_repr): Result<_, _> =
-            SqlRepresentation::try_from_json_schema_ty(prop_name, prop.as_ref())
+            SqlRepresentation::try_from_json_schema_ty(prop_name, prop.as_ref(), options)
         else {
             problems.insert(prop.as_ref().clone());
             continue;
         };
 
+        if let Some(variants) = sql::enum_variants(prop.as_ref()) {
+            enum_variants
+                .entry(prop_name.to_string())
+                .or_default()
+                .extend(variants.iter().cloned());
+        }
+
         // special check for the primary key id property
         if prop_name == ELEMENT_PK_COL {
             if let SqlRepresentation::Column {
@@ -216,6 +452,81 @@ fn handle_properties<I: Iterator<Item = (U, T)>, U: AsRef<str>, T: AsRef<Type>>(
 
         let curr_reprs = columns.entry(prop_name.to_string()).or_default();
         curr_reprs.insert(new_repr);
+        definitions_by_property
+            .entry(prop_name.to_string())
+            .or_default()
+            .insert(def_name.to_owned());
     }
     Ok(())
 }
+
+/// Determines the element type described by a definition, from the constant value of its `@type`
+/// property
+fn type_const(properties: &BTreeMap<String, Box<Type>>) -> Option<&str> {
+    match properties.get(TYPE_COL).map(AsRef::as_ref) {
+        Some(Type::Concrete(ConcreteType::String {
+            constant: Some(type_name),
+            ..
+        })) => Some(type_name),
+        _ => None,
+    }
+}
+
+/// Determines the properties which are required by every element type
+///
+/// Without any element type, no property is required.
+fn required_by_all_types(
+    required_by_type: &BTreeMap<String, BTreeSet<String>>,
+    properties_by_type: &BTreeMap<String, BTreeSet<String>>,
+) -> BTreeSet<String> {
+    let mut type_names = properties_by_type.keys();
+    let Some(first_type_name) = type_names.next() else {
+        return BTreeSet::new();
+    };
+
+    let mut result = required_by_type
+        .get(first_type_name)
+        .cloned()
+        .unwrap_or_default();
+    for type_name in type_names {
+        let required = required_by_type.get(type_name);
+        result.retain(|prop_name| required.is_some_and(|r| r.contains(prop_name)));
+    }
+    result
+}
+
+/// Collects the required properties of a definition which must not be null
+///
+/// The elements table is shared by all element types, hence the NOT NULL constraints are per type.
+/// The type name is taken from the constant value of the `@type` property if present, otherwise
+/// the definition name is used. The primary key and polymorphic properties are skipped, as are
+/// properties whose JSON type allows null.
+fn collect_required(
+    def_name: &str,
+    properties: &BTreeMap<String, Box<Type>>,
+    required: &[String],
+    required_by_type: &mut BTreeMap<String, BTreeSet<String>>,
+    options: &SchemaOptions,
+) {
+    let type_name = type_const(properties).unwrap_or(def_name).to_owned();
+
+    for prop_name in required {
+        if prop_name == ELEMENT_PK_COL || config().is_polymorphic(prop_name) {
+            continue;
+        }
+
+        let Some(prop) = properties.get(prop_name) else {
+            warn!("definition {def_name:?} requires the undefined property {prop_name:?}");
+            continue;
+        };
+
+        if let Ok(SqlRepresentation::Column { null: false, .. }) =
+            SqlRepresentation::try_from_json_schema_ty(prop_name, prop, options)
+        {
+            required_by_type
+                .entry(type_name.clone())
+                .or_default()
+                .insert(prop_name.to_owned());
+        }
+    }
+}
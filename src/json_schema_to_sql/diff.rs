@@ -0,0 +1,169 @@
+//! Comparison of two JSON schemata, and of the SQL schemata derived from them
+
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    io::Write,
+};
+
+use eyre::Result;
+use rusqlite::Connection;
+
+use super::{
+    CompositeType, ConcreteType, Definition, Root, SchemaOptions, Type, consume_json_schema,
+};
+use crate::{config::config, migrate_schema::split_table_definition};
+
+/// Prints the differences between two JSON schemata
+///
+/// First the added (`+`), removed (`-`) and changed (`~`) definitions are listed, including the
+/// differences in their properties. Then the differences of the columns in the SQL schemata
+/// generated from both JSON schemata are listed.
+pub(crate) fn schema_diff<W: Write>(
+    old: &Root,
+    new: &Root,
+    options: &SchemaOptions,
+    out: &mut W,
+) -> Result<()> {
+    writeln!(out, "# definitions")?;
+    for (def_name, old_def, new_def) in outer_join(&old.defs, &new.defs) {
+        match (old_def, new_def) {
+            (Some(_), None) => writeln!(out, "- {def_name}")?,
+            (None, Some(_)) => writeln!(out, "+ {def_name}")?,
+            (Some(old_def), Some(new_def)) if old_def != new_def => {
+                writeln!(out, "~ {def_name}")?;
+                write_property_diff(old_def, new_def, out)?;
+            }
+            _ => {}
+        }
+    }
+
+    writeln!(out, "\n# columns")?;
+    let old_tables = generated_tables(old, options)?;
+    let new_tables = generated_tables(new, options)?;
+    for (table, old_sql, new_sql) in outer_join(&old_tables, &new_tables) {
+        let (old_columns, old_constraints) = match old_sql {
+            Some(sql) => split_table_definition(sql)?,
+            None => Default::default(),
+        };
+        let (new_columns, new_constraints) = match new_sql {
+            Some(sql) => split_table_definition(sql)?,
+            None => Default::default(),
+        };
+
+        for (column, old_column, new_column) in outer_join(&old_columns, &new_columns) {
+            match (old_column, new_column) {
+                (Some(def), None) => writeln!(out, "- {table}: {def}")?,
+                (None, Some(def)) => writeln!(out, "+ {table}: {def}")?,
+                (Some(old_def), Some(new_def)) if old_def != new_def => {
+                    writeln!(out, "~ {table}.{column}:\n    - {old_def}\n    + {new_def}")?
+                }
+                _ => {}
+            }
+        }
+
+        for constraint in old_constraints.difference(&new_constraints) {
+            writeln!(out, "- {table}: {constraint}")?;
+        }
+        for constraint in new_constraints.difference(&old_constraints) {
+            writeln!(out, "+ {table}: {constraint}")?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Prints the differences of the properties of two versions of a definition
+fn write_property_diff<W: Write>(
+    old_def: &Definition,
+    new_def: &Definition,
+    out: &mut W,
+) -> Result<()> {
+    let (old_properties, old_required) = definition_properties(&old_def.ty);
+    let (new_properties, new_required) = definition_properties(&new_def.ty);
+
+    for (prop_name, old_prop, new_prop) in outer_join(&old_properties, &new_properties) {
+        match (old_prop, new_prop) {
+            (Some(_), None) => writeln!(out, "    - {prop_name}")?,
+            (None, Some(prop)) => {
+                writeln!(out, "    + {prop_name}: {}", serde_json::to_string(prop)?)?
+            }
+            (Some(old_prop), Some(new_prop)) if old_prop != new_prop => writeln!(
+                out,
+                "    ~ {prop_name}: {} -> {}",
+                serde_json::to_string(old_prop)?,
+                serde_json::to_string(new_prop)?
+            )?,
+            _ => {}
+        }
+    }
+
+    for prop_name in new_required.difference(&old_required) {
+        writeln!(out, "    ~ {prop_name}: now required")?;
+    }
+    for prop_name in old_required.difference(&new_required) {
+        writeln!(out, "    ~ {prop_name}: no longer required")?;
+    }
+
+    Ok(())
+}
+
+/// Collects the properties and required properties of a definition
+///
+/// For composite definitions, the properties of all contained objects are merged.
+fn definition_properties(ty: &Type) -> (BTreeMap<&str, &Type>, BTreeSet<&str>) {
+    let objects = match ty {
+        Type::Composite(CompositeType::AnyOf { any_of: types })
+        | Type::Composite(CompositeType::OneOf { one_of: types }) => types.iter().collect(),
+        ty => vec![ty],
+    };
+
+    let mut properties = BTreeMap::new();
+    let mut required = BTreeSet::new();
+    for object in objects {
+        if let Type::Concrete(ConcreteType::Object {
+            properties: props,
+            required: req,
+            ..
+        }) = object
+        {
+            properties.extend(props.iter().map(|(n, p)| (n.as_str(), p.as_ref())));
+            required.extend(req.iter().map(String::as_str));
+        }
+    }
+
+    (properties, required)
+}
+
+/// Generates the SQL schema from a JSON schema, returning the definitions of the core tables
+fn generated_tables(schema: &Root, options: &SchemaOptions) -> Result<BTreeMap<String, String>> {
+    let sql = consume_json_schema(schema, None, options)?;
+
+    let conn = Connection::open_in_memory()?;
+    conn.execute_batch(&sql)?;
+
+    let config = config();
+    let mut tables = BTreeMap::new();
+    for table in [
+        &config.elements_table,
+        &config.relations_table,
+        &config.extended_table,
+    ] {
+        let sql: String = conn.query_row(
+            "SELECT sql FROM sqlite_schema WHERE type = 'table' AND name = ?1",
+            [table],
+            |row| row.get(0),
+        )?;
+        tables.insert(table.to_owned(), sql);
+    }
+
+    Ok(tables)
+}
+
+/// Joins two maps by their keys, yielding the values of both maps for each key
+fn outer_join<'a, K: Ord, V>(
+    left: &'a BTreeMap<K, V>,
+    right: &'a BTreeMap<K, V>,
+) -> impl Iterator<Item = (&'a K, Option<&'a V>, Option<&'a V>)> {
+    let keys: BTreeSet<_> = left.keys().chain(right.keys()).collect();
+    keys.into_iter().map(|k| (k, left.get(k), right.get(k)))
+}
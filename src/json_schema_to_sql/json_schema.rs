@@ -51,19 +51,80 @@ pub(super) enum ConcreteType {
     },
     String {
         // // TODO this field is not always present, how to distinguish Null and string?
-        #[serde(default, rename = "enum")]
+        #[serde(default, rename = "enum", skip_serializing_if = "Option::is_none")]
         enumeration: Option<Vec<String>>,
 
-        #[serde(default)]
+        #[serde(default, skip_serializing_if = "Option::is_none")]
         format: Option<String>,
 
-        #[serde(default, rename = "const")]
+        #[serde(default, rename = "const", skip_serializing_if = "Option::is_none")]
         constant: Option<String>,
     },
     Null,
     Boolean,
-    Number,
-    Integer,
+    Number {
+        #[serde(flatten)]
+        constraints: NumericConstraints,
+    },
+    Integer {
+        #[serde(flatten)]
+        constraints: NumericConstraints,
+    },
+}
+
+/// Constraints on the value of a number or an integer
+#[derive(Clone, Debug, Default, Deserialize, Serialize, PartialOrd, Ord, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub(super) struct NumericConstraints {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(super) minimum: Option<NumberLiteral>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(super) maximum: Option<NumberLiteral>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(super) exclusive_minimum: Option<NumberLiteral>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(super) exclusive_maximum: Option<NumberLiteral>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(super) multiple_of: Option<NumberLiteral>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(super) format: Option<String>,
+}
+
+/// A number from the JSON schema, kept verbatim so that it can be emitted as SQL literal
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq, Eq)]
+#[serde(transparent)]
+pub(super) struct NumberLiteral(pub(super) serde_json::Number);
+
+impl NumberLiteral {
+    /// Whether the number is an integer
+    pub(super) fn is_integer(&self) -> bool {
+        !self.0.is_f64()
+    }
+}
+
+impl std::fmt::Display for NumberLiteral {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
+// the order is only needed to keep types in ordered collections, hence the textual representation
+// suffices
+impl std::cmp::Ord for NumberLiteral {
+    fn cmp(&self, other: &Self) -> std::cmp::Ordering {
+        self.0.to_string().cmp(&other.0.to_string())
+    }
+}
+
+impl std::cmp::PartialOrd for NumberLiteral {
+    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+        Some(self.cmp(other))
+    }
 }
 
 #[derive(Clone, Debug, Deserialize, Serialize, PartialOrd, Ord, PartialEq, Eq)]
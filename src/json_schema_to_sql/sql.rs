@@ -1,13 +1,17 @@
-use std::collections::BTreeMap;
+use std::collections::{BTreeMap, BTreeSet};
 
 use eyre::{Result, bail, ensure};
 
 use crate::{
-    config::{ELEMENT_PK_COL, ELEMENTS_TABLE, EXTENDED_TABLE, POLYMORPHIC_PROPS, RELATIONS_TABLE},
+    config::{
+        ELEMENT_PK_COL, ENUM_TABLE_PREFIX, FTS_COLUMNS, FTS_TABLE, OWNED_ELEMENT_RELATION,
+        PROJECT_COL, PROPERTY_DOMAINS_TABLE, RAW_JSON_COL, RELATION_NAMES_TABLE, SCHEMA_META_TABLE,
+        TARGET_PROJECT_COL, TYPE_COL, config,
+    },
     util::{escape_sql_ident, escape_sql_str_lit},
 };
 
-use super::{CompositeType, ConcreteType, Type};
+use super::{CompositeType, ConcreteType, NumericConstraints, SchemaOptions, Type};
 
 /// Enum that describes how something from the JSON-Schema will be represented in our SQL schema
 #[derive(Clone, Debug, Hash, PartialEq, Eq, PartialOrd, Ord)]
@@ -71,18 +75,20 @@ impl SqlRepresentation {
                 },
             ) => {
                 ensure!(
-                    self_fkc == other_fkc || POLYMORPHIC_PROPS.contains(&column_name),
+                    self_fkc == other_fkc || config().is_polymorphic(column_name),
                     "Fusing two SqlRepresentations with differing id_foreign_key_constraint values for column {column_name:?}: {s:?}, {o:?}, prop = {column_name:?}"
                 );
 
-                // allow relaxation of varying TEXT types
-                if !(self_ty.starts_with("TEXT") && other_ty.starts_with("TEXT")) {
-                    ensure!(
-                        self_ty == other_ty,
-                        "Fusing two SqlRepresentations with differing type: {self_ty} vs. {other_ty}"
-                    );
-                } else if self_ty.starts_with("TEXT") && other_ty.starts_with("TEXT") {
-                    *self_ty = "TEXT".to_string();
+                // allow relaxation of varying constraints on the same type, e.g. of TEXT types
+                // with differing CHECKs
+                let base_type =
+                    |ty: &str| ty.split_whitespace().next().unwrap_or_default().to_owned();
+                ensure!(
+                    base_type(self_ty) == base_type(other_ty),
+                    "Fusing two SqlRepresentations with differing type: {self_ty} vs. {other_ty}"
+                );
+                if self_ty != other_ty {
+                    *self_ty = base_type(self_ty);
                 }
 
                 ensure!(self_uniq == other_uniq, "Uniqueness must not differ");
@@ -145,11 +151,15 @@ impl SqlRepresentation {
 ///
 /// # Remaining issues
 ///
-pub(super) fn to_create_table(columns: &BTreeMap<String, SqlRepresentation>) -> Result<String> {
-    let create_table = |table_name, inner| {
+pub(super) fn to_create_table(
+    columns: &BTreeMap<String, SqlRepresentation>,
+    options: &SchemaOptions,
+) -> Result<String> {
+    let create_table = |table_name, inner, without_rowid| {
         format!(
-            "CREATE TABLE {} (\n{inner}\n) STRICT;\n",
-            escape_sql_ident(table_name)
+            "CREATE TABLE {} (\n{inner}\n){};\n",
+            escape_sql_ident(table_name),
+            table_options(options, without_rowid)
         )
     };
 
@@ -169,30 +179,65 @@ pub(super) fn to_create_table(columns: &BTreeMap<String, SqlRepresentation>) ->
                 column_def.push(escape_sql_ident(name));
 
                 // type-name
-                column_def.push(ty.to_owned());
+                if options.strict_tables {
+                    column_def.push(ty.to_owned());
+                } else if ty != "ANY" {
+                    // without STRICT, values which can not be converted to the affinity of the type
+                    // would be stored as they are
+                    column_def.push(ty.to_owned());
+                    column_def.push(storage_class_check(name, ty));
+                }
+                // outside of STRICT tables, ANY would imply NUMERIC affinity, while a column
+                // without type stores all values as they are
+
+                // with the elements stored as raw JSON, the columns are extracted from it
+                if options.maybe_hot_fields.is_some() && name != ELEMENT_PK_COL {
+                    column_def.push(format!(
+                        "GENERATED ALWAYS AS (json_extract({}, {})) VIRTUAL",
+                        escape_sql_ident(RAW_JSON_COL),
+                        escape_sql_str_lit(json_path(name))
+                    ));
+                }
 
                 // column-constraint
-                if name == ELEMENT_PK_COL {
+                if name == ELEMENT_PK_COL && options.multi_project {
+                    // the primary key spans the project and the element id, see below
+                    column_def.push("NOT NULL".to_string());
+                } else if name == ELEMENT_PK_COL {
                     column_def.push("PRIMARY KEY".to_string());
+
+                    // only STRICT tables reject NULL in a primary key column by themselves
+                    if !options.strict_tables {
+                        column_def.push("NOT NULL".to_string());
+                    }
                 }
 
-                if !null {
-                    // TODO figure out NOT NULL stuff via trigger
-                    //     column_def.push("NOT NULL".to_string());
+                // only columns required by all element types are NOT NULL, as the elements table is
+                // shared by all types; `create_not_null_triggers` enforces the others per type
+                if !null && name != ELEMENT_PK_COL {
+                    column_def.push("NOT NULL".to_string());
                 }
 
-                if *unique {
+                // ids only are unique within a project
+                if *unique && !options.multi_project {
                     column_def.push("UNIQUE".to_string());
                 }
 
                 // foreign-key-clause
-                if *id_foreign_key_constraint {
+                if *id_foreign_key_constraint && !options.multi_project {
                     column_def.push("REFERENCES".to_string());
-                    column_def.push(escape_sql_ident(ELEMENTS_TABLE));
+                    column_def.push(escape_sql_ident(&config().elements_table));
                     column_def.push(format!("({})", escape_sql_ident(ELEMENT_PK_COL)));
                 }
 
                 column_defs.push(column_def.join(" "));
+
+                if options.maybe_hot_fields.is_some() && name == ELEMENT_PK_COL {
+                    let raw_json_column_escaped = escape_sql_ident(RAW_JSON_COL);
+                    column_defs.push(format!(
+                        "{raw_json_column_escaped} TEXT NOT NULL CHECK(json_valid({raw_json_column_escaped}))"
+                    ));
+                }
             }
 
             // ignore representations about other tables
@@ -200,13 +245,23 @@ pub(super) fn to_create_table(columns: &BTreeMap<String, SqlRepresentation>) ->
         }
     }
 
+    let project_column_escaped = escape_sql_ident(PROJECT_COL);
+    if options.multi_project {
+        column_defs.push(format!("{project_column_escaped} TEXT NOT NULL"));
+        column_defs.push(format!(
+            "PRIMARY KEY({project_column_escaped}, {})",
+            escape_sql_ident(ELEMENT_PK_COL)
+        ));
+    }
+
     let mut stmt = create_table(
-        ELEMENTS_TABLE,
+        &config().elements_table,
         column_defs
             .iter()
             .map(|cd| format!("\t{cd}"))
             .collect::<Vec<_>>()
             .join(",\n"),
+        options.without_rowid_elements,
     );
     column_defs.clear();
     stmt += "\n\n";
@@ -215,31 +270,57 @@ pub(super) fn to_create_table(columns: &BTreeMap<String, SqlRepresentation>) ->
     // this concludes the elements table, now the relations table
     //
 
-    let main_table_escaped = escape_sql_ident(ELEMENTS_TABLE);
+    let main_table_escaped = escape_sql_ident(&config().elements_table);
     let pk_column_escaped = escape_sql_ident(ELEMENT_PK_COL);
-    let allowed_relation_names = columns
+    let relation_names_table_escaped = escape_sql_ident(RELATION_NAMES_TABLE);
+    let known_relation_names = columns
         .iter()
         .filter_map(|(n, c)| match c {
             SqlRepresentation::RelationsTable => Some(n.to_owned()),
             _ => None,
         })
-        .chain(POLYMORPHIC_PROPS.into_iter().map(str::to_string))
-        .chain(std::iter::once("analysisAction".to_owned())) // TODO remove hot-fix
-        .map(escape_sql_str_lit)
+        .chain(config().polymorphic_props.iter().cloned())
+        .map(|n| format!("\t({})", escape_sql_str_lit(n)))
         .collect::<Vec<_>>()
-        .join(",\n\t\t");
+        .join(",\n");
+
+    // the importer registers relation names not known from the JSON schema on first sight
+    stmt.push_str(&format!(
+        "CREATE TABLE {relation_names_table_escaped} (\n\t\"name\" TEXT PRIMARY KEY\n){};\n\
+        INSERT INTO {relation_names_table_escaped} (\"name\") VALUES\n{known_relation_names};\n\n",
+        table_options(options, true)
+    ));
 
     // TODO rename 'name' to 'property'
-    stmt.push_str(&create_table(
-        RELATIONS_TABLE,
+    let relations_table_inner = if options.multi_project {
+        // the target of a relation may live in another project, e.g. a library project
+        let target_project_column_escaped = escape_sql_ident(TARGET_PROJECT_COL);
+        format!(
+            r#"    {project_column_escaped} TEXT NOT NULL,
+	"name" TEXT NOT NULL,
+	"origin_id" TEXT NOT NULL,
+	"target_id" TEXT NOT NULL,
+	{target_project_column_escaped} TEXT NOT NULL,
+	FOREIGN KEY("name") REFERENCES {relation_names_table_escaped}("name"),
+	FOREIGN KEY({project_column_escaped},"origin_id") REFERENCES {main_table_escaped}({project_column_escaped},{pk_column_escaped}) DEFERRABLE INITIALLY DEFERRED,
+	FOREIGN KEY({target_project_column_escaped},"target_id") REFERENCES {main_table_escaped}({project_column_escaped},{pk_column_escaped}) DEFERRABLE INITIALLY DEFERRED,
+	PRIMARY KEY({project_column_escaped},"name","origin_id","target_id")"#
+        )
+    } else {
         format!(
-            r#"    "name" TEXT NOT NULL CHECK("name" IN ({allowed_relation_names})),
+            r#"    "name" TEXT NOT NULL,
 	"origin_id" TEXT NOT NULL,
 	"target_id" TEXT NOT NULL,
+	FOREIGN KEY("name") REFERENCES {relation_names_table_escaped}("name"),
 	FOREIGN KEY("origin_id") REFERENCES {main_table_escaped}({pk_column_escaped}) DEFERRABLE INITIALLY DEFERRED,
 	FOREIGN KEY("target_id") REFERENCES {main_table_escaped}({pk_column_escaped}) DEFERRABLE INITIALLY DEFERRED,
 	PRIMARY KEY("name","origin_id","target_id")"#
-        ),
+        )
+    };
+    stmt.push_str(&create_table(
+        &config().relations_table,
+        relations_table_inner,
+        false,
     ));
     stmt += "\n\n";
 
@@ -269,32 +350,81 @@ pub(super) fn to_create_table(columns: &BTreeMap<String, SqlRepresentation>) ->
             SqlRepresentation::Column { .. } | SqlRepresentation::RelationsTable => {}
         }
     }
-    column_defs.push(format!(
-        "FOREIGN KEY({pk_column_escaped}) REFERENCES {main_table_escaped}({pk_column_escaped}) DEFERRABLE INITIALLY DEFERRED"
-    ));
+    if options.multi_project {
+        column_defs.push(format!("{project_column_escaped} TEXT NOT NULL"));
+        column_defs.push(format!(
+            "FOREIGN KEY({project_column_escaped},{pk_column_escaped}) REFERENCES {main_table_escaped}({project_column_escaped},{pk_column_escaped}) DEFERRABLE INITIALLY DEFERRED"
+        ));
+    } else {
+        column_defs.push(format!(
+            "FOREIGN KEY({pk_column_escaped}) REFERENCES {main_table_escaped}({pk_column_escaped}) DEFERRABLE INITIALLY DEFERRED"
+        ));
+    }
 
     stmt.push_str(&create_table(
-        EXTENDED_TABLE,
+        &config().extended_table,
         column_defs
             .iter()
             .map(|cd| format!("\t{cd}"))
             .collect::<Vec<_>>()
             .join(",\n"),
+        false,
     ));
     column_defs.clear();
 
     stmt += "\n\n";
 
-    // and finally, add indexes for quicker lookups
-    stmt.push_str(&create_index());
+    // add indexes for quicker lookups
+    stmt.push_str(&create_index(columns, options));
+
+    // and finally, add the full-text search index, which refers to the elements by their rowid
+    let is_column =
+        |name: &str| matches!(columns.get(name), Some(SqlRepresentation::Column { .. }));
+    if options.without_rowid_elements {
+        warn!("omitting the full-text index, as the elements table has no rowid");
+    } else if !FTS_COLUMNS.iter().all(|name| is_column(name)) {
+        warn!("omitting the full-text index, as the hot fields lack some of {FTS_COLUMNS:?}");
+    } else {
+        stmt.push_str(&create_fts());
+    }
 
     Ok(stmt)
 }
 
 impl SqlRepresentation {
+    /// Describes the representation in a short, human readable form
+    fn describe(&self) -> String {
+        match self {
+            SqlRepresentation::Column {
+                unique,
+                null,
+                id_foreign_key_constraint,
+                ty,
+            } => {
+                let mut description = format!("column {ty}");
+                if !null {
+                    description.push_str(" NOT NULL");
+                }
+                if *unique {
+                    description.push_str(" UNIQUE");
+                }
+                if *id_foreign_key_constraint {
+                    description.push_str(" REFERENCES elements");
+                }
+                description
+            }
+            SqlRepresentation::RelationsTable => "relations table".to_owned(),
+            SqlRepresentation::ExtendedPropertiesTable => "extended properties table".to_owned(),
+        }
+    }
+
     /// Tries to convert a [`Type`] into a [`SqlRepresentation`]
     // TODO maybe emit SQL Check constraints as side-effect of transformation
-    pub(super) fn try_from_json_schema_ty(prop_name: &str, prop: &Type) -> Result<Self> {
+    pub(super) fn try_from_json_schema_ty(
+        prop_name: &str,
+        prop: &Type,
+        options: &SchemaOptions,
+    ) -> Result<Self> {
         let null = ConcreteType::Null;
 
         let sql_repr = match prop {
@@ -316,9 +446,7 @@ impl SqlRepresentation {
             }
 
             // string which must be unique and adhere to a specific format
-            // TODO set column type to string
-            // TODO trigger to check values matches the UUID format
-            Type::Concrete(ConcreteType::String {
+            ty @ Type::Concrete(ConcreteType::String {
                 enumeration: None,
                 format: Some(format),
                 constant: None,
@@ -326,7 +454,7 @@ impl SqlRepresentation {
                 null: false,
                 id_foreign_key_constraint: false,
                 unique: true,
-                ty: "TEXT".to_string(),
+                ty: json_schema_type_to_sql_type(ty, prop_name, options)?,
             },
 
             // a string
@@ -335,7 +463,17 @@ impl SqlRepresentation {
                 null: false,
                 id_foreign_key_constraint: false,
                 unique: false,
-                ty: json_schema_type_to_sql_type(ty, prop_name)?,
+                ty: json_schema_type_to_sql_type(ty, prop_name, options)?,
+            },
+
+            // a number, an integer or a boolean
+            ty @ Type::Concrete(
+                ConcreteType::Integer { .. } | ConcreteType::Number { .. } | ConcreteType::Boolean,
+            ) => SqlRepresentation::Column {
+                null: false,
+                id_foreign_key_constraint: false,
+                unique: false,
+                ty: json_schema_type_to_sql_type(ty, prop_name, options)?,
             },
 
             // reference to exactly one other element
@@ -380,10 +518,18 @@ impl SqlRepresentation {
                     null: true,
                     id_foreign_key_constraint: false,
                     unique: false,
-                    ty: json_schema_type_to_sql_type(other_json_type, prop_name)?,
+                    ty: json_schema_type_to_sql_type(other_json_type, prop_name, options)?,
                 }
             }
 
+            // an object which is not a reference, stored as JSON text
+            ty @ Type::Concrete(ConcreteType::Object { .. }) => SqlRepresentation::Column {
+                null: false,
+                id_foreign_key_constraint: false,
+                unique: false,
+                ty: json_schema_type_to_sql_type(ty, prop_name, options)?,
+            },
+
             x => bail!("Unsure how to represent {x:#?}"),
         };
 
@@ -395,15 +541,36 @@ impl SqlRepresentation {
 // Helper functions
 //
 
+/// GLOB pattern matching the textual representation of a UUID
+///
+/// The pattern checks for the 8-4-4-4-12 structure of hexadecimal digits (in either case). Unlike
+/// LIKE, GLOB is case sensitive and supports character classes, so nothing but hex digits and
+/// the four hyphens is accepted.
+fn uuid_glob_pattern() -> String {
+    const HEX_DIGIT: &str = "[0-9a-fA-F]";
+
+    [8, 4, 4, 4, 12].map(|len| HEX_DIGIT.repeat(len)).join("-")
+}
+
 /// Convert a JSON-Schema type to a SQLite type, assuming the JSON-Schema type to be a
 /// [`Type::Concrete`]
 ///
 /// See <https://www.sqlite.org/datatype3.html> for more information.
 // TODO add emitation of check/constraints?
-fn json_schema_type_to_sql_type(json_ty: &Type, column_name: &str) -> Result<String> {
+fn json_schema_type_to_sql_type(
+    json_ty: &Type,
+    column_name: &str,
+    options: &SchemaOptions,
+) -> Result<String> {
     let column_name_escaped = escape_sql_ident(column_name);
 
     let ty = match json_ty {
+        Type::Concrete(ConcreteType::String {
+            enumeration: Some(_),
+            format: None,
+            constant: None,
+        }) if options.enum_lookup_tables => enum_lookup_column_type(column_name),
+
         Type::Concrete(ConcreteType::String {
             enumeration: Some(variants),
             format: None,
@@ -425,10 +592,8 @@ fn json_schema_type_to_sql_type(json_ty: &Type, column_name: &str) -> Result<Str
             // see <https://json-schema.org/understanding-json-schema/reference/string>
             // and <https://datatracker.ietf.org/doc/html/rfc4122>
             "uuid" => {
-                // TODO this is weak
-                let uuid_like_pattern = "________-____-____-____-____________";
-                let uuid_like_pattern_escaped = escape_sql_str_lit(uuid_like_pattern);
-                format!("TEXT CHECK({column_name_escaped} LIKE ({uuid_like_pattern_escaped}))")
+                let uuid_glob_pattern_escaped = escape_sql_str_lit(uuid_glob_pattern());
+                format!("TEXT CHECK({column_name_escaped} GLOB ({uuid_glob_pattern_escaped}))")
             }
             _ => {
                 bail!("There is no SQLite type for format {format:?} defined");
@@ -446,16 +611,166 @@ fn json_schema_type_to_sql_type(json_ty: &Type, column_name: &str) -> Result<Str
         }
 
         Type::Concrete(ConcreteType::String { .. }) => "TEXT".to_string(),
-        Type::Concrete(ConcreteType::Integer) | Type::Concrete(ConcreteType::Boolean) => {
-            "INTEGER".to_string()
+        Type::Concrete(ConcreteType::Integer { constraints }) => {
+            with_numeric_checks("INTEGER", column_name, constraints, true)?
+        }
+        Type::Concrete(ConcreteType::Boolean) => "INTEGER".to_string(),
+        Type::Concrete(ConcreteType::Number { constraints }) => {
+            with_numeric_checks("REAL", column_name, constraints, false)?
+        }
+        Type::Concrete(ConcreteType::Object { .. }) => {
+            format!("TEXT CHECK(json_valid({column_name_escaped}))")
         }
-        Type::Concrete(ConcreteType::Number) => "REAL".to_string(),
         _ => bail!("There is no suitable SQLite counterpart type for {json_ty:#?} defined"),
     };
 
     Ok(ty)
 }
 
+/// JSON path to the property of an element a column stems from, e.g. `$."declaredName"`
+///
+/// Flattened nested objects are stored in columns named `<property>.<nested property>`, see
+/// [`flattenable_object`].
+fn json_path(column_name: &str) -> String {
+    let mut path = String::from("$");
+    for token in column_name.split('.') {
+        path.push_str(&format!(".\"{token}\""));
+    }
+    path
+}
+
+/// Table options to append to a `CREATE TABLE` statement
+///
+/// `STRICT` is omitted if strict tables are disabled in the `options`.
+fn table_options(options: &SchemaOptions, without_rowid: bool) -> &'static str {
+    match (options.strict_tables, without_rowid) {
+        (true, true) => " STRICT, WITHOUT ROWID",
+        (true, false) => " STRICT",
+        (false, true) => " WITHOUT ROWID",
+        (false, false) => "",
+    }
+}
+
+/// CHECK constraint restricting a column to the storage class of its type, or NULL
+///
+/// This emulates the type enforcement of `STRICT` tables. The constraint is evaluated after the
+/// type affinity was applied, hence e.g. the integer `1` is accepted by a `REAL` column, while the
+/// text `'abc'` is refused by an `INTEGER` column.
+fn storage_class_check(column_name: &str, ty: &str) -> String {
+    let storage_class = ty
+        .split_whitespace()
+        .next()
+        .unwrap_or_default()
+        .to_lowercase();
+    format!(
+        "CHECK(typeof({}) IN ({}, 'null'))",
+        escape_sql_ident(column_name),
+        escape_sql_str_lit(storage_class)
+    )
+}
+
+/// Maximum number of properties of a nested object, for it to be flattened into columns
+const MAX_FLATTENED_PROPERTIES: usize = 4;
+
+/// Returns the properties of a nested object, if it is small enough to be flattened
+///
+/// Objects are flattened into one column per property, if they have a fixed set of at most
+/// [`MAX_FLATTENED_PROPERTIES`] properties, each of which is represented as column on its own. This
+/// covers both mandatory and nullable objects. All other objects are stored as JSON text.
+pub(super) fn flattenable_object<'a>(
+    prop_name: &str,
+    prop: &'a Type,
+    options: &SchemaOptions,
+) -> Option<&'a BTreeMap<String, Box<Type>>> {
+    match prop {
+        Type::Concrete(ConcreteType::Object {
+            properties,
+            additional_properties: false,
+            ..
+        }) if !properties.is_empty()
+            && properties.len() <= MAX_FLATTENED_PROPERTIES
+            && properties.iter().all(|(nested_name, nested_prop)| {
+                matches!(
+                    SqlRepresentation::try_from_json_schema_ty(
+                        &format!("{prop_name}.{nested_name}"),
+                        nested_prop,
+                        options
+                    ),
+                    Ok(SqlRepresentation::Column { .. })
+                )
+            }) =>
+        {
+            Some(properties)
+        }
+        Type::Composite(CompositeType::OneOf { one_of })
+            if one_of.len() == 2 && one_of.contains(&ConcreteType::Null.into()) =>
+        {
+            one_of
+                .iter()
+                .find_map(|ty| flattenable_object(prop_name, ty, options))
+        }
+        _ => None,
+    }
+}
+
+/// Appends CHECK constraints enforcing the numeric constraints from the JSON schema to a type
+///
+/// The integer formats `int8` to `int64` and `uint8` to `uint32` are checked for their range.
+/// For integers which are multiples of an integer, `multipleOf` is checked exactly, otherwise with a
+/// small tolerance for rounding errors.
+fn with_numeric_checks(
+    ty: &str,
+    column_name: &str,
+    constraints: &NumericConstraints,
+    integer: bool,
+) -> Result<String> {
+    let column_name_escaped = escape_sql_ident(column_name);
+
+    let mut checks = Vec::new();
+    if let Some(format) = &constraints.format {
+        let range = match format.as_str() {
+            "int8" => Some((i64::from(i8::MIN), i64::from(i8::MAX))),
+            "int16" => Some((i64::from(i16::MIN), i64::from(i16::MAX))),
+            "int32" => Some((i64::from(i32::MIN), i64::from(i32::MAX))),
+            "uint8" => Some((0, i64::from(u8::MAX))),
+            "uint16" => Some((0, i64::from(u16::MAX))),
+            "uint32" => Some((0, i64::from(u32::MAX))),
+            // SQLite stores integers with up to 64 bit, floating point numbers as double
+            "int64" | "float" | "double" => None,
+            _ => bail!("There is no SQLite check for the numeric format {format:?} defined"),
+        };
+        if let Some((min, max)) = range {
+            checks.push(format!("{column_name_escaped} BETWEEN {min} AND {max}"));
+        }
+    }
+
+    for (bound, operator) in [
+        (&constraints.minimum, ">="),
+        (&constraints.maximum, "<="),
+        (&constraints.exclusive_minimum, ">"),
+        (&constraints.exclusive_maximum, "<"),
+    ] {
+        if let Some(bound) = bound {
+            checks.push(format!("{column_name_escaped} {operator} {bound}"));
+        }
+    }
+
+    match &constraints.multiple_of {
+        Some(factor) if integer && factor.is_integer() => {
+            checks.push(format!("{column_name_escaped} % {factor} = 0"));
+        }
+        Some(factor) => checks.push(format!(
+            "abs({column_name_escaped} / {factor} - round({column_name_escaped} / {factor})) < 1e-9"
+        )),
+        None => {}
+    }
+
+    if checks.is_empty() {
+        return Ok(ty.to_owned());
+    }
+    Ok(format!("{ty} CHECK({})", checks.join(" AND ")))
+}
+
 /// Check if a string ends with the definition id of Identified
 fn identified_str<S: AsRef<str>>(str_to_check: S) -> bool {
     str_to_check.as_ref().ends_with("/Identified")
@@ -467,7 +782,7 @@ fn identified_ref(t_to_check: &Type) -> bool {
 }
 
 // Function to create indexes on relevant columns
-fn create_index() -> String {
+fn create_index(columns: &BTreeMap<String, SqlRepresentation>, options: &SchemaOptions) -> String {
     let create_index = |table, column| {
         let index_name_escaped = escape_sql_ident(format!("{table}.{column}"));
         let table_name_escaped = escape_sql_ident(table);
@@ -481,7 +796,7 @@ fn create_index() -> String {
 
     let idxs = [
         (
-            "elements",
+            config().elements_table.as_str(),
             &[
                 // "@id, // is already contained, its the primary key
                 "@type",
@@ -494,7 +809,7 @@ fn create_index() -> String {
             ][..], // make Rust treat this as slice, not ref to fixed size array
         ),
         (
-            "relations",
+            config().relations_table.as_str(),
             &[
                 //"name", // as all three columns are the primary index, name is not required
                 "origin_id",
@@ -504,10 +819,506 @@ fn create_index() -> String {
     ];
 
     let mut result = String::new();
-    for (table, columns) in idxs {
-        for column in columns {
+    for (table, indexed_columns) in idxs {
+        for &column in indexed_columns {
+            // with the elements stored as raw JSON, only the hot fields are columns
+            if table == config().elements_table
+                && !matches!(columns.get(column), Some(SqlRepresentation::Column { .. }))
+            {
+                continue;
+            }
             result = result + &create_index(table, column);
         }
     }
+
+    // with multiple projects, the primary key starts with the project, hence it does not help to
+    // look up an element by its id across all projects
+    if options.multi_project {
+        result = result + &create_index(config().elements_table.as_str(), ELEMENT_PK_COL);
+    }
+    result
+}
+
+/// Function to create a FTS5 full-text index over the textual columns of the elements table
+///
+/// The index is an external content table, i.e. it does not duplicate the text but references the
+/// rows of the elements table. It is kept up to date via triggers. Documentation bodies are covered
+/// by the `body` column, as documentation is represented by elements on its own.
+///
+/// Note that the importer replaces existing rows via `INSERT OR REPLACE`, which only fires the
+/// delete trigger if `recursive_triggers` are enabled.
+pub(crate) fn create_fts() -> String {
+    let fts_table_escaped = escape_sql_ident(FTS_TABLE);
+    let elements_table_escaped = escape_sql_ident(&config().elements_table);
+    let columns = FTS_COLUMNS.map(escape_sql_ident).join(", ");
+    let new_columns = FTS_COLUMNS
+        .map(|c| format!("new.{}", escape_sql_ident(c)))
+        .join(", ");
+    let old_columns = FTS_COLUMNS
+        .map(|c| format!("old.{}", escape_sql_ident(c)))
+        .join(", ");
+
+    let trigger_name = |suffix| escape_sql_ident(format!("{FTS_TABLE}.{suffix}"));
+    let insert_trigger = trigger_name("insert");
+    let delete_trigger = trigger_name("delete");
+    let update_trigger = trigger_name("update");
+
+    format!(
+        "CREATE VIRTUAL TABLE {fts_table_escaped} USING fts5(\n\
+        \t{columns},\n\
+        \tcontent = {elements_table_escaped},\n\
+        \tcontent_rowid = 'rowid'\n\
+        );\n\n\
+        CREATE TRIGGER {insert_trigger} AFTER INSERT ON {elements_table_escaped} BEGIN\n\
+        \tINSERT INTO {fts_table_escaped}(rowid, {columns}) VALUES (new.rowid, {new_columns});\n\
+        END;\n\n\
+        CREATE TRIGGER {delete_trigger} AFTER DELETE ON {elements_table_escaped} BEGIN\n\
+        \tINSERT INTO {fts_table_escaped}({fts_table_escaped}, rowid, {columns}) VALUES ('delete', old.rowid, {old_columns});\n\
+        END;\n\n\
+        CREATE TRIGGER {update_trigger} AFTER UPDATE ON {elements_table_escaped} BEGIN\n\
+        \tINSERT INTO {fts_table_escaped}({fts_table_escaped}, rowid, {columns}) VALUES ('delete', old.rowid, {old_columns});\n\
+        \tINSERT INTO {fts_table_escaped}(rowid, {columns}) VALUES (new.rowid, {new_columns});\n\
+        END;\n\n"
+    )
+}
+
+/// Function to create triggers which reject elements lacking a required property
+///
+/// `required_by_type` maps each element type to the columns which must not be null for elements of
+/// this type. For each type, one trigger for inserts and one for updates is generated.
+pub(super) fn create_not_null_triggers(
+    required_by_type: &BTreeMap<String, BTreeSet<String>>,
+) -> String {
+    let elements_table_escaped = escape_sql_ident(&config().elements_table);
+
+    let mut result = String::new();
+    for (type_name, required) in required_by_type {
+        let type_name_escaped = escape_sql_str_lit(type_name);
+        let condition = required
+            .iter()
+            .map(|column| format!("new.{} IS NULL", escape_sql_ident(column)))
+            .collect::<Vec<_>>()
+            .join(" OR ");
+        let message = escape_sql_str_lit(format!(
+            "elements of type {type_name} require non-null values for {}",
+            required.iter().cloned().collect::<Vec<_>>().join(", ")
+        ));
+
+        for event in ["INSERT", "UPDATE"] {
+            let trigger_name_escaped = escape_sql_ident(format!(
+                "{}.not_null.{type_name}.{}",
+                config().elements_table,
+                event.to_lowercase()
+            ));
+            result.push_str(&format!(
+                "CREATE TRIGGER {trigger_name_escaped} BEFORE {event} ON {elements_table_escaped}\n\
+                WHEN new.\"@type\" = {type_name_escaped} AND ({condition}) BEGIN\n\
+                \tSELECT RAISE(ABORT, {message});\n\
+                END;\n\n"
+            ));
+        }
+    }
+    result
+}
+
+/// Function to create one view per element type, exposing only the columns this type defines
+///
+/// `properties_by_type` maps each element type to the columns it defines. Each view is named after
+/// the type it selects. Types whose name collides with one of the tables are skipped.
+pub(super) fn create_type_views(properties_by_type: &BTreeMap<String, BTreeSet<String>>) -> String {
+    let elements_table_escaped = escape_sql_ident(&config().elements_table);
+    let type_column_escaped = escape_sql_ident(TYPE_COL);
+
+    let mut result = String::new();
+    for (type_name, properties) in properties_by_type {
+        let config = config();
+        if [
+            config.elements_table.as_str(),
+            config.relations_table.as_str(),
+            config.extended_table.as_str(),
+            FTS_TABLE,
+            PROPERTY_DOMAINS_TABLE,
+            RELATION_NAMES_TABLE,
+            SCHEMA_META_TABLE,
+        ]
+        .contains(&type_name.as_str())
+        {
+            warn!("not creating a view for type {type_name:?}, as its name is taken by a table");
+            continue;
+        }
+
+        // the primary key and type always come first
+        let columns = [ELEMENT_PK_COL, TYPE_COL]
+            .into_iter()
+            .chain(
+                properties
+                    .iter()
+                    .map(String::as_str)
+                    .filter(|p| ![ELEMENT_PK_COL, TYPE_COL].contains(p)),
+            )
+            .map(escape_sql_ident)
+            .collect::<Vec<_>>()
+            .join(",\n\t");
+
+        result.push_str(&format!(
+            "CREATE VIEW {} AS SELECT\n\t{columns}\nFROM {elements_table_escaped}\n\
+            WHERE {type_column_escaped} = {};\n\n",
+            escape_sql_ident(type_name),
+            escape_sql_str_lit(type_name),
+        ));
+    }
+    result
+}
+
+/// Function to create curated views answering common questions about a model
+///
+/// The views cover requirements with their documentation, the ownership tree of parts, connections
+/// and allocations with their ends, and documentation texts with the element they document. The
+/// element types each view selects are those defining the property it is based on, e.g. `reqId`
+/// for requirements. Views whose types or columns are missing from the schema are skipped.
+pub(super) fn create_convenience_views(
+    properties_by_type: &BTreeMap<String, BTreeSet<String>>,
+    fused_columns: &BTreeMap<String, SqlRepresentation>,
+    options: &SchemaOptions,
+) -> String {
+    let elements = escape_sql_ident(&config().elements_table);
+    let relations = escape_sql_ident(&config().relations_table);
+    let pk = escape_sql_ident(ELEMENT_PK_COL);
+    let ty = escape_sql_ident(TYPE_COL);
+    let project_col = escape_sql_ident(PROJECT_COL);
+    let target_project_col = escape_sql_ident(TARGET_PROJECT_COL);
+
+    // the types defining a property, as SQL list
+    let types_defining = |property: &str| {
+        properties_by_type
+            .iter()
+            .filter(|(_, properties)| properties.contains(property))
+            .map(|(type_name, _)| escape_sql_str_lit(type_name))
+            .collect::<Vec<_>>()
+            .join(", ")
+    };
+    let has_columns = |names: &[&str]| {
+        names.iter().all(|name| {
+            matches!(
+                fused_columns.get(*name),
+                Some(SqlRepresentation::Column { .. })
+            )
+        })
+    };
+    // in dbs storing multiple projects, the project is selected first and joins stay within it
+    let project_select = |alias: &str| match options.multi_project {
+        true => format!("{alias}.{project_col}, "),
+        false => String::new(),
+    };
+    let origin_join = |relation: &str, element: &str| match options.multi_project {
+        true => format!(" AND {relation}.{project_col} = {element}.{project_col}"),
+        false => String::new(),
+    };
+    let target_join = |relation: &str, element: &str| match options.multi_project {
+        true => format!(" AND {element}.{project_col} = {relation}.{target_project_col}"),
+        false => String::new(),
+    };
+
+    let mut result = String::new();
+    let mut push_view = |name: &str, types: &str, columns: &[&str], body: String| {
+        if types.is_empty() || !has_columns(columns) {
+            debug!("not creating the view {name:?}, as its types or columns are missing");
+            return;
+        }
+        result.push_str(&format!(
+            "CREATE VIEW {} AS {body};\n\n",
+            escape_sql_ident(name)
+        ));
+    };
+
+    let requirement_types = types_defining("reqId");
+    push_view(
+        "requirements",
+        &requirement_types,
+        &["reqId", "declaredName", "qualifiedName", "body"],
+        format!(
+            r#"SELECT
+	{}e.{pk}, e.{ty}, e."reqId", e."declaredName", e."qualifiedName",
+	(SELECT group_concat(d."body", char(10)) FROM {relations} AS r
+		JOIN {elements} AS d ON d.{pk} = r."target_id"{}
+		WHERE r."origin_id" = e.{pk}{} AND r."name" = 'documentation') AS "documentation"
+FROM {elements} AS e
+WHERE e.{ty} IN ({requirement_types})"#,
+            project_select("e"),
+            target_join("r", "d"),
+            origin_join("r", "e"),
+        ),
+    );
+
+    let part_types = ["PartDefinition", "PartUsage"]
+        .into_iter()
+        .filter(|type_name| properties_by_type.contains_key(*type_name))
+        .map(escape_sql_str_lit)
+        .collect::<Vec<_>>()
+        .join(", ");
+    let owned_element = escape_sql_str_lit(OWNED_ELEMENT_RELATION);
+    let tree_project_col = match options.multi_project {
+        true => format!("{project_col}, "),
+        false => String::new(),
+    };
+    push_view(
+        "parts_tree",
+        &part_types,
+        &["declaredName"],
+        format!(
+            r#"WITH RECURSIVE "tree"({tree_project_col}{pk}, {ty}, "declaredName", "parent_id", "depth") AS (
+	SELECT {}e.{pk}, e.{ty}, e."declaredName", NULL, 0 FROM {elements} AS e
+	WHERE e.{ty} IN ({part_types}) AND NOT EXISTS (
+		SELECT 1 FROM {relations} AS r
+		JOIN {elements} AS o ON o.{pk} = r."origin_id"{}
+		WHERE r."target_id" = e.{pk}{} AND r."name" = {owned_element} AND o.{ty} IN ({part_types})
+	)
+	UNION ALL
+	SELECT {}c.{pk}, c.{ty}, c."declaredName", t.{pk}, t."depth" + 1 FROM "tree" AS t
+	JOIN {relations} AS r ON r."origin_id" = t.{pk}{} AND r."name" = {owned_element}
+	JOIN {elements} AS c ON c.{pk} = r."target_id"{}
+	WHERE c.{ty} IN ({part_types})
+)
+SELECT * FROM "tree""#,
+            project_select("e"),
+            origin_join("r", "o"),
+            match options.multi_project {
+                true => format!(" AND r.{target_project_col} = e.{project_col}"),
+                false => String::new(),
+            },
+            project_select("c"),
+            origin_join("r", "t"),
+            target_join("r", "c"),
+        ),
+    );
+
+    // connections and allocations both relate their source to their target
+    let connection_view = |types: &str| {
+        format!(
+            r#"SELECT
+	{}c.{pk}, c.{ty}, c."declaredName",
+	s."target_id" AS "source_id", se."declaredName" AS "source_name",
+	t."target_id" AS "target_id", te."declaredName" AS "target_name"
+FROM {elements} AS c
+JOIN {relations} AS s ON s."origin_id" = c.{pk}{} AND s."name" = 'source'
+JOIN {relations} AS t ON t."origin_id" = c.{pk}{} AND t."name" = 'target'
+LEFT JOIN {elements} AS se ON se.{pk} = s."target_id"{}
+LEFT JOIN {elements} AS te ON te.{pk} = t."target_id"{}
+WHERE c.{ty} IN ({types})"#,
+            project_select("c"),
+            origin_join("s", "c"),
+            origin_join("t", "c"),
+            target_join("s", "se"),
+            target_join("t", "te"),
+        )
+    };
+    let connection_types = types_defining("connectorEnd");
+    push_view(
+        "connections",
+        &connection_types,
+        &["declaredName"],
+        connection_view(&connection_types),
+    );
+    let allocation_types = types_defining("allocationDefinition");
+    push_view(
+        "allocations",
+        &allocation_types,
+        &["declaredName"],
+        connection_view(&allocation_types),
+    );
+
+    let documentation_types = types_defining("documentedElement");
+    push_view(
+        "documentation_texts",
+        &documentation_types,
+        &["declaredName", "body"],
+        format!(
+            r#"SELECT
+	{}d.{pk}, r."target_id" AS "element_id", e."declaredName" AS "element_name", d."body"
+FROM {elements} AS d
+JOIN {relations} AS r ON r."origin_id" = d.{pk}{} AND r."name" = 'documentedElement'
+LEFT JOIN {elements} AS e ON e.{pk} = r."target_id"{}
+WHERE d.{ty} IN ({documentation_types})"#,
+            project_select("d"),
+            origin_join("r", "d"),
+            target_join("r", "e"),
+        ),
+    );
+
+    result
+}
+
+/// Function to create and fill a table mapping each property to the element types that may carry it
+///
+/// `properties_by_type` maps each element type to all properties it defines, regardless of whether
+/// they are stored in the elements table, the relations table or the extended properties table.
+pub(super) fn create_property_domains(
+    properties_by_type: &BTreeMap<String, BTreeSet<String>>,
+    options: &SchemaOptions,
+) -> String {
+    let table_escaped = escape_sql_ident(PROPERTY_DOMAINS_TABLE);
+    let type_column_escaped = escape_sql_ident(TYPE_COL);
+    let table_options = table_options(options, true);
+
+    let mut result = format!(
+        r#"CREATE TABLE {table_escaped} (
+	"property" TEXT NOT NULL,
+	{type_column_escaped} TEXT NOT NULL,
+	PRIMARY KEY("property", {type_column_escaped})
+){table_options};
+"#
+    );
+
+    let values = properties_by_type
+        .iter()
+        .flat_map(|(type_name, properties)| {
+            properties.iter().map(move |property| {
+                format!(
+                    "\t({}, {})",
+                    escape_sql_str_lit(property),
+                    escape_sql_str_lit(type_name)
+                )
+            })
+        })
+        .collect::<Vec<_>>();
+
+    if !values.is_empty() {
+        result.push_str(&format!(
+            "INSERT INTO {table_escaped} (\"property\", {type_column_escaped}) VALUES\n{};\n",
+            values.join(",\n")
+        ));
+    }
+    result += "\n";
+
+    result
+}
+
+/// Function to create and fill a table documenting the provenance of each property
+///
+/// For each property, the table records where it ended up, the definitions of the JSON schema
+/// defining it, all candidate representations which were fused and whether null values were allowed
+/// only due to fusing. `columns` contains the candidate representations of each property,
+/// `fused_columns` the result of fusing them.
+pub(super) fn create_schema_meta(
+    columns: &BTreeMap<String, BTreeSet<SqlRepresentation>>,
+    fused_columns: &BTreeMap<String, SqlRepresentation>,
+    definitions_by_property: &BTreeMap<String, BTreeSet<String>>,
+    options: &SchemaOptions,
+) -> String {
+    let table_escaped = escape_sql_ident(SCHEMA_META_TABLE);
+    let table_options = table_options(options, true);
+
+    let mut result = format!(
+        r#"CREATE TABLE {table_escaped} (
+	"property" TEXT PRIMARY KEY,
+	"table" TEXT NOT NULL,
+	"sql_type" TEXT,
+	"definitions" TEXT NOT NULL,
+	"candidates" TEXT NOT NULL,
+	"nullability_relaxed" INTEGER NOT NULL
+){table_options};
+"#
+    );
+
+    let config = config();
+    let values = fused_columns
+        .iter()
+        .map(|(property, repr)| {
+            let (table, sql_type) = match repr {
+                SqlRepresentation::Column { ty, .. } => (&config.elements_table, Some(ty)),
+                SqlRepresentation::RelationsTable => (&config.relations_table, None),
+                SqlRepresentation::ExtendedPropertiesTable => (&config.extended_table, None),
+            };
+            let candidates = columns.get(property).cloned().unwrap_or_default();
+            let nullability_relaxed = matches!(repr, SqlRepresentation::Column { null: true, .. })
+                && candidates
+                    .iter()
+                    .any(|c| matches!(c, SqlRepresentation::Column { null: false, .. }));
+
+            let definitions = definitions_by_property
+                .get(property)
+                .map(|d| d.iter().map(String::as_str).collect::<Vec<_>>())
+                .unwrap_or_default();
+            let candidates = candidates
+                .iter()
+                .map(SqlRepresentation::describe)
+                .collect::<Vec<_>>();
+
+            format!(
+                "\t({}, {}, {}, {}, {}, {})",
+                escape_sql_str_lit(property),
+                escape_sql_str_lit(table),
+                sql_type.map_or("NULL".to_owned(), escape_sql_str_lit),
+                escape_sql_str_lit(serde_json::Value::from(definitions).to_string()),
+                escape_sql_str_lit(serde_json::Value::from(candidates).to_string()),
+                u8::from(nullability_relaxed)
+            )
+        })
+        .collect::<Vec<_>>();
+
+    if !values.is_empty() {
+        result.push_str(&format!(
+            "INSERT INTO {table_escaped} VALUES\n{};\n",
+            values.join(",\n")
+        ));
+    }
+    result += "\n";
+
+    result
+}
+
+/// Returns the legal variants of a string enum property, if the property is one
+///
+/// This covers both mandatory and nullable string enums.
+pub(super) fn enum_variants(prop: &Type) -> Option<&[String]> {
+    match prop {
+        Type::Concrete(ConcreteType::String {
+            enumeration: Some(variants),
+            ..
+        }) => Some(variants),
+        Type::Composite(CompositeType::OneOf { one_of })
+            if one_of.len() == 2 && one_of.contains(&ConcreteType::Null.into()) =>
+        {
+            one_of.iter().find_map(enum_variants)
+        }
+        _ => None,
+    }
+}
+
+/// Name of the lookup table containing the legal variants of a string enum column
+fn enum_lookup_table_name(column_name: &str) -> String {
+    format!("{ENUM_TABLE_PREFIX}{column_name}")
+}
+
+/// Type of a string enum column whose legal variants are stored in a lookup table
+pub(super) fn enum_lookup_column_type(column_name: &str) -> String {
+    format!(
+        "TEXT REFERENCES {}(\"value\")",
+        escape_sql_ident(enum_lookup_table_name(column_name))
+    )
+}
+
+/// Function to create and fill one lookup table per string enum column
+///
+/// `enum_variants` maps each column holding a string enum to its legal variants.
+pub(super) fn create_enum_lookup_tables(
+    enum_variants: &BTreeMap<String, BTreeSet<String>>,
+    options: &SchemaOptions,
+) -> String {
+    let table_options = table_options(options, true);
+    let mut result = String::new();
+    for (column_name, variants) in enum_variants {
+        let table_escaped = escape_sql_ident(enum_lookup_table_name(column_name));
+        let values = variants
+            .iter()
+            .map(|variant| format!("\t({})", escape_sql_str_lit(variant)))
+            .collect::<Vec<_>>()
+            .join(",\n");
+
+        result.push_str(&format!(
+            "CREATE TABLE {table_escaped} (\n\t\"value\" TEXT PRIMARY KEY\n){table_options};\n\
+            INSERT INTO {table_escaped} (\"value\") VALUES\n{values};\n\n"
+        ));
+    }
     result
 }
@@ -0,0 +1,757 @@
+//! This tool allows to interact with SysML v2 models via SQL
+//!
+//! It does so by importing model data expressed in the canonical JSON schema described in the
+//! [`schemas.json`](https://raw.githubusercontent.com/Systems-Modeling/SysML-v2-API-Services/refs/heads/master/conf/json/schema/api/schemas.json)
+//! file into a SQLite database.
+//!
+//! There is a strong coupling between the model expressivity described in said `schemas.json` and
+//! the database schema. To avoid erroneous manual labor, this tool can also generate a SQLite
+//! compatible SQL Schema from aforementioned JSON schema.
+//!
+//! Besides the CLI, the import and the schema generation are available to non-Rust tooling via a C
+//! ABI, see `ffi`. Rust code may embed the fetch and import via the async functions of [`api`].
+
+#![deny(missing_docs)]
+#![deny(unsafe_code)]
+#![deny(clippy::all)]
+
+use std::io::Write;
+
+use clap::Parser;
+use eyre::{Context, Result};
+
+use crate::cli::Commands;
+
+#[macro_use]
+extern crate tracing;
+
+pub mod api;
+mod backup;
+mod bench;
+mod cli;
+mod closure;
+mod config;
+mod db_info;
+mod db_json;
+mod diff;
+mod edit;
+mod export;
+mod failure;
+#[cfg(feature = "fetch")]
+mod fetch;
+#[cfg(feature = "ffi")]
+pub mod ffi;
+mod import;
+mod init_db;
+mod json_schema_to_sql;
+mod lint;
+mod logging;
+mod migrate_schema;
+mod optimize;
+mod owner;
+mod package_filter;
+mod paths;
+mod progress;
+mod projects;
+mod query;
+mod redact;
+mod regen_embedded_schema;
+mod report;
+mod schema_info;
+mod script;
+mod stats;
+mod surrogate_keys;
+mod transform;
+mod tweaks;
+mod upgrade_db;
+mod util;
+mod verify;
+
+/// Runs the CLI, reporting errors on stderr and telling their kind apart by the exit code
+pub fn main() -> std::process::ExitCode {
+    match run() {
+        Ok(()) => std::process::ExitCode::SUCCESS,
+        Err(report) => {
+            eprintln!("Error: {report:?}");
+            std::process::ExitCode::from(failure::exit_code(&report))
+        }
+    }
+}
+
+/// Runs the command given by the CLI arguments
+fn run() -> Result<()> {
+    dotenv::dotenv().ok();
+
+    // parse the CLI arguments
+    let args = cli::Cli::parse();
+
+    // intialize logger
+    logging::init_logging(
+        args.verbose,
+        args.quiet,
+        args.log_format,
+        args.log_file.as_deref(),
+    )?;
+    color_eyre::install()?;
+
+    trace!("parsed args");
+
+    let maybe_config_path = args
+        .config
+        .clone()
+        .or_else(|| std::env::var_os("SYSML_CONFIG").map(std::path::PathBuf::from));
+    config::load_config(maybe_config_path.as_deref(), &args.pragma, args.max_memory)?;
+
+    let maybe_journal_mode = args
+        .journal_mode
+        .or(args.wal.then_some(cli::JournalMode::Wal));
+    let mut conn = if args.read_only || args.immutable {
+        if maybe_journal_mode.is_some() {
+            eyre::bail!(
+                "--journal-mode and --wal can not be combined with --read-only or --immutable"
+            );
+        }
+        if !args.command.is_read_only() {
+            eyre::bail!("this command modifies the db, thus it can not run on a read-only db");
+        }
+        info!("opening database {:?} read-only", args.db_file);
+        util::open_read_only(&args.db_file, args.immutable)?
+    } else {
+        info!("opening database {:?}", args.db_file);
+        rusqlite::Connection::open(&args.db_file)?
+    };
+
+    util::attach_databases(&conn, &args.attach)?;
+
+    if let Some(project_id) = &args.project {
+        if args.command.is_read_only() {
+            projects::select_project(&conn, project_id)?;
+        }
+    }
+
+    let in_memory = args.db_file.as_os_str() == ":memory:";
+    if let Some(save_to) = args.save_to.as_deref().filter(|p| in_memory && p.exists()) {
+        backup::load_from(&mut conn, save_to)?;
+    }
+    tweaks::configure_connection(
+        &conn,
+        std::time::Duration::from_millis(args.busy_timeout),
+        maybe_journal_mode,
+    )?;
+
+    match args.command {
+        Commands::InitDb => init_db::init_db(&mut conn)?,
+        Commands::UpgradeDb => upgrade_db::upgrade_db(&mut conn)?,
+        Commands::Backup {
+            file,
+            vacuum,
+            force,
+        } => backup::backup(&conn, &file, vacuum, force)?,
+        Commands::SetAttribute {
+            id,
+            attribute,
+            value,
+        } => edit::set_attribute(
+            &mut conn,
+            args.project.as_deref(),
+            &id,
+            &attribute,
+            value.as_deref(),
+        )?,
+        Commands::DeleteElement { id, recursive } => {
+            edit::delete_element(&mut conn, args.project.as_deref(), &id, recursive)?;
+        }
+        Commands::AddRelation {
+            name,
+            origin_id,
+            target_id,
+        } => edit::add_relation(
+            &mut conn,
+            args.project.as_deref(),
+            &name,
+            &origin_id,
+            &target_id,
+        )?,
+        Commands::RemoveRelation {
+            name,
+            origin_id,
+            target_id,
+        } => edit::remove_relation(
+            &mut conn,
+            args.project.as_deref(),
+            &name,
+            &origin_id,
+            &target_id,
+        )?,
+        Commands::ImportJson {
+            file,
+            vacuum,
+            import_args,
+        } => {
+            let elements_stream = crate::util::CloneableJsonArrayStreamIterator::new(&file)?;
+            let options = import::ImportOptions {
+                vacuum,
+                provenance: import::ImportProvenance {
+                    source: Some(file.to_string_lossy().into_owned()),
+                    project_id: args.project,
+                    ..Default::default()
+                },
+                ..import_args.to_options(&file)?
+            };
+            import::import_from_iter(elements_stream, &mut conn, &options)?;
+        }
+        Commands::Search {
+            pattern,
+            r#type,
+            fts,
+            limit,
+        } => {
+            query::search(
+                &conn,
+                &pattern,
+                r#type.as_deref(),
+                fts,
+                limit,
+                &mut std::io::stdout().lock(),
+            )?;
+        }
+        Commands::Tree {
+            root,
+            max_depth,
+            r#type,
+        } => {
+            query::tree(
+                &conn,
+                root.as_deref(),
+                max_depth,
+                &r#type,
+                &mut std::io::stdout().lock(),
+            )?;
+        }
+        Commands::Show { element } => {
+            query::show(&conn, &element, &mut std::io::stdout().lock())?;
+        }
+        Commands::Trace {
+            requirement,
+            max_depth,
+            csv,
+        } => {
+            query::trace(
+                &conn,
+                &requirement,
+                max_depth,
+                csv.as_deref(),
+                &mut std::io::stdout().lock(),
+            )?;
+        }
+        Commands::TraceMatrix { xlsx } => {
+            query::trace_matrix(&conn, &xlsx)?;
+        }
+        Commands::Coverage { json } => {
+            query::coverage(&conn, json, &mut std::io::stdout().lock())?;
+        }
+        Commands::Path {
+            from,
+            to,
+            directed,
+            relation,
+        } => {
+            query::path(
+                &conn,
+                &from,
+                &to,
+                directed,
+                &relation,
+                &mut std::io::stdout().lock(),
+            )?;
+        }
+        Commands::Impact {
+            element,
+            reverse,
+            relation,
+            max_depth,
+        } => {
+            query::impact(
+                &conn,
+                &element,
+                reverse,
+                &relation,
+                max_depth,
+                &mut std::io::stdout().lock(),
+            )?;
+        }
+        Commands::Bom { root, json } => {
+            query::bom(&conn, &root, json, &mut std::io::stdout().lock())?;
+        }
+        Commands::ExportGraph {
+            format,
+            root,
+            relation,
+            r#type,
+            max_depth,
+        } => {
+            export::export_graph(
+                &conn,
+                format,
+                &export::GraphFilter {
+                    maybe_root: root,
+                    relations: relation,
+                    types: r#type,
+                    maybe_max_depth: max_depth,
+                },
+                &mut std::io::stdout().lock(),
+            )?;
+        }
+        Commands::ExportParquet { dir, force } => {
+            export::export_parquet(&conn, &dir, force)?;
+        }
+        Commands::ExportAnonymized {
+            out,
+            db,
+            force,
+            redact,
+        } => {
+            export::export_anonymized(&conn, &out, db, force, &redact)?;
+        }
+        Commands::ExportMetadata { title } => {
+            let db_name = args
+                .db_file
+                .file_stem()
+                .unwrap_or_default()
+                .to_string_lossy();
+            export::export_metadata(
+                &conn,
+                &db_name,
+                title.as_deref(),
+                &mut std::io::stdout().lock(),
+            )?;
+        }
+        Commands::ExportPlantuml { root, max_depth } => {
+            export::export_plantuml(&conn, &root, max_depth, &mut std::io::stdout().lock())?;
+        }
+        Commands::Report { format, template } => {
+            report::report(
+                &conn,
+                format,
+                template.as_deref(),
+                &mut std::io::stdout().lock(),
+            )?;
+        }
+        Commands::DbDiff { other, summary } => {
+            diff::db_diff(&conn, &other, summary, &mut std::io::stdout().lock())?;
+        }
+        Commands::JsonDiff { old, new, json } => {
+            diff::json_diff(&old, &new, json, &mut std::io::stdout().lock())?;
+        }
+        Commands::Stats { json } => {
+            stats::stats(&conn, json, &mut std::io::stdout().lock())?;
+        }
+        Commands::DbInfo { json } => {
+            db_info::db_info(&conn, json, &mut std::io::stdout().lock())?;
+        }
+        Commands::Bench {
+            file,
+            json_schema,
+            runs,
+            variant,
+            dir,
+        } => {
+            let dir = dir.unwrap_or_else(std::env::temp_dir);
+            let maybe_schema = json_schema
+                .as_deref()
+                .map(crate::util::read_json_file)
+                .transpose()?;
+            bench::bench(
+                &file,
+                maybe_schema.as_ref(),
+                runs,
+                &variant,
+                &dir,
+                &mut std::io::stdout().lock(),
+            )?;
+        }
+        Commands::Optimize { apply } => {
+            optimize::optimize(&conn, apply, &mut std::io::stdout().lock())?;
+        }
+        Commands::Lint { json } => {
+            lint::lint(&conn, json, &mut std::io::stdout().lock())?;
+        }
+        Commands::Sql {
+            file,
+            execute,
+            param,
+            format,
+        } => {
+            let sql = match (execute, file) {
+                (Some(sql), _) => sql,
+                (None, Some(file)) if file.as_os_str() == "-" => {
+                    std::io::read_to_string(std::io::stdin())?
+                }
+                (None, Some(file)) => std::fs::read_to_string(&file)
+                    .wrap_err_with(|| format!("reading the SQL script {file:?}"))?,
+                (None, None) => unreachable!("clap requires a script"),
+            };
+            script::run_script(&conn, &sql, &param, format, &mut std::io::stdout().lock())?;
+        }
+        Commands::Validate {
+            rules_dir,
+            json,
+            fail_on_warning,
+        } => {
+            lint::validate(
+                &conn,
+                rules_dir.as_deref(),
+                json,
+                fail_on_warning,
+                &mut std::io::stdout().lock(),
+            )?;
+        }
+        Commands::Verify {
+            file,
+            both_ways,
+            json,
+        } => {
+            verify::verify(&conn, &file, both_ways, json, &mut std::io::stdout().lock())?;
+        }
+        Commands::JsonSchemaToSqlSchema {
+            file,
+            dump_sql,
+            no_init,
+            schema_args,
+        } => {
+            let schema = crate::util::read_json_file(&file)?;
+
+            let maybe_conn = (!no_init).then_some(&mut conn);
+
+            let options = schema_args.to_options();
+
+            let schema = json_schema_to_sql::consume_json_schema(&schema, maybe_conn, &options)?;
+
+            if let Some(path) = dump_sql {
+                info!("writing the fetched data to {path:?}");
+                let mut f = std::fs::File::create(path)?;
+                f.write_all(schema.as_bytes())?;
+            }
+        }
+        Commands::SchemaDiff {
+            old,
+            new,
+            schema_args,
+        } => {
+            let old = crate::util::read_json_file(&old)?;
+            let new = crate::util::read_json_file(&new)?;
+            json_schema_to_sql::schema_diff(
+                &old,
+                &new,
+                &schema_args.to_options(),
+                &mut std::io::stdout().lock(),
+            )?;
+        }
+        Commands::MigrateSchema {
+            file,
+            dump_sql,
+            apply,
+            schema_args,
+        } => {
+            let schema = crate::util::read_json_file(&file)?;
+            let new_schema =
+                json_schema_to_sql::consume_json_schema(&schema, None, &schema_args.to_options())?;
+
+            let migration = migrate_schema::generate_migration(&conn, &new_schema)?;
+
+            if let Some(path) = dump_sql {
+                info!("writing the migration to {path:?}");
+                let mut f = std::fs::File::create(path)?;
+                f.write_all(migration.to_script().as_bytes())?;
+            }
+
+            if apply {
+                migrate_schema::apply_migration(&mut conn, &migration)?;
+            }
+        }
+        #[cfg(feature = "fetch")]
+        Commands::DownloadSchema {
+            file,
+            url,
+            allow_invalid_certs,
+            ca_cert,
+            apply,
+            schema_args,
+        } => {
+            let url = reqwest::Url::parse(&url)?;
+
+            let rt = runtime()?;
+            let http_options = fetch::HttpOptions {
+                allow_invalid_certs,
+                maybe_ca_cert: ca_cert,
+                ..Default::default()
+            };
+            let schema_text = rt.block_on(fetch::download_schema(url, &http_options))?;
+
+            // refuse to store anything that is not a JSON schema understood by this tool
+            let schema: json_schema_to_sql::Root = serde_json::from_str(&schema_text)?;
+
+            info!("writing the JSON schema to {file:?}");
+            std::fs::write(&file, &schema_text)?;
+
+            if apply {
+                let options = schema_args.to_options();
+                if util::table_exists(&conn, &config::config().elements_table)? {
+                    let new_schema =
+                        json_schema_to_sql::consume_json_schema(&schema, None, &options)?;
+                    let migration = migrate_schema::generate_migration(&conn, &new_schema)?;
+                    migrate_schema::apply_migration(&mut conn, &migration)?;
+                } else {
+                    json_schema_to_sql::consume_json_schema(&schema, Some(&mut conn), &options)?;
+                }
+            }
+        }
+        Commands::RegenEmbeddedSchema {
+            file,
+            write,
+            schema_args,
+        } => {
+            let schema = crate::util::read_json_file(&file)?;
+            regen_embedded_schema::regen_embedded_schema(
+                &schema,
+                &schema_args.to_options(),
+                write.as_deref(),
+                &mut std::io::stdout().lock(),
+            )?;
+        }
+        #[cfg(feature = "fetch")]
+        Commands::Fetch {
+            base_url,
+            http_args,
+            dump_json,
+            page_size,
+            pretty,
+            no_import,
+            import_args,
+            derived,
+            relationships,
+            query_id,
+            query,
+            project,
+        } => {
+            if dump_json.is_none() && pretty {
+                warn!("the -p/--pretty flag has no effect if FILE is not set");
+            }
+            // the skipped elements are written next to the dump, if any, or else the db
+            let import_options =
+                import_args.to_options(dump_json.as_deref().unwrap_or(&args.db_file))?;
+
+            let sysml_browser =
+                fetch::SysmlV2ApiBrowser::new(&base_url, http_args.to_options(), args.api_version)?;
+            let source = sysml_browser.base_url().to_string();
+
+            let rt = runtime()?;
+            let result: Result<()> = rt.block_on(async {
+                let (project_id, commit_id) =
+                    fetch::interprete_cli(&sysml_browser, &project).await?;
+                if let Some(selected) = args.project.as_ref().filter(|p| **p != project_id) {
+                    eyre::bail!(
+                        "the fetched project {project_id:?} differs from the selected project {selected:?}"
+                    );
+                }
+
+                let url_path = fetch::build_url_path(&project_id, &commit_id, page_size);
+                let maybe_conn = (!no_import).then_some(&mut conn);
+                // the results of a query are part of the commit only
+                let partial = match (&query_id, &query) {
+                    (Some(query_id), _) => vec![format!("only the results of the query {query_id}")],
+                    (None, Some(query)) => vec![format!("only the results of the query {query}")],
+                    (None, None) => Vec::new(),
+                };
+                let import_options = import::ImportOptions {
+                    provenance: import::ImportProvenance {
+                        source: Some(source),
+                        project_id: Some(project_id.clone()),
+                        commit_id: Some(commit_id.clone()),
+                        partial,
+                    },
+                    ..import_options
+                };
+                let maybe_query = (query_id.is_some() || query.is_some()).then(|| {
+                    fetch::ElementQuery {
+                        url_path: fetch::build_query_url_path(
+                            &project_id,
+                            &commit_id,
+                            query_id.as_deref(),
+                        ),
+                        maybe_query: query,
+                    }
+                });
+                fetch::fetch_from_url_to_file(
+                    sysml_browser,
+                    &url_path,
+                    &dump_json,
+                    maybe_conn,
+                    &import_options,
+                    pretty,
+                    fetch::FetchOptions {
+                        maybe_query,
+                        derived,
+                        relationships,
+                        ..Default::default()
+                    },
+                )
+                .await?;
+
+                Ok(())
+            });
+            result?;
+        }
+        #[cfg(feature = "fetch")]
+        Commands::Sync {
+            base_url,
+            project,
+            interval,
+            once,
+            listen,
+            two_way,
+            http_args,
+            page_size,
+            skip_schema_check,
+        } => {
+            let sysml_browser =
+                fetch::SysmlV2ApiBrowser::new(&base_url, http_args.to_options(), args.api_version)?;
+            let import_options = import::ImportOptions {
+                skip_schema_check,
+                provenance: import::ImportProvenance {
+                    source: Some(sysml_browser.base_url().to_string()),
+                    ..Default::default()
+                },
+                ..Default::default()
+            };
+
+            let rt = runtime()?;
+            rt.block_on(fetch::sync(
+                &sysml_browser,
+                &mut conn,
+                &project,
+                &fetch::SyncOptions {
+                    interval,
+                    once,
+                    maybe_listen_addr: listen,
+                    maybe_page_size: page_size,
+                    two_way,
+                },
+                &import_options,
+            ))?;
+        }
+        #[cfg(feature = "fetch")]
+        Commands::Push {
+            base_url,
+            project_id,
+            branch_id,
+            message,
+            dry_run,
+            http_args,
+            page_size,
+        } => {
+            let sysml_browser =
+                fetch::SysmlV2ApiBrowser::new(&base_url, http_args.to_options(), args.api_version)?;
+
+            let rt = runtime()?;
+            rt.block_on(fetch::push(
+                &sysml_browser,
+                &mut conn,
+                &project_id,
+                &fetch::PushOptions {
+                    maybe_branch_id: branch_id,
+                    maybe_message: message,
+                    dry_run,
+                    maybe_page_size: page_size,
+                },
+                &mut std::io::stdout().lock(),
+            ))?;
+        }
+        #[cfg(feature = "fetch")]
+        Commands::CreateProject {
+            base_url,
+            name,
+            description,
+            http_args,
+        } => {
+            let sysml_browser =
+                fetch::SysmlV2ApiBrowser::new(&base_url, http_args.to_options(), args.api_version)?;
+
+            let rt = runtime()?;
+            rt.block_on(fetch::create_project(
+                &sysml_browser,
+                &name,
+                description.as_deref(),
+                &mut std::io::stdout().lock(),
+            ))?;
+        }
+        #[cfg(feature = "fetch")]
+        Commands::CreateBranch {
+            base_url,
+            project_id,
+            name,
+            commit_id,
+            http_args,
+        } => {
+            let sysml_browser =
+                fetch::SysmlV2ApiBrowser::new(&base_url, http_args.to_options(), args.api_version)?;
+
+            let rt = runtime()?;
+            rt.block_on(fetch::create_branch(
+                &sysml_browser,
+                &project_id,
+                &name,
+                commit_id.as_deref(),
+                &mut std::io::stdout().lock(),
+            ))?;
+        }
+        #[cfg(feature = "fetch")]
+        Commands::Clone {
+            base_url,
+            project_id,
+            branches,
+            all_commits,
+            http_args,
+            page_size,
+            skip_schema_check,
+        } => {
+            if args.project.is_some() {
+                warn!("the selected project is ignored, each commit is stored under its own id");
+            }
+
+            let sysml_browser =
+                fetch::SysmlV2ApiBrowser::new(&base_url, http_args.to_options(), args.api_version)?;
+            let import_options = import::ImportOptions {
+                skip_schema_check,
+                provenance: import::ImportProvenance {
+                    source: Some(sysml_browser.base_url().to_string()),
+                    ..Default::default()
+                },
+                ..Default::default()
+            };
+
+            let rt = runtime()?;
+            rt.block_on(fetch::clone_project(
+                &sysml_browser,
+                &mut conn,
+                &project_id,
+                &branches,
+                all_commits,
+                page_size,
+                &import_options,
+            ))?;
+        }
+    }
+
+    if let Some(save_to) = &args.save_to {
+        backup::save_to(&conn, save_to)?;
+    }
+
+    Ok(())
+}
+
+/// Starts the async runtime the commands talking to the API server run on
+#[cfg(feature = "fetch")]
+fn runtime() -> Result<tokio::runtime::Runtime> {
+    tokio::runtime::Runtime::new().wrap_err("starting the async runtime")
+}
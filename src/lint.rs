@@ -0,0 +1,284 @@
+//! Sanity checks for an imported model
+//!
+//! Each check is a SQL query yielding one row per finding. The first column of each row is the id
+//! of the offending element (or `NULL`, if the finding is not about a single element), the second
+//! column is a human readable message. Checks which are not expressible in SQL, such as the cycle
+//! detection, are implemented in Rust.
+
+use std::{io::Write, path::Path};
+
+use eyre::{Result, WrapErr, bail};
+use rusqlite::Connection;
+
+mod cycles;
+mod rules;
+
+use crate::{
+    config::{ELEMENT_PK_COL, PROPERTY_DOMAINS_TABLE, config},
+    util::{escape_sql_ident, table_exists},
+};
+
+/// Severity of a finding
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, serde::Serialize)]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum Severity {
+    /// Something is suspicious, but may be intended
+    Warning,
+
+    /// Something is broken
+    Error,
+}
+
+impl std::fmt::Display for Severity {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Severity::Warning => write!(f, "warning"),
+            Severity::Error => write!(f, "error"),
+        }
+    }
+}
+
+impl std::str::FromStr for Severity {
+    type Err = eyre::Report;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "warning" => Ok(Severity::Warning),
+            "error" => Ok(Severity::Error),
+            _ => bail!("unknown severity {s:?}, expected either \"warning\" or \"error\""),
+        }
+    }
+}
+
+/// A single check, to be run against the database
+pub(crate) struct Check {
+    /// Short, unique name of the check
+    pub(crate) name: String,
+
+    /// Severity of the findings of this check
+    pub(crate) severity: Severity,
+
+    /// Query yielding the findings, see the module level documentation
+    pub(crate) query: String,
+}
+
+/// A single finding of a [`Check`]
+#[derive(Debug, Clone, serde::Serialize)]
+pub(crate) struct Finding {
+    /// Name of the check which produced this finding
+    pub(crate) check: String,
+
+    /// Severity of the finding
+    pub(crate) severity: Severity,
+
+    /// Id of the offending element, if any
+    pub(crate) element_id: Option<String>,
+
+    /// Human readable description of the finding
+    pub(crate) message: String,
+}
+
+/// The checks performed by the `lint` command
+fn builtin_checks(conn: &Connection) -> Result<Vec<Check>> {
+    let elements_table = escape_sql_ident(&config().elements_table);
+    let relations_table = escape_sql_ident(&config().relations_table);
+    let extended_table = escape_sql_ident(&config().extended_table);
+    let pk_col = escape_sql_ident(ELEMENT_PK_COL);
+
+    let mut checks = vec![
+        Check {
+            name: "orphan".to_owned(),
+            severity: Severity::Warning,
+            query: format!(
+                r#"SELECT e.{pk_col}, 'element of type ' || COALESCE(e."@type", 'NULL') || ' has no owner'
+                FROM {elements_table} AS e
+                WHERE COALESCE(e."@type", '') NOT IN ('Namespace', 'Package', 'LibraryPackage')
+                AND NOT EXISTS (
+                    SELECT 1 FROM {relations_table} AS r WHERE r."origin_id" = e.{pk_col}
+                    AND r."name" IN ('owner', 'owningRelatedElement')
+                )"#
+            ),
+        },
+        Check {
+            name: "duplicate-qualified-name".to_owned(),
+            severity: Severity::Warning,
+            query: format!(
+                r#"SELECT e.{pk_col}, 'qualified name ' || quote(e."qualifiedName") || ' is not unique'
+                FROM {elements_table} AS e
+                WHERE e."qualifiedName" IN (
+                    SELECT "qualifiedName" FROM {elements_table}
+                    GROUP BY "qualifiedName" HAVING COUNT(*) > 1
+                )"#
+            ),
+        },
+        Check {
+            name: "empty-name".to_owned(),
+            severity: Severity::Warning,
+            query: format!(
+                r#"SELECT {pk_col}, 'element has an empty or blank name'
+                FROM {elements_table}
+                WHERE trim("declaredName") = '' OR trim("name") = ''"#
+            ),
+        },
+        Check {
+            name: "missing-type".to_owned(),
+            severity: Severity::Error,
+            query: format!(
+                r#"SELECT {pk_col}, 'element has no @type' FROM {elements_table}
+                WHERE "@type" IS NULL"#
+            ),
+        },
+    ];
+
+    for (column, role) in [("origin_id", "origin"), ("target_id", "target")] {
+        checks.push(Check {
+            name: format!("dangling-relation-{role}"),
+            severity: Severity::Error,
+            query: format!(
+                r#"SELECT NULL, 'relation ' || quote(r."name") || ' from ' || r."origin_id"
+                    || ' to ' || r."target_id" || ' references the missing {role} element'
+                FROM {relations_table} AS r
+                WHERE NOT EXISTS (SELECT 1 FROM {elements_table} AS e WHERE e.{pk_col} = r."{column}")"#
+            ),
+        });
+    }
+
+    checks.push(Check {
+        name: "dangling-extended-property".to_owned(),
+        severity: Severity::Error,
+        query: format!(
+            r#"SELECT DISTINCT p.{pk_col}, 'extended property references a missing element'
+            FROM {extended_table} AS p
+            WHERE NOT EXISTS (SELECT 1 FROM {elements_table} AS e WHERE e.{pk_col} = p.{pk_col})"#
+        ),
+    });
+
+    // only databases initialized from a generated schema know which properties a type may carry
+    if table_exists(conn, PROPERTY_DOMAINS_TABLE)? {
+        let domains_table = escape_sql_ident(PROPERTY_DOMAINS_TABLE);
+        checks.push(Check {
+            name: "property-outside-domain".to_owned(),
+            severity: Severity::Warning,
+            query: format!(
+                r#"SELECT DISTINCT r."origin_id", 'element of type ' || e."@type"
+                    || ' carries the property ' || quote(r."name") || ' which its type does not define'
+                FROM {relations_table} AS r
+                JOIN {elements_table} AS e ON e.{pk_col} = r."origin_id"
+                WHERE EXISTS (SELECT 1 FROM {domains_table} AS d WHERE d."@type" = e."@type")
+                AND NOT EXISTS (
+                    SELECT 1 FROM {domains_table} AS d
+                    WHERE d."@type" = e."@type" AND d."property" = r."name"
+                )"#
+            ),
+        });
+    }
+
+    Ok(checks)
+}
+
+/// Run a set of checks against the database, collecting all findings
+pub(crate) fn run_checks(conn: &Connection, checks: &[Check]) -> Result<Vec<Finding>> {
+    let mut findings = Vec::new();
+    for check in checks {
+        let now = std::time::Instant::now();
+        debug!("running check {:?}", check.name);
+        trace!("check query:\n{}", check.query);
+
+        let mut stmt = conn
+            .prepare(&check.query)
+            .wrap_err_with(|| format!("preparing the query of check {:?}", check.name))?;
+        let mut rows = stmt.query(())?;
+        let findings_before = findings.len();
+        while let Some(row) = rows.next()? {
+            findings.push(Finding {
+                check: check.name.clone(),
+                severity: check.severity,
+                element_id: row.get(0)?,
+                message: row.get(1)?,
+            });
+        }
+        debug!(
+            "check {:?} yielded {} findings in {:?}",
+            check.name,
+            findings.len() - findings_before,
+            now.elapsed()
+        );
+    }
+
+    Ok(findings)
+}
+
+/// Print findings, either as tab separated lines or as JSON array
+///
+/// Returns an error if at least one finding has [`Severity::Error`], or if `fail_on_warning` is set
+/// and there is at least one finding at all.
+pub(crate) fn report_findings<W: Write>(
+    findings: &[Finding],
+    json: bool,
+    fail_on_warning: bool,
+    out: &mut W,
+) -> Result<()> {
+    if json {
+        serde_json::to_writer_pretty(&mut *out, findings)?;
+        writeln!(out)?;
+    } else {
+        for f in findings {
+            writeln!(
+                out,
+                "{}\t{}\t{}\t{}",
+                f.severity,
+                f.check,
+                f.element_id.as_deref().unwrap_or("-"),
+                f.message
+            )?;
+        }
+    }
+
+    let errors = findings
+        .iter()
+        .filter(|f| f.severity == Severity::Error)
+        .count();
+    let warnings = findings.len() - errors;
+    info!("found {errors} errors and {warnings} warnings");
+
+    if errors != 0 {
+        bail!("{errors} checks failed with severity error");
+    }
+    if fail_on_warning && warnings != 0 {
+        bail!("{warnings} checks failed with severity warning");
+    }
+
+    Ok(())
+}
+
+/// Run all builtin sanity checks against the database and report the findings
+pub(crate) fn lint<W: Write>(conn: &Connection, json: bool, out: &mut W) -> Result<()> {
+    let mut findings = run_checks(conn, &builtin_checks(conn)?)?;
+    findings.append(&mut cycles::find_cycles(conn)?);
+    report_findings(&findings, json, false, out)
+}
+
+/// Run user-defined validation rules against the database and report the findings
+///
+/// Rules are loaded from `maybe_rules_dir` (if given) and from the rules table in the database (if
+/// it exists).
+pub(crate) fn validate<W: Write>(
+    conn: &Connection,
+    maybe_rules_dir: Option<&Path>,
+    json: bool,
+    fail_on_warning: bool,
+    out: &mut W,
+) -> Result<()> {
+    let mut rules = rules::load_rules_from_table(conn)?;
+    if let Some(rules_dir) = maybe_rules_dir {
+        rules.append(&mut rules::load_rules_from_dir(rules_dir)?);
+    }
+
+    if rules.is_empty() {
+        warn!("no validation rules found");
+    }
+    info!("running {} validation rules", rules.len());
+
+    let findings = run_checks(conn, &rules)?;
+    report_findings(&findings, json, fail_on_warning, out)
+}
@@ -0,0 +1,115 @@
+use std::collections::{HashMap, HashSet};
+
+use eyre::Result;
+use rusqlite::Connection;
+
+use crate::{
+    config::config,
+    util::{escape_sql_ident, escape_sql_str_lit},
+};
+
+use super::{Finding, Severity};
+
+/// Groups of relations whose union must be acyclic by construction
+///
+/// The ownership group contains both the direct `owner` relation and the indirect path via the
+/// owning relationship (usually a membership), as the ownership of relationships is only visible
+/// through the latter.
+const ACYCLIC_RELATION_GROUPS: [(&str, &[&str]); 2] = [
+    (
+        "ownership",
+        &["owner", "owningRelationship", "owningRelatedElement"],
+    ),
+    ("namespace", &["owningNamespace"]),
+];
+
+/// Detect cycles in all relation groups which must be acyclic, yielding one finding per cycle
+pub(super) fn find_cycles(conn: &Connection) -> Result<Vec<Finding>> {
+    let relations_table = escape_sql_ident(&config().relations_table);
+    let mut findings = Vec::new();
+
+    for (group_name, relation_names) in ACYCLIC_RELATION_GROUPS {
+        let now = std::time::Instant::now();
+        debug!("searching for cycles in the {group_name} relations");
+
+        let relation_names = relation_names
+            .iter()
+            .map(escape_sql_str_lit)
+            .collect::<Vec<_>>()
+            .join(", ");
+        let mut stmt = conn.prepare(&format!(
+            r#"SELECT "origin_id", "target_id" FROM {relations_table}
+            WHERE "name" IN ({relation_names})"#
+        ))?;
+        let mut graph: HashMap<String, Vec<String>> = HashMap::new();
+        let mut rows = stmt.query(())?;
+        while let Some(row) = rows.next()? {
+            graph.entry(row.get(0)?).or_default().push(row.get(1)?);
+        }
+
+        for cycle in cycles_in(&graph) {
+            findings.push(Finding {
+                check: format!("cycle-{group_name}"),
+                severity: Severity::Error,
+                element_id: Some(cycle[0].to_owned()),
+                message: format!(
+                    "cycle in the {group_name} relations: {} -> {}",
+                    cycle.join(" -> "),
+                    cycle[0]
+                ),
+            });
+        }
+
+        debug!("cycle search took {:?}", now.elapsed());
+    }
+
+    Ok(findings)
+}
+
+/// Find cycles in a directed graph via depth-first search
+///
+/// Returns one cycle per back edge found. Each cycle is rotated so that it starts with its
+/// smallest node, which makes the output deterministic.
+fn cycles_in(graph: &HashMap<String, Vec<String>>) -> Vec<Vec<&str>> {
+    let mut finished: HashSet<&str> = HashSet::new();
+    let mut cycles = Vec::new();
+
+    let mut start_nodes: Vec<&str> = graph.keys().map(String::as_str).collect();
+    start_nodes.sort_unstable();
+
+    for start in start_nodes {
+        if finished.contains(start) {
+            continue;
+        }
+
+        // the current path, each node with the index of its next successor to visit
+        let mut path: Vec<(&str, usize)> = vec![(start, 0)];
+        let mut on_path: HashSet<&str> = HashSet::from([start]);
+
+        while let Some((node, next_idx)) = path.last_mut() {
+            let successors = graph.get(*node).map(Vec::as_slice).unwrap_or_default();
+            let Some(successor) = successors.get(*next_idx) else {
+                // all successors visited, backtrack
+                finished.insert(node);
+                on_path.remove(node);
+                path.pop();
+                continue;
+            };
+            *next_idx += 1;
+
+            if on_path.contains(successor.as_str()) {
+                // back edge, the path from the successor to the current node is a cycle
+                let cycle_start = path.iter().position(|(n, _)| n == successor).unwrap();
+                let mut cycle: Vec<&str> = path[cycle_start..].iter().map(|(n, _)| *n).collect();
+                let min_idx = (0..cycle.len()).min_by_key(|i| cycle[*i]).unwrap();
+                cycle.rotate_left(min_idx);
+                cycles.push(cycle);
+            } else if !finished.contains(successor.as_str()) {
+                on_path.insert(successor);
+                path.push((successor, 0));
+            }
+        }
+    }
+
+    cycles
+}
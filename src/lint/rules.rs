@@ -0,0 +1,98 @@
+use std::path::Path;
+
+use eyre::{Result, WrapErr, bail};
+use rusqlite::Connection;
+
+use crate::{
+    config::VALIDATION_RULES_TABLE,
+    util::{escape_sql_ident, table_exists},
+};
+
+use super::{Check, Severity};
+
+/// Prefix of the comment line declaring the severity of a rule file
+const SEVERITY_DIRECTIVE: &str = "-- severity:";
+
+/// Load user-defined rules from a directory
+///
+/// Every file ending in `.sql` is a rule, named after the file stem. The query must yield the id
+/// of the offending element (or `NULL`) and a message per violation. The severity defaults to
+/// error, but can be overridden by a line like `-- severity: warning` anywhere in the file.
+pub(super) fn load_rules_from_dir(dir: &Path) -> Result<Vec<Check>> {
+    let mut paths = Vec::new();
+    for entry in std::fs::read_dir(dir).wrap_err_with(|| format!("reading rules from {dir:?}"))? {
+        let path = entry?.path();
+        if path.extension().is_some_and(|ext| ext == "sql") {
+            paths.push(path);
+        }
+    }
+    paths.sort();
+
+    let mut rules = Vec::with_capacity(paths.len());
+    for path in paths {
+        trace!("loading rule from {path:?}");
+        let query = std::fs::read_to_string(&path)?;
+        let name = path
+            .file_stem()
+            .expect("file with an extension has a stem")
+            .to_string_lossy()
+            .into_owned();
+
+        let mut severity = Severity::Error;
+        for line in query.lines() {
+            if let Some(value) = line.trim().strip_prefix(SEVERITY_DIRECTIVE) {
+                severity = value
+                    .trim()
+                    .parse()
+                    .wrap_err_with(|| format!("parsing the severity of rule {path:?}"))?;
+            }
+        }
+
+        rules.push(Check {
+            name,
+            severity,
+            query,
+        });
+    }
+    debug!("loaded {} rules from {dir:?}", rules.len());
+
+    Ok(rules)
+}
+
+/// Load user-defined rules from the rules table, if it exists
+///
+/// The table has the columns `name`, `severity` and `query`, with the same semantics as for
+/// [`load_rules_from_dir`].
+pub(super) fn load_rules_from_table(conn: &Connection) -> Result<Vec<Check>> {
+    if !table_exists(conn, VALIDATION_RULES_TABLE)? {
+        debug!("there is no {VALIDATION_RULES_TABLE:?} table, skipping it");
+        return Ok(Vec::new());
+    }
+
+    let rules_table = escape_sql_ident(VALIDATION_RULES_TABLE);
+    let mut stmt = conn.prepare(&format!(
+        r#"SELECT "name", "severity", "query" FROM {rules_table} ORDER BY "name""#
+    ))?;
+    let mut rows = stmt.query(())?;
+    let mut rules = Vec::new();
+    while let Some(row) = rows.next()? {
+        let name: String = row.get(0)?;
+        let severity: String = row.get(1)?;
+        let Ok(severity) = severity.parse() else {
+            bail!(
+                "rule {name:?} in the {VALIDATION_RULES_TABLE:?} table has the invalid severity {severity:?}"
+            );
+        };
+        rules.push(Check {
+            name,
+            severity,
+            query: row.get(2)?,
+        });
+    }
+    debug!(
+        "loaded {} rules from the {VALIDATION_RULES_TABLE:?} table",
+        rules.len()
+    );
+
+    Ok(rules)
+}
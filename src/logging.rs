@@ -0,0 +1,68 @@
+//! Setup of the log output, to stderr and optionally to a file
+
+use std::{fs::File, path::Path, sync::Arc};
+
+use eyre::{Context, Result};
+use tracing_subscriber::{EnvFilter, Layer, layer::SubscriberExt, util::SubscriberInitExt};
+
+use crate::cli::LogFormat;
+
+/// Filter of the log file, all messages of this tool and debug messages of its dependencies
+const LOG_FILE_FILTER: &str = "debug,sysml_v2_sql=trace";
+
+/// Initialize the logging to stderr, and to `maybe_log_file` if given
+///
+/// With `quiet`, only errors are written to stderr. Otherwise, the level of the messages on stderr
+/// is taken from the environment variable RUST_LOG if set, or else from `verbose`. The log file
+/// always receives the messages of all levels, so that it can be attached to support requests in
+/// full. It is truncated first.
+pub(crate) fn init_logging(
+    verbose: u8,
+    quiet: bool,
+    format: LogFormat,
+    maybe_log_file: Option<&Path>,
+) -> Result<()> {
+    let console_filter = if quiet {
+        EnvFilter::new("error")
+    } else {
+        EnvFilter::try_from_default_env().unwrap_or_else(|_| {
+            EnvFilter::new(match verbose {
+                0 => "info",
+                1 => "debug",
+                _ => "trace",
+            })
+        })
+    };
+    let console_layer = tracing_subscriber::fmt::layer().with_writer(std::io::stderr);
+    let console_layer = match format {
+        LogFormat::Text => console_layer
+            .without_time()
+            .with_target(false)
+            .with_ansi(std::io::IsTerminal::is_terminal(&std::io::stderr()))
+            .boxed(),
+        LogFormat::Json => console_layer.json().boxed(),
+    };
+
+    let maybe_file_layer = match maybe_log_file {
+        Some(path) => {
+            let file =
+                File::create(path).wrap_err_with(|| format!("creating the log file {path:?}"))?;
+            let file_layer = tracing_subscriber::fmt::layer()
+                .with_writer(Arc::new(file))
+                .with_ansi(false);
+            let file_layer = match format {
+                LogFormat::Text => file_layer.boxed(),
+                LogFormat::Json => file_layer.json().boxed(),
+            };
+            Some(file_layer.with_filter(EnvFilter::new(LOG_FILE_FILTER)))
+        }
+        None => None,
+    };
+
+    tracing_subscriber::registry()
+        .with(console_layer.with_filter(console_filter))
+        .with(maybe_file_layer)
+        .try_init()?;
+
+    Ok(())
+}
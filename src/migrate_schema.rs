@@ -0,0 +1,514 @@
+//! Migration of a db created from an older JSON schema to a newly generated SQL schema
+//!
+//! The newly generated SQL schema is loaded into an in-memory db, which is then compared against
+//! the db to migrate. The core tables (elements, relations and extended properties) carry the data,
+//! hence they are migrated in place where possible: columns which were only added are appended via
+//! `ALTER TABLE ... ADD COLUMN`, which is cheap regardless of the amount of data. Tables which
+//! changed otherwise are rebuilt following the procedure recommended by SQLite, i.e. a new table is
+//! created, the data from all columns present in both versions is copied and the old table is
+//! replaced. All other objects generated from the JSON schema (indexes, triggers, views, auxiliary
+//! tables) are dropped and recreated from the new schema.
+//!
+//! The import adds columns and indexes to the core tables at runtime, e.g. the owner column, see
+//! [`crate::owner`]. The new schema lacks them, yet they are kept, as are the tables maintained by
+//! the import, e.g. the import log. Before generating the statements, the migration is tried on an
+//! empty copy of the db, which also yields the fingerprint the migrated db is stamped with.
+
+use std::collections::{BTreeMap, BTreeSet};
+
+use eyre::{Result, WrapErr, bail};
+use rusqlite::{Connection, types::ValueRef};
+
+use crate::{
+    config::{FTS_TABLE, OWNER_COL, RELATION_NAMES_TABLE, config},
+    failure::Failure,
+    schema_info::{SchemaOrigin, schema_fingerprint, stamp_schema, stamp_statements},
+    upgrade_db::{SCHEMA_VERSION, set_schema_version},
+    util::{escape_sql_ident, escape_sql_str_lit},
+};
+
+/// Columns the import adds to the core tables at runtime, which are kept regardless of the new
+/// schema
+const IMPORT_MANAGED_COLUMNS: &[&str] = &[OWNER_COL];
+
+/// Statements migrating a db to a new schema
+pub(crate) struct Migration {
+    /// The statements, to be executed in order within one transaction
+    statements: Vec<String>,
+
+    /// The schema fingerprint of the migrated db, see [`crate::schema_info`]
+    fingerprint: String,
+}
+
+impl Migration {
+    /// Renders the migration as self-contained SQL script
+    pub(crate) fn to_script(&self) -> String {
+        let mut script = String::from("PRAGMA foreign_keys = OFF;\nBEGIN;\n\n");
+        for statement in &self.statements {
+            script.push_str(statement);
+            script.push_str(";\n\n");
+        }
+        script.push_str(&format!("PRAGMA user_version = {SCHEMA_VERSION};\n"));
+        script.push_str(&stamp_statements(
+            &self.fingerprint,
+            SchemaOrigin::Generated,
+        ));
+        script.push_str("COMMIT;\nPRAGMA foreign_keys = ON;\n");
+        script
+    }
+}
+
+/// An object from the `sqlite_schema` table
+struct SchemaObject {
+    ty: String,
+    name: String,
+    tbl_name: String,
+    sql: String,
+}
+
+/// Generates the statements to migrate the db to the SQL schema `new_schema`
+pub(crate) fn generate_migration(conn: &Connection, new_schema: &str) -> Result<Migration> {
+    let new_conn = Connection::open_in_memory()?;
+    new_conn.execute_batch(new_schema)?;
+
+    let config = config();
+    let core_tables = [
+        config.elements_table.as_str(),
+        config.relations_table.as_str(),
+        config.extended_table.as_str(),
+    ];
+
+    let old_objects = schema_objects(conn)?;
+    let new_objects = schema_objects(&new_conn)?;
+
+    // indexes on the core tables lacking from the new schema were added at runtime, by the import
+    // or `optimize`, or stem from the old schema. Either way, they are kept as long as their
+    // columns are.
+    let mut kept_indexes = Vec::new();
+    for object in &old_objects {
+        if object.ty == "index"
+            && core_tables.contains(&object.tbl_name.as_str())
+            && !new_objects.iter().any(|o| o.name == object.name)
+        {
+            kept_indexes.push((object, index_columns(conn, &object.name)?));
+        }
+    }
+
+    // objects generated from the JSON schema, apart from the core tables
+    let new_derived: Vec<_> = new_objects
+        .iter()
+        .filter(|o| !core_tables.contains(&o.name.as_str()))
+        .collect();
+    let new_derived_names: BTreeSet<_> = new_derived.iter().map(|o| o.name.as_str()).collect();
+
+    let mut statements = Vec::new();
+
+    //
+    // drop the derived objects, views first as they may depend on anything else
+    //
+
+    for ty in ["view", "trigger", "index", "table"] {
+        for object in old_objects.iter().filter(|o| o.ty == ty) {
+            if core_tables.contains(&object.name.as_str()) {
+                continue;
+            }
+
+            let attached_to_core_table = matches!(ty, "trigger" | "index")
+                && core_tables.contains(&object.tbl_name.as_str());
+            if attached_to_core_table || new_derived_names.contains(object.name.as_str()) {
+                statements.push(format!(
+                    "DROP {} {}",
+                    ty.to_uppercase(),
+                    escape_sql_ident(&object.name)
+                ));
+            }
+        }
+    }
+
+    //
+    // migrate the core tables
+    //
+
+    for table in core_tables {
+        let Some(new_table) = new_objects.iter().find(|o| o.name == table) else {
+            bail!("the new schema lacks the {table:?} table");
+        };
+        let Some(old_table) = old_objects.iter().find(|o| o.name == table) else {
+            info!("creating the {table:?} table");
+            statements.push(new_table.sql.clone());
+            continue;
+        };
+
+        statements.extend(migrate_table(conn, table, &old_table.sql, &new_table.sql)?);
+    }
+
+    for (index, columns) in kept_indexes {
+        let table_sql = &new_objects
+            .iter()
+            .find(|o| o.name == index.tbl_name)
+            .expect("the core tables are part of the new schema")
+            .sql;
+        let (table_columns, _) = split_table_definition(table_sql)?;
+        let is_kept = |c: &Option<String>| {
+            c.as_ref().is_some_and(|c| {
+                table_columns.contains_key(c) || IMPORT_MANAGED_COLUMNS.contains(&c.as_str())
+            })
+        };
+        if columns.iter().all(is_kept) {
+            statements.push(index.sql.clone());
+        } else {
+            warn!(
+                "dropping the index {:?}, as the columns it covers are dropped",
+                index.name
+            );
+        }
+    }
+
+    //
+    // recreate the derived objects, including the content of auxiliary tables
+    //
+
+    for object in &new_derived {
+        statements.push(object.sql.clone());
+        if object.ty == "table" && !object.sql.starts_with("CREATE VIRTUAL TABLE") {
+            statements.extend(table_content(&new_conn, &object.name)?);
+        }
+    }
+
+    // relation names registered by past imports are unknown to the new schema
+    if new_derived_names.contains(RELATION_NAMES_TABLE) {
+        statements.push(format!(
+            r#"INSERT OR IGNORE INTO {}("name") SELECT DISTINCT "name" FROM {}"#,
+            escape_sql_ident(RELATION_NAMES_TABLE),
+            escape_sql_ident(&config.relations_table)
+        ));
+    }
+
+    if new_derived_names.contains(FTS_TABLE) {
+        let fts_table_escaped = escape_sql_ident(FTS_TABLE);
+        statements.push(format!(
+            "INSERT INTO {fts_table_escaped}({fts_table_escaped}) VALUES ('rebuild')"
+        ));
+    }
+
+    // an empty copy of the db, to try the migration and compute the fingerprint of the result
+    let scratch_conn = Connection::open_in_memory()?;
+    for object in &old_objects {
+        scratch_conn.execute_batch(&object.sql).wrap_err_with(|| {
+            format!("copying the schema of the {:?} {}", object.name, object.ty)
+        })?;
+    }
+    for statement in &statements {
+        scratch_conn
+            .execute_batch(statement)
+            .wrap_err_with(|| format!("trying the migration statement:\n{statement}"))?;
+    }
+    let fingerprint = schema_fingerprint(&scratch_conn)?;
+
+    Ok(Migration {
+        statements,
+        fingerprint,
+    })
+}
+
+/// Applies a migration to the db
+///
+/// The migration is atomic, i.e. if it fails, the db remains unchanged.
+pub(crate) fn apply_migration(conn: &mut Connection, migration: &Migration) -> Result<()> {
+    let now = std::time::Instant::now();
+
+    // foreign keys can not be toggled inside of a transaction
+    conn.pragma_update(None, "foreign_keys", "OFF")?;
+
+    let db_ta = conn.transaction()?;
+    for statement in &migration.statements {
+        trace!("executing the migration statement:\n{statement}");
+        db_ta.execute_batch(statement)?;
+    }
+
+    let violations: usize =
+        db_ta.query_row("SELECT COUNT(*) FROM pragma_foreign_key_check", (), |row| {
+            row.get(0)
+        })?;
+    if violations != 0 {
+        return Err(Failure::ForeignKeyViolation.report(format!(
+            "the migrated db violates {violations} foreign key constraints"
+        )));
+    }
+
+    set_schema_version(&db_ta, SCHEMA_VERSION)?;
+    stamp_schema(&db_ta, SchemaOrigin::Generated)?;
+    db_ta.commit()?;
+
+    conn.pragma_update(None, "foreign_keys", "ON")?;
+
+    info!("migration took {:?}", now.elapsed());
+    Ok(())
+}
+
+/// Lists all objects in the schema of a db, in the order of their creation
+///
+/// Internal objects (without SQL or maintained by SQLite, e.g. the statistics of `ANALYZE`) and
+/// the shadow tables of virtual tables are omitted.
+fn schema_objects(conn: &Connection) -> Result<Vec<SchemaObject>> {
+    let mut stmt = conn.prepare(
+        "SELECT type, name, tbl_name, sql FROM sqlite_schema WHERE sql IS NOT NULL AND name NOT LIKE 'sqlite\\_%' ESCAPE '\\' ORDER BY rowid",
+    )?;
+    let objects: Vec<SchemaObject> = stmt
+        .query_map((), |row| {
+            Ok(SchemaObject {
+                ty: row.get(0)?,
+                name: row.get(1)?,
+                tbl_name: row.get(2)?,
+                sql: row.get(3)?,
+            })
+        })?
+        .collect::<Result<_, _>>()?;
+
+    let virtual_tables: Vec<_> = objects
+        .iter()
+        .filter(|o| o.sql.starts_with("CREATE VIRTUAL TABLE"))
+        .map(|o| format!("{}_", o.name))
+        .collect();
+
+    Ok(objects
+        .into_iter()
+        .filter(|o| !virtual_tables.iter().any(|vt| o.name.starts_with(vt)))
+        .collect())
+}
+
+/// The columns covered by an index, `None` for expressions
+fn index_columns(conn: &Connection, index: &str) -> Result<Vec<Option<String>>> {
+    let mut stmt = conn.prepare("SELECT name FROM pragma_index_info(?1) ORDER BY seqno")?;
+    let columns = stmt
+        .query_map([index], |row| row.get(0))?
+        .collect::<Result<_, _>>()?;
+    Ok(columns)
+}
+
+/// Generates the statements to migrate a single table from its old to its new definition
+///
+/// Fails if the new definition adds a `NOT NULL` column without default, or makes a column
+/// `NOT NULL` without default, while rows of the table lack a value for it.
+fn migrate_table(
+    conn: &Connection,
+    table: &str,
+    old_sql: &str,
+    new_sql: &str,
+) -> Result<Vec<String>> {
+    let (old_columns, old_constraints) = split_table_definition(old_sql)?;
+    let (new_columns, new_constraints) = split_table_definition(new_sql)?;
+
+    // the columns managed by the import are added to the rebuilt table separately
+    let import_managed: Vec<_> = old_columns
+        .iter()
+        .filter(|(c, _)| {
+            IMPORT_MANAGED_COLUMNS.contains(&c.as_str()) && !new_columns.contains_key(*c)
+        })
+        .collect();
+
+    let dropped: Vec<_> = old_columns
+        .keys()
+        .filter(|c| !new_columns.contains_key(*c) && !IMPORT_MANAGED_COLUMNS.contains(&c.as_str()))
+        .collect();
+    let changed: Vec<_> = old_columns
+        .iter()
+        .filter(|(c, def)| new_columns.get(*c).is_some_and(|new_def| new_def != *def))
+        .map(|(c, _)| c)
+        .collect();
+    let added: Vec<_> = new_columns
+        .iter()
+        .filter(|(c, _)| !old_columns.contains_key(*c))
+        .collect();
+
+    if dropped.is_empty() && changed.is_empty() && added.is_empty() {
+        if old_constraints == new_constraints {
+            debug!("the {table:?} table is unchanged");
+            return Ok(Vec::new());
+        }
+    } else {
+        info!(
+            "the {table:?} table gains {} columns, {} columns change and {} columns are dropped",
+            added.len(),
+            changed.len(),
+            dropped.len()
+        );
+    }
+
+    if !dropped.is_empty() {
+        warn!("the data of the columns {dropped:?} in the {table:?} table will be lost");
+    }
+
+    let table_escaped = escape_sql_ident(table);
+
+    // rows lacking a value for a column which is newly NOT NULL can not be migrated
+    for (column, def) in &new_columns {
+        let def_upper = def.to_uppercase();
+        if !def_upper.contains("NOT NULL") || def_upper.contains(" DEFAULT ") {
+            continue;
+        }
+        let condition = match old_columns.get(column) {
+            Some(old_def) if old_def.to_uppercase().contains("NOT NULL") => continue,
+            Some(_) => format!(" WHERE {} IS NULL", escape_sql_ident(column)),
+            None => String::new(),
+        };
+        let rows: usize = conn.query_row(
+            &format!("SELECT COUNT(*) FROM {table_escaped}{condition}"),
+            (),
+            |row| row.get(0),
+        )?;
+        if rows != 0 {
+            bail!(
+                "the new schema requires a value for the column {column:?} of the {table:?} table, which {rows} rows lack; the column needs a default value in the new schema, or the rows need values before the migration"
+            );
+        }
+    }
+
+    // columns which are only added can be appended in place
+    let addable = |def: &str| {
+        let def = def.to_uppercase();
+        !def.contains("PRIMARY KEY") && !def.contains("UNIQUE") && !def.contains("NOT NULL")
+    };
+    if dropped.is_empty()
+        && changed.is_empty()
+        && old_constraints == new_constraints
+        && added.iter().all(|(_, def)| addable(def))
+    {
+        debug!("adding the new columns to the {table:?} table in place");
+        return Ok(added
+            .into_iter()
+            .map(|(_, def)| format!("ALTER TABLE {table_escaped} ADD COLUMN {def}"))
+            .collect());
+    }
+
+    debug!("rebuilding the {table:?} table");
+    let new_table = format!("{table}.new");
+    let new_table_escaped = escape_sql_ident(&new_table);
+    let create_prefix = format!("CREATE TABLE {table_escaped}");
+    let Some(definition) = new_sql.strip_prefix(&create_prefix) else {
+        bail!("unable to rename the new definition of the {table:?} table:\n{new_sql}");
+    };
+
+    let common_columns = new_columns
+        .keys()
+        .chain(import_managed.iter().map(|(c, _)| *c))
+        .filter(|c| old_columns.contains_key(*c))
+        .map(escape_sql_ident)
+        .collect::<Vec<_>>()
+        .join(", ");
+
+    let mut statements = vec![format!("CREATE TABLE {new_table_escaped}{definition}")];
+    statements.extend(
+        import_managed
+            .iter()
+            .map(|(_, def)| format!("ALTER TABLE {new_table_escaped} ADD COLUMN {def}")),
+    );
+    statements.extend([
+        format!(
+            "INSERT INTO {new_table_escaped} ({common_columns}) SELECT {common_columns} FROM {table_escaped}"
+        ),
+        format!("DROP TABLE {table_escaped}"),
+        format!("ALTER TABLE {new_table_escaped} RENAME TO {table_escaped}"),
+    ]);
+    Ok(statements)
+}
+
+/// Splits the definition of a table into its column definitions and its table constraints
+///
+/// Column definitions are keyed by the column name. Whitespace is normalized, so that definitions
+/// can be compared regardless of their formatting.
+pub(crate) fn split_table_definition(
+    sql: &str,
+) -> Result<(BTreeMap<String, String>, BTreeSet<String>)> {
+    let (Some(start), Some(end)) = (sql.find('('), sql.rfind(')')) else {
+        bail!("unable to parse the table definition:\n{sql}");
+    };
+    let parts = split_top_level(&sql[start + 1..end]);
+
+    let mut columns = BTreeMap::new();
+    let mut constraints = BTreeSet::new();
+    for part in parts {
+        let part = part.split_whitespace().collect::<Vec<_>>().join(" ");
+        if part.is_empty() {
+            continue;
+        }
+
+        let keyword = part.split(['(', ' ']).next().unwrap_or_default();
+        let is_constraint = ["CONSTRAINT", "PRIMARY", "UNIQUE", "CHECK", "FOREIGN"]
+            .contains(&keyword.to_uppercase().as_str());
+        if is_constraint {
+            constraints.insert(part);
+        } else {
+            let name = match part.strip_prefix('"') {
+                Some(rest) => rest.split('"').next().unwrap_or_default().to_owned(),
+                None => keyword.to_owned(),
+            };
+            columns.insert(name, part);
+        }
+    }
+
+    Ok((columns, constraints))
+}
+
+/// Splits a list of definitions at the top-level commas, which are neither nested in parentheses
+/// nor quoted
+///
+/// The parts keep their surrounding whitespace.
+pub(crate) fn split_top_level(list: &str) -> Vec<&str> {
+    let mut parts = Vec::new();
+    let mut depth = 0;
+    let mut quote = None;
+    let mut part_start = 0;
+    for (idx, c) in list.char_indices() {
+        match (quote, c) {
+            (Some(q), c) if c == q => quote = None,
+            (Some(_), _) => {}
+            (None, '"' | '\'' | '`') => quote = Some(c),
+            (None, '(') => depth += 1,
+            (None, ')') => depth -= 1,
+            (None, ',') if depth == 0 => {
+                parts.push(&list[part_start..idx]);
+                part_start = idx + 1;
+            }
+            _ => {}
+        }
+    }
+    parts.push(&list[part_start..]);
+    parts
+}
+
+/// Generates the statements to insert the content of a table
+fn table_content(conn: &Connection, table: &str) -> Result<Vec<String>> {
+    let table_escaped = escape_sql_ident(table);
+    let mut stmt = conn.prepare(&format!("SELECT * FROM {table_escaped}"))?;
+    let column_count = stmt.column_count();
+
+    let mut rows = stmt.query(())?;
+    let mut values = Vec::new();
+    while let Some(row) = rows.next()? {
+        let mut literals = Vec::with_capacity(column_count);
+        for idx in 0..column_count {
+            literals.push(match row.get_ref(idx)? {
+                ValueRef::Null => "NULL".to_owned(),
+                ValueRef::Integer(i) => i.to_string(),
+                ValueRef::Real(r) => r.to_string(),
+                ValueRef::Text(t) => escape_sql_str_lit(String::from_utf8_lossy(t)),
+                ValueRef::Blob(b) => {
+                    format!(
+                        "X'{}'",
+                        b.iter().map(|b| format!("{b:02x}")).collect::<String>()
+                    )
+                }
+            });
+        }
+        values.push(format!("({})", literals.join(", ")));
+    }
+
+    if values.is_empty() {
+        return Ok(Vec::new());
+    }
+    Ok(vec![format!(
+        "INSERT INTO {table_escaped} VALUES {}",
+        values.join(", ")
+    )])
+}
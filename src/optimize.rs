@@ -0,0 +1,225 @@
+//! Index advisor
+//!
+//! The indices of the embedded schema are a compromise for typical models. This module compares the
+//! indices of a db with a catalogue of the query shapes used by this tool and common analytical
+//! queries, and uses the statistics gathered by `ANALYZE` to spot indices which hardly narrow down
+//! any lookup.
+
+use std::io::Write;
+
+use eyre::Result;
+use rusqlite::Connection;
+
+use crate::{
+    config::{ELEMENT_PK_COL, TYPE_COL, config},
+    util::escape_sql_ident,
+};
+
+/// A query shape which benefits from an index
+struct QueryShape {
+    /// Table the query looks up rows in
+    table: String,
+
+    /// Columns the query constrains by equality, in the order they should be indexed
+    columns: &'static [&'static str],
+
+    /// Human readable description of the queries of this shape
+    description: &'static str,
+}
+
+/// The query shapes the advisor knows about
+fn query_shapes() -> Vec<QueryShape> {
+    let config = config();
+    vec![
+        QueryShape {
+            table: config.elements_table.clone(),
+            columns: &[TYPE_COL],
+            description: "filtering elements by type (search, tree, views)",
+        },
+        QueryShape {
+            table: config.elements_table.clone(),
+            columns: &["qualifiedName"],
+            description: "resolving qualified names (show, trace, path, impact)",
+        },
+        QueryShape {
+            table: config.relations_table.clone(),
+            columns: &["origin_id"],
+            description: "following relations (show, tree, path, impact)",
+        },
+        QueryShape {
+            table: config.relations_table.clone(),
+            columns: &["target_id"],
+            description: "following relations backwards (show, path)",
+        },
+        QueryShape {
+            table: config.relations_table.clone(),
+            columns: &["name", "target_id"],
+            description: "following relations of one name backwards (tree, trace, impact --reverse)",
+        },
+        QueryShape {
+            table: config.extended_table.clone(),
+            columns: &[ELEMENT_PK_COL],
+            description: "looking up the extended properties of an element (show, import)",
+        },
+    ]
+}
+
+/// An index of the db, together with its statistics
+struct Index {
+    /// Name of the index
+    name: String,
+
+    /// Table the index belongs to
+    table: String,
+
+    /// Indexed columns, in order
+    columns: Vec<String>,
+
+    /// Whether the index backs a `PRIMARY KEY` or `UNIQUE` constraint, and thus can not be dropped
+    backs_constraint: bool,
+
+    /// Number of rows and average number of rows per distinct value of the first column, as
+    /// recorded in `sqlite_stat1`
+    stat: Option<(u64, u64)>,
+}
+
+/// Analyze the db, report missing and unused indices, and optionally create the missing ones
+///
+/// An index is missing if no index of the db starts with the columns of a known query shape. An
+/// index is reported as unused if the statistics show that it hardly narrows down lookups, or if
+/// its columns are a prefix of those of another index. Unused indices are only reported, never
+/// dropped. With `apply`, the missing indices are created, and the db is analyzed again.
+pub(crate) fn optimize<W: Write>(conn: &Connection, apply: bool, out: &mut W) -> Result<()> {
+    info!("analyzing the db");
+    conn.execute_batch("ANALYZE")?;
+
+    let config = config();
+    let tables = [
+        &config.elements_table,
+        &config.relations_table,
+        &config.extended_table,
+    ];
+    let mut indices = Vec::new();
+    for table in tables {
+        indices.append(&mut table_indices(conn, table)?);
+    }
+
+    let mut missing = Vec::new();
+    for shape in query_shapes() {
+        let covered = indices.iter().any(|index| {
+            index.table == shape.table
+                && index.columns.len() >= shape.columns.len()
+                && index.columns.iter().zip(shape.columns).all(|(a, b)| a == b)
+        });
+        if covered {
+            continue;
+        }
+
+        let index_name = format!("{}.{}", shape.table, shape.columns.join(","));
+        let sql = format!(
+            "CREATE INDEX {} ON {} ({});",
+            escape_sql_ident(&index_name),
+            escape_sql_ident(&shape.table),
+            shape
+                .columns
+                .iter()
+                .map(escape_sql_ident)
+                .collect::<Vec<_>>()
+                .join(", ")
+        );
+        writeln!(out, "missing\t{index_name}\t{}", shape.description)?;
+        missing.push(sql);
+    }
+
+    for index in &indices {
+        if index.backs_constraint {
+            continue;
+        }
+
+        if let Some(other) = indices.iter().find(|other| {
+            other.name != index.name
+                && other.table == index.table
+                && other.columns.len() > index.columns.len()
+                && other.columns.starts_with(&index.columns)
+        }) {
+            writeln!(
+                out,
+                "unused\t{}\tredundant, the index {:?} starts with the same columns",
+                index.name, other.name
+            )?;
+            continue;
+        }
+
+        match index.stat {
+            // an index which selects at least half of the rows on average is ignored by the query
+            // planner in favor of a full table scan
+            Some((rows, rows_per_value)) if rows > 0 && rows_per_value * 2 >= rows => writeln!(
+                out,
+                "unused\t{}\thardly selective, each value matches {rows_per_value} of {rows} rows on average",
+                index.name
+            )?,
+            _ => {}
+        }
+    }
+
+    if apply && !missing.is_empty() {
+        info!("creating {} missing indices", missing.len());
+        let sql = missing.join("\n");
+        debug!("executing the following SQL:\n{sql}");
+        conn.execute_batch(&format!("BEGIN;\n{sql}\nCOMMIT;\nANALYZE;"))?;
+    }
+
+    Ok(())
+}
+
+/// Collects the indices of a table, together with their statistics
+fn table_indices(conn: &Connection, table: &str) -> Result<Vec<Index>> {
+    let mut stmt = conn.prepare(
+        r#"SELECT l."name", l."origin" IN ('pk', 'u'), s."stat"
+        FROM pragma_index_list(?1) AS l
+        LEFT JOIN "sqlite_stat1" AS s ON s."tbl" = ?1 AND s."idx" = l."name""#,
+    )?;
+    let rows = stmt
+        .query_map([table], |row| {
+            Ok((
+                row.get::<_, String>(0)?,
+                row.get::<_, bool>(1)?,
+                row.get::<_, Option<String>>(2)?,
+            ))
+        })?
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let mut column_stmt =
+        conn.prepare(r#"SELECT "name" FROM pragma_index_info(?1) ORDER BY "seqno""#)?;
+
+    let mut indices = Vec::new();
+    for (name, backs_constraint, maybe_stat) in rows {
+        let columns = column_stmt
+            .query_map([&name], |row| row.get::<_, Option<String>>(0))?
+            .collect::<Result<Vec<_>, _>>()?
+            .into_iter()
+            // expressions are indexed without column name
+            .map(Option::unwrap_or_default)
+            .collect();
+
+        // the stat consists of the number of rows, followed by the average number of rows per
+        // distinct value of each prefix of the indexed columns
+        let stat = maybe_stat.and_then(|stat| {
+            let mut numbers = stat.split_whitespace().map(str::parse);
+            match (numbers.next(), numbers.next()) {
+                (Some(Ok(rows)), Some(Ok(rows_per_value))) => Some((rows, rows_per_value)),
+                _ => None,
+            }
+        });
+
+        indices.push(Index {
+            name,
+            table: table.to_owned(),
+            columns,
+            backs_constraint,
+            stat,
+        });
+    }
+
+    Ok(indices)
+}
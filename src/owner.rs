@@ -0,0 +1,107 @@
+//! Denormalized owner of each element, stored in an optional column of the elements table
+//!
+//! Ownership is by far the most queried relation. Storing the owner of each element in an indexed
+//! column turns the children of an element into a plain index lookup and saves a join over the
+//! relations table per level of a tree query. The column is derived from the relations, see
+//! [`OWNER_RELATIONS`]. Once added, each import and edit keeps it up to date.
+
+use eyre::{Result, bail};
+use rusqlite::Connection;
+
+use crate::{
+    config::{ELEMENT_PK_COL, OWNER_COL, OWNER_COL_DEF, OWNER_RELATIONS, PROJECT_COL, config},
+    import::get_table_columns,
+    util::{escape_sql_ident, escape_sql_str_lit},
+};
+
+/// Adds the owner column to the elements table and populates it, unless it exists already
+///
+/// Dbs storing multiple projects are not supported.
+pub(crate) fn add_owner_column(conn: &Connection) -> Result<()> {
+    let columns = get_table_columns(conn, &config().elements_table)?;
+    if columns.iter().any(|(n, _)| n == OWNER_COL) {
+        return Ok(());
+    }
+    if columns.iter().any(|(n, _)| n == PROJECT_COL) {
+        bail!("the owner column is not supported for dbs storing multiple projects");
+    }
+
+    info!("maintaining the owner of each element in the {OWNER_COL:?} column from now on");
+    let elements_table = &config().elements_table;
+    let elements_table_escaped = escape_sql_ident(elements_table);
+    let owner_col = escape_sql_ident(OWNER_COL);
+    // the owner refers to the elements without a foreign key, as it is only consistent again once
+    // updated after a change
+    conn.execute_batch(&format!(
+        r#"
+        ALTER TABLE {elements_table_escaped} ADD COLUMN {OWNER_COL_DEF};
+
+        CREATE INDEX {} ON {elements_table_escaped}({owner_col});
+        "#,
+        escape_sql_ident(format!("{elements_table}.{OWNER_COL}"))
+    ))?;
+
+    update_owner_column(conn, None)
+}
+
+/// Updates the owner column, if any, after the relations changed
+///
+/// `maybe_changed_elements` is a query selecting the ids of all elements whose relations were
+/// inserted, replaced or deleted, or which were deleted themselves. Only the owner of these and of
+/// the elements they owned is updated. Without the query, the owner of all elements is updated.
+pub(crate) fn update_owner_column(
+    conn: &Connection,
+    maybe_changed_elements: Option<&str>,
+) -> Result<()> {
+    let has_owner_column = get_table_columns(conn, &config().elements_table)?
+        .iter()
+        .any(|(n, _)| n == OWNER_COL);
+    if !has_owner_column {
+        return Ok(());
+    }
+
+    let now = std::time::Instant::now();
+    let elements_table = escape_sql_ident(&config().elements_table);
+    let pk_col = escape_sql_ident(ELEMENT_PK_COL);
+    let owner_col = escape_sql_ident(OWNER_COL);
+
+    let owner_expr = owner_expr("e");
+    let condition = match maybe_changed_elements {
+        Some(changed_elements) => format!(
+            " WHERE e.{pk_col} IN ({changed_elements}) OR e.{owner_col} IN ({changed_elements})"
+        ),
+        None => String::new(),
+    };
+
+    debug!("updating the owner column");
+    let updated = conn.execute(
+        &format!("UPDATE {elements_table} AS e SET {owner_col} = {owner_expr}{condition}"),
+        (),
+    )?;
+    debug!(
+        "updating the owner of {updated} elements took {:?}",
+        now.elapsed()
+    );
+
+    Ok(())
+}
+
+/// SQL expression evaluating to the id of the owner of the element of the elements table aliased
+/// as `alias`, or `NULL` for unowned elements
+pub(crate) fn owner_expr(alias: &str) -> String {
+    let relations_table = escape_sql_ident(&config().relations_table);
+    let pk_col = escape_sql_ident(ELEMENT_PK_COL);
+
+    let owner_exprs = OWNER_RELATIONS
+        .iter()
+        .map(|name| {
+            format!(
+                r#"(SELECT r."target_id" FROM {relations_table} AS r
+                WHERE r."name" = {} AND r."origin_id" = {alias}.{pk_col})"#,
+                escape_sql_str_lit(name)
+            )
+        })
+        .collect::<Vec<_>>()
+        .join(", ");
+    format!("coalesce({owner_exprs})")
+}
@@ -0,0 +1,166 @@
+//! Filter importing only the elements within a namespace, to build small dbs from huge dumps
+//!
+//! An element is within the namespace `Vehicle::Powertrain` if its qualified name is
+//! `Vehicle::Powertrain` or starts with `Vehicle::Powertrain::`, or if it is owned, directly or
+//! indirectly, by such an element. The latter covers the elements without qualified name, e.g.
+//! memberships. The elements outside of the namespace which are referred to from within are
+//! imported as stubs, carrying only their id, type and names, so that the relations remain intact.
+//!
+//! The filter is a transform, see [`crate::transform`], built by scanning the elements twice ahead
+//! of the import: once to find the elements within the namespace and once to find the elements
+//! they refer to. Only the ids of the elements are kept in memory.
+//!
+//! The import is logged as partial, see [`crate::import::ImportProvenance::partial`], as pushing
+//! from it would delete the elements outside of the namespace on the server.
+
+use std::{
+    collections::{HashMap, HashSet},
+    sync::Arc,
+};
+
+use eyre::{Result, bail};
+use serde_json::{Map, Value};
+
+use crate::{
+    config::{ELEMENT_PK_COL, OWNER_RELATIONS, TYPE_COL},
+    import::{Element, is_relation_object},
+    transform::{ElementTransform, Transform},
+};
+
+/// Separator of the segments of qualified names
+const NAMESPACE_SEPARATOR: &str = "::";
+
+/// Attributes kept by the stubs of the elements outside of the namespace, besides the id
+const STUB_ATTRS: &[&str] = &[
+    TYPE_COL,
+    "declaredName",
+    "declaredShortName",
+    "name",
+    "shortName",
+    "qualifiedName",
+];
+
+/// The filter keeping the elements within the namespace `package`, given by its qualified name
+///
+/// Fails if no element is within the namespace.
+pub(crate) fn package_filter<E: Send + Sync + std::error::Error + 'static>(
+    elements: impl Clone + Iterator<Item = Result<Element, E>>,
+    package: &str,
+) -> Result<Transform> {
+    info!("scanning the elements for those within the namespace {package:?}");
+    let prefix = format!("{package}{NAMESPACE_SEPARATOR}");
+    let mut within = HashSet::new();
+    let mut owners = HashMap::new();
+    for element in elements.clone() {
+        let element = element?;
+        let is_within = match element.rest.get("qualifiedName") {
+            Some(Value::String(name)) => name == package || name.starts_with(&prefix),
+            _ => false,
+        };
+        if is_within {
+            within.insert(element.id);
+        } else if let Some(owner_id) = owner_id(&element.rest) {
+            owners.insert(element.id, owner_id.to_owned());
+        }
+    }
+    if within.is_empty() {
+        bail!("no element has the qualified name {package:?} or one within it");
+    }
+
+    // the owned elements are within the namespace of their owner
+    for id in owners.keys() {
+        let mut chain = Vec::new();
+        let mut current = id;
+        let is_within = loop {
+            if within.contains(current) {
+                break true;
+            }
+            // elements owning themselves via a cycle are not within the namespace
+            if chain.len() > owners.len() {
+                break false;
+            }
+            chain.push(current);
+            match owners.get(current) {
+                Some(owner_id) => current = owner_id,
+                None => break false,
+            }
+        };
+        if is_within {
+            within.extend(chain.into_iter().cloned());
+        }
+    }
+
+    let mut stubs = HashSet::new();
+    for element in elements {
+        let element = element?;
+        if !within.contains(&element.id) {
+            continue;
+        }
+        for value in element.rest.values() {
+            for target_id in referenced_ids(value) {
+                if !within.contains(target_id) {
+                    stubs.insert(target_id.to_owned());
+                }
+            }
+        }
+    }
+    info!(
+        "importing {} elements within the namespace {package:?} and {} stubs of elements referred to from within",
+        within.len(),
+        stubs.len()
+    );
+
+    Ok(Transform::new(Arc::new(PackageFilter { within, stubs }))
+        .described(format!("only the namespace {package}")))
+}
+
+/// The id of the owner of an element, if any
+fn owner_id(attributes: &Map<String, Value>) -> Option<&str> {
+    OWNER_RELATIONS
+        .iter()
+        .find_map(|relation| match attributes.get(*relation) {
+            Some(Value::Object(o)) if is_relation_object(o) => o[ELEMENT_PK_COL].as_str(),
+            _ => None,
+        })
+}
+
+/// The ids of the elements referred to by the value of an attribute
+fn referenced_ids(value: &Value) -> Vec<&str> {
+    match value {
+        Value::Object(o) if is_relation_object(o) => {
+            o[ELEMENT_PK_COL].as_str().into_iter().collect()
+        }
+        Value::Array(values) => values.iter().flat_map(referenced_ids).collect(),
+        _ => Vec::new(),
+    }
+}
+
+/// Keeps the elements within a namespace and stubs of those referred to, see [`package_filter`]
+struct PackageFilter {
+    /// Ids of the elements within the namespace
+    within: HashSet<String>,
+
+    /// Ids of the elements outside of the namespace which are referred to from within
+    stubs: HashSet<String>,
+}
+
+impl ElementTransform for PackageFilter {
+    fn transform(&self, element: Value) -> Result<Option<Value>> {
+        let Value::Object(mut attributes) = element else {
+            return Ok(Some(element));
+        };
+        let Some(id) = attributes.get(ELEMENT_PK_COL).and_then(Value::as_str) else {
+            return Ok(Some(Value::Object(attributes)));
+        };
+
+        if self.within.contains(id) {
+            Ok(Some(Value::Object(attributes)))
+        } else if self.stubs.contains(id) {
+            attributes
+                .retain(|name, _| name == ELEMENT_PK_COL || STUB_ATTRS.contains(&name.as_str()));
+            Ok(Some(Value::Object(attributes)))
+        } else {
+            Ok(None)
+        }
+    }
+}
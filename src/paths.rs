@@ -0,0 +1,97 @@
+//! Materialized ownership path of each element
+//!
+//! The paths table maps each element to the ids of its owners, from the root of the ownership tree
+//! down to the element itself, joined by [`PATH_SEPARATOR`], and to its depth in the tree. This
+//! turns "all elements under an element" into a range query over the indexed paths instead of a
+//! recursive join, `0` being the character following `/`:
+//!
+//! ```sql
+//! SELECT p."@id" FROM "element_paths" AS p, "element_paths" AS x
+//! WHERE x."@id" = 'some-id' AND p."path" > x."path" || '/' AND p."path" < x."path" || '0'
+//! ```
+//!
+//! Elements in an ownership cycle have no path. Once created, each import and edit rebuilds the
+//! table.
+
+use eyre::{Result, bail};
+use rusqlite::Connection;
+
+use crate::{
+    config::{ELEMENT_PATHS_TABLE, ELEMENT_PK_COL, PATH_SEPARATOR, PROJECT_COL, config},
+    import::get_table_columns,
+    util::{escape_sql_ident, escape_sql_str_lit, table_exists},
+};
+
+/// Creates the paths table and populates it, unless it exists already
+///
+/// Dbs storing multiple projects are not supported.
+pub(crate) fn add_element_paths(conn: &Connection) -> Result<()> {
+    if table_exists(conn, ELEMENT_PATHS_TABLE)? {
+        return Ok(());
+    }
+    let is_multi_project = get_table_columns(conn, &config().elements_table)?
+        .iter()
+        .any(|(n, _)| n == PROJECT_COL);
+    if is_multi_project {
+        bail!("the paths of elements are not supported for dbs storing multiple projects");
+    }
+
+    info!("maintaining the ownership path of each element from now on");
+    let paths_table = escape_sql_ident(ELEMENT_PATHS_TABLE);
+    let pk_col = escape_sql_ident(ELEMENT_PK_COL);
+    conn.execute_batch(&format!(
+        r#"
+        CREATE TABLE {paths_table} (
+            {pk_col} TEXT PRIMARY KEY,
+            "path" TEXT NOT NULL,
+            "depth" INTEGER NOT NULL
+        ) STRICT, WITHOUT ROWID;
+
+        CREATE INDEX "{ELEMENT_PATHS_TABLE}.path" ON {paths_table}("path");
+        "#
+    ))?;
+
+    update_element_paths(conn)
+}
+
+/// Rebuilds the paths table, if any, after the relations changed
+///
+/// The table is rebuilt from scratch, as moving an element changes the paths of everything it
+/// owns, directly or indirectly.
+pub(crate) fn update_element_paths(conn: &Connection) -> Result<()> {
+    if !table_exists(conn, ELEMENT_PATHS_TABLE)? {
+        return Ok(());
+    }
+
+    let now = std::time::Instant::now();
+    let paths_table = escape_sql_ident(ELEMENT_PATHS_TABLE);
+    let elements_table = escape_sql_ident(&config().elements_table);
+    let pk_col = escape_sql_ident(ELEMENT_PK_COL);
+    let separator = escape_sql_str_lit(PATH_SEPARATOR);
+    let owner_expr = crate::owner::owner_expr("e");
+
+    debug!("updating the paths of elements");
+    // each element has at most one owner, hence starting from the unowned elements the traversal
+    // never reaches a cycle and terminates
+    conn.execute_batch(&format!(
+        r#"
+        CREATE TEMPORARY TABLE "path_owners"("id" TEXT PRIMARY KEY, "owner_id" TEXT);
+        INSERT INTO "path_owners" SELECT e.{pk_col}, {owner_expr} FROM {elements_table} AS e;
+        CREATE INDEX temp."path_owners.owner_id" ON "path_owners"("owner_id");
+
+        DELETE FROM {paths_table};
+        WITH RECURSIVE "paths"("id", "path", "depth") AS (
+            SELECT "id", "id", 0 FROM "path_owners" WHERE "owner_id" IS NULL
+            UNION ALL
+            SELECT o."id", p."path" || {separator} || o."id", p."depth" + 1 FROM "paths" AS p
+            JOIN "path_owners" AS o ON o."owner_id" = p."id"
+        )
+        INSERT INTO {paths_table}({pk_col}, "path", "depth") SELECT * FROM "paths";
+
+        DROP TABLE "path_owners";
+        "#
+    ))?;
+    debug!("updating the paths of elements took {:?}", now.elapsed());
+
+    Ok(())
+}
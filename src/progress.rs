@@ -0,0 +1,84 @@
+//! Progress reports for library users, to drive their own progress UIs
+//!
+//! The CLI logs the progress of long running operations. Library users instead implement
+//! [`ProgressSink`], which is passed along with the options of these operations.
+
+use std::{fmt::Debug, ops::Deref, sync::Arc};
+
+/// Phases of fetching and importing a model
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum Phase {
+    /// Fetching the elements from the API server
+    Fetching,
+
+    /// Inserting the elements into the elements table
+    InsertingElements,
+
+    /// Inserting the relations and extended properties of the elements
+    InsertingRelations,
+
+    /// Updating the tables derived from the relations, e.g. the closures
+    UpdatingDerivedTables,
+
+    /// Committing the import to the db
+    Committing,
+}
+
+/// Receiver of progress reports
+///
+/// All methods do nothing by default, so that implementors pick the reports they care about. The
+/// methods are called from the threads doing the work, often once per element, hence they should
+/// return quickly.
+pub trait ProgressSink: Send + Sync {
+    /// A new phase started
+    fn phase(&self, _phase: Phase) {}
+
+    /// The number of pages fetched from the API server so far
+    fn pages_fetched(&self, _pages: usize) {}
+
+    /// The number of elements parsed in the current phase so far, from the fetched pages or the
+    /// imported file
+    fn elements_parsed(&self, _elements: usize) {}
+
+    /// The number of rows inserted in the current phase so far
+    fn rows_inserted(&self, _rows: usize) {}
+}
+
+/// The sink of progress reports passed along with the options of an operation
+///
+/// Defaults to discarding all reports.
+#[derive(Clone)]
+pub(crate) struct Progress(Arc<dyn ProgressSink>);
+
+impl Progress {
+    /// Report the progress to `sink`
+    pub(crate) fn new(sink: Arc<dyn ProgressSink>) -> Self {
+        Self(sink)
+    }
+}
+
+/// A sink discarding all reports
+struct NoProgress;
+
+impl ProgressSink for NoProgress {}
+
+impl Default for Progress {
+    fn default() -> Self {
+        Self(Arc::new(NoProgress))
+    }
+}
+
+impl Debug for Progress {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("Progress")
+    }
+}
+
+impl Deref for Progress {
+    type Target = dyn ProgressSink;
+
+    fn deref(&self) -> &Self::Target {
+        self.0.as_ref()
+    }
+}
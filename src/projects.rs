@@ -0,0 +1,136 @@
+//! Support for dbs storing multiple projects
+//!
+//! Such dbs are generated with `--multi-project`, which adds a project column to the elements,
+//! relations and extended properties tables. Each import stores its elements under one project.
+//!
+//! To spare every query from filtering by project, a project is selected by shadowing the core
+//! tables with temporary views, which only contain the rows of the selected project. SQLite resolves
+//! unqualified table names in the temporary schema first, hence all queries of this tool (and all
+//! user-supplied queries, such as validation rules) transparently operate on the selected project.
+
+use eyre::{Result, bail};
+use rusqlite::Connection;
+
+use crate::{
+    config::{ELEMENT_PK_COL, FTS_TABLE, PROJECT_COL, TARGET_PROJECT_COL, config},
+    util::{escape_sql_ident, escape_sql_str_lit, table_exists},
+};
+
+/// Checks whether the db stores multiple projects
+pub(crate) fn is_multi_project(conn: &Connection) -> Result<bool> {
+    let has_project_column = conn
+        .prepare("SELECT 1 FROM pragma_table_info(?1) WHERE name = ?2")?
+        .exists((&config().elements_table, PROJECT_COL))?;
+    Ok(has_project_column)
+}
+
+/// Lists the projects stored in the db, together with their number of elements
+pub(crate) fn list_projects(conn: &Connection) -> Result<Vec<(String, u64)>> {
+    let mut stmt = conn.prepare(&format!(
+        "SELECT {project_col}, COUNT(*) FROM main.{} GROUP BY {project_col} ORDER BY {project_col}",
+        escape_sql_ident(&config().elements_table),
+        project_col = escape_sql_ident(PROJECT_COL)
+    ))?;
+    let projects = stmt
+        .query_map((), |row| Ok((row.get(0)?, row.get(1)?)))?
+        .collect::<Result<_, _>>()?;
+    Ok(projects)
+}
+
+/// Restricts the connection to the rows of one project
+///
+/// Replaces an earlier selection. Does nothing but warn if the db stores only a single project.
+pub(crate) fn select_project(conn: &Connection, project_id: &str) -> Result<()> {
+    if !is_multi_project(conn)? {
+        warn!("the db stores a single project only, ignoring the selected project {project_id:?}");
+        return Ok(());
+    }
+
+    let projects = list_projects(conn)?;
+    if !projects.iter().any(|(p, _)| p == project_id) {
+        let known: Vec<_> = projects.iter().map(|(p, _)| p.as_str()).collect();
+        bail!("the db contains no project {project_id:?}, only the projects {known:?}");
+    }
+
+    info!("selecting the project {project_id:?}");
+    let config = config();
+    let project_condition = format!(
+        "{} = {}",
+        escape_sql_ident(PROJECT_COL),
+        escape_sql_str_lit(project_id)
+    );
+    for table in [
+        &config.elements_table,
+        &config.relations_table,
+        &config.extended_table,
+    ] {
+        let table_escaped = escape_sql_ident(table);
+
+        // the full-text index refers to the elements by their rowid, which is appended last to keep
+        // the positions of the columns of the table
+        let columns = if table == &config.elements_table && table_exists(conn, FTS_TABLE)? {
+            "*, rowid AS rowid"
+        } else {
+            "*"
+        };
+        conn.execute_batch(&format!(
+            "DROP VIEW IF EXISTS temp.{table_escaped};
+            CREATE TEMPORARY VIEW {table_escaped} AS SELECT {columns} FROM main.{table_escaped} WHERE {project_condition};"
+        ))?;
+    }
+
+    Ok(())
+}
+
+/// Lifts the restriction of the connection to one project, see [`select_project`]
+#[cfg(feature = "fetch")]
+pub(crate) fn deselect_project(conn: &Connection) -> Result<()> {
+    let config = config();
+    for table in [
+        &config.elements_table,
+        &config.relations_table,
+        &config.extended_table,
+    ] {
+        conn.execute_batch(&format!(
+            "DROP VIEW IF EXISTS temp.{};",
+            escape_sql_ident(table)
+        ))?;
+    }
+    Ok(())
+}
+
+/// Lets relations point to elements of other projects, if their target is not in the same project
+///
+/// The importer assumes the target of each relation to be in the same project as its origin. For
+/// relations whose target is not found there, but in another project (e.g. a library project
+/// imported earlier), the project of the target is corrected. If several other projects contain the
+/// target, the first one in order of their ids is chosen. Relations whose target is in no project
+/// are left alone, and thus violate the foreign key constraint on commit.
+pub(crate) fn resolve_cross_project_targets(conn: &Connection) -> Result<usize> {
+    let config = config();
+    let elements_table = escape_sql_ident(&config.elements_table);
+    let relations_table = escape_sql_ident(&config.relations_table);
+    let project_col = escape_sql_ident(PROJECT_COL);
+    let target_project_col = escape_sql_ident(TARGET_PROJECT_COL);
+    let pk_col = escape_sql_ident(ELEMENT_PK_COL);
+
+    let resolved = conn.execute(
+        &format!(
+            r#"UPDATE main.{relations_table} AS r SET {target_project_col} = (
+                SELECT e.{project_col} FROM main.{elements_table} AS e
+                WHERE e.{pk_col} = r."target_id" ORDER BY e.{project_col} LIMIT 1
+            )
+            WHERE NOT EXISTS (
+                SELECT 1 FROM main.{elements_table} AS e
+                WHERE e.{project_col} = r.{target_project_col} AND e.{pk_col} = r."target_id"
+            ) AND EXISTS (
+                SELECT 1 FROM main.{elements_table} AS e WHERE e.{pk_col} = r."target_id"
+            )"#
+        ),
+        (),
+    )?;
+    if resolved > 0 {
+        info!("resolved {resolved} relations to elements of other projects");
+    }
+    Ok(resolved)
+}
@@ -0,0 +1,139 @@
+//! Read-only commands to explore an imported model
+//!
+//! These commands spare users from hand-writing the most common SQL queries against the database.
+
+use std::collections::{HashMap, HashSet};
+
+use eyre::{Result, bail};
+use rusqlite::{Connection, OptionalExtension};
+
+use crate::{
+    config::{ELEMENT_PK_COL, OWNED_ELEMENT_RELATION, config},
+    util::{escape_sql_ident, escape_sql_str_lit},
+};
+
+mod bom;
+mod coverage;
+mod impact;
+mod path;
+mod search;
+mod show;
+mod trace;
+mod tree;
+
+pub(crate) use bom::bom;
+pub(crate) use coverage::coverage;
+pub(crate) use impact::impact;
+pub(crate) use path::path;
+pub(crate) use search::search;
+pub(crate) use show::show;
+pub(crate) use trace::{REQUIREMENT_TYPES, trace, trace_matrix};
+pub(crate) use tree::tree;
+
+/// Types of the elements forming the package hierarchy
+pub(crate) const PACKAGE_TYPES: [&str; 2] = ["Package", "LibraryPackage"];
+
+/// SQL expression yielding a human readable label for the element aliased as `alias`
+pub(crate) fn element_label_expr(alias: &str) -> String {
+    format!(r#"COALESCE({alias}."qualifiedName", {alias}."name", {alias}."declaredName", '')"#)
+}
+
+/// SQL expression yielding the short name of the element aliased as `alias`
+pub(crate) fn element_name_expr(alias: &str) -> String {
+    format!(r#"COALESCE({alias}."name", {alias}."declaredName", '')"#)
+}
+
+/// Resolve either an element id or a qualified name to an element id
+pub(crate) fn resolve_element(conn: &Connection, id_or_qualified_name: &str) -> Result<String> {
+    let elements_table = escape_sql_ident(&config().elements_table);
+    let pk_col = escape_sql_ident(ELEMENT_PK_COL);
+
+    let maybe_id: Option<String> = conn
+        .query_row(
+            &format!(r#"SELECT {pk_col} FROM {elements_table} WHERE {pk_col} = ?1"#),
+            [id_or_qualified_name],
+            |row| row.get(0),
+        )
+        .optional()?;
+    if let Some(id) = maybe_id {
+        return Ok(id);
+    }
+
+    let mut stmt = conn.prepare(&format!(
+        r#"SELECT {pk_col} FROM {elements_table} WHERE "qualifiedName" = ?1"#
+    ))?;
+    let ids = stmt
+        .query_map([id_or_qualified_name], |row| row.get::<_, String>(0))?
+        .collect::<Result<Vec<_>, _>>()?;
+
+    match ids.as_slice() {
+        [] => bail!("no element with the id or qualified name {id_or_qualified_name:?} found"),
+        [id] => Ok(id.to_owned()),
+        _ => bail!(
+            "the qualified name {id_or_qualified_name:?} is ambiguous, it matches the elements {ids:?}"
+        ),
+    }
+}
+
+/// Describe an element in one line, consisting of its label, its type and its id
+pub(crate) fn describe_element(conn: &Connection, id: &str) -> Result<String> {
+    let elements_table = escape_sql_ident(&config().elements_table);
+    let pk_col = escape_sql_ident(ELEMENT_PK_COL);
+    let label = element_label_expr("e");
+
+    let maybe_description = conn
+        .query_row(
+            &format!(
+                r#"SELECT {label}, COALESCE(e."@type", '') FROM {elements_table} AS e
+                WHERE e.{pk_col} = ?1"#
+            ),
+            [id],
+            |row| {
+                Ok(format!(
+                    "{} [{}] {id}",
+                    row.get::<_, String>(0)?,
+                    row.get::<_, String>(1)?
+                ))
+            },
+        )
+        .optional()?;
+
+    Ok(maybe_description.unwrap_or_else(|| format!("<missing element> {id}")))
+}
+
+/// Read the ownership of all elements, mapping the id of each owned element to that of its owner
+pub(crate) fn read_owners(conn: &Connection) -> Result<HashMap<String, String>> {
+    let relations_table = escape_sql_ident(&config().relations_table);
+    let owned_element = escape_sql_str_lit(OWNED_ELEMENT_RELATION);
+
+    let mut stmt = conn.prepare(&format!(
+        r#"SELECT "target_id", "origin_id" FROM {relations_table} WHERE "name" = {owned_element}"#
+    ))?;
+    let owners = stmt
+        .query_map((), |row| Ok((row.get(0)?, row.get(1)?)))?
+        .collect::<Result<_, _>>()?;
+    Ok(owners)
+}
+
+/// Find the closest (transitive) owner of an element which is one of the `candidates`
+///
+/// Typically used to find the package an element is in. Stops with a warning on ownership cycles.
+pub(crate) fn closest_owner<'a, T>(
+    owners: &'a HashMap<String, String>,
+    candidates: &HashMap<String, T>,
+    id: &'a String,
+) -> Option<&'a String> {
+    let mut visited = HashSet::from([id]);
+    let mut current = id;
+    while let Some(owner) = owners.get(current) {
+        if !visited.insert(owner) {
+            warn!("the ownership of the element {id:?} is cyclic");
+            return None;
+        }
+        if candidates.contains_key(owner) {
+            return Some(owner);
+        }
+        current = owner;
+    }
+    None
+}
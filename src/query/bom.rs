@@ -0,0 +1,227 @@
+use std::{collections::BTreeMap, io::Write};
+
+use eyre::Result;
+use rusqlite::{Connection, OptionalExtension, Statement, types::Value};
+
+use crate::{
+    config::{ELEMENT_PK_COL, OWNED_ELEMENT_RELATION, config},
+    util::{escape_csv, escape_sql_ident, escape_sql_str_lit},
+};
+
+use super::{element_label_expr, resolve_element};
+
+/// Type of the features counted in the bill of materials
+const PART_TYPE: &str = "PartUsage";
+
+/// Relation from a usage to its definitions
+const DEFINITION_RELATION: &str = "definition";
+
+/// Relation from a feature to its multiplicity
+const MULTIPLICITY_RELATION: &str = "multiplicity";
+
+/// Relation from a multiplicity range to the expression of its upper bound
+const UPPER_BOUND_RELATION: &str = "upperBound";
+
+/// One line of the bill of materials
+#[derive(Debug, Clone, serde::Serialize)]
+struct BomLine {
+    /// Label of the definition of the parts, or of the part itself if it has no definition
+    item: String,
+
+    /// Type of the element the item refers to, e.g. `PartDefinition`
+    r#type: String,
+
+    /// Id of the element the item refers to
+    id: String,
+
+    /// Number of parts of this item within the root
+    quantity: u64,
+}
+
+/// Prepared statements to walk the part tree
+struct Statements<'conn> {
+    /// Yields the id of the part usages owned by an element
+    owned_parts: Statement<'conn>,
+
+    /// Yields the id, label and type of the definitions of a usage
+    definitions: Statement<'conn>,
+
+    /// Yields the type and value of the upper bound of the multiplicity of a feature
+    multiplicity: Statement<'conn>,
+
+    /// Yields the id, label and type of an element
+    element: Statement<'conn>,
+}
+
+/// Print the flattened bill of materials of a part, as CSV or JSON
+///
+/// Walks the part usages owned by the root, recursively, multiplying the quantities along the way.
+/// A part owning no parts itself is expanded by the parts of its definitions. The quantity of a
+/// part usage is the upper bound of its multiplicity; parts without multiplicity, or with an
+/// unbounded or non-literal one, count once. The parts are counted per definition, parts without
+/// definition on their own.
+pub(crate) fn bom<W: Write>(
+    conn: &Connection,
+    root: &str,
+    json: bool,
+    out: &mut W,
+) -> Result<usize> {
+    let elements_table = escape_sql_ident(&config().elements_table);
+    let relations_table = escape_sql_ident(&config().relations_table);
+    let pk_col = escape_sql_ident(ELEMENT_PK_COL);
+    let owned_element = escape_sql_str_lit(OWNED_ELEMENT_RELATION);
+    let part_type = escape_sql_str_lit(PART_TYPE);
+    let definition = escape_sql_str_lit(DEFINITION_RELATION);
+    let multiplicity = escape_sql_str_lit(MULTIPLICITY_RELATION);
+    let upper_bound = escape_sql_str_lit(UPPER_BOUND_RELATION);
+    let label = element_label_expr("e");
+
+    let mut statements = Statements {
+        owned_parts: conn.prepare(&format!(
+            r#"SELECT e.{pk_col} FROM {relations_table} AS r
+            JOIN {elements_table} AS e ON e.{pk_col} = r."target_id"
+            WHERE r."name" = {owned_element} AND r."origin_id" = ?1 AND e."@type" = {part_type}
+            ORDER BY 1"#
+        ))?,
+        definitions: conn.prepare(&format!(
+            r#"SELECT e.{pk_col}, {label}, COALESCE(e."@type", '') FROM {relations_table} AS r
+            JOIN {elements_table} AS e ON e.{pk_col} = r."target_id"
+            WHERE r."name" = {definition} AND r."origin_id" = ?1
+            ORDER BY 2, 1"#
+        ))?,
+        multiplicity: conn.prepare(&format!(
+            r#"SELECT b."@type", b."value" FROM {relations_table} AS rm
+            JOIN {elements_table} AS m ON m.{pk_col} = rm."target_id"
+            LEFT JOIN {relations_table} AS rb
+                ON rb."origin_id" = m.{pk_col} AND rb."name" = {upper_bound}
+            LEFT JOIN {elements_table} AS b ON b.{pk_col} = rb."target_id"
+            WHERE rm."name" = {multiplicity} AND rm."origin_id" = ?1"#
+        ))?,
+        element: conn.prepare(&format!(
+            r#"SELECT e.{pk_col}, {label}, COALESCE(e."@type", '') FROM {elements_table} AS e
+            WHERE e.{pk_col} = ?1"#
+        ))?,
+    };
+
+    let root_id = resolve_element(conn, root)?;
+    let mut lines = BTreeMap::new();
+    walk_parts(
+        &mut statements,
+        &root_id,
+        1,
+        &mut vec![root_id.clone()],
+        &mut lines,
+    )?;
+
+    let mut lines: Vec<BomLine> = lines.into_values().collect();
+    lines.sort_by(|a, b| (&a.item, &a.id).cmp(&(&b.item, &b.id)));
+    debug!("counted {} distinct items", lines.len());
+
+    if json {
+        serde_json::to_writer_pretty(&mut *out, &lines)?;
+        writeln!(out)?;
+    } else {
+        writeln!(out, "item,type,id,quantity")?;
+        for line in &lines {
+            writeln!(
+                out,
+                "{},{},{},{}",
+                escape_csv(&line.item),
+                escape_csv(&line.r#type),
+                escape_csv(&line.id),
+                line.quantity
+            )?;
+        }
+    }
+
+    Ok(lines.len())
+}
+
+/// Count the parts below an element, each `factor` times, into `lines`
+///
+/// `definition_ids` holds the definitions which are currently expanded, to stop at definitions
+/// containing usages of themselves.
+fn walk_parts(
+    statements: &mut Statements,
+    owner_id: &str,
+    factor: u64,
+    definition_ids: &mut Vec<String>,
+    lines: &mut BTreeMap<String, BomLine>,
+) -> Result<()> {
+    let mut part_ids = query_ids(&mut statements.owned_parts, owner_id)?;
+    let mut pushed_definitions = 0;
+    if part_ids.is_empty() {
+        for definition_id in query_ids(&mut statements.definitions, owner_id)? {
+            if definition_ids.contains(&definition_id) {
+                warn!("not expanding the recursive definition {definition_id:?} once more");
+                continue;
+            }
+            part_ids.extend(query_ids(&mut statements.owned_parts, &definition_id)?);
+            definition_ids.push(definition_id);
+            pushed_definitions += 1;
+        }
+    }
+
+    for part_id in part_ids {
+        let quantity = factor.saturating_mul(multiplicity(statements, &part_id)?);
+
+        // parts are counted per definition, parts without definition on their own
+        let item_row = |row: &rusqlite::Row| -> rusqlite::Result<(String, String, String)> {
+            Ok((row.get(0)?, row.get(1)?, row.get(2)?))
+        };
+        let (id, item, r#type) = match statements
+            .definitions
+            .query_row([&part_id], item_row)
+            .optional()?
+        {
+            Some(definition) => definition,
+            None => statements.element.query_row([&part_id], item_row)?,
+        };
+        lines
+            .entry(id.clone())
+            .or_insert_with(|| BomLine {
+                item,
+                r#type,
+                id,
+                quantity: 0,
+            })
+            .quantity += quantity;
+
+        walk_parts(statements, &part_id, quantity, definition_ids, lines)?;
+    }
+
+    definition_ids.truncate(definition_ids.len() - pushed_definitions);
+
+    Ok(())
+}
+
+/// The quantity of a part usage, the upper bound of its multiplicity
+fn multiplicity(statements: &mut Statements, part_id: &str) -> Result<u64> {
+    let maybe_multiplicity = statements
+        .multiplicity
+        .query_row([part_id], |row| {
+            Ok((row.get::<_, Option<String>>(0)?, row.get::<_, Value>(1)?))
+        })
+        .optional()?;
+
+    match maybe_multiplicity {
+        // no multiplicity, or one without bounds, i.e. the default of exactly one
+        None | Some((None, _)) => Ok(1),
+        Some((_, Value::Integer(upper_bound))) if upper_bound >= 0 => Ok(upper_bound as u64),
+        Some((Some(bound_type), _)) => {
+            warn!(
+                "the multiplicity of the part {part_id:?} has an upper bound of type \
+                {bound_type:?}, counting it once"
+            );
+            Ok(1)
+        }
+    }
+}
+
+/// Run a statement taking an element id, collecting the ids in the first column of its rows
+fn query_ids(stmt: &mut Statement, id: &str) -> Result<Vec<String>> {
+    let ids = stmt
+        .query_map([id], |row| row.get(0))?
+        .collect::<Result<_, _>>()?;
+    Ok(ids)
+}
@@ -0,0 +1,253 @@
+use std::{
+    collections::{BTreeMap, HashMap, HashSet},
+    io::Write,
+};
+
+use eyre::Result;
+use rusqlite::Connection;
+
+use crate::{
+    config::{ELEMENT_PK_COL, config},
+    util::{escape_sql_ident, escape_sql_str_lit},
+};
+
+use super::{
+    PACKAGE_TYPES, REQUIREMENT_TYPES, closest_owner, element_label_expr, read_owners,
+    trace::TRACE_LINKS,
+};
+
+/// Kind of the trace links satisfying a requirement, see [`TRACE_LINKS`]
+const SATISFY_KIND: &str = "satisfy";
+
+/// Kind of the trace links verifying a requirement, see [`TRACE_LINKS`]
+const VERIFY_KIND: &str = "verify";
+
+/// Label of the group of requirements which are not in any package
+const NO_PACKAGE_LABEL: &str = "(no package)";
+
+/// Coverage of the requirements by satisfy and verify links
+#[derive(Debug, Clone, serde::Serialize)]
+struct Coverage {
+    /// Coverage of all requirements
+    total: CoverageCounts,
+
+    /// Coverage of the requirements per package they are in, ordered by package
+    packages: Vec<PackageCoverage>,
+
+    /// Coverage of each requirement, ordered by label
+    requirements: Vec<RequirementCoverage>,
+}
+
+/// Number of requirements and how many of them are satisfied and verified
+#[derive(Debug, Clone, Default, serde::Serialize)]
+struct CoverageCounts {
+    requirements: u64,
+    satisfied: u64,
+    verified: u64,
+
+    /// Share of the satisfied requirements in percent, absent if there are no requirements
+    satisfied_percentage: Option<f64>,
+
+    /// Share of the verified requirements in percent, absent if there are no requirements
+    verified_percentage: Option<f64>,
+}
+
+/// Coverage of the requirements for which a package is the closest package owning them
+#[derive(Debug, Clone, serde::Serialize)]
+struct PackageCoverage {
+    /// Label of the package
+    package: String,
+
+    /// Id of the package, absent for the requirements which are not in any package
+    package_id: Option<String>,
+
+    #[serde(flatten)]
+    counts: CoverageCounts,
+}
+
+/// Coverage of a single requirement
+#[derive(Debug, Clone, serde::Serialize)]
+struct RequirementCoverage {
+    id: String,
+    label: String,
+    r#type: String,
+
+    /// Id of the closest package owning the requirement
+    package_id: Option<String>,
+
+    satisfied: bool,
+    verified: bool,
+}
+
+impl CoverageCounts {
+    /// Count a requirement
+    fn add(&mut self, requirement: &RequirementCoverage) {
+        self.requirements += 1;
+        self.satisfied += u64::from(requirement.satisfied);
+        self.verified += u64::from(requirement.verified);
+
+        let percentage = |count: u64| count as f64 * 100.0 / self.requirements as f64;
+        self.satisfied_percentage = Some(percentage(self.satisfied));
+        self.verified_percentage = Some(percentage(self.verified));
+    }
+}
+
+/// Print which requirements are satisfied and verified, with percentages per package
+///
+/// A requirement is satisfied (verified) if it is the target of at least one satisfy (verify)
+/// link, see [`super::trace`]. The requirements are grouped by the closest package owning them.
+/// Requirements lacking either kind of link are listed, to reveal the gaps. With `json`, the
+/// coverage is printed as JSON object instead, e.g. to feed dashboards.
+pub(crate) fn coverage<W: Write>(conn: &Connection, json: bool, out: &mut W) -> Result<()> {
+    let elements_table = escape_sql_ident(&config().elements_table);
+    let pk_col = escape_sql_ident(ELEMENT_PK_COL);
+    let requirement_types = REQUIREMENT_TYPES
+        .iter()
+        .map(escape_sql_str_lit)
+        .collect::<Vec<_>>()
+        .join(", ");
+    let package_types = PACKAGE_TYPES
+        .iter()
+        .map(escape_sql_str_lit)
+        .collect::<Vec<_>>()
+        .join(", ");
+    let label = element_label_expr("e");
+
+    let satisfied = traced_requirements(conn, SATISFY_KIND)?;
+    let verified = traced_requirements(conn, VERIFY_KIND)?;
+
+    let mut stmt = conn.prepare(&format!(
+        r#"SELECT e.{pk_col}, {label} FROM {elements_table} AS e
+        WHERE e."@type" IN ({package_types})"#
+    ))?;
+    let packages: HashMap<String, String> = stmt
+        .query_map((), |row| Ok((row.get(0)?, row.get(1)?)))?
+        .collect::<Result<_, _>>()?;
+    let owners = read_owners(conn)?;
+
+    let mut stmt = conn.prepare(&format!(
+        r#"SELECT e.{pk_col}, {label}, e."@type" FROM {elements_table} AS e
+        WHERE e."@type" IN ({requirement_types}) ORDER BY 2, 1"#
+    ))?;
+    let requirements = stmt
+        .query_map((), |row| {
+            let id: String = row.get(0)?;
+            Ok(RequirementCoverage {
+                package_id: closest_owner(&owners, &packages, &id).cloned(),
+                satisfied: satisfied.contains(&id),
+                verified: verified.contains(&id),
+                id,
+                label: row.get(1)?,
+                r#type: row.get(2)?,
+            })
+        })?
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let mut total = CoverageCounts::default();
+    let mut by_package: BTreeMap<Option<(&String, &String)>, CoverageCounts> = BTreeMap::new();
+    for requirement in &requirements {
+        total.add(requirement);
+        let package_key = requirement
+            .package_id
+            .as_ref()
+            .map(|id| (&packages[id], id));
+        by_package.entry(package_key).or_default().add(requirement);
+    }
+    let package_coverages: Vec<PackageCoverage> = by_package
+        .into_iter()
+        .map(|(package_key, counts)| PackageCoverage {
+            package: package_key
+                .map_or_else(|| NO_PACKAGE_LABEL.to_owned(), |(label, _)| label.clone()),
+            package_id: package_key.map(|(_, id)| id.clone()),
+            counts,
+        })
+        .collect();
+
+    let coverage = Coverage {
+        total,
+        packages: package_coverages,
+        requirements,
+    };
+
+    if json {
+        serde_json::to_writer_pretty(&mut *out, &coverage)?;
+        writeln!(out)?;
+        return Ok(());
+    }
+
+    writeln!(out, "# coverage per package")?;
+    writeln!(out, "requirements\tsatisfied\tverified\tpackage")?;
+    let rows = coverage
+        .packages
+        .iter()
+        .map(|package| (&package.counts, package.package.as_str()))
+        .chain([(&coverage.total, "(total)")]);
+    for (counts, package) in rows {
+        writeln!(
+            out,
+            "{}\t{}\t{}\t{package}",
+            counts.requirements,
+            format_share(counts.satisfied, counts.satisfied_percentage),
+            format_share(counts.verified, counts.verified_percentage),
+        )?;
+    }
+
+    let requirements = &coverage.requirements;
+    let gaps: [(&str, Vec<_>); 2] = [
+        (
+            "not satisfied",
+            requirements.iter().filter(|r| !r.satisfied).collect(),
+        ),
+        (
+            "not verified",
+            requirements.iter().filter(|r| !r.verified).collect(),
+        ),
+    ];
+    for (title, gap) in gaps {
+        writeln!(out, "\n# {title}")?;
+        for requirement in gap {
+            writeln!(
+                out,
+                "{} [{}] {}",
+                requirement.label, requirement.r#type, requirement.id
+            )?;
+        }
+    }
+
+    Ok(())
+}
+
+/// The ids of all requirements traced by at least one trace link of the given kind
+fn traced_requirements(conn: &Connection, kind: &str) -> Result<HashSet<String>> {
+    let elements_table = escape_sql_ident(&config().elements_table);
+    let relations_table = escape_sql_ident(&config().relations_table);
+    let pk_col = escape_sql_ident(ELEMENT_PK_COL);
+    let link = TRACE_LINKS
+        .iter()
+        .find(|link| link.kind == kind)
+        .expect("the trace link kind to be known");
+
+    let mut stmt = conn.prepare(&format!(
+        r#"SELECT DISTINCT l."target_id" FROM {relations_table} AS l
+        WHERE l."name" = ?1 AND (?2 IS NULL OR EXISTS (
+            SELECT 1 FROM {relations_table} AS t WHERE t."origin_id" = l."origin_id" AND t."name" = ?2
+        )) AND (?3 IS NULL OR EXISTS (
+            SELECT 1 FROM {elements_table} AS k WHERE k.{pk_col} = l."origin_id" AND k."@type" = ?3
+        ))"#
+    ))?;
+    let ids = stmt
+        .query_map(
+            (link.to_requirement, link.to_tracing, link.linking_type),
+            |row| row.get(0),
+        )?
+        .collect::<Result<_, _>>()?;
+    Ok(ids)
+}
+
+/// Format a number of requirements together with its share in percent
+fn format_share(count: u64, maybe_percentage: Option<f64>) -> String {
+    match maybe_percentage {
+        Some(percentage) => format!("{count} ({percentage:.1}%)"),
+        None => format!("{count} (-)"),
+    }
+}
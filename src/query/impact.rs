@@ -0,0 +1,81 @@
+use std::{
+    collections::{HashMap, VecDeque},
+    io::Write,
+};
+
+use eyre::Result;
+use rusqlite::Connection;
+
+use crate::{config::config, util::escape_sql_ident};
+
+use super::{describe_element, resolve_element};
+
+/// Compute all elements transitively reachable from (or leading to) an element and print them
+///
+/// Each reached element is printed with its distance to the start element, ordered by distance. If
+/// `relations` is not empty, only relations with one of the given names are followed. With
+/// `reverse`, relations are followed from their target to their origin, yielding all elements
+/// which lead to the start element instead.
+pub(crate) fn impact<W: Write>(
+    conn: &Connection,
+    element: &str,
+    reverse: bool,
+    relations: &[String],
+    maybe_max_depth: Option<usize>,
+    out: &mut W,
+) -> Result<usize> {
+    let relations_table = escape_sql_ident(&config().relations_table);
+
+    let start_id = resolve_element(conn, element)?;
+
+    let (this_col, other_col) = if reverse {
+        ("target_id", "origin_id")
+    } else {
+        ("origin_id", "target_id")
+    };
+    let mut stmt = conn.prepare(&format!(
+        r#"SELECT "name", "{other_col}" FROM {relations_table} WHERE "{this_col}" = ?1"#
+    ))?;
+
+    // breadth-first search, memorizing the distance of each reached element
+    let mut distances = HashMap::from([(start_id.clone(), 0)]);
+    let mut reached = Vec::new();
+    let mut queue = VecDeque::from([start_id.clone()]);
+    while let Some(current) = queue.pop_front() {
+        let depth = distances[&current];
+        if maybe_max_depth.is_some_and(|max_depth| depth >= max_depth) {
+            continue;
+        }
+
+        let mut rows = stmt.query([&current])?;
+        while let Some(row) = rows.next()? {
+            let relation: String = row.get(0)?;
+            let neighbour: String = row.get(1)?;
+
+            if !relations.is_empty() && !relations.contains(&relation) {
+                continue;
+            }
+
+            if !distances.contains_key(&neighbour) {
+                distances.insert(neighbour.clone(), depth + 1);
+                reached.push((depth + 1, neighbour.clone()));
+                queue.push_back(neighbour);
+            }
+        }
+    }
+
+    for (depth, id) in &reached {
+        writeln!(out, "{depth}\t{}", describe_element(conn, id)?)?;
+    }
+    info!(
+        "found {} elements {} {start_id:?}",
+        reached.len(),
+        if reverse {
+            "leading to"
+        } else {
+            "reachable from"
+        }
+    );
+
+    Ok(reached.len())
+}
@@ -0,0 +1,105 @@
+use std::{
+    collections::{HashMap, VecDeque},
+    io::Write,
+};
+
+use eyre::{Result, bail};
+use rusqlite::Connection;
+
+use crate::{config::config, util::escape_sql_ident};
+
+use super::{describe_element, resolve_element};
+
+/// One step of a path, i.e. one relation traversed either along or against its direction
+struct Step {
+    /// Element from which this step originates
+    from: String,
+
+    /// Name of the relation
+    relation: String,
+
+    /// Whether the relation was traversed from its origin to its target
+    forward: bool,
+}
+
+/// Find the shortest chain of relations between two elements and print it
+///
+/// Performs a breadth-first search over the relations table. Unless `directed` is set, relations
+/// are traversed in both directions. If `relations` is not empty, only relations with one of the
+/// given names are considered.
+pub(crate) fn path<W: Write>(
+    conn: &Connection,
+    from: &str,
+    to: &str,
+    directed: bool,
+    relations: &[String],
+    out: &mut W,
+) -> Result<usize> {
+    let relations_table = escape_sql_ident(&config().relations_table);
+
+    let from_id = resolve_element(conn, from)?;
+    let to_id = resolve_element(conn, to)?;
+
+    let mut stmt = conn.prepare(&format!(
+        r#"SELECT "name", "target_id", 1 FROM {relations_table} WHERE "origin_id" = ?1
+        UNION ALL
+        SELECT "name", "origin_id", 0 FROM {relations_table} WHERE "target_id" = ?1 AND NOT ?2"#
+    ))?;
+
+    // maps each discovered element to the step over which it was discovered first
+    let mut discovered_via: HashMap<String, Option<Step>> =
+        HashMap::from([(from_id.clone(), None)]);
+    let mut queue = VecDeque::from([from_id.clone()]);
+
+    while let Some(current) = queue.pop_front() {
+        if current == to_id {
+            break;
+        }
+
+        let mut rows = stmt.query((&current, directed))?;
+        while let Some(row) = rows.next()? {
+            let relation: String = row.get(0)?;
+            let neighbour: String = row.get(1)?;
+            let forward: bool = row.get(2)?;
+
+            if !relations.is_empty() && !relations.contains(&relation) {
+                continue;
+            }
+
+            discovered_via.entry(neighbour.clone()).or_insert_with(|| {
+                queue.push_back(neighbour);
+                Some(Step {
+                    from: current.clone(),
+                    relation,
+                    forward,
+                })
+            });
+        }
+    }
+    debug!("discovered {} elements", discovered_via.len());
+
+    if !discovered_via.contains_key(&to_id) {
+        bail!("there is no path from {from_id:?} to {to_id:?}");
+    }
+
+    // walk back from the destination to the start
+    let mut steps = Vec::new();
+    let mut current = &to_id;
+    while let Some(Some(step)) = discovered_via.get(current) {
+        steps.push((step, current));
+        current = &step.from;
+    }
+    steps.reverse();
+
+    writeln!(out, "{}", describe_element(conn, &from_id)?)?;
+    for (step, element) in &steps {
+        let arrow = if step.forward {
+            format!("--{}-->", step.relation)
+        } else {
+            format!("<--{}--", step.relation)
+        };
+        writeln!(out, "  {arrow} {}", describe_element(conn, element)?)?;
+    }
+
+    Ok(steps.len())
+}
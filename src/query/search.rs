@@ -0,0 +1,79 @@
+use std::io::Write;
+
+use eyre::{Result, ensure};
+use rusqlite::Connection;
+
+use crate::{
+    config::{ELEMENT_PK_COL, FTS_TABLE, config},
+    util::{escape_like, escape_sql_ident, table_exists},
+};
+
+use super::element_label_expr;
+
+/// Search for elements by name, qualified name or type, printing one matching element per line
+///
+/// Without `use_fts`, `pattern` is matched literally as substring (case insensitive for ASCII)
+/// against the `name`, `declaredName` and `qualifiedName` columns, `%` and `_` are no wildcards.
+/// With `use_fts`, `pattern` is passed verbatim as FTS5 query to the full-text index, which is
+/// significantly faster on big models and supports the FTS5 query syntax.
+pub(crate) fn search<W: Write>(
+    conn: &Connection,
+    pattern: &str,
+    maybe_type: Option<&str>,
+    use_fts: bool,
+    limit: usize,
+    out: &mut W,
+) -> Result<usize> {
+    let elements_table = escape_sql_ident(&config().elements_table);
+    let pk_col = escape_sql_ident(ELEMENT_PK_COL);
+
+    let label = element_label_expr("e");
+    let statement = if use_fts {
+        ensure!(
+            table_exists(conn, FTS_TABLE)?,
+            "the db lacks the full-text index, search without it instead"
+        );
+        let fts_table = escape_sql_ident(FTS_TABLE);
+        format!(
+            r#"WITH "hits" AS (SELECT rowid, rank FROM {fts_table} WHERE {fts_table} MATCH ?1)
+            SELECT e.{pk_col}, e."@type", {label} FROM "hits" AS h
+            JOIN {elements_table} AS e ON e.rowid = h.rowid
+            WHERE ?2 IS NULL OR e."@type" = ?2
+            ORDER BY h.rank LIMIT ?3"#
+        )
+    } else {
+        format!(
+            r#"SELECT e.{pk_col}, e."@type", {label} FROM {elements_table} AS e
+            WHERE (e."name" LIKE ?1 ESCAPE '\' OR e."declaredName" LIKE ?1 ESCAPE '\'
+                OR e."qualifiedName" LIKE ?1 ESCAPE '\')
+                AND (?2 IS NULL OR e."@type" = ?2)
+            ORDER BY 3 LIMIT ?3"#
+        )
+    };
+    trace!("prepared the following statement:\n{statement}");
+
+    let pattern = if use_fts {
+        pattern.to_owned()
+    } else {
+        format!("%{}%", escape_like(pattern))
+    };
+
+    let mut stmt = conn.prepare(&statement)?;
+    let mut rows = stmt.query((pattern, maybe_type, limit as i64))?;
+
+    let mut matches = 0;
+    while let Some(row) = rows.next()? {
+        let id: String = row.get(0)?;
+        let ty: Option<String> = row.get(1)?;
+        let label: String = row.get(2)?;
+        writeln!(out, "{id}\t{}\t{label}", ty.unwrap_or_default())?;
+        matches += 1;
+    }
+
+    if matches == limit {
+        warn!("output was truncated to {limit} matches, use -l/--limit to see more");
+    }
+    debug!("found {matches} matching elements");
+
+    Ok(matches)
+}
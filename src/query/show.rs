@@ -0,0 +1,127 @@
+use std::io::Write;
+
+use eyre::Result;
+use rusqlite::{Connection, types::ValueRef};
+
+use crate::{
+    config::{ELEMENT_PK_COL, config},
+    util::escape_sql_ident,
+};
+
+use super::{element_label_expr, resolve_element};
+
+/// Print everything the database knows about a single element
+///
+/// This comprises all non-null columns of the element, all outgoing and incoming relations grouped
+/// by the relation name, and all extended properties.
+pub(crate) fn show<W: Write>(
+    conn: &Connection,
+    id_or_qualified_name: &str,
+    out: &mut W,
+) -> Result<()> {
+    let elements_table = escape_sql_ident(&config().elements_table);
+    let relations_table = escape_sql_ident(&config().relations_table);
+    let extended_table = escape_sql_ident(&config().extended_table);
+    let pk_col = escape_sql_ident(ELEMENT_PK_COL);
+
+    let id = resolve_element(conn, id_or_qualified_name)?;
+
+    //
+    // Columns
+    //
+
+    let mut stmt = conn.prepare(&format!(
+        r#"SELECT * FROM {elements_table} WHERE {pk_col} = ?1"#
+    ))?;
+    let column_names: Vec<String> = stmt.column_names().into_iter().map(str::to_owned).collect();
+    let columns = stmt.query_row([&id], |row| {
+        let mut columns = Vec::new();
+        for (idx, column_name) in column_names.iter().enumerate() {
+            if let Some(value) = format_value(row.get_ref(idx)?) {
+                columns.push((column_name, value));
+            }
+        }
+        Ok(columns)
+    })?;
+    for (column_name, value) in columns {
+        writeln!(out, "{column_name}: {value}")?;
+    }
+
+    //
+    // Relations
+    //
+
+    for (direction, this_col, other_col) in [
+        ("outgoing", "origin_id", "target_id"),
+        ("incoming", "target_id", "origin_id"),
+    ] {
+        let label = element_label_expr("e");
+        let mut stmt = conn.prepare(&format!(
+            r#"SELECT r."name", r."{other_col}", e."@type", {label} FROM {relations_table} AS r
+            LEFT JOIN {elements_table} AS e ON e.{pk_col} = r."{other_col}"
+            WHERE r."{this_col}" = ?1
+            ORDER BY r."name", 4, r."{other_col}""#
+        ))?;
+        let mut rows = stmt.query([&id])?;
+
+        let mut current_relation_name = None;
+        while let Some(row) = rows.next()? {
+            let relation_name: String = row.get(0)?;
+            let other_id: String = row.get(1)?;
+            let other_type: Option<String> = row.get(2)?;
+            let other_label: Option<String> = row.get(3)?;
+
+            if current_relation_name.is_none() {
+                writeln!(out, "\n{direction} relations:")?;
+            }
+            if current_relation_name.as_ref() != Some(&relation_name) {
+                writeln!(out, "  {relation_name}:")?;
+                current_relation_name = Some(relation_name);
+            }
+            writeln!(
+                out,
+                "    {other_id}\t{}\t{}",
+                other_type.unwrap_or_default(),
+                other_label.unwrap_or_default()
+            )?;
+        }
+    }
+
+    //
+    // Extended properties
+    //
+
+    let mut stmt = conn.prepare(&format!(
+        r#"SELECT * FROM {extended_table} WHERE {pk_col} = ?1"#
+    ))?;
+    let column_names: Vec<String> = stmt.column_names().into_iter().map(str::to_owned).collect();
+    let mut rows = stmt.query([&id])?;
+    let mut header_printed = false;
+    while let Some(row) = rows.next()? {
+        for (idx, column_name) in column_names.iter().enumerate() {
+            if column_name == ELEMENT_PK_COL {
+                continue;
+            }
+            if let Some(value) = format_value(row.get_ref(idx)?) {
+                if !header_printed {
+                    writeln!(out, "\nextended properties:")?;
+                    header_printed = true;
+                }
+                writeln!(out, "  {column_name}: {value}")?;
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Format a SQLite value for display, returning [`None`] for `NULL`
+fn format_value(value: ValueRef) -> Option<String> {
+    match value {
+        ValueRef::Null => None,
+        ValueRef::Integer(i) => Some(i.to_string()),
+        ValueRef::Real(r) => Some(r.to_string()),
+        ValueRef::Text(t) => Some(String::from_utf8_lossy(t).into_owned()),
+        ValueRef::Blob(b) => Some(format!("<{} bytes blob>", b.len())),
+    }
+}
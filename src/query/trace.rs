@@ -0,0 +1,331 @@
+use std::{
+    collections::{BTreeSet, HashMap, HashSet},
+    io::Write,
+    path::Path,
+};
+
+use eyre::Result;
+use rusqlite::{Connection, Statement};
+
+use crate::{
+    config::{ELEMENT_PK_COL, config},
+    export::{Cell, Sheet, write_xlsx},
+    util::{escape_csv, escape_sql_ident},
+};
+
+use super::{element_label_expr, resolve_element};
+
+/// Describes how a trace link is represented in the relations table
+///
+/// A trace link is established by a linking element which points to the traced requirement via
+/// the `to_requirement` relation. The element tracing the requirement is either found by following
+/// the `to_tracing` relation from the linking element, or is the linking element itself. Relations
+/// shared by several kinds of links are told apart by the `@type` of the linking element.
+pub(super) struct TraceLink {
+    /// Kind of the trace link, as printed
+    pub(super) kind: &'static str,
+
+    /// `@type` of the linking element, any type if `None`
+    pub(super) linking_type: Option<&'static str>,
+
+    /// Relation from the linking element to the requirement
+    pub(super) to_requirement: &'static str,
+
+    /// Relation from the linking element to the element tracing the requirement
+    pub(super) to_tracing: Option<&'static str>,
+}
+
+/// All trace links which are followed
+///
+/// Derivations are connections (`#derivation connection`) from the original requirement (the
+/// source) to the derived requirements (the targets). Refinements are dependencies
+/// (`#refinement dependency`) from the refining element (the client) to the refined requirement
+/// (the supplier).
+pub(super) const TRACE_LINKS: [TraceLink; 4] = [
+    TraceLink {
+        kind: "satisfy",
+        linking_type: None,
+        to_requirement: "satisfiedRequirement",
+        to_tracing: Some("satisfyingFeature"),
+    },
+    TraceLink {
+        kind: "verify",
+        linking_type: None,
+        to_requirement: "verifiedRequirement",
+        to_tracing: None,
+    },
+    TraceLink {
+        kind: "derive",
+        linking_type: Some("ConnectionUsage"),
+        to_requirement: "source",
+        to_tracing: Some("target"),
+    },
+    TraceLink {
+        kind: "refine",
+        linking_type: Some("Dependency"),
+        to_requirement: "supplier",
+        to_tracing: Some("client"),
+    },
+];
+
+/// Types of the elements which are listed in the traceability matrix even if nothing traces them
+pub(crate) const REQUIREMENT_TYPES: [&str; 2] = ["RequirementUsage", "RequirementDefinition"];
+
+/// One row of the traceability matrix
+struct TraceRow {
+    requirement_id: String,
+    kind: &'static str,
+    tracing_id: String,
+    tracing_type: String,
+    tracing_label: String,
+}
+
+/// Follow satisfy, verify, derive and refine relations transitively, starting at a requirement
+///
+/// Prints the resulting traceability chain as indented tree. Optionally, the traceability matrix
+/// (one row per requirement and element tracing it) is written to a CSV file.
+pub(crate) fn trace<W: Write>(
+    conn: &Connection,
+    requirement: &str,
+    maybe_max_depth: Option<usize>,
+    maybe_csv_path: Option<&Path>,
+    out: &mut W,
+) -> Result<usize> {
+    let elements_table = escape_sql_ident(&config().elements_table);
+    let relations_table = escape_sql_ident(&config().relations_table);
+    let pk_col = escape_sql_ident(ELEMENT_PK_COL);
+    let label = element_label_expr("e");
+
+    let root_id = resolve_element(conn, requirement)?;
+
+    let root_label: String = conn.query_row(
+        &format!(r#"SELECT {label} FROM {elements_table} AS e WHERE e.{pk_col} = ?1"#),
+        [&root_id],
+        |row| row.get(0),
+    )?;
+
+    // finds the elements tracing a given element via one kind of trace link
+    let mut stmt = conn.prepare(&format!(
+        r#"SELECT DISTINCT e.{pk_col}, e."@type", {label} FROM {relations_table} AS l
+        LEFT JOIN {relations_table} AS t ON t."origin_id" = l."origin_id" AND t."name" = ?3
+        JOIN {elements_table} AS e ON e.{pk_col} = CASE WHEN ?3 IS NULL
+            THEN l."origin_id"
+            ELSE t."target_id"
+        END
+        WHERE l."target_id" = ?1 AND l."name" = ?2 AND (?4 IS NULL OR EXISTS (
+            SELECT 1 FROM {elements_table} AS k WHERE k.{pk_col} = l."origin_id" AND k."@type" = ?4
+        ))
+        ORDER BY 3, 1"#
+    ))?;
+
+    writeln!(out, "{root_label} {root_id}")?;
+
+    let mut rows = Vec::new();
+    let mut visited = HashSet::from([root_id.clone()]);
+    trace_recursively(
+        &mut stmt,
+        &root_id,
+        1,
+        maybe_max_depth,
+        &mut visited,
+        &mut rows,
+        out,
+    )?;
+
+    if let Some(csv_path) = maybe_csv_path {
+        info!("writing traceability matrix to {csv_path:?}");
+        let mut f = std::io::BufWriter::new(std::fs::File::create(csv_path)?);
+        writeln!(
+            f,
+            "requirement_id,kind,tracing_id,tracing_type,tracing_label"
+        )?;
+        for row in &rows {
+            writeln!(
+                f,
+                "{},{},{},{},{}",
+                escape_csv(&row.requirement_id),
+                escape_csv(row.kind),
+                escape_csv(&row.tracing_id),
+                escape_csv(&row.tracing_type),
+                escape_csv(&row.tracing_label)
+            )?;
+        }
+    }
+
+    Ok(rows.len())
+}
+
+/// Depth-first traversal of all elements tracing `requirement_id`, printing and collecting them
+fn trace_recursively<W: Write>(
+    stmt: &mut Statement,
+    requirement_id: &str,
+    depth: usize,
+    maybe_max_depth: Option<usize>,
+    visited: &mut HashSet<String>,
+    rows: &mut Vec<TraceRow>,
+    out: &mut W,
+) -> Result<()> {
+    if maybe_max_depth.is_some_and(|max_depth| depth > max_depth) {
+        return Ok(());
+    }
+
+    for link in &TRACE_LINKS {
+        let tracing_elements = stmt
+            .query_map(
+                (
+                    requirement_id,
+                    link.to_requirement,
+                    link.to_tracing,
+                    link.linking_type,
+                ),
+                |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)),
+            )?
+            .collect::<Result<Vec<(String, Option<String>, String)>, _>>()?;
+
+        for (tracing_id, tracing_type, tracing_label) in tracing_elements {
+            let tracing_type = tracing_type.unwrap_or_default();
+            let indent = "  ".repeat(depth);
+            writeln!(
+                out,
+                "{indent}{} <- {tracing_label} [{tracing_type}] {tracing_id}",
+                link.kind
+            )?;
+
+            rows.push(TraceRow {
+                requirement_id: requirement_id.to_owned(),
+                kind: link.kind,
+                tracing_id: tracing_id.clone(),
+                tracing_type,
+                tracing_label,
+            });
+
+            if visited.insert(tracing_id.clone()) {
+                trace_recursively(
+                    stmt,
+                    &tracing_id,
+                    depth + 1,
+                    maybe_max_depth,
+                    visited,
+                    rows,
+                    out,
+                )?;
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Write the traceability matrices of all requirements to an Excel workbook
+///
+/// The workbook contains one sheet per kind of trace link, with one row per requirement and one
+/// column per element tracing a requirement via that kind of link. Requirements which are not
+/// traced at all are listed as well, revealing the gaps. Only direct trace links are considered,
+/// see [`trace`] for the transitive traceability chain of a single requirement.
+pub(crate) fn trace_matrix(conn: &Connection, xlsx_path: &Path) -> Result<()> {
+    let elements_table = escape_sql_ident(&config().elements_table);
+    let relations_table = escape_sql_ident(&config().relations_table);
+    let pk_col = escape_sql_ident(ELEMENT_PK_COL);
+    let label = element_label_expr("e");
+
+    let mut stmt = conn.prepare(&format!(
+        r#"SELECT e.{pk_col}, {label}, COALESCE(e."@type", '') FROM {elements_table} AS e"#
+    ))?;
+    let mut labels = HashMap::new();
+    let mut requirements = BTreeSet::new();
+    let mut rows = stmt.query(())?;
+    while let Some(row) = rows.next()? {
+        let (id, label, r#type): (String, String, String) = (row.get(0)?, row.get(1)?, row.get(2)?);
+        if REQUIREMENT_TYPES.contains(&r#type.as_str()) {
+            requirements.insert((label.clone(), id.clone()));
+        }
+        labels.insert(id, label);
+    }
+    let label_of = |id: &str| labels.get(id).cloned().unwrap_or_default();
+
+    // finds all pairs of a requirement and an element tracing it via one kind of trace link
+    let mut stmt = conn.prepare(&format!(
+        r#"SELECT DISTINCT l."target_id", CASE WHEN ?2 IS NULL
+            THEN l."origin_id"
+            ELSE t."target_id"
+        END AS "tracing_id"
+        FROM {relations_table} AS l
+        LEFT JOIN {relations_table} AS t ON t."origin_id" = l."origin_id" AND t."name" = ?2
+        WHERE l."name" = ?1 AND "tracing_id" IS NOT NULL AND (?3 IS NULL OR EXISTS (
+            SELECT 1 FROM {elements_table} AS k WHERE k.{pk_col} = l."origin_id" AND k."@type" = ?3
+        ))"#
+    ))?;
+
+    let mut sheets = Vec::new();
+    for link in &TRACE_LINKS {
+        let links = stmt
+            .query_map(
+                (link.to_requirement, link.to_tracing, link.linking_type),
+                |row| Ok((row.get(0)?, row.get(1)?)),
+            )?
+            .collect::<Result<HashSet<(String, String)>, _>>()?;
+
+        // rows and columns are sorted by label, then by id
+        let mut requirement_rows = requirements.clone();
+        let mut tracing_columns = BTreeSet::new();
+        for (requirement_id, tracing_id) in &links {
+            requirement_rows.insert((label_of(requirement_id), requirement_id.clone()));
+            tracing_columns.insert((label_of(tracing_id), tracing_id.clone()));
+        }
+
+        // the first two rows hold the labels and ids of the tracing elements, the first three
+        // columns the label and id of the requirement and the number of elements tracing it
+        let mut label_row = vec![
+            Cell::Text("requirement".to_owned()),
+            Cell::Text("id".to_owned()),
+            Cell::Text(link.kind.to_owned()),
+        ];
+        let mut id_row = vec![Cell::Empty, Cell::Empty, Cell::Empty];
+        for (tracing_label, tracing_id) in &tracing_columns {
+            label_row.push(Cell::Text(tracing_label.clone()));
+            id_row.push(Cell::Text(tracing_id.clone()));
+        }
+
+        let mut rows = vec![label_row, id_row];
+        for (requirement_label, requirement_id) in &requirement_rows {
+            let marks: Vec<Cell> = tracing_columns
+                .iter()
+                .map(|(_, tracing_id)| {
+                    if links.contains(&(requirement_id.clone(), tracing_id.clone())) {
+                        Cell::Text("x".to_owned())
+                    } else {
+                        Cell::Empty
+                    }
+                })
+                .collect();
+            let count = marks.iter().filter(|c| matches!(c, Cell::Text(_))).count();
+
+            let mut row = vec![
+                Cell::Text(requirement_label.clone()),
+                Cell::Text(requirement_id.clone()),
+                Cell::Number(count as i64),
+            ];
+            row.extend(marks);
+            rows.push(row);
+        }
+
+        info!(
+            "found {} {} links between {} requirements and {} elements",
+            links.len(),
+            link.kind,
+            requirement_rows.len(),
+            tracing_columns.len()
+        );
+        sheets.push(Sheet {
+            name: link.kind.to_owned(),
+            rows,
+        });
+    }
+
+    info!("writing traceability matrices to {xlsx_path:?}");
+    let mut f = std::io::BufWriter::new(std::fs::File::create(xlsx_path)?);
+    write_xlsx(&sheets, &mut f)?;
+    f.flush()?;
+
+    Ok(())
+}
@@ -0,0 +1,90 @@
+use std::io::Write;
+
+use eyre::Result;
+use rusqlite::Connection;
+
+use crate::{
+    config::{ELEMENT_PK_COL, OWNED_ELEMENT_RELATION, OWNED_RELATIONSHIP_RELATION, config},
+    util::{escape_sql_ident, escape_sql_str_lit},
+};
+
+use super::{element_name_expr, resolve_element};
+
+/// Print the ownership hierarchy below an element, one element per line
+///
+/// The tree is derived from the `ownedElement` relation. If no root is given, all elements which
+/// are not owned by any other element (or relationship) are used as roots. Elements are only
+/// printed if `types` is empty or contains their `@type`, however the traversal continues through
+/// elements which are not printed. Ownership cycles are cut at the first element repeated.
+pub(crate) fn tree<W: Write>(
+    conn: &Connection,
+    maybe_root: Option<&str>,
+    maybe_max_depth: Option<u32>,
+    types: &[String],
+    out: &mut W,
+) -> Result<usize> {
+    let elements_table = escape_sql_ident(&config().elements_table);
+    let relations_table = escape_sql_ident(&config().relations_table);
+    let pk_col = escape_sql_ident(ELEMENT_PK_COL);
+    let owned_element = escape_sql_str_lit(OWNED_ELEMENT_RELATION);
+    let owned_relationship = escape_sql_str_lit(OWNED_RELATIONSHIP_RELATION);
+
+    let maybe_root_id = maybe_root
+        .map(|root| resolve_element(conn, root))
+        .transpose()?;
+
+    let root_name = element_name_expr("e");
+    let child_name = element_name_expr("c");
+
+    // Each row carries the path of names (and ids, to disambiguate equally named siblings) from the
+    // root, separated by control characters sorting before all printable ones. Ordering by this
+    // path yields a depth-first traversal with siblings in alphabetical order. The path also stops
+    // the traversal at elements which are already on it, as malformed models may own themselves
+    // via a cycle.
+    let statement = format!(
+        r#"WITH RECURSIVE "tree"("id", "depth", "name", "type", "path") AS (
+            SELECT e.{pk_col}, 0, {root_name}, e."@type", {root_name} || char(30) || e.{pk_col}
+            FROM {elements_table} AS e
+            WHERE CASE WHEN ?1 IS NULL
+                THEN NOT EXISTS (
+                    SELECT 1 FROM {relations_table} AS r WHERE r."target_id" = e.{pk_col}
+                    AND r."name" IN ({owned_element}, {owned_relationship})
+                )
+                ELSE e.{pk_col} = ?1
+            END
+            UNION ALL
+            SELECT c.{pk_col}, t."depth" + 1, {child_name}, c."@type",
+                t."path" || char(31) || {child_name} || char(30) || c.{pk_col}
+            FROM "tree" AS t
+            JOIN {relations_table} AS r ON r."origin_id" = t."id" AND r."name" = {owned_element}
+            JOIN {elements_table} AS c ON c.{pk_col} = r."target_id"
+            WHERE (?2 IS NULL OR t."depth" < ?2)
+                AND instr(t."path" || char(31), char(30) || c.{pk_col} || char(31)) = 0
+        )
+        SELECT "id", "depth", "name", "type" FROM "tree" ORDER BY "path""#
+    );
+    trace!("prepared the following statement:\n{statement}");
+
+    let mut stmt = conn.prepare(&statement)?;
+    let mut rows = stmt.query((maybe_root_id, maybe_max_depth))?;
+
+    let mut printed = 0;
+    while let Some(row) = rows.next()? {
+        let id: String = row.get(0)?;
+        let depth: usize = row.get(1)?;
+        let name: String = row.get(2)?;
+        let ty: Option<String> = row.get(3)?;
+        let ty = ty.unwrap_or_default();
+
+        if !types.is_empty() && !types.contains(&ty) {
+            continue;
+        }
+
+        let indent = "  ".repeat(depth);
+        writeln!(out, "{indent}{name} [{ty}] {id}")?;
+        printed += 1;
+    }
+    debug!("printed {printed} elements");
+
+    Ok(printed)
+}
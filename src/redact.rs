@@ -0,0 +1,239 @@
+//! Redaction of attributes and pseudonymization of ids during the import, so that dbs can be shared
+//! without proprietary content
+//!
+//! The attributes to redact are given by `--redact` and the `[redact]` section of the config file,
+//! each either blanked or replaced by a hash of its value, see [`RedactMode`]. The structure of the
+//! model is kept intact: no element is dropped, and references to other elements are never
+//! redacted. Hashing keeps equal values equal, e.g. to still spot duplicated documentation, but
+//! values which are easy to guess, like short names, can be recovered by hashing guesses.
+//!
+//! The ids of the elements, which may tell e.g. the tool and organization the model stems from, are
+//! replaced by pseudonyms via `--pseudonymize-ids`, see [`pseudonymization`]. Unlike the hashes of
+//! redacted attributes, the pseudonyms are keyed, so that only the holders of the key can tell the
+//! pseudonym of a known id.
+//!
+//! Imports redacting attributes or pseudonymizing ids are logged as partial, so that the redacted
+//! values and pseudonyms are never pushed back to the server.
+//!
+//! Both are combined by `export-anonymized`, to strip a db of the model content, see
+//! [`anonymization`].
+
+use std::{collections::BTreeMap, sync::Arc};
+
+use eyre::{Result, WrapErr, ensure};
+use hmac::{Hmac, Mac};
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+
+use crate::{
+    cli::RedactMode,
+    config::config,
+    transform::{ElementTransform, Transform},
+};
+
+/// Environment variable holding the key of the pseudonyms of the ids
+const PSEUDONYM_KEY_VAR: &str = "SYSML_PSEUDONYM_KEY";
+
+/// Attribute which repeats the id of the element, in addition to `@id`
+const ELEMENT_ID_ATTR: &str = "elementId";
+
+/// Suffix of the attributes holding the id of another element as string, e.g. `memberElementId`
+const ELEMENT_ID_ATTR_SUFFIX: &str = "ElementId";
+
+/// Attributes holding names and documentation, which `export-anonymized` blanks
+const ANONYMIZED_ATTRS: &[&str] = &[
+    "aliasIds",
+    "body",
+    "declaredName",
+    "declaredShortName",
+    "description",
+    "memberName",
+    "memberShortName",
+    "name",
+    "ownedMemberName",
+    "ownedMemberShortName",
+    "qualifiedName",
+    "reqId",
+    "shortName",
+    "specification",
+];
+
+/// Prefix of the hashes replacing the values of attributes redacted by [`RedactMode::Hash`]
+const HASH_PREFIX: &str = "sha256:";
+
+/// The redaction by the rules of the config file and `cli_rules`, `None` if there are no rules
+///
+/// The rules of the command line take precedence over those of the config file for the same
+/// attribute.
+pub(crate) fn redaction(cli_rules: &[(String, RedactMode)]) -> Result<Option<Transform>> {
+    let mut rules = config().redact.clone();
+    rules.extend(cli_rules.iter().cloned());
+    if rules.is_empty() {
+        return Ok(None);
+    }
+    redaction_by_rules(rules).map(Some)
+}
+
+/// The redaction of the attributes named by `rules`
+fn redaction_by_rules(rules: BTreeMap<String, RedactMode>) -> Result<Transform> {
+    for attribute in rules.keys() {
+        ensure!(
+            !attribute.starts_with('@'),
+            "unable to redact the attribute {attribute:?}, as the import relies on it"
+        );
+    }
+    info!("redacting the attributes {rules:?}");
+    let description = format!(
+        "redacting the attributes {}",
+        rules.keys().cloned().collect::<Vec<_>>().join(", ")
+    );
+    Ok(Transform::new(Arc::new(Redaction(rules))).described(description))
+}
+
+/// The anonymization of `export-anonymized`, stripping the names, documentation and ids
+///
+/// The attributes [`ANONYMIZED_ATTRS`] are blanked, besides those redacted by the config file and
+/// `cli_rules`, and the ids are pseudonymized. The key of the pseudonyms is taken from
+/// `SYSML_PSEUDONYM_KEY` if set, to match the pseudonyms of earlier exports, and drawn at random
+/// otherwise.
+pub(crate) fn anonymization(cli_rules: &[(String, RedactMode)]) -> Result<Transform> {
+    let mut rules: BTreeMap<String, RedactMode> = ANONYMIZED_ATTRS
+        .iter()
+        .map(|attribute| ((*attribute).to_owned(), RedactMode::Blank))
+        .collect();
+    rules.extend(config().redact.clone());
+    rules.extend(cli_rules.iter().cloned());
+    let redaction = redaction_by_rules(rules)?;
+
+    let pseudonymization = match std::env::var_os(PSEUDONYM_KEY_VAR) {
+        Some(_) => pseudonymization()?,
+        None => {
+            let mut key = [0; 32];
+            getrandom::fill(&mut key)
+                .map_err(|e| eyre::eyre!("drawing the key of the pseudonyms: {e}"))?;
+            pseudonymization_by_key(&key)?
+        }
+    };
+    Ok(redaction.then(pseudonymization))
+}
+
+/// Redacts the attributes of the elements by their name, see [`redaction`]
+struct Redaction(BTreeMap<String, RedactMode>);
+
+impl ElementTransform for Redaction {
+    fn transform(&self, mut element: Value) -> Result<Option<Value>> {
+        if let Some(attributes) = element.as_object_mut() {
+            for (attribute, mode) in &self.0 {
+                if let Some(value) = attributes.get_mut(attribute) {
+                    redact_value(value, *mode);
+                }
+            }
+        }
+        Ok(Some(element))
+    }
+}
+
+/// Redacts `value` in place, including the values of arrays
+///
+/// Strings are blanked or hashed. Numbers and booleans are set to `null` in either mode, as their
+/// columns can not hold hashes. Objects refer to other elements and are kept.
+fn redact_value(value: &mut Value, mode: RedactMode) {
+    match value {
+        Value::String(s) => {
+            *s = match mode {
+                RedactMode::Blank => String::new(),
+                RedactMode::Hash => format!("{HASH_PREFIX}{:x}", Sha256::digest(s.as_bytes())),
+            }
+        }
+        Value::Array(values) => values.iter_mut().for_each(|v| redact_value(v, mode)),
+        Value::Bool(_) | Value::Number(_) => *value = Value::Null,
+        Value::Null | Value::Object(_) => {}
+    }
+}
+
+/// The pseudonymization of the ids of the elements, keyed by the variable `SYSML_PSEUDONYM_KEY`
+///
+/// Each id is replaced by a pseudonym derived from the id by HMAC-SHA256 with the key, in the shape
+/// of a UUID of version 8. The ids in the references to other elements and in the attributes
+/// `elementId` and `...ElementId`, e.g. `memberElementId`, are replaced alike, so that the
+/// relations of the db still join. As the pseudonyms
+/// are deterministic, dbs imported with the same key can be joined and compared, and reimporting a
+/// changed model updates the elements in place.
+pub(crate) fn pseudonymization() -> Result<Transform> {
+    let key = std::env::var(PSEUDONYM_KEY_VAR)
+        .wrap_err_with(|| format!("reading the key of the pseudonyms from {PSEUDONYM_KEY_VAR}"))?;
+    ensure!(
+        !key.is_empty(),
+        "the key of the pseudonyms in {PSEUDONYM_KEY_VAR} is empty"
+    );
+
+    pseudonymization_by_key(key.as_bytes())
+}
+
+/// The pseudonymization of the ids of the elements with `key`
+fn pseudonymization_by_key(key: &[u8]) -> Result<Transform> {
+    info!("pseudonymizing the ids of the elements");
+    let mac = Hmac::<Sha256>::new_from_slice(key)?;
+    Ok(Transform::new(Arc::new(Pseudonymization(mac))).described("pseudonymizing the ids"))
+}
+
+/// Replaces the ids of the elements and their references by pseudonyms, see [`pseudonymization`]
+struct Pseudonymization(Hmac<Sha256>);
+
+impl Pseudonymization {
+    /// The pseudonym of `id`, the leading 128 bits of its HMAC formatted as UUID of version 8
+    fn pseudonym(&self, id: &str) -> String {
+        let mut mac = self.0.clone();
+        mac.update(id.as_bytes());
+        let mut bytes: [u8; 16] = mac.finalize().into_bytes()[..16]
+            .try_into()
+            .expect("the HMAC is longer than 16 bytes");
+        bytes[6] = (bytes[6] & 0x0f) | 0x80;
+        bytes[8] = (bytes[8] & 0x3f) | 0x80;
+
+        let hex: String = bytes.iter().map(|b| format!("{b:02x}")).collect();
+        format!(
+            "{}-{}-{}-{}-{}",
+            &hex[..8],
+            &hex[8..12],
+            &hex[12..16],
+            &hex[16..20],
+            &hex[20..]
+        )
+    }
+
+    /// Replaces the ids in the references to other elements within `value`
+    fn pseudonymize_references(&self, value: &mut Value) {
+        match value {
+            Value::Object(reference) => {
+                if let Some(Value::String(id)) = reference.get_mut("@id") {
+                    *id = self.pseudonym(id);
+                }
+            }
+            Value::Array(values) => values
+                .iter_mut()
+                .for_each(|v| self.pseudonymize_references(v)),
+            _ => {}
+        }
+    }
+}
+
+impl ElementTransform for Pseudonymization {
+    fn transform(&self, mut element: Value) -> Result<Option<Value>> {
+        if let Some(attributes) = element.as_object_mut() {
+            for (attribute, value) in attributes.iter_mut() {
+                match value {
+                    Value::String(id)
+                        if attribute == "@id"
+                            || attribute == ELEMENT_ID_ATTR
+                            || attribute.ends_with(ELEMENT_ID_ATTR_SUFFIX) =>
+                    {
+                        *id = self.pseudonym(id)
+                    }
+                    value => self.pseudonymize_references(value),
+                }
+            }
+        }
+        Ok(Some(element))
+    }
+}
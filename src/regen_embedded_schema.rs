@@ -0,0 +1,106 @@
+//! Maintenance of the SQL schema embedded into this tool
+//!
+//! `init-db` initializes dbs with the SQL schema from `assets/schema.sql`, which is baked into the
+//! binary. This module regenerates said asset from a JSON schema, and verifies that the embedded
+//! schema matches the one generated from a JSON schema, so that drift between the asset and the
+//! generator is detected.
+
+use std::{collections::BTreeMap, io::Write, path::Path};
+
+use eyre::{Result, bail};
+use rusqlite::Connection;
+
+use crate::{
+    init_db::EMBEDDED_SCHEMA,
+    json_schema_to_sql::{Root, SchemaOptions, consume_json_schema},
+};
+
+/// Regenerates the embedded schema from a JSON schema, or verifies that it matches
+///
+/// With `maybe_asset`, the SQL schema generated from `schema` is written to the given path. The
+/// tool then has to be rebuilt to embed it. Otherwise, the objects of the embedded schema are
+/// compared against the generated ones, printing objects only present in the embedded schema
+/// (`-`), only present in the generated schema (`+`) and those which differ (`~`). Differences
+/// are an error.
+pub(crate) fn regen_embedded_schema<W: Write>(
+    schema: &Root,
+    options: &SchemaOptions,
+    maybe_asset: Option<&Path>,
+    out: &mut W,
+) -> Result<()> {
+    let generated = consume_json_schema(schema, None, options)?;
+
+    if let Some(asset) = maybe_asset {
+        info!("writing the generated schema to {asset:?}");
+        std::fs::write(asset, &generated)?;
+        info!("rebuild the tool to embed the new schema");
+        return Ok(());
+    }
+
+    let embedded_objects = schema_objects(EMBEDDED_SCHEMA)?;
+    let generated_objects = schema_objects(&generated)?;
+
+    let mut differences = 0;
+    for (object, embedded_sql) in &embedded_objects {
+        match generated_objects.get(object) {
+            None => writeln!(out, "- {} {}", object.0, object.1)?,
+            Some(generated_sql) if generated_sql != embedded_sql => {
+                writeln!(out, "~ {} {}", object.0, object.1)?
+            }
+            Some(_) => continue,
+        }
+        differences += 1;
+    }
+    for object in generated_objects.keys() {
+        if !embedded_objects.contains_key(object) {
+            writeln!(out, "+ {} {}", object.0, object.1)?;
+            differences += 1;
+        }
+    }
+
+    if differences != 0 {
+        bail!(
+            "the embedded schema differs from the generated one in {differences} objects; regenerate the embedded schema"
+        );
+    }
+
+    info!("the embedded schema matches the generated one");
+    Ok(())
+}
+
+/// Loads a SQL schema into an in-memory db, returning its objects keyed by their type and name
+///
+/// The SQL of each object is normalized by removing whitespace outside of quotes, so that
+/// differently formatted but otherwise identical statements compare equal.
+fn schema_objects(sql: &str) -> Result<BTreeMap<(String, String), String>> {
+    let conn = Connection::open_in_memory()?;
+    conn.execute_batch(sql)?;
+
+    let mut stmt =
+        conn.prepare("SELECT type, name, sql FROM sqlite_schema WHERE sql IS NOT NULL")?;
+    let objects = stmt
+        .query_map((), |row| {
+            let sql: String = row.get(2)?;
+            Ok(((row.get(0)?, row.get(1)?), normalize_sql(&sql)))
+        })?
+        .collect::<Result<_, _>>()?;
+
+    Ok(objects)
+}
+
+/// Removes all whitespace outside of quoted identifiers and string literals
+fn normalize_sql(sql: &str) -> String {
+    let mut normalized = String::with_capacity(sql.len());
+    let mut quote = None;
+    for c in sql.chars() {
+        match (quote, c) {
+            (Some(q), c) if c == q => quote = None,
+            (Some(_), _) => {}
+            (None, '"' | '\'' | '`') => quote = Some(c),
+            (None, c) if c.is_whitespace() => continue,
+            _ => {}
+        }
+        normalized.push(c);
+    }
+    normalized
+}
@@ -0,0 +1,355 @@
+//! Static report about the model in a db, rendered from a template
+//!
+//! The report is meant to publish snapshots of a model to people who do not work with the db. The
+//! built-in templates render Markdown or HTML; custom templates use the
+//! [MiniJinja](https://docs.rs/minijinja/latest/minijinja/syntax/index.html) syntax and receive the
+//! context described by [`Report`].
+
+use std::{
+    collections::{BTreeMap, HashMap},
+    io::Write,
+    path::Path,
+};
+
+use eyre::{Context, Result};
+use rusqlite::Connection;
+
+use crate::{
+    cli::ReportFormat,
+    config::{ELEMENT_PK_COL, OWNED_ELEMENT_RELATION, config},
+    query::{
+        PACKAGE_TYPES, REQUIREMENT_TYPES, closest_owner, element_label_expr, element_name_expr,
+        read_owners,
+    },
+    util::{escape_sql_ident, escape_sql_str_lit},
+};
+
+/// Built-in template rendering Markdown
+const MARKDOWN_TEMPLATE: &str = include_str!("../assets/report.md.jinja");
+
+/// Built-in template rendering a standalone HTML page
+const HTML_TEMPLATE: &str = include_str!("../assets/report.html.jinja");
+
+/// Relations from an element to its documentation
+const DOCUMENTATION_RELATIONS: [&str; 2] = ["documentation", OWNED_ELEMENT_RELATION];
+
+/// Type of the elements holding documentation text in their `body`
+const DOCUMENTATION_TYPE: &str = "Documentation";
+
+/// The context the template is rendered with
+#[derive(Debug, Clone, serde::Serialize)]
+struct Report {
+    /// File name of the db, absent for in-memory dbs
+    database: Option<String>,
+
+    /// Basic metrics of the model
+    metrics: Metrics,
+
+    /// The packages which are not owned by another package, each with its nested packages
+    packages: Vec<Package>,
+
+    /// The elements of each type, ordered by type
+    inventories: Vec<Inventory>,
+
+    /// All requirements, ordered by label
+    requirements: Vec<Requirement>,
+}
+
+/// Basic metrics of the model
+#[derive(Debug, Clone, Default, serde::Serialize)]
+struct Metrics {
+    elements: u64,
+    relations: u64,
+    types: u64,
+    packages: u64,
+    requirements: u64,
+
+    /// Number of requirements with at least one non-empty documentation
+    documented_requirements: u64,
+}
+
+/// A package together with the packages nested in it
+#[derive(Debug, Clone, serde::Serialize)]
+struct Package {
+    id: String,
+    name: String,
+    label: String,
+    r#type: String,
+
+    /// Number of elements directly owned by the package
+    owned_elements: u64,
+
+    /// Packages nested in this package, ordered by name
+    children: Vec<Package>,
+}
+
+/// All elements of one type
+#[derive(Debug, Clone, serde::Serialize)]
+struct Inventory {
+    r#type: String,
+    count: u64,
+
+    /// The elements, ordered by label
+    elements: Vec<Element>,
+}
+
+/// An element listed in an inventory
+#[derive(Debug, Clone, serde::Serialize)]
+struct Element {
+    id: String,
+    label: String,
+}
+
+/// A requirement together with its documentation
+#[derive(Debug, Clone, serde::Serialize)]
+struct Requirement {
+    id: String,
+    label: String,
+    r#type: String,
+
+    /// The user-defined id of the requirement, if any
+    req_id: Option<String>,
+
+    /// The bodies of the documentation of the requirement
+    documentation: Vec<String>,
+}
+
+/// Render a report about the model in the db
+///
+/// The report consists of the package tree, the elements of each type, the requirements with their
+/// documentation and some basic metrics. Unless a custom template is given, the built-in template
+/// for `format` is used. Custom templates are HTML-escaped if their file name ends in `.html`,
+/// `.htm` or `.xml`.
+pub(crate) fn report<W: Write>(
+    conn: &Connection,
+    format: ReportFormat,
+    maybe_template: Option<&Path>,
+    out: &mut W,
+) -> Result<()> {
+    let (name, source) = match maybe_template {
+        Some(path) => (
+            path.file_name()
+                .unwrap_or_default()
+                .to_string_lossy()
+                .into_owned(),
+            std::fs::read_to_string(path)
+                .wrap_err_with(|| format!("unable to read the template {path:?}"))?,
+        ),
+        None => match format {
+            ReportFormat::Markdown => ("report.md".to_owned(), MARKDOWN_TEMPLATE.to_owned()),
+            ReportFormat::Html => ("report.html".to_owned(), HTML_TEMPLATE.to_owned()),
+        },
+    };
+
+    let mut env = minijinja::Environment::new();
+    env.set_trim_blocks(true);
+    env.set_lstrip_blocks(true);
+    env.set_keep_trailing_newline(true);
+    let template = env.template_from_named_str(&name, &source)?;
+
+    let report = read_report(conn)?;
+    debug!(
+        "rendering the report of {} elements with the template {name:?}",
+        report.metrics.elements
+    );
+    out.write_all(template.render(&report)?.as_bytes())?;
+
+    Ok(())
+}
+
+/// Gather the context of the report from the db
+fn read_report(conn: &Connection) -> Result<Report> {
+    let elements_table = escape_sql_ident(&config().elements_table);
+    let relations_table = escape_sql_ident(&config().relations_table);
+    let pk_col = escape_sql_ident(ELEMENT_PK_COL);
+    let label = element_label_expr("e");
+
+    let mut metrics = Metrics {
+        relations: conn.query_row(
+            &format!("SELECT COUNT(*) FROM {relations_table}"),
+            (),
+            |row| row.get(0),
+        )?,
+        ..Default::default()
+    };
+
+    let mut inventories: BTreeMap<String, Inventory> = BTreeMap::new();
+    let mut stmt = conn.prepare(&format!(
+        r#"SELECT e.{pk_col}, {label}, COALESCE(e."@type", '(unknown type)')
+        FROM {elements_table} AS e ORDER BY 2, 1"#
+    ))?;
+    let mut rows = stmt.query(())?;
+    while let Some(row) = rows.next()? {
+        let ty: String = row.get(2)?;
+        let inventory = inventories.entry(ty.clone()).or_insert_with(|| Inventory {
+            r#type: ty,
+            count: 0,
+            elements: Vec::new(),
+        });
+        inventory.count += 1;
+        inventory.elements.push(Element {
+            id: row.get(0)?,
+            label: row.get(1)?,
+        });
+    }
+    metrics.elements = inventories.values().map(|inventory| inventory.count).sum();
+    metrics.types = inventories.len() as u64;
+
+    let packages = read_package_tree(conn)?;
+    metrics.packages = count_packages(&packages);
+
+    let requirements = read_requirements(conn)?;
+    metrics.requirements = requirements.len() as u64;
+    metrics.documented_requirements = requirements
+        .iter()
+        .filter(|requirement| !requirement.documentation.is_empty())
+        .count() as u64;
+
+    Ok(Report {
+        database: conn
+            .path()
+            .filter(|path| !path.is_empty())
+            .and_then(|path| Path::new(path).file_name())
+            .map(|file_name| file_name.to_string_lossy().into_owned()),
+        metrics,
+        packages,
+        inventories: inventories.into_values().collect(),
+        requirements,
+    })
+}
+
+/// Read all packages and nest each in the closest package owning it
+fn read_package_tree(conn: &Connection) -> Result<Vec<Package>> {
+    let elements_table = escape_sql_ident(&config().elements_table);
+    let pk_col = escape_sql_ident(ELEMENT_PK_COL);
+    let package_types = sql_str_list(&PACKAGE_TYPES);
+    let name = element_name_expr("e");
+    let label = element_label_expr("e");
+
+    let owners = read_owners(conn)?;
+    let mut owned_counts: HashMap<&String, u64> = HashMap::new();
+    for owner in owners.values() {
+        *owned_counts.entry(owner).or_default() += 1;
+    }
+
+    let mut packages: HashMap<String, Package> = HashMap::new();
+    let mut stmt = conn.prepare(&format!(
+        r#"SELECT e.{pk_col}, {name}, {label}, e."@type" FROM {elements_table} AS e
+        WHERE e."@type" IN ({package_types})"#
+    ))?;
+    let mut rows = stmt.query(())?;
+    while let Some(row) = rows.next()? {
+        let id: String = row.get(0)?;
+        packages.insert(
+            id.clone(),
+            Package {
+                owned_elements: owned_counts.get(&id).copied().unwrap_or_default(),
+                id,
+                name: row.get(1)?,
+                label: row.get(2)?,
+                r#type: row.get(3)?,
+                children: Vec::new(),
+            },
+        );
+    }
+
+    let parents: HashMap<&String, &String> = packages
+        .keys()
+        .filter_map(|id| Some((id, closest_owner(&owners, &packages, id)?)))
+        .collect();
+
+    let mut children: HashMap<String, Vec<String>> = HashMap::new();
+    let mut roots = Vec::new();
+    for id in packages.keys() {
+        match parents.get(id) {
+            Some(&parent) => children.entry(parent.clone()).or_default().push(id.clone()),
+            None => roots.push(id.clone()),
+        }
+    }
+
+    Ok(nest_packages(&roots, &mut packages, &children))
+}
+
+/// Take the packages with the given ids out of `packages`, each with its nested packages
+fn nest_packages(
+    ids: &[String],
+    packages: &mut HashMap<String, Package>,
+    children: &HashMap<String, Vec<String>>,
+) -> Vec<Package> {
+    let mut nested: Vec<Package> = ids
+        .iter()
+        .filter_map(|id| {
+            let mut package = packages.remove(id)?;
+            if let Some(child_ids) = children.get(id) {
+                package.children = nest_packages(child_ids, packages, children);
+            }
+            Some(package)
+        })
+        .collect();
+    nested.sort_by(|a, b| (&a.name, &a.id).cmp(&(&b.name, &b.id)));
+    nested
+}
+
+/// Count the packages of a package tree
+fn count_packages(packages: &[Package]) -> u64 {
+    packages
+        .iter()
+        .map(|package| 1 + count_packages(&package.children))
+        .sum()
+}
+
+/// Read all requirements together with their documentation
+fn read_requirements(conn: &Connection) -> Result<Vec<Requirement>> {
+    let elements_table = escape_sql_ident(&config().elements_table);
+    let relations_table = escape_sql_ident(&config().relations_table);
+    let pk_col = escape_sql_ident(ELEMENT_PK_COL);
+    let requirement_types = sql_str_list(&REQUIREMENT_TYPES);
+    let documentation_relations = sql_str_list(&DOCUMENTATION_RELATIONS);
+    let documentation_type = escape_sql_str_lit(DOCUMENTATION_TYPE);
+    let label = element_label_expr("e");
+
+    let mut documentation: HashMap<String, Vec<String>> = HashMap::new();
+    let mut stmt = conn.prepare(&format!(
+        r#"SELECT DISTINCT r."origin_id", d.{pk_col}, d."body"
+        FROM {relations_table} AS r
+        JOIN {elements_table} AS d ON d.{pk_col} = r."target_id"
+        WHERE r."name" IN ({documentation_relations}) AND d."@type" = {documentation_type}
+            AND TRIM(COALESCE(d."body", '')) != ''
+        ORDER BY 1, 2"#
+    ))?;
+    let mut rows = stmt.query(())?;
+    while let Some(row) = rows.next()? {
+        documentation
+            .entry(row.get(0)?)
+            .or_default()
+            .push(row.get(2)?);
+    }
+
+    let mut stmt = conn.prepare(&format!(
+        r#"SELECT e.{pk_col}, {label}, e."@type", e."reqId" FROM {elements_table} AS e
+        WHERE e."@type" IN ({requirement_types}) ORDER BY 2, 1"#
+    ))?;
+    let requirements = stmt
+        .query_map((), |row| {
+            let id: String = row.get(0)?;
+            Ok(Requirement {
+                documentation: documentation.remove(&id).unwrap_or_default(),
+                id,
+                label: row.get(1)?,
+                r#type: row.get(2)?,
+                req_id: row.get(3)?,
+            })
+        })?
+        .collect::<Result<_, _>>()?;
+
+    Ok(requirements)
+}
+
+/// Comma separated list of SQL string literals
+fn sql_str_list(values: &[&str]) -> String {
+    values
+        .iter()
+        .map(escape_sql_str_lit)
+        .collect::<Vec<_>>()
+        .join(", ")
+}
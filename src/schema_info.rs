@@ -0,0 +1,163 @@
+//! Bookkeeping about the schema a database was initialized with
+//!
+//! When a database is initialized, it is stamped with a fingerprint of the layout of its core
+//! tables. Imports verify this stamp, so that a database built with another schema than the one
+//! shipped with this tool is refused with a clear error, instead of failing somewhere in the middle
+//! of the import.
+
+use eyre::Result;
+use rusqlite::{Connection, OptionalExtension};
+
+use crate::{
+    config::{OWNER_COL_DEF, SCHEMA_INFO_TABLE, config},
+    failure::Failure,
+    util::{escape_sql_ident, escape_sql_str_lit, table_exists},
+};
+
+/// Where the schema of a database originates from
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum SchemaOrigin {
+    /// The schema embedded into this tool, used by `init-db`
+    Embedded,
+
+    /// A schema generated from a JSON schema via `json-schema-to-sql-schema`
+    Generated,
+}
+
+impl SchemaOrigin {
+    fn as_str(self) -> &'static str {
+        match self {
+            SchemaOrigin::Embedded => "embedded",
+            SchemaOrigin::Generated => "generated",
+        }
+    }
+}
+
+/// Computes the fingerprint of the layout of the core tables in a database
+///
+/// The fingerprint is a FNV-1a hash over the `CREATE` statements of the elements, relations and
+/// extended properties tables, as recorded by SQLite with runs of whitespace collapsed. The owner
+/// column, which is added to the elements table on demand, is left out, see [`crate::owner`]. It is
+/// not meant to be cryptographically secure, only to detect differing schemas.
+pub(crate) fn schema_fingerprint(conn: &Connection) -> Result<String> {
+    let config = config();
+    let mut stmt = conn.prepare(
+        "SELECT name, sql FROM sqlite_schema WHERE type = 'table' AND name IN (?1, ?2, ?3) ORDER BY name",
+    )?;
+    let mut rows = stmt.query([
+        &config.elements_table,
+        &config.relations_table,
+        &config.extended_table,
+    ])?;
+
+    let mut hash: u64 = 0xcbf2_9ce4_8422_2325;
+    while let Some(row) = rows.next()? {
+        let name: String = row.get(0)?;
+        let sql: String = row.get(1)?;
+        let mut sql = sql.split_whitespace().collect::<Vec<_>>().join(" ");
+        if name == config.elements_table {
+            sql = sql.replacen(&format!(", {})", OWNER_COL_DEF), ")", 1);
+        }
+        for byte in sql.bytes().chain(std::iter::once(0)) {
+            hash ^= u64::from(byte);
+            hash = hash.wrapping_mul(0x0000_0100_0000_01b3);
+        }
+    }
+
+    Ok(format!("{hash:016x}"))
+}
+
+/// Computes the fingerprint of the schema embedded into this tool
+pub(crate) fn embedded_schema_fingerprint() -> Result<String> {
+    let conn = Connection::open_in_memory()?;
+    conn.execute_batch(&crate::init_db::embedded_schema())?;
+    schema_fingerprint(&conn)
+}
+
+/// Stamps the database with the fingerprint of its current schema
+pub(crate) fn stamp_schema(conn: &Connection, origin: SchemaOrigin) -> Result<()> {
+    let fingerprint = schema_fingerprint(conn)?;
+    debug!("stamping the db with the schema fingerprint {fingerprint}");
+    conn.execute_batch(&stamp_statements(&fingerprint, origin))?;
+    Ok(())
+}
+
+/// The statements stamping a database with `fingerprint`, for scripts applied to it later
+pub(crate) fn stamp_statements(fingerprint: &str, origin: SchemaOrigin) -> String {
+    let table = escape_sql_ident(SCHEMA_INFO_TABLE);
+    format!(
+        r#"CREATE TABLE IF NOT EXISTS {table} (
+    "key" TEXT PRIMARY KEY,
+    "value" TEXT NOT NULL
+) STRICT, WITHOUT ROWID;
+INSERT OR REPLACE INTO {table} ("key", "value") VALUES ('fingerprint', {}), ('origin', {});
+"#,
+        escape_sql_str_lit(fingerprint),
+        escape_sql_str_lit(origin.as_str())
+    )
+}
+
+/// Reads a value from the schema info table, if present
+pub(crate) fn read_schema_info(conn: &Connection, key: &str) -> Result<Option<String>> {
+    if !table_exists(conn, SCHEMA_INFO_TABLE)? {
+        return Ok(None);
+    }
+
+    let table = escape_sql_ident(SCHEMA_INFO_TABLE);
+    let value = conn
+        .query_row(
+            &format!(r#"SELECT "value" FROM {table} WHERE "key" = ?1"#),
+            [key],
+            |row| row.get(0),
+        )
+        .optional()?;
+    Ok(value)
+}
+
+/// Verifies that the database matches the schema it was stamped with
+///
+/// The schema version must match the one of this tool, see [`crate::upgrade_db`]. Databases
+/// initialized from the embedded schema must additionally match the schema embedded into this
+/// version of the tool. Databases without stamp are accepted with a warning. In any case, the core
+/// tables must exist.
+pub(crate) fn verify_schema(conn: &Connection) -> Result<()> {
+    let config = config();
+    for (kind, table) in [
+        ("elements", &config.elements_table),
+        ("relations", &config.relations_table),
+        ("extended properties", &config.extended_table),
+    ] {
+        if !table_exists(conn, table)? {
+            return Err(Failure::SchemaMismatch.report(format!(
+                "the db lacks the {kind} table {table:?}; initialize it via init-db, or check the table names of the config"
+            )));
+        }
+    }
+
+    crate::upgrade_db::check_schema_version(conn)?;
+
+    let Some(stamped) = read_schema_info(conn, "fingerprint")? else {
+        warn!("the db carries no schema fingerprint, unable to verify its schema");
+        return Ok(());
+    };
+
+    let actual = schema_fingerprint(conn)?;
+    if actual != stamped {
+        return Err(Failure::SchemaMismatch.report(format!(
+            "the tables of the db were altered since it was initialized (schema fingerprint {actual}, expected {stamped})"
+        )));
+    }
+
+    let origin = read_schema_info(conn, "origin")?;
+    if origin.as_deref() == Some(SchemaOrigin::Embedded.as_str()) {
+        let embedded = embedded_schema_fingerprint()?;
+        if stamped != embedded {
+            return Err(Failure::SchemaMismatch.report(format!(
+                "the db was initialized with a different schema than the one embedded into this version of the tool (schema fingerprint {stamped}, expected {embedded}); re-initialize the db or skip this check"
+            )));
+        }
+    }
+
+    debug!("the db matches the schema fingerprint {stamped}");
+    Ok(())
+}
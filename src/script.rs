@@ -0,0 +1,236 @@
+//! Execution of SQL scripts against the db, printing the result sets
+//!
+//! This is the glue for shell automation: scripts are run statement by statement, each statement
+//! returning rows has its result set printed in the chosen format, and the first failing
+//! statement aborts the script with an exit code of its own, see [`Failure::Sql`].
+
+use std::{collections::HashSet, io::Write};
+
+use eyre::Result;
+use rusqlite::{
+    Batch, Connection,
+    types::{Value, ValueRef},
+};
+
+use crate::{cli::ResultFormat, failure::Failure, util::escape_csv};
+
+/// A result set, i.e. the rows returned by one statement
+struct ResultSet {
+    columns: Vec<String>,
+    rows: Vec<Vec<serde_json::Value>>,
+}
+
+/// Run the SQL script `sql`, printing the result set of each statement returning rows
+///
+/// The statements are executed in order, each in its own transaction unless the script manages
+/// transactions itself. Hence the statements before a failing statement remain in effect. Result
+/// sets are separated by an empty line, in JSON each is an array of objects on a line of its own.
+/// Returns the number of statements executed.
+///
+/// Named parameters like `:name`, `@name` or `$name` in the statements are bound to the values of
+/// `params` of the same name. Statements with parameters lacking a value are refused, instead of
+/// silently binding `NULL`.
+pub(crate) fn run_script<W: Write>(
+    conn: &Connection,
+    sql: &str,
+    params: &[(String, Value)],
+    format: ResultFormat,
+    out: &mut W,
+) -> Result<usize> {
+    let mut batch = Batch::new(conn, sql);
+    let mut used_params = HashSet::new();
+    let mut executed = 0;
+    let mut result_sets = 0;
+    loop {
+        let n = executed + 1;
+        let mut stmt = match batch.next() {
+            Ok(Some(stmt)) => stmt,
+            Ok(None) => break,
+            Err(e) => return Err(statement_failure(n, e)),
+        };
+        for idx in 1..=stmt.parameter_count() {
+            let Some(param_name) = stmt.parameter_name(idx).map(str::to_owned) else {
+                return Err(Failure::Sql.report(format!(
+                    "statement {n} of the script has a positional parameter, only named parameters can be bound"
+                )));
+            };
+            let name = &param_name[1..];
+            let Some((_, value)) = params.iter().find(|(n, _)| n == name) else {
+                return Err(Failure::Sql.report(format!(
+                    "statement {n} of the script refers to the parameter {param_name:?}, which is not given"
+                )));
+            };
+            stmt.raw_bind_parameter(idx, value)?;
+            used_params.insert(name.to_owned());
+        }
+        trace!(
+            "executing statement {n}:\n{}",
+            stmt.expanded_sql().unwrap_or_default()
+        );
+
+        let columns: Vec<String> = stmt.column_names().into_iter().map(String::from).collect();
+        if columns.is_empty() {
+            let changed = stmt.raw_execute().map_err(|e| statement_failure(n, e))?;
+            debug!("statement {n} changed {changed} rows");
+        } else {
+            let mut rows = Vec::new();
+            let mut query = stmt.raw_query();
+            while let Some(row) = query.next().map_err(|e| statement_failure(n, e))? {
+                rows.push(
+                    (0..columns.len())
+                        .map(|idx| row.get_ref(idx).map(json_value))
+                        .collect::<Result<_, _>>()?,
+                );
+            }
+
+            if result_sets > 0 && !matches!(format, ResultFormat::Json) {
+                writeln!(out)?;
+            }
+            write_result_set(&ResultSet { columns, rows }, format, out)?;
+            result_sets += 1;
+        }
+        executed = n;
+    }
+    debug!("executed {executed} statements, which returned {result_sets} result sets");
+    for (name, _) in params {
+        if !used_params.contains(name) {
+            warn!("the parameter {name:?} is not used by the script");
+        }
+    }
+
+    Ok(executed)
+}
+
+/// The error report for the failure of the `n`th statement of a script
+fn statement_failure(n: usize, e: rusqlite::Error) -> eyre::Report {
+    Failure::Sql.report(format!("statement {n} of the script failed: {e}"))
+}
+
+/// Convert a value of the db to JSON, BLOBs become hex strings
+fn json_value(value: ValueRef) -> serde_json::Value {
+    match value {
+        ValueRef::Null => serde_json::Value::Null,
+        ValueRef::Integer(i) => i.into(),
+        ValueRef::Real(f) => f.into(),
+        ValueRef::Text(t) => String::from_utf8_lossy(t).into(),
+        ValueRef::Blob(b) => b
+            .iter()
+            .map(|byte| format!("{byte:02x}"))
+            .collect::<String>()
+            .into(),
+    }
+}
+
+/// Render a value as text, `NULL` becoming `null_text`
+fn text_value(value: &serde_json::Value, null_text: &str) -> String {
+    match value {
+        serde_json::Value::Null => null_text.to_owned(),
+        serde_json::Value::String(s) => s.to_owned(),
+        other => other.to_string(),
+    }
+}
+
+/// Write a result set in the given format
+fn write_result_set<W: Write>(
+    result_set: &ResultSet,
+    format: ResultFormat,
+    out: &mut W,
+) -> Result<()> {
+    let ResultSet { columns, rows } = result_set;
+    match format {
+        ResultFormat::Table => {
+            let cells: Vec<Vec<String>> = rows
+                .iter()
+                .map(|row| row.iter().map(|v| text_value(v, "NULL")).collect())
+                .collect();
+            let widths: Vec<usize> = columns
+                .iter()
+                .enumerate()
+                .map(|(idx, column)| {
+                    cells
+                        .iter()
+                        .map(|row| row[idx].chars().count())
+                        .chain(std::iter::once(column.chars().count()))
+                        .max()
+                        .unwrap_or_default()
+                })
+                .collect();
+            let write_line = |out: &mut W, line: Vec<String>| -> Result<()> {
+                writeln!(out, "{}", line.join(" | ").trim_end())?;
+                Ok(())
+            };
+            let pad =
+                |s: &str, width: usize| format!("{s}{}", " ".repeat(width - s.chars().count()));
+
+            write_line(
+                out,
+                columns
+                    .iter()
+                    .zip(&widths)
+                    .map(|(c, w)| pad(c, *w))
+                    .collect(),
+            )?;
+            write_line(out, widths.iter().map(|w| "-".repeat(*w)).collect())?;
+            for row in &cells {
+                write_line(
+                    out,
+                    row.iter().zip(&widths).map(|(c, w)| pad(c, *w)).collect(),
+                )?;
+            }
+        }
+        ResultFormat::Tsv => {
+            // tabs and line breaks inside of values are escaped, so that each row is one line
+            let escape = |s: String| {
+                s.replace('\\', "\\\\")
+                    .replace('\t', "\\t")
+                    .replace('\n', "\\n")
+                    .replace('\r', "\\r")
+            };
+            writeln!(
+                out,
+                "{}",
+                columns
+                    .iter()
+                    .cloned()
+                    .map(escape)
+                    .collect::<Vec<_>>()
+                    .join("\t")
+            )?;
+            for row in rows {
+                let line: Vec<_> = row.iter().map(|v| escape(text_value(v, ""))).collect();
+                writeln!(out, "{}", line.join("\t"))?;
+            }
+        }
+        ResultFormat::Csv => {
+            writeln!(
+                out,
+                "{}",
+                columns.iter().map(escape_csv).collect::<Vec<_>>().join(",")
+            )?;
+            for row in rows {
+                let line: Vec<_> = row.iter().map(|v| escape_csv(text_value(v, ""))).collect();
+                writeln!(out, "{}", line.join(","))?;
+            }
+        }
+        ResultFormat::Json => {
+            // the objects are assembled by hand, as maps of serde_json do not keep the order of
+            // the columns
+            let objects = rows
+                .iter()
+                .map(|row| {
+                    let members = columns
+                        .iter()
+                        .zip(row)
+                        .map(|(column, value)| {
+                            Ok(format!("{}:{}", serde_json::to_string(column)?, value))
+                        })
+                        .collect::<Result<Vec<_>, serde_json::Error>>()?;
+                    Ok(format!("{{{}}}", members.join(",")))
+                })
+                .collect::<Result<Vec<_>, serde_json::Error>>()?;
+            writeln!(out, "[{}]", objects.join(","))?;
+        }
+    }
+
+    Ok(())
+}
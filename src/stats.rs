@@ -0,0 +1,126 @@
+//! Statistics about the model in a db and the db itself
+
+use std::{collections::BTreeMap, io::Write};
+
+use eyre::Result;
+use rusqlite::Connection;
+
+use crate::{
+    config::{ELEMENT_PK_COL, PROJECT_COL, config},
+    import::get_table_columns,
+    util::escape_sql_ident,
+};
+
+/// Statistics about the model in a db and the db itself
+#[derive(Debug, Clone, Default, serde::Serialize)]
+struct Stats {
+    /// Number of elements per `@type`
+    elements_by_type: BTreeMap<String, u64>,
+
+    /// Number of relations per name
+    relations_by_name: BTreeMap<String, u64>,
+
+    /// Number of extended property values per property
+    extended_properties: BTreeMap<String, u64>,
+
+    /// Size of the db in bytes
+    db_size: u64,
+
+    /// Size of each index in bytes, absent if SQLite lacks the `dbstat` virtual table
+    index_sizes: Option<BTreeMap<String, u64>>,
+}
+
+/// Print statistics about the model in the db and the db itself
+///
+/// These are the number of elements per type, of relations per name and of extended property values
+/// per property, as well as the size of the db and of each of its indices. With `json`, the
+/// statistics are printed as JSON object instead.
+pub(crate) fn stats<W: Write>(conn: &Connection, json: bool, out: &mut W) -> Result<()> {
+    let config = config();
+    let elements_table = escape_sql_ident(&config.elements_table);
+    let relations_table = escape_sql_ident(&config.relations_table);
+    let extended_table = escape_sql_ident(&config.extended_table);
+
+    let mut stats = Stats {
+        elements_by_type: count_grouped(
+            conn,
+            &format!(
+                r#"SELECT COALESCE("@type", '(unknown type)'), COUNT(*) FROM {elements_table} GROUP BY 1"#
+            ),
+        )?,
+        relations_by_name: count_grouped(
+            conn,
+            &format!(r#"SELECT "name", COUNT(*) FROM {relations_table} GROUP BY 1"#),
+        )?,
+        ..Default::default()
+    };
+
+    for (column, _) in get_table_columns(conn, &config.extended_table)? {
+        if column == ELEMENT_PK_COL || column == PROJECT_COL {
+            continue;
+        }
+        let count = conn.query_row(
+            &format!(
+                "SELECT COUNT({}) FROM {extended_table}",
+                escape_sql_ident(&column)
+            ),
+            (),
+            |row| row.get(0),
+        )?;
+        stats.extended_properties.insert(column, count);
+    }
+
+    let page_count: u64 = conn.pragma_query_value(None, "page_count", |row| row.get(0))?;
+    let page_size: u64 = conn.pragma_query_value(None, "page_size", |row| row.get(0))?;
+    stats.db_size = page_count * page_size;
+
+    // the dbstat virtual table is an optional feature of SQLite
+    stats.index_sizes = match count_grouped(
+        conn,
+        "SELECT name, SUM(pgsize) FROM dbstat
+        WHERE name IN (SELECT name FROM sqlite_schema WHERE type = 'index')
+        GROUP BY name",
+    ) {
+        Ok(index_sizes) => Some(index_sizes),
+        Err(e) => {
+            warn!("unable to determine the size of the indices: {e}");
+            None
+        }
+    };
+
+    if json {
+        serde_json::to_writer_pretty(&mut *out, &stats)?;
+        writeln!(out)?;
+        return Ok(());
+    }
+
+    let sections = [
+        ("elements by type", &stats.elements_by_type),
+        ("relations by name", &stats.relations_by_name),
+        ("extended properties", &stats.extended_properties),
+    ];
+    for (title, counts) in sections {
+        writeln!(out, "# {title}")?;
+        for (name, count) in counts {
+            writeln!(out, "{count}\t{name}")?;
+        }
+        writeln!(out, "{}\t(total)\n", counts.values().sum::<u64>())?;
+    }
+
+    writeln!(out, "# size in bytes")?;
+    writeln!(out, "{}\t(db)", stats.db_size)?;
+    for (index, size) in stats.index_sizes.iter().flatten() {
+        writeln!(out, "{size}\t{index}")?;
+    }
+
+    Ok(())
+}
+
+/// Runs a query yielding a name and a number per row, collecting the rows into a map
+fn count_grouped(conn: &Connection, query: &str) -> Result<BTreeMap<String, u64>> {
+    let mut stmt = conn.prepare(query)?;
+    let counts = stmt
+        .query_map((), |row| Ok((row.get(0)?, row.get(1)?)))?
+        .collect::<Result<_, _>>()?;
+    Ok(counts)
+}
@@ -0,0 +1,116 @@
+//! Integer surrogate keys of the elements, and a copy of the relations using them
+//!
+//! Joining over integers is significantly faster than joining over the TEXT `"@id"`s, which
+//! matters for analytical queries over hundreds of millions of relations. Once added via
+//! `--integer-keys`, each import and edit keeps the keys and the keyed relations up to date.
+
+use eyre::Result;
+use rusqlite::Connection;
+
+use crate::{
+    config::{ELEMENT_KEYS_TABLE, ELEMENT_PK_COL, KEYED_RELATIONS_TABLE, config},
+    util::{escape_sql_ident, table_exists},
+};
+
+/// Maintains integer surrogate keys for all elements and a copy of the relations table using them
+///
+/// Each element gets an integer key assigned exactly once, keys of elements which are re-imported
+/// remain stable. Keys of elements which were deleted, e.g. by pruning, are deleted as well. If the
+/// query `maybe_changed_elements` is given, only the keys of the elements it selects and the keyed
+/// relations from and to them are updated, as only the relations originating from these elements
+/// changed. Otherwise, e.g. when the keys are added or after pruning, everything is rebuilt.
+pub(crate) fn update_surrogate_keys(
+    conn: &Connection,
+    maybe_changed_elements: Option<&str>,
+) -> Result<()> {
+    let now = std::time::Instant::now();
+
+    let keys_table = escape_sql_ident(ELEMENT_KEYS_TABLE);
+    let keyed_relations_table = escape_sql_ident(KEYED_RELATIONS_TABLE);
+    let elements_table = escape_sql_ident(&config().elements_table);
+    let relations_table = escape_sql_ident(&config().relations_table);
+    let pk_col = escape_sql_ident(ELEMENT_PK_COL);
+
+    // keys which are just added have to be computed for all elements
+    let maybe_changed_elements = if table_exists(conn, KEYED_RELATIONS_TABLE)? {
+        maybe_changed_elements
+    } else {
+        None
+    };
+
+    info!("updating integer surrogate keys");
+    conn.execute_batch(&format!(
+        r#"
+        CREATE TABLE IF NOT EXISTS {keys_table} (
+            "key" INTEGER PRIMARY KEY,
+            {pk_col} TEXT NOT NULL UNIQUE
+        ) STRICT;
+
+        CREATE TABLE IF NOT EXISTS {keyed_relations_table} (
+            "name" TEXT NOT NULL,
+            "origin_key" INTEGER NOT NULL REFERENCES {keys_table}("key"),
+            "target_key" INTEGER NOT NULL REFERENCES {keys_table}("key"),
+            PRIMARY KEY("name", "origin_key", "target_key")
+        ) STRICT, WITHOUT ROWID;
+
+        CREATE INDEX IF NOT EXISTS "{KEYED_RELATIONS_TABLE}.target_key"
+            ON {keyed_relations_table}("target_key");
+        "#
+    ))?;
+
+    match maybe_changed_elements {
+        Some(changed_elements) => {
+            // relations to a changed element may only now find its key, or lose it
+            conn.execute_batch(&format!(
+                r#"
+                CREATE TEMPORARY TABLE "keyed_elements"("id" TEXT PRIMARY KEY);
+                INSERT OR IGNORE INTO "keyed_elements" {changed_elements};
+                CREATE TEMPORARY TABLE "changed_keys"("key" INTEGER PRIMARY KEY);
+                INSERT INTO "changed_keys"
+                    SELECT "key" FROM {keys_table}
+                    WHERE {pk_col} IN (SELECT "id" FROM "keyed_elements");
+
+                DELETE FROM {keyed_relations_table}
+                    WHERE "origin_key" IN (SELECT "key" FROM "changed_keys")
+                    OR "target_key" IN (SELECT "key" FROM "changed_keys");
+
+                DELETE FROM {keys_table}
+                    WHERE {pk_col} IN (SELECT "id" FROM "keyed_elements")
+                    AND {pk_col} NOT IN (SELECT {pk_col} FROM {elements_table});
+                INSERT OR IGNORE INTO {keys_table}({pk_col})
+                    SELECT {pk_col} FROM {elements_table}
+                    WHERE {pk_col} IN (SELECT "id" FROM "keyed_elements");
+
+                INSERT INTO {keyed_relations_table}("name", "origin_key", "target_key")
+                    SELECT r."name", o."key", t."key" FROM {relations_table} AS r
+                    JOIN {keys_table} AS o ON o.{pk_col} = r."origin_id"
+                    JOIN {keys_table} AS t ON t.{pk_col} = r."target_id"
+                    WHERE r."origin_id" IN (SELECT "id" FROM "keyed_elements")
+                    OR r."target_id" IN (SELECT "id" FROM "keyed_elements");
+
+                DROP TABLE "keyed_elements";
+                DROP TABLE "changed_keys";
+                "#
+            ))?;
+        }
+        None => {
+            conn.execute_batch(&format!(
+                r#"
+                DELETE FROM {keyed_relations_table};
+
+                DELETE FROM {keys_table}
+                    WHERE {pk_col} NOT IN (SELECT {pk_col} FROM {elements_table});
+                INSERT OR IGNORE INTO {keys_table}({pk_col}) SELECT {pk_col} FROM {elements_table};
+
+                INSERT INTO {keyed_relations_table}("name", "origin_key", "target_key")
+                    SELECT r."name", o."key", t."key" FROM {relations_table} AS r
+                    JOIN {keys_table} AS o ON o.{pk_col} = r."origin_id"
+                    JOIN {keys_table} AS t ON t.{pk_col} = r."target_id";
+                "#
+            ))?;
+        }
+    }
+    debug!("updating surrogate keys took {:?}", now.elapsed());
+
+    Ok(())
+}
@@ -0,0 +1,192 @@
+//! Transforms rewriting or dropping each element before it is imported
+//!
+//! This is the hook to fix quirks of the tools exporting the model, e.g. renaming attributes,
+//! without forking the importer. Library users implement [`ElementTransform`], on the command line
+//! a Lua script defines the transform, see [`lua_transform`]. The redaction of attributes is a
+//! transform as well, see [`crate::redact`].
+//!
+//! The importer passes over the elements twice, first inserting the elements and then their
+//! relations. The transform is applied on each pass, hence it must yield the same result for the
+//! same element. Dropping an element which others refer to fails the import, unless the references
+//! are dropped as well.
+
+use std::{fmt::Debug, path::Path, sync::Arc};
+
+use eyre::{Context, Result};
+use serde_json::Value;
+
+use crate::{cli::RedactMode, import::Element, redact};
+
+/// Rewrites or drops each element before it is imported
+pub trait ElementTransform: Send + Sync {
+    /// Transform `element`, a JSON object as found in the imported JSON, returning `None` to drop it
+    ///
+    /// The `@id` of the element may be changed as well, as long as the references to it are
+    /// changed accordingly.
+    fn transform(&self, element: Value) -> Result<Option<Value>>;
+}
+
+/// Description of the transforms which do not describe themselves, see [`Transform::described`]
+const DEFAULT_DESCRIPTION: &str = "transformed";
+
+/// The transform passed along with the options of an import, with a description of what it does
+///
+/// The description is logged as reason why the import is partial, see
+/// [`crate::import::ImportProvenance::partial`].
+#[derive(Clone)]
+pub(crate) struct Transform(Arc<dyn ElementTransform>, String);
+
+impl Transform {
+    /// Transform the elements by `transform`
+    pub(crate) fn new(transform: Arc<dyn ElementTransform>) -> Self {
+        Self(transform, DEFAULT_DESCRIPTION.to_owned())
+    }
+
+    /// The transform described by `description`, e.g. `transformed by the script "fix.lua"`
+    pub(crate) fn described(self, description: impl Into<String>) -> Self {
+        Self(self.0, description.into())
+    }
+
+    /// What the transform does
+    pub(crate) fn description(&self) -> &str {
+        &self.1
+    }
+
+    /// Apply the transform to `element`, `None` if it is dropped
+    pub(crate) fn apply(&self, element: Element) -> Result<Option<Element>> {
+        let id = element.id.clone();
+        let transformed = serde_json::to_value(element)
+            .map_err(eyre::Report::from)
+            .and_then(|value| self.0.transform(value))
+            .wrap_err_with(|| format!("transforming the element {id:?}"))?;
+
+        let Some(transformed) = transformed else {
+            trace!("the transform dropped the element {id:?}");
+            return Ok(None);
+        };
+        let element = serde_json::from_value(transformed).wrap_err_with(|| {
+            format!("the transform turned the element {id:?} into something else than an element")
+        })?;
+        Ok(Some(element))
+    }
+
+    /// Apply `self`, then `next` to the elements `self` keeps
+    pub(crate) fn then(self, next: Transform) -> Self {
+        let description = format!("{}; {}", self.1, next.1);
+        Self(Arc::new(Chain(self, next)), description)
+    }
+}
+
+/// Two transforms applied one after the other, see [`Transform::then`]
+struct Chain(Transform, Transform);
+
+impl ElementTransform for Chain {
+    fn transform(&self, element: Value) -> Result<Option<Value>> {
+        match self.0.0.transform(element)? {
+            Some(element) => self.1.0.transform(element),
+            None => Ok(None),
+        }
+    }
+}
+
+impl Debug for Transform {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_tuple("Transform").field(&self.1).finish()
+    }
+}
+
+/// The transform of an import given on the command line, `None` if there is none
+///
+/// The Lua script `--transform`, if any, is applied first, then the redaction of `--redact` and
+/// the config file, so that attributes the script adds are redacted as well, and last the
+/// pseudonymization of the ids, if `pseudonymize_ids` is set.
+pub(crate) fn cli_transform(
+    maybe_script: Option<&Path>,
+    redact_rules: &[(String, RedactMode)],
+    pseudonymize_ids: bool,
+) -> Result<Option<Transform>> {
+    let transforms = [
+        maybe_script.map(lua_transform).transpose()?,
+        redact::redaction(redact_rules)?,
+        pseudonymize_ids
+            .then(redact::pseudonymization)
+            .transpose()?,
+    ];
+    Ok(transforms
+        .into_iter()
+        .flatten()
+        .reduce(|transform, next| transform.then(next)))
+}
+
+/// The transform defined by the Lua script at `path`
+///
+/// The script defines a global function `transform`, which is called with each element as table
+/// and returns the element, changed in place or newly built, or `nil` to drop it. For example,
+/// this script renames an attribute and drops all comments:
+///
+/// ```lua
+/// function transform(element)
+///     if element["@type"] == "Comment" then
+///         return nil
+///     end
+///     element.declaredName, element.name = element.name, nil
+///     return element
+/// end
+/// ```
+///
+/// Attributes which are `null` in the JSON are absent from the table.
+#[cfg(feature = "lua")]
+pub(crate) fn lua_transform(path: &Path) -> Result<Transform> {
+    info!("loading the transform from {path:?}");
+    let script =
+        std::fs::read_to_string(path).wrap_err_with(|| format!("reading the script {path:?}"))?;
+
+    let lua = mlua::Lua::new();
+    lua.load(&script)
+        .set_name(path.to_string_lossy())
+        .exec()
+        .wrap_err_with(|| format!("running the script {path:?}"))?;
+    let defines_transform = lua
+        .globals()
+        .get::<_, mlua::Value>("transform")?
+        .is_function();
+    eyre::ensure!(
+        defines_transform,
+        "the script {path:?} does not define the function `transform`"
+    );
+
+    Ok(
+        Transform::new(Arc::new(LuaTransform(std::sync::Mutex::new(lua))))
+            .described(format!("transformed by the script {path:?}")),
+    )
+}
+
+/// Fails, as this build lacks the support for Lua scripts
+#[cfg(not(feature = "lua"))]
+pub(crate) fn lua_transform(path: &Path) -> Result<Transform> {
+    eyre::bail!(
+        "unable to load the transform {path:?}, as this build lacks the `lua` feature for running Lua scripts"
+    )
+}
+
+/// A transform defined by the function `transform` of a Lua script, see [`lua_transform`]
+#[cfg(feature = "lua")]
+struct LuaTransform(std::sync::Mutex<mlua::Lua>);
+
+#[cfg(feature = "lua")]
+impl ElementTransform for LuaTransform {
+    fn transform(&self, element: Value) -> Result<Option<Value>> {
+        use mlua::LuaSerdeExt;
+
+        let lua = self.0.lock().expect("the Lua state is poisoned");
+        let options = mlua::SerializeOptions::new()
+            .serialize_none_to_null(false)
+            .serialize_unit_to_null(false);
+        let function: mlua::Function = lua.globals().get("transform")?;
+        let transformed: mlua::Value = function.call(lua.to_value_with(&element, options)?)?;
+        if transformed.is_nil() {
+            return Ok(None);
+        }
+        Ok(Some(lua.from_value(transformed)?))
+    }
+}
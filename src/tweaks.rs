@@ -1,25 +1,121 @@
-use eyre::Result;
+use eyre::{Result, bail};
 use rusqlite::Connection;
 
+use crate::{
+    cli::JournalMode,
+    config::{Pragmas, config},
+};
+
+/// Time to wait for a lock held by another process on the db, the same as the CLI defaults to
+const DEFAULT_BUSY_TIMEOUT: std::time::Duration = std::time::Duration::from_millis(5000);
+
+/// Open the db at `path`, configured as the CLI does by default
+///
+/// This is for using the tool as library, where there are no CLI arguments to configure the
+/// connection.
+pub(crate) fn open_db(path: &std::path::Path) -> Result<Connection> {
+    info!("opening database {path:?}");
+    let conn = Connection::open(path)?;
+    configure_connection(&conn, DEFAULT_BUSY_TIMEOUT, None)?;
+    Ok(conn)
+}
+
+/// Configure a freshly opened connection for coexisting with other processes using the same db
+///
+/// The busy timeout lets the connection wait for locks held by other connections, instead of
+/// failing immediately. The pragmas of the config which apply to every connection are set, see
+/// [`crate::config::Pragmas`]. With `maybe_journal_mode`, the db is switched to that journal mode,
+/// which is a persistent property of the db file, e.g. to write-ahead logging.
+pub(crate) fn configure_connection(
+    conn: &Connection,
+    busy_timeout: std::time::Duration,
+    maybe_journal_mode: Option<JournalMode>,
+) -> Result<()> {
+    debug!("setting the busy timeout to {busy_timeout:?}");
+    conn.busy_timeout(busy_timeout)?;
+
+    if let Some(max_memory) = config().max_memory {
+        let soft_heap_limit: i64 =
+            conn.pragma_update_and_check(None, "soft_heap_limit", max_memory, |row| row.get(0))?;
+        debug!("limited the heap of SQLite to {soft_heap_limit} bytes");
+    }
+
+    // the journal mode requested on the command line takes precedence
+    let mut pragmas = config().pragmas.clone();
+    if maybe_journal_mode.is_some() {
+        pragmas.journal_mode = None;
+    }
+    apply_connection_pragmas(conn, &pragmas)?;
+
+    if let Some(requested_mode) = maybe_journal_mode.map(JournalMode::pragma_value) {
+        let journal_mode: String =
+            conn.pragma_update_and_check(None, "journal_mode", requested_mode, |row| row.get(0))?;
+        if !journal_mode.eq_ignore_ascii_case(requested_mode) {
+            bail!(
+                "unable to switch the db to the {requested_mode} journal mode, it remains in {journal_mode:?} mode"
+            );
+        }
+        info!("the db is in {requested_mode} journal mode");
+    }
+
+    Ok(())
+}
+
+/// Set those of the pragmas which apply to every connection, i.e. all but those of bulk inserts
+pub(crate) fn apply_connection_pragmas(conn: &Connection, pragmas: &Pragmas) -> Result<()> {
+    if let Some(mmap_size) = pragmas.mmap_size {
+        // the effective mmap size is capped at compile time of SQLite
+        let mmap_size: u64 =
+            conn.pragma_update_and_check(None, "mmap_size", mmap_size, |row| row.get(0))?;
+        debug!("set the mmap size to {mmap_size} bytes");
+    }
+    if let Some(threads) = pragmas.threads {
+        let threads: u32 =
+            conn.pragma_update_and_check(None, "threads", threads, |row| row.get(0))?;
+        debug!("allowing {threads} auxiliary threads");
+    }
+    if let Some(requested_mode) = &pragmas.journal_mode {
+        // a read-only db can not switch its journal mode, which is no reason to refuse reading it
+        match conn.pragma_update_and_check(None, "journal_mode", requested_mode, |row| {
+            row.get::<_, String>(0)
+        }) {
+            Ok(journal_mode) if journal_mode.eq_ignore_ascii_case(requested_mode) => {
+                debug!("the db is in {journal_mode:?} journal mode");
+            }
+            Ok(journal_mode) => warn!(
+                "unable to switch the db to the {requested_mode:?} journal mode, it remains in {journal_mode:?} mode"
+            ),
+            Err(e) => warn!("unable to switch the db to the {requested_mode:?} journal mode: {e}"),
+        }
+    }
+
+    Ok(())
+}
+
 /// Apply tweaks to the SQLite database that we expect to be better w/r/t performance
 ///
 ///
 /// journal_mode = WAL significantly slows down our bulk-inserts
 /// locking_mode = EXCLUSIVE has no significant impact on performance, as we use big transactions anyhow
 /// temp_store = MEMORY has no significant impact on performance
-pub(crate) fn before_bulk_insert(conn: &mut Connection) -> Result<()> {
-    let page_size = 4096;
-    let cache_size = page_size * 2usize.pow(15); // 4096 * 2^16 => 256 MiB
+///
+/// The cache size, page size and synchronous mode are taken from `pragmas`, usually those of the
+/// config. The cache is bounded to half of the memory limit, if any.
+pub(crate) fn before_bulk_insert(conn: &mut Connection, pragmas: &Pragmas) -> Result<()> {
+    let cache_size = match config().max_memory {
+        Some(max_memory) => bounded_cache_size(pragmas, max_memory / 2),
+        None => pragmas.cache_size,
+    };
 
     info!("applying performance tweaks");
     conn.pragma_update(None, "cache_size", cache_size)?; // non-persistent
-    conn.pragma_update(None, "page_size", page_size)?;
+    conn.pragma_update(None, "page_size", pragmas.page_size)?;
 
     #[allow(clippy::single_element_loop)]
     for (name, value) in [
         // ("journal_mode", "WAL"),
         // ("locking_mode", "EXCLUSIVE"),
-        ("synchronous", "OFF"),
+        ("synchronous", pragmas.synchronous.as_str()),
         // ("temp_store", "MEMORY"),
     ] {
         conn.pragma_update(None, name, value)?;
@@ -28,6 +124,31 @@ pub(crate) fn before_bulk_insert(conn: &mut Connection) -> Result<()> {
     Ok(())
 }
 
+/// The cache_size pragma of the config, reduced if the cache would exceed `max_cache_bytes`
+///
+/// A positive cache size counts pages, a negative one KiB.
+fn bounded_cache_size(pragmas: &Pragmas, max_cache_bytes: u64) -> i64 {
+    let cache_bytes = if pragmas.cache_size >= 0 {
+        pragmas
+            .cache_size
+            .unsigned_abs()
+            .saturating_mul(u64::from(pragmas.page_size))
+    } else {
+        pragmas.cache_size.unsigned_abs().saturating_mul(1024)
+    };
+    if cache_bytes <= max_cache_bytes {
+        return pragmas.cache_size;
+    }
+
+    debug!("bounding the cache to {max_cache_bytes} bytes due to the memory limit");
+    -i64::try_from(max_cache_bytes / 1024).unwrap_or(i64::MAX)
+}
+
+/// Reset the tweaks of [`before_bulk_insert`] and tidy up the db after the bulk insert was committed
+///
+/// In WAL mode, the write-ahead log holds the entire bulk insert, so it is checkpointed into the
+/// db and truncated. Readers still using older snapshots of the db may prevent that, in which case
+/// the log is left to the automatic checkpoints.
 pub(crate) fn after_bulk_insert(conn: &mut Connection, vacuum: bool) -> Result<()> {
     info!("resetting performance tweaks");
 
@@ -52,5 +173,29 @@ pub(crate) fn after_bulk_insert(conn: &mut Connection, vacuum: bool) -> Result<(
         info!("that took {:?}", now.elapsed());
     }
 
+    let journal_mode: String = conn.pragma_query_value(None, "journal_mode", |row| row.get(0))?;
+    if journal_mode.eq_ignore_ascii_case("wal") {
+        checkpoint_wal(conn)?;
+    }
+
+    Ok(())
+}
+
+/// Checkpoint the write-ahead log into the db, truncating it if no reader is in the way
+fn checkpoint_wal(conn: &Connection) -> Result<()> {
+    let now = std::time::Instant::now();
+    info!("checkpointing the write-ahead log");
+    let (busy, log_pages, checkpointed_pages): (bool, i64, i64) =
+        conn.query_row("PRAGMA wal_checkpoint(TRUNCATE)", [], |row| {
+            Ok((row.get(0)?, row.get(1)?, row.get(2)?))
+        })?;
+    if busy {
+        warn!(
+            "the write-ahead log could not be checkpointed completely while other connections use the db, {checkpointed_pages} of {log_pages} pages were written back"
+        );
+    } else {
+        info!("that took {:?}", now.elapsed());
+    }
+
     Ok(())
 }
@@ -0,0 +1,320 @@
+use eyre::{Result, bail};
+use rusqlite::{Connection, Transaction};
+
+use crate::{
+    config::{FTS_TABLE, RELATION_NAMES_TABLE, config},
+    failure::Failure,
+    migrate_schema::split_top_level,
+    schema_info::{SchemaOrigin, embedded_schema_fingerprint, schema_fingerprint, stamp_schema},
+    util::{escape_sql_ident, table_exists},
+};
+
+/// A single step migrating a db from one schema version to the next
+struct Migration {
+    /// Human readable description of the changes
+    description: &'static str,
+
+    /// Function applying the changes
+    apply: fn(&Transaction) -> Result<()>,
+}
+
+/// All migrations, the n-th migration migrates from schema version n to n + 1
+///
+/// Append only, never reorder or remove a migration, as the schema version of a db is the number of
+/// migrations applied to it.
+///
+/// The schema version covers the objects the tool relies on, i.e. the embedded schema and the
+/// tables maintained by the importer. Any change to them has to append a migration, the test
+/// `init_db_schema_fingerprint` pins the fingerprint of the embedded schema to remind of that.
+/// Objects which the schema generation adds on top, e.g. the `NOT NULL` triggers, the `CHECK`
+/// constraints on UUIDs, the type and convenience views, [`PROPERTY_DOMAINS_TABLE`] and
+/// [`SCHEMA_META_TABLE`], are optional: the tool uses them if present and does without otherwise.
+/// Changes to them do not bump the schema version, generated dbs follow them via `migrate-schema`,
+/// and changes to the core tables are caught by the fingerprint, see [`crate::schema_info`].
+///
+/// [`PROPERTY_DOMAINS_TABLE`]: crate::config::PROPERTY_DOMAINS_TABLE
+/// [`SCHEMA_META_TABLE`]: crate::config::SCHEMA_META_TABLE
+const MIGRATIONS: &[Migration] = &[
+    Migration {
+        description: "add the full-text index over the elements",
+        apply: add_full_text_index,
+    },
+    Migration {
+        description: "move the allowed relation names into a table",
+        apply: add_relation_names_table,
+    },
+];
+
+/// Schema version of dbs initialized by this version of the tool
+pub(crate) const SCHEMA_VERSION: u32 = MIGRATIONS.len() as u32;
+
+/// Reads the schema version of a db from `PRAGMA user_version`
+pub(crate) fn schema_version(conn: &Connection) -> Result<u32> {
+    Ok(conn.pragma_query_value(None, "user_version", |row| row.get(0))?)
+}
+
+/// Sets the schema version of a db via `PRAGMA user_version`
+pub(crate) fn set_schema_version(conn: &Connection, version: u32) -> Result<()> {
+    conn.pragma_update(None, "user_version", version)?;
+    Ok(())
+}
+
+/// Refuses dbs whose schema version differs from [`SCHEMA_VERSION`]
+///
+/// Dbs without an elements table are not checked, as they are not initialized at all.
+pub(crate) fn check_schema_version(conn: &Connection) -> Result<()> {
+    if !table_exists(conn, &config().elements_table)? {
+        return Ok(());
+    }
+
+    let version = schema_version(conn)?;
+    if version < SCHEMA_VERSION {
+        return Err(Failure::SchemaMismatch.report(format!(
+            "the db has schema version {version}, but this version of the tool requires schema version {SCHEMA_VERSION}; run upgrade-db first"
+        )));
+    } else if version > SCHEMA_VERSION {
+        return Err(Failure::SchemaMismatch.report(format!(
+            "the db has schema version {version}, which is newer than the schema version {SCHEMA_VERSION} supported by this version of the tool"
+        )));
+    }
+
+    Ok(())
+}
+
+/// Migrates a db to the current [`SCHEMA_VERSION`], applying all pending migrations
+///
+/// Each migration is applied in its own transaction, together with the bump of the schema version.
+/// Thus, if a migration fails, the db remains at the last successfully applied schema version.
+pub(crate) fn upgrade_db(conn: &mut Connection) -> Result<()> {
+    let mut version = schema_version(conn)?;
+    if version > SCHEMA_VERSION {
+        bail!(
+            "the db has schema version {version}, which is newer than the schema version {SCHEMA_VERSION} supported by this version of the tool"
+        );
+    }
+
+    if version == SCHEMA_VERSION {
+        info!("the db already is at schema version {SCHEMA_VERSION}, nothing to do");
+        return Ok(());
+    }
+
+    for migration in &MIGRATIONS[version as usize..] {
+        info!(
+            "migrating from schema version {version} to {}: {}",
+            version + 1,
+            migration.description
+        );
+        let db_ta = conn.transaction()?;
+        (migration.apply)(&db_ta)?;
+        version += 1;
+        set_schema_version(&db_ta, version)?;
+        db_ta.commit()?;
+    }
+
+    // the migrations may have altered the core tables, hence the fingerprint has to be renewed
+    let origin = if schema_fingerprint(conn)? == embedded_schema_fingerprint()? {
+        SchemaOrigin::Embedded
+    } else {
+        SchemaOrigin::Generated
+    };
+    stamp_schema(conn, origin)?;
+
+    info!("done");
+    Ok(())
+}
+
+/// Adds the full-text index, and fills it with the existing elements
+///
+/// Elements tables without rowid are left without full-text index, as with the schema generation.
+fn add_full_text_index(db_ta: &Transaction) -> Result<()> {
+    if table_exists(db_ta, FTS_TABLE)? {
+        debug!("the {FTS_TABLE:?} table already exists");
+        return Ok(());
+    }
+
+    let elements_sql = table_sql(db_ta, &config().elements_table)?;
+    if table_options(&elements_sql).contains("WITHOUT ROWID") {
+        warn!("omitting the full-text index, as the elements table has no rowid");
+        return Ok(());
+    }
+
+    db_ta.execute_batch(&crate::json_schema_to_sql::create_fts())?;
+
+    let fts_table_escaped = escape_sql_ident(FTS_TABLE);
+    db_ta.execute_batch(&format!(
+        "INSERT INTO {fts_table_escaped}({fts_table_escaped}) VALUES ('rebuild');"
+    ))?;
+
+    Ok(())
+}
+
+/// Replaces the `CHECK` constraint on the relation names by a foreign key into a table of names
+///
+/// The table is filled with the names of the relations present in the db, further names are
+/// registered by the importer. The relations table is rebuilt, as SQLite can not drop a constraint
+/// in place. Its new definition is derived from the existing one, see [`relations_table_sql`], so
+/// that the columns and table options of generated dbs are kept.
+fn add_relation_names_table(db_ta: &Transaction) -> Result<()> {
+    let config = config();
+    let names_table_escaped = escape_sql_ident(RELATION_NAMES_TABLE);
+    let relations_table = &config.relations_table;
+    let relations_table_escaped = escape_sql_ident(relations_table);
+    let new_relations_table_escaped = escape_sql_ident(format!("{relations_table}.new"));
+
+    let old_sql = table_sql(db_ta, relations_table)?;
+    let new_sql = relations_table_sql(&old_sql, &names_table_escaped)?;
+    let create_prefix = format!("CREATE TABLE {relations_table_escaped}");
+    let Some(definition) = new_sql.strip_prefix(&create_prefix) else {
+        bail!("unable to rename the definition of the {relations_table:?} table:\n{old_sql}");
+    };
+
+    // indices and triggers are dropped together with the table, thus they have to be recreated.
+    // Views are dropped and recreated as well, as SQLite refuses to rename a table while views refer
+    // to a missing one.
+    let dependents = db_ta
+        .prepare(
+            "SELECT type, name, sql FROM sqlite_schema WHERE (type IN ('index', 'trigger') AND tbl_name = ?1 OR type = 'view') AND sql IS NOT NULL ORDER BY rowid",
+        )?
+        .query_map([relations_table], |row| {
+            Ok((
+                row.get::<_, String>(0)?,
+                row.get::<_, String>(1)?,
+                row.get::<_, String>(2)?,
+            ))
+        })?
+        .collect::<Result<Vec<_>, _>>()?;
+    for (_, name, _) in dependents.iter().filter(|(ty, _, _)| ty == "view") {
+        db_ta.execute_batch(&format!("DROP VIEW {}", escape_sql_ident(name)))?;
+    }
+
+    // the names table is strict if the relations table is, as with the schema generation
+    let names_table_options = if table_options(&old_sql).contains("STRICT") {
+        "STRICT,\nWITHOUT ROWID"
+    } else {
+        "WITHOUT ROWID"
+    };
+    db_ta.execute_batch(&format!(
+        r#"CREATE TABLE {names_table_escaped} ("name" TEXT PRIMARY KEY) {names_table_options};
+
+INSERT INTO {names_table_escaped} ("name") SELECT DISTINCT "name" FROM {relations_table_escaped};
+
+CREATE TABLE {new_relations_table_escaped}{definition};
+
+INSERT INTO {new_relations_table_escaped} SELECT * FROM {relations_table_escaped};
+
+DROP TABLE {relations_table_escaped};
+
+ALTER TABLE {new_relations_table_escaped} RENAME TO {relations_table_escaped};"#
+    ))?;
+
+    for (_, _, sql) in dependents {
+        db_ta.execute_batch(&sql)?;
+    }
+
+    Ok(())
+}
+
+/// The `CREATE TABLE` statement of a table
+fn table_sql(conn: &Connection, table: &str) -> Result<String> {
+    Ok(conn.query_row(
+        "SELECT sql FROM sqlite_schema WHERE type = 'table' AND name = ?1",
+        [table],
+        |row| row.get(0),
+    )?)
+}
+
+/// The options following the definition of a table, e.g. `STRICT`, in upper case
+fn table_options(sql: &str) -> String {
+    sql.rfind(')')
+        .map(|end| sql[end + 1..].to_uppercase())
+        .unwrap_or_default()
+}
+
+/// The definition of the relations table `old_sql`, with the `CHECK` constraint on the relation
+/// names replaced by a foreign key into the table of names
+///
+/// Only the constraint is replaced, everything else is kept as is. Thus, the relations table of a
+/// db initialized from the embedded schema ends up exactly like in the current embedded schema, and
+/// shares its fingerprint.
+fn relations_table_sql(old_sql: &str, names_table_escaped: &str) -> Result<String> {
+    let (Some(start), Some(end)) = (old_sql.find('('), old_sql.rfind(')')) else {
+        bail!("unable to parse the definition of the relations table:\n{old_sql}");
+    };
+
+    let is_name_check = |part: &str| {
+        let part = part.trim_start();
+        part.len() >= 5 && part[..5].eq_ignore_ascii_case("CHECK") && part.contains(r#""name""#)
+    };
+    let is_table_constraint = |part: &str| {
+        let keyword = part
+            .trim_start()
+            .split(['(', ' '])
+            .next()
+            .unwrap_or_default();
+        ["CONSTRAINT", "PRIMARY", "UNIQUE", "CHECK", "FOREIGN"]
+            .contains(&keyword.to_uppercase().as_str())
+    };
+
+    // the foreign key follows the formatting of the others, as in the embedded and generated schemas
+    let foreign_key = if old_sql.contains("FOREIGN KEY (") {
+        format!(r#"FOREIGN KEY ("name") REFERENCES {names_table_escaped} ("name")"#)
+    } else {
+        format!(r#"FOREIGN KEY("name") REFERENCES {names_table_escaped}("name")"#)
+    };
+
+    let mut parts = Vec::new();
+    let mut foreign_key_added = false;
+    for part in split_top_level(&old_sql[start + 1..end]) {
+        if is_name_check(part) {
+            continue;
+        }
+        if !foreign_key_added && is_table_constraint(part) {
+            let indentation = &part[..part.len() - part.trim_start().len()];
+            parts.push(format!("{indentation}{foreign_key}"));
+            foreign_key_added = true;
+        }
+        match part.trim_start().strip_prefix(r#""name""#) {
+            Some(_) => parts.push(without_check(part)),
+            None => parts.push(part.to_owned()),
+        }
+    }
+    if !foreign_key_added {
+        parts.push(format!(" {foreign_key}"));
+    }
+
+    Ok(format!(
+        "{}{}{}",
+        &old_sql[..=start],
+        parts.join(","),
+        &old_sql[end..]
+    ))
+}
+
+/// The definition of a column without its `CHECK` constraint, if any
+fn without_check(column_def: &str) -> String {
+    let mut depth = 0;
+    let mut quote = None;
+    let mut maybe_check_start = None;
+    for (idx, c) in column_def.char_indices() {
+        match (quote, c) {
+            (Some(q), c) if c == q => quote = None,
+            (Some(_), _) => {}
+            (None, '"' | '\'' | '`') => quote = Some(c),
+            (None, '(') => depth += 1,
+            (None, ')') => {
+                depth -= 1;
+                if let (0, Some(check_start)) = (depth, maybe_check_start) {
+                    return format!("{}{}", &column_def[..check_start], &column_def[idx + 1..]);
+                }
+            }
+            (None, c) if depth == 0 && c.is_whitespace() && maybe_check_start.is_none() => {
+                let rest = column_def[idx..].trim_start();
+                if rest.len() >= 5 && rest[..5].eq_ignore_ascii_case("CHECK") {
+                    maybe_check_start = Some(idx);
+                }
+            }
+            _ => {}
+        }
+    }
+    column_def.to_owned()
+}
@@ -1,8 +1,16 @@
-use eyre::Result;
+use eyre::{Result, bail};
+use rusqlite::{Connection, OptionalExtension};
 use serde::de::DeserializeOwned;
-use std::{fs::File, io::BufReader, path::Path};
+use std::{
+    fs::File,
+    io::BufReader,
+    path::{Path, PathBuf},
+};
 
 mod stream_json;
+mod zip;
+
+pub(crate) use zip::write_zip;
 
 /// Reads a JSON file, parsing it into a type `T`
 ///
@@ -22,6 +30,60 @@ pub(crate) fn read_json_file<T: DeserializeOwned>(file: &Path) -> Result<T> {
     Ok(parsed)
 }
 
+/// Opens an existing db read-only
+///
+/// With `immutable`, SQLite assumes that the db file can not change, and thus takes no locks at all.
+/// This is required for files on read-only media, but leads to wrong results if another process
+/// modifies the db meanwhile.
+pub(crate) fn open_read_only(path: &Path, immutable: bool) -> Result<Connection> {
+    use rusqlite::OpenFlags;
+
+    // characters with a special meaning in URIs have to be escaped in the path
+    let mut uri = String::from("file:");
+    for c in path.to_string_lossy().chars() {
+        match c {
+            '%' | '?' | '#' => uri.push_str(&format!("%{:02X}", c as u32)),
+            c => uri.push(c),
+        }
+    }
+    if immutable {
+        uri.push_str("?immutable=1");
+    }
+
+    let flags = OpenFlags::SQLITE_OPEN_READ_ONLY
+        | OpenFlags::SQLITE_OPEN_URI
+        | OpenFlags::SQLITE_OPEN_NO_MUTEX;
+    Ok(Connection::open_with_flags(uri, flags)?)
+}
+
+/// Attaches other dbs to the connection, each under its alias
+pub(crate) fn attach_databases(conn: &Connection, attachments: &[(String, PathBuf)]) -> Result<()> {
+    for (alias, path) in attachments {
+        info!("attaching database {path:?} as {alias:?}");
+        if !path.exists() {
+            bail!("the db {path:?} to be attached as {alias:?} does not exist");
+        }
+        conn.execute(
+            &format!("ATTACH DATABASE ?1 AS {}", escape_sql_ident(alias)),
+            [path.to_string_lossy()],
+        )?;
+    }
+    Ok(())
+}
+
+/// Checks whether a table of the given name exists in the database
+pub(crate) fn table_exists(conn: &Connection, table_name: &str) -> Result<bool> {
+    let exists = conn
+        .query_row(
+            "SELECT 1 FROM sqlite_schema WHERE type = 'table' AND name = ?1",
+            [table_name],
+            |_| Ok(()),
+        )
+        .optional()?
+        .is_some();
+    Ok(exists)
+}
+
 /// Escapes string in the way prescribed by the SQL standard, but generic over the quotation symbol
 /// in use
 pub(crate) fn escape_sql<const DELIM: char, S: AsRef<str>>(str_to_escape: S) -> String {
@@ -54,6 +116,33 @@ pub(crate) fn escape_sql_ident<S: AsRef<str>>(str_to_escape: S) -> String {
     escape_sql::<'"', S>(str_to_escape)
 }
 
+/// Escape a string to be matched literally by a `LIKE` pattern with `ESCAPE '\'`
+///
+/// The wildcards `%` and `_` as well as the escape character itself are prefixed by `\`.
+pub(crate) fn escape_like<S: AsRef<str>>(str_to_escape: S) -> String {
+    let mut escaped = String::new();
+    for c in str_to_escape.as_ref().chars() {
+        if matches!(c, '\\' | '%' | '_') {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    escaped
+}
+
+/// Escape a string to be used as field in a CSV file
+///
+/// Follows <https://www.rfc-editor.org/rfc/rfc4180>, i.e. fields containing commas, quotes or line
+/// breaks are enclosed in double quotes, with double quotes inside being doubled.
+pub(crate) fn escape_csv<S: AsRef<str>>(str_to_escape: S) -> String {
+    let s = str_to_escape.as_ref();
+    if s.contains([',', '"', '\n', '\r']) {
+        escape_sql::<'"', _>(s)
+    } else {
+        s.to_owned()
+    }
+}
+
 /// This type is a wrapper arround the streaming JSON iterator provided in [`stream_json`]
 ///
 /// Open a JSON file, assuming it to be an array of elements of type `T`. Streams the file to
@@ -112,7 +201,7 @@ macro_rules! maybe_time_report {
                 time_per_insertion = elapsed_since_start.div_f64($rows_inserted as f64),
                 insertions_per_second = $rows_inserted as f64 / elapsed_since_start.as_secs_f64()
             );
-            $duration_since_last_update += $crate::config::TIME_BETWEEN_STATUS_REPORTS;
+            $duration_since_last_update += $crate::config::config().time_between_status_reports();
         }
     };
 
@@ -57,10 +57,45 @@ fn yield_next_obj<T: DeserializeOwned, R: Read>(
     }
 }
 
+/// A reader counting the bytes read through it
+struct CountingReader<R> {
+    inner: R,
+    bytes_read: u64,
+}
+
+impl<R: Read> Read for CountingReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let n = self.inner.read(buf)?;
+        self.bytes_read += n as u64;
+        Ok(n)
+    }
+}
+
 /// Create an iterator for a reader, assuming a JSON file with a top-level array
+///
+/// Errors are located by the index of the array item and the byte offset it starts at, as the line
+/// and column reported by the parser are relative to the item. The offset is approximate, as it
+/// also counts the whitespace and comma preceding the item.
 pub(super) fn iter_json_array<T: DeserializeOwned, R: Read>(
-    mut reader: R,
+    reader: R,
 ) -> impl Iterator<Item = Result<T, io::Error>> {
+    let mut reader = CountingReader {
+        inner: reader,
+        bytes_read: 0,
+    };
     let mut at_start = false;
-    std::iter::from_fn(move || yield_next_obj(&mut reader, &mut at_start).transpose())
+    let mut item_idx = 0usize;
+    std::iter::from_fn(move || {
+        let item_offset = reader.bytes_read;
+        let maybe_item = yield_next_obj(&mut reader, &mut at_start).map_err(|e| {
+            io::Error::new(
+                e.kind(),
+                format!(
+                    "{e}, relative to the array item {item_idx} starting near byte offset {item_offset}"
+                ),
+            )
+        });
+        item_idx += 1;
+        maybe_item.transpose()
+    })
 }
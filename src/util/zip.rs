@@ -0,0 +1,109 @@
+//! Minimal writer of ZIP archives
+//!
+//! The entries are stored without compression, which every ZIP reader supports. See the
+//! [specification](https://pkware.cachefly.net/webdocs/casestudies/APPNOTE.TXT) for the format.
+
+use std::io::Write;
+
+use eyre::{Result, bail};
+
+/// Version of the specification needed to extract the entries, 2.0 for stored entries
+const VERSION_NEEDED: u16 = 20;
+
+/// Modification date of all entries, 1980-01-01 in MS-DOS format
+const DOS_DATE: u16 = (1 << 5) | 1;
+
+/// Write a ZIP archive consisting of the given entries, each a path and its content
+pub(crate) fn write_zip<W: Write>(entries: &[(&str, &[u8])], out: &mut W) -> Result<()> {
+    if entries.len() > usize::from(u16::MAX) {
+        bail!("too many entries for a ZIP archive without ZIP64 extension");
+    }
+
+    let mut offset: u32 = 0;
+    let mut central_directory = Vec::new();
+    for (path, content) in entries {
+        let (Ok(size), Ok(path_len)) = (u32::try_from(content.len()), u16::try_from(path.len()))
+        else {
+            bail!("the entry {path:?} is too large for a ZIP archive without ZIP64 extension");
+        };
+        let crc = crc32(content);
+
+        // local file header, followed by the content
+        let mut header = Vec::with_capacity(30 + path.len());
+        header.extend_from_slice(&0x04034b50u32.to_le_bytes());
+        header.extend_from_slice(&VERSION_NEEDED.to_le_bytes());
+        header.extend_from_slice(&0u16.to_le_bytes()); // flags
+        header.extend_from_slice(&0u16.to_le_bytes()); // compression method: stored
+        header.extend_from_slice(&0u16.to_le_bytes()); // modification time
+        header.extend_from_slice(&DOS_DATE.to_le_bytes());
+        header.extend_from_slice(&crc.to_le_bytes());
+        header.extend_from_slice(&size.to_le_bytes()); // compressed size
+        header.extend_from_slice(&size.to_le_bytes()); // uncompressed size
+        header.extend_from_slice(&path_len.to_le_bytes());
+        header.extend_from_slice(&0u16.to_le_bytes()); // extra field length
+        header.extend_from_slice(path.as_bytes());
+        out.write_all(&header)?;
+        out.write_all(content)?;
+
+        // central directory header, pointing to the local file header
+        let cd = &mut central_directory;
+        cd.extend_from_slice(&0x02014b50u32.to_le_bytes());
+        cd.extend_from_slice(&VERSION_NEEDED.to_le_bytes()); // version made by
+        cd.extend_from_slice(&VERSION_NEEDED.to_le_bytes());
+        cd.extend_from_slice(&0u16.to_le_bytes()); // flags
+        cd.extend_from_slice(&0u16.to_le_bytes()); // compression method: stored
+        cd.extend_from_slice(&0u16.to_le_bytes()); // modification time
+        cd.extend_from_slice(&DOS_DATE.to_le_bytes());
+        cd.extend_from_slice(&crc.to_le_bytes());
+        cd.extend_from_slice(&size.to_le_bytes()); // compressed size
+        cd.extend_from_slice(&size.to_le_bytes()); // uncompressed size
+        cd.extend_from_slice(&path_len.to_le_bytes());
+        cd.extend_from_slice(&0u16.to_le_bytes()); // extra field length
+        cd.extend_from_slice(&0u16.to_le_bytes()); // comment length
+        cd.extend_from_slice(&0u16.to_le_bytes()); // disk number
+        cd.extend_from_slice(&0u16.to_le_bytes()); // internal attributes
+        cd.extend_from_slice(&0u32.to_le_bytes()); // external attributes
+        cd.extend_from_slice(&offset.to_le_bytes());
+        cd.extend_from_slice(path.as_bytes());
+
+        let Some(next_offset) = u32::try_from(header.len())
+            .ok()
+            .and_then(|len| offset.checked_add(len)?.checked_add(size))
+        else {
+            bail!("the archive is too large for a ZIP archive without ZIP64 extension");
+        };
+        offset = next_offset;
+    }
+    out.write_all(&central_directory)?;
+
+    // end of central directory record
+    let entry_count = entries.len() as u16;
+    let mut end = Vec::with_capacity(22);
+    end.extend_from_slice(&0x06054b50u32.to_le_bytes());
+    end.extend_from_slice(&0u16.to_le_bytes()); // number of this disk
+    end.extend_from_slice(&0u16.to_le_bytes()); // disk with the central directory
+    end.extend_from_slice(&entry_count.to_le_bytes()); // entries on this disk
+    end.extend_from_slice(&entry_count.to_le_bytes()); // entries in total
+    end.extend_from_slice(&(central_directory.len() as u32).to_le_bytes());
+    end.extend_from_slice(&offset.to_le_bytes());
+    end.extend_from_slice(&0u16.to_le_bytes()); // comment length
+    out.write_all(&end)?;
+
+    Ok(())
+}
+
+/// CRC-32 checksum as used by ZIP, with the reversed polynomial `0xedb88320`
+fn crc32(data: &[u8]) -> u32 {
+    let mut crc = !0u32;
+    for &byte in data {
+        crc ^= u32::from(byte);
+        for _ in 0..8 {
+            crc = if crc & 1 == 1 {
+                (crc >> 1) ^ 0xedb88320
+            } else {
+                crc >> 1
+            };
+        }
+    }
+    !crc
+}
@@ -0,0 +1,300 @@
+//! Verification that a db faithfully represents a JSON dump
+//!
+//! The importer warns about properties it could not understand, but it does not tell which of the
+//! elements are affected, nor whether the db still matches the dump after later imports or manual
+//! edits. The verification compares each element of the dump with its representation in the db,
+//! reporting the discrepancies as findings in the same way as [`crate::lint`].
+
+use std::{
+    collections::{BTreeMap, BTreeSet, HashSet},
+    io::Write,
+    path::Path,
+};
+
+use eyre::Result;
+use rusqlite::{Connection, OptionalExtension, types::Value as RusValue};
+use serde_json::Value;
+
+use crate::{
+    config::{ELEMENT_PK_COL, OWNER_COL, PROJECT_COL, RAW_JSON_COL, config},
+    import::{
+        Element, element_db_value, get_table_columns, is_column_of_property, is_relation_object,
+    },
+    lint::{Finding, Severity, report_findings},
+    util::{CloneableJsonArrayStreamIterator, escape_sql_ident},
+};
+
+/// Verify that every element of a JSON dump is represented in the db, and report the discrepancies
+///
+/// For each element of the dump, the columns of its row in the elements table, its relations and
+/// its extended properties are compared to the dump. Properties which are represented nowhere in
+/// the db are reported as warnings, as the import loses them by design. With `both_ways`, also the
+/// elements and the relations in the db which are missing from the dump are reported.
+pub(crate) fn verify<W: Write>(
+    conn: &Connection,
+    dump: &Path,
+    both_ways: bool,
+    json: bool,
+    out: &mut W,
+) -> Result<()> {
+    let config = config();
+    let elements_table = escape_sql_ident(&config.elements_table);
+    let relations_table = escape_sql_ident(&config.relations_table);
+    let extended_table = escape_sql_ident(&config.extended_table);
+    let pk_col = escape_sql_ident(ELEMENT_PK_COL);
+
+    let elements_table_columns = get_table_columns(conn, &config.elements_table)?;
+    let extended_table_columns: Vec<_> = get_table_columns(conn, &config.extended_table)?
+        .into_iter()
+        .map(|(name, _)| name)
+        .filter(|name| name != ELEMENT_PK_COL && name != PROJECT_COL)
+        .collect();
+
+    // generated columns are left out, as the importer does not store them
+    let mut element_stmt = conn.prepare(&format!(
+        "SELECT {} FROM {elements_table} WHERE {pk_col} = ?1",
+        elements_table_columns
+            .iter()
+            .map(|(name, _)| escape_sql_ident(name))
+            .collect::<Vec<_>>()
+            .join(", ")
+    ))?;
+    let mut relations_stmt = conn.prepare(&format!(
+        r#"SELECT "name", "target_id" FROM {relations_table} WHERE "origin_id" = ?1"#
+    ))?;
+    let extended_stmts = extended_table_columns
+        .iter()
+        .map(|column| {
+            let column = escape_sql_ident(column);
+            conn.prepare(&format!(
+                "SELECT {column} FROM {extended_table} WHERE {pk_col} = ?1 AND {column} IS NOT NULL"
+            ))
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+    let mut extended_stmts: Vec<_> = extended_table_columns.iter().zip(extended_stmts).collect();
+
+    let mut findings = Vec::new();
+    let mut finding = |severity, check: &str, element_id: &str, message: String| {
+        findings.push(Finding {
+            check: check.to_owned(),
+            severity,
+            element_id: Some(element_id.to_owned()),
+            message,
+        })
+    };
+
+    // number of elements per property which is represented nowhere in the db
+    let mut unrepresented_properties: BTreeMap<String, usize> = BTreeMap::new();
+
+    let mut dump_ids = HashSet::new();
+    for maybe_element in CloneableJsonArrayStreamIterator::<Element>::new(dump)? {
+        let element = maybe_element?;
+        let id = element.id.as_str();
+        dump_ids.insert(element.id.clone());
+
+        //
+        // Columns
+        //
+
+        let maybe_row: Option<Vec<RusValue>> = element_stmt
+            .query_row([id], |row| {
+                (0..elements_table_columns.len())
+                    .map(|idx| row.get(idx))
+                    .collect()
+            })
+            .optional()?;
+        let Some(row) = maybe_row else {
+            finding(
+                Severity::Error,
+                "missing-element",
+                id,
+                "the element is missing in the db".to_owned(),
+            );
+            continue;
+        };
+
+        for ((column_name, column_type), actual) in elements_table_columns.iter().zip(row) {
+            if [ELEMENT_PK_COL, PROJECT_COL, OWNER_COL].contains(&column_name.as_str()) {
+                continue;
+            }
+
+            let Some(expected) = element_db_value(&element, column_name, *column_type)? else {
+                finding(
+                    Severity::Warning,
+                    "unrepresentable-value",
+                    id,
+                    format!("the value of {column_name:?} can not be stored in its column"),
+                );
+                continue;
+            };
+
+            if !same_db_value(&expected, &actual) {
+                finding(
+                    Severity::Error,
+                    "column-mismatch",
+                    id,
+                    format!(
+                        "{column_name:?} is {} in the db, but {} in the dump",
+                        format_db_value(&actual),
+                        format_db_value(&expected)
+                    ),
+                );
+            }
+        }
+
+        //
+        // Relations
+        //
+
+        let mut expected_relations = BTreeSet::new();
+        for (property, value) in &element.rest {
+            let targets = match value {
+                Value::Object(o) if is_relation_object(o) => vec![value],
+                Value::Array(a)
+                    if a.iter()
+                        .all(|v| matches!(v, Value::Object(o) if is_relation_object(o))) =>
+                {
+                    a.iter().collect()
+                }
+                _ => continue,
+            };
+            for target in targets {
+                let target_id = target[ELEMENT_PK_COL].as_str().unwrap_or_default();
+                expected_relations.insert((property.to_owned(), target_id.to_owned()));
+            }
+        }
+
+        let actual_relations = relations_stmt
+            .query_map([id], |row| Ok((row.get(0)?, row.get(1)?)))?
+            .collect::<Result<BTreeSet<(String, String)>, _>>()?;
+
+        for (name, target_id) in expected_relations.difference(&actual_relations) {
+            finding(
+                Severity::Error,
+                "missing-relation",
+                id,
+                format!("the relation {name:?} to {target_id} is missing in the db"),
+            );
+        }
+        if both_ways {
+            for (name, target_id) in actual_relations.difference(&expected_relations) {
+                finding(
+                    Severity::Error,
+                    "extra-relation",
+                    id,
+                    format!("the relation {name:?} to {target_id} is missing in the dump"),
+                );
+            }
+        }
+
+        //
+        // Extended properties
+        //
+
+        for (column, stmt) in &mut extended_stmts {
+            let expected: BTreeSet<String> = match element.rest.get(column.as_str()) {
+                Some(Value::Array(values)) => values
+                    .iter()
+                    .filter_map(|v| v.as_str().map(str::to_owned))
+                    .collect(),
+                _ => BTreeSet::new(),
+            };
+            let actual = stmt
+                .query_map([id], |row| row.get(0))?
+                .collect::<Result<BTreeSet<String>, _>>()?;
+
+            for value in expected.difference(&actual) {
+                finding(
+                    Severity::Error,
+                    "missing-extended-property",
+                    id,
+                    format!("the value {value:?} of {column:?} is missing in the db"),
+                );
+            }
+            if both_ways {
+                for value in actual.difference(&expected) {
+                    finding(
+                        Severity::Error,
+                        "extra-extended-property",
+                        id,
+                        format!("the value {value:?} of {column:?} is missing in the dump"),
+                    );
+                }
+            }
+        }
+
+        //
+        // Properties represented nowhere
+        //
+
+        for (property, value) in &element.rest {
+            let represented = elements_table_columns.iter().any(|(column, _)| {
+                column == RAW_JSON_COL || is_column_of_property(column, property)
+            }) || expected_relations.iter().any(|(name, _)| name == property)
+                || extended_table_columns.contains(property);
+            let is_empty = match value {
+                Value::Null => true,
+                Value::Array(a) => a.is_empty(),
+                _ => false,
+            };
+            if !represented && !is_empty {
+                *unrepresented_properties
+                    .entry(property.to_owned())
+                    .or_default() += 1;
+            }
+        }
+    }
+
+    for (property, count) in unrepresented_properties {
+        findings.push(Finding {
+            check: "unrepresented-property".to_owned(),
+            severity: Severity::Warning,
+            element_id: None,
+            message: format!(
+                "the property {property:?} of {count} elements is not stored in the db"
+            ),
+        });
+    }
+
+    if both_ways {
+        let mut stmt = conn.prepare(&format!("SELECT {pk_col} FROM {elements_table}"))?;
+        let mut rows = stmt.query(())?;
+        while let Some(row) = rows.next()? {
+            let id: String = row.get(0)?;
+            if !dump_ids.contains(&id) {
+                findings.push(Finding {
+                    check: "extra-element".to_owned(),
+                    severity: Severity::Error,
+                    element_id: Some(id),
+                    message: "the element is missing in the dump".to_owned(),
+                });
+            }
+        }
+    }
+
+    info!("verified {} elements", dump_ids.len());
+    report_findings(&findings, json, false, out)
+}
+
+/// Compares a value expected from the dump with the value stored in the db
+///
+/// Numbers are compared by value, as columns may convert between integers and reals.
+pub(crate) fn same_db_value(expected: &RusValue, actual: &RusValue) -> bool {
+    match (expected, actual) {
+        (RusValue::Integer(e), RusValue::Real(a)) | (RusValue::Real(a), RusValue::Integer(e)) => {
+            *e as f64 == *a
+        }
+        (expected, actual) => expected == actual,
+    }
+}
+
+/// Formats a value stored in the db like a SQL literal
+fn format_db_value(value: &RusValue) -> String {
+    match value {
+        RusValue::Null => "NULL".to_owned(),
+        RusValue::Integer(i) => i.to_string(),
+        RusValue::Real(r) => r.to_string(),
+        RusValue::Text(t) => crate::util::escape_sql_str_lit(t),
+        RusValue::Blob(b) => format!("a blob of {} bytes", b.len()),
+    }
+}
@@ -81,3 +81,6982 @@ fn import_missing_schema() {
 
     db_file.close().unwrap();
 }
+
+/// Changes to the embedded schema must append a migration, see `upgrade_db::MIGRATIONS`, and update
+/// the fingerprint and schema version pinned here
+#[test]
+fn init_db_schema_fingerprint() {
+    let db_file = tempfile::NamedTempFile::new().unwrap();
+
+    let output = test_bin::get_test_bin(BIN)
+        .arg(db_file.path())
+        .arg("init-db")
+        .output()
+        .expect("Failed to start {BIN}");
+    assert!(output.status.success());
+
+    let conn = rusqlite::Connection::open(db_file.path()).unwrap();
+    let fingerprint: String = conn
+        .query_row(
+            r#"SELECT "value" FROM "schema_info" WHERE "key" = 'fingerprint'"#,
+            (),
+            |r| r.get(0),
+        )
+        .unwrap();
+    let version: u32 = conn
+        .pragma_query_value(None, "user_version", |r| r.get(0))
+        .unwrap();
+    assert_eq!((fingerprint.as_str(), version), ("c46a103757567366", 2));
+
+    db_file.close().unwrap();
+}
+
+#[test]
+fn init_db_configured_tables() {
+    let db_dir = tempfile::tempdir().unwrap();
+    let db_file = db_dir.path().join("db.sqlite");
+    let config_path = db_dir.path().join("config.toml");
+    std::fs::write(
+        &config_path,
+        "elements-table = \"els\"\n\
+         relations-table = \"rels\"\n\
+         extended-table = \"ext\"\n\
+         polymorphic-props = [\"value\", \"body\"]\n",
+    )
+    .unwrap();
+
+    for command in [
+        &["init-db"][..],
+        &["import-json", "tests/example-dump.json"],
+    ] {
+        let output = test_bin::get_test_bin(BIN)
+            .arg("--config")
+            .arg(&config_path)
+            .arg(&db_file)
+            .args(command)
+            .output()
+            .expect("Failed to start {BIN}");
+        assert!(
+            output.status.success(),
+            "{}",
+            String::from_utf8_lossy(&output.stderr)
+        );
+    }
+
+    let conn = rusqlite::Connection::open(&db_file).unwrap();
+    let count = |table: &str| -> i64 {
+        conn.query_row(&format!(r#"SELECT COUNT(*) FROM "{table}""#), (), |r| {
+            r.get(0)
+        })
+        .unwrap()
+    };
+    assert_eq!(count("els"), 93);
+    assert!(count("rels") > 0);
+    assert_eq!(count("ext"), 0);
+    for table in ["elements", "relations", "extended_properties"] {
+        assert!(
+            conn.prepare(&format!(r#"SELECT * FROM "{table}""#))
+                .is_err()
+        );
+    }
+
+    // the polymorphic properties are typed ANY, the indices are named after the renamed tables
+    let body_type: String = conn
+        .query_row(
+            r#"SELECT "type" FROM pragma_table_info('els') WHERE "name" = 'body'"#,
+            (),
+            |r| r.get(0),
+        )
+        .unwrap();
+    assert_eq!(body_type, "ANY");
+    let index_table: String = conn
+        .query_row(
+            r#"SELECT "tbl_name" FROM sqlite_schema WHERE "name" = 'els.@type'"#,
+            (),
+            |r| r.get(0),
+        )
+        .unwrap();
+    assert_eq!(index_table, "els");
+}
+
+#[test]
+fn import_integer_keys() {
+    let db_file = tempfile::NamedTempFile::new().unwrap();
+
+    let output = test_bin::get_test_bin(BIN)
+        .arg(db_file.path())
+        .arg("init-db")
+        .output()
+        .expect("Failed to start {BIN}");
+
+    assert!(output.status.success());
+
+    let output = test_bin::get_test_bin(BIN)
+        .arg(db_file.path())
+        .arg("import-json")
+        .arg("--integer-keys")
+        .arg("tests/example-dump.json")
+        .output()
+        .expect("Failed to start {BIN}");
+
+    assert!(output.status.success());
+
+    let conn = rusqlite::Connection::open(db_file.path()).unwrap();
+    let count = |table: &str| -> i64 {
+        conn.query_row(&format!(r#"SELECT COUNT(*) FROM "{table}""#), (), |r| {
+            r.get(0)
+        })
+        .unwrap()
+    };
+    assert_eq!(count("elements"), count("element_keys"));
+    assert_eq!(count("relations"), count("keyed_relations"));
+
+    // the keys of deleted elements are deleted along with them
+    let id: String = conn
+        .query_row(
+            r#"SELECT "origin_id" FROM "relations" WHERE "name" = 'ownedElement' LIMIT 1"#,
+            (),
+            |r| r.get(0),
+        )
+        .unwrap();
+    let elements_before = count("elements");
+    let output = test_bin::get_test_bin(BIN)
+        .arg(db_file.path())
+        .arg("delete-element")
+        .arg("--recursive")
+        .arg(&id)
+        .output()
+        .expect("Failed to start {BIN}");
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    assert!(count("elements") < elements_before);
+    assert_eq!(count("elements"), count("element_keys"));
+    let orphaned_keys: i64 = conn
+        .query_row(
+            r#"SELECT COUNT(*) FROM "element_keys" WHERE "@id" NOT IN (SELECT "@id" FROM "elements")"#,
+            (),
+            |r| r.get(0),
+        )
+        .unwrap();
+    assert_eq!(orphaned_keys, 0);
+    assert_eq!(count("relations"), count("keyed_relations"));
+
+    // later imports keep the keys up to date, even without --integer-keys, and the keys of
+    // elements already present stable
+    let keys = || -> Vec<(String, i64)> {
+        let mut stmt = conn
+            .prepare(r#"SELECT "@id", "key" FROM "element_keys" ORDER BY "@id""#)
+            .unwrap();
+        stmt.query_map((), |r| Ok((r.get(0)?, r.get(1)?)))
+            .unwrap()
+            .collect::<Result<_, _>>()
+            .unwrap()
+    };
+    let keys_before = keys();
+    let output = test_bin::get_test_bin(BIN)
+        .arg(db_file.path())
+        .arg("import-json")
+        .arg("tests/example-dump.json")
+        .output()
+        .expect("Failed to start {BIN}");
+    assert!(output.status.success());
+    assert_eq!(count("elements"), elements_before);
+    assert_eq!(count("elements"), count("element_keys"));
+    let keys_after = keys();
+    assert!(keys_before.iter().all(|key| keys_after.contains(key)));
+    assert_eq!(count("relations"), count("keyed_relations"));
+    let unkeyed_relations: i64 = conn
+        .query_row(
+            r#"SELECT COUNT(*) FROM "relations" AS r WHERE NOT EXISTS (
+                SELECT 1 FROM "keyed_relations" AS k
+                JOIN "element_keys" AS o ON o."key" = k."origin_key"
+                JOIN "element_keys" AS t ON t."key" = k."target_key"
+                WHERE k."name" = r."name" AND o."@id" = r."origin_id" AND t."@id" = r."target_id"
+            )"#,
+            (),
+            |r| r.get(0),
+        )
+        .unwrap();
+    assert_eq!(unkeyed_relations, 0);
+
+    db_file.close().unwrap();
+}
+
+#[test]
+fn full_text_index_survives_reimport() {
+    let db_file = tempfile::NamedTempFile::new().unwrap();
+
+    let output = test_bin::get_test_bin(BIN)
+        .arg(db_file.path())
+        .arg("init-db")
+        .output()
+        .expect("Failed to start {BIN}");
+
+    assert!(output.status.success());
+
+    for _ in 0..2 {
+        let output = test_bin::get_test_bin(BIN)
+            .arg(db_file.path())
+            .arg("import-json")
+            .arg("tests/example-dump.json")
+            .output()
+            .expect("Failed to start {BIN}");
+
+        assert!(output.status.success());
+    }
+
+    let conn = rusqlite::Connection::open(db_file.path()).unwrap();
+    let matches: i64 = conn
+        .query_row(
+            r#"SELECT COUNT(*) FROM "elements_fts" WHERE "elements_fts" MATCH 'Wing'"#,
+            (),
+            |r| r.get(0),
+        )
+        .unwrap();
+    assert_eq!(matches, 1);
+
+    let integrity: rusqlite::Result<usize> = conn.execute(
+        r#"INSERT INTO "elements_fts"("elements_fts") VALUES ('integrity-check')"#,
+        (),
+    );
+    assert!(integrity.is_ok());
+
+    db_file.close().unwrap();
+}
+
+/// Creates a db with the example dump imported into it
+fn imported_db() -> tempfile::NamedTempFile {
+    let db_file = tempfile::NamedTempFile::new().unwrap();
+
+    let output = test_bin::get_test_bin(BIN)
+        .arg(db_file.path())
+        .arg("init-db")
+        .output()
+        .expect("Failed to start {BIN}");
+    assert!(output.status.success());
+
+    let output = test_bin::get_test_bin(BIN)
+        .arg(db_file.path())
+        .arg("import-json")
+        .arg("tests/example-dump.json")
+        .output()
+        .expect("Failed to start {BIN}");
+    assert!(output.status.success());
+
+    db_file
+}
+
+/// Creates a db with requirements traced by each kind of trace link
+///
+/// The requirement `r1` is satisfied by the part `d`, verified by the test `t`, derived into the
+/// requirement `r2` and refined by the part `d`.
+fn requirements_db() -> tempfile::NamedTempFile {
+    let db_file = tempfile::NamedTempFile::new().unwrap();
+    let dump_path = db_file.path().with_extension("json");
+    std::fs::write(
+        &dump_path,
+        r#"[
+            {"@id": "r1", "@type": "RequirementUsage", "declaredName": "Req1"},
+            {"@id": "r2", "@type": "RequirementUsage", "declaredName": "Req2"},
+            {"@id": "d", "@type": "PartUsage", "declaredName": "Design"},
+            {"@id": "t", "@type": "VerificationCaseUsage", "declaredName": "Test", "verifiedRequirement": [{"@id": "r1"}]},
+            {"@id": "s", "@type": "SatisfyRequirementUsage", "satisfiedRequirement": {"@id": "r1"}, "satisfyingFeature": {"@id": "d"}},
+            {"@id": "c", "@type": "ConnectionUsage", "source": [{"@id": "r1"}], "target": [{"@id": "r2"}]},
+            {"@id": "dep", "@type": "Dependency", "client": [{"@id": "d"}], "supplier": [{"@id": "r1"}]}
+        ]"#,
+    )
+    .unwrap();
+
+    let output = test_bin::get_test_bin(BIN)
+        .arg(db_file.path())
+        .arg("init-db")
+        .output()
+        .expect("Failed to start {BIN}");
+    assert!(output.status.success());
+
+    let output = test_bin::get_test_bin(BIN)
+        .arg(db_file.path())
+        .arg("import-json")
+        .arg(&dump_path)
+        .output()
+        .expect("Failed to start {BIN}");
+    assert!(output.status.success());
+    std::fs::remove_file(&dump_path).unwrap();
+
+    db_file
+}
+
+#[test]
+fn search() {
+    let db_file = imported_db();
+
+    let output = test_bin::get_test_bin(BIN)
+        .arg(db_file.path())
+        .arg("search")
+        .arg("--type")
+        .arg("PartUsage")
+        .arg("wing")
+        .output()
+        .expect("Failed to start {BIN}");
+
+    assert!(output.status.success());
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert_eq!(stdout.lines().count(), 2);
+    assert!(stdout.contains("AviationExample::A350::Body::LeftWing"));
+
+    // the wildcards of LIKE are matched literally, no name contains them
+    for pattern in ["%", "_", "Left%", "Left_ing"] {
+        let output = test_bin::get_test_bin(BIN)
+            .arg(db_file.path())
+            .arg("search")
+            .arg(pattern)
+            .output()
+            .expect("Failed to start {BIN}");
+        assert!(output.status.success());
+        assert_eq!(output.stdout, b"", "{pattern}");
+    }
+
+    db_file.close().unwrap();
+}
+
+#[test]
+fn tree() {
+    let db_file = imported_db();
+
+    let output = test_bin::get_test_bin(BIN)
+        .arg(db_file.path())
+        .arg("tree")
+        .arg("--max-depth")
+        .arg("1")
+        .arg("AviationExample")
+        .output()
+        .expect("Failed to start {BIN}");
+
+    assert!(output.status.success());
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    let lines: Vec<_> = stdout.lines().collect();
+    assert_eq!(lines.len(), 2);
+    assert!(lines[0].starts_with("AviationExample [Package]"));
+    assert!(lines[1].starts_with("  A350 [PartUsage]"));
+
+    db_file.close().unwrap();
+}
+
+#[test]
+fn tree_ownership_cycle() {
+    let db_file = tempfile::NamedTempFile::new().unwrap();
+    let dump_path = db_file.path().with_extension("json");
+    std::fs::write(
+        &dump_path,
+        r#"[
+            {"@id": "a", "@type": "Package", "declaredName": "A", "ownedElement": [{"@id": "b"}]},
+            {"@id": "b", "@type": "Package", "declaredName": "B", "ownedElement": [{"@id": "c"}]},
+            {"@id": "c", "@type": "Package", "declaredName": "C", "ownedElement": [{"@id": "a"}]}
+        ]"#,
+    )
+    .unwrap();
+
+    let output = test_bin::get_test_bin(BIN)
+        .arg(db_file.path())
+        .arg("init-db")
+        .output()
+        .expect("Failed to start {BIN}");
+    assert!(output.status.success());
+
+    let output = test_bin::get_test_bin(BIN)
+        .arg(db_file.path())
+        .arg("import-json")
+        .arg(&dump_path)
+        .output()
+        .expect("Failed to start {BIN}");
+    assert!(output.status.success());
+
+    let output = test_bin::get_test_bin(BIN)
+        .arg(db_file.path())
+        .arg("tree")
+        .arg("a")
+        .output()
+        .expect("Failed to start {BIN}");
+    assert!(output.status.success());
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    let lines: Vec<_> = stdout.lines().collect();
+    assert_eq!(
+        lines,
+        ["A [Package] a", "  B [Package] b", "    C [Package] c"]
+    );
+
+    std::fs::remove_file(&dump_path).unwrap();
+    db_file.close().unwrap();
+}
+
+#[test]
+fn show() {
+    let db_file = imported_db();
+
+    let output = test_bin::get_test_bin(BIN)
+        .arg(db_file.path())
+        .arg("show")
+        .arg("AviationExample::A350::Body::LeftWing")
+        .output()
+        .expect("Failed to start {BIN}");
+    assert!(output.status.success());
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    let lines: Vec<_> = stdout.lines().collect();
+    assert_eq!(
+        lines[..3],
+        [
+            "@id: 781a48c3-4b2b-484a-accf-47c5e08bbedb",
+            "@type: PartUsage",
+            "declaredName: LeftWing"
+        ]
+    );
+    assert!(lines.contains(&"qualifiedName: AviationExample::A350::Body::LeftWing"));
+
+    // the relations are grouped by name, outgoing ones first
+    let (outgoing, incoming) = stdout.split_once("\nincoming relations:\n").unwrap();
+    assert!(outgoing.contains(
+        "\n  definition:\n    72d3b964-431a-4f5e-97de-5d6db239595d\tPartDefinition\tAviationLibraryATA::StructureATAs::Wing\n"
+    ));
+    assert!(!outgoing.contains("ownedElement:\n    c3b237db-8b19-496b-b873-00fc11d91dc9"));
+    assert!(incoming.contains(
+        "\n  ownedElement:\n    c3b237db-8b19-496b-b873-00fc11d91dc9\tPartUsage\tAviationExample::A350::Body\n"
+    ));
+
+    // the element is found by its id alike
+    let output = test_bin::get_test_bin(BIN)
+        .arg(db_file.path())
+        .arg("show")
+        .arg("781a48c3-4b2b-484a-accf-47c5e08bbedb")
+        .output()
+        .expect("Failed to start {BIN}");
+    assert!(output.status.success());
+    assert_eq!(String::from_utf8(output.stdout).unwrap(), stdout);
+
+    let output = test_bin::get_test_bin(BIN)
+        .arg(db_file.path())
+        .arg("show")
+        .arg("AviationExample::A350::Body::MissingWing")
+        .output()
+        .expect("Failed to start {BIN}");
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains(
+        r#"no element with the id or qualified name "AviationExample::A350::Body::MissingWing" found"#
+    ));
+
+    db_file.close().unwrap();
+}
+
+#[test]
+fn path() {
+    let db_file = imported_db();
+
+    let path = |args: &[&str]| {
+        test_bin::get_test_bin(BIN)
+            .arg(db_file.path())
+            .arg("path")
+            .args(args)
+            .output()
+            .expect("Failed to start {BIN}")
+    };
+
+    let output = path(&[
+        "AviationExample::A350::Body::LeftWing",
+        "AviationLibraryATA::StructureATAs::Wing",
+    ]);
+    assert!(output.status.success());
+    assert_eq!(
+        String::from_utf8(output.stdout).unwrap(),
+        "AviationExample::A350::Body::LeftWing [PartUsage] 781a48c3-4b2b-484a-accf-47c5e08bbedb\n  \
+        --definition--> AviationLibraryATA::StructureATAs::Wing [PartDefinition] 72d3b964-431a-4f5e-97de-5d6db239595d\n"
+    );
+
+    // undirected, relations are followed against their direction as well
+    let output = path(&[
+        "--relation",
+        "ownedElement",
+        "AviationExample::A350::Body::LeftWing",
+        "AviationExample::A350",
+    ]);
+    assert!(output.status.success());
+    assert_eq!(
+        String::from_utf8(output.stdout).unwrap(),
+        "AviationExample::A350::Body::LeftWing [PartUsage] 781a48c3-4b2b-484a-accf-47c5e08bbedb\n  \
+        <--ownedElement-- AviationExample::A350::Body [PartUsage] c3b237db-8b19-496b-b873-00fc11d91dc9\n  \
+        <--ownedElement-- AviationExample::A350 [PartUsage] 850d9e98-453c-4ccb-935a-f5ce70f4604c\n"
+    );
+
+    let output = path(&[
+        "--directed",
+        "--relation",
+        "ownedElement",
+        "AviationExample::A350::Body::LeftWing",
+        "AviationExample::A350",
+    ]);
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains(
+        r#"there is no path from "781a48c3-4b2b-484a-accf-47c5e08bbedb" to "850d9e98-453c-4ccb-935a-f5ce70f4604c""#
+    ));
+
+    let output = path(&[
+        "--directed",
+        "--relation",
+        "ownedElement",
+        "AviationExample::A350",
+        "AviationExample::A350::Body::LeftWing",
+    ]);
+    assert!(output.status.success());
+    assert_eq!(String::from_utf8(output.stdout).unwrap().lines().count(), 3);
+
+    db_file.close().unwrap();
+}
+
+#[test]
+fn impact() {
+    let db_file = imported_db();
+
+    let impact = |args: &[&str]| -> Vec<String> {
+        let output = test_bin::get_test_bin(BIN)
+            .arg(db_file.path())
+            .arg("impact")
+            .args(args)
+            .output()
+            .expect("Failed to start {BIN}");
+        assert!(output.status.success());
+        let mut lines: Vec<_> = String::from_utf8(output.stdout)
+            .unwrap()
+            .lines()
+            .map(str::to_owned)
+            .collect();
+        lines.sort();
+        lines
+    };
+
+    assert_eq!(
+        impact(&[
+            "--relation",
+            "ownedElement",
+            "--max-depth",
+            "1",
+            "AviationExample::A350::Body"
+        ]),
+        [
+            "1\t [Multiplicity] 5aace0eb-318b-457a-9ff5-2fc96c8e0d7f",
+            "1\tAviationExample::A350::Body::LeftWing [PartUsage] 781a48c3-4b2b-484a-accf-47c5e08bbedb",
+            "1\tAviationExample::A350::Body::LongFusalage [PartUsage] ae3c15aa-1343-4ed4-b394-9d2c61dd577e",
+            "1\tAviationExample::A350::Body::RightWing [PartUsage] 524e402d-ec5d-4797-be1b-f7a4f2e3f3d3",
+        ]
+    );
+
+    // in reverse, the owners are affected, each at its distance
+    assert_eq!(
+        impact(&[
+            "--reverse",
+            "--relation",
+            "ownedElement",
+            "AviationExample::A350::Body::LeftWing"
+        ]),
+        [
+            "1\tAviationExample::A350::Body [PartUsage] c3b237db-8b19-496b-b873-00fc11d91dc9",
+            "2\tAviationExample::A350 [PartUsage] 850d9e98-453c-4ccb-935a-f5ce70f4604c",
+            "3\tAviationExample [Package] 23438f8f-44bf-4087-99af-d4dad6574e34",
+        ]
+    );
+
+    // without depth limit, all transitively owned elements are affected, each once
+    let owned = impact(&["--relation", "ownedElement", "AviationExample::A350"]);
+    assert!(owned.contains(
+        &"2\tAviationExample::A350::Body::LeftWing [PartUsage] 781a48c3-4b2b-484a-accf-47c5e08bbedb"
+            .to_owned()
+    ));
+    let mut ids: Vec<_> = owned
+        .iter()
+        .map(|line| line.rsplit_once(' ').unwrap().1)
+        .collect();
+    ids.sort();
+    ids.dedup();
+    assert_eq!(ids.len(), owned.len());
+
+    db_file.close().unwrap();
+}
+
+#[test]
+fn lint() {
+    let db_file = imported_db();
+
+    let output = test_bin::get_test_bin(BIN)
+        .arg(db_file.path())
+        .arg("lint")
+        .arg("--json")
+        .output()
+        .expect("Failed to start {BIN}");
+
+    assert!(output.status.success());
+    let findings: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(findings, serde_json::json!([]));
+
+    db_file.close().unwrap();
+}
+
+#[test]
+fn lint_ownership_cycle() {
+    let db_file = imported_db();
+    let a350 = "850d9e98-453c-4ccb-935a-f5ce70f4604c";
+    let left_wing = "781a48c3-4b2b-484a-accf-47c5e08bbedb";
+
+    // the left wing owns the aircraft it is part of
+    let edit = |command: &str| {
+        let output = test_bin::get_test_bin(BIN)
+            .arg(db_file.path())
+            .arg(command)
+            .arg("owner")
+            .arg(a350)
+            .arg(left_wing)
+            .output()
+            .expect("Failed to start {BIN}");
+        assert!(output.status.success());
+    };
+    edit("add-relation");
+
+    let output = test_bin::get_test_bin(BIN)
+        .arg(db_file.path())
+        .arg("lint")
+        .arg("--json")
+        .output()
+        .expect("Failed to start {BIN}");
+    assert!(!output.status.success());
+    let findings: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    let findings = findings.as_array().unwrap();
+    assert!(!findings.is_empty());
+    for finding in findings {
+        assert_eq!(finding["check"], "cycle-ownership");
+        assert_eq!(finding["severity"], "error");
+        let message = finding["message"].as_str().unwrap();
+        assert!(
+            message.starts_with("cycle in the ownership relations: "),
+            "{message}"
+        );
+        assert!(
+            message.contains(&format!("{a350} -> {left_wing}")),
+            "{message}"
+        );
+    }
+    // the cycle via the owners, besides those via the owning memberships
+    assert!(findings.iter().any(|finding| finding["message"]
+        == format!(
+            "cycle in the ownership relations: {left_wing} -> c3b237db-8b19-496b-b873-00fc11d91dc9 -> {a350} -> {left_wing}"
+        )));
+
+    edit("remove-relation");
+    let output = test_bin::get_test_bin(BIN)
+        .arg(db_file.path())
+        .arg("lint")
+        .output()
+        .expect("Failed to start {BIN}");
+    assert!(output.status.success());
+    assert_eq!(output.stdout, b"");
+
+    db_file.close().unwrap();
+}
+
+#[test]
+fn validate() {
+    let db_file = imported_db();
+    let rules_dir = tempfile::tempdir().unwrap();
+    std::fs::write(
+        rules_dir.path().join("wings.sql"),
+        r#"-- severity: warning
+        SELECT "@id", 'the wing ' || "declaredName" FROM "elements"
+        WHERE "@type" = 'PartUsage' AND "declaredName" LIKE '%Wing'"#,
+    )
+    .unwrap();
+    std::fs::write(rules_dir.path().join("notes.txt"), "SELECT nonsense").unwrap();
+
+    let validate = |args: &[&str]| {
+        test_bin::get_test_bin(BIN)
+            .arg(db_file.path())
+            .arg("validate")
+            .arg("--rules-dir")
+            .arg(rules_dir.path())
+            .args(args)
+            .output()
+            .expect("Failed to start {BIN}")
+    };
+
+    let output = validate(&["--json"]);
+    assert!(output.status.success());
+    let findings: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    let mut findings: Vec<_> = findings
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|f| {
+            assert_eq!(f["check"], "wings");
+            assert_eq!(f["severity"], "warning");
+            f["message"].as_str().unwrap().to_owned()
+        })
+        .collect();
+    findings.sort();
+    assert_eq!(findings, ["the wing LeftWing", "the wing RightWing"]);
+
+    let output = validate(&["--fail-on-warning"]);
+    assert!(!output.status.success());
+    assert_eq!(String::from_utf8(output.stdout).unwrap().lines().count(), 2);
+
+    // the rules of the rules table are run as well, with the severity error by default
+    let conn = rusqlite::Connection::open(db_file.path()).unwrap();
+    conn.execute_batch(
+        r#"CREATE TABLE "validation_rules" ("name" TEXT, "severity" TEXT, "query" TEXT);
+        INSERT INTO "validation_rules" VALUES
+            ('aircraft', 'error', 'SELECT "@id", ''an aircraft'' FROM "elements" WHERE "qualifiedName" = ''AviationExample::A350'''),
+            ('packages', 'error', 'SELECT NULL, ''no packages'' WHERE NOT EXISTS (SELECT 1 FROM "elements" WHERE "@type" = ''Package'')');"#,
+    )
+    .unwrap();
+    drop(conn);
+
+    let output = validate(&[]);
+    assert!(!output.status.success());
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    let mut lines: Vec<_> = stdout.lines().collect();
+    lines.sort();
+    assert_eq!(
+        lines,
+        [
+            "error\taircraft\t850d9e98-453c-4ccb-935a-f5ce70f4604c\tan aircraft",
+            "warning\twings\t524e402d-ec5d-4797-be1b-f7a4f2e3f3d3\tthe wing RightWing",
+            "warning\twings\t781a48c3-4b2b-484a-accf-47c5e08bbedb\tthe wing LeftWing",
+        ]
+    );
+    assert!(
+        String::from_utf8_lossy(&output.stderr).contains("1 checks failed with severity error")
+    );
+
+    db_file.close().unwrap();
+}
+
+#[test]
+fn import_altered_schema() {
+    let db_file = imported_db();
+
+    let conn = rusqlite::Connection::open(db_file.path()).unwrap();
+    conn.execute(r#"ALTER TABLE "elements" ADD COLUMN "foo" TEXT"#, ())
+        .unwrap();
+
+    let output = test_bin::get_test_bin(BIN)
+        .arg(db_file.path())
+        .arg("import-json")
+        .arg("tests/example-dump.json")
+        .output()
+        .expect("Failed to start {BIN}");
+    assert!(!output.status.success());
+
+    let output = test_bin::get_test_bin(BIN)
+        .arg(db_file.path())
+        .arg("import-json")
+        .arg("--skip-schema-check")
+        .arg("tests/example-dump.json")
+        .output()
+        .expect("Failed to start {BIN}");
+    assert!(output.status.success());
+
+    db_file.close().unwrap();
+}
+
+#[test]
+fn import_missing_table() {
+    let db_file = imported_db();
+    let config_path = db_file.path().with_extension("toml");
+    std::fs::write(&config_path, "elements-table = \"els\"\n").unwrap();
+
+    // refused up front, with or without the schema check
+    for args in [&[][..], &["--skip-schema-check"]] {
+        let output = test_bin::get_test_bin(BIN)
+            .arg("--config")
+            .arg(&config_path)
+            .arg(db_file.path())
+            .arg("import-json")
+            .args(args)
+            .arg("tests/example-dump.json")
+            .output()
+            .expect("Failed to start {BIN}");
+        assert_eq!(output.status.code(), Some(3));
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        assert!(
+            stderr.contains(r#"the db lacks the elements table "els""#),
+            "{stderr}"
+        );
+    }
+
+    db_file.close().unwrap();
+}
+
+#[test]
+fn db_diff() {
+    let old_db_file = imported_db();
+    let new_db_file = imported_db();
+
+    let conn = rusqlite::Connection::open(new_db_file.path()).unwrap();
+    conn.execute(
+        r#"UPDATE "elements" SET "declaredName" = 'Airliner' WHERE "qualifiedName" = 'AviationExample::A350'"#,
+        (),
+    )
+    .unwrap();
+
+    let output = test_bin::get_test_bin(BIN)
+        .arg(old_db_file.path())
+        .arg("db-diff")
+        .arg(new_db_file.path())
+        .output()
+        .expect("Failed to start {BIN}");
+
+    assert!(output.status.success());
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    let lines: Vec<_> = stdout.lines().collect();
+    assert_eq!(lines[0], "# PartUsage");
+    assert!(lines[1].starts_with("~ "));
+    assert!(lines[1].ends_with("\tAviationExample::A350\tdeclaredName"));
+
+    old_db_file.close().unwrap();
+    new_db_file.close().unwrap();
+}
+
+#[test]
+fn json_diff_identical() {
+    let db_file = tempfile::NamedTempFile::new().unwrap();
+
+    let output = test_bin::get_test_bin(BIN)
+        .arg(db_file.path())
+        .arg("json-diff")
+        .arg("tests/example-dump.json")
+        .arg("tests/example-dump.json")
+        .output()
+        .expect("Failed to start {BIN}");
+
+    assert!(output.status.success());
+    assert!(output.stdout.is_empty());
+
+    db_file.close().unwrap();
+}
+
+#[test]
+fn json_diff_changes() {
+    let db_file = tempfile::NamedTempFile::new().unwrap();
+    let old_path = db_file.path().with_extension("old.json");
+    let new_path = db_file.path().with_extension("new.json");
+    std::fs::write(
+        &old_path,
+        r#"[{"@id": "a", "@type": "PartUsage", "declaredName": "Car", "mass": 1000, "ownedElement": [{"@id": "b"}, {"@id": "c"}]},
+            {"@id": "b", "@type": "PartUsage", "declaredName": "Wheel"},
+            {"@id": "c", "@type": "PartUsage", "declaredName": "Engine"}]"#,
+    )
+    .unwrap();
+    std::fs::write(
+        &new_path,
+        r#"[{"@id": "a", "@type": "PartUsage", "declaredName": "Car", "mass": 1200, "ownedElement": [{"@id": "d"}, {"@id": "b"}]},
+            {"@id": "b", "@type": "PartUsage", "declaredName": "Wheel"},
+            {"@id": "d", "@type": "RequirementUsage", "declaredName": "Safety"}]"#,
+    )
+    .unwrap();
+    let json_diff = |args: &[&str]| {
+        test_bin::get_test_bin(BIN)
+            .arg(db_file.path())
+            .arg("json-diff")
+            .arg(&old_path)
+            .arg(&new_path)
+            .args(args)
+            .output()
+            .expect("Failed to start {BIN}")
+    };
+
+    // differences are no failure
+    let output = json_diff(&[]);
+    assert_eq!(
+        output.status.code(),
+        Some(0),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    assert_eq!(
+        String::from_utf8(output.stdout).unwrap(),
+        "# PartUsage\n\
+         ~ a\tCar\n\
+         \x20   ~ mass: 1000 -> 1200\n\
+         \x20   + -ownedElement-> d\n\
+         \x20   - -ownedElement-> c\n\
+         - c\tEngine\n\
+         \n\
+         # RequirementUsage\n\
+         + d\tSafety\n\
+         \n"
+    );
+
+    let output = json_diff(&["--json"]);
+    assert_eq!(output.status.code(), Some(0));
+    let diffs: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(
+        diffs,
+        serde_json::json!([
+            {
+                "@id": "a",
+                "@type": "PartUsage",
+                "label": "Car",
+                "change": "changed",
+                "attributes": [{"name": "mass", "old": 1000, "new": 1200}],
+                "relations": [{"name": "ownedElement", "added": ["d"], "removed": ["c"]}]
+            },
+            {"@id": "c", "@type": "PartUsage", "label": "Engine", "change": "removed"},
+            {"@id": "d", "@type": "RequirementUsage", "label": "Safety", "change": "added"}
+        ])
+    );
+
+    // a dump with duplicate ids cannot be compared
+    std::fs::write(&new_path, r#"[{"@id": "a"}, {"@id": "a"}]"#).unwrap();
+    let output = json_diff(&[]);
+    assert_eq!(output.status.code(), Some(1));
+    assert!(output.stdout.is_empty());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(
+        stderr.contains(r#"the element "a" occurs more than once"#),
+        "{stderr}"
+    );
+
+    std::fs::remove_file(&old_path).unwrap();
+    std::fs::remove_file(&new_path).unwrap();
+    db_file.close().unwrap();
+}
+
+#[test]
+fn verify() {
+    let db_file = imported_db();
+
+    let output = test_bin::get_test_bin(BIN)
+        .arg(db_file.path())
+        .arg("verify")
+        .arg("--both-ways")
+        .arg("tests/example-dump.json")
+        .output()
+        .expect("Failed to start {BIN}");
+    assert!(output.status.success());
+
+    let conn = rusqlite::Connection::open(db_file.path()).unwrap();
+    conn.execute(
+        r#"UPDATE "elements" SET "declaredName" = 'Airliner' WHERE "qualifiedName" = 'AviationExample::A350'"#,
+        (),
+    )
+    .unwrap();
+
+    let output = test_bin::get_test_bin(BIN)
+        .arg(db_file.path())
+        .arg("verify")
+        .arg("tests/example-dump.json")
+        .output()
+        .expect("Failed to start {BIN}");
+    assert!(!output.status.success());
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(stdout.contains("error\tcolumn-mismatch\t"));
+
+    db_file.close().unwrap();
+}
+
+#[test]
+fn stats() {
+    let db_file = imported_db();
+
+    let output = test_bin::get_test_bin(BIN)
+        .arg(db_file.path())
+        .arg("stats")
+        .arg("--json")
+        .output()
+        .expect("Failed to start {BIN}");
+    assert!(output.status.success());
+
+    let stats: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(stats["elements_by_type"]["PartUsage"], 12);
+    assert!(stats["db_size"].as_u64().unwrap() > 0);
+
+    db_file.close().unwrap();
+}
+
+#[test]
+fn db_info() {
+    let db_file = imported_db();
+
+    let output = test_bin::get_test_bin(BIN)
+        .arg(db_file.path())
+        .arg("db-info")
+        .arg("--json")
+        .output()
+        .expect("Failed to start {BIN}");
+    assert!(output.status.success());
+
+    let info: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(info["fingerprint"], info["stamped_fingerprint"]);
+    assert_eq!(info["imports"][0]["source"], "tests/example-dump.json");
+    assert_eq!(info["imports"][0]["elements"], 93);
+
+    db_file.close().unwrap();
+}
+
+#[test]
+fn optimize() {
+    let db_file = imported_db();
+
+    let output = test_bin::get_test_bin(BIN)
+        .arg(db_file.path())
+        .arg("optimize")
+        .arg("--apply")
+        .output()
+        .expect("Failed to start {BIN}");
+    assert!(output.status.success());
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(stdout.contains("missing\trelations.name,target_id\t"));
+
+    let output = test_bin::get_test_bin(BIN)
+        .arg(db_file.path())
+        .arg("optimize")
+        .output()
+        .expect("Failed to start {BIN}");
+    assert!(output.status.success());
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(!stdout.contains("missing\t"));
+
+    db_file.close().unwrap();
+}
+
+#[test]
+fn backup() {
+    let db_file = imported_db();
+    let backup_dir = tempfile::tempdir().unwrap();
+    let backup_file = backup_dir.path().join("backup.db");
+
+    for _ in 0..2 {
+        let output = test_bin::get_test_bin(BIN)
+            .arg(db_file.path())
+            .arg("backup")
+            .arg("--force")
+            .arg(&backup_file)
+            .output()
+            .expect("Failed to start {BIN}");
+        assert!(output.status.success());
+    }
+
+    let conn = rusqlite::Connection::open(&backup_file).unwrap();
+    let elements: u64 = conn
+        .query_row(r#"SELECT COUNT(*) FROM "elements""#, (), |row| row.get(0))
+        .unwrap();
+    assert_eq!(elements, 93);
+
+    db_file.close().unwrap();
+}
+
+#[test]
+fn read_only() {
+    let db_file = imported_db();
+
+    let output = test_bin::get_test_bin(BIN)
+        .arg("--read-only")
+        .arg(db_file.path())
+        .arg("stats")
+        .output()
+        .expect("Failed to start {BIN}");
+    assert!(output.status.success());
+
+    let output = test_bin::get_test_bin(BIN)
+        .arg("--read-only")
+        .arg(db_file.path())
+        .arg("import-json")
+        .arg("tests/example-dump.json")
+        .output()
+        .expect("Failed to start {BIN}");
+    assert!(!output.status.success());
+
+    // SQL scripts may run on a read-only db, but the db rejects modifying it
+    let sql = |sql: &str| {
+        test_bin::get_test_bin(BIN)
+            .arg("--read-only")
+            .arg(db_file.path())
+            .arg("sql")
+            .arg("--execute")
+            .arg(sql)
+            .output()
+            .expect("Failed to start {BIN}")
+    };
+    let output = sql(r#"SELECT COUNT(*) AS "n" FROM "elements""#);
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    let output = sql(r#"DELETE FROM "elements""#);
+    assert_eq!(output.status.code(), Some(8));
+    assert!(String::from_utf8_lossy(&output.stderr).contains("readonly database"));
+
+    db_file.close().unwrap();
+}
+
+#[test]
+fn read_only_commands() {
+    let db_file = imported_db();
+
+    // a write-protected copy in a write-protected directory, which also rules out journal files
+    let db_dir = tempfile::tempdir().unwrap();
+    let out_dir = tempfile::tempdir().unwrap();
+    let db_path = db_dir.path().join("model.db");
+    std::fs::copy(db_file.path(), &db_path).unwrap();
+    let set_readonly = |path: &std::path::Path, readonly: bool| {
+        let mut permissions = std::fs::metadata(path).unwrap().permissions();
+        permissions.set_readonly(readonly);
+        std::fs::set_permissions(path, permissions).unwrap();
+    };
+    set_readonly(&db_path, true);
+    set_readonly(db_dir.path(), true);
+
+    let out = |name: &str| out_dir.path().join(name).to_string_lossy().into_owned();
+    let element = "AviationExample::A350";
+    let dump = "tests/example-dump.json";
+    let schema_path = out("schema.json");
+    std::fs::write(&schema_path, TINY_JSON_SCHEMA).unwrap();
+    let db = db_path.to_string_lossy().into_owned();
+
+    // the commands which only read the db, with the exit code expected on the example model; the
+    // API server is unreachable, and the tiny schema does not match the embedded one
+    #[allow(unused_mut)]
+    let mut read_only_commands: Vec<(Vec<String>, i32)> = [
+        (vec!["backup", &out("backup.db")], 0),
+        (vec!["search", "A350"], 0),
+        (vec!["tree"], 0),
+        (vec!["show", element], 0),
+        (vec!["trace", element], 0),
+        (vec!["trace-matrix", &out("matrix.xlsx")], 0),
+        (vec!["coverage"], 0),
+        (vec!["path", element, "AviationExample"], 0),
+        (vec!["impact", element], 0),
+        (vec!["bom", element], 0),
+        (vec!["export-graph", "--format", "dot"], 0),
+        (vec!["export-parquet", &out("parquet")], 0),
+        (vec!["export-anonymized", &out("anonymized.json")], 0),
+        (vec!["export-metadata"], 0),
+        (vec!["export-plantuml", element], 0),
+        (vec!["report"], 0),
+        (vec!["db-diff", &db], 0),
+        (vec!["json-diff", dump, dump], 0),
+        (vec!["stats"], 0),
+        (vec!["db-info"], 0),
+        (
+            vec![
+                "bench",
+                dump,
+                "--runs",
+                "1",
+                "--variant",
+                "base:",
+                "--dir",
+                &out(""),
+            ],
+            0,
+        ),
+        (vec!["lint"], 0),
+        (vec!["sql", "--execute", "SELECT 1"], 0),
+        (vec!["validate"], 0),
+        (vec!["verify", dump], 0),
+        (
+            vec!["json-schema-to-sql-schema", "--no-init", &schema_path],
+            0,
+        ),
+        (vec!["schema-diff", &schema_path, &schema_path], 0),
+        (vec!["migrate-schema", &schema_path], 1),
+        (vec!["regen-embedded-schema", &schema_path], 1),
+    ]
+    .into_iter()
+    .map(|(args, code)| (args.into_iter().map(str::to_owned).collect(), code))
+    .collect();
+    #[cfg(feature = "fetch")]
+    {
+        let api = "http://127.0.0.1:9/api";
+        read_only_commands.extend(
+            [
+                vec!["download-schema", "--url", api, &out("downloaded.json")],
+                vec![
+                    "fetch",
+                    "--no-import",
+                    api,
+                    "project-id",
+                    "p",
+                    "commit-id",
+                    "c",
+                ],
+                vec!["push", "--dry-run", api, "p"],
+                vec!["create-project", api, "name"],
+                vec!["create-branch", api, "p", "name"],
+            ]
+            .into_iter()
+            .map(|args| (args.into_iter().map(str::to_owned).collect(), 5)),
+        );
+    }
+    let writing_commands = [
+        "import-json",
+        "init-db",
+        "upgrade-db",
+        "set-attribute",
+        "delete-element",
+        "add-relation",
+        "remove-relation",
+        "optimize",
+        "sync",
+        "clone",
+    ];
+
+    // each command has to be classified, a new command must not be forgotten
+    let output = test_bin::get_test_bin(BIN)
+        .arg("--help")
+        .output()
+        .expect("Failed to start {BIN}");
+    let help = String::from_utf8(output.stdout).unwrap();
+    let commands: Vec<_> = help
+        .lines()
+        .skip_while(|line| *line != "Commands:")
+        .skip(1)
+        .take_while(|line| !line.is_empty())
+        .filter_map(|line| line.split_whitespace().next())
+        .filter(|command| *command != "help")
+        .collect();
+    assert!(commands.contains(&"sql"));
+    for command in &commands {
+        assert!(
+            writing_commands.contains(command)
+                || read_only_commands
+                    .iter()
+                    .any(|(args, _)| args[0] == *command),
+            "the command {command} is neither listed as read-only nor as writing command"
+        );
+    }
+
+    for (args, code) in &read_only_commands {
+        let output = test_bin::get_test_bin(BIN)
+            .arg("--read-only")
+            .arg(&db_path)
+            .args(args)
+            .output()
+            .expect("Failed to start {BIN}");
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        assert_eq!(output.status.code(), Some(*code), "{args:?}: {stderr}");
+        assert!(!stderr.contains("read-only db"), "{args:?}: {stderr}");
+        assert!(!stderr.contains("readonly database"), "{args:?}: {stderr}");
+    }
+
+    set_readonly(db_dir.path(), false);
+    set_readonly(&db_path, false);
+    db_file.close().unwrap();
+}
+
+#[test]
+fn in_memory() {
+    let db_dir = tempfile::tempdir().unwrap();
+    let db_file = db_dir.path().join("model.db");
+
+    for command in [
+        &["init-db"][..],
+        &["import-json", "tests/example-dump.json"],
+    ] {
+        let output = test_bin::get_test_bin(BIN)
+            .arg("--save-to")
+            .arg(&db_file)
+            .arg(":memory:")
+            .args(command)
+            .output()
+            .expect("Failed to start {BIN}");
+        assert!(output.status.success());
+    }
+
+    let conn = rusqlite::Connection::open(&db_file).unwrap();
+    let elements: u64 = conn
+        .query_row(r#"SELECT COUNT(*) FROM "elements""#, (), |row| row.get(0))
+        .unwrap();
+    assert_eq!(elements, 93);
+}
+
+#[test]
+fn pragmas() {
+    let db_dir = tempfile::tempdir().unwrap();
+    let config_path = db_dir.path().join("config.toml");
+    std::fs::write(&config_path, "[pragmas]\npage-size = 8192\nthreads = 2\n").unwrap();
+
+    // the pragmas of the config apply, unless overridden on the command line
+    for (pragmas, page_size) in [(&[][..], 8192), (&["--pragma", "page_size=16384"], 16384)] {
+        let db_file = db_dir.path().join(format!("{page_size}.db"));
+        let output = test_bin::get_test_bin(BIN)
+            .arg("--config")
+            .arg(&config_path)
+            .arg(&db_file)
+            .arg("init-db")
+            .output()
+            .expect("Failed to start {BIN}");
+        assert!(output.status.success());
+
+        // the page size of an existing db changes once vacuumed
+        let output = test_bin::get_test_bin(BIN)
+            .arg("-v")
+            .arg("--config")
+            .arg(&config_path)
+            .args(pragmas)
+            .arg(&db_file)
+            .arg("import-json")
+            .arg("--vacuum")
+            .arg("tests/example-dump.json")
+            .output()
+            .expect("Failed to start {BIN}");
+        assert!(
+            output.status.success(),
+            "{}",
+            String::from_utf8_lossy(&output.stderr)
+        );
+        assert!(String::from_utf8_lossy(&output.stderr).contains("allowing 2 auxiliary threads"));
+
+        let conn = rusqlite::Connection::open(&db_file).unwrap();
+        let actual: u32 = conn
+            .pragma_query_value(None, "page_size", |row| row.get(0))
+            .unwrap();
+        assert_eq!(actual, page_size);
+    }
+
+    for (pragma, error) in [
+        (
+            "temp_store=MEMORY",
+            r#"the pragma "temp_store" can not be set"#,
+        ),
+        (
+            "cache_size=lots",
+            r#"invalid value "lots" for the pragma "cache_size""#,
+        ),
+    ] {
+        let output = test_bin::get_test_bin(BIN)
+            .arg("--pragma")
+            .arg(pragma)
+            .arg(db_dir.path().join("invalid.db"))
+            .arg("init-db")
+            .output()
+            .expect("Failed to start {BIN}");
+        assert!(!output.status.success());
+        assert!(
+            String::from_utf8_lossy(&output.stderr).contains(error),
+            "{}",
+            String::from_utf8_lossy(&output.stderr)
+        );
+    }
+}
+
+#[test]
+fn busy_timeout() {
+    let db_file = imported_db();
+    let stats = |busy_timeout: &str| {
+        test_bin::get_test_bin(BIN)
+            .arg("--busy-timeout")
+            .arg(busy_timeout)
+            .arg(db_file.path())
+            .arg("stats")
+            .output()
+            .expect("Failed to start {BIN}")
+    };
+
+    // the lock of a writer is waited for until the timeout, then the command fails
+    let conn = rusqlite::Connection::open(db_file.path()).unwrap();
+    conn.execute_batch("BEGIN EXCLUSIVE").unwrap();
+    let t0 = std::time::Instant::now();
+    let output = stats("700");
+    assert!(t0.elapsed() >= std::time::Duration::from_millis(700));
+    assert!(!output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("database is locked"), "{stderr}");
+
+    // a lock released within the timeout is no failure
+    let writer = std::thread::spawn(move || {
+        std::thread::sleep(std::time::Duration::from_millis(300));
+        conn.execute_batch("COMMIT").unwrap();
+    });
+    let output = stats("5000");
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    writer.join().unwrap();
+
+    db_file.close().unwrap();
+}
+
+#[test]
+fn journal_mode_wal() {
+    let db_file = imported_db();
+    let journal_mode = |conn: &rusqlite::Connection| -> String {
+        conn.pragma_query_value(None, "journal_mode", |row| row.get(0))
+            .unwrap()
+    };
+
+    let output = test_bin::get_test_bin(BIN)
+        .arg("-v")
+        .arg("--journal-mode")
+        .arg("wal")
+        .arg(db_file.path())
+        .arg("import-json")
+        .arg("tests/example-dump.json")
+        .output()
+        .expect("Failed to start {BIN}");
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("the db is in WAL journal mode"), "{stderr}");
+    assert!(stderr.contains("checkpointing the write-ahead log"));
+
+    // the journal mode persists, and a reader does not block the import
+    let conn = rusqlite::Connection::open(db_file.path()).unwrap();
+    assert_eq!(journal_mode(&conn), "wal");
+    conn.execute_batch("BEGIN").unwrap();
+    let elements: i64 = conn
+        .query_row(r#"SELECT COUNT(*) FROM "elements""#, [], |row| row.get(0))
+        .unwrap();
+    assert_eq!(elements, 93);
+
+    let output = test_bin::get_test_bin(BIN)
+        .arg("--busy-timeout")
+        .arg("100")
+        .arg(db_file.path())
+        .arg("import-json")
+        .arg("tests/example-dump.json")
+        .output()
+        .expect("Failed to start {BIN}");
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    // the reader still holds its snapshot, which the checkpoint must not overwrite
+    assert!(
+        String::from_utf8_lossy(&output.stderr)
+            .contains("the write-ahead log could not be checkpointed completely")
+    );
+    conn.execute_batch("COMMIT").unwrap();
+    drop(conn);
+
+    let output = test_bin::get_test_bin(BIN)
+        .arg("--wal")
+        .arg("--read-only")
+        .arg(db_file.path())
+        .arg("stats")
+        .output()
+        .expect("Failed to start {BIN}");
+    assert!(!output.status.success());
+
+    // switching back to the rollback journal, which requires the db to be used by nobody else
+    let output = test_bin::get_test_bin(BIN)
+        .arg("--journal-mode")
+        .arg("delete")
+        .arg(db_file.path())
+        .arg("stats")
+        .output()
+        .expect("Failed to start {BIN}");
+    assert!(output.status.success());
+    let conn = rusqlite::Connection::open(db_file.path()).unwrap();
+    assert_eq!(journal_mode(&conn), "delete");
+    drop(conn);
+
+    db_file.close().unwrap();
+}
+
+#[test]
+fn max_memory() {
+    let db_dir = tempfile::tempdir().unwrap();
+    let db_file = db_dir.path().join("model.db");
+    let config_path = db_dir.path().join("config.toml");
+    std::fs::write(&config_path, "max-memory = 33554432\n").unwrap();
+
+    let output = test_bin::get_test_bin(BIN)
+        .arg(&db_file)
+        .arg("init-db")
+        .output()
+        .expect("Failed to start {BIN}");
+    assert!(output.status.success());
+
+    // the limit of the command line takes precedence over the one of the config
+    for (limit, heap_limit, cache_limit) in [
+        (&[][..], "33554432", "16777216"),
+        (&["--max-memory", "8M"], "8388608", "4194304"),
+    ] {
+        let output = test_bin::get_test_bin(BIN)
+            .arg("-v")
+            .arg("--config")
+            .arg(&config_path)
+            .args(limit)
+            .arg(&db_file)
+            .arg("import-json")
+            .arg("tests/example-dump.json")
+            .output()
+            .expect("Failed to start {BIN}");
+        assert!(
+            output.status.success(),
+            "{}",
+            String::from_utf8_lossy(&output.stderr)
+        );
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        assert!(
+            stderr.contains(&format!("limited the heap of SQLite to {heap_limit} bytes")),
+            "{stderr}"
+        );
+        assert!(stderr.contains(&format!(
+            "bounding the cache to {cache_limit} bytes due to the memory limit"
+        )));
+    }
+
+    for limit in ["0", "2T", "lots"] {
+        let output = test_bin::get_test_bin(BIN)
+            .arg("--max-memory")
+            .arg(limit)
+            .arg(&db_file)
+            .arg("stats")
+            .output()
+            .expect("Failed to start {BIN}");
+        assert!(!output.status.success(), "{limit}");
+        assert!(
+            String::from_utf8_lossy(&output.stderr).contains("expected a positive number of bytes")
+        );
+    }
+}
+
+#[test]
+fn attach() {
+    let old_db_file = imported_db();
+    let new_db_file = imported_db();
+
+    let conn = rusqlite::Connection::open(new_db_file.path()).unwrap();
+    conn.execute(
+        r#"UPDATE "elements" SET "declaredName" = 'Airliner' WHERE "qualifiedName" = 'AviationExample::A350'"#,
+        (),
+    )
+    .unwrap();
+
+    let rules_dir = tempfile::tempdir().unwrap();
+    std::fs::write(
+        rules_dir.path().join("renamed.sql"),
+        r#"-- severity: warning
+SELECT e."@id", 'renamed' FROM "elements" AS e
+JOIN "old"."elements" AS o ON o."@id" = e."@id"
+WHERE e."declaredName" IS NOT o."declaredName""#,
+    )
+    .unwrap();
+
+    let output = test_bin::get_test_bin(BIN)
+        .arg("--attach")
+        .arg(format!("old={}", old_db_file.path().display()))
+        .arg(new_db_file.path())
+        .arg("validate")
+        .arg("--rules-dir")
+        .arg(rules_dir.path())
+        .output()
+        .expect("Failed to start {BIN}");
+    assert!(output.status.success());
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert_eq!(stdout.lines().count(), 1);
+    assert!(stdout.starts_with("warning\trenamed\t"));
+
+    old_db_file.close().unwrap();
+    new_db_file.close().unwrap();
+}
+
+#[test]
+fn edit_elements() {
+    let db_file = imported_db();
+    let a350 = "850d9e98-453c-4ccb-935a-f5ce70f4604c";
+
+    let output = test_bin::get_test_bin(BIN)
+        .arg(db_file.path())
+        .arg("set-attribute")
+        .arg(a350)
+        .arg("declaredName")
+        .arg("Airliner")
+        .output()
+        .expect("Failed to start {BIN}");
+    assert!(output.status.success());
+
+    let output = test_bin::get_test_bin(BIN)
+        .arg(db_file.path())
+        .arg("set-attribute")
+        .arg(a350)
+        .arg("noSuchAttribute")
+        .arg("1")
+        .output()
+        .expect("Failed to start {BIN}");
+    assert!(!output.status.success());
+
+    let conn = rusqlite::Connection::open(db_file.path()).unwrap();
+    let name: String = conn
+        .query_row(
+            r#"SELECT "declaredName" FROM "elements" WHERE "@id" = ?1"#,
+            [a350],
+            |r| r.get(0),
+        )
+        .unwrap();
+    assert_eq!(name, "Airliner");
+
+    let output = test_bin::get_test_bin(BIN)
+        .arg(db_file.path())
+        .arg("delete-element")
+        .arg("--recursive")
+        .arg(a350)
+        .output()
+        .expect("Failed to start {BIN}");
+    assert!(output.status.success());
+
+    let dangling: i64 = conn
+        .query_row(
+            r#"SELECT COUNT(*) FROM "relations" WHERE "origin_id" = ?1 OR "target_id" = ?1"#,
+            [a350],
+            |r| r.get(0),
+        )
+        .unwrap();
+    assert_eq!(dangling, 0);
+    let body: i64 = conn
+        .query_row(
+            r#"SELECT COUNT(*) FROM "elements" WHERE "qualifiedName" LIKE 'AviationExample::A350%'"#,
+            (),
+            |r| r.get(0),
+        )
+        .unwrap();
+    assert_eq!(body, 0);
+
+    db_file.close().unwrap();
+}
+
+#[test]
+fn edit_relations() {
+    let db_file = imported_db();
+    let package = "23438f8f-44bf-4087-99af-d4dad6574e34";
+    let a350 = "850d9e98-453c-4ccb-935a-f5ce70f4604c";
+
+    for (command, name, success) in [
+        ("add-relation", "noSuchRelation", false),
+        ("add-relation", "ownedMember", false),
+        ("remove-relation", "ownedElement", true),
+        ("remove-relation", "ownedElement", false),
+        ("add-relation", "ownedElement", true),
+    ] {
+        let output = test_bin::get_test_bin(BIN)
+            .arg(db_file.path())
+            .arg(command)
+            .arg(name)
+            .arg(package)
+            .arg(a350)
+            .output()
+            .expect("Failed to start {BIN}");
+        assert_eq!(output.status.success(), success, "{command} {name}");
+    }
+
+    db_file.close().unwrap();
+}
+
+#[test]
+fn export_graphml() {
+    let db_file = imported_db();
+
+    let output = test_bin::get_test_bin(BIN)
+        .arg(db_file.path())
+        .arg("export-graph")
+        .arg("--format")
+        .arg("graphml")
+        .output()
+        .expect("Failed to start {BIN}");
+    assert!(output.status.success());
+
+    let graphml = String::from_utf8(output.stdout).unwrap();
+    assert!(graphml.starts_with("<?xml"));
+    assert_eq!(graphml.matches("<node ").count(), 93);
+    assert!(graphml.contains(
+        r#"<node id="850d9e98-453c-4ccb-935a-f5ce70f4604c"><data key="label">A350</data>"#
+    ));
+    assert!(graphml.trim_end().ends_with("</graphml>"));
+
+    db_file.close().unwrap();
+}
+
+#[test]
+fn export_dot_subgraph() {
+    let db_file = imported_db();
+
+    let output = test_bin::get_test_bin(BIN)
+        .arg(db_file.path())
+        .arg("export-graph")
+        .arg("--format")
+        .arg("dot")
+        .arg("AviationExample::A350")
+        .arg("--relation")
+        .arg("ownedElement")
+        .arg("--max-depth")
+        .arg("1")
+        .output()
+        .expect("Failed to start {BIN}");
+    assert!(output.status.success());
+
+    let dot = String::from_utf8(output.stdout).unwrap();
+    assert!(dot.starts_with("digraph model {"));
+    assert!(dot.contains(r#"[label="A350\n«PartUsage»"];"#));
+    assert_eq!(dot.matches(" -> ").count(), 2);
+    assert_eq!(dot.matches(r#"[label="ownedElement"]"#).count(), 2);
+
+    db_file.close().unwrap();
+}
+
+#[test]
+fn export_cypher() {
+    let db_file = imported_db();
+
+    let output = test_bin::get_test_bin(BIN)
+        .arg(db_file.path())
+        .arg("export-graph")
+        .arg("--format")
+        .arg("cypher")
+        .output()
+        .expect("Failed to start {BIN}");
+    assert!(output.status.success());
+
+    let cypher = String::from_utf8(output.stdout).unwrap();
+    assert!(cypher.starts_with("CREATE CONSTRAINT"));
+    assert_eq!(cypher.matches("{id: '").count(), 93);
+    assert!(
+        cypher.contains(
+            "{id: '850d9e98-453c-4ccb-935a-f5ce70f4604c', type: 'PartUsage', name: 'A350'}"
+        )
+    );
+    assert!(cypher.contains("CREATE (o)-[:`ownedElement`]->(t);"));
+
+    db_file.close().unwrap();
+}
+
+#[test]
+fn export_turtle() {
+    let db_file = imported_db();
+
+    let output = test_bin::get_test_bin(BIN)
+        .arg(db_file.path())
+        .arg("export-graph")
+        .arg("--format")
+        .arg("turtle")
+        .arg("AviationExample::A350")
+        .arg("--relation")
+        .arg("ownedElement")
+        .arg("--max-depth")
+        .arg("1")
+        .output()
+        .expect("Failed to start {BIN}");
+    assert!(output.status.success());
+
+    let turtle = String::from_utf8(output.stdout).unwrap();
+    assert!(turtle.contains("@prefix sysml: <"));
+    assert!(turtle.contains(
+        "<urn:uuid:850d9e98-453c-4ccb-935a-f5ce70f4604c> a sysml:PartUsage ;\n    rdfs:label \"A350\" ;\n    sysml:ownedElement <urn:uuid:"
+    ));
+    assert_eq!(turtle.matches(" a sysml:PartUsage ;").count(), 3);
+
+    db_file.close().unwrap();
+}
+
+#[test]
+fn export_parquet() {
+    let db_file = imported_db();
+    let out_dir = tempfile::tempdir().unwrap();
+
+    for (force, success) in [(false, true), (false, false), (true, true)] {
+        let mut command = test_bin::get_test_bin(BIN);
+        command
+            .arg(db_file.path())
+            .arg("export-parquet")
+            .arg(out_dir.path());
+        if force {
+            command.arg("--force");
+        }
+        let output = command.output().expect("Failed to start {BIN}");
+        assert_eq!(output.status.success(), success, "force: {force}");
+    }
+
+    let conn = rusqlite::Connection::open(db_file.path()).unwrap();
+    for table in ["elements", "relations", "extended_properties"] {
+        let (columns, rows) = read_parquet(&out_dir.path().join(format!("{table}.parquet")));
+
+        let mut stmt = conn
+            .prepare(&format!(r#"SELECT * FROM "{table}""#))
+            .unwrap();
+        let db_columns: Vec<String> = stmt.column_names().into_iter().map(Into::into).collect();
+        assert_eq!(columns, db_columns, "{table}");
+        let db_rows: Vec<Vec<Option<String>>> = stmt
+            .query_map([], |row| {
+                (0..db_columns.len())
+                    .map(|idx| {
+                        Ok(match row.get_ref(idx)? {
+                            rusqlite::types::ValueRef::Null => None,
+                            rusqlite::types::ValueRef::Integer(i) => Some(i.to_string()),
+                            rusqlite::types::ValueRef::Real(r) => Some(r.to_string()),
+                            rusqlite::types::ValueRef::Text(t)
+                            | rusqlite::types::ValueRef::Blob(t) => {
+                                Some(String::from_utf8_lossy(t).into_owned())
+                            }
+                        })
+                    })
+                    .collect()
+            })
+            .unwrap()
+            .collect::<Result<_, _>>()
+            .unwrap();
+        assert!(!db_rows.is_empty() || table == "extended_properties");
+        assert_eq!(rows, db_rows, "{table}");
+    }
+
+    db_file.close().unwrap();
+}
+
+/// Reads a Parquet file back, returning its column names and its values as strings
+fn read_parquet(path: &std::path::Path) -> (Vec<String>, Vec<Vec<Option<String>>>) {
+    use parquet::{
+        file::reader::{FileReader, SerializedFileReader},
+        record::Field,
+    };
+
+    let reader = SerializedFileReader::new(std::fs::File::open(path).unwrap()).unwrap();
+    let columns = reader
+        .metadata()
+        .file_metadata()
+        .schema_descr()
+        .columns()
+        .iter()
+        .map(|column| column.name().to_owned())
+        .collect();
+    let rows = reader
+        .get_row_iter(None)
+        .unwrap()
+        .map(|row| {
+            row.unwrap()
+                .get_column_iter()
+                .map(|(_, field)| match field {
+                    Field::Null => None,
+                    Field::Long(i) => Some(i.to_string()),
+                    Field::Double(r) => Some(r.to_string()),
+                    Field::Str(s) => Some(s.to_owned()),
+                    Field::Bytes(b) => Some(String::from_utf8_lossy(b.data()).into_owned()),
+                    field => panic!("unexpected field {field:?}"),
+                })
+                .collect()
+        })
+        .collect();
+    (columns, rows)
+}
+
+#[test]
+fn export_parquet_row_groups() {
+    use parquet::file::reader::{FileReader, SerializedFileReader};
+
+    let db_file = tempfile::NamedTempFile::new().unwrap();
+    let output = test_bin::get_test_bin(BIN)
+        .arg(db_file.path())
+        .arg("init-db")
+        .output()
+        .expect("Failed to start {BIN}");
+    assert!(output.status.success());
+
+    // enough rows for two row groups, and names long enough for two pages in the first one
+    let num_rows = 20_000;
+    let mut conn = rusqlite::Connection::open(db_file.path()).unwrap();
+    let tx = conn.transaction().unwrap();
+    for idx in 0..num_rows {
+        tx.execute(
+            r#"INSERT INTO "elements" ("@id", "@type", "declaredName") VALUES (?1, 'Package', ?2)"#,
+            (format!("e{idx}"), format!("{idx:0>100}")),
+        )
+        .unwrap();
+    }
+    tx.commit().unwrap();
+    drop(conn);
+
+    let out_dir = tempfile::tempdir().unwrap();
+    let output = test_bin::get_test_bin(BIN)
+        .arg(db_file.path())
+        .arg("export-parquet")
+        .arg(out_dir.path())
+        .output()
+        .expect("Failed to start {BIN}");
+    assert!(output.status.success());
+
+    let path = out_dir.path().join("elements.parquet");
+    let reader = SerializedFileReader::new(std::fs::File::open(&path).unwrap()).unwrap();
+    assert_eq!(reader.metadata().num_row_groups(), 2);
+    assert_eq!(reader.metadata().file_metadata().num_rows(), num_rows);
+
+    let (columns, rows) = read_parquet(&path);
+    let id_idx = columns.iter().position(|c| c == "@id").unwrap();
+    let name_idx = columns.iter().position(|c| c == "declaredName").unwrap();
+    assert_eq!(rows.len(), num_rows as usize);
+    for (idx, row) in rows.iter().enumerate() {
+        assert_eq!(row[id_idx], Some(format!("e{idx}")));
+        assert_eq!(row[name_idx], Some(format!("{idx:0>100}")));
+    }
+
+    db_file.close().unwrap();
+}
+
+#[test]
+fn export_metadata() {
+    let db_file = imported_db();
+
+    let output = test_bin::get_test_bin(BIN)
+        .arg(db_file.path())
+        .arg("export-metadata")
+        .arg("--title")
+        .arg("Aviation")
+        .output()
+        .expect("Failed to start {BIN}");
+    assert!(output.status.success());
+
+    let metadata: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(metadata["title"], "Aviation");
+    let db_name = db_file.path().file_stem().unwrap().to_str().unwrap();
+    let db = &metadata["databases"][db_name];
+    assert!(db["tables"]["elements"]["description"].is_string());
+    assert!(db["tables"]["relations"]["columns"]["target_id"].is_string());
+    assert!(
+        db["queries"]["owned_elements"]["sql"]
+            .as_str()
+            .unwrap()
+            .contains(":id")
+    );
+
+    db_file.close().unwrap();
+}
+
+#[test]
+fn trace() {
+    let db_file = requirements_db();
+
+    let output = test_bin::get_test_bin(BIN)
+        .arg(db_file.path())
+        .arg("trace")
+        .arg("r1")
+        .output()
+        .expect("Failed to start {BIN}");
+    assert!(output.status.success());
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    let links: Vec<_> = stdout.lines().skip(1).map(str::trim).collect();
+    // the derivation is no refinement, and the refinement no derivation
+    assert_eq!(
+        links,
+        [
+            "satisfy <- Design [PartUsage] d",
+            "verify <- Test [VerificationCaseUsage] t",
+            "derive <- Req2 [RequirementUsage] r2",
+            "refine <- Design [PartUsage] d",
+        ]
+    );
+
+    db_file.close().unwrap();
+}
+
+#[test]
+fn trace_matrix() {
+    use calamine::{Reader, Xlsx, open_workbook};
+
+    let db_file = requirements_db();
+    let out_dir = tempfile::tempdir().unwrap();
+    let xlsx_path = out_dir.path().join("trace.xlsx");
+
+    let output = test_bin::get_test_bin(BIN)
+        .arg(db_file.path())
+        .arg("trace-matrix")
+        .arg(&xlsx_path)
+        .output()
+        .expect("Failed to start {BIN}");
+    assert!(output.status.success());
+
+    let mut workbook: Xlsx<_> = open_workbook(&xlsx_path).unwrap();
+    assert_eq!(
+        workbook.sheet_names(),
+        ["satisfy", "verify", "derive", "refine"]
+    );
+    let mut cells = |sheet: &str| -> Vec<Vec<String>> {
+        workbook
+            .worksheet_range(sheet)
+            .unwrap()
+            .rows()
+            .map(|row| row.iter().map(ToString::to_string).collect())
+            .collect()
+    };
+
+    // the labels and ids of the tracing elements head the columns, one row per requirement
+    assert_eq!(
+        cells("satisfy"),
+        [
+            ["requirement", "id", "satisfy", "Design"],
+            ["", "", "", "d"],
+            ["Req1", "r1", "1", "x"],
+            ["Req2", "r2", "0", ""],
+        ]
+    );
+    assert_eq!(
+        cells("verify"),
+        [
+            ["requirement", "id", "verify", "Test"],
+            ["", "", "", "t"],
+            ["Req1", "r1", "1", "x"],
+            ["Req2", "r2", "0", ""],
+        ]
+    );
+    assert_eq!(
+        cells("derive"),
+        [
+            ["requirement", "id", "derive", "Req2"],
+            ["", "", "", "r2"],
+            ["Req1", "r1", "1", "x"],
+            ["Req2", "r2", "0", ""],
+        ]
+    );
+    assert_eq!(
+        cells("refine"),
+        [
+            ["requirement", "id", "refine", "Design"],
+            ["", "", "", "d"],
+            ["Req1", "r1", "1", "x"],
+            ["Req2", "r2", "0", ""],
+        ]
+    );
+
+    db_file.close().unwrap();
+}
+
+#[test]
+fn report() {
+    let db_file = imported_db();
+
+    for (format, heading) in [("markdown", "## Packages"), ("html", "<h2>Packages</h2>")] {
+        let output = test_bin::get_test_bin(BIN)
+            .arg(db_file.path())
+            .arg("report")
+            .arg("--format")
+            .arg(format)
+            .output()
+            .expect("Failed to start {BIN}");
+
+        assert!(output.status.success(), "{format}");
+        let stdout = String::from_utf8(output.stdout).unwrap();
+        assert!(stdout.contains(heading), "{format}");
+        assert!(stdout.contains("AviationLibraryATA"), "{format}");
+    }
+
+    let template_dir = tempfile::tempdir().unwrap();
+    let template_path = template_dir.path().join("report.txt");
+    std::fs::write(&template_path, "{{ metrics.packages }} packages").unwrap();
+
+    let output = test_bin::get_test_bin(BIN)
+        .arg(db_file.path())
+        .arg("report")
+        .arg("--template")
+        .arg(&template_path)
+        .output()
+        .expect("Failed to start {BIN}");
+
+    assert!(output.status.success());
+    assert_eq!(output.stdout, b"4 packages");
+
+    db_file.close().unwrap();
+}
+
+#[test]
+fn export_plantuml() {
+    let db_file = imported_db();
+
+    let output = test_bin::get_test_bin(BIN)
+        .arg(db_file.path())
+        .arg("export-plantuml")
+        .arg("AviationExample::A350")
+        .arg("--max-depth")
+        .arg("1")
+        .output()
+        .expect("Failed to start {BIN}");
+
+    assert!(output.status.success());
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(stdout.starts_with("@startuml\n"));
+    assert!(stdout.ends_with("@enduml\n"));
+    assert!(stdout.contains(r#""Systems : AircraftSystem" <<part>>"#));
+    assert!(!stdout.contains("Transponder"));
+
+    db_file.close().unwrap();
+}
+
+#[test]
+fn bom() {
+    let db_file = imported_db();
+
+    let output = test_bin::get_test_bin(BIN)
+        .arg(db_file.path())
+        .arg("bom")
+        .arg("AviationExample::A350")
+        .output()
+        .expect("Failed to start {BIN}");
+
+    assert!(output.status.success());
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(stdout.starts_with("item,type,id,quantity\n"));
+    // two wings, and the CPUs of three flight control computers plus one GPU
+    assert!(stdout.contains("AviationLibraryATA::StructureATAs::Wing,PartDefinition,"));
+    assert!(stdout.contains(",72d3b964-431a-4f5e-97de-5d6db239595d,2\n"));
+    assert!(stdout.contains(",3c9270aa-9baf-41c5-8835-071930ba8738,4\n"));
+
+    db_file.close().unwrap();
+}
+
+#[test]
+fn coverage() {
+    let db_file = imported_db();
+
+    let output = test_bin::get_test_bin(BIN)
+        .arg(db_file.path())
+        .arg("coverage")
+        .arg("--json")
+        .output()
+        .expect("Failed to start {BIN}");
+
+    assert!(output.status.success());
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(stdout.contains(r#""requirements": 0,"#));
+    assert!(stdout.contains(r#""satisfied_percentage": null"#));
+
+    db_file.close().unwrap();
+}
+
+#[test]
+fn import_strict_exit_code() {
+    let db_file = tempfile::NamedTempFile::new().unwrap();
+
+    let output = test_bin::get_test_bin(BIN)
+        .arg(db_file.path())
+        .arg("init-db")
+        .output()
+        .expect("Failed to start {BIN}");
+    assert!(output.status.success());
+
+    // the example dump has attributes which are neither columns nor relations
+    let output = test_bin::get_test_bin(BIN)
+        .arg("--quiet")
+        .arg(db_file.path())
+        .arg("import-json")
+        .arg("--strict")
+        .arg("tests/example-dump.json")
+        .output()
+        .expect("Failed to start {BIN}");
+    assert_eq!(output.status.code(), Some(7));
+
+    db_file.close().unwrap();
+}
+
+#[test]
+fn logging() {
+    let db_file = tempfile::NamedTempFile::new().unwrap();
+    let log_path = db_file.path().with_extension("log");
+
+    // the log file receives the messages of all levels, regardless of --quiet
+    let output = test_bin::get_test_bin(BIN)
+        .arg("--quiet")
+        .arg("--log-file")
+        .arg(&log_path)
+        .arg(db_file.path())
+        .arg("init-db")
+        .output()
+        .expect("Failed to start {BIN}");
+    assert!(output.status.success());
+    assert!(output.stderr.is_empty());
+    let log = std::fs::read_to_string(&log_path).unwrap();
+    assert!(log.contains("TRACE sysml_v2_sql: parsed args"), "{log}");
+    assert!(
+        log.contains("DEBUG sysml_v2_sql::tweaks: setting the busy timeout"),
+        "{log}"
+    );
+    assert!(
+        log.contains("INFO sysml_v2_sql::init_db: creating tables"),
+        "{log}"
+    );
+
+    // with JSON, stderr and the log file carry one object per message, with the spans of the phases
+    let output = test_bin::get_test_bin(BIN)
+        .env_remove("RUST_LOG")
+        .arg("--log-format")
+        .arg("json")
+        .arg("--log-file")
+        .arg(&log_path)
+        .arg(db_file.path())
+        .arg("import-json")
+        .arg("tests/example-dump.json")
+        .output()
+        .expect("Failed to start {BIN}");
+    assert!(output.status.success());
+    let parse_lines = |text: &str| -> Vec<serde_json::Value> {
+        text.lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect()
+    };
+    let stderr_messages = parse_lines(&String::from_utf8(output.stderr).unwrap());
+    let log_messages = parse_lines(&std::fs::read_to_string(&log_path).unwrap());
+    assert!(
+        stderr_messages
+            .iter()
+            .all(|message| !["DEBUG", "TRACE"].contains(&message["level"].as_str().unwrap()))
+    );
+    assert!(
+        log_messages
+            .iter()
+            .any(|message| message["level"] == "DEBUG")
+    );
+    for messages in [&stderr_messages, &log_messages] {
+        assert!(messages.iter().any(|message| {
+            message["level"] == "INFO"
+                && message["fields"]["message"] == "inserting elements"
+                && message["span"]["name"] == "insert_elements"
+        }));
+    }
+
+    std::fs::remove_file(&log_path).unwrap();
+    db_file.close().unwrap();
+}
+
+#[test]
+fn sql_script() {
+    let db_file = imported_db();
+
+    let output = test_bin::get_test_bin(BIN)
+        .arg(db_file.path())
+        .arg("sql")
+        .arg("--format")
+        .arg("json")
+        .arg("--execute")
+        .arg(
+            r#"CREATE TEMPORARY TABLE "t"("x");
+            SELECT "declaredName", "@type" FROM "elements" WHERE "qualifiedName" = 'AviationExample';
+            SELECT count(*) AS "n" FROM "t""#,
+        )
+        .output()
+        .expect("Failed to start {BIN}");
+    assert!(output.status.success());
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    let lines: Vec<_> = stdout.lines().collect();
+    assert_eq!(
+        lines,
+        [
+            r#"[{"declaredName":"AviationExample","@type":"Package"}]"#,
+            r#"[{"n":0}]"#
+        ]
+    );
+
+    // SQL errors have an exit code of their own
+    let output = test_bin::get_test_bin(BIN)
+        .arg("--quiet")
+        .arg(db_file.path())
+        .arg("sql")
+        .arg("--execute")
+        .arg(r#"SELECT * FROM "no_such_table""#)
+        .output()
+        .expect("Failed to start {BIN}");
+    assert_eq!(output.status.code(), Some(8));
+
+    db_file.close().unwrap();
+}
+
+#[test]
+fn sql_params() {
+    let db_file = imported_db();
+
+    let output = test_bin::get_test_bin(BIN)
+        .arg(db_file.path())
+        .arg("sql")
+        .arg("--format")
+        .arg("json")
+        .arg("--param")
+        .arg("name=AviationExample")
+        .arg("--param")
+        .arg("limit:int=1")
+        .arg("--execute")
+        .arg(
+            r#"SELECT "@type", typeof(:limit) AS "limit_type" FROM "elements"
+            WHERE "declaredName" = :name LIMIT :limit"#,
+        )
+        .output()
+        .expect("Failed to start {BIN}");
+    assert!(output.status.success());
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert_eq!(
+        stdout.trim(),
+        r#"[{"@type":"Package","limit_type":"integer"}]"#
+    );
+
+    // parameters without value are refused instead of being bound to NULL
+    let output = test_bin::get_test_bin(BIN)
+        .arg("--quiet")
+        .arg(db_file.path())
+        .arg("sql")
+        .arg("--execute")
+        .arg(r#"SELECT * FROM "elements" WHERE "declaredName" = :name"#)
+        .output()
+        .expect("Failed to start {BIN}");
+    assert_eq!(output.status.code(), Some(8));
+
+    db_file.close().unwrap();
+}
+
+#[test]
+fn import_collect_errors() {
+    let db_file = tempfile::NamedTempFile::new().unwrap();
+    let dump_dir = tempfile::tempdir().unwrap();
+    let dump_path = dump_dir.path().join("dump.json");
+    std::fs::write(
+        &dump_path,
+        r#"[
+            {"@id": "a", "@type": "Element", "isAbstract": "maybe"},
+            {"@id": "b", "@type": "Element", "owner": {"@id": "missing"}}
+        ]"#,
+    )
+    .unwrap();
+
+    let output = test_bin::get_test_bin(BIN)
+        .arg(db_file.path())
+        .arg("init-db")
+        .output()
+        .expect("Failed to start {BIN}");
+    assert!(output.status.success());
+
+    let output = test_bin::get_test_bin(BIN)
+        .arg(db_file.path())
+        .arg("import-json")
+        .arg("--collect-errors")
+        .arg(&dump_path)
+        .output()
+        .expect("Failed to start {BIN}");
+    assert!(!output.status.success());
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(stderr.contains(r#"at "/isAbstract" in the element "a""#));
+    assert!(stderr.contains(r#"at "/owner" in the element "b""#));
+    assert!(stderr.contains("found 2 errors"));
+
+    db_file.close().unwrap();
+}
+
+#[test]
+fn import_continue_on_error() {
+    let db_file = tempfile::NamedTempFile::new().unwrap();
+    let dump_dir = tempfile::tempdir().unwrap();
+    let dump_path = dump_dir.path().join("dump.json");
+    let skipped_path = dump_dir.path().join("dump.json.skipped.json");
+    std::fs::write(
+        &dump_path,
+        r#"[
+            {"@id": "a", "@type": "Element", "isAbstract": "maybe"},
+            {"@id": "b", "@type": "Element", "owner": {"@id": "a"}},
+            {"@id": "c", "@type": "Element", "declaredName": "fine"}
+        ]"#,
+    )
+    .unwrap();
+
+    let output = test_bin::get_test_bin(BIN)
+        .arg(db_file.path())
+        .arg("init-db")
+        .output()
+        .expect("Failed to start {BIN}");
+    assert!(output.status.success());
+
+    let output = test_bin::get_test_bin(BIN)
+        .arg(db_file.path())
+        .arg("import-json")
+        .arg("--continue-on-error")
+        .arg(&dump_path)
+        .output()
+        .expect("Failed to start {BIN}");
+    assert!(output.status.success());
+
+    // b refers to the skipped a, hence it is skipped as well
+    let skipped: Vec<serde_json::Value> =
+        serde_json::from_str(&std::fs::read_to_string(&skipped_path).unwrap()).unwrap();
+    let skipped_ids: Vec<_> = skipped.iter().map(|s| s["@id"].as_str().unwrap()).collect();
+    assert_eq!(skipped_ids, ["a", "b"]);
+    assert_eq!(skipped[1]["element"]["owner"]["@id"], "a");
+
+    let output = test_bin::get_test_bin(BIN)
+        .arg(db_file.path())
+        .arg("search")
+        .arg("fine")
+        .output()
+        .expect("Failed to start {BIN}");
+    assert!(output.status.success());
+    assert!(String::from_utf8(output.stdout).unwrap().contains("c"));
+
+    db_file.close().unwrap();
+}
+
+#[test]
+fn import_error_context() {
+    let dump_dir = tempfile::tempdir().unwrap();
+    let import = |db_file: &tempfile::NamedTempFile, dump: &str| -> String {
+        let dump_path = dump_dir.path().join("dump.json");
+        std::fs::write(&dump_path, dump).unwrap();
+        let output = test_bin::get_test_bin(BIN)
+            .arg(db_file.path())
+            .arg("import-json")
+            .arg(&dump_path)
+            .output()
+            .expect("Failed to start {BIN}");
+        assert!(!output.status.success());
+        String::from_utf8(output.stderr).unwrap()
+    };
+
+    let db_file = tempfile::NamedTempFile::new().unwrap();
+    let output = test_bin::get_test_bin(BIN)
+        .arg(db_file.path())
+        .arg("init-db")
+        .output()
+        .expect("Failed to start {BIN}");
+    assert!(output.status.success());
+
+    // values of the extended properties are located by the element and the attribute
+    let stderr = import(
+        &db_file,
+        r#"[{"@id": "a", "@type": "Element", "aliasIds": ["x", 1]}]"#,
+    );
+    assert!(
+        stderr.contains(r#"at "/aliasIds" in the element "a""#),
+        "{stderr}"
+    );
+
+    // parse errors are located by the array item and its byte offset
+    let stderr = import(
+        &db_file,
+        "[{\"@id\": \"a\", \"@type\": \"Element\"},\n {\"@id\": \"b\", \"@type\": \"Element\", \"declaredName\": }]",
+    );
+    assert!(
+        stderr.contains("relative to the array item 1 starting near byte offset 33"),
+        "{stderr}"
+    );
+    db_file.close().unwrap();
+
+    // rejected rows are located by the element, as is the column of flattened nested properties
+    let (db_file, _) = generated_db_from(
+        &TINY_JSON_SCHEMA.replace(
+            r#""aliasIds": {"type": "array", "items": {"type": "string"}}"#,
+            r#""aliasIds": {"type": "array", "items": {"type": "string"}},
+                "position": {"type": "object", "properties": {"x": {"type": "number"}}, "required": ["x"], "additionalProperties": false}"#,
+        ),
+        &[],
+    );
+    let [car, ..] = TINY_IDS;
+    let stderr = import(
+        &db_file,
+        &format!(
+            r#"[{{"@id": "{car}", "@type": "PartUsage", "isLibraryElement": false, "mass": 1.0, "position": {{"x": "far"}}}}]"#
+        ),
+    );
+    assert!(
+        stderr.contains(&format!(r#"inserting the element "{car}""#)),
+        "{stderr}"
+    );
+    assert!(stderr.contains("cannot store TEXT value in REAL column elements.position.x"));
+
+    db_file.close().unwrap();
+}
+
+#[test]
+fn bench() {
+    let dir = tempfile::tempdir().unwrap();
+
+    let output = test_bin::get_test_bin(BIN)
+        .arg(":memory:")
+        .arg("bench")
+        .arg("tests/example-dump.json")
+        .arg("--runs")
+        .arg("1")
+        .arg("--variant")
+        .arg("base:")
+        .arg("--variant")
+        .arg("wal:journal_mode=WAL,synchronous=NORMAL")
+        .arg("--dir")
+        .arg(dir.path())
+        .output()
+        .expect("Failed to start {BIN}");
+    assert!(output.status.success());
+
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    let variants: Vec<_> = stdout
+        .lines()
+        .skip(1)
+        .map(|line| line.split('\t').next().unwrap())
+        .collect();
+    assert_eq!(variants, ["base", "wal"]);
+
+    // the throwaway dbs are deleted
+    assert_eq!(std::fs::read_dir(dir.path()).unwrap().count(), 0);
+
+    dir.close().unwrap();
+}
+
+#[test]
+fn bench_without_rowid() {
+    let dir = tempfile::tempdir().unwrap();
+    let schema_path = dir.path().join("schema.json");
+    std::fs::write(&schema_path, TINY_JSON_SCHEMA).unwrap();
+    let dump_path = dir.path().join("dump.json");
+    let [car, wheel, ..] = TINY_IDS;
+    std::fs::write(
+        &dump_path,
+        format!(
+            r#"[{{"@id": "{car}", "@type": "PartUsage", "isLibraryElement": false, "mass": 1000.0, "ownedElement": [{{"@id": "{wheel}"}}]}},
+                {{"@id": "{wheel}", "@type": "PartUsage", "isLibraryElement": false, "mass": 10.0, "owner": {{"@id": "{car}"}}}}]"#
+        ),
+    )
+    .unwrap();
+    let db_dir = tempfile::tempdir().unwrap();
+    let bench = |args: &[&std::ffi::OsStr]| {
+        test_bin::get_test_bin(BIN)
+            .arg(":memory:")
+            .arg("bench")
+            .arg(&dump_path)
+            .arg("--runs")
+            .arg("1")
+            .arg("--variant")
+            .arg("rowid:")
+            .arg("--variant")
+            .arg("without_rowid:without_rowid=true")
+            .arg("--dir")
+            .arg(db_dir.path())
+            .args(args)
+            .output()
+            .expect("Failed to start {BIN}")
+    };
+
+    // the schema is generated for each variant, which requires the JSON schema
+    let output = bench(&[]);
+    assert!(!output.status.success());
+    assert!(
+        String::from_utf8_lossy(&output.stderr).contains(
+            r#"the variant "without_rowid" generates the elements table WITHOUT ROWID, which requires a JSON schema"#
+        ),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+
+    let output = bench(&["--json-schema".as_ref(), schema_path.as_os_str()]);
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    let mut lines = stdout.lines();
+    let header: Vec<_> = lines.next().unwrap().split('\t').collect();
+    assert_eq!(header[7..], ["size [KiB]", "lookups/s"]);
+    let variants: Vec<Vec<_>> = lines.map(|line| line.split('\t').collect()).collect();
+    assert_eq!(variants.len(), 2);
+    assert_eq!(variants[0][0], "rowid");
+    assert_eq!(variants[1][0], "without_rowid");
+    for variant in &variants {
+        assert!(variant[7].parse::<u64>().unwrap() > 0);
+        assert!(variant[8].parse::<f64>().unwrap() > 0.0);
+    }
+    assert_eq!(std::fs::read_dir(db_dir.path()).unwrap().count(), 0);
+
+    db_dir.close().unwrap();
+    dir.close().unwrap();
+}
+
+#[test]
+fn import_batch_sizes() {
+    let stats_by_batch_size: Vec<serde_json::Value> = ["1", "7"]
+        .into_iter()
+        .map(|batch_size| {
+            let db_file = tempfile::NamedTempFile::new().unwrap();
+
+            let output = test_bin::get_test_bin(BIN)
+                .arg(db_file.path())
+                .arg("init-db")
+                .output()
+                .expect("Failed to start {BIN}");
+            assert!(output.status.success());
+
+            let output = test_bin::get_test_bin(BIN)
+                .arg(db_file.path())
+                .arg("import-json")
+                .arg("--batch-size")
+                .arg(batch_size)
+                .arg("tests/example-dump.json")
+                .output()
+                .expect("Failed to start {BIN}");
+            assert!(output.status.success());
+
+            let output = test_bin::get_test_bin(BIN)
+                .arg(db_file.path())
+                .arg("stats")
+                .arg("--json")
+                .output()
+                .expect("Failed to start {BIN}");
+            assert!(output.status.success());
+
+            db_file.close().unwrap();
+            let mut stats: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+            // the sizes depend on the order of the inserts
+            stats["db_size"].take();
+            stats["index_sizes"].take();
+            stats
+        })
+        .collect();
+
+    assert_eq!(stats_by_batch_size[0], stats_by_batch_size[1]);
+    assert_eq!(stats_by_batch_size[0]["elements_by_type"]["PartUsage"], 12);
+}
+
+#[test]
+fn import_closure() {
+    let db_file = tempfile::NamedTempFile::new().unwrap();
+    let dump_path = db_file.path().with_extension("json");
+    std::fs::write(
+        &dump_path,
+        r#"[
+            {"@id": "a", "@type": "Element", "ownedElement": [{"@id": "b"}]},
+            {"@id": "b", "@type": "Element", "ownedElement": [{"@id": "c"}]},
+            {"@id": "c", "@type": "Element", "ownedElement": []}
+        ]"#,
+    )
+    .unwrap();
+
+    let output = test_bin::get_test_bin(BIN)
+        .arg(db_file.path())
+        .arg("init-db")
+        .output()
+        .expect("Failed to start {BIN}");
+    assert!(output.status.success());
+
+    let output = test_bin::get_test_bin(BIN)
+        .arg(db_file.path())
+        .arg("import-json")
+        .arg("--closure")
+        .arg("ownedElement")
+        .arg(&dump_path)
+        .output()
+        .expect("Failed to start {BIN}");
+    assert!(output.status.success());
+
+    let closure = || -> Vec<(String, String)> {
+        let conn = rusqlite::Connection::open(db_file.path()).unwrap();
+        let mut stmt = conn
+            .prepare(
+                r#"SELECT "origin_id", "target_id" FROM "relation_closure"
+                WHERE "name" = 'ownedElement' ORDER BY 1, 2"#,
+            )
+            .unwrap();
+        stmt.query_map([], |row| Ok((row.get(0)?, row.get(1)?)))
+            .unwrap()
+            .collect::<Result<_, _>>()
+            .unwrap()
+    };
+    let pairs = |pairs: &[(&str, &str)]| -> Vec<(String, String)> {
+        pairs
+            .iter()
+            .map(|(o, t)| (o.to_string(), t.to_string()))
+            .collect()
+    };
+    assert_eq!(closure(), pairs(&[("a", "b"), ("a", "c"), ("b", "c")]));
+
+    // the closure is maintained by edits, too
+    let output = test_bin::get_test_bin(BIN)
+        .arg(db_file.path())
+        .arg("remove-relation")
+        .arg("ownedElement")
+        .arg("b")
+        .arg("c")
+        .output()
+        .expect("Failed to start {BIN}");
+    assert!(output.status.success());
+    assert_eq!(closure(), pairs(&[("a", "b")]));
+
+    std::fs::remove_file(dump_path).unwrap();
+    db_file.close().unwrap();
+}
+
+#[test]
+fn import_owner_column() {
+    let db_file = tempfile::NamedTempFile::new().unwrap();
+    let dump_path = db_file.path().with_extension("json");
+    std::fs::write(
+        &dump_path,
+        r#"[
+            {"@id": "a", "@type": "Package", "owner": null},
+            {"@id": "m", "@type": "OwningMembership", "owningRelatedElement": {"@id": "a"}},
+            {"@id": "b", "@type": "Package", "owner": {"@id": "a"}}
+        ]"#,
+    )
+    .unwrap();
+
+    let output = test_bin::get_test_bin(BIN)
+        .arg(db_file.path())
+        .arg("init-db")
+        .output()
+        .expect("Failed to start {BIN}");
+    assert!(output.status.success());
+
+    let output = test_bin::get_test_bin(BIN)
+        .arg(db_file.path())
+        .arg("import-json")
+        .arg("--owner-column")
+        .arg(&dump_path)
+        .output()
+        .expect("Failed to start {BIN}");
+    assert!(output.status.success());
+
+    let owners = || -> Vec<(String, Option<String>)> {
+        let conn = rusqlite::Connection::open(db_file.path()).unwrap();
+        let mut stmt = conn
+            .prepare(r#"SELECT "@id", "owner_id" FROM "elements" ORDER BY 1"#)
+            .unwrap();
+        stmt.query_map([], |row| Ok((row.get(0)?, row.get(1)?)))
+            .unwrap()
+            .collect::<Result<_, _>>()
+            .unwrap()
+    };
+    assert_eq!(
+        owners(),
+        vec![
+            ("a".to_string(), None),
+            ("b".to_string(), Some("a".to_string())),
+            ("m".to_string(), Some("a".to_string())),
+        ]
+    );
+
+    // the column is maintained by edits, too
+    let output = test_bin::get_test_bin(BIN)
+        .arg(db_file.path())
+        .arg("remove-relation")
+        .arg("owner")
+        .arg("b")
+        .arg("a")
+        .output()
+        .expect("Failed to start {BIN}");
+    assert!(output.status.success());
+    assert_eq!(owners()[1], ("b".to_string(), None));
+
+    // and by later imports, which accept the altered elements table
+    let output = test_bin::get_test_bin(BIN)
+        .arg(db_file.path())
+        .arg("import-json")
+        .arg(&dump_path)
+        .output()
+        .expect("Failed to start {BIN}");
+    assert!(output.status.success());
+    assert_eq!(owners()[1], ("b".to_string(), Some("a".to_string())));
+
+    std::fs::remove_file(dump_path).unwrap();
+    db_file.close().unwrap();
+}
+
+#[test]
+fn import_element_paths() {
+    let db_file = tempfile::NamedTempFile::new().unwrap();
+    let dump_path = db_file.path().with_extension("json");
+    std::fs::write(
+        &dump_path,
+        r#"[
+            {"@id": "a", "@type": "Package", "owner": null},
+            {"@id": "b", "@type": "Package", "owner": {"@id": "a"}},
+            {"@id": "c", "@type": "Package", "owner": {"@id": "b"}},
+            {"@id": "d", "@type": "Package", "owner": null}
+        ]"#,
+    )
+    .unwrap();
+
+    let output = test_bin::get_test_bin(BIN)
+        .arg(db_file.path())
+        .arg("init-db")
+        .output()
+        .expect("Failed to start {BIN}");
+    assert!(output.status.success());
+
+    let output = test_bin::get_test_bin(BIN)
+        .arg(db_file.path())
+        .arg("import-json")
+        .arg("--element-paths")
+        .arg(&dump_path)
+        .output()
+        .expect("Failed to start {BIN}");
+    assert!(output.status.success());
+
+    let paths = || -> Vec<(String, String, i64)> {
+        let conn = rusqlite::Connection::open(db_file.path()).unwrap();
+        let mut stmt = conn
+            .prepare(r#"SELECT "@id", "path", "depth" FROM "element_paths" ORDER BY 1"#)
+            .unwrap();
+        stmt.query_map([], |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)))
+            .unwrap()
+            .collect::<Result<_, _>>()
+            .unwrap()
+    };
+    let expected = |rows: &[(&str, &str, i64)]| -> Vec<(String, String, i64)> {
+        rows.iter()
+            .map(|(id, path, depth)| (id.to_string(), path.to_string(), *depth))
+            .collect()
+    };
+    assert_eq!(
+        paths(),
+        expected(&[
+            ("a", "a", 0),
+            ("b", "a/b", 1),
+            ("c", "a/b/c", 2),
+            ("d", "d", 0)
+        ])
+    );
+
+    // the paths are maintained by edits, too
+    let output = test_bin::get_test_bin(BIN)
+        .arg(db_file.path())
+        .arg("remove-relation")
+        .arg("owner")
+        .arg("b")
+        .arg("a")
+        .output()
+        .expect("Failed to start {BIN}");
+    assert!(output.status.success());
+    assert_eq!(
+        paths(),
+        expected(&[("a", "a", 0), ("b", "b", 0), ("c", "b/c", 1), ("d", "d", 0)])
+    );
+
+    std::fs::remove_file(dump_path).unwrap();
+    db_file.close().unwrap();
+}
+
+#[cfg(feature = "ffi")]
+#[test]
+fn ffi_import() {
+    use std::ffi::{CStr, CString};
+
+    let db_file = tempfile::NamedTempFile::new().unwrap();
+    let db_path = CString::new(db_file.path().to_str().unwrap()).unwrap();
+    let dump_path = CString::new("tests/example-dump.json").unwrap();
+
+    unsafe {
+        assert_eq!(sysml_v2_sql::ffi::sysml_init_db(db_path.as_ptr()), 0);
+        assert!(sysml_v2_sql::ffi::sysml_last_error().is_null());
+
+        assert_eq!(
+            sysml_v2_sql::ffi::sysml_import_json(db_path.as_ptr(), dump_path.as_ptr()),
+            0
+        );
+
+        // the schema exists already
+        assert_eq!(sysml_v2_sql::ffi::sysml_init_db(db_path.as_ptr()), 1);
+        let msg = CStr::from_ptr(sysml_v2_sql::ffi::sysml_last_error());
+        assert!(msg.to_str().unwrap().contains("already exists"));
+    }
+
+    db_file.close().unwrap();
+}
+
+#[cfg(feature = "fetch")]
+#[test]
+fn fetch_and_import_cancel() {
+    use sysml_v2_sql::api::{
+        ApiVersion, CancellationToken, Cancelled, CommitSelector, FetchImport, ProjectSelector,
+        fetch_and_import,
+    };
+
+    // a server which accepts connections, but never responds
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let db_file = tempfile::NamedTempFile::new().unwrap();
+
+    let request = FetchImport {
+        server: format!("http://{}/api", listener.local_addr().unwrap()),
+        project: ProjectSelector::ProjectId {
+            project_id: "some-project".to_owned(),
+            commit: CommitSelector::DefaultBranch,
+        },
+        db_file: db_file.path().to_owned(),
+        http_options: Default::default(),
+        maybe_api_version: Some(ApiVersion::V1),
+        derived: false,
+        relationships: false,
+        maybe_progress: None,
+        maybe_transform: None,
+    };
+    let cancel = CancellationToken::new();
+
+    let rt = tokio::runtime::Runtime::new().unwrap();
+    let result = rt.block_on(async {
+        let cancel_clone = cancel.clone();
+        tokio::spawn(async move {
+            tokio::time::sleep(std::time::Duration::from_millis(200)).await;
+            cancel_clone.cancel();
+        });
+        fetch_and_import(request, cancel).await
+    });
+    assert!(result.unwrap_err().is::<Cancelled>());
+
+    db_file.close().unwrap();
+}
+
+#[cfg(feature = "fetch")]
+#[test]
+fn fetch_and_import_cancel_during_import() {
+    use std::sync::Arc;
+
+    use sysml_v2_sql::api::{
+        CancellationToken, Cancelled, CommitSelector, FetchImport, Phase, ProgressSink,
+        ProjectSelector, fetch_and_import,
+    };
+
+    /// Cancels once the import is about to commit
+    struct CancelOnCommit(CancellationToken);
+
+    impl ProgressSink for CancelOnCommit {
+        fn phase(&self, phase: Phase) {
+            if phase == Phase::Committing {
+                self.0.cancel();
+                // the cancellation arrives while no statement runs, which interrupting misses
+                std::thread::sleep(std::time::Duration::from_millis(100));
+            }
+        }
+    }
+
+    let base_url = serve_elements(
+        r#"[{"@id": "a", "@type": "Package", "owner": null, "declaredName": "A"},
+            {"@id": "b", "@type": "Package", "owner": {"@id": "a"}, "declaredName": "B"}]"#,
+    );
+    let db_file = tempfile::NamedTempFile::new().unwrap();
+    let output = test_bin::get_test_bin(BIN)
+        .arg(db_file.path())
+        .arg("init-db")
+        .output()
+        .expect("Failed to start {BIN}");
+    assert!(output.status.success());
+
+    let cancel = CancellationToken::new();
+    let request = FetchImport {
+        server: base_url,
+        project: ProjectSelector::ProjectId {
+            project_id: "P".to_owned(),
+            commit: CommitSelector::CommitId {
+                commit_id: "C".to_owned(),
+            },
+        },
+        db_file: db_file.path().to_owned(),
+        http_options: Default::default(),
+        maybe_api_version: None,
+        derived: false,
+        relationships: false,
+        maybe_progress: Some(Arc::new(CancelOnCommit(cancel.clone()))),
+        maybe_transform: None,
+    };
+
+    let rt = tokio::runtime::Runtime::new().unwrap();
+    let result = rt.block_on(fetch_and_import(request, cancel));
+    assert!(result.unwrap_err().is::<Cancelled>());
+
+    // the import was rolled back as a whole
+    let conn = rusqlite::Connection::open(db_file.path()).unwrap();
+    let elements: i64 = conn
+        .query_row(r#"SELECT count(*) FROM "elements""#, [], |row| row.get(0))
+        .unwrap();
+    assert_eq!(elements, 0);
+    let import_logs: i64 = conn
+        .query_row(
+            r#"SELECT count(*) FROM "sqlite_master" WHERE "name" = 'import_log'"#,
+            [],
+            |row| row.get(0),
+        )
+        .unwrap();
+    assert_eq!(import_logs, 0);
+    drop(conn);
+
+    db_file.close().unwrap();
+}
+
+#[test]
+fn import_progress() {
+    use std::sync::{Arc, Mutex};
+
+    use sysml_v2_sql::api::{ImportJson, Phase, ProgressSink, import_json_file};
+
+    #[derive(Default)]
+    struct Recorder {
+        phases: Mutex<Vec<Phase>>,
+        elements_parsed: Mutex<usize>,
+        rows_inserted: Mutex<usize>,
+    }
+
+    impl ProgressSink for Recorder {
+        fn phase(&self, phase: Phase) {
+            self.phases.lock().unwrap().push(phase);
+        }
+
+        fn elements_parsed(&self, elements: usize) {
+            *self.elements_parsed.lock().unwrap() = elements;
+        }
+
+        fn rows_inserted(&self, rows: usize) {
+            *self.rows_inserted.lock().unwrap() = rows;
+        }
+    }
+
+    let db_file = tempfile::NamedTempFile::new().unwrap();
+    let output = test_bin::get_test_bin(BIN)
+        .arg(db_file.path())
+        .arg("init-db")
+        .output()
+        .expect("Failed to start {BIN}");
+    assert!(output.status.success());
+
+    let recorder = Arc::new(Recorder::default());
+    import_json_file(ImportJson {
+        json_file: "tests/example-dump.json".into(),
+        db_file: db_file.path().to_owned(),
+        maybe_progress: Some(recorder.clone()),
+        maybe_transform: None,
+    })
+    .unwrap();
+
+    assert_eq!(
+        *recorder.phases.lock().unwrap(),
+        [
+            Phase::InsertingElements,
+            Phase::InsertingRelations,
+            Phase::UpdatingDerivedTables,
+            Phase::Committing
+        ]
+    );
+    let elements: usize = serde_json::from_str::<Vec<serde_json::Value>>(
+        &std::fs::read_to_string("tests/example-dump.json").unwrap(),
+    )
+    .unwrap()
+    .len();
+    assert_eq!(*recorder.elements_parsed.lock().unwrap(), elements);
+    // the rows of the last phase inserting rows are the relations
+    assert!(*recorder.rows_inserted.lock().unwrap() > 0);
+
+    db_file.close().unwrap();
+}
+
+#[cfg(feature = "lua")]
+#[test]
+fn import_transform() {
+    let db_file = tempfile::NamedTempFile::new().unwrap();
+    let dump_path = db_file.path().with_extension("json");
+    std::fs::write(
+        &dump_path,
+        r#"[
+            {"@id": "a", "@type": "Package", "owner": null, "declaredName": "A"},
+            {"@id": "b", "@type": "Package", "owner": {"@id": "a"}, "name": "B"},
+            {"@id": "c", "@type": "Comment", "owner": {"@id": "b"}, "body": "dropped"}
+        ]"#,
+    )
+    .unwrap();
+    let script_path = db_file.path().with_extension("lua");
+    std::fs::write(
+        &script_path,
+        r#"
+        function transform(element)
+            if element["@type"] == "Comment" then
+                return nil
+            end
+            element.declaredName = element.declaredName or element.name
+            return element
+        end
+        "#,
+    )
+    .unwrap();
+
+    let output = test_bin::get_test_bin(BIN)
+        .arg(db_file.path())
+        .arg("init-db")
+        .output()
+        .expect("Failed to start {BIN}");
+    assert!(output.status.success());
+
+    let output = test_bin::get_test_bin(BIN)
+        .arg(db_file.path())
+        .arg("import-json")
+        .arg("--transform")
+        .arg(&script_path)
+        .arg(&dump_path)
+        .output()
+        .expect("Failed to start {BIN}");
+    assert!(output.status.success());
+
+    let conn = rusqlite::Connection::open(db_file.path()).unwrap();
+    let mut stmt = conn
+        .prepare(r#"SELECT "@id", "declaredName" FROM "elements" ORDER BY 1"#)
+        .unwrap();
+    let elements: Vec<(String, String)> = stmt
+        .query_map([], |row| Ok((row.get(0)?, row.get(1)?)))
+        .unwrap()
+        .collect::<Result<_, _>>()
+        .unwrap();
+    assert_eq!(
+        elements,
+        [
+            ("a".to_owned(), "A".to_owned()),
+            ("b".to_owned(), "B".to_owned())
+        ]
+    );
+    let relations: usize = conn
+        .query_row(r#"SELECT count(*) FROM "relations""#, [], |row| row.get(0))
+        .unwrap();
+    assert_eq!(relations, 1);
+    let partial: String = conn
+        .query_row(r#"SELECT "partial" FROM "import_log""#, [], |row| {
+            row.get(0)
+        })
+        .unwrap();
+    assert_eq!(
+        partial,
+        format!("transformed by the script {script_path:?}")
+    );
+
+    std::fs::remove_file(&script_path).unwrap();
+    std::fs::remove_file(&dump_path).unwrap();
+    db_file.close().unwrap();
+}
+
+#[test]
+fn import_redact() {
+    let db_file = tempfile::NamedTempFile::new().unwrap();
+    let dump_path = db_file.path().with_extension("json");
+    std::fs::write(
+        &dump_path,
+        r#"[
+            {"@id": "a", "@type": "Package", "owner": null, "declaredName": "A"},
+            {"@id": "c", "@type": "Comment", "owner": {"@id": "a"}, "body": "secret"}
+        ]"#,
+    )
+    .unwrap();
+
+    let output = test_bin::get_test_bin(BIN)
+        .arg(db_file.path())
+        .arg("init-db")
+        .output()
+        .expect("Failed to start {BIN}");
+    assert!(output.status.success());
+
+    let output = test_bin::get_test_bin(BIN)
+        .arg(db_file.path())
+        .arg("import-json")
+        .arg("--redact")
+        .arg("declaredName,body:hash,owner")
+        .arg(&dump_path)
+        .output()
+        .expect("Failed to start {BIN}");
+    assert!(output.status.success());
+
+    let conn = rusqlite::Connection::open(db_file.path()).unwrap();
+    let mut stmt = conn
+        .prepare(r#"SELECT "@id", "declaredName", "body" FROM "elements" ORDER BY 1"#)
+        .unwrap();
+    let elements: Vec<(String, Option<String>, Option<String>)> = stmt
+        .query_map([], |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)))
+        .unwrap()
+        .collect::<Result<_, _>>()
+        .unwrap();
+    assert_eq!(
+        elements,
+        [
+            ("a".to_owned(), Some(String::new()), None),
+            (
+                "c".to_owned(),
+                None,
+                Some(
+                    "sha256:2bb80d537b1da3e38bd30361aa855686bde0eacd7162fef6a25fe97bf527a25b"
+                        .to_owned()
+                )
+            )
+        ]
+    );
+    // references to other elements are never redacted
+    let relations: usize = conn
+        .query_row(r#"SELECT count(*) FROM "relations""#, [], |row| row.get(0))
+        .unwrap();
+    assert_eq!(relations, 1);
+    let partial: String = conn
+        .query_row(r#"SELECT "partial" FROM "import_log""#, [], |row| {
+            row.get(0)
+        })
+        .unwrap();
+    assert_eq!(
+        partial,
+        "redacting the attributes body, declaredName, owner"
+    );
+
+    let output = test_bin::get_test_bin(BIN)
+        .arg(db_file.path())
+        .arg("import-json")
+        .arg("--redact")
+        .arg("@id")
+        .arg(&dump_path)
+        .output()
+        .expect("Failed to start {BIN}");
+    assert!(!output.status.success());
+
+    std::fs::remove_file(&dump_path).unwrap();
+    db_file.close().unwrap();
+}
+
+#[test]
+fn import_pseudonymize_ids() {
+    let db_file = tempfile::NamedTempFile::new().unwrap();
+    let dump_path = db_file.path().with_extension("json");
+    std::fs::write(
+        &dump_path,
+        r#"[
+            {"@id": "a", "@type": "Package", "elementId": "a", "owner": null, "declaredName": "A"},
+            {"@id": "b", "@type": "Package", "elementId": "b", "owner": {"@id": "a"}, "declaredName": "B"}
+        ]"#,
+    )
+    .unwrap();
+
+    let output = test_bin::get_test_bin(BIN)
+        .arg(db_file.path())
+        .arg("init-db")
+        .output()
+        .expect("Failed to start {BIN}");
+    assert!(output.status.success());
+
+    // the pseudonyms are keyed, hence the key must be given
+    let output = test_bin::get_test_bin(BIN)
+        .arg(db_file.path())
+        .arg("import-json")
+        .arg("--pseudonymize-ids")
+        .arg(&dump_path)
+        .env_remove("SYSML_PSEUDONYM_KEY")
+        .output()
+        .expect("Failed to start {BIN}");
+    assert!(!output.status.success());
+
+    // importing twice yields the same pseudonyms, updating the elements in place
+    for _ in 0..2 {
+        let output = test_bin::get_test_bin(BIN)
+            .arg(db_file.path())
+            .arg("import-json")
+            .arg("--pseudonymize-ids")
+            .arg(&dump_path)
+            .env("SYSML_PSEUDONYM_KEY", "secret")
+            .output()
+            .expect("Failed to start {BIN}");
+        assert!(output.status.success());
+    }
+
+    let conn = rusqlite::Connection::open(db_file.path()).unwrap();
+    let mut stmt = conn
+        .prepare(r#"SELECT "@id", "elementId", "declaredName" FROM "elements" ORDER BY 3"#)
+        .unwrap();
+    let elements: Vec<(String, String, String)> = stmt
+        .query_map([], |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)))
+        .unwrap()
+        .collect::<Result<_, _>>()
+        .unwrap();
+    assert_eq!(elements.len(), 2);
+    for (id, element_id, _) in &elements {
+        assert_eq!(id, element_id);
+        assert_eq!(id.len(), 36);
+        assert_eq!(&id[14..15], "8");
+    }
+    assert_ne!(elements[0].0, elements[1].0);
+
+    // the relations still join
+    let (origin_id, target_id): (String, String) = conn
+        .query_row(
+            r#"SELECT "origin_id", "target_id" FROM "relations" WHERE "name" = 'owner'"#,
+            [],
+            |row| Ok((row.get(0)?, row.get(1)?)),
+        )
+        .unwrap();
+    assert_eq!(origin_id, elements[1].0);
+    assert_eq!(target_id, elements[0].0);
+    let mut stmt = conn
+        .prepare(r#"SELECT "partial" FROM "import_log""#)
+        .unwrap();
+    let partial: Vec<String> = stmt
+        .query_map([], |row| row.get(0))
+        .unwrap()
+        .collect::<Result<_, _>>()
+        .unwrap();
+    assert!(!partial.is_empty());
+    assert!(partial.iter().all(|p| p == "pseudonymizing the ids"));
+
+    std::fs::remove_file(&dump_path).unwrap();
+    db_file.close().unwrap();
+}
+
+#[test]
+fn export_anonymized() {
+    let db_file = tempfile::NamedTempFile::new().unwrap();
+    let dump_path = db_file.path().with_extension("json");
+    std::fs::write(
+        &dump_path,
+        r#"[
+            {"@id": "a", "@type": "Package", "owner": null, "declaredName": "Secret"},
+            {"@id": "b", "@type": "PartUsage", "owner": {"@id": "a"}, "declaredName": "Engine", "isAbstract": true},
+            {"@id": "c", "@type": "Documentation", "owner": {"@id": "b"}, "body": "proprietary"}
+        ]"#,
+    )
+    .unwrap();
+    let out_path = db_file.path().with_extension("anonymized.json");
+    let out_db_path = db_file.path().with_extension("anonymized.db");
+
+    let output = test_bin::get_test_bin(BIN)
+        .arg(db_file.path())
+        .arg("init-db")
+        .output()
+        .expect("Failed to start {BIN}");
+    assert!(output.status.success());
+    let output = test_bin::get_test_bin(BIN)
+        .arg(db_file.path())
+        .arg("import-json")
+        .arg(&dump_path)
+        .output()
+        .expect("Failed to start {BIN}");
+    assert!(output.status.success());
+
+    let output = test_bin::get_test_bin(BIN)
+        .arg(db_file.path())
+        .arg("export-anonymized")
+        .arg(&out_path)
+        .output()
+        .expect("Failed to start {BIN}");
+    assert!(output.status.success());
+
+    let dump = std::fs::read_to_string(&out_path).unwrap();
+    for content in ["\"a\"", "\"b\"", "\"c\"", "Secret", "Engine", "proprietary"] {
+        assert!(!dump.contains(content), "{content} leaked into {dump}");
+    }
+    let elements: Vec<serde_json::Value> = serde_json::from_str(&dump).unwrap();
+    assert_eq!(elements.len(), 3);
+    let part = elements.iter().find(|e| e["@type"] == "PartUsage").unwrap();
+    assert_eq!(part["declaredName"], "");
+    assert_eq!(part["isAbstract"], true);
+    let package = elements.iter().find(|e| e["@type"] == "Package").unwrap();
+    assert_eq!(part["owner"]["@id"], package["@id"]);
+
+    // existing files are kept
+    let output = test_bin::get_test_bin(BIN)
+        .arg(db_file.path())
+        .arg("export-anonymized")
+        .arg(&out_path)
+        .output()
+        .expect("Failed to start {BIN}");
+    assert!(!output.status.success());
+
+    let output = test_bin::get_test_bin(BIN)
+        .arg(db_file.path())
+        .arg("export-anonymized")
+        .arg("--db")
+        .arg(&out_db_path)
+        .output()
+        .expect("Failed to start {BIN}");
+    assert!(output.status.success());
+    let conn = rusqlite::Connection::open(&out_db_path).unwrap();
+    let (elements, relations): (usize, usize) = conn
+        .query_row(
+            r#"SELECT (SELECT count(*) FROM "elements"), (SELECT count(*) FROM "relations")"#,
+            [],
+            |row| Ok((row.get(0)?, row.get(1)?)),
+        )
+        .unwrap();
+    assert_eq!((elements, relations), (3, 2));
+
+    std::fs::remove_file(&out_db_path).unwrap();
+    std::fs::remove_file(&out_path).unwrap();
+    std::fs::remove_file(&dump_path).unwrap();
+    db_file.close().unwrap();
+}
+
+#[test]
+fn import_package() {
+    let db_file = tempfile::NamedTempFile::new().unwrap();
+    let dump_path = db_file.path().with_extension("json");
+    std::fs::write(
+        &dump_path,
+        r#"[
+            {"@id": "v", "@type": "Package", "owner": null, "qualifiedName": "Vehicle", "declaredName": "Vehicle"},
+            {"@id": "p", "@type": "Package", "owner": {"@id": "v"}, "qualifiedName": "Vehicle::Powertrain", "declaredName": "Powertrain"},
+            {"@id": "m", "@type": "OwningMembership", "owner": {"@id": "p"}, "memberElement": {"@id": "e"}},
+            {"@id": "e", "@type": "PartUsage", "owner": {"@id": "p"}, "qualifiedName": "Vehicle::Powertrain::engine", "declaredName": "engine", "definition": [{"@id": "d"}]},
+            {"@id": "d", "@type": "PartDefinition", "owner": {"@id": "v"}, "qualifiedName": "Vehicle::Engine", "declaredName": "Engine", "isAbstract": true},
+            {"@id": "c", "@type": "PartUsage", "owner": {"@id": "v"}, "qualifiedName": "Vehicle::chassis", "declaredName": "chassis"}
+        ]"#,
+    )
+    .unwrap();
+
+    let output = test_bin::get_test_bin(BIN)
+        .arg(db_file.path())
+        .arg("init-db")
+        .output()
+        .expect("Failed to start {BIN}");
+    assert!(output.status.success());
+
+    let output = test_bin::get_test_bin(BIN)
+        .arg(db_file.path())
+        .arg("import-json")
+        .arg("--package")
+        .arg("Vehicle::Powertrain")
+        .arg(&dump_path)
+        .output()
+        .expect("Failed to start {BIN}");
+    assert!(output.status.success());
+
+    let conn = rusqlite::Connection::open(db_file.path()).unwrap();
+    let mut stmt = conn
+        .prepare(r#"SELECT "@id", "isAbstract" FROM "elements" ORDER BY 1"#)
+        .unwrap();
+    let elements: Vec<(String, Option<bool>)> = stmt
+        .query_map([], |row| Ok((row.get(0)?, row.get(1)?)))
+        .unwrap()
+        .collect::<Result<_, _>>()
+        .unwrap();
+    // the owner and the definition outside of the namespace are stubs, the chassis is left out
+    assert_eq!(
+        elements,
+        [
+            ("d".to_owned(), None),
+            ("e".to_owned(), None),
+            ("m".to_owned(), None),
+            ("p".to_owned(), None),
+            ("v".to_owned(), None),
+        ]
+    );
+    let mut stmt = conn
+        .prepare(r#"SELECT "origin_id", "name", "target_id" FROM "relations" ORDER BY 1, 2"#)
+        .unwrap();
+    let relations: Vec<(String, String, String)> = stmt
+        .query_map([], |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)))
+        .unwrap()
+        .collect::<Result<_, _>>()
+        .unwrap();
+    assert_eq!(
+        relations,
+        [
+            ("e".to_owned(), "definition".to_owned(), "d".to_owned()),
+            ("e".to_owned(), "owner".to_owned(), "p".to_owned()),
+            ("m".to_owned(), "memberElement".to_owned(), "e".to_owned()),
+            ("m".to_owned(), "owner".to_owned(), "p".to_owned()),
+            ("p".to_owned(), "owner".to_owned(), "v".to_owned()),
+        ]
+    );
+    let partial: Option<String> = conn
+        .query_row(r#"SELECT "partial" FROM "import_log""#, [], |row| {
+            row.get(0)
+        })
+        .unwrap();
+    assert_eq!(
+        partial.as_deref(),
+        Some("only the namespace Vehicle::Powertrain")
+    );
+
+    let output = test_bin::get_test_bin(BIN)
+        .arg(db_file.path())
+        .arg("import-json")
+        .arg("--package")
+        .arg("Vehicle::Missing")
+        .arg(&dump_path)
+        .output()
+        .expect("Failed to start {BIN}");
+    assert!(!output.status.success());
+
+    std::fs::remove_file(&dump_path).unwrap();
+    db_file.close().unwrap();
+}
+
+#[test]
+fn import_attribute_filter() {
+    let db_file = tempfile::NamedTempFile::new().unwrap();
+    let dump_path = db_file.path().with_extension("json");
+    std::fs::write(
+        &dump_path,
+        r#"[
+            {"@id": "a", "@type": "Package", "owner": null, "declaredName": "A", "isLibraryElement": false},
+            {"@id": "b", "@type": "Package", "owner": {"@id": "a"}, "declaredName": "B", "ownedElement": [], "unknownAttribute": 1}
+        ]"#,
+    )
+    .unwrap();
+
+    let output = test_bin::get_test_bin(BIN)
+        .arg(db_file.path())
+        .arg("init-db")
+        .output()
+        .expect("Failed to start {BIN}");
+    assert!(output.status.success());
+
+    let count = |conn: &rusqlite::Connection, sql: &str| -> usize {
+        conn.query_row(sql, [], |row| row.get(0)).unwrap()
+    };
+
+    // the unknown attribute is not even seen, hence strict mode does not refuse it
+    let output = test_bin::get_test_bin(BIN)
+        .arg(db_file.path())
+        .arg("import-json")
+        .arg("--strict")
+        .arg("--only-attributes")
+        .arg("declaredName,owner")
+        .arg(&dump_path)
+        .output()
+        .expect("Failed to start {BIN}");
+    assert!(output.status.success());
+    let conn = rusqlite::Connection::open(db_file.path()).unwrap();
+    assert_eq!(
+        count(
+            &conn,
+            r#"SELECT count(*) FROM "elements" WHERE "declaredName" IS NOT NULL AND "@type" = 'Package'"#
+        ),
+        2
+    );
+    assert_eq!(
+        count(
+            &conn,
+            r#"SELECT count(*) FROM "elements" WHERE "isLibraryElement" IS NOT NULL"#
+        ),
+        0
+    );
+    assert_eq!(count(&conn, r#"SELECT count(*) FROM "relations""#), 1);
+
+    let output = test_bin::get_test_bin(BIN)
+        .arg(db_file.path())
+        .arg("import-json")
+        .arg("--skip-attributes")
+        .arg("declaredName,owner,unknownAttribute")
+        .arg(&dump_path)
+        .output()
+        .expect("Failed to start {BIN}");
+    assert!(output.status.success());
+    assert_eq!(
+        count(
+            &conn,
+            r#"SELECT count(*) FROM "elements" WHERE "declaredName" IS NOT NULL"#
+        ),
+        0
+    );
+    assert_eq!(
+        count(
+            &conn,
+            r#"SELECT count(*) FROM "elements" WHERE "isLibraryElement" IS NOT NULL"#
+        ),
+        1
+    );
+    assert_eq!(count(&conn, r#"SELECT count(*) FROM "relations""#), 0);
+    let mut stmt = conn
+        .prepare(r#"SELECT "partial" FROM "import_log" ORDER BY rowid"#)
+        .unwrap();
+    let partial: Vec<Option<String>> = stmt
+        .query_map([], |row| row.get(0))
+        .unwrap()
+        .collect::<Result<_, _>>()
+        .unwrap();
+    assert!(partial.contains(&Some("only the attributes declaredName, owner".to_owned())));
+    assert_eq!(
+        partial.last().unwrap().as_deref(),
+        Some("skipping the attributes declaredName, owner, unknownAttribute")
+    );
+
+    std::fs::remove_file(&dump_path).unwrap();
+    db_file.close().unwrap();
+}
+
+#[cfg(feature = "fetch")]
+#[test]
+fn push_refuses_partial_import() {
+    let db_file = tempfile::NamedTempFile::new().unwrap();
+    let dump_path = db_file.path().with_extension("json");
+    std::fs::write(
+        &dump_path,
+        r#"[
+            {"@id": "v", "@type": "Package", "owner": null, "qualifiedName": "Vehicle", "declaredName": "Vehicle"},
+            {"@id": "p", "@type": "Package", "owner": {"@id": "v"}, "qualifiedName": "Vehicle::Powertrain", "declaredName": "Powertrain"},
+            {"@id": "c", "@type": "PartUsage", "owner": {"@id": "v"}, "qualifiedName": "Vehicle::chassis", "declaredName": "chassis"}
+        ]"#,
+    )
+    .unwrap();
+
+    let output = test_bin::get_test_bin(BIN)
+        .arg(db_file.path())
+        .arg("init-db")
+        .output()
+        .expect("Failed to start {BIN}");
+    assert!(output.status.success());
+
+    let output = test_bin::get_test_bin(BIN)
+        .arg(db_file.path())
+        .arg("import-json")
+        .arg("--package")
+        .arg("Vehicle::Powertrain")
+        .arg(&dump_path)
+        .output()
+        .expect("Failed to start {BIN}");
+    assert!(output.status.success());
+
+    // pretend the partial import was fetched from a project
+    let conn = rusqlite::Connection::open(db_file.path()).unwrap();
+    conn.execute(
+        r#"UPDATE "import_log" SET "project_id" = 'P', "commit_id" = 'C'"#,
+        [],
+    )
+    .unwrap();
+    drop(conn);
+
+    // refused before connecting to the server, which does not exist
+    let output = test_bin::get_test_bin(BIN)
+        .arg(db_file.path())
+        .arg("push")
+        .arg("--dry-run")
+        .arg("http://127.0.0.1:1/api")
+        .arg("P")
+        .output()
+        .expect("Failed to start {BIN}");
+    assert!(!output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(
+        stderr.contains("last imported partially (only the namespace Vehicle::Powertrain)"),
+        "{stderr}"
+    );
+}
+
+/// Serves `body` as answer to every request, returns the base URL of the API of the server
+#[cfg(feature = "fetch")]
+fn serve_elements(body: &'static str) -> String {
+    serve_api(move |_| (200, String::new(), body.to_owned())).0
+}
+
+/// A request received by the fake API server, see [`serve_api`]
+#[cfg(feature = "fetch")]
+#[derive(Debug, Clone)]
+struct ApiRequest {
+    method: String,
+
+    /// Path of the request, including the query
+    path: String,
+
+    /// Headers of the request, with their names in lower case
+    headers: Vec<(String, String)>,
+
+    body: String,
+}
+
+#[cfg(feature = "fetch")]
+impl ApiRequest {
+    /// The value of the header of the given name in lower case, if present
+    fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(n, _)| n == name)
+            .map(|(_, value)| value.as_str())
+    }
+}
+
+/// Answers each request by `respond`, which returns the status, additional header lines each
+/// terminated by `\r\n`, and the body, e.g. the JSON text
+///
+/// Returns the base URL of the API of the server, and the requests received so far. Each connection
+/// is served on a thread of its own, hence `respond` may stall.
+#[cfg(feature = "fetch")]
+fn serve_api<B: Into<Vec<u8>>>(
+    respond: impl Fn(&ApiRequest) -> (u16, String, B) + Send + Sync + 'static,
+) -> (String, std::sync::Arc<std::sync::Mutex<Vec<ApiRequest>>>) {
+    use std::{
+        io::{BufRead, BufReader, Read, Write},
+        sync::{Arc, Mutex},
+    };
+
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let base_url = format!("http://{}/api", listener.local_addr().unwrap());
+    let requests = Arc::new(Mutex::new(Vec::new()));
+    let respond = Arc::new(respond);
+    let requests_clone = requests.clone();
+    std::thread::spawn(move || {
+        for stream in listener.incoming() {
+            let mut stream = stream.unwrap();
+            let requests = requests_clone.clone();
+            let respond = respond.clone();
+            std::thread::spawn(move || {
+                let mut reader = BufReader::new(stream.try_clone().unwrap());
+                let mut request_line = String::new();
+                reader.read_line(&mut request_line).unwrap();
+                let mut parts = request_line.split_whitespace();
+                let method = parts.next().unwrap_or_default().to_owned();
+                let path = parts.next().unwrap_or_default().to_owned();
+
+                let mut headers = Vec::new();
+                loop {
+                    let mut line = String::new();
+                    reader.read_line(&mut line).unwrap();
+                    if line.trim().is_empty() {
+                        break;
+                    }
+                    if let Some((name, value)) = line.split_once(':') {
+                        headers.push((name.to_ascii_lowercase(), value.trim().to_owned()));
+                    }
+                }
+                let content_length = headers
+                    .iter()
+                    .find(|(name, _)| name == "content-length")
+                    .map_or(0, |(_, value)| value.parse().unwrap());
+                let mut body = vec![0; content_length];
+                reader.read_exact(&mut body).unwrap();
+
+                let request = ApiRequest {
+                    method,
+                    path,
+                    headers,
+                    body: String::from_utf8(body).unwrap(),
+                };
+                requests.lock().unwrap().push(request.clone());
+
+                let (status, headers, body) = respond(&request);
+                let body = body.into();
+                // the client may have given up already, e.g. after a timeout
+                let _ = write!(
+                    stream,
+                    "HTTP/1.1 {status} Fake\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n{headers}\r\n",
+                    body.len()
+                )
+                .and_then(|()| stream.write_all(&body));
+            });
+        }
+    });
+
+    (base_url, requests)
+}
+
+#[cfg(feature = "fetch")]
+#[test]
+fn fetch_import_options() {
+    let base_url = serve_elements(
+        r#"[{"@id": "a", "@type": "Element", "isAbstract": "maybe"},
+            {"@id": "c", "@type": "Element", "declaredName": "fine", "frobnicated": true}]"#,
+    );
+
+    let db_file = tempfile::NamedTempFile::new().unwrap();
+    let output = test_bin::get_test_bin(BIN)
+        .arg(db_file.path())
+        .arg("init-db")
+        .output()
+        .expect("Failed to start {BIN}");
+    assert!(output.status.success());
+    let fetch = |args: &[&str]| {
+        test_bin::get_test_bin(BIN)
+            .arg("--quiet")
+            .arg(db_file.path())
+            .arg("fetch")
+            .args(args)
+            .arg(&base_url)
+            .arg("project-id")
+            .arg("P")
+            .arg("commit-id")
+            .arg("C")
+            .output()
+            .expect("Failed to start {BIN}")
+    };
+
+    // the options of the import apply to fetched elements as well
+    let output = fetch(&["--strict", "--only-attributes", "frobnicated"]);
+    assert_eq!(output.status.code(), Some(7));
+
+    let skipped_dir = tempfile::tempdir().unwrap();
+    let skipped_path = skipped_dir.path().join("skipped.json");
+    let output = fetch(&[
+        "--continue-on-error",
+        "--skipped-file",
+        skipped_path.to_str().unwrap(),
+    ]);
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    let skipped: Vec<serde_json::Value> =
+        serde_json::from_str(&std::fs::read_to_string(&skipped_path).unwrap()).unwrap();
+    let skipped_ids: Vec<_> = skipped.iter().map(|s| s["@id"].as_str().unwrap()).collect();
+    assert_eq!(skipped_ids, ["a"]);
+
+    let conn = rusqlite::Connection::open(db_file.path()).unwrap();
+    let names: Vec<String> = conn
+        .prepare(r#"SELECT "declaredName" FROM "elements""#)
+        .unwrap()
+        .query_map([], |row| row.get(0))
+        .unwrap()
+        .collect::<Result<_, _>>()
+        .unwrap();
+    assert_eq!(names, ["fine"]);
+    drop(conn);
+
+    db_file.close().unwrap();
+}
+
+#[cfg(feature = "fetch")]
+#[test]
+fn fetch_query_is_partial() {
+    let base_url =
+        serve_elements(r#"[{"@id": "a", "@type": "Package", "owner": null, "declaredName": "A"}]"#);
+
+    let db_file = tempfile::NamedTempFile::new().unwrap();
+    let output = test_bin::get_test_bin(BIN)
+        .arg(db_file.path())
+        .arg("init-db")
+        .output()
+        .expect("Failed to start {BIN}");
+    assert!(output.status.success());
+
+    let output = test_bin::get_test_bin(BIN)
+        .arg(db_file.path())
+        .arg("fetch")
+        .arg("--query")
+        .arg(r#"{"@type": "Query"}"#)
+        .arg(&base_url)
+        .arg("project-id")
+        .arg("P")
+        .arg("commit-id")
+        .arg("C")
+        .output()
+        .expect("Failed to start {BIN}");
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+
+    let conn = rusqlite::Connection::open(db_file.path()).unwrap();
+    let (commit_id, partial): (String, String) = conn
+        .query_row(
+            r#"SELECT "commit_id", "partial" FROM "import_log""#,
+            [],
+            |row| Ok((row.get(0)?, row.get(1)?)),
+        )
+        .unwrap();
+    assert_eq!(commit_id, "C");
+    assert_eq!(
+        partial,
+        r#"only the results of the query {"@type":"Query"}"#
+    );
+    drop(conn);
+
+    let output = test_bin::get_test_bin(BIN)
+        .arg(db_file.path())
+        .arg("push")
+        .arg("--dry-run")
+        .arg(&base_url)
+        .arg("P")
+        .output()
+        .expect("Failed to start {BIN}");
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("last imported partially"));
+
+    db_file.close().unwrap();
+}
+
+#[cfg(feature = "fetch")]
+#[test]
+fn fetch_query_pages() {
+    // the results of both the posted and the stored query span two pages
+    let (base_url, requests) = serve_api(|request| {
+        let host = request.header("host").unwrap();
+        let next_page =
+            |path: &str| format!("Link: <http://{host}{path}&page=2>; rel=\"next\"\r\n");
+        let a = r#"[{"@id": "a", "@type": "Package", "owner": null, "declaredName": "A"}]"#;
+        let b = r#"[{"@id": "b", "@type": "Package", "owner": null, "declaredName": "B"}]"#;
+        let (headers, body) = match (request.method.as_str(), request.path.as_str()) {
+            ("POST", path @ "/api/projects/P/query-results?commitId=C")
+            | ("GET", path @ "/api/projects/P/queries/Q/results?commitId=C") => {
+                (next_page(path), a)
+            }
+            ("GET", "/api/projects/P/query-results?commitId=C&page=2")
+            | ("GET", "/api/projects/P/queries/Q/results?commitId=C&page=2") => (String::new(), b),
+            _ => return (404, String::new(), "{}".to_owned()),
+        };
+        (200, headers, body.to_owned())
+    });
+
+    let query = r#"{"@type": "Query", "select": ["declaredName"]}"#;
+    for (args, partial) in [
+        (
+            ["--query", query],
+            r#"only the results of the query {"@type":"Query","select":["declaredName"]}"#,
+        ),
+        (["--query-id", "Q"], "only the results of the query Q"),
+    ] {
+        let db_file = tempfile::NamedTempFile::new().unwrap();
+        let output = test_bin::get_test_bin(BIN)
+            .arg(db_file.path())
+            .arg("init-db")
+            .output()
+            .expect("Failed to start {BIN}");
+        assert!(output.status.success());
+
+        requests.lock().unwrap().clear();
+        let output = test_bin::get_test_bin(BIN)
+            .arg(db_file.path())
+            .arg("fetch")
+            .args(args)
+            .arg(&base_url)
+            .arg("project-id")
+            .arg("P")
+            .arg("commit-id")
+            .arg("C")
+            .output()
+            .expect("Failed to start {BIN}");
+        assert!(
+            output.status.success(),
+            "{}",
+            String::from_utf8_lossy(&output.stderr)
+        );
+
+        let requests_sent = requests.lock().unwrap().clone();
+        assert_eq!(requests_sent.len(), 2);
+        if args[0] == "--query" {
+            assert_eq!(requests_sent[0].method, "POST");
+            assert_eq!(
+                serde_json::from_str::<serde_json::Value>(&requests_sent[0].body).unwrap(),
+                serde_json::from_str::<serde_json::Value>(query).unwrap()
+            );
+        } else {
+            assert_eq!(requests_sent[0].method, "GET");
+        }
+
+        let conn = rusqlite::Connection::open(db_file.path()).unwrap();
+        let ids: Vec<String> = conn
+            .prepare(r#"SELECT "@id" FROM "elements" ORDER BY 1"#)
+            .unwrap()
+            .query_map([], |row| row.get(0))
+            .unwrap()
+            .collect::<Result<_, _>>()
+            .unwrap();
+        assert_eq!(ids, ["a", "b"]);
+        let logged_partial: String = conn
+            .query_row(r#"SELECT "partial" FROM "import_log""#, [], |row| {
+                row.get(0)
+            })
+            .unwrap();
+        assert_eq!(logged_partial, partial);
+        drop(conn);
+
+        db_file.close().unwrap();
+    }
+}
+
+#[cfg(feature = "fetch")]
+#[test]
+fn download_schema() {
+    let db_file = tempfile::NamedTempFile::new().unwrap();
+    let schema_path = db_file.path().with_extension("schema.json");
+    let download = |url: &str| {
+        test_bin::get_test_bin(BIN)
+            .arg(db_file.path())
+            .arg("download-schema")
+            .arg(&schema_path)
+            .arg("--url")
+            .arg(url)
+            .arg("--apply")
+            .output()
+            .expect("Failed to start {BIN}")
+    };
+
+    // the download is stored, and the SQL schema is generated from it into the empty db
+    let base_url = serve_elements(TINY_JSON_SCHEMA);
+    let output = download(&format!("{base_url}/schemas.json"));
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    assert_eq!(
+        std::fs::read_to_string(&schema_path).unwrap(),
+        TINY_JSON_SCHEMA
+    );
+    let conn = rusqlite::Connection::open(db_file.path()).unwrap();
+    let columns: Vec<String> = conn
+        .prepare(r#"SELECT "name" FROM pragma_table_info('elements')"#)
+        .unwrap()
+        .query_map([], |row| row.get(0))
+        .unwrap()
+        .collect::<Result<_, _>>()
+        .unwrap();
+    assert!(columns.iter().any(|c| c == "mass"), "{columns:?}");
+    assert!(columns.iter().any(|c| c == "reqId"), "{columns:?}");
+    drop(conn);
+    std::fs::remove_file(&schema_path).unwrap();
+
+    // a failed download is neither stored nor applied
+    let (base_url, _) = serve_api(|_| (404, String::new(), "{}".to_owned()));
+    let output = download(&format!("{base_url}/schemas.json"));
+    assert!(!output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(
+        stderr.contains("downloading the JSON schema failed with status 404"),
+        "{stderr}"
+    );
+    assert!(!schema_path.exists());
+
+    db_file.close().unwrap();
+}
+
+#[cfg(feature = "fetch")]
+#[test]
+fn sync_webhook() {
+    use std::{
+        io::{BufRead, BufReader, Read, Write},
+        net::TcpStream,
+        sync::mpsc,
+        time::Duration,
+    };
+
+    let base_url =
+        serve_elements(r#"[{"@id": "a", "@type": "Package", "owner": null, "declaredName": "A"}]"#);
+    let db_file = tempfile::NamedTempFile::new().unwrap();
+    let output = test_bin::get_test_bin(BIN)
+        .arg(db_file.path())
+        .arg("init-db")
+        .output()
+        .expect("Failed to start {BIN}");
+    assert!(output.status.success());
+
+    let sync = |listen: &str| {
+        let mut command = test_bin::get_test_bin(BIN);
+        command
+            .arg(db_file.path())
+            .arg("sync")
+            .arg("--interval")
+            .arg("1h")
+            .arg("--listen")
+            .arg(listen)
+            .arg(&base_url)
+            .arg("project-id")
+            .arg("P")
+            .arg("commit-id")
+            .arg("C");
+        command
+    };
+
+    // without a token, the webhook is only served on loopback addresses
+    let output = sync("0.0.0.0:0")
+        .env_remove("SYSML_WEBHOOK_TOKEN")
+        .output()
+        .expect("Failed to start {BIN}");
+    assert!(!output.status.success());
+    assert!(
+        String::from_utf8_lossy(&output.stderr)
+            .contains("refusing to serve the webhook on 0.0.0.0:0 without authentication")
+    );
+
+    let addr = std::net::TcpListener::bind("127.0.0.1:0")
+        .unwrap()
+        .local_addr()
+        .unwrap();
+    let mut child = sync(&addr.to_string())
+        .env("SYSML_WEBHOOK_TOKEN", "secret")
+        .stderr(std::process::Stdio::piped())
+        .spawn()
+        .expect("Failed to start {BIN}");
+    let (tx, rx) = mpsc::channel();
+    let stderr = child.stderr.take().unwrap();
+    std::thread::spawn(move || {
+        for line in BufReader::new(stderr).lines() {
+            let Ok(line) = line else { break };
+            if tx.send(line).is_err() {
+                break;
+            }
+        }
+    });
+    let wait_for = |message: &str| loop {
+        let line = rx
+            .recv_timeout(Duration::from_secs(30))
+            .unwrap_or_else(|e| panic!("waiting for {message:?}: {e}"));
+        if line.contains(message) {
+            break;
+        }
+    };
+    wait_for("import took");
+
+    // a client which never completes its request does not block the others
+    let _stalled = TcpStream::connect(addr).unwrap();
+
+    let post = |headers: &str| {
+        let mut stream = TcpStream::connect(addr).unwrap();
+        stream
+            .set_read_timeout(Some(Duration::from_secs(30)))
+            .unwrap();
+        write!(
+            stream,
+            "POST /sync HTTP/1.1\r\nHost: localhost\r\n{headers}\r\n"
+        )
+        .unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).unwrap();
+        response
+    };
+    assert!(post("").starts_with("HTTP/1.1 401"));
+    assert!(post("Authorization: Bearer wrong\r\n").starts_with("HTTP/1.1 401"));
+    assert!(post("Authorization: Bearer secret\r\n").starts_with("HTTP/1.1 202"));
+
+    // the webhook triggers a check long before the interval passed
+    wait_for(r#"the project "P" is up to date at the commit "C""#);
+
+    child.kill().unwrap();
+    child.wait().unwrap();
+    db_file.close().unwrap();
+}
+
+#[cfg(feature = "fetch")]
+#[test]
+fn sync_two_way() {
+    use std::sync::{Arc, Mutex};
+
+    // the branch B moves from C1 to C2, adding c on the server, and to C3 once the local changes
+    // are pushed
+    let head = Arc::new(Mutex::new("C1".to_owned()));
+    let head_clone = head.clone();
+    let (base_url, requests) = serve_api(move |request| {
+        let a = r#"{"@id": "a", "@type": "Package", "owner": null, "declaredName": "A"}"#;
+        let b = r#"{"@id": "b", "@type": "Package", "owner": null, "declaredName": "B"}"#;
+        let b2 = r#"{"@id": "b", "@type": "Package", "owner": null, "declaredName": "B2"}"#;
+        let c = r#"{"@id": "c", "@type": "Package", "owner": null, "declaredName": "C"}"#;
+        let mut head = head_clone.lock().unwrap();
+        let body = match (request.method.as_str(), request.path.as_str()) {
+            ("GET", "/api/projects/P/branches/B") => format!(
+                r#"{{"@id": "B", "created": "", "name": "main", "owningProject": {{"@id": "P"}}, "head": {{"@id": "{head}"}}}}"#
+            ),
+            ("GET", "/api/projects/P/commits/C1/elements") => format!("[{a}, {b}]"),
+            ("GET", "/api/projects/P/commits/C2/elements") => format!("[{a}, {b}, {c}]"),
+            ("GET", "/api/projects/P/commits/C3/elements") => format!("[{a}, {b2}, {c}]"),
+            ("POST", "/api/projects/P/commits?branchId=B") => {
+                *head = "C3".to_owned();
+                r#"{"@id": "C3", "created": ""}"#.to_owned()
+            }
+            _ => return (404, String::new(), "{}".to_owned()),
+        };
+        (200, String::new(), body)
+    });
+
+    let db_file = tempfile::NamedTempFile::new().unwrap();
+    let output = test_bin::get_test_bin(BIN)
+        .arg(db_file.path())
+        .arg("init-db")
+        .output()
+        .expect("Failed to start {BIN}");
+    assert!(output.status.success());
+
+    let sync = || {
+        let output = test_bin::get_test_bin(BIN)
+            .arg("--api-version")
+            .arg("v1")
+            .arg(db_file.path())
+            .arg("sync")
+            .arg("--once")
+            .arg("--two-way")
+            .arg(&base_url)
+            .arg("project-id")
+            .arg("P")
+            .arg("branch-id")
+            .arg("B")
+            .output()
+            .expect("Failed to start {BIN}");
+        assert!(
+            output.status.success(),
+            "{}",
+            String::from_utf8_lossy(&output.stderr)
+        );
+    };
+    let names = || -> Vec<(String, String)> {
+        let conn = rusqlite::Connection::open(db_file.path()).unwrap();
+        let mut stmt = conn
+            .prepare(r#"SELECT "@id", "declaredName" FROM "elements" ORDER BY "@id""#)
+            .unwrap();
+        stmt.query_map([], |row| Ok((row.get(0)?, row.get(1)?)))
+            .unwrap()
+            .collect::<Result<_, _>>()
+            .unwrap()
+    };
+    let last_commit = || -> String {
+        let conn = rusqlite::Connection::open(db_file.path()).unwrap();
+        conn.query_row(
+            r#"SELECT "commit_id" FROM "import_log" ORDER BY rowid DESC LIMIT 1"#,
+            [],
+            |row| row.get(0),
+        )
+        .unwrap()
+    };
+
+    // the first sync only pulls, as there is nothing to compare the db with
+    sync();
+    assert_eq!(
+        names(),
+        [("a".into(), "A".into()), ("b".into(), "B".into())]
+    );
+    assert_eq!(last_commit(), "C1");
+
+    // b is changed locally, while c is added on the server
+    let conn = rusqlite::Connection::open(db_file.path()).unwrap();
+    conn.execute(
+        r#"UPDATE "elements" SET "declaredName" = 'B2' WHERE "@id" = 'b'"#,
+        [],
+    )
+    .unwrap();
+    drop(conn);
+    *head.lock().unwrap() = "C2".to_owned();
+
+    requests.lock().unwrap().clear();
+    sync();
+
+    // the local change is pushed on top of C2, only the changed element is sent
+    let posted: Vec<_> = requests
+        .lock()
+        .unwrap()
+        .iter()
+        .filter(|r| r.method == "POST")
+        .cloned()
+        .collect();
+    assert_eq!(posted.len(), 1);
+    assert_eq!(posted[0].path, "/api/projects/P/commits?branchId=B");
+    assert_eq!(posted[0].header("content-type"), Some("application/json"));
+    let commit: serde_json::Value = serde_json::from_str(&posted[0].body).unwrap();
+    assert_eq!(commit["@type"], "Commit");
+    assert_eq!(commit["previousCommit"], serde_json::json!([{"@id": "C2"}]));
+    let changes = commit["change"].as_array().unwrap();
+    assert_eq!(changes.len(), 1, "{commit:#}");
+    assert_eq!(changes[0]["@type"], "DataVersion");
+    assert_eq!(changes[0]["identity"]["@id"], "b");
+    assert_eq!(changes[0]["payload"]["declaredName"], "B2");
+
+    // the pushed commit is pulled, including the element added on the server
+    assert_eq!(
+        names(),
+        [
+            ("a".into(), "A".into()),
+            ("b".into(), "B2".into()),
+            ("c".into(), "C".into())
+        ]
+    );
+    assert_eq!(last_commit(), "C3");
+
+    // nothing changed since, hence nothing is pushed
+    requests.lock().unwrap().clear();
+    sync();
+    assert!(requests.lock().unwrap().iter().all(|r| r.method == "GET"));
+    assert_eq!(last_commit(), "C3");
+
+    db_file.close().unwrap();
+}
+
+#[cfg(feature = "fetch")]
+#[test]
+fn create_project_and_branch() {
+    let (base_url, requests) = serve_api(|request| {
+        let project = r#"{"@id": "P", "created": "", "defaultBranch": {"@id": "B1"}, "name": "Car", "description": null}"#;
+        let body = match (request.method.as_str(), request.path.as_str()) {
+            ("POST", "/api/projects") | ("GET", "/api/projects/P") => project.to_owned(),
+            ("GET", "/api/projects/P/branches/B1") => {
+                r#"{"@id": "B1", "created": "", "name": "main", "owningProject": {"@id": "P"}, "head": {"@id": "C1"}}"#.to_owned()
+            }
+            ("POST", "/api/projects/P/branches") => {
+                r#"{"@id": "B2", "created": "", "name": "dev", "owningProject": {"@id": "P"}}"#.to_owned()
+            }
+            _ => return (404, String::new(), "{}".to_owned()),
+        };
+        (201, String::new(), body)
+    });
+    let db_file = tempfile::NamedTempFile::new().unwrap();
+    let run = |args: &[&str]| {
+        requests.lock().unwrap().clear();
+        let output = test_bin::get_test_bin(BIN)
+            .arg(db_file.path())
+            .arg(args[0])
+            .arg(&base_url)
+            .args(&args[1..])
+            .output()
+            .expect("Failed to start {BIN}");
+        assert!(
+            output.status.success(),
+            "{}",
+            String::from_utf8_lossy(&output.stderr)
+        );
+        let posted: Vec<serde_json::Value> = requests
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|r| r.method == "POST")
+            .map(|r| serde_json::from_str(&r.body).unwrap())
+            .collect();
+        (String::from_utf8(output.stdout).unwrap(), posted)
+    };
+
+    let (stdout, posted) = run(&["create-project", "Car", "--description", "A car"]);
+    assert_eq!(stdout, "P\n");
+    assert_eq!(
+        posted,
+        [serde_json::json!({"@type": "Project", "name": "Car", "description": "A car"})]
+    );
+
+    // starts at the head of the default branch, unless given a commit
+    let (stdout, posted) = run(&["create-branch", "P", "dev"]);
+    assert_eq!(stdout, "B2\n");
+    assert_eq!(
+        posted,
+        [serde_json::json!({"@type": "Branch", "name": "dev", "head": {"@id": "C1"}})]
+    );
+    let (stdout, posted) = run(&["create-branch", "P", "dev", "--commit-id", "C0"]);
+    assert_eq!(stdout, "B2\n");
+    assert_eq!(
+        posted,
+        [serde_json::json!({"@type": "Branch", "name": "dev", "head": {"@id": "C0"}})]
+    );
+    assert_eq!(requests.lock().unwrap().len(), 1);
+
+    db_file.close().unwrap();
+}
+
+#[cfg(feature = "fetch")]
+#[test]
+fn fetch_spans() {
+    let base_url = serve_elements(r#"[{"@id": "a", "@type": "Package", "owner": null}]"#);
+    let db_file = tempfile::NamedTempFile::new().unwrap();
+    let output = test_bin::get_test_bin(BIN)
+        .arg(db_file.path())
+        .arg("init-db")
+        .output()
+        .expect("Failed to start {BIN}");
+    assert!(output.status.success());
+    let fetch = |format: &str| {
+        let output = test_bin::get_test_bin(BIN)
+            .env("RUST_LOG", "trace")
+            .arg("--log-format")
+            .arg(format)
+            .arg(db_file.path())
+            .arg("fetch")
+            .arg(&base_url)
+            .arg("project-id")
+            .arg("P")
+            .arg("commit-id")
+            .arg("C")
+            .output()
+            .expect("Failed to start {BIN}");
+        let stderr = String::from_utf8(output.stderr).unwrap();
+        assert!(output.status.success(), "{stderr}");
+        stderr
+    };
+
+    // the pages are requested in the span fetch, and parsed in its child span parse
+    let stderr = fetch("text");
+    let fetch_span = "fetch{url_path=projects/P/commits/C/elements}";
+    assert!(
+        stderr.contains(&format!("{fetch_span}: sending new request to {base_url}/")),
+        "{stderr}"
+    );
+    assert!(
+        stderr.contains(&format!("{fetch_span}:parse: parsing new response body")),
+        "{stderr}"
+    );
+
+    let stderr = fetch("json");
+    let span_names = |message: &str| -> Vec<String> {
+        let line = stderr
+            .lines()
+            .map(|line| serde_json::from_str::<serde_json::Value>(line).unwrap())
+            .find(|line| line["fields"]["message"] == message)
+            .unwrap_or_else(|| panic!("no message {message:?} in {stderr}"));
+        line["spans"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|span| span["name"].as_str().unwrap().to_owned())
+            .collect()
+    };
+    assert_eq!(span_names("fetching started"), ["fetch"]);
+    assert_eq!(span_names("parsing new response body"), ["fetch", "parse"]);
+
+    db_file.close().unwrap();
+}
+
+#[cfg(feature = "fetch")]
+#[test]
+fn fetch_form_login() {
+    let (base_url, requests) = serve_api(|request| {
+        match (request.method.as_str(), request.path.as_str()) {
+            ("POST", "/login") if request.body == "user=alice&pass=secret" => (
+                200,
+                "Set-Cookie: session=abc; Path=/\r\n".to_owned(),
+                "{}".to_owned(),
+            ),
+            ("POST", "/login") => (401, String::new(), "{}".to_owned()),
+            // the API sits below a path, the session cookie is required
+            ("GET", "/api/sysml/projects/P/commits/C/elements")
+                if request.header("cookie") == Some("session=abc") =>
+            {
+                (
+                    200,
+                    String::new(),
+                    r#"[{"@id": "a", "@type": "Package", "owner": null, "declaredName": "A"}]"#
+                        .to_owned(),
+                )
+            }
+            _ => (401, String::new(), "{}".to_owned()),
+        }
+    });
+    let login_url = format!("{}/login", base_url.strip_suffix("/api").unwrap());
+
+    let db_file = tempfile::NamedTempFile::new().unwrap();
+    let output = test_bin::get_test_bin(BIN)
+        .arg(db_file.path())
+        .arg("init-db")
+        .output()
+        .expect("Failed to start {BIN}");
+    assert!(output.status.success());
+
+    let fetch = |password: &str| {
+        requests.lock().unwrap().clear();
+        test_bin::get_test_bin(BIN)
+            .arg("--api-version")
+            .arg("v1")
+            .arg(db_file.path())
+            .arg("fetch")
+            .arg(format!("{base_url}/sysml"))
+            .arg("project-id")
+            .arg("P")
+            .arg("commit-id")
+            .arg("C")
+            .env("SYSML_LOGIN_URL", &login_url)
+            .env("SYSML_LOGIN_USERNAME_FIELD", "user")
+            .env("SYSML_LOGIN_PASSWORD_FIELD", "pass")
+            .env("SYSML_USERNAME", "alice")
+            .env("SYSML_PASSWORD", password)
+            .output()
+            .expect("Failed to start {BIN}")
+    };
+
+    let output = fetch("secret");
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    let requests_sent = requests.lock().unwrap().clone();
+    assert_eq!(
+        requests_sent
+            .iter()
+            .map(|r| (r.method.as_str(), r.path.as_str()))
+            .collect::<Vec<_>>(),
+        [
+            ("POST", "/login"),
+            ("GET", "/api/sysml/projects/P/commits/C/elements")
+        ]
+    );
+    assert_eq!(
+        requests_sent[0].header("content-type"),
+        Some("application/x-www-form-urlencoded")
+    );
+    // the session cookie authenticates the requests instead of HTTP basic auth
+    assert_eq!(requests_sent[1].header("authorization"), None);
+
+    let conn = rusqlite::Connection::open(db_file.path()).unwrap();
+    let name: String = conn
+        .query_row(
+            r#"SELECT "declaredName" FROM "elements" WHERE "@id" = 'a'"#,
+            [],
+            |row| row.get(0),
+        )
+        .unwrap();
+    assert_eq!(name, "A");
+    drop(conn);
+
+    let output = fetch("wrong");
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("logging in failed with status 401"));
+    assert_eq!(requests.lock().unwrap().len(), 1);
+
+    db_file.close().unwrap();
+}
+
+#[cfg(feature = "fetch")]
+#[test]
+fn detect_api_version() {
+    for (openapi_status, openapi_document, previous_commit) in [
+        (
+            200,
+            r#"{"info": {"version": "1.0.0"}}"#,
+            serde_json::json!([{"@id": "C"}]),
+        ),
+        (
+            200,
+            r#"{"info": {"version": "0.9.1"}}"#,
+            serde_json::json!({"@id": "C"}),
+        ),
+        (404, "{}", serde_json::json!({"@id": "C"})),
+    ] {
+        let (base_url, requests) = serve_api(move |request| match request.path.as_str() {
+            "/api/docs/openapi.json" => {
+                (openapi_status, String::new(), openapi_document.to_owned())
+            }
+            _ => (
+                200,
+                String::new(),
+                r#"[{"@id": "a", "@type": "Package", "owner": null, "declaredName": "A"}]"#
+                    .to_owned(),
+            ),
+        });
+
+        let db_file = tempfile::NamedTempFile::new().unwrap();
+        for args in [
+            &["init-db"][..],
+            &["fetch", &base_url, "project-id", "P", "commit-id", "C"],
+        ] {
+            let output = test_bin::get_test_bin(BIN)
+                .arg(db_file.path())
+                .args(args)
+                .output()
+                .expect("Failed to start {BIN}");
+            assert!(output.status.success());
+        }
+        let conn = rusqlite::Connection::open(db_file.path()).unwrap();
+        conn.execute(r#"UPDATE "elements" SET "declaredName" = 'B'"#, [])
+            .unwrap();
+        drop(conn);
+
+        // pilot servers take the previous commit as single identity, later versions as list
+        let output = test_bin::get_test_bin(BIN)
+            .arg(db_file.path())
+            .arg("push")
+            .arg("--dry-run")
+            .arg(&base_url)
+            .arg("P")
+            .output()
+            .expect("Failed to start {BIN}");
+        assert!(
+            output.status.success(),
+            "{}",
+            String::from_utf8_lossy(&output.stderr)
+        );
+        let commit: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+        assert_eq!(
+            commit["previousCommit"], previous_commit,
+            "{openapi_document}"
+        );
+        assert_eq!(
+            requests
+                .lock()
+                .unwrap()
+                .iter()
+                .filter(|r| r.path == "/api/docs/openapi.json")
+                .count(),
+            1
+        );
+
+        db_file.close().unwrap();
+    }
+}
+
+#[cfg(feature = "fetch")]
+#[test]
+fn fetch_derived_properties() {
+    // the listed elements lack the qualified names, which only the single elements carry
+    let (base_url, requests) = serve_api(|request| {
+        let body = match request.path.as_str() {
+            "/api/projects/P/commits/C/elements?page[size]=10" => {
+                r#"[
+                    {"@id": "a", "@type": "Package", "owner": null, "declaredName": "A"},
+                    {"@id": "b", "@type": "Package", "owner": {"@id": "a"}, "declaredName": "B", "qualifiedName": null}
+                ]"#
+            }
+            "/api/projects/P/commits/C/elements/a" => {
+                r#"{"@id": "a", "@type": "Package", "owner": null, "declaredName": "Other", "qualifiedName": "A"}"#
+            }
+            "/api/projects/P/commits/C/elements/b" => {
+                r#"{"@id": "b", "@type": "Package", "owner": {"@id": "a"}, "declaredName": "B", "qualifiedName": "A::B"}"#
+            }
+            _ => return (404, String::new(), "{}".to_owned()),
+        };
+        (200, String::new(), body.to_owned())
+    });
+
+    let db_file = tempfile::NamedTempFile::new().unwrap();
+    for args in [
+        &["init-db"][..],
+        &[
+            "fetch",
+            "--derived",
+            "--page-size",
+            "10",
+            &base_url,
+            "project-id",
+            "P",
+            "commit-id",
+            "C",
+        ],
+    ] {
+        let output = test_bin::get_test_bin(BIN)
+            .arg(db_file.path())
+            .args(args)
+            .output()
+            .expect("Failed to start {BIN}");
+        assert!(
+            output.status.success(),
+            "{}",
+            String::from_utf8_lossy(&output.stderr)
+        );
+    }
+    assert_eq!(requests.lock().unwrap().len(), 3);
+
+    // only absent or null properties are merged
+    let conn = rusqlite::Connection::open(db_file.path()).unwrap();
+    let elements: Vec<(String, String, String)> = conn
+        .prepare(r#"SELECT "@id", "declaredName", "qualifiedName" FROM "elements" ORDER BY 1"#)
+        .unwrap()
+        .query_map([], |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)))
+        .unwrap()
+        .collect::<Result<_, _>>()
+        .unwrap();
+    assert_eq!(
+        elements,
+        [
+            ("a".into(), "A".into(), "A".into()),
+            ("b".into(), "B".into(), "A::B".into())
+        ]
+    );
+
+    db_file.close().unwrap();
+}
+
+#[cfg(feature = "fetch")]
+#[test]
+fn fetch_relationships() {
+    // the listed relationship r lacks its source, s is not listed at all. The relationships of a are
+    // served on two pages.
+    let (base_url, requests) = serve_api(|request| {
+        let host = request.header("host").unwrap();
+        let elements_path = "/api/projects/P/commits/C/elements";
+        let (headers, body) = match request.path.strip_prefix(elements_path).unwrap_or_default() {
+            "" => (
+                String::new(),
+                r#"[
+                    {"@id": "a", "@type": "Package", "owner": null, "declaredName": "A"},
+                    {"@id": "b", "@type": "Package", "owner": null, "declaredName": "B"},
+                    {"@id": "r", "@type": "Dependency", "owner": null, "source": null, "target": [{"@id": "b"}]}
+                ]"#,
+            ),
+            "/a/relationships?direction=both" => (
+                format!(
+                    "Link: <http://{host}{elements_path}/a/relationships?direction=both&page=2>; rel=\"next\"\r\n"
+                ),
+                r#"[{"@id": "r", "@type": "Dependency", "owner": null, "source": [{"@id": "a"}], "target": [{"@id": "a"}]}]"#,
+            ),
+            "/a/relationships?direction=both&page=2" => (
+                String::new(),
+                r#"[{"@id": "s", "@type": "Dependency", "owner": null, "source": [{"@id": "a"}], "target": [{"@id": "b"}]}]"#,
+            ),
+            "/b/relationships?direction=both" | "/r/relationships?direction=both" => {
+                (String::new(), "[]")
+            }
+            _ => return (404, String::new(), "{}".to_owned()),
+        };
+        (200, headers, body.to_owned())
+    });
+
+    let db_file = tempfile::NamedTempFile::new().unwrap();
+    for args in [
+        &["init-db"][..],
+        &[
+            "fetch",
+            "--relationships",
+            &base_url,
+            "project-id",
+            "P",
+            "commit-id",
+            "C",
+        ],
+    ] {
+        let output = test_bin::get_test_bin(BIN)
+            .arg(db_file.path())
+            .args(args)
+            .output()
+            .expect("Failed to start {BIN}");
+        assert!(
+            output.status.success(),
+            "{}",
+            String::from_utf8_lossy(&output.stderr)
+        );
+    }
+    assert_eq!(requests.lock().unwrap().len(), 5);
+
+    // s is added, r gets its source but keeps its target
+    let conn = rusqlite::Connection::open(db_file.path()).unwrap();
+    let relations: Vec<(String, String, String)> = conn
+        .prepare(
+            r#"SELECT "origin_id", "name", "target_id" FROM "relations"
+            WHERE "name" IN ('source', 'target') ORDER BY 1, 2"#,
+        )
+        .unwrap()
+        .query_map([], |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)))
+        .unwrap()
+        .collect::<Result<_, _>>()
+        .unwrap();
+    assert_eq!(
+        relations,
+        [
+            ("r".into(), "source".into(), "a".into()),
+            ("r".into(), "target".into(), "b".into()),
+            ("s".into(), "source".into(), "a".into()),
+            ("s".into(), "target".into(), "b".into())
+        ]
+    );
+
+    db_file.close().unwrap();
+}
+
+#[cfg(feature = "fetch")]
+#[test]
+fn fetch_compressed() {
+    use std::io::Write;
+
+    // the commit G is served gzip compressed, the commit B brotli compressed
+    let (base_url, requests) = serve_api(|request| {
+        let (encoding, name) = if request.path.contains("/commits/G/") {
+            ("gzip", "Gzipped")
+        } else {
+            ("br", "Brotlied")
+        };
+        let body = format!(
+            r#"[{{"@id": "a", "@type": "Package", "owner": null, "declaredName": "{name}"}}]"#
+        );
+        let compressed = if encoding == "gzip" {
+            let mut encoder =
+                flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+            encoder.write_all(body.as_bytes()).unwrap();
+            encoder.finish().unwrap()
+        } else {
+            let mut encoder = brotli::CompressorWriter::new(Vec::new(), 4096, 5, 22);
+            encoder.write_all(body.as_bytes()).unwrap();
+            encoder.into_inner()
+        };
+        (200, format!("Content-Encoding: {encoding}\r\n"), compressed)
+    });
+
+    let db_file = tempfile::NamedTempFile::new().unwrap();
+    let output = test_bin::get_test_bin(BIN)
+        .arg(db_file.path())
+        .arg("init-db")
+        .output()
+        .expect("Failed to start {BIN}");
+    assert!(output.status.success());
+    for (commit, name) in [("G", "Gzipped"), ("B", "Brotlied")] {
+        let output = test_bin::get_test_bin(BIN)
+            .arg(db_file.path())
+            .arg("fetch")
+            .arg(&base_url)
+            .arg("project-id")
+            .arg("P")
+            .arg("commit-id")
+            .arg(commit)
+            .output()
+            .expect("Failed to start {BIN}");
+        assert!(
+            output.status.success(),
+            "{}",
+            String::from_utf8_lossy(&output.stderr)
+        );
+
+        let conn = rusqlite::Connection::open(db_file.path()).unwrap();
+        let declared_name: String = conn
+            .query_row(
+                r#"SELECT "declaredName" FROM "elements" WHERE "@id" = 'a'"#,
+                [],
+                |row| row.get(0),
+            )
+            .unwrap();
+        assert_eq!(declared_name, name);
+    }
+
+    // both encodings are offered
+    let requests = requests.lock().unwrap();
+    assert_eq!(requests.len(), 2);
+    for request in requests.iter() {
+        let accepted = request.header("accept-encoding").unwrap();
+        assert!(accepted.contains("gzip"), "{accepted}");
+        assert!(accepted.contains("br"), "{accepted}");
+    }
+    drop(requests);
+
+    db_file.close().unwrap();
+}
+
+#[cfg(feature = "fetch")]
+#[test]
+fn fetch_connection_tuning() {
+    // the commit S stalls longer than the request timeout
+    let (base_url, _) = serve_api(|request| {
+        if request.path.contains("/commits/S/") {
+            std::thread::sleep(std::time::Duration::from_secs(5));
+        }
+        (
+            200,
+            String::new(),
+            r#"[{"@id": "a", "@type": "Package", "owner": null, "declaredName": "A"}]"#.to_owned(),
+        )
+    });
+
+    let db_file = tempfile::NamedTempFile::new().unwrap();
+    let output = test_bin::get_test_bin(BIN)
+        .arg(db_file.path())
+        .arg("init-db")
+        .output()
+        .expect("Failed to start {BIN}");
+    assert!(output.status.success());
+
+    let fetch = |commit_id: &str| {
+        test_bin::get_test_bin(BIN)
+            .arg(db_file.path())
+            .arg("fetch")
+            .args(["--pool-size", "1", "--http2", "--keep-alive", "30s"])
+            .args(["--idle-timeout", "90s", "--connect-timeout", "5s"])
+            .args(["--timeout", "1s"])
+            .arg(&base_url)
+            .arg("project-id")
+            .arg("P")
+            .arg("commit-id")
+            .arg(commit_id)
+            .output()
+            .expect("Failed to start {BIN}")
+    };
+
+    let output = fetch("C");
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+
+    // the stalled request is given up after the timeout, as network error
+    let t0 = std::time::Instant::now();
+    let output = fetch("S");
+    assert!(t0.elapsed() < std::time::Duration::from_secs(5));
+    assert_eq!(
+        output.status.code(),
+        Some(5),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+
+    db_file.close().unwrap();
+}
+
+#[cfg(all(
+    feature = "fetch",
+    any(feature = "bundled-tls", feature = "native-tls")
+))]
+#[test]
+fn fetch_ca_cert_errors() {
+    let db_file = tempfile::NamedTempFile::new().unwrap();
+    let empty_pem = db_file.path().with_extension("empty.pem");
+    std::fs::write(&empty_pem, "").unwrap();
+    let missing_pem = db_file.path().with_extension("missing.pem");
+
+    // refused before connecting to the server, which does not exist
+    for (pem, error) in [
+        (&empty_pem, "found no CA certificates in"),
+        (&missing_pem, "reading the CA certificates from"),
+    ] {
+        let output = test_bin::get_test_bin(BIN)
+            .arg(db_file.path())
+            .arg("fetch")
+            .arg("--ca-cert")
+            .arg(pem)
+            .arg("http://127.0.0.1:1/api")
+            .arg("project-id")
+            .arg("P")
+            .arg("commit-id")
+            .arg("C")
+            .output()
+            .expect("Failed to start {BIN}");
+        assert!(!output.status.success());
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        assert!(stderr.contains(error), "{stderr}");
+    }
+
+    std::fs::remove_file(&empty_pem).unwrap();
+    db_file.close().unwrap();
+}
+
+#[cfg(feature = "fetch")]
+#[test]
+fn server_profiles() {
+    let (base_url, requests) = serve_api(|_| {
+        (
+            200,
+            String::new(),
+            r#"[{"@id": "a", "@type": "Package", "owner": null, "declaredName": "A"}]"#.to_owned(),
+        )
+    });
+
+    let db_file = tempfile::NamedTempFile::new().unwrap();
+    let config_path = db_file.path().with_extension("toml");
+    std::fs::write(
+        &config_path,
+        format!(
+            r#"[profiles.dev]
+base-url = "{base_url}"
+api-version = "v1"
+username = "alice"
+password = "secret"
+
+[profiles.broken]
+base-url = "not a URL"
+"#
+        ),
+    )
+    .unwrap();
+    let run = |args: &[&str]| {
+        requests.lock().unwrap().clear();
+        test_bin::get_test_bin(BIN)
+            .arg("--config")
+            .arg(&config_path)
+            .arg(db_file.path())
+            .args(args)
+            .env_remove("SYSML_USERNAME")
+            .env_remove("SYSML_PASSWORD")
+            .output()
+            .expect("Failed to start {BIN}")
+    };
+
+    let output = run(&["init-db"]);
+    assert!(output.status.success());
+
+    // the profile gives the base URL and the credentials
+    let output = run(&["fetch", "dev", "project-id", "P", "commit-id", "C"]);
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    let requests_sent = requests.lock().unwrap().clone();
+    assert_eq!(requests_sent.len(), 1);
+    assert_eq!(requests_sent[0].path, "/api/projects/P/commits/C/elements");
+    assert_eq!(
+        requests_sent[0].header("authorization"),
+        Some("Basic YWxpY2U6c2VjcmV0")
+    );
+
+    // the API version of the profile applies instead of the detected one, unless given as flag
+    let conn = rusqlite::Connection::open(db_file.path()).unwrap();
+    conn.execute(r#"UPDATE "elements" SET "declaredName" = 'B'"#, [])
+        .unwrap();
+    drop(conn);
+    for (flags, previous_commit) in [
+        (&[][..], serde_json::json!([{"@id": "C"}])),
+        (&["--api-version", "pilot"], serde_json::json!({"@id": "C"})),
+    ] {
+        let output = run(&[flags, &["push", "--dry-run", "dev", "P"]].concat());
+        assert!(
+            output.status.success(),
+            "{}",
+            String::from_utf8_lossy(&output.stderr)
+        );
+        let commit: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+        assert_eq!(commit["previousCommit"], previous_commit);
+        assert!(
+            requests
+                .lock()
+                .unwrap()
+                .iter()
+                .all(|r| r.path != "/api/docs/openapi.json")
+        );
+    }
+
+    for (server, error) in [
+        (
+            "broken",
+            r#"the base URL of the server profile "broken" is invalid"#,
+        ),
+        (
+            "missing",
+            r#""missing" is neither a valid URL nor the name of a server profile"#,
+        ),
+    ] {
+        let output = run(&["fetch", server, "project-id", "P", "commit-id", "C"]);
+        assert!(!output.status.success());
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        assert!(stderr.contains(error), "{stderr}");
+    }
+
+    std::fs::remove_file(&config_path).unwrap();
+    db_file.close().unwrap();
+}
+
+#[cfg(feature = "fetch")]
+#[test]
+fn credentials_lookup() {
+    let (base_url, requests) = serve_api(|_| {
+        (
+            200,
+            String::new(),
+            r#"[{"@id": "a", "@type": "Package", "owner": null, "declaredName": "A"}]"#.to_owned(),
+        )
+    });
+
+    let db_file = tempfile::NamedTempFile::new().unwrap();
+    let config_path = db_file.path().with_extension("toml");
+    std::fs::write(
+        &config_path,
+        format!(
+            r#"[profiles.full]
+base-url = "{base_url}"
+username = "alice"
+password = "secret"
+
+[profiles.username-only]
+base-url = "{base_url}"
+username = "alice"
+
+[profiles.other-user]
+base-url = "{base_url}"
+username = "carol"
+"#
+        ),
+    )
+    .unwrap();
+    let netrc_path = db_file.path().with_extension("netrc");
+    std::fs::write(
+        &netrc_path,
+        "machine example.com login alice password wrong\n\
+         machine 127.0.0.1 login alice password fromnetrc\n",
+    )
+    .unwrap();
+
+    let output = test_bin::get_test_bin(BIN)
+        .arg(db_file.path())
+        .arg("init-db")
+        .output()
+        .expect("Failed to start {BIN}");
+    assert!(output.status.success());
+
+    // the Authorization header sent with the given server and environment variables
+    let authorization = |server: &str, env: &[(&str, &str)]| {
+        requests.lock().unwrap().clear();
+        let output = test_bin::get_test_bin(BIN)
+            .arg("--config")
+            .arg(&config_path)
+            .arg(db_file.path())
+            .arg("fetch")
+            .arg("--no-import")
+            .arg(server)
+            .arg("project-id")
+            .arg("P")
+            .arg("commit-id")
+            .arg("C")
+            .env_remove("SYSML_USERNAME")
+            .env_remove("SYSML_PASSWORD")
+            .env("NETRC", &netrc_path)
+            .envs(env.iter().copied())
+            .output()
+            .expect("Failed to start {BIN}");
+        assert!(
+            output.status.success(),
+            "{}",
+            String::from_utf8_lossy(&output.stderr)
+        );
+        requests.lock().unwrap()[0]
+            .header("authorization")
+            .map(str::to_owned)
+    };
+    let basic = |credentials: &str| Some(format!("Basic {credentials}"));
+
+    // the environment variables take precedence over the profile, each on its own
+    let env = [("SYSML_USERNAME", "bob"), ("SYSML_PASSWORD", "pw")];
+    assert_eq!(authorization("full", &env), basic("Ym9iOnB3")); // bob:pw
+    let env = [("SYSML_USERNAME", "bob")];
+    assert_eq!(authorization("full", &env), basic("Ym9iOnNlY3JldA==")); // bob:secret
+    assert_eq!(authorization("full", &[]), basic("YWxpY2U6c2VjcmV0")); // alice:secret
+
+    // a missing password is looked up in the netrc file, if given for the same login
+    let netrc_credentials = basic("YWxpY2U6ZnJvbW5ldHJj"); // alice:fromnetrc
+    assert_eq!(authorization("username-only", &[]), netrc_credentials);
+    assert_eq!(authorization(&base_url, &[]), netrc_credentials);
+    assert_eq!(authorization("other-user", &[]), basic("Y2Fyb2w6")); // carol without password
+
+    std::fs::remove_file(&config_path).unwrap();
+    std::fs::remove_file(&netrc_path).unwrap();
+    db_file.close().unwrap();
+}
+
+/// A tiny JSON schema in the shape of the SysML v2 schema, for the tests of the schema generation
+///
+/// Part usages require a non-negative `mass` and have an even `count` below 100 and an enum
+/// `direction`, requirements refer to their documentation, which requires a `body` and refers back
+/// to the documented element.
+const TINY_JSON_SCHEMA: &str = r#"{
+    "$schema": "https://json-schema.org/draft/2020-12/schema",
+    "$defs": {
+        "Identified": {
+            "$id": "https://www.omg.org/spec/SysML/2.0/API/Identified",
+            "type": "object",
+            "properties": {"@id": {"type": "string", "format": "uuid"}},
+            "required": ["@id"],
+            "additionalProperties": false
+        },
+        "PartUsage": {
+            "$id": "https://www.omg.org/spec/SysML/2.0/API/PartUsage",
+            "type": "object",
+            "properties": {
+                "@id": {"type": "string", "format": "uuid"},
+                "@type": {"type": "string", "const": "PartUsage"},
+                "declaredName": {"oneOf": [{"type": "string"}, {"type": "null"}]},
+                "declaredShortName": {"oneOf": [{"type": "string"}, {"type": "null"}]},
+                "name": {"oneOf": [{"type": "string"}, {"type": "null"}]},
+                "qualifiedName": {"oneOf": [{"type": "string"}, {"type": "null"}]},
+                "isLibraryElement": {"type": "boolean"},
+                "owner": {"oneOf": [{"$ref": "https://www.omg.org/spec/SysML/2.0/API/Identified"}, {"type": "null"}]},
+                "mass": {"type": "number", "minimum": 0},
+                "count": {"oneOf": [{"type": "integer", "format": "uint8", "exclusiveMaximum": 100, "multipleOf": 2}, {"type": "null"}]},
+                "direction": {"oneOf": [{"type": "string", "enum": ["in", "out"]}, {"type": "null"}]},
+                "ownedElement": {"type": "array", "items": {"$ref": "https://www.omg.org/spec/SysML/2.0/API/Identified"}},
+                "aliasIds": {"type": "array", "items": {"type": "string"}}
+            },
+            "required": ["@id", "@type", "isLibraryElement", "mass"],
+            "additionalProperties": false
+        },
+        "RequirementUsage": {
+            "$id": "https://www.omg.org/spec/SysML/2.0/API/RequirementUsage",
+            "type": "object",
+            "properties": {
+                "@id": {"type": "string", "format": "uuid"},
+                "@type": {"type": "string", "const": "RequirementUsage"},
+                "declaredName": {"oneOf": [{"type": "string"}, {"type": "null"}]},
+                "declaredShortName": {"oneOf": [{"type": "string"}, {"type": "null"}]},
+                "name": {"oneOf": [{"type": "string"}, {"type": "null"}]},
+                "qualifiedName": {"oneOf": [{"type": "string"}, {"type": "null"}]},
+                "isLibraryElement": {"type": "boolean"},
+                "owner": {"oneOf": [{"$ref": "https://www.omg.org/spec/SysML/2.0/API/Identified"}, {"type": "null"}]},
+                "reqId": {"oneOf": [{"type": "string"}, {"type": "null"}]},
+                "documentation": {"type": "array", "items": {"$ref": "https://www.omg.org/spec/SysML/2.0/API/Identified"}}
+            },
+            "required": ["@id", "@type", "isLibraryElement"],
+            "additionalProperties": false
+        },
+        "Documentation": {
+            "$id": "https://www.omg.org/spec/SysML/2.0/API/Documentation",
+            "type": "object",
+            "properties": {
+                "@id": {"type": "string", "format": "uuid"},
+                "@type": {"type": "string", "const": "Documentation"},
+                "declaredName": {"oneOf": [{"type": "string"}, {"type": "null"}]},
+                "declaredShortName": {"oneOf": [{"type": "string"}, {"type": "null"}]},
+                "name": {"oneOf": [{"type": "string"}, {"type": "null"}]},
+                "qualifiedName": {"oneOf": [{"type": "string"}, {"type": "null"}]},
+                "isLibraryElement": {"type": "boolean"},
+                "owner": {"oneOf": [{"$ref": "https://www.omg.org/spec/SysML/2.0/API/Identified"}, {"type": "null"}]},
+                "body": {"type": "string"},
+                "documentedElement": {"$ref": "https://www.omg.org/spec/SysML/2.0/API/Identified"}
+            },
+            "required": ["@id", "@type", "isLibraryElement", "body"],
+            "additionalProperties": false
+        }
+    }
+}"#;
+
+/// Ids of the elements inserted into the dbs generated from [`TINY_JSON_SCHEMA`]
+const TINY_IDS: [&str; 4] = [
+    "00000000-0000-4000-8000-000000000001",
+    "00000000-0000-4000-8000-000000000002",
+    "00000000-0000-4000-8000-000000000003",
+    "00000000-0000-4000-8000-000000000004",
+];
+
+/// Generates the SQL schema of [`TINY_JSON_SCHEMA`] with the options `args` into a new db
+///
+/// Returns the db and the generated SQL.
+fn generated_db(args: &[&str]) -> (tempfile::NamedTempFile, String) {
+    generated_db_from(TINY_JSON_SCHEMA, args)
+}
+
+/// Generates the SQL schema of the JSON schema `schema` with the options `args` into a new db
+///
+/// Returns the db and the generated SQL.
+fn generated_db_from(schema: &str, args: &[&str]) -> (tempfile::NamedTempFile, String) {
+    let db_file = tempfile::NamedTempFile::new().unwrap();
+    let schema_path = db_file.path().with_extension("schema.json");
+    let sql_path = db_file.path().with_extension("sql");
+    std::fs::write(&schema_path, schema).unwrap();
+
+    let output = test_bin::get_test_bin(BIN)
+        .arg(db_file.path())
+        .arg("json-schema-to-sql-schema")
+        .arg("--dump-sql")
+        .arg(&sql_path)
+        .args(args)
+        .arg(&schema_path)
+        .output()
+        .expect("Failed to start {BIN}");
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+
+    let sql = std::fs::read_to_string(&sql_path).unwrap();
+    std::fs::remove_file(&schema_path).unwrap();
+    std::fs::remove_file(&sql_path).unwrap();
+    (db_file, sql)
+}
+
+#[test]
+fn generate_not_null_triggers() {
+    let (db_file, sql) = generated_db(&[]);
+    assert!(sql.contains(r#"CREATE TRIGGER "elements.not_null.PartUsage.insert""#));
+    assert!(sql.contains(r#"CREATE TRIGGER "elements.not_null.Documentation.update""#));
+    // required by some types only, the columns are nullable
+    assert!(sql.contains(r#""mass" REAL CHECK("mass" >= 0),"#));
+
+    let conn = rusqlite::Connection::open(db_file.path()).unwrap();
+    let insert = r#"INSERT INTO "elements" ("@id", "@type", "isLibraryElement", "mass") VALUES (?1, ?2, 0, ?3)"#;
+    let err = conn
+        .execute(insert, (TINY_IDS[0], "PartUsage", None::<f64>))
+        .unwrap_err();
+    assert!(
+        err.to_string()
+            .contains("elements of type PartUsage require non-null values for mass"),
+        "{err}"
+    );
+    conn.execute(insert, (TINY_IDS[0], "PartUsage", Some(1.5)))
+        .unwrap();
+    conn.execute(insert, (TINY_IDS[1], "RequirementUsage", None::<f64>))
+        .unwrap();
+    let err = conn
+        .execute(
+            r#"UPDATE "elements" SET "mass" = NULL WHERE "@id" = ?1"#,
+            [TINY_IDS[0]],
+        )
+        .unwrap_err();
+    assert!(err.to_string().contains("require non-null values for mass"));
+    drop(conn);
+    db_file.close().unwrap();
+
+    let (db_file, sql) = generated_db(&["--no-not-null-triggers"]);
+    assert!(!sql.contains("not_null"));
+    let conn = rusqlite::Connection::open(db_file.path()).unwrap();
+    conn.execute(insert, (TINY_IDS[0], "PartUsage", None::<f64>))
+        .unwrap();
+    drop(conn);
+    db_file.close().unwrap();
+}
+
+#[test]
+fn generate_not_null_columns() {
+    // required by all types, the columns are NOT NULL and need no triggers
+    let (db_file, sql) = generated_db(&[]);
+    assert!(sql.contains(r#""@type" TEXT NOT NULL,"#));
+    assert!(sql.contains(r#""isLibraryElement" INTEGER NOT NULL,"#));
+    assert!(sql.contains(r#""body" TEXT,"#));
+    assert!(!sql.contains(r#"new."isLibraryElement" IS NULL"#));
+
+    let conn = rusqlite::Connection::open(db_file.path()).unwrap();
+    let err = conn
+        .execute(
+            r#"INSERT INTO "elements" ("@id", "@type") VALUES (?1, 'RequirementUsage')"#,
+            [TINY_IDS[0]],
+        )
+        .unwrap_err();
+    assert!(
+        err.to_string()
+            .contains("NOT NULL constraint failed: elements.isLibraryElement"),
+        "{err}"
+    );
+    drop(conn);
+    db_file.close().unwrap();
+
+    // once a type does not require it, the column is nullable and checked per type
+    let (db_file, sql) = generated_db_from(
+        &TINY_JSON_SCHEMA.replace(
+            r#""required": ["@id", "@type", "isLibraryElement", "body"]"#,
+            r#""required": ["@id", "@type", "body"]"#,
+        ),
+        &[],
+    );
+    assert!(sql.contains(r#""isLibraryElement" INTEGER,"#));
+    assert!(sql.contains(r#"new."isLibraryElement" IS NULL"#));
+
+    let conn = rusqlite::Connection::open(db_file.path()).unwrap();
+    conn.execute(
+        r#"INSERT INTO "elements" ("@id", "@type", "body") VALUES (?1, 'Documentation', 'Text')"#,
+        [TINY_IDS[0]],
+    )
+    .unwrap();
+    let err = conn
+        .execute(
+            r#"INSERT INTO "elements" ("@id", "@type") VALUES (?1, 'RequirementUsage')"#,
+            [TINY_IDS[1]],
+        )
+        .unwrap_err();
+    assert!(
+        err.to_string().contains(
+            "elements of type RequirementUsage require non-null values for isLibraryElement"
+        ),
+        "{err}"
+    );
+    drop(conn);
+    db_file.close().unwrap();
+}
+
+#[test]
+fn generate_uuid_check() {
+    let (db_file, sql) = generated_db(&[]);
+    assert!(sql.contains(r#""@id" TEXT CHECK("@id" GLOB ('[0-9a-fA-F]"#));
+
+    let conn = rusqlite::Connection::open(db_file.path()).unwrap();
+    let insert = r#"INSERT INTO "elements" ("@id", "@type", "isLibraryElement") VALUES (?1, 'RequirementUsage', 0)"#;
+    for id in [
+        "not-a-uuid",
+        "00000000-0000-4000-8000-00000000000",
+        "00000000-0000-4000-8000-0000000000001",
+        "0000000g-0000-4000-8000-000000000001",
+        "00000000_0000_4000_8000_000000000001",
+    ] {
+        let err = conn.execute(insert, [id]).unwrap_err();
+        assert!(err.to_string().contains("CHECK constraint failed"), "{id}");
+    }
+    conn.execute(insert, [TINY_IDS[0]]).unwrap();
+    conn.execute(insert, ["ABCDEF00-0000-4000-8000-00000000000a"])
+        .unwrap();
+    drop(conn);
+
+    db_file.close().unwrap();
+}
+
+#[test]
+fn generate_type_views() {
+    let (db_file, sql) = generated_db(&[]);
+    assert!(sql.contains(r#"CREATE VIEW "PartUsage" AS SELECT"#));
+    assert!(sql.contains(r#"CREATE VIEW "RequirementUsage" AS SELECT"#));
+    assert!(sql.contains(r#"CREATE VIEW "Documentation" AS SELECT"#));
+
+    let conn = rusqlite::Connection::open(db_file.path()).unwrap();
+    conn.execute(
+        r#"INSERT INTO "elements" ("@id", "@type", "isLibraryElement", "mass", "declaredName") VALUES (?1, 'PartUsage', 0, 2.0, 'Wheel')"#,
+        [TINY_IDS[0]],
+    )
+    .unwrap();
+    conn.execute(
+        r#"INSERT INTO "elements" ("@id", "@type", "isLibraryElement", "reqId") VALUES (?1, 'RequirementUsage', 0, 'R-1')"#,
+        [TINY_IDS[1]],
+    )
+    .unwrap();
+
+    // each view holds the elements of its type, with the columns of the type
+    let columns = |view: &str| -> Vec<String> {
+        let stmt = conn.prepare(&format!(r#"SELECT * FROM "{view}""#)).unwrap();
+        stmt.column_names().into_iter().map(str::to_owned).collect()
+    };
+    let part_columns = columns("PartUsage");
+    assert!(part_columns.iter().any(|c| c == "mass"));
+    assert!(part_columns.iter().any(|c| c == "direction"));
+    assert!(!part_columns.iter().any(|c| c == "reqId"));
+    let requirement_columns = columns("RequirementUsage");
+    assert!(requirement_columns.iter().any(|c| c == "reqId"));
+    assert!(!requirement_columns.iter().any(|c| c == "mass"));
+
+    let parts: Vec<(String, String, f64)> = conn
+        .prepare(r#"SELECT "@id", "declaredName", "mass" FROM "PartUsage""#)
+        .unwrap()
+        .query_map([], |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)))
+        .unwrap()
+        .collect::<Result<_, _>>()
+        .unwrap();
+    assert_eq!(parts, [(TINY_IDS[0].to_owned(), "Wheel".to_owned(), 2.0)]);
+    let req_ids: Vec<String> = conn
+        .prepare(r#"SELECT "reqId" FROM "RequirementUsage""#)
+        .unwrap()
+        .query_map([], |row| row.get(0))
+        .unwrap()
+        .collect::<Result<_, _>>()
+        .unwrap();
+    assert_eq!(req_ids, ["R-1"]);
+    let documentation: usize = conn
+        .query_row(r#"SELECT count(*) FROM "Documentation""#, [], |row| {
+            row.get(0)
+        })
+        .unwrap();
+    assert_eq!(documentation, 0);
+    drop(conn);
+
+    db_file.close().unwrap();
+}
+
+#[test]
+fn generate_property_domains() {
+    let (db_file, sql) = generated_db(&[]);
+    assert!(sql.contains(r#"CREATE TABLE "property_domains" ("#));
+
+    let conn = rusqlite::Connection::open(db_file.path()).unwrap();
+    let domain_of = |property: &str| -> Vec<String> {
+        conn.prepare(
+            r#"SELECT "@type" FROM "property_domains" WHERE "property" = ?1 ORDER BY "@type""#,
+        )
+        .unwrap()
+        .query_map([property], |row| row.get(0))
+        .unwrap()
+        .collect::<Result<_, _>>()
+        .unwrap()
+    };
+    assert_eq!(domain_of("mass"), ["PartUsage"]);
+    assert_eq!(domain_of("ownedElement"), ["PartUsage"]);
+    assert_eq!(domain_of("documentation"), ["RequirementUsage"]);
+    assert_eq!(
+        domain_of("owner"),
+        ["Documentation", "PartUsage", "RequirementUsage"]
+    );
+
+    conn.execute(
+        r#"INSERT INTO "elements" ("@id", "@type", "isLibraryElement", "mass") VALUES (?1, 'PartUsage', 0, 1.0)"#,
+        [TINY_IDS[0]],
+    )
+    .unwrap();
+    conn.execute(
+        r#"INSERT INTO "elements" ("@id", "@type", "isLibraryElement") VALUES (?1, 'RequirementUsage', 0)"#,
+        [TINY_IDS[1]],
+    )
+    .unwrap();
+
+    // requirements own no elements, the relation is refused when added on the command line
+    let output = test_bin::get_test_bin(BIN)
+        .arg(db_file.path())
+        .arg("add-relation")
+        .arg("ownedElement")
+        .arg(TINY_IDS[1])
+        .arg(TINY_IDS[0])
+        .output()
+        .expect("Failed to start {BIN}");
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains(
+        r#"elements of type "RequirementUsage" do not carry the relation "ownedElement""#
+    ));
+
+    // and reported by the lint when inserted otherwise
+    conn.execute(
+        r#"INSERT INTO "relations" ("name", "origin_id", "target_id") VALUES ('ownedElement', ?1, ?2)"#,
+        [TINY_IDS[1], TINY_IDS[0]],
+    )
+    .unwrap();
+    drop(conn);
+
+    let output = test_bin::get_test_bin(BIN)
+        .arg(db_file.path())
+        .arg("lint")
+        .arg("--json")
+        .output()
+        .expect("Failed to start {BIN}");
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    let findings: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    let outside_domain: Vec<_> = findings
+        .as_array()
+        .unwrap()
+        .iter()
+        .filter(|finding| finding["check"] == "property-outside-domain")
+        .collect();
+    assert_eq!(outside_domain.len(), 1, "{findings}");
+    assert_eq!(outside_domain[0]["element_id"], TINY_IDS[1]);
+
+    db_file.close().unwrap();
+}
+
+#[test]
+fn generate_enum_tables() {
+    let insert = r#"INSERT INTO "elements" ("@id", "@type", "isLibraryElement", "mass", "direction") VALUES (?1, 'PartUsage', 0, 1.0, ?2)"#;
+
+    // by default, the variants are checked by a CHECK constraint
+    let (db_file, sql) = generated_db(&[]);
+    assert!(sql.contains(r#""direction" TEXT CHECK("direction" IN ('in', 'out')),"#));
+    assert!(!sql.contains("enum_direction"));
+    let conn = rusqlite::Connection::open(db_file.path()).unwrap();
+    conn.execute(insert, (TINY_IDS[0], "in")).unwrap();
+    let err = conn.execute(insert, (TINY_IDS[1], "up")).unwrap_err();
+    assert!(err.to_string().contains("CHECK constraint failed"), "{err}");
+    drop(conn);
+    db_file.close().unwrap();
+
+    let (db_file, sql) = generated_db(&["--enum-tables"]);
+    assert!(sql.contains(r#""direction" TEXT REFERENCES "enum_direction"("value"),"#));
+    assert!(sql.contains(r#"CREATE TABLE "enum_direction" ("#));
+    let conn = rusqlite::Connection::open(db_file.path()).unwrap();
+    let variants: Vec<String> = conn
+        .prepare(r#"SELECT "value" FROM "enum_direction" ORDER BY "value""#)
+        .unwrap()
+        .query_map([], |row| row.get(0))
+        .unwrap()
+        .collect::<Result<_, _>>()
+        .unwrap();
+    assert_eq!(variants, ["in", "out"]);
+    conn.pragma_update(None, "foreign_keys", true).unwrap();
+    conn.execute(insert, (TINY_IDS[0], "out")).unwrap();
+    let err = conn.execute(insert, (TINY_IDS[1], "up")).unwrap_err();
+    assert!(
+        err.to_string().contains("FOREIGN KEY constraint failed"),
+        "{err}"
+    );
+    drop(conn);
+    db_file.close().unwrap();
+}
+
+#[test]
+fn generate_strict_tables() {
+    let insert = r#"INSERT INTO "elements" ("@id", "@type", "isLibraryElement", "mass") VALUES (?1, 'PartUsage', 0, ?2)"#;
+
+    let (db_file, sql) = generated_db(&[]);
+    assert!(sql.contains(") STRICT;"));
+    let conn = rusqlite::Connection::open(db_file.path()).unwrap();
+    conn.execute(insert, (TINY_IDS[0], "1.5")).unwrap();
+    let err = conn.execute(insert, (TINY_IDS[1], "heavy")).unwrap_err();
+    assert!(
+        err.to_string()
+            .contains("cannot store TEXT value in REAL column"),
+        "{err}"
+    );
+    drop(conn);
+    db_file.close().unwrap();
+
+    // without STRICT, the storage classes are checked by CHECK constraints
+    let (db_file, sql) = generated_db(&["--no-strict"]);
+    assert!(!sql.contains("STRICT"));
+    assert!(sql.contains(r#"CHECK(typeof("mass") IN ('real', 'null'))"#));
+    let conn = rusqlite::Connection::open(db_file.path()).unwrap();
+    // the text is converted by the type affinity of the column
+    conn.execute(insert, (TINY_IDS[0], "1.5")).unwrap();
+    let mass: f64 = conn
+        .query_row(r#"SELECT "mass" FROM "elements""#, [], |row| row.get(0))
+        .unwrap();
+    assert_eq!(mass, 1.5);
+    let err = conn.execute(insert, (TINY_IDS[1], "heavy")).unwrap_err();
+    assert!(err.to_string().contains("CHECK constraint failed"), "{err}");
+    drop(conn);
+    db_file.close().unwrap();
+}
+
+#[test]
+fn generate_without_rowid() {
+    let (db_file, sql) = generated_db(&[]);
+    assert!(sql.contains("\"value\" ANY\n) STRICT;"));
+    assert!(sql.contains("elements_fts"));
+    db_file.close().unwrap();
+
+    let (db_file, sql) = generated_db(&["--without-rowid"]);
+    assert!(sql.contains("\"value\" ANY\n) STRICT, WITHOUT ROWID;"));
+    // the full-text index refers to the rowids, hence it is omitted
+    assert!(!sql.contains("elements_fts"));
+
+    let conn = rusqlite::Connection::open(db_file.path()).unwrap();
+    let elements_sql: String = conn
+        .query_row(
+            r#"SELECT "sql" FROM "sqlite_master" WHERE "name" = 'elements'"#,
+            [],
+            |row| row.get(0),
+        )
+        .unwrap();
+    assert!(elements_sql.ends_with("WITHOUT ROWID"), "{elements_sql}");
+    conn.execute(
+        r#"INSERT INTO "elements" ("@id", "@type", "isLibraryElement", "mass", "qualifiedName") VALUES (?1, 'PartUsage', 0, 1.0, 'Car::Wheel')"#,
+        [TINY_IDS[0]],
+    )
+    .unwrap();
+    drop(conn);
+
+    // only the search without the full-text index works
+    let output = test_bin::get_test_bin(BIN)
+        .arg(db_file.path())
+        .arg("search")
+        .arg("Whe")
+        .output()
+        .expect("Failed to start {BIN}");
+    assert!(output.status.success());
+    assert_eq!(
+        String::from_utf8(output.stdout).unwrap(),
+        format!("{}\tPartUsage\tCar::Wheel\n", TINY_IDS[0])
+    );
+    let output = test_bin::get_test_bin(BIN)
+        .arg(db_file.path())
+        .arg("search")
+        .arg("--fts")
+        .arg("Wheel")
+        .output()
+        .expect("Failed to start {BIN}");
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("the db lacks the full-text index"));
+
+    db_file.close().unwrap();
+}
+
+#[test]
+fn generate_raw_json() {
+    let (db_file, sql) = generated_db(&["--raw-json"]);
+    assert!(sql.contains(r#""@json" TEXT NOT NULL CHECK(json_valid("@json"))"#));
+    assert!(sql.contains(
+        r#""declaredName" TEXT GENERATED ALWAYS AS (json_extract("@json", '$."declaredName"')) VIRTUAL"#
+    ));
+    // only the hot fields get a column
+    assert!(!sql.contains(r#""mass""#));
+    assert!(sql.contains("elements_fts"));
+
+    // attributes unknown to the schema are kept instead of rejected
+    let [car, wheel, ..] = TINY_IDS;
+    let dump_path = db_file.path().with_extension("dump.json");
+    std::fs::write(
+        &dump_path,
+        format!(
+            r#"[{{"@id": "{car}", "@type": "PartUsage", "isLibraryElement": false, "mass": 1000.0, "declaredName": "Car", "ownedElement": [{{"@id": "{wheel}"}}]}},
+                {{"@id": "{wheel}", "@type": "PartUsage", "isLibraryElement": false, "mass": 10.0, "declaredName": "Wheel", "owner": {{"@id": "{car}"}}, "frobnicated": true}}]"#
+        ),
+    )
+    .unwrap();
+    let output = test_bin::get_test_bin(BIN)
+        .arg(db_file.path())
+        .arg("import-json")
+        .arg("--strict")
+        .arg(&dump_path)
+        .output()
+        .expect("Failed to start {BIN}");
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+
+    let conn = rusqlite::Connection::open(db_file.path()).unwrap();
+    let (name, mass, frobnicated): (String, f64, bool) = conn
+        .query_row(
+            r#"SELECT "declaredName", json_extract("@json", '$.mass'), json_extract("@json", '$.frobnicated') FROM "elements" WHERE "@id" = ?1"#,
+            [wheel],
+            |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)),
+        )
+        .unwrap();
+    assert_eq!((name.as_str(), mass, frobnicated), ("Wheel", 10.0, true));
+    let owner: String = conn
+        .query_row(
+            r#"SELECT "target_id" FROM "relations" WHERE "origin_id" = ?1 AND "name" = 'owner'"#,
+            [wheel],
+            |row| row.get(0),
+        )
+        .unwrap();
+    assert_eq!(owner, car);
+    drop(conn);
+
+    let output = test_bin::get_test_bin(BIN)
+        .arg(db_file.path())
+        .arg("search")
+        .arg("--fts")
+        .arg("Wheel")
+        .output()
+        .expect("Failed to start {BIN}");
+    assert!(output.status.success());
+    assert_eq!(
+        String::from_utf8(output.stdout).unwrap(),
+        format!("{wheel}\tPartUsage\tWheel\n")
+    );
+
+    let output = test_bin::get_test_bin(BIN)
+        .arg(db_file.path())
+        .arg("verify")
+        .arg(&dump_path)
+        .output()
+        .expect("Failed to start {BIN}");
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    assert!(
+        !String::from_utf8_lossy(&output.stderr).contains("WARN"),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    db_file.close().unwrap();
+
+    // without all of the searched columns, the full-text index is omitted
+    let (db_file, sql) = generated_db(&["--raw-json", "--hot-field", "declaredName"]);
+    assert!(sql.contains(r#""declaredName" TEXT GENERATED"#));
+    assert!(!sql.contains(r#""qualifiedName""#));
+    assert!(!sql.contains("elements_fts"));
+    db_file.close().unwrap();
+}
+
+#[test]
+fn generate_schema_meta() {
+    let (db_file, sql) = generated_db(&[]);
+    assert!(sql.contains(r#"CREATE TABLE "schema_meta" ("#));
+
+    let conn = rusqlite::Connection::open(db_file.path()).unwrap();
+    let meta = |property: &str| -> (String, Option<String>, String, bool) {
+        conn.query_row(
+            r#"SELECT "table", "sql_type", "definitions", "nullability_relaxed" FROM "schema_meta" WHERE "property" = ?1"#,
+            [property],
+            |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?, row.get(3)?)),
+        )
+        .unwrap()
+    };
+    // required by the part usages only, the column is nullable and the trigger enforces it
+    assert_eq!(
+        meta("mass"),
+        (
+            "elements".to_owned(),
+            Some(r#"REAL CHECK("mass" >= 0)"#.to_owned()),
+            r#"["PartUsage"]"#.to_owned(),
+            true
+        )
+    );
+    assert_eq!(
+        meta("isLibraryElement"),
+        (
+            "elements".to_owned(),
+            Some("INTEGER".to_owned()),
+            r#"["Documentation","PartUsage","RequirementUsage"]"#.to_owned(),
+            false
+        )
+    );
+    assert_eq!(
+        meta("documentation"),
+        (
+            "relations".to_owned(),
+            None,
+            r#"["RequirementUsage"]"#.to_owned(),
+            false
+        )
+    );
+    assert_eq!(
+        meta("aliasIds"),
+        (
+            "extended_properties".to_owned(),
+            None,
+            r#"["PartUsage"]"#.to_owned(),
+            false
+        )
+    );
+
+    // each column of the tables is recorded
+    let unrecorded: Vec<String> = conn
+        .prepare(
+            r#"SELECT c."name" FROM pragma_table_info('elements') AS c
+            WHERE NOT EXISTS (SELECT 1 FROM "schema_meta" AS m WHERE m."property" = c."name")"#,
+        )
+        .unwrap()
+        .query_map([], |row| row.get(0))
+        .unwrap()
+        .collect::<Result<_, _>>()
+        .unwrap();
+    assert_eq!(unrecorded, Vec::<String>::new());
+    drop(conn);
+
+    db_file.close().unwrap();
+}
+
+#[test]
+fn generate_numeric_checks() {
+    let (db_file, sql) = generated_db(&[]);
+    assert!(sql.contains(r#""mass" REAL CHECK("mass" >= 0),"#));
+    assert!(sql.contains(
+        r#""count" INTEGER CHECK("count" BETWEEN 0 AND 255 AND "count" < 100 AND "count" % 2 = 0),"#
+    ));
+
+    let conn = rusqlite::Connection::open(db_file.path()).unwrap();
+    let insert = r#"INSERT INTO "elements" ("@id", "@type", "isLibraryElement", "mass", "count") VALUES (?1, 'PartUsage', 0, ?2, ?3)"#;
+    conn.execute(insert, (TINY_IDS[0], 0.0, Some(98))).unwrap();
+    conn.execute(insert, (TINY_IDS[1], 2.5, None::<i64>))
+        .unwrap();
+    for (mass, count) in [
+        (-0.5, None),
+        (1.0, Some(-2)),
+        (1.0, Some(100)),
+        (1.0, Some(3)),
+    ] {
+        let err = conn
+            .execute(insert, (TINY_IDS[2], mass, count))
+            .unwrap_err();
+        assert!(
+            err.to_string().contains("CHECK constraint failed"),
+            "{mass} {count:?}: {err}"
+        );
+    }
+    drop(conn);
+
+    db_file.close().unwrap();
+}
+
+#[test]
+fn generate_convenience_views() {
+    let (db_file, sql) = generated_db(&[]);
+    assert!(sql.contains(r#"CREATE VIEW "requirements" AS SELECT"#));
+    assert!(sql.contains(r#"CREATE VIEW "parts_tree" AS WITH RECURSIVE"#));
+    assert!(sql.contains(r#"CREATE VIEW "documentation_texts" AS SELECT"#));
+    // no type of the schema has connector ends or allocations
+    assert!(!sql.contains(r#"CREATE VIEW "connections""#));
+    assert!(!sql.contains(r#"CREATE VIEW "allocations""#));
+
+    let [car, wheel, requirement, documentation] = TINY_IDS;
+    let conn = rusqlite::Connection::open(db_file.path()).unwrap();
+    conn.execute_batch(&format!(
+        r#"INSERT INTO "elements" ("@id", "@type", "isLibraryElement", "mass", "declaredName", "reqId", "body") VALUES
+            ('{car}', 'PartUsage', 0, 1000.0, 'Car', NULL, NULL),
+            ('{wheel}', 'PartUsage', 0, 10.0, 'Wheel', NULL, NULL),
+            ('{requirement}', 'RequirementUsage', 0, NULL, 'Mass', 'R-1', NULL),
+            ('{documentation}', 'Documentation', 0, NULL, NULL, NULL, 'The car is light.');
+        INSERT INTO "relations" ("name", "origin_id", "target_id") VALUES
+            ('ownedElement', '{car}', '{wheel}'),
+            ('documentation', '{requirement}', '{documentation}'),
+            ('documentedElement', '{documentation}', '{requirement}');"#
+    ))
+    .unwrap();
+
+    let requirements: (String, String, String) = conn
+        .query_row(
+            r#"SELECT "@id", "reqId", "documentation" FROM "requirements""#,
+            [],
+            |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)),
+        )
+        .unwrap();
+    assert_eq!(
+        requirements,
+        (
+            requirement.to_owned(),
+            "R-1".to_owned(),
+            "The car is light.".to_owned()
+        )
+    );
+
+    let tree: Vec<(String, Option<String>, i64)> = conn
+        .prepare(
+            r#"SELECT "declaredName", "parent_id", "depth" FROM "parts_tree" ORDER BY "depth""#,
+        )
+        .unwrap()
+        .query_map([], |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)))
+        .unwrap()
+        .collect::<Result<_, _>>()
+        .unwrap();
+    assert_eq!(
+        tree,
+        [
+            ("Car".to_owned(), None, 0),
+            ("Wheel".to_owned(), Some(car.to_owned()), 1)
+        ]
+    );
+
+    let texts: (String, String, String, String) = conn
+        .query_row(
+            r#"SELECT "@id", "element_id", "element_name", "body" FROM "documentation_texts""#,
+            [],
+            |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?, row.get(3)?)),
+        )
+        .unwrap();
+    assert_eq!(
+        texts,
+        (
+            documentation.to_owned(),
+            requirement.to_owned(),
+            "Mass".to_owned(),
+            "The car is light.".to_owned()
+        )
+    );
+    drop(conn);
+
+    db_file.close().unwrap();
+}
+
+#[test]
+fn generate_nested_objects() {
+    // a small object of primitives is flattened, an open one is stored as JSON
+    let (db_file, sql) = generated_db_from(
+        &TINY_JSON_SCHEMA.replace(
+            r#""aliasIds": {"type": "array", "items": {"type": "string"}}"#,
+            r#""aliasIds": {"type": "array", "items": {"type": "string"}},
+                "position": {"oneOf": [{"type": "object", "properties": {"x": {"type": "number"}, "y": {"type": "number"}}, "required": ["x", "y"], "additionalProperties": false}, {"type": "null"}]},
+                "annotations": {"type": "object", "properties": {"source": {"type": "string"}}, "additionalProperties": true}"#,
+        ),
+        &[],
+    );
+    assert!(sql.contains(r#""position.x" REAL"#), "{sql}");
+    assert!(sql.contains(r#""position.y" REAL"#));
+    assert!(!sql.contains(r#""position" "#));
+    assert!(sql.contains(r#""annotations" TEXT CHECK(json_valid("annotations"))"#));
+
+    let [car, wheel, ..] = TINY_IDS;
+    let dump_path = db_file.path().with_extension("dump.json");
+    std::fs::write(
+        &dump_path,
+        format!(
+            r#"[{{"@id": "{car}", "@type": "PartUsage", "isLibraryElement": false, "mass": 1000.0, "position": {{"x": 1.5, "y": -2.0}}, "annotations": {{"source": "CAD", "tags": ["a", "b"]}}}},
+                {{"@id": "{wheel}", "@type": "PartUsage", "isLibraryElement": false, "mass": 10.0, "position": null}}]"#
+        ),
+    )
+    .unwrap();
+    let output = test_bin::get_test_bin(BIN)
+        .arg(db_file.path())
+        .arg("import-json")
+        .arg(&dump_path)
+        .output()
+        .expect("Failed to start {BIN}");
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+
+    let conn = rusqlite::Connection::open(db_file.path()).unwrap();
+    let elements: Vec<(Option<f64>, Option<f64>, Option<String>)> = conn
+        .prepare(
+            r#"SELECT "position.x", "position.y", "annotations" FROM "PartUsage" ORDER BY "@id""#,
+        )
+        .unwrap()
+        .query_map([], |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)))
+        .unwrap()
+        .collect::<Result<_, _>>()
+        .unwrap();
+    assert_eq!(elements.len(), 2);
+    assert_eq!((elements[0].0, elements[0].1), (Some(1.5), Some(-2.0)));
+    let annotations: serde_json::Value =
+        serde_json::from_str(elements[0].2.as_deref().unwrap()).unwrap();
+    assert_eq!(
+        annotations,
+        serde_json::json!({"source": "CAD", "tags": ["a", "b"]})
+    );
+    assert_eq!(elements[1], (None, None, None));
+    drop(conn);
+
+    std::fs::remove_file(&dump_path).unwrap();
+    db_file.close().unwrap();
+}
+
+#[test]
+fn generate_relation_names() {
+    let (db_file, sql) = generated_db(&[]);
+    assert!(sql.contains(r#"FOREIGN KEY("name") REFERENCES "relation_names"("name")"#));
+    assert!(!sql.contains("analysisAction"));
+
+    let relation_names = |conn: &rusqlite::Connection| -> Vec<String> {
+        conn.prepare(r#"SELECT "name" FROM "relation_names" ORDER BY "name""#)
+            .unwrap()
+            .query_map([], |row| row.get(0))
+            .unwrap()
+            .collect::<Result<_, _>>()
+            .unwrap()
+    };
+    let conn = rusqlite::Connection::open(db_file.path()).unwrap();
+    let known = relation_names(&conn);
+    for name in [
+        "documentation",
+        "documentedElement",
+        "ownedElement",
+        "owner",
+    ] {
+        assert!(known.iter().any(|n| n == name), "{name} in {known:?}");
+    }
+
+    // relations of unknown names are rejected by the schema
+    let [car, wheel, requirement, _] = TINY_IDS;
+    conn.execute_batch(&format!(
+        r#"PRAGMA foreign_keys = ON;
+        INSERT INTO "elements" ("@id", "@type", "isLibraryElement", "mass") VALUES
+            ('{car}', 'PartUsage', 0, 1000.0),
+            ('{wheel}', 'PartUsage', 0, 10.0);"#
+    ))
+    .unwrap();
+    let err = conn
+        .execute(
+            r#"INSERT INTO "relations" ("name", "origin_id", "target_id") VALUES ('satisfiedRequirement', ?1, ?2)"#,
+            [car, wheel],
+        )
+        .unwrap_err();
+    assert!(
+        err.to_string().contains("FOREIGN KEY constraint failed"),
+        "{err}"
+    );
+    conn.execute_batch(r#"DELETE FROM "elements""#).unwrap();
+    drop(conn);
+
+    // but the import registers them on first sight, while empty arrays register nothing
+    let dump_path = db_file.path().with_extension("dump.json");
+    std::fs::write(
+        &dump_path,
+        format!(
+            r#"[{{"@id": "{car}", "@type": "PartUsage", "isLibraryElement": false, "mass": 1000.0, "satisfiedRequirement": {{"@id": "{requirement}"}}, "analysisAction": []}},
+                {{"@id": "{requirement}", "@type": "RequirementUsage", "isLibraryElement": false}}]"#
+        ),
+    )
+    .unwrap();
+    let output = test_bin::get_test_bin(BIN)
+        .arg(db_file.path())
+        .arg("import-json")
+        .arg(&dump_path)
+        .output()
+        .expect("Failed to start {BIN}");
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+
+    let conn = rusqlite::Connection::open(db_file.path()).unwrap();
+    let registered = relation_names(&conn);
+    assert_eq!(registered.len(), known.len() + 1);
+    assert!(registered.iter().any(|n| n == "satisfiedRequirement"));
+    assert!(!registered.iter().any(|n| n == "analysisAction"));
+    let relation: (String, String) = conn
+        .query_row(
+            r#"SELECT "origin_id", "target_id" FROM "relations" WHERE "name" = 'satisfiedRequirement'"#,
+            [],
+            |row| Ok((row.get(0)?, row.get(1)?)),
+        )
+        .unwrap();
+    assert_eq!(relation, (car.to_owned(), requirement.to_owned()));
+    drop(conn);
+
+    std::fs::remove_file(&dump_path).unwrap();
+    db_file.close().unwrap();
+}
+
+/// Imports the elements `dump` into the project `project_id` of the db generated by [`generated_db`]
+fn import_into_project(
+    db_file: &tempfile::NamedTempFile,
+    project_id: &str,
+    dump: &str,
+) -> std::process::Output {
+    let dump_path = db_file.path().with_extension(format!("{project_id}.json"));
+    std::fs::write(&dump_path, dump).unwrap();
+    let output = test_bin::get_test_bin(BIN)
+        .arg("--project")
+        .arg(project_id)
+        .arg(db_file.path())
+        .arg("import-json")
+        .arg(&dump_path)
+        .output()
+        .expect("Failed to start {BIN}");
+    std::fs::remove_file(&dump_path).unwrap();
+    output
+}
+
+#[test]
+fn multi_project() {
+    let (db_file, sql) = generated_db(&["--multi-project"]);
+    assert!(sql.contains(r#"PRIMARY KEY("project_id", "@id")"#));
+
+    // the same element id in two projects
+    let [car, wheel, ..] = TINY_IDS;
+    let output = import_into_project(
+        &db_file,
+        "car",
+        &format!(
+            r#"[
+                {{"@id": "{car}", "@type": "PartUsage", "isLibraryElement": false, "mass": 1000.0, "qualifiedName": "Car", "ownedElement": [{{"@id": "{wheel}"}}]}},
+                {{"@id": "{wheel}", "@type": "PartUsage", "isLibraryElement": false, "mass": 10.0, "qualifiedName": "Car::Wheel", "owner": {{"@id": "{car}"}}}}
+            ]"#
+        ),
+    );
+    assert!(output.status.success());
+    let output = import_into_project(
+        &db_file,
+        "bike",
+        &format!(
+            r#"[{{"@id": "{wheel}", "@type": "PartUsage", "isLibraryElement": false, "mass": 1.0, "qualifiedName": "Bike::Wheel"}}]"#
+        ),
+    );
+    assert!(output.status.success());
+
+    // each project is queried on its own
+    let search = |project_id: &str| {
+        test_bin::get_test_bin(BIN)
+            .arg("--project")
+            .arg(project_id)
+            .arg(db_file.path())
+            .arg("search")
+            .arg("Wheel")
+            .output()
+            .expect("Failed to start {BIN}")
+    };
+    let output = search("car");
+    assert!(output.status.success());
+    assert_eq!(
+        String::from_utf8(output.stdout).unwrap(),
+        format!("{wheel}\tPartUsage\tCar::Wheel\n")
+    );
+    let output = search("bike");
+    assert!(output.status.success());
+    assert_eq!(
+        String::from_utf8(output.stdout).unwrap(),
+        format!("{wheel}\tPartUsage\tBike::Wheel\n")
+    );
+    let output = search("plane");
+    assert!(!output.status.success());
+    assert!(
+        String::from_utf8_lossy(&output.stderr)
+            .contains(r#"the db contains no project "plane", only the projects ["bike", "car"]"#)
+    );
+
+    // so are SQL scripts
+    let output = test_bin::get_test_bin(BIN)
+        .arg("--project")
+        .arg("car")
+        .arg(db_file.path())
+        .arg("sql")
+        .arg("--format")
+        .arg("json")
+        .arg("--execute")
+        .arg(r#"SELECT "qualifiedName" FROM "elements" ORDER BY "qualifiedName""#)
+        .output()
+        .expect("Failed to start {BIN}");
+    assert!(output.status.success());
+    assert_eq!(
+        String::from_utf8(output.stdout).unwrap(),
+        "[{\"qualifiedName\":\"Car\"},{\"qualifiedName\":\"Car::Wheel\"}]\n"
+    );
+
+    let output = test_bin::get_test_bin(BIN)
+        .arg(db_file.path())
+        .arg("db-info")
+        .output()
+        .expect("Failed to start {BIN}");
+    assert!(output.status.success());
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(
+        stdout.contains("# projects\nbike\t1 elements\ncar\t2 elements\n"),
+        "{stdout}"
+    );
+
+    // reimporting a project leaves the other one alone
+    let output = import_into_project(
+        &db_file,
+        "bike",
+        &format!(
+            r#"[{{"@id": "{wheel}", "@type": "PartUsage", "isLibraryElement": false, "mass": 1.5, "qualifiedName": "Bike::Wheel"}}]"#
+        ),
+    );
+    assert!(output.status.success());
+    let conn = rusqlite::Connection::open(db_file.path()).unwrap();
+    let masses: Vec<(String, f64)> = conn
+        .prepare(
+            r#"SELECT "project_id", "mass" FROM "elements" WHERE "@id" = ?1 ORDER BY "project_id""#,
+        )
+        .unwrap()
+        .query_map([wheel], |row| Ok((row.get(0)?, row.get(1)?)))
+        .unwrap()
+        .collect::<Result<_, _>>()
+        .unwrap();
+    assert_eq!(masses, [("bike".to_owned(), 1.5), ("car".to_owned(), 10.0)]);
+    drop(conn);
+
+    db_file.close().unwrap();
+}
+
+#[test]
+fn multi_project_cross_project_targets() {
+    let (db_file, _) = generated_db(&["--multi-project"]);
+    let [bolt, car, wheel, nut] = TINY_IDS;
+
+    let output = import_into_project(
+        &db_file,
+        "library",
+        &format!(
+            r#"[{{"@id": "{bolt}", "@type": "PartUsage", "isLibraryElement": true, "mass": 0.1, "qualifiedName": "Library::Bolt"}}]"#
+        ),
+    );
+    assert!(output.status.success());
+
+    // the car refers to the bolt of the library
+    let output = import_into_project(
+        &db_file,
+        "car",
+        &format!(
+            r#"[
+                {{"@id": "{car}", "@type": "PartUsage", "isLibraryElement": false, "mass": 1000.0, "ownedElement": [{{"@id": "{wheel}"}}, {{"@id": "{bolt}"}}]}},
+                {{"@id": "{wheel}", "@type": "PartUsage", "isLibraryElement": false, "mass": 10.0}}
+            ]"#
+        ),
+    );
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+
+    let conn = rusqlite::Connection::open(db_file.path()).unwrap();
+    let targets: Vec<(String, String)> = conn
+        .prepare(
+            r#"SELECT "target_id", "target_project_id" FROM "relations"
+            WHERE "project_id" = 'car' AND "origin_id" = ?1 ORDER BY "target_id""#,
+        )
+        .unwrap()
+        .query_map([car], |row| Ok((row.get(0)?, row.get(1)?)))
+        .unwrap()
+        .collect::<Result<_, _>>()
+        .unwrap();
+    assert_eq!(
+        targets,
+        [
+            (bolt.to_owned(), "library".to_owned()),
+            (wheel.to_owned(), "car".to_owned())
+        ]
+    );
+    drop(conn);
+
+    // a target in no project fails the import
+    let output = import_into_project(
+        &db_file,
+        "bike",
+        &format!(
+            r#"[{{"@id": "{wheel}", "@type": "PartUsage", "isLibraryElement": false, "mass": 1.0, "ownedElement": [{{"@id": "{nut}"}}]}}]"#
+        ),
+    );
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("FOREIGN KEY constraint failed"));
+
+    db_file.close().unwrap();
+}
+
+#[cfg(feature = "fetch")]
+#[test]
+fn clone_project() {
+    // main is at C3, preceded by C2 and C1, dev is at C1, empty has no commits yet
+    let (base_url, requests) = serve_api(|request| {
+        let [car, wheel, ..] = TINY_IDS;
+        let branch = |id: &str, name: &str, head: &str| {
+            format!(
+                r#"{{"@id": "{id}", "created": "", "name": "{name}", "owningProject": {{"@id": "P"}}, "head": {head}}}"#
+            )
+        };
+        let commit = |id: &str, previous: &str| {
+            format!(r#"{{"@id": "{id}", "created": "", "previousCommit": {previous}}}"#)
+        };
+        let elements = |mass: f64| {
+            format!(
+                r#"[
+                    {{"@id": "{car}", "@type": "PartUsage", "isLibraryElement": false, "mass": {mass}, "ownedElement": [{{"@id": "{wheel}"}}]}},
+                    {{"@id": "{wheel}", "@type": "PartUsage", "isLibraryElement": false, "mass": 10.0, "owner": {{"@id": "{car}"}}}}
+                ]"#
+            )
+        };
+        let body = match request.path.as_str() {
+            "/api/projects/P/branches" => format!(
+                "[{}, {}, {}]",
+                branch("B1", "main", r#"{"@id": "C3"}"#),
+                branch("B2", "dev", r#"{"@id": "C1"}"#),
+                branch("B3", "empty", "null")
+            ),
+            "/api/projects/P/commits/C3" => commit("C3", r#"[{"@id": "C2"}]"#),
+            "/api/projects/P/commits/C2" => commit("C2", r#"[{"@id": "C1"}]"#),
+            "/api/projects/P/commits/C1" => commit("C1", "null"),
+            "/api/projects/P/commits/C1/elements" => elements(1000.0),
+            "/api/projects/P/commits/C2/elements" => elements(1200.0),
+            "/api/projects/P/commits/C3/elements" => elements(1100.0),
+            _ => return (404, String::new(), "{}".to_owned()),
+        };
+        (200, String::new(), body)
+    });
+
+    let (db_file, _) = generated_db(&["--multi-project"]);
+    let clone = |args: &[&str]| {
+        let output = test_bin::get_test_bin(BIN)
+            .arg(db_file.path())
+            .arg("clone")
+            .args(args)
+            .arg(&base_url)
+            .arg("P")
+            .output()
+            .expect("Failed to start {BIN}");
+        assert!(
+            output.status.success(),
+            "{}",
+            String::from_utf8_lossy(&output.stderr)
+        );
+    };
+    let fetched_commits = || -> Vec<String> {
+        let mut paths: Vec<_> = requests
+            .lock()
+            .unwrap()
+            .iter()
+            .filter_map(|r| {
+                r.path
+                    .strip_prefix("/api/projects/P/commits/")?
+                    .strip_suffix("/elements")
+                    .map(str::to_owned)
+            })
+            .collect();
+        paths.sort();
+        requests.lock().unwrap().clear();
+        paths
+    };
+
+    // only the heads of the branches, each stored under its commit id
+    clone(&[]);
+    assert_eq!(fetched_commits(), ["C1", "C3"]);
+    let conn = rusqlite::Connection::open(db_file.path()).unwrap();
+    let [car, wheel, ..] = TINY_IDS;
+    let masses: Vec<(String, f64)> = conn
+        .prepare(r#"SELECT "project_id", "mass" FROM "elements" WHERE "@id" = ?1 ORDER BY 1"#)
+        .unwrap()
+        .query_map([car], |row| Ok((row.get(0)?, row.get(1)?)))
+        .unwrap()
+        .collect::<Result<_, _>>()
+        .unwrap();
+    assert_eq!(
+        masses,
+        [("C1".to_owned(), 1000.0), ("C3".to_owned(), 1100.0)]
+    );
+    let relations: Vec<(String, String, String)> = conn
+        .prepare(
+            r#"SELECT "project_id", "target_id", "target_project_id" FROM "relations"
+            WHERE "name" = 'ownedElement' ORDER BY 1"#,
+        )
+        .unwrap()
+        .query_map([], |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)))
+        .unwrap()
+        .collect::<Result<_, _>>()
+        .unwrap();
+    assert_eq!(
+        relations,
+        [
+            ("C1".to_owned(), wheel.to_owned(), "C1".to_owned()),
+            ("C3".to_owned(), wheel.to_owned(), "C3".to_owned())
+        ]
+    );
+    let branches: Vec<(String, String, String, String)> = conn
+        .prepare(r#"SELECT * FROM "branches" ORDER BY "branch_id""#)
+        .unwrap()
+        .query_map([], |row| {
+            Ok((row.get(0)?, row.get(1)?, row.get(2)?, row.get(3)?))
+        })
+        .unwrap()
+        .collect::<Result<_, _>>()
+        .unwrap();
+    assert_eq!(
+        branches,
+        [
+            ("P".into(), "B1".into(), "main".into(), "C3".into()),
+            ("P".into(), "B2".into(), "dev".into(), "C1".into())
+        ]
+    );
+    let imports: Vec<(String, String)> = conn
+        .prepare(r#"SELECT "project_id", "commit_id" FROM "import_log" ORDER BY "commit_id""#)
+        .unwrap()
+        .query_map([], |row| Ok((row.get(0)?, row.get(1)?)))
+        .unwrap()
+        .collect::<Result<_, _>>()
+        .unwrap();
+    assert_eq!(
+        imports,
+        [
+            ("P".to_owned(), "C1".to_owned()),
+            ("P".to_owned(), "C3".to_owned())
+        ]
+    );
+    drop(conn);
+
+    // the complete history, fetching only the commit not stored yet
+    clone(&["--all-commits"]);
+    assert_eq!(fetched_commits(), ["C2"]);
+    let output = test_bin::get_test_bin(BIN)
+        .arg("--project")
+        .arg("C2")
+        .arg(db_file.path())
+        .arg("show")
+        .arg(car)
+        .output()
+        .expect("Failed to start {BIN}");
+    assert!(output.status.success());
+    assert!(String::from_utf8_lossy(&output.stdout).contains("1200"));
+
+    // unknown branches are refused
+    let output = test_bin::get_test_bin(BIN)
+        .arg(db_file.path())
+        .arg("clone")
+        .arg("--branch")
+        .arg("feature")
+        .arg(&base_url)
+        .arg("P")
+        .output()
+        .expect("Failed to start {BIN}");
+    assert!(!output.status.success());
+    assert!(
+        String::from_utf8_lossy(&output.stderr)
+            .contains(r#"the project "P" has no branch named "feature""#)
+    );
+
+    db_file.close().unwrap();
+}
+
+/// Imports a car owning a wheel into a db generated by [`generated_db`], maintaining the owner column
+fn populated_tiny_db() -> (tempfile::NamedTempFile, std::path::PathBuf) {
+    let (db_file, _) = generated_db(&[]);
+    let [car, wheel, ..] = TINY_IDS;
+    let dump_path = db_file.path().with_extension("dump.json");
+    std::fs::write(
+        &dump_path,
+        format!(
+            r#"[{{"@id": "{car}", "@type": "PartUsage", "isLibraryElement": false, "mass": 1000.0, "declaredName": "Car", "ownedElement": [{{"@id": "{wheel}"}}]}},
+                {{"@id": "{wheel}", "@type": "PartUsage", "isLibraryElement": false, "mass": 10.0, "declaredName": "Wheel", "owner": {{"@id": "{car}"}}}}]"#
+        ),
+    )
+    .unwrap();
+
+    let output = test_bin::get_test_bin(BIN)
+        .arg(db_file.path())
+        .arg("import-json")
+        .arg("--owner-column")
+        .arg(&dump_path)
+        .output()
+        .expect("Failed to start {BIN}");
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    (db_file, dump_path)
+}
+
+/// [`TINY_JSON_SCHEMA`] with a new optional `isVariation` of part usages, and a lower bound of
+/// `mass` which requires to rebuild the elements table
+fn migrated_tiny_json_schema() -> String {
+    TINY_JSON_SCHEMA
+        .replace(r#""minimum": 0"#, r#""minimum": -1"#)
+        .replace(
+            r#""aliasIds": {"type": "array", "items": {"type": "string"}}"#,
+            r#""aliasIds": {"type": "array", "items": {"type": "string"}},
+                "isVariation": {"oneOf": [{"type": "boolean"}, {"type": "null"}]}"#,
+        )
+}
+
+/// Runs `migrate-schema` with the options `args` to the JSON schema `schema` on the db
+fn migrate_tiny_db(
+    db_file: &tempfile::NamedTempFile,
+    schema: &str,
+    args: &[&std::ffi::OsStr],
+) -> std::process::Output {
+    let schema_path = db_file.path().with_extension("migrated.json");
+    std::fs::write(&schema_path, schema).unwrap();
+    let output = test_bin::get_test_bin(BIN)
+        .arg(db_file.path())
+        .arg("migrate-schema")
+        .args(args)
+        .arg(&schema_path)
+        .output()
+        .expect("Failed to start {BIN}");
+    std::fs::remove_file(&schema_path).unwrap();
+    output
+}
+
+/// Asserts that the db of [`populated_tiny_db`] was migrated to [`migrated_tiny_json_schema`]
+/// without losing data, and that it is still accepted by the import
+fn assert_tiny_db_migrated(db_file: &tempfile::NamedTempFile, dump_path: &std::path::Path) {
+    let [car, wheel, ..] = TINY_IDS;
+    let conn = rusqlite::Connection::open(db_file.path()).unwrap();
+    let elements: Vec<(String, f64, Option<bool>, Option<String>)> = conn
+        .prepare(
+            r#"SELECT "declaredName", "mass", "isVariation", "owner_id" FROM "elements" ORDER BY "@id""#,
+        )
+        .unwrap()
+        .query_map([], |row| {
+            Ok((row.get(0)?, row.get(1)?, row.get(2)?, row.get(3)?))
+        })
+        .unwrap()
+        .collect::<Result<_, _>>()
+        .unwrap();
+    assert_eq!(
+        elements,
+        [
+            ("Car".to_owned(), 1000.0, None, None),
+            ("Wheel".to_owned(), 10.0, None, Some(car.to_owned()))
+        ]
+    );
+    let relations: i64 = conn
+        .query_row(r#"SELECT COUNT(*) FROM "relations""#, [], |row| row.get(0))
+        .unwrap();
+    assert_eq!(relations, 2);
+
+    // the index of the owner column and the import log are kept
+    let kept: i64 = conn
+        .query_row(
+            r#"SELECT COUNT(*) FROM "sqlite_master" WHERE "name" IN ('elements.owner_id', 'import_log')"#,
+            [],
+            |row| row.get(0),
+        )
+        .unwrap();
+    assert_eq!(kept, 2);
+
+    // the migrated table accepts what the old one refused
+    conn.execute(
+        r#"UPDATE "elements" SET "mass" = -0.5 WHERE "@id" = ?1"#,
+        [wheel],
+    )
+    .unwrap();
+    let version: u32 = conn
+        .query_row("PRAGMA user_version", [], |row| row.get(0))
+        .unwrap();
+    assert!(version > 0);
+    drop(conn);
+
+    // the schema fingerprint matches the migrated db
+    let output = test_bin::get_test_bin(BIN)
+        .arg(db_file.path())
+        .arg("import-json")
+        .arg(dump_path)
+        .output()
+        .expect("Failed to start {BIN}");
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+}
+
+#[test]
+fn migrate_schema_in_place() {
+    let (db_file, dump_path) = populated_tiny_db();
+    let output = migrate_tiny_db(
+        &db_file,
+        &migrated_tiny_json_schema(),
+        &["--apply".as_ref()],
+    );
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    assert_tiny_db_migrated(&db_file, &dump_path);
+
+    std::fs::remove_file(&dump_path).unwrap();
+    db_file.close().unwrap();
+}
+
+#[test]
+fn migrate_schema_script() {
+    let (db_file, dump_path) = populated_tiny_db();
+    let script_path = db_file.path().with_extension("migration.sql");
+    let output = migrate_tiny_db(
+        &db_file,
+        &migrated_tiny_json_schema(),
+        &["--dump-sql".as_ref(), script_path.as_os_str()],
+    );
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+
+    let conn = rusqlite::Connection::open(db_file.path()).unwrap();
+    let script = std::fs::read_to_string(&script_path).unwrap();
+    conn.execute_batch(&script).unwrap();
+    drop(conn);
+    assert_tiny_db_migrated(&db_file, &dump_path);
+
+    std::fs::remove_file(&script_path).unwrap();
+    std::fs::remove_file(&dump_path).unwrap();
+    db_file.close().unwrap();
+}
+
+#[test]
+fn migrate_schema_new_not_null_column() {
+    let (db_file, dump_path) = populated_tiny_db();
+
+    // required by all types, hence NOT NULL
+    let schema = TINY_JSON_SCHEMA
+        .replace(
+            r#""isLibraryElement": {"type": "boolean"},"#,
+            r#""isLibraryElement": {"type": "boolean"}, "isVariation": {"type": "boolean"},"#,
+        )
+        .replace(
+            r#""@type", "isLibraryElement""#,
+            r#""@type", "isLibraryElement", "isVariation""#,
+        );
+    let output = migrate_tiny_db(&db_file, &schema, &["--apply".as_ref()]);
+    assert!(!output.status.success());
+    assert!(
+        String::from_utf8_lossy(&output.stderr).contains(
+            r#"the new schema requires a value for the column "isVariation" of the "elements" table, which 2 rows lack"#
+        ),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+
+    // the db is unchanged
+    let conn = rusqlite::Connection::open(db_file.path()).unwrap();
+    let elements: i64 = conn
+        .query_row(r#"SELECT COUNT(*) FROM "elements""#, [], |row| row.get(0))
+        .unwrap();
+    assert_eq!(elements, 2);
+    drop(conn);
+
+    std::fs::remove_file(&dump_path).unwrap();
+    db_file.close().unwrap();
+}
+
+#[test]
+fn schema_diff() {
+    let db_file = tempfile::NamedTempFile::new().unwrap();
+    let old_path = db_file.path().with_extension("old.json");
+    let new_path = db_file.path().with_extension("new.json");
+    std::fs::write(&old_path, TINY_JSON_SCHEMA).unwrap();
+    std::fs::write(
+        &new_path,
+        migrated_tiny_json_schema().replace(
+            r#""required": ["@id", "@type", "isLibraryElement", "body"]"#,
+            r#""required": ["@id", "@type", "isLibraryElement"]"#,
+        ),
+    )
+    .unwrap();
+
+    let output = test_bin::get_test_bin(BIN)
+        .arg(db_file.path())
+        .arg("schema-diff")
+        .arg(&old_path)
+        .arg(&new_path)
+        .output()
+        .expect("Failed to start {BIN}");
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let (definitions, columns) = stdout.split_once("# columns").unwrap();
+    assert!(definitions.contains("~ Documentation\n    ~ body: no longer required\n"));
+    assert!(
+        definitions
+            .contains(r#"    + isVariation: {"oneOf":[{"type":"boolean"},{"type":"null"}]}"#)
+    );
+    assert!(definitions.contains(
+        r#"    ~ mass: {"type":"number","minimum":0} -> {"type":"number","minimum":-1}"#
+    ));
+    assert!(!definitions.contains("RequirementUsage"));
+    assert!(columns.contains(r#"+ elements: "isVariation" INTEGER"#));
+    assert!(columns.contains(
+        "~ elements.mass:\n    - \"mass\" REAL CHECK(\"mass\" >= 0)\n    + \"mass\" REAL CHECK(\"mass\" >= -1)"
+    ));
+
+    // identical schemata have no differences
+    let output = test_bin::get_test_bin(BIN)
+        .arg(db_file.path())
+        .arg("schema-diff")
+        .arg(&old_path)
+        .arg(&old_path)
+        .output()
+        .expect("Failed to start {BIN}");
+    assert!(output.status.success());
+    assert_eq!(
+        String::from_utf8_lossy(&output.stdout),
+        "# definitions\n\n# columns\n"
+    );
+
+    std::fs::remove_file(&old_path).unwrap();
+    std::fs::remove_file(&new_path).unwrap();
+    db_file.close().unwrap();
+}
+
+#[test]
+fn regen_embedded_schema() {
+    let db_file = tempfile::NamedTempFile::new().unwrap();
+    let schema_path = db_file.path().with_extension("schema.json");
+    let asset_path = db_file.path().with_extension("sql");
+    std::fs::write(&schema_path, TINY_JSON_SCHEMA).unwrap();
+
+    // the embedded schema was not generated from the tiny schema
+    let output = test_bin::get_test_bin(BIN)
+        .arg(db_file.path())
+        .arg("regen-embedded-schema")
+        .arg(&schema_path)
+        .output()
+        .expect("Failed to start {BIN}");
+    assert!(!output.status.success());
+    assert!(
+        String::from_utf8_lossy(&output.stderr)
+            .contains("the embedded schema differs from the generated one"),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.lines().any(|l| l == "~ table elements"), "{stdout}");
+    assert!(stdout.lines().any(|l| l == "+ view PartUsage"));
+    assert!(!stdout.lines().any(|l| l.ends_with(" table relations")));
+
+    // the regenerated asset is the generated schema
+    let output = test_bin::get_test_bin(BIN)
+        .arg(db_file.path())
+        .arg("regen-embedded-schema")
+        .arg("--write")
+        .arg(&asset_path)
+        .arg(&schema_path)
+        .output()
+        .expect("Failed to start {BIN}");
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    let (generated_db_file, generated_sql) = generated_db(&[]);
+    assert_eq!(std::fs::read_to_string(&asset_path).unwrap(), generated_sql);
+    generated_db_file.close().unwrap();
+
+    std::fs::remove_file(&schema_path).unwrap();
+    std::fs::remove_file(&asset_path).unwrap();
+    db_file.close().unwrap();
+}
+
+/// Turns a db into one of schema version 0, which lacks the full-text index and restricts the
+/// relation names by a `CHECK` constraint instead of the table of names
+fn downgrade_to_v0(db_file: &tempfile::NamedTempFile) {
+    let conn = rusqlite::Connection::open(db_file.path()).unwrap();
+    conn.pragma_update(None, "foreign_keys", "OFF").unwrap();
+
+    let names: Vec<String> = conn
+        .prepare(r#"SELECT "name" FROM "relation_names" ORDER BY "name""#)
+        .unwrap()
+        .query_map([], |row| row.get(0))
+        .unwrap()
+        .collect::<Result<_, _>>()
+        .unwrap();
+    let relations_sql: String = conn
+        .query_row(
+            r#"SELECT "sql" FROM "sqlite_schema" WHERE "name" = 'relations'"#,
+            [],
+            |row| row.get(0),
+        )
+        .unwrap();
+    let indexes: Vec<String> = conn
+        .prepare(
+            r#"SELECT "sql" FROM "sqlite_schema" WHERE "type" = 'index' AND "tbl_name" = 'relations' AND "sql" IS NOT NULL"#,
+        )
+        .unwrap()
+        .query_map([], |row| row.get(0))
+        .unwrap()
+        .collect::<Result<_, _>>()
+        .unwrap();
+
+    // drop the foreign key along with the whitespace up to the next constraint
+    let foreign_key_start = relations_sql
+        .find(r#"FOREIGN KEY ("name")"#)
+        .or_else(|| relations_sql.find(r#"FOREIGN KEY("name")"#))
+        .unwrap();
+    let foreign_key_end =
+        foreign_key_start + relations_sql[foreign_key_start..].find(',').unwrap() + 1;
+    let next_start = foreign_key_end + relations_sql[foreign_key_end..].len()
+        - relations_sql[foreign_key_end..].trim_start().len();
+    let check = format!(
+        r#""name" TEXT NOT NULL CHECK("name" IN ({}))"#,
+        names
+            .iter()
+            .map(|n| format!("'{n}'"))
+            .collect::<Vec<_>>()
+            .join(", ")
+    );
+    let v0_sql = format!(
+        "{}{}",
+        &relations_sql[..foreign_key_start],
+        &relations_sql[next_start..]
+    )
+    .replacen(r#""name" TEXT NOT NULL"#, &check, 1)
+    .replacen(
+        r#"CREATE TABLE "relations""#,
+        r#"CREATE TABLE "relations.v0""#,
+        1,
+    );
+
+    conn.execute_batch(&format!(
+        r#"PRAGMA legacy_alter_table = ON;
+        BEGIN;
+        DROP TRIGGER IF EXISTS "elements_fts.insert";
+        DROP TRIGGER IF EXISTS "elements_fts.delete";
+        DROP TRIGGER IF EXISTS "elements_fts.update";
+        DROP TABLE IF EXISTS "elements_fts";
+        {v0_sql};
+        INSERT INTO "relations.v0" SELECT * FROM "relations";
+        DROP TABLE "relations";
+        ALTER TABLE "relations.v0" RENAME TO "relations";
+        {};
+        DROP TABLE "relation_names";
+        PRAGMA user_version = 0;
+        COMMIT;"#,
+        indexes.join(";\n")
+    ))
+    .unwrap();
+}
+
+/// Upgrades the db, and asserts that it is at the current schema version and kept its relations
+fn assert_upgraded(db_file: &tempfile::NamedTempFile, relations: i64) {
+    let output = test_bin::get_test_bin(BIN)
+        .arg(db_file.path())
+        .arg("upgrade-db")
+        .output()
+        .expect("Failed to start {BIN}");
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+
+    let conn = rusqlite::Connection::open(db_file.path()).unwrap();
+    let version: u32 = conn
+        .query_row("PRAGMA user_version", [], |row| row.get(0))
+        .unwrap();
+    assert_eq!(version, 2);
+    let upgraded_relations: i64 = conn
+        .query_row(r#"SELECT COUNT(*) FROM "relations""#, [], |row| row.get(0))
+        .unwrap();
+    assert_eq!(upgraded_relations, relations);
+    let unknown_names: i64 = conn
+        .query_row(
+            r#"SELECT COUNT(*) FROM "relations" WHERE "name" NOT IN (SELECT "name" FROM "relation_names")"#,
+            [],
+            |row| row.get(0),
+        )
+        .unwrap();
+    assert_eq!(unknown_names, 0);
+}
+
+#[test]
+fn upgrade_db() {
+    let db_file = imported_db();
+    let conn = rusqlite::Connection::open(db_file.path()).unwrap();
+    let relations: i64 = conn
+        .query_row(r#"SELECT COUNT(*) FROM "relations""#, [], |row| row.get(0))
+        .unwrap();
+    let relations_sql: String = conn
+        .query_row(
+            r#"SELECT "sql" FROM "sqlite_schema" WHERE "name" = 'relations'"#,
+            [],
+            |row| row.get(0),
+        )
+        .unwrap();
+    drop(conn);
+
+    downgrade_to_v0(&db_file);
+    let output = test_bin::get_test_bin(BIN)
+        .arg(db_file.path())
+        .arg("import-json")
+        .arg("tests/example-dump.json")
+        .output()
+        .expect("Failed to start {BIN}");
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("run upgrade-db first"));
+
+    assert_upgraded(&db_file, relations);
+
+    // the relations table is back to the layout of the embedded schema
+    let conn = rusqlite::Connection::open(db_file.path()).unwrap();
+    let upgraded_sql: String = conn
+        .query_row(
+            r#"SELECT "sql" FROM "sqlite_schema" WHERE "name" = 'relations'"#,
+            [],
+            |row| row.get(0),
+        )
+        .unwrap();
+    assert_eq!(upgraded_sql, relations_sql);
+    let origin: String = conn
+        .query_row(
+            r#"SELECT "value" FROM "schema_info" WHERE "key" = 'origin'"#,
+            [],
+            |row| row.get(0),
+        )
+        .unwrap();
+    assert_eq!(origin, "embedded");
+    drop(conn);
+
+    // the full-text index covers the elements imported before the upgrade
+    let output = test_bin::get_test_bin(BIN)
+        .arg(db_file.path())
+        .arg("search")
+        .arg("--fts")
+        .arg("wing")
+        .output()
+        .expect("Failed to start {BIN}");
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    assert!(!output.stdout.is_empty());
+
+    let output = test_bin::get_test_bin(BIN)
+        .arg(db_file.path())
+        .arg("import-json")
+        .arg("tests/example-dump.json")
+        .output()
+        .expect("Failed to start {BIN}");
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+
+    db_file.close().unwrap();
+}
+
+#[test]
+fn upgrade_db_without_rowid() {
+    let (db_file, dump_path) = {
+        let (db_file, _) = generated_db(&["--without-rowid", "--no-strict"]);
+        let [car, wheel, ..] = TINY_IDS;
+        let dump_path = db_file.path().with_extension("dump.json");
+        std::fs::write(
+            &dump_path,
+            format!(
+                r#"[{{"@id": "{car}", "@type": "PartUsage", "isLibraryElement": false, "mass": 1000.0, "ownedElement": [{{"@id": "{wheel}"}}]}},
+                    {{"@id": "{wheel}", "@type": "PartUsage", "isLibraryElement": false, "mass": 10.0, "owner": {{"@id": "{car}"}}}}]"#
+            ),
+        )
+        .unwrap();
+        (db_file, dump_path)
+    };
+    let output = test_bin::get_test_bin(BIN)
+        .arg(db_file.path())
+        .arg("import-json")
+        .arg(&dump_path)
+        .output()
+        .expect("Failed to start {BIN}");
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+
+    downgrade_to_v0(&db_file);
+    assert_upgraded(&db_file, 2);
+
+    // the layout of the generated db is kept, without a full-text index
+    let conn = rusqlite::Connection::open(db_file.path()).unwrap();
+    let tables: Vec<(String, String)> = conn
+        .prepare(
+            r#"SELECT "name", "sql" FROM "sqlite_schema" WHERE "name" IN ('elements', 'relations', 'relation_names', 'elements_fts') ORDER BY "name""#,
+        )
+        .unwrap()
+        .query_map([], |row| Ok((row.get(0)?, row.get(1)?)))
+        .unwrap()
+        .collect::<Result<_, _>>()
+        .unwrap();
+    let names: Vec<_> = tables.iter().map(|(name, _)| name.as_str()).collect();
+    assert_eq!(names, ["elements", "relation_names", "relations"]);
+    assert!(tables[0].1.ends_with(") WITHOUT ROWID"));
+    assert!(tables[1].1.ends_with(") WITHOUT ROWID"));
+    assert!(tables[2].1.ends_with(')'));
+    assert!(!tables[2].1.contains("STRICT"));
+    assert!(
+        tables[2]
+            .1
+            .contains(r#"FOREIGN KEY("name") REFERENCES "relation_names"("name")"#)
+    );
+    drop(conn);
+
+    let output = test_bin::get_test_bin(BIN)
+        .arg(db_file.path())
+        .arg("import-json")
+        .arg(&dump_path)
+        .output()
+        .expect("Failed to start {BIN}");
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+
+    std::fs::remove_file(&dump_path).unwrap();
+    db_file.close().unwrap();
+}